@@ -0,0 +1,17 @@
+// Single frame payloads, as carried by frame records and Symphonia packets: decoding must never
+// panic, whether or not the frame passes validation
+#![no_main]
+use gapless_lossy_codec::codec::{Decoder, EncodedFrame, OverlapState};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]|
+{
+    let Some((&channels, payload)) = data.split_first() else { return };
+    let channels = (channels % 8 + 1) as usize;
+    if let Ok(frame) = bincode::deserialize::<EncodedFrame>(payload)
+    {
+        let _ = frame.validate(channels, 1024);
+        let mut state = OverlapState::new(channels);
+        let _ = Decoder::new(channels, 44100).decode_frame(&frame, &mut state);
+    }
+});
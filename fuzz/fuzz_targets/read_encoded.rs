@@ -0,0 +1,14 @@
+// Whole streams as `load_encoded` reads them: any input must either fail to parse or decode
+// without panicking
+#![no_main]
+use gapless_lossy_codec::codec::{encoded_from_bytes, Decoder};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]|
+{
+    if let Ok(encoded) = encoded_from_bytes(data)
+    {
+        let mut decoder = Decoder::new(encoded.header.channels as usize, encoded.header.sample_rate);
+        let _ = decoder.decode(&encoded, None);
+    }
+});
@@ -0,0 +1,9 @@
+// Damaged files as `glc repair` salvages them: scanning for intact frames must never panic
+#![no_main]
+use gapless_lossy_codec::container::repair;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]|
+{
+    let _ = repair(data, Vec::new());
+});
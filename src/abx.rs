@@ -0,0 +1,160 @@
+//! ABX listening tests, the final word on whether an encode sounds like its original
+//!
+//! An [`AbxTest`] deals out trials, each a random excerpt of the track where X is secretly
+//! either A (the original) or B (the encode), and keeps score of the listener's calls. Its
+//! [`p_value`](AbxTest::p_value) is the chance of scoring at least as well by guessing, so a
+//! low one means the listener really could tell the two apart.
+use std::ops::Range;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::playlist::next_random;
+
+/// Length of the fade in and out of each excerpt, so they don't start or stop with a click
+pub const ABX_FADE_SECONDS: f64 = 0.01;
+
+/// One trial: where its excerpt starts, and what X really is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Trial
+{
+    /// First frame of the excerpt
+    pub start: u64,
+    /// Whether X is A (the original) rather than B (the encode)
+    pub x_is_a: bool,
+}
+
+/// A run of ABX trials over a track and how the listener has done so far
+#[derive(Clone, Debug)]
+pub struct AbxTest
+{
+    frames: u64,
+    /// Length of each excerpt in frames, no longer than the track
+    segment: u64,
+    trials: usize,
+    /// Whether each answered trial was called correctly, in order
+    answers: Vec<bool>,
+    current: Trial,
+    seed: u64,
+}
+
+impl AbxTest
+{
+    /// `trials` trials over a track of `frames` frames, each playing `segment` frames of it,
+    /// seeded from the clock
+    pub fn new(frames: u64, segment: u64, trials: usize) -> Self
+    {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        Self::with_seed(frames, segment, trials, nanos)
+    }
+
+    /// As [`new`](Self::new), drawing trials from `seed`, so the same seed deals the same trials
+    pub fn with_seed(frames: u64, segment: u64, trials: usize, seed: u64) -> Self
+    {
+        let mut test = Self
+        {
+            frames,
+            segment: segment.min(frames),
+            trials,
+            answers: Vec::new(),
+            current: Trial { start: 0, x_is_a: true },
+            seed,
+        };
+        test.current = test.deal();
+        test
+    }
+
+    /// The trial being played, or None once all of them are answered
+    pub fn trial(&self) -> Option<Trial>
+    {
+        (!self.finished()).then_some(self.current)
+    }
+
+    /// Frames of the current trial's excerpt
+    pub fn segment(&self) -> Range<u64>
+    {
+        self.current.start..self.current.start + self.segment
+    }
+
+    /// Record the listener's call on the current trial and move on to the next; returns whether
+    /// it was right, or None if the test is already over
+    pub fn answer(&mut self, x_is_a: bool) -> Option<bool>
+    {
+        if self.finished()
+        {
+            return None;
+        }
+        let correct = x_is_a == self.current.x_is_a;
+        self.answers.push(correct);
+        self.current = self.deal();
+        Some(correct)
+    }
+
+    pub fn trials(&self) -> usize
+    {
+        self.trials
+    }
+
+    pub fn answered(&self) -> usize
+    {
+        self.answers.len()
+    }
+
+    pub fn correct(&self) -> usize
+    {
+        self.answers.iter().filter(|&&correct| correct).count()
+    }
+
+    pub fn finished(&self) -> bool
+    {
+        self.answers.len() >= self.trials
+    }
+
+    /// Chance of getting at least this many of the answered trials right by guessing
+    pub fn p_value(&self) -> f64
+    {
+        guessing_probability(self.correct(), self.answered())
+    }
+
+    /// A fresh trial: a random excerpt, with X a coin flip between A and B
+    fn deal(&mut self) -> Trial
+    {
+        let starts = self.frames - self.segment + 1;
+        let start = next_random(&mut self.seed) % starts;
+        Trial { start, x_is_a: next_random(&mut self.seed) & 1 == 0 }
+    }
+}
+
+/// Chance of getting at least `correct` of `trials` coin flips right: the one-sided binomial tail
+pub fn guessing_probability(correct: usize, trials: usize) -> f64
+{
+    // Each term is C(trials, k) / 2^trials, built up from the previous one to stay in range
+    let mut term = 0.5f64.powi(trials as i32);
+    let mut tail = 0.0;
+    for k in 0..=trials
+    {
+        if k >= correct
+        {
+            tail += term;
+        }
+        term *= (trials - k) as f64 / (k + 1) as f64;
+    }
+    tail.min(1.0)
+}
+
+/// `frames` frames of interleaved `samples` from frame `start`, faded in and out over
+/// [`ABX_FADE_SECONDS`]; frames past the end of `samples` are left out
+pub fn excerpt(samples: &[f32], channels: u16, sample_rate: u32, start: u64, frames: u64) -> Vec<f32>
+{
+    let channels = channels.max(1) as usize;
+    let total = (samples.len() / channels) as u64;
+    let (start, end) = (start.min(total), start.saturating_add(frames).min(total));
+    let frames = (end - start) as usize;
+    let fade = ((ABX_FADE_SECONDS * sample_rate as f64) as usize).clamp(1, frames.max(2) / 2);
+    samples[start as usize * channels..end as usize * channels]
+        .chunks_exact(channels)
+        .enumerate()
+        .flat_map(|(i, frame)|
+        {
+            let gain = (i.min(frames - 1 - i) as f32 / fade as f32).min(1.0);
+            frame.iter().map(move |sample| sample * gain)
+        })
+        .collect()
+}
@@ -0,0 +1,433 @@
+//! ALAC (Apple Lossless) export in an MP4 (`.m4a`) container, for Apple devices and software
+//!
+//! Frames use ALAC's fixed first-order predictor (predictor order 31) and its adaptive Golomb-Rice
+//! coder, with stereo coded as mid/side. A frame that would not shrink is stored verbatim instead.
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use crate::audio::convert_f32_to_i16;
+
+/// Samples per channel in each frame (the ALAC default)
+const FRAME_LENGTH: usize = 4096;
+
+/// Output bit depth, same as the WAV and FLAC exporters
+const BIT_DEPTH: u32 = 16;
+
+/// Adaptive Golomb-Rice parameters of the reference encoder, also stored in the magic cookie
+const HISTORY_MULT: u32 = 40;
+const INITIAL_HISTORY: u32 = 10;
+const RICE_LIMIT: u32 = 14;
+const MAX_RUN: u16 = 255;
+
+/// Predictor order that decoders treat as plain first-order prediction
+const FIRST_ORDER: u32 = 31;
+
+/// Coefficient shift written in the predictor header (unused by first-order prediction)
+const DEN_SHIFT: u32 = 9;
+
+/// Scales the history multiplier, in quarters
+const PB_FACTOR: u32 = 4;
+
+/// Element tags: single channel, channel pair, end of frame
+const ID_SCE: u32 = 0;
+const ID_CPE: u32 = 1;
+const ID_END: u32 = 7;
+
+/// Encode interleaved `samples` to an `.m4a` file with ALAC audio on `writer`
+/// Only mono and stereo are supported
+pub fn encode_alac<W: Write>(mut writer: W, samples: &[f32], sample_rate: u32, channels: u16) -> Result<W>
+{
+    if !(1..=2).contains(&channels)
+    {
+        return Err(anyhow!("ALAC export supports mono and stereo, not {} channels", channels));
+    }
+    let ch = channels as usize;
+    let samples = convert_f32_to_i16(samples);
+    let length = samples.len() / ch;
+
+    let frames: Vec<Vec<u8>> = samples[..length * ch].chunks(FRAME_LENGTH * ch)
+                                                     .map(|frame| encode_frame(frame, ch))
+                                                     .collect();
+    let data_len: usize = frames.iter().map(Vec::len).sum();
+
+    let ftyp = mp4_box(b"ftyp", &[b"M4A ".as_slice(), &0u32.to_be_bytes(), b"M4A ", b"mp42", b"isom"].concat());
+    writer.write_all(&ftyp)?;
+
+    // Use a 64-bit box size if the audio doesn't fit in 4 GB
+    let data_offset = match u32::try_from(8 + data_len)
+    {
+        Ok(size) =>
+        {
+            writer.write_all(&size.to_be_bytes())?;
+            writer.write_all(b"mdat")?;
+            ftyp.len() + 8
+        }
+        Err(_) =>
+        {
+            writer.write_all(&1u32.to_be_bytes())?;
+            writer.write_all(b"mdat")?;
+            writer.write_all(&(16 + data_len as u64).to_be_bytes())?;
+            ftyp.len() + 16
+        }
+    };
+    for frame in &frames
+    {
+        writer.write_all(frame)?;
+    }
+
+    let moov = movie_box(&frames, length, sample_rate, channels, data_offset as u32)?;
+    writer.write_all(&moov)?;
+    Ok(writer)
+}
+
+/// Export interleaved `samples` to an `.m4a` file (see [`encode_alac`])
+pub fn export_to_alac(path: &Path, samples: &[f32], sample_rate: u32, channels: u16) -> Result<()>
+{
+    let writer = BufWriter::new(File::create(path)?);
+    encode_alac(writer, samples, sample_rate, channels)?.flush()?;
+    Ok(())
+}
+
+/// Encode one frame of interleaved samples, compressed unless storing it verbatim is smaller
+fn encode_frame(frame: &[i16], channels: usize) -> Vec<u8>
+{
+    let count = frame.len() / channels;
+    let partial = count < FRAME_LENGTH;
+
+    let mut writer = BitWriter::new();
+    write_element_header(&mut writer, channels, count, false);
+
+    // Mid/side for stereo; decoders undo it with a shift of 1 and a left weight of 1
+    let mut planes: Vec<Vec<i32>> = (0..channels).map(|c| frame.iter().skip(c).step_by(channels).map(|&s| s as i32).collect())
+                                                 .collect();
+    let mix = if channels == 2
+    {
+        let (first, second) = planes.split_at_mut(1);
+        for (mid, side) in first[0].iter_mut().zip(second[0].iter_mut())
+        {
+            let (left, right) = (*mid, *side);
+            *mid = (left + right) >> 1;
+            *side = left - right;
+        }
+        1
+    }
+    else
+    {
+        0
+    };
+    writer.write(8, mix);
+    writer.write(8, mix);
+
+    // Stereo side samples need one more bit
+    let sample_size = BIT_DEPTH + channels as u32 - 1;
+    for _ in 0..channels
+    {
+        writer.write(4, 0);
+        writer.write(4, DEN_SHIFT);
+        writer.write(3, PB_FACTOR);
+        writer.write(5, FIRST_ORDER);
+        for _ in 0..FIRST_ORDER
+        {
+            writer.write(16, 0);
+        }
+    }
+    for plane in &planes
+    {
+        encode_residuals(&mut writer, &predict(plane, sample_size), sample_size);
+    }
+    writer.write(3, ID_END);
+
+    let verbatim_bits = 23 + if partial { 32 } else { 0 } + count * channels * BIT_DEPTH as usize + 3;
+    if writer.bit_len() < verbatim_bits
+    {
+        return writer.finish();
+    }
+
+    let mut writer = BitWriter::new();
+    write_element_header(&mut writer, channels, count, true);
+    for &sample in frame
+    {
+        writer.write(BIT_DEPTH, sample as u16 as u32);
+    }
+    writer.write(3, ID_END);
+    writer.finish()
+}
+
+/// Element tag and instance, then the partial frame and verbatim flags (no shifted-out low bytes at 16 bits)
+fn write_element_header(writer: &mut BitWriter, channels: usize, count: usize, verbatim: bool)
+{
+    let partial = count < FRAME_LENGTH;
+    writer.write(3, if channels == 2 { ID_CPE } else { ID_SCE });
+    writer.write(4, 0);
+    writer.write(12, 0);
+    writer.write(1, partial as u32);
+    writer.write(2, 0);
+    writer.write(1, verbatim as u32);
+    if partial
+    {
+        writer.write(32, count as u32);
+    }
+}
+
+/// First-order prediction residuals, wrapped to `sample_size` bits like the decoder's sums
+fn predict(samples: &[i32], sample_size: u32) -> Vec<i32>
+{
+    let shift = 32 - sample_size;
+    let mut previous = 0;
+    samples.iter()
+           .map(|&sample|
+           {
+               let residual = (sample.wrapping_sub(previous) << shift) >> shift;
+               previous = sample;
+               residual
+           })
+           .collect()
+}
+
+/// Adaptive Golomb-Rice coding of one channel's residuals, with run-length coding of zeros
+/// whenever the running average gets small
+fn encode_residuals(writer: &mut BitWriter, residuals: &[i32], sample_size: u32)
+{
+    let multiplier = HISTORY_MULT * PB_FACTOR / 4;
+    let mut history = INITIAL_HISTORY;
+    let mut sign_modifier = 0;
+    let mut i = 0;
+
+    while i < residuals.len()
+    {
+        let k = log2((history >> 9) + 3);
+        let residual = residuals[i];
+        let value = if residual < 0 { (-2 * residual - 1) as u32 } else { 2 * residual as u32 };
+        i += 1;
+
+        encode_scalar(writer, value - sign_modifier, k, sample_size);
+
+        history = history + value * multiplier - ((history * multiplier) >> 9);
+        sign_modifier = 0;
+        if value > 0xFFFF
+        {
+            history = 0xFFFF;
+        }
+
+        if history < 128 && i < residuals.len()
+        {
+            let k = 7 - log2(history) + ((history + 16) >> 6);
+            let run = residuals[i..].iter().take_while(|&&r| r == 0).count();
+            i += run;
+
+            encode_scalar(writer, run as u32, k, 16);
+            sign_modifier = (run <= 0xFFFF) as u32;
+            history = 0;
+        }
+    }
+}
+
+/// Golomb code with divisor `2^k - 1`, or an escape followed by the raw value when the quotient is large
+fn encode_scalar(writer: &mut BitWriter, value: u32, k: u32, escape_bits: u32)
+{
+    let k = k.min(RICE_LIMIT);
+    let divisor = (1 << k) - 1;
+    let quotient = value / divisor;
+    let remainder = value % divisor;
+
+    if quotient > 8
+    {
+        writer.write(9, 0x1FF);
+        writer.write(escape_bits, value);
+        return;
+    }
+
+    writer.write(quotient + 1, ((1 << quotient) - 1) << 1);
+    if k != 1
+    {
+        if remainder > 0
+        {
+            writer.write(k, remainder + 1);
+        }
+        else
+        {
+            writer.write(k - 1, 0);
+        }
+    }
+}
+
+/// Floor of log2, with `log2(0) == 0`
+fn log2(value: u32) -> u32
+{
+    31 - (value | 1).leading_zeros()
+}
+
+/// MSB-first bit packer
+struct BitWriter
+{
+    bytes: Vec<u8>,
+    buffer: u64,
+    bits: u32,
+}
+
+impl BitWriter
+{
+    fn new() -> Self
+    {
+        BitWriter { bytes: Vec::new(), buffer: 0, bits: 0 }
+    }
+
+    /// Append the low `count` bits of `value` (at most 32)
+    fn write(&mut self, count: u32, value: u32)
+    {
+        self.buffer = (self.buffer << count) | (value as u64 & ((1u64 << count) - 1));
+        self.bits += count;
+        while self.bits >= 8
+        {
+            self.bits -= 8;
+            self.bytes.push((self.buffer >> self.bits) as u8);
+        }
+    }
+
+    fn bit_len(&self) -> usize
+    {
+        self.bytes.len() * 8 + self.bits as usize
+    }
+
+    /// Pad with zeros to a whole byte
+    fn finish(mut self) -> Vec<u8>
+    {
+        if self.bits > 0
+        {
+            let pad = 8 - self.bits;
+            self.write(pad, 0);
+        }
+        self.bytes
+    }
+}
+
+/// The `moov` box: one sound track whose frames are stored as a single chunk at `data_offset`
+fn movie_box(frames: &[Vec<u8>], length: usize, sample_rate: u32, channels: u16, data_offset: u32) -> Result<Vec<u8>>
+{
+    let duration = u32::try_from(length).map_err(|_| anyhow!("Too many samples for an ALAC file"))?;
+    let identity: [u32; 9] = [0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000];
+    let matrix: Vec<u8> = identity.iter().flat_map(|v| v.to_be_bytes()).collect();
+
+    let mut mvhd = Body::new();
+    mvhd.u32(0).u32(0).u32(sample_rate).u32(duration).u32(0x0001_0000).u16(0x0100).u16(0).u32(0).u32(0)
+        .bytes(&matrix).bytes(&[0; 24]).u32(2);
+
+    let mut tkhd = Body::new();
+    tkhd.u32(0).u32(0).u32(1).u32(0).u32(duration).u32(0).u32(0).u16(0).u16(0).u16(0x0100).u16(0)
+        .bytes(&matrix).u32(0).u32(0);
+
+    // Language "und"
+    let mut mdhd = Body::new();
+    mdhd.u32(0).u32(0).u32(sample_rate).u32(duration).u16(0x55C4).u16(0);
+
+    let mut hdlr = Body::new();
+    hdlr.u32(0).bytes(b"soun").u32(0).u32(0).u32(0).bytes(b"SoundHandler\0");
+
+    let mut dref = Body::new();
+    dref.u32(1).bytes(&full_box(b"url ", 1, &[]));
+
+    // The magic cookie decoders are configured from
+    let max_frame_bytes = frames.iter().map(Vec::len).max().unwrap_or(0) as u32;
+    let data_len: u64 = frames.iter().map(|f| f.len() as u64).sum();
+    let seconds = length as f64 / sample_rate.max(1) as f64;
+    let average_bitrate = if seconds > 0.0 { (data_len as f64 * 8.0 / seconds).round() as u32 } else { 0 };
+    let mut cookie = Body::new();
+    cookie.u32(FRAME_LENGTH as u32).u8(0).u8(BIT_DEPTH as u8).u8(HISTORY_MULT as u8).u8(INITIAL_HISTORY as u8)
+          .u8(RICE_LIMIT as u8).u8(channels as u8).u16(MAX_RUN).u32(max_frame_bytes).u32(average_bitrate).u32(sample_rate);
+
+    // The 16.16 rate field can't hold rates above 65535; decoders use the cookie's
+    let mut entry = Body::new();
+    entry.bytes(&[0; 6]).u16(1).u32(0).u32(0).u16(channels).u16(BIT_DEPTH as u16).u16(0).u16(0)
+         .u32(if sample_rate <= 0xFFFF { sample_rate << 16 } else { 0 })
+         .bytes(&full_box(b"alac", 0, &cookie.0));
+
+    let mut stsd = Body::new();
+    stsd.u32(1).bytes(&mp4_box(b"alac", &entry.0));
+
+    // Every frame is FRAME_LENGTH samples except maybe the last
+    let mut stts = Body::new();
+    let last = length - (frames.len().saturating_sub(1) * FRAME_LENGTH);
+    match frames.len()
+    {
+        0 => stts.u32(0),
+        n if last == FRAME_LENGTH => stts.u32(1).u32(n as u32).u32(FRAME_LENGTH as u32),
+        1 => stts.u32(1).u32(1).u32(last as u32),
+        n => stts.u32(2).u32(n as u32 - 1).u32(FRAME_LENGTH as u32).u32(1).u32(last as u32),
+    };
+
+    let mut stsc = Body::new();
+    let mut stco = Body::new();
+    if frames.is_empty()
+    {
+        stsc.u32(0);
+        stco.u32(0);
+    }
+    else
+    {
+        stsc.u32(1).u32(1).u32(frames.len() as u32).u32(1);
+        stco.u32(1).u32(data_offset);
+    }
+
+    let mut stsz = Body::new();
+    stsz.u32(0).u32(frames.len() as u32);
+    for frame in frames
+    {
+        stsz.u32(frame.len() as u32);
+    }
+
+    let stbl = [full_box(b"stsd", 0, &stsd.0), full_box(b"stts", 0, &stts.0), full_box(b"stsc", 0, &stsc.0),
+                full_box(b"stsz", 0, &stsz.0), full_box(b"stco", 0, &stco.0)].concat();
+    let minf = [full_box(b"smhd", 0, &[0; 4]), mp4_box(b"dinf", &full_box(b"dref", 0, &dref.0)), mp4_box(b"stbl", &stbl)].concat();
+    let mdia = [full_box(b"mdhd", 0, &mdhd.0), full_box(b"hdlr", 0, &hdlr.0), mp4_box(b"minf", &minf)].concat();
+    let trak = [full_box(b"tkhd", 7, &tkhd.0), mp4_box(b"mdia", &mdia)].concat();
+    let moov = [full_box(b"mvhd", 0, &mvhd.0), mp4_box(b"trak", &trak)].concat();
+    Ok(mp4_box(b"moov", &moov))
+}
+
+/// Box contents built from big-endian fields
+struct Body(Vec<u8>);
+
+impl Body
+{
+    fn new() -> Self
+    {
+        Body(Vec::new())
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> &mut Self
+    {
+        self.0.extend_from_slice(bytes);
+        self
+    }
+
+    fn u8(&mut self, value: u8) -> &mut Self
+    {
+        self.bytes(&[value])
+    }
+
+    fn u16(&mut self, value: u16) -> &mut Self
+    {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    fn u32(&mut self, value: u32) -> &mut Self
+    {
+        self.bytes(&value.to_be_bytes())
+    }
+}
+
+fn mp4_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8>
+{
+    let mut data = Vec::with_capacity(8 + body.len());
+    data.extend_from_slice(&(8 + body.len() as u32).to_be_bytes());
+    data.extend_from_slice(kind);
+    data.extend_from_slice(body);
+    data
+}
+
+/// Box with a version byte (always 0) and 24 bits of flags
+fn full_box(kind: &[u8; 4], flags: u32, body: &[u8]) -> Vec<u8>
+{
+    mp4_box(kind, &[&flags.to_be_bytes()[..], body].concat())
+}
@@ -0,0 +1,183 @@
+//! Per-frame statistics of an encoded stream, for inspecting and tuning the encoder's decisions,
+//! and whole-file summaries for auditing encodes
+use anyhow::Result;
+use serde::Serialize;
+use std::io::Write;
+use crate::codec::{EncodedAudio, EncodedFrame, padded_block};
+use crate::compare::compare;
+use crate::loudness::measure;
+use crate::container::serialize_frame;
+
+/// Sub-blocks per frame used by the transient detector
+const TRANSIENT_SUB_BLOCKS: usize = 8;
+/// Energy jump (relative to the mean of the preceding sub-blocks) that marks an attack: 10 dB
+const TRANSIENT_RATIO: f32 = 10.0;
+/// Sub-blocks quieter than this mean square (-60 dBFS) never count as an attack
+const TRANSIENT_FLOOR: f32 = 1e-6;
+
+/// What the encoder did with one frame
+#[derive(Clone, Debug, Serialize)]
+pub struct FrameStats
+{
+    pub frame: usize,
+    /// Position of the frame's first sample in the source, per channel
+    /// (negative for the first frame, which starts inside the encoder delay)
+    pub start_sample: i64,
+    /// The frame fell back to raw PCM
+    pub raw_pcm: bool,
+    /// Coefficients kept per channel, out of the hop size (empty for raw frames)
+    pub coefficients: Vec<usize>,
+    /// Scale factor per channel (empty for raw frames)
+    pub scale_factors: Vec<f32>,
+    /// Serialized size of the frame
+    pub bits: u64,
+    /// The frame contains a sharp energy rise, where pre-echo is most likely
+    pub transient: bool,
+}
+
+/// Statistics for every frame of `encoded`
+/// `samples` is the interleaved signal the frames are compared against for transient detection:
+/// the encoder's input, or the decoded output when only the `.glc` file is available
+pub fn analyze(encoded: &EncodedAudio, samples: &[f32]) -> Result<Vec<FrameStats>>
+{
+    let channels = encoded.header.channels.max(1) as usize;
+    let hop = encoded.header.hop_size as usize;
+    let planar: Vec<Vec<f32>> = (0..channels)
+        .map(|ch| samples.iter().skip(ch).step_by(channels).copied().collect())
+        .collect();
+
+    encoded.frames
+           .iter()
+           .enumerate()
+           .map(|(index, frame)| frame_stats(index, frame, &planar, hop))
+           .collect()
+}
+
+fn frame_stats(index: usize, frame: &EncodedFrame, planar: &[Vec<f32>], hop: usize) -> Result<FrameStats>
+{
+    let start = index * hop;
+    let blocks: Vec<Vec<f32>> = planar.iter().map(|c| padded_block(c, start, hop)).collect();
+
+    Ok(FrameStats
+    {
+        frame: index,
+        start_sample: start as i64 - hop as i64,
+        raw_pcm: frame.raw_pcm.is_some(),
+        coefficients: frame.sparse_coeffs_per_channel.iter().map(|c| c.len()).collect(),
+        scale_factors: frame.scale_factors.clone(),
+        bits: serialize_frame(frame)?.len() as u64 * 8,
+        transient: is_transient(&blocks),
+    })
+}
+
+/// Whether any sub-block of the frame is much louder than the sub-blocks before it
+fn is_transient(blocks: &[Vec<f32>]) -> bool
+{
+    let len = blocks.first().map_or(0, |b| b.len()) / TRANSIENT_SUB_BLOCKS;
+    let energies: Vec<f32> = (0..TRANSIENT_SUB_BLOCKS)
+        .map(|j|
+        {
+            let sum: f32 = blocks.iter()
+                                 .flat_map(|b| &b[j * len..(j + 1) * len])
+                                 .map(|s| s * s)
+                                 .sum();
+            sum / (len * blocks.len().max(1)) as f32
+        })
+        .collect();
+
+    (1..TRANSIENT_SUB_BLOCKS).any(|j|
+    {
+        let before = energies[..j].iter().sum::<f32>() / j as f32;
+        energies[j] > TRANSIENT_FLOOR && energies[j] > before * TRANSIENT_RATIO
+    })
+}
+
+/// Write `stats` as CSV with a header row; per-channel values are separated by spaces
+pub fn write_csv<W: Write>(stats: &[FrameStats], mut writer: W) -> Result<()>
+{
+    writeln!(writer, "frame,start_sample,raw_pcm,coefficients,scale_factors,bits,transient")?;
+
+    for s in stats
+    {
+        let join = |values: Vec<String>| values.join(" ");
+        writeln!(writer, "{},{},{},{},{},{},{}",
+                 s.frame,
+                 s.start_sample,
+                 s.raw_pcm,
+                 join(s.coefficients.iter().map(|c| c.to_string()).collect()),
+                 join(s.scale_factors.iter().map(|f| f.to_string()).collect()),
+                 s.bits,
+                 s.transient)?;
+    }
+
+    Ok(())
+}
+
+/// Summary of a whole encode against its source, as written by `glc encode --report`
+/// Measures that don't exist are infinite (null in JSON): the SNR of an exact match, the loudness of silence
+#[derive(Clone, Debug, Serialize)]
+pub struct QualitySummary
+{
+    pub frames: usize,
+    pub duration_secs: f64,
+    /// Average bitrate of the encoded file, in kbit/s
+    pub kbps: f64,
+    /// Share of the compressed frames' coefficients that were dropped, from 0 to 1
+    pub sparsity: f64,
+    /// Share of frames stored as raw PCM, in percent
+    pub raw_pcm_percent: f64,
+    /// SNR of the decoded output against the source
+    pub snr_db: f64,
+    pub weighted_snr_db: f64,
+    /// Integrated loudness of the decoded output, in LUFS
+    pub loudness_lufs: f64,
+    /// Sample peak of the decoded output
+    pub peak: f32,
+}
+
+/// Summarize `encoded`, stored in `file_size` bytes, from the interleaved `original` it was encoded
+/// from and the `decoded` output
+pub fn summarize(encoded: &EncodedAudio, file_size: u64, original: &[f32], decoded: &[f32]) -> QualitySummary
+{
+    let header = &encoded.header;
+    let channels = header.channels.max(1) as usize;
+    let frames = encoded.frames.len();
+    let duration_secs = (encoded.gapless_info.original_length / channels as u64) as f64 / header.sample_rate.max(1) as f64;
+
+    let compressed: Vec<&EncodedFrame> = encoded.frames.iter().filter(|f| f.raw_pcm.is_none()).collect();
+    let kept: usize = compressed.iter().flat_map(|f| &f.sparse_coeffs_per_channel).map(Vec::len).sum();
+    let bins = compressed.len() * channels * header.hop_size as usize;
+
+    let quality = compare(original, decoded, header.sample_rate, header.channels);
+    let loudness = measure(decoded, header.sample_rate, header.channels);
+
+    QualitySummary
+    {
+        frames,
+        duration_secs,
+        kbps: if duration_secs > 0.0 { file_size as f64 * 8.0 / duration_secs / 1000.0 } else { 0.0 },
+        sparsity: if bins > 0 { 1.0 - kept as f64 / bins as f64 } else { 0.0 },
+        raw_pcm_percent: if frames > 0 { (frames - compressed.len()) as f64 * 100.0 / frames as f64 } else { 0.0 },
+        snr_db: quality.snr_db,
+        weighted_snr_db: quality.metrics.weighted_snr_db,
+        loudness_lufs: loudness.integrated_lufs,
+        peak: loudness.peak,
+    }
+}
+
+/// Write `summary` as CSV: a header row and one row of values
+pub fn write_summary_csv<W: Write>(summary: &QualitySummary, mut writer: W) -> Result<()>
+{
+    writeln!(writer, "frames,duration_secs,kbps,sparsity,raw_pcm_percent,snr_db,weighted_snr_db,loudness_lufs,peak")?;
+    writeln!(writer, "{},{},{},{},{},{},{},{},{}",
+             summary.frames,
+             summary.duration_secs,
+             summary.kbps,
+             summary.sparsity,
+             summary.raw_pcm_percent,
+             summary.snr_db,
+             summary.weighted_snr_db,
+             summary.loudness_lufs,
+             summary.peak)?;
+    Ok(())
+}
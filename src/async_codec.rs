@@ -0,0 +1,51 @@
+//! tokio-compatible async wrappers around [`Encoder`] and [`Decoder`]
+//! All CPU-heavy work runs on tokio's blocking pool so the async runtime stays responsive
+//! These functions must be called from within a tokio runtime
+use anyhow::Result;
+use crossbeam_channel::Sender;
+use std::sync::Arc;
+use tokio_stream::Stream;
+use tokio_stream::wrappers::ReceiverStream;
+use crate::codec::{Encoder, Decoder, EncodedAudio, AudioChunk, Progress, StreamOptions};
+
+/// Encode PCM `samples` (interleaved if multichannel) on the blocking pool
+pub async fn encode_async(samples: Vec<f32>, sample_rate: u32, channels: u16) -> Result<EncodedAudio>
+{
+    tokio::task::spawn_blocking(move ||
+    {
+        let mut encoder = Encoder::new(sample_rate);
+        encoder.encode(&samples, channels)
+    }).await?
+}
+
+/// Decode `encoded` on the blocking pool, returning all (gapless-trimmed) samples
+pub async fn decode_async(encoded: Arc<EncodedAudio>, progress_sender: Option<Sender<Progress>>) -> Result<Vec<f32>>
+{
+    tokio::task::spawn_blocking(move ||
+    {
+        let mut decoder = Decoder::new(encoded.header.channels as usize, encoded.header.sample_rate);
+        decoder.decode(&encoded, progress_sender)
+    }).await?
+}
+
+/// Decode `encoded` on the blocking pool, yielding interleaved chunks as they become available
+/// Chunks are the same as those produced by [`Decoder::decode_streaming`] (untrimmed, last one flagged)
+/// Dropping the stream stops the decode at the next chunk boundary
+pub fn decode_stream(encoded: Arc<EncodedAudio>, progress_sender: Option<Sender<Progress>>) -> impl Stream<Item = AudioChunk>
+{
+    decode_stream_with(encoded, progress_sender, StreamOptions::default())
+}
+
+/// Like [`decode_stream`], with custom chunk size and channel depth
+pub fn decode_stream_with(encoded: Arc<EncodedAudio>, progress_sender: Option<Sender<Progress>>, options: StreamOptions) -> impl Stream<Item = AudioChunk>
+{
+    let (tx, rx) = tokio::sync::mpsc::channel(options.channel_depth.max(1));
+    let decoder = Decoder::new(encoded.header.channels as usize, encoded.header.sample_rate);
+
+    tokio::task::spawn_blocking(move ||
+    {
+        decoder.decode_chunks(&encoded, progress_sender, options.frames_per_chunk, |chunk| tx.blocking_send(chunk).is_ok());
+    });
+
+    ReceiverStream::new(rx)
+}
@@ -1,22 +1,32 @@
-//! Handles file I/O for mainstream lossless audio codecs (WAV and FLAC)
+//! Handles file I/O for mainstream lossless audio formats (WAV, FLAC, AIFF, and CAF)
 use anyhow::{anyhow, Result};
-use std::path::Path;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 use hound;
 use claxon;
 use crate::flac as pure_flac;
+use crate::codec::{Encoder, DEFAULT_QUALITY, HOP_SIZE};
+use crate::container::{GlcEncoder, SizeEstimate, SizeEstimator, Tags};
+use crate::edit::common_tags;
+use crate::ffmpeg::FfmpegReader;
+use crate::loudness::{Loudness, LoudnessMeter, Normalize};
+use crate::pcm::{f64_to_extended, PcmReader};
+#[cfg(feature = "transcode")]
+use crate::transcode::{TranscodeReader, is_transcode_input};
 
 
 /// Helper function to convert f32 samples to i16
 /// For each f32 sample, multiply by i16 max, then clamp to valid i16 range
-fn convert_f32_to_i16(samples: &[f32]) -> Vec<i16>
+pub(crate) fn convert_f32_to_i16(samples: &[f32]) -> Vec<i16>
 {
     samples.iter()
            .map(|&sample| (sample * 32767.0).clamp(-32768.0, 32767.0) as i16)
            .collect()
 }
 
-/// Load audio file from `Path` (only supports WAV and FLAC)
-/// Calls [`load_wav`] or [`load_flac`] depending on filetype
+/// Load audio file from `Path` (supports WAV, FLAC, AIFF/AIFF-C, and CAF)
+/// Calls [`load_flac`] or [`load_streamed`] depending on filetype
 /// Returns the sample vector, sample rate, and number of channels
 pub fn load_audio_file_lossless(path: &Path) -> Result<(Vec<f32>, u32, u16)>
 {
@@ -28,39 +38,21 @@ pub fn load_audio_file_lossless(path: &Path) -> Result<(Vec<f32>, u32, u16)>
 
     match ext.as_str()
     {
-        "wav" => load_wav(path),
         "flac" => load_flac(path),
+        "wav" | "aif" | "aiff" | "aifc" | "caf" => load_streamed(path),
         _ => Err(anyhow!("Unsupported file format: {}", ext)),
     }
 }
 
-/// Load WAV file from `Path`
+/// Load a whole file through [`LosslessReader`]
 /// Returns the sample vector, sample rate, and number of channels
-fn load_wav(path: &Path) -> Result<(Vec<f32>, u32, u16)> 
+fn load_streamed(path: &Path) -> Result<(Vec<f32>, u32, u16)>
 {
-    let mut reader = hound::WavReader::open(path)?;
-    let spec = reader.spec();
-
-    let samples: Vec<f32> = match spec.sample_format 
-    {
-        hound::SampleFormat::Float => 
-        {
-            // Pass through f32 samples
-            reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?
-        }
-        hound::SampleFormat::Int => 
-        {
-            // Divide by max sample value to convert i32 samples to f32
-            let bits = spec.bits_per_sample;
-            let max = (1 << (bits - 1)) as f32;
-            reader
-                .samples::<i32>()
-                .map(|s| Ok::<f32, hound::Error>(s? as f32 / max))
-                .collect::<Result<Vec<_>, _>>()?
-        }
-    };
+    let mut reader = LosslessReader::open(path)?;
+    let mut samples = Vec::new();
+    while reader.read_block(&mut samples)? {}
 
-    Ok((samples, spec.sample_rate, spec.channels))
+    Ok((samples, reader.sample_rate(), reader.channels()))
 }
 
 /// Load FLAC file from `Path`
@@ -82,6 +74,446 @@ fn load_flac(path: &Path) -> Result<(Vec<f32>, u32, u16)>
     Ok((samples, info.sample_rate, info.channels as u16))
 }
 
+/// Interleaved frames read per block from WAV, AIFF, and CAF files by [`LosslessReader`]
+const PCM_BLOCK_FRAMES: usize = 4096;
+
+/// Interleaved frames handed to the GLC encoder at a time by [`encode_lossless`] (~64 codec frames)
+const ENCODE_BATCH_FRAMES: usize = 1 << 16;
+
+/// Streaming reader for WAV, FLAC, AIFF, and CAF files that yields interleaved f32 samples
+/// one block at a time, so long files never need to be held in memory
+pub struct LosslessReader
+{
+    source: LosslessSource,
+    sample_rate: u32,
+    channels: u16,
+    channel_mask: u32,
+    tags: Tags,
+}
+
+enum LosslessSource
+{
+    /// Reader, max sample value, and the reusable block buffer
+    Flac(claxon::FlacReader<File>, f32, Vec<i32>),
+    /// WAV/RF64, AIFF/AIFF-C, or CAF
+    Pcm(PcmReader),
+    /// Lossy input decoded through Symphonia
+    #[cfg(feature = "transcode")]
+    Transcode(Box<TranscodeReader>),
+    /// Any other format, decoded by an external ffmpeg
+    Ffmpeg(FfmpegReader),
+}
+
+/// Whether `path` has an extension [`LosslessReader::open`] handles without ffmpeg
+pub fn is_native_input(path: &Path) -> bool
+{
+    #[cfg(feature = "transcode")]
+    if is_transcode_input(path)
+    {
+        return true;
+    }
+
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| matches!(e.to_lowercase().as_str(), "wav" | "flac" | "aif" | "aiff" | "aifc" | "caf"))
+}
+
+impl LosslessReader
+{
+    /// Open a WAV, FLAC, AIFF/AIFF-C, or CAF file (by extension), reading only its header
+    /// With the `transcode` feature, MP3, Ogg Vorbis, and AAC/M4A files are also accepted
+    pub fn open(path: &Path) -> Result<Self>
+    {
+        #[cfg(feature = "transcode")]
+        if is_transcode_input(path)
+        {
+            let reader = TranscodeReader::open(path)?;
+            return Ok(Self
+            {
+                sample_rate: reader.sample_rate(),
+                channels: reader.channels(),
+                channel_mask: 0,
+                tags: reader.tags().clone(),
+                source: LosslessSource::Transcode(Box::new(reader)),
+            });
+        }
+
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .ok_or_else(|| anyhow!("No file extension"))?
+            .to_lowercase();
+
+        match ext.as_str()
+        {
+            "flac" =>
+            {
+                let reader = claxon::FlacReader::open(path)?;
+                let info = reader.streaminfo();
+                let max = (1i64 << (info.bits_per_sample - 1)) as f32;
+
+                let mut tags = Tags::default();
+                for (key, value) in reader.tags()
+                {
+                    tags.add(key, value);
+                }
+
+                Ok(Self
+                {
+                    source: LosslessSource::Flac(reader, max, Vec::new()),
+                    sample_rate: info.sample_rate,
+                    channels: info.channels as u16,
+                    channel_mask: 0,
+                    tags,
+                })
+            }
+            "wav" | "aif" | "aiff" | "aifc" | "caf" =>
+            {
+                let reader = match ext.as_str()
+                {
+                    "wav" => PcmReader::open_wav(path)?,
+                    "caf" => PcmReader::open_caf(path)?,
+                    _ => PcmReader::open_aiff(path)?,
+                };
+                Ok(Self
+                {
+                    sample_rate: reader.sample_rate(),
+                    channels: reader.channels(),
+                    channel_mask: reader.channel_mask(),
+                    tags: reader.tags().clone(),
+                    source: LosslessSource::Pcm(reader),
+                })
+            }
+            _ => Err(anyhow!("Unsupported file format: {}", ext)),
+        }
+    }
+
+    /// Open `path` like [`open`](Self::open), but if `allow_ffmpeg` is set and its format isn't
+    /// supported natively, decode it through ffmpeg instead (see [`FfmpegReader`])
+    pub fn open_with(path: &Path, allow_ffmpeg: bool) -> Result<Self>
+    {
+        if !allow_ffmpeg || is_native_input(path)
+        {
+            return Self::open(path);
+        }
+
+        let reader = FfmpegReader::open(path)?;
+        Ok(Self
+        {
+            sample_rate: reader.sample_rate(),
+            channels: reader.channels(),
+            channel_mask: 0,
+            tags: reader.tags().clone(),
+            source: LosslessSource::Ffmpeg(reader),
+        })
+    }
+
+    pub fn sample_rate(&self) -> u32
+    {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u16
+    {
+        self.channels
+    }
+
+    /// Speaker layout as a WAVE_FORMAT_EXTENSIBLE channel mask, or 0 if the file does not specify one
+    pub fn channel_mask(&self) -> u32
+    {
+        self.channel_mask
+    }
+
+    /// Length of the file in frames, if its header gives one (WAV, AIFF, and CAF always do; FLAC
+    /// usually does; lossy and ffmpeg input don't)
+    pub fn frames(&self) -> Option<u64>
+    {
+        match &self.source
+        {
+            LosslessSource::Flac(reader, _, _) => reader.streaminfo().samples,
+            LosslessSource::Pcm(reader) => Some(reader.frames()),
+            #[cfg(feature = "transcode")]
+            LosslessSource::Transcode(_) => None,
+            LosslessSource::Ffmpeg(_) => None,
+        }
+    }
+
+    /// Metadata read from the file (Vorbis comments for FLAC, text chunks for WAV/AIFF/CAF)
+    pub fn tags(&self) -> &Tags
+    {
+        &self.tags
+    }
+
+    /// Append the next block of interleaved samples to `out`
+    /// Returns false (appending nothing) once the end of the file is reached
+    pub fn read_block(&mut self, out: &mut Vec<f32>) -> Result<bool>
+    {
+        let start = out.len();
+
+        match self.source
+        {
+            LosslessSource::Flac(ref mut reader, max, ref mut buffer) =>
+            {
+                match reader.blocks().read_next_or_eof(std::mem::take(buffer))?
+                {
+                    Some(block) =>
+                    {
+                        // FLAC blocks are planar; interleave them
+                        for i in 0..block.duration()
+                        {
+                            for ch in 0..block.channels()
+                            {
+                                out.push(block.sample(ch, i) as f32 / max);
+                            }
+                        }
+                        *buffer = block.into_buffer();
+                    }
+                    None => return Ok(false),
+                }
+            }
+            LosslessSource::Pcm(ref mut reader) => return reader.read_block(out, PCM_BLOCK_FRAMES),
+            #[cfg(feature = "transcode")]
+            LosslessSource::Transcode(ref mut reader) => return reader.read_block(out),
+            LosslessSource::Ffmpeg(ref mut reader) => return reader.read_block(out, PCM_BLOCK_FRAMES),
+        }
+
+        Ok(out.len() > start)
+    }
+}
+
+/// Encode everything left in `reader` to a GLC stream on `writer` block by block through
+/// [`GlcEncoder`], without loading the whole input into memory; returns the writer
+/// Tags read from the input are carried over
+pub fn encode_lossless<W: Write>(mut reader: LosslessReader, writer: W) -> Result<W>
+{
+    let mut encoder = GlcEncoder::new(writer, reader.sample_rate(), reader.channels())?;
+    encoder.set_channel_mask(reader.channel_mask());
+    encoder.set_tags(std::mem::take(&mut reader.tags));
+
+    stream_into(&mut reader, &mut encoder, 1.0)?;
+    encoder.finish()
+}
+
+/// Measure the loudness and peak of a WAV or FLAC file, streaming it block by block
+pub fn measure_loudness(input: &Path) -> Result<Loudness>
+{
+    measure_loudness_with(input, false)
+}
+
+/// [`measure_loudness`], opening `input` with [`LosslessReader::open_with`]
+pub(crate) fn measure_loudness_with(input: &Path, allow_ffmpeg: bool) -> Result<Loudness>
+{
+    let mut reader = LosslessReader::open_with(input, allow_ffmpeg)?;
+    let mut meter = LoudnessMeter::new(reader.sample_rate(), reader.channels());
+    let mut block = Vec::new();
+    while reader.read_block(&mut block)?
+    {
+        meter.add_samples(&block);
+        block.clear();
+    }
+    Ok(meter.loudness())
+}
+
+/// Settings for encoding a file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EncodeOptions
+{
+    /// See [`crate::codec::Encoder::set_quality`]
+    pub quality: f32,
+    /// Level the file while encoding it
+    pub normalize: Option<Normalize>,
+    /// Decode inputs in formats that aren't supported natively through ffmpeg
+    pub allow_ffmpeg: bool,
+    /// Samples each frame advances by (see [`crate::codec::Encoder::with_hop_size`])
+    pub hop_size: usize,
+    /// Lowpass cutoff in Hz (see [`crate::codec::Encoder::set_cutoff`])
+    pub cutoff: Option<f32>,
+    /// High-pass cutoff in Hz (see [`crate::codec::Encoder::set_highpass`])
+    pub highpass: Option<f32>,
+    /// Predict frames from earlier ones (see [`crate::codec::Encoder::set_long_term_prediction`]);
+    /// [`estimate_size`] leaves it out
+    pub long_term_prediction: bool,
+    /// Bandwidth-extension crossover in Hz (see [`crate::codec::Encoder::set_bandwidth_extension`])
+    pub bandwidth_extension: Option<f32>,
+    /// Vary the quality per frame (see [`crate::codec::Encoder::set_adaptive_quality`])
+    pub adaptive_quality: bool,
+    /// Target bitrate in kbit/s, keeping a fixed number of coefficients per frame instead of
+    /// selecting them by `quality` (see [`crate::codec::Encoder::set_bitrate`])
+    pub bitrate: Option<u32>,
+    /// Diffuse coding error across each frame's spectrum (see [`crate::codec::Encoder::set_noise_shaping`])
+    pub noise_shaping: bool,
+}
+
+impl Default for EncodeOptions
+{
+    fn default() -> Self
+    {
+        Self { quality: DEFAULT_QUALITY, normalize: None, allow_ffmpeg: false, hop_size: HOP_SIZE, cutoff: None, highpass: None,
+               long_term_prediction: false, bandwidth_extension: None, adaptive_quality: false,
+               bitrate: None, noise_shaping: false }
+    }
+}
+
+impl EncodeOptions
+{
+    /// Set `encoder` up with these options' encoder settings
+    pub fn apply(&self, encoder: &mut Encoder)
+    {
+        encoder.set_quality(self.quality);
+        encoder.set_cutoff(self.cutoff);
+        encoder.set_highpass(self.highpass);
+        encoder.set_long_term_prediction(self.long_term_prediction);
+        encoder.set_bandwidth_extension(self.bandwidth_extension);
+        encoder.set_adaptive_quality(self.adaptive_quality);
+        encoder.set_bitrate(self.bitrate);
+        encoder.set_noise_shaping(self.noise_shaping);
+    }
+
+    /// An encoder for a stream in `sample_rate` with these options' hop size and settings
+    pub fn encoder(&self, sample_rate: u32) -> Result<Encoder>
+    {
+        let mut encoder = Encoder::with_hop_size(sample_rate, self.hop_size)?;
+        self.apply(&mut encoder);
+        Ok(encoder)
+    }
+}
+
+/// Encode `input` like [`encode_lossless`] with `options`
+/// When normalizing, the input is read twice: once to measure it, once to encode it
+/// Returns the writer and the normalization gain applied, in dB
+pub fn encode_lossless_with<W: Write>(input: &Path, options: &EncodeOptions, writer: W) -> Result<(W, Option<f64>)>
+{
+    let gain_db = match options.normalize
+    {
+        Some(normalize) => Some(normalize.gain_db(&measure_loudness_with(input, options.allow_ffmpeg)?)),
+        None => None,
+    };
+
+    let mut reader = LosslessReader::open_with(input, options.allow_ffmpeg)?;
+    let mut encoder = GlcEncoder::with_encoder(writer, options.encoder(reader.sample_rate())?, reader.channels())?;
+    encoder.set_channel_mask(reader.channel_mask());
+    encoder.set_tags(std::mem::take(&mut reader.tags));
+
+    stream_into(&mut reader, &mut encoder, 10f64.powf(gain_db.unwrap_or(0.0) / 20.0) as f32)?;
+    Ok((encoder.finish()?, gain_db))
+}
+
+/// Frames skipped between the ones [`estimate_size`] encodes by default
+pub const DEFAULT_ESTIMATE_STRIDE: usize = 8;
+
+/// Predict the size of encoding `input` with `options` without writing anything, encoding
+/// only every `stride`-th frame (see [`SizeEstimator`])
+pub fn estimate_size(input: &Path, options: &EncodeOptions, stride: usize) -> Result<SizeEstimate>
+{
+    let gain = match options.normalize
+    {
+        Some(normalize) => 10f64.powf(normalize.gain_db(&measure_loudness_with(input, options.allow_ffmpeg)?) / 20.0) as f32,
+        None => 1.0,
+    };
+
+    let mut reader = LosslessReader::open_with(input, options.allow_ffmpeg)?;
+    let mut estimator = SizeEstimator::with_encoder(options.encoder(reader.sample_rate())?, reader.channels(), stride)?;
+    estimator.set_tags(std::mem::take(&mut reader.tags));
+
+    let mut block = Vec::new();
+    while reader.read_block(&mut block)?
+    {
+        if gain != 1.0
+        {
+            block.iter_mut().for_each(|s| *s *= gain);
+        }
+        estimator.write_samples(&block)?;
+        block.clear();
+    }
+    estimator.finish()
+}
+
+/// Encode several inputs back to back into one gapless GLC stream on `writer`, one track per input
+/// Every input must have the same sample rate and channel count. Each track keeps its own tags
+/// (titled after its file name if it has none); tags shared by every track become the stream's tags
+pub fn encode_album<W: Write>(inputs: &[PathBuf], writer: W) -> Result<W>
+{
+    let readers = inputs.iter()
+                        .map(|path| LosslessReader::open(path).map_err(|e| anyhow!("{}: {}", path.display(), e)))
+                        .collect::<Result<Vec<_>>>()?;
+    let first = readers.first().ok_or_else(|| anyhow!("No tracks to encode"))?;
+    let (sample_rate, channels) = (first.sample_rate(), first.channels());
+
+    for (path, reader) in inputs.iter().zip(&readers)
+    {
+        if reader.sample_rate() != sample_rate || reader.channels() != channels
+        {
+            return Err(anyhow!("{} is {} Hz, {} channels but the first track is {} Hz, {} channels; resample it first",
+                               path.display(), reader.sample_rate(), reader.channels(), sample_rate, channels));
+        }
+    }
+
+    let channel_mask = if readers.iter().all(|r| r.channel_mask() == first.channel_mask()) { first.channel_mask() } else { 0 };
+    let album_tags = common_tags(readers.iter().map(|r| r.tags()));
+
+    let mut encoder = GlcEncoder::new(writer, sample_rate, channels)?;
+    encoder.set_channel_mask(channel_mask);
+    encoder.set_tags(album_tags);
+
+    for (path, mut reader) in inputs.iter().zip(readers)
+    {
+        let mut tags = std::mem::take(&mut reader.tags);
+        if tags.get("title").is_none()
+            && let Some(stem) = path.file_stem()
+        {
+            tags.set("title", stem.to_string_lossy().into_owned());
+        }
+
+        encoder.start_track(tags);
+        stream_into(&mut reader, &mut encoder, 1.0)?;
+    }
+
+    encoder.finish()
+}
+
+/// Feed everything left in `reader` to `encoder`, multiplied by `gain`
+fn stream_into<W: Write>(reader: &mut LosslessReader, encoder: &mut GlcEncoder<W>, gain: f32) -> Result<()>
+{
+    // Gather several reads per write so the encoder has enough whole frames to spread across threads
+    let batch_len = ENCODE_BATCH_FRAMES * reader.channels() as usize;
+    let mut block = Vec::with_capacity(batch_len);
+    let mut more = true;
+    while more
+    {
+        while more && block.len() < batch_len
+        {
+            more = reader.read_block(&mut block)?;
+        }
+        if gain != 1.0
+        {
+            block.iter_mut().for_each(|s| *s *= gain);
+        }
+        encoder.write_samples(&block)?;
+        block.clear();
+    }
+
+    Ok(())
+}
+
+/// Encode a WAV or FLAC file to a `.glc` file (see [`encode_lossless`])
+pub fn encode_lossless_file(input: &Path, output: &Path) -> Result<()>
+{
+    let reader = LosslessReader::open(input)?;
+    let writer = BufWriter::new(File::create(output)?);
+    encode_lossless(reader, writer)?.flush()?;
+    Ok(())
+}
+
+/// Encode a WAV or FLAC file to a `.glc` file with `options` (see [`encode_lossless_with`]);
+/// returns the normalization gain applied in dB
+pub fn encode_lossless_file_with(input: &Path, output: &Path, options: &EncodeOptions) -> Result<Option<f64>>
+{
+    let writer = BufWriter::new(File::create(output)?);
+    let (mut writer, gain_db) = encode_lossless_with(input, options, writer)?;
+    writer.flush()?;
+    Ok(gain_db)
+}
+
 /// Export `samples` to `Path` using FLAC encoding (pure Rust implementation)
 /// Uses 16-bit depth and a compression level of 5
 pub fn export_to_flac(
@@ -130,4 +562,445 @@ pub fn export_to_wav(
 
     writer.finalize()?;
     Ok(())
-}
\ No newline at end of file
+}
+/// Export `samples` to `Path` as an AIFF file
+/// Uses 16-bit depth, like [`export_to_wav`]
+pub fn export_to_aiff(
+    path: &Path,
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u16,
+) -> Result<()>
+{
+    // Rounded at 2^15 to full scale, the inverse of how the AIFF reader converts 16-bit samples
+    let i16_samples: Vec<i16> = samples.iter().map(|&s| (s * 32768.0).round().clamp(-32768.0, 32767.0) as i16).collect();
+    let frames = i16_samples.len() / channels.max(1) as usize;
+
+    // FORM type, then the COMM chunk (18 bytes) and the SSND chunk (offset and block size, then the samples)
+    let data_len = i16_samples.len() * 2;
+    let form_len = u32::try_from(4 + 8 + 18 + 8 + 8 + data_len)
+        .map_err(|_| anyhow!("Too much audio for an AIFF file"))?;
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(b"FORM")?;
+    writer.write_all(&form_len.to_be_bytes())?;
+    writer.write_all(b"AIFF")?;
+
+    writer.write_all(b"COMM")?;
+    writer.write_all(&18u32.to_be_bytes())?;
+    writer.write_all(&channels.to_be_bytes())?;
+    writer.write_all(&(frames as u32).to_be_bytes())?;
+    writer.write_all(&16u16.to_be_bytes())?;
+    writer.write_all(&f64_to_extended(sample_rate as f64))?;
+
+    writer.write_all(b"SSND")?;
+    writer.write_all(&(8 + data_len as u32).to_be_bytes())?;
+    writer.write_all(&[0; 8])?;
+    for sample in i16_samples
+    {
+        writer.write_all(&sample.to_be_bytes())?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Sample format for headerless PCM output, named as in ffmpeg's `-f` option
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RawFormat
+{
+    F32Le,
+    S16Le,
+    S24Le,
+    S32Le,
+}
+
+impl RawFormat
+{
+    /// Parse an ffmpeg format name: `f32le`, `s16le`, `s24le` or `s32le`
+    pub fn parse(text: &str) -> Result<Self>
+    {
+        match text.trim().to_lowercase().as_str()
+        {
+            "f32le" => Ok(RawFormat::F32Le),
+            "s16le" => Ok(RawFormat::S16Le),
+            "s24le" => Ok(RawFormat::S24Le),
+            "s32le" => Ok(RawFormat::S32Le),
+            _ => Err(anyhow!("Unknown raw format '{}': expected f32le, s16le, s24le or s32le", text)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str
+    {
+        match self
+        {
+            RawFormat::F32Le => "f32le",
+            RawFormat::S16Le => "s16le",
+            RawFormat::S24Le => "s24le",
+            RawFormat::S32Le => "s32le",
+        }
+    }
+
+    pub fn bytes_per_sample(&self) -> usize
+    {
+        match self
+        {
+            RawFormat::S16Le => 2,
+            RawFormat::S24Le => 3,
+            RawFormat::F32Le | RawFormat::S32Le => 4,
+        }
+    }
+}
+
+/// Write interleaved `samples` to `writer` as headerless PCM in `format`
+/// Integer formats are clamped to full scale, as in [`export_to_wav`]
+pub fn write_raw_pcm<W: Write>(writer: &mut W, samples: &[f32], format: RawFormat) -> Result<()>
+{
+    let mut bytes = Vec::with_capacity(PCM_BLOCK_FRAMES * format.bytes_per_sample());
+    for chunk in samples.chunks(PCM_BLOCK_FRAMES)
+    {
+        bytes.clear();
+        for &s in chunk
+        {
+            match format
+            {
+                RawFormat::F32Le => bytes.extend_from_slice(&s.to_le_bytes()),
+                RawFormat::S16Le => bytes.extend_from_slice(&((s * 32767.0).clamp(-32768.0, 32767.0) as i16).to_le_bytes()),
+                RawFormat::S24Le => bytes.extend_from_slice(&((s * 8388607.0).clamp(-8388608.0, 8388607.0) as i32).to_le_bytes()[..3]),
+                RawFormat::S32Le => bytes.extend_from_slice(&((s as f64 * 2147483647.0).clamp(-2147483648.0, 2147483647.0) as i32).to_le_bytes()),
+            }
+        }
+        writer.write_all(&bytes)?;
+    }
+    Ok(())
+}
+
+/// Kernels are precomputed when the output positions cycle through at most this many input phases
+const RESAMPLE_MAX_PHASES: u64 = 4096;
+
+/// Convert interleaved `samples` from `from` Hz to `to` Hz with a Blackman-windowed sinc filter
+/// Downsampling low-passes at the new Nyquist frequency; the first output sample lines up with the first input sample
+pub fn resample(samples: &[f32], channels: u16, from: u32, to: u32) -> Vec<f32>
+{
+    let mut resampler = Resampler::new(channels, from, to);
+    let mut out = Vec::new();
+    resampler.process(samples, &mut out);
+    resampler.finish(&mut out);
+    out
+}
+
+/// Streaming form of [`resample`]: feed interleaved blocks to [`process`](Self::process), then call
+/// [`finish`](Self::finish); the output matches resampling all of the input at once
+pub struct Resampler
+{
+    channels: usize,
+    from: u32,
+    to: u32,
+    /// Output frame j sits at input position j * num / den
+    num: u64,
+    den: u64,
+    cutoff: f64,
+    half_width: i64,
+    /// Kernels for each phase, unless there are too many phases to precompute
+    table: Option<Vec<Vec<f32>>>,
+    /// Input still needed by upcoming output frames, starting at input frame `start`
+    input: Vec<f32>,
+    start: u64,
+    received: u64,
+    produced: u64,
+}
+
+impl Resampler
+{
+    pub fn new(channels: u16, from: u32, to: u32) -> Self
+    {
+        const HALF_TAPS: f64 = 32.0;
+
+        let passthrough = from == to || from == 0 || to == 0;
+        let divisor = if passthrough { 1 } else { gcd(from, to) };
+        let cutoff = if passthrough { 1.0 } else { (to as f64 / from as f64).min(1.0) };
+        let mut resampler = Self
+        {
+            channels: channels.max(1) as usize,
+            from,
+            to,
+            num: (from / divisor.max(1)) as u64,
+            den: (to / divisor.max(1)) as u64,
+            cutoff,
+            half_width: (HALF_TAPS / cutoff).ceil() as i64,
+            table: None,
+            input: Vec::new(),
+            start: 0,
+            received: 0,
+            produced: 0,
+        };
+        if !passthrough && resampler.den <= RESAMPLE_MAX_PHASES
+        {
+            resampler.table = Some((0..resampler.den).map(|phase| resampler.kernel(phase)).collect());
+        }
+        resampler
+    }
+
+    /// Whether input is passed through unchanged (equal rates, or a rate of 0)
+    pub fn is_passthrough(&self) -> bool
+    {
+        self.from == self.to || self.from == 0 || self.to == 0
+    }
+
+    /// Resample the next block of interleaved input, appending every output frame it completes to `out`
+    /// Output lags the input by the filter's half-width until [`finish`](Self::finish)
+    pub fn process(&mut self, samples: &[f32], out: &mut Vec<f32>)
+    {
+        if self.is_passthrough()
+        {
+            out.extend_from_slice(samples);
+            return;
+        }
+
+        let frames = samples.len() / self.channels;
+        self.input.extend_from_slice(&samples[..frames * self.channels]);
+        self.received += frames as u64;
+
+        // Stop at the first output frame whose kernel reaches past the input received so far
+        while ((self.produced * self.num / self.den) as i64 + self.half_width) < self.received as i64
+        {
+            self.produce(out);
+        }
+
+        // Drop input no later output frame will reach
+        let first_needed = ((self.produced * self.num / self.den) as i64 - self.half_width + 1).max(0) as u64;
+        if first_needed > self.start
+        {
+            let drop = (first_needed - self.start).min((self.input.len() / self.channels) as u64) as usize;
+            self.input.drain(..drop * self.channels);
+            self.start += drop as u64;
+        }
+    }
+
+    /// Append the remaining output frames, treating the input as silent past its end, and start
+    /// over as if newly created
+    pub fn finish(&mut self, out: &mut Vec<f32>)
+    {
+        if !self.is_passthrough()
+        {
+            let total = (self.received * self.to as u64 + self.from as u64 / 2) / self.from as u64;
+            while self.produced < total
+            {
+                self.produce(out);
+            }
+        }
+
+        self.input.clear();
+        self.start = 0;
+        self.received = 0;
+        self.produced = 0;
+    }
+
+    /// Compute the next output frame from the buffered input
+    fn produce(&mut self, out: &mut Vec<f32>)
+    {
+        let ch = self.channels;
+        let position = self.produced * self.num;
+        let (base, phase) = ((position / self.den) as i64, position % self.den);
+        let computed;
+        let weights = match &self.table
+        {
+            Some(table) => &table[phase as usize],
+            None =>
+            {
+                computed = self.kernel(phase);
+                &computed
+            }
+        };
+
+        let offset = out.len();
+        out.resize(offset + ch, 0.0);
+        let first = base - self.half_width + 1;
+        for (k, &w) in weights.iter().enumerate()
+        {
+            let i = first + k as i64;
+            if i < self.start as i64 || i >= self.received as i64
+            {
+                continue;
+            }
+            let index = (i as u64 - self.start) as usize * ch;
+            for (o, &s) in out[offset..].iter_mut().zip(&self.input[index..index + ch])
+            {
+                *o += w * s;
+            }
+        }
+        self.produced += 1;
+    }
+
+    /// Windowed-sinc weights for an output frame `phase / den` of the way between two input frames
+    fn kernel(&self, phase: u64) -> Vec<f32>
+    {
+        let frac = phase as f64 / self.den as f64;
+        let taps = 2 * self.half_width;
+        let weights: Vec<f64> = (0..taps).map(|k|
+        {
+            let d = (k - self.half_width + 1) as f64 - frac;
+            let x = std::f64::consts::PI * self.cutoff * d;
+            let sinc = if x == 0.0 { 1.0 } else { x.sin() / x };
+            let t = std::f64::consts::PI * d / self.half_width as f64;
+            sinc * (0.42 + 0.5 * t.cos() + 0.08 * (2.0 * t).cos())
+        }).collect();
+        // Unity gain at DC
+        let sum: f64 = weights.iter().sum();
+        weights.iter().map(|w| (w / sum) as f32).collect()
+    }
+}
+
+/// Converts interleaved blocks of any sample rate and channel count to one output format, so tracks
+/// that differ can be played as one continuous stream
+///
+/// Resampler state carries over between blocks, and between tracks at the same sample rate, so
+/// consecutive blocks join without gaps or clicks
+pub struct FormatConverter
+{
+    sample_rate: u32,
+    channels: u16,
+    /// Input rate the resampler is set up for
+    input_rate: u32,
+    resampler: Resampler,
+}
+
+impl FormatConverter
+{
+    pub fn new(sample_rate: u32, channels: u16) -> Self
+    {
+        Self
+        {
+            sample_rate,
+            channels,
+            input_rate: sample_rate,
+            resampler: Resampler::new(channels, sample_rate, sample_rate),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32
+    {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u16
+    {
+        self.channels
+    }
+
+    /// Convert a block of interleaved `channels`-channel audio at `sample_rate`, appending the result to `out`
+    /// Mono is copied to every output channel and anything is averaged down to mono; otherwise
+    /// channels are kept in order, with extra ones dropped or missing ones left silent
+    pub fn push(&mut self, samples: &[f32], sample_rate: u32, channels: u16, out: &mut Vec<f32>)
+    {
+        if sample_rate != self.input_rate
+        {
+            self.resampler.finish(out);
+            self.resampler = Resampler::new(self.channels, sample_rate, self.sample_rate);
+            self.input_rate = sample_rate;
+        }
+
+        if channels == self.channels
+        {
+            self.resampler.process(samples, out);
+            return;
+        }
+
+        let (from, to) = (channels.max(1) as usize, self.channels.max(1) as usize);
+        let mut mapped = Vec::with_capacity(samples.len() / from * to);
+        for frame in samples.chunks_exact(from)
+        {
+            if from == 1
+            {
+                mapped.resize(mapped.len() + to, frame[0]);
+            }
+            else if to == 1
+            {
+                mapped.push(frame.iter().sum::<f32>() / from as f32);
+            }
+            else
+            {
+                mapped.extend((0..to).map(|c| frame.get(c).copied().unwrap_or(0.0)));
+            }
+        }
+        self.resampler.process(&mapped, out);
+    }
+
+    /// Append the resampler's remaining output at the end of the stream
+    pub fn finish(&mut self, out: &mut Vec<f32>)
+    {
+        self.resampler.finish(out);
+    }
+
+    /// Drop any input still held by the resampler, e.g. after seeking
+    pub fn reset(&mut self)
+    {
+        self.resampler.finish(&mut Vec::new());
+    }
+}
+
+fn gcd(mut a: u32, mut b: u32) -> u32
+{
+    while b != 0
+    {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Bitrate setting for MP3 export
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mp3Mode
+{
+    /// Constant bitrate in kbit/s, one of [`Mp3Mode::BITRATES`]
+    Cbr(u32),
+    /// Variable bitrate at LAME's `-V` quality, 0 (best) to 9 (smallest)
+    Vbr(u8),
+}
+
+impl Default for Mp3Mode
+{
+    fn default() -> Self
+    {
+        Mp3Mode::Vbr(2)
+    }
+}
+
+impl Mp3Mode
+{
+    /// Bitrates allowed for [`Mp3Mode::Cbr`]
+    pub const BITRATES: [u32; 13] = [32, 40, 48, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+
+    /// Parse a constant bitrate in kbit/s (`192`, `192k`) or a VBR quality (`V0` to `V9`)
+    pub fn parse(text: &str) -> Result<Self>
+    {
+        let text = text.trim();
+        if let Some(quality) = text.strip_prefix(['V', 'v'])
+        {
+            return quality.parse::<u8>()
+                          .ok()
+                          .filter(|q| *q <= 9)
+                          .map(Mp3Mode::Vbr)
+                          .ok_or_else(|| anyhow!("Invalid VBR quality '{}': expected V0 to V9", text));
+        }
+
+        let kbps = text.trim_end_matches(['k', 'K']).parse::<u32>().ok();
+        match kbps
+        {
+            Some(kbps) if Self::BITRATES.contains(&kbps) => Ok(Mp3Mode::Cbr(kbps)),
+            _ => Err(anyhow!("Invalid MP3 bitrate '{}': expected one of {:?} kbit/s or V0 to V9", text, Self::BITRATES)),
+        }
+    }
+}
+
+impl std::fmt::Display for Mp3Mode
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            Mp3Mode::Cbr(kbps) => write!(f, "{} kbit/s", kbps),
+            Mp3Mode::Vbr(quality) => write!(f, "V{} (VBR)", quality),
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! Encode several audio files concurrently
+//! Files are scheduled across the rayon pool when the `parallel` feature is enabled
+//! (each encode also parallelizes its own frames), and run one after another otherwise
+use anyhow::Result;
+use crossbeam_channel::Sender;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use crate::audio::{encode_lossless_file_with, EncodeOptions};
+use crate::codec::Progress;
+
+/// Sizes of a successfully encoded file
+pub struct EncodedFile
+{
+    pub input_size: u64,
+    pub output_size: u64,
+    /// Gain applied by normalization, in dB
+    pub gain_db: Option<f64>,
+}
+
+/// Outcome of one file in a batch
+pub struct BatchResult
+{
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub result: Result<EncodedFile>,
+}
+
+/// Queue of files to encode concurrently
+#[derive(Default)]
+pub struct BatchEncoder
+{
+    jobs: Vec<(PathBuf, PathBuf)>,
+    options: EncodeOptions,
+}
+
+impl BatchEncoder
+{
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    /// Queue `input` (WAV or FLAC) to be encoded to `output`
+    pub fn add(&mut self, input: impl Into<PathBuf>, output: impl Into<PathBuf>) -> &mut Self
+    {
+        self.jobs.push((input.into(), output.into()));
+        self
+    }
+
+    /// Queue `input`, writing the `.glc` file next to it
+    pub fn add_file(&mut self, input: impl Into<PathBuf>) -> &mut Self
+    {
+        let input = input.into();
+        let output = input.with_extension("glc");
+        self.add(input, output)
+    }
+
+    /// Encode every file with `options`
+    pub fn options(&mut self, options: EncodeOptions) -> &mut Self
+    {
+        self.options = options;
+        self
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.jobs.is_empty()
+    }
+
+    /// Encode every queued file, returning one result per file in the order they were added
+    /// `progress_sender` receives a `Status` message and an overall `Encoding` percentage
+    /// as each file finishes, then `Complete` once the whole batch is done
+    pub fn run(&self, progress_sender: Option<Sender<Progress>>) -> Vec<BatchResult>
+    {
+        let total = self.jobs.len();
+        let finished = AtomicUsize::new(0);
+
+        let encode_job = |(input, output): &(PathBuf, PathBuf)| -> BatchResult
+        {
+            let result = encode_one(input, output, &self.options);
+
+            let done = finished.fetch_add(1, Ordering::Relaxed) + 1;
+            if let Some(ref s) = progress_sender
+            {
+                let name = input.file_name().unwrap_or_default().to_string_lossy();
+                let message = match result
+                {
+                    Ok(_) => format!("[{}/{}] Encoded {}", done, total, name),
+                    Err(ref e) => format!("[{}/{}] Failed {}: {}", done, total, name, e),
+                };
+                let _ = s.send(Progress::Status(message));
+                let _ = s.send(Progress::Encoding(done as f32 / total as f32 * 100.0));
+            }
+
+            BatchResult
+            {
+                input: input.clone(),
+                output: output.clone(),
+                result,
+            }
+        };
+
+        #[cfg(feature = "parallel")]
+        let results: Vec<BatchResult> = self.jobs.par_iter().map(encode_job).collect();
+        #[cfg(not(feature = "parallel"))]
+        let results: Vec<BatchResult> = self.jobs.iter().map(encode_job).collect();
+
+        if let Some(ref s) = progress_sender
+        {
+            let failed = results.iter().filter(|r| r.result.is_err()).count();
+            let _ = s.send(Progress::Complete(format!("Encoded {} of {} files", total - failed, total)));
+        }
+
+        results
+    }
+}
+
+fn encode_one(input: &Path, output: &Path, options: &EncodeOptions) -> Result<EncodedFile>
+{
+    let gain_db = encode_lossless_file_with(input, output, options)?;
+
+    Ok(EncodedFile
+    {
+        input_size: std::fs::metadata(input)?.len(),
+        output_size: std::fs::metadata(output)?.len(),
+        gain_db,
+    })
+}
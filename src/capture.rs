@@ -0,0 +1,148 @@
+//! Saving the audio being played to a file as it plays ("record what you hear")
+//!
+//! A [`Capture`] takes blocks of output samples (after ReplayGain, tempo, EQ, and volume) from the
+//! audio thread without blocking it, and a writer thread saves them: streamed to a 32-bit float WAV,
+//! which holds them exactly, or gathered up and written as a 16-bit FLAC once the capture ends.
+//! Listening back to the capture shows whether track changes really were seamless at the output.
+use anyhow::{anyhow, Result};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+
+/// File format of a capture, chosen by its extension
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaptureFormat
+{
+    Wav,
+    Flac,
+}
+
+impl CaptureFormat
+{
+    pub fn from_path(path: &Path) -> Result<Self>
+    {
+        match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref()
+        {
+            Some("wav") => Ok(Self::Wav),
+            Some("flac") => Ok(Self::Flac),
+            _ => Err(anyhow!("Can't capture to {}: expected a .wav or .flac file", path.display())),
+        }
+    }
+}
+
+/// What a finished [`Capture`] wrote
+#[derive(Clone, Debug, PartialEq)]
+pub struct CaptureSummary
+{
+    pub path: PathBuf,
+    /// Samples per channel
+    pub frames: u64,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl CaptureSummary
+{
+    pub fn duration(&self) -> f64
+    {
+        self.frames as f64 / self.sample_rate.max(1) as f64
+    }
+}
+
+/// Output samples in the format they were played in
+struct CaptureBlock
+{
+    samples: Vec<f32>,
+    sample_rate: u32,
+    channels: u16,
+}
+
+/// A file being written from played audio on a thread of its own
+pub struct Capture
+{
+    sender: Sender<CaptureBlock>,
+    writer: JoinHandle<Result<CaptureSummary>>,
+}
+
+impl Capture
+{
+    /// Start capturing to `path` (a `.wav` or `.flac` file), which is created straight away so a
+    /// bad path shows up now rather than when playback starts
+    pub fn start(path: &Path) -> Result<Self>
+    {
+        let format = CaptureFormat::from_path(path)?;
+        File::create(path).map_err(|e| anyhow!("Can't capture to {}: {}", path.display(), e))?;
+
+        let (sender, receiver) = unbounded();
+        let path = path.to_path_buf();
+        let writer = thread::spawn(move || write_capture(&path, format, receiver));
+        Ok(Self { sender, writer })
+    }
+
+    /// Hand a block of played samples to the writer; never blocks
+    pub fn send(&self, samples: Vec<f32>, sample_rate: u32, channels: u16)
+    {
+        // If the writer has given up, its error comes out of `finish`
+        self.sender.send(CaptureBlock { samples, sample_rate, channels }).ok();
+    }
+
+    /// Write out what has been sent and close the file
+    pub fn finish(self) -> Result<CaptureSummary>
+    {
+        drop(self.sender);
+        self.writer.join().map_err(|_| anyhow!("The capture writer panicked"))?
+    }
+}
+
+/// Write blocks from `receiver` to `path` until the sender hangs up
+fn write_capture(path: &Path, format: CaptureFormat, receiver: Receiver<CaptureBlock>) -> Result<CaptureSummary>
+{
+    let Ok(first) = receiver.recv()
+    else
+    {
+        std::fs::remove_file(path).ok();
+        return Err(anyhow!("Nothing played while capturing to {}", path.display()));
+    };
+    let (sample_rate, channels) = (first.sample_rate, first.channels);
+
+    let mut wav = match format
+    {
+        CaptureFormat::Wav =>
+        {
+            let spec = hound::WavSpec { channels, sample_rate, bits_per_sample: 32, sample_format: hound::SampleFormat::Float };
+            Some(hound::WavWriter::create(path, spec)?)
+        }
+        CaptureFormat::Flac => None,
+    };
+    let mut gathered = Vec::new();
+    let mut samples = 0u64;
+    for block in std::iter::once(first).chain(receiver)
+    {
+        // One file has one format; a playlist in another ends the capture
+        if (block.sample_rate, block.channels) != (sample_rate, channels)
+        {
+            return Err(anyhow!("Playback changed from {} Hz, {} channels to {} Hz, {} channels; capture stopped",
+                               sample_rate, channels, block.sample_rate, block.channels));
+        }
+        samples += block.samples.len() as u64;
+        match wav
+        {
+            Some(ref mut writer) =>
+            {
+                for &sample in &block.samples
+                {
+                    writer.write_sample(sample)?;
+                }
+            }
+            None => gathered.extend_from_slice(&block.samples),
+        }
+    }
+
+    match wav
+    {
+        Some(writer) => writer.finalize()?,
+        None => crate::audio::export_to_flac(path, &gathered, sample_rate, channels)?,
+    }
+    Ok(CaptureSummary { path: path.to_path_buf(), frames: samples / channels.max(1) as u64, sample_rate, channels })
+}
@@ -1,29 +1,109 @@
 //! Lossy codec with MDCT, psychoacoustic masking, and gapless playback
-//! - Precomputed cosine table
-//! - Parallel encode and batch-parallel decode (rayon)
+//! - MDCT via TDAC folding + DCT-IV over a compact precomputed cosine table,
+//!   shared by all encoders/decoders with the same frame size
+//! - Parallel encode and batch-parallel decode (rayon, behind the `parallel` feature)
 //! - Proper multichannel storage: per-frame, per-channel coeffs & scales
 //! - Matching normalization on MDCT and IMDCT
 //! - Preserves gapless playback via Overlap-Add
-use anyhow::Result;
+//! - 1024-sample hop by default, or a shorter one for low-delay streams
+//! - Optional long-term prediction of each frame from audio decoded a few frames earlier
+//! - Optional bandwidth extension: the band above a crossover kept only as a coarse envelope
+//! - Optional adaptive quality: masking thresholds scaled per frame by its perceptual entropy
+//! - Optional coefficient budget: a fixed number of the largest coefficients kept per frame
+//! - Optional noise shaping: each bin's coding error fed into the next bin's quantization decision
+//! - Adaptive quality, the coefficient budget, and noise shaping only change which coefficients
+//!   are kept, so streams encoded with them are in the same format as without
+use anyhow::{anyhow, Result};
 use serde::{Serialize, Deserialize};
 use std::f32::consts::PI;
-use crossbeam_channel::{Sender, Receiver, bounded};
+use crossbeam_channel::Sender;
+#[cfg(not(target_arch = "wasm32"))]
+use crossbeam_channel::{Receiver, bounded};
+#[cfg(not(target_arch = "wasm32"))]
 use std::time::Instant;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
 
-const FRAME_SIZE: usize = 2048;  // 2N (samples per MDCT block)
-const HOP_SIZE: usize = 1024;    // N (hop, 50% overlap)
+pub(crate) const HOP_SIZE: usize = 1024;    // N (hop, 50% overlap); frames span 2N samples
+/// Hop of low-delay streams (see [`Encoder::with_hop_size`]): a quarter of the default, so each
+/// frame spans 512 samples, about 11 ms at 48 kHz
+pub const LOW_DELAY_HOP_SIZE: usize = 256;
+/// Smallest hop a stream may use; hops are powers of two from this up to the default 1024
+const MIN_HOP_SIZE: usize = 64;
+/// Most channels a stream may have (enough for 22.2 surround), so a corrupt header can't demand huge buffers
+pub const MAX_CHANNELS: u16 = 32;
+/// Highest sample rate a stream may have, in Hz
+pub const MAX_SAMPLE_RATE: u32 = 768_000;
 const QUANTIZATION_BITS: u32 = 16;
+
+/// Position of a kept MDCT coefficient in its frame, one of the hop size's bins
+pub type CoefficientIndex = u16;
+/// A coefficient quantized to `QUANTIZATION_BITS` bits, relative to its channel's scale factor
+pub type QuantizedCoefficient = i16;
+
+// The largest hop must have every bin addressable, and quantized values must fit their field,
+// or frames would silently wrap; widen the types (a format change) before raising either
+const _: () = assert!(HOP_SIZE - 1 <= CoefficientIndex::MAX as usize, "hop size too large for coefficient indices");
+const _: () = assert!(QUANTIZATION_BITS <= QuantizedCoefficient::BITS, "quantization too fine for quantized coefficients");
+const _: () = assert!(MAX_QUANTIZATION_BITS <= QUANTIZATION_BITS, "quantization too fine for quantized coefficients");
 const FRAMES_PER_CHUNK: usize = 500;
+const STREAM_CHANNEL_DEPTH: usize = 5;  // chunks buffered ahead of the consumer
 const DECODE_BATCH: usize = 32;  // how many frames to decode in parallel per batch
+#[cfg(not(target_arch = "wasm32"))]
+const DECODE_LOOKAHEAD: usize = 2;  // batches decoded ahead of overlap-add in streaming decode
 
 // Lossy compression parameters
 const NOISE_FLOOR_DB: f32 = -48.0;
-const QUALITY_FACTOR: f32 = 0.7;     // Lower = more aggressive compression (0.1-1.0)
+/// Default for [`Encoder::set_quality`]
+pub const DEFAULT_QUALITY: f32 = 0.7;
+/// Range accepted by [`Encoder::set_quality`]; lower = more aggressive compression
+pub const QUALITY_RANGE: std::ops::RangeInclusive<f32> = 0.1..=1.0;
+
+/// Lowest cutoff [`Encoder::set_cutoff`] accepts, in Hz
+pub const MIN_CUTOFF: f32 = 1000.0;
+/// Cutoffs [`Encoder::set_highpass`] accepts, in Hz: high enough to remove DC offset and rumble,
+/// low enough to leave all audible bass alone
+pub const HIGHPASS_RANGE: std::ops::RangeInclusive<f32> = 5.0..=20.0;
+/// Crossovers [`Encoder::set_bandwidth_extension`] accepts, in Hz: low enough to save space on
+/// speech, high enough that the band below still holds its formants
+pub const BANDWIDTH_EXTENSION_RANGE: std::ops::RangeInclusive<f32> = 4000.0..=16000.0;
+/// Target bitrates [`Encoder::set_bitrate`] accepts, in kbit/s; the lowest is enough for stereo at
+/// the default hop up to 48 kHz, while other streams may need more (see [`Encoder::min_bitrate`])
+pub const BITRATE_RANGE: std::ops::RangeInclusive<u32> = 32..=1536;
+/// Bands a [`SpectralEnvelope`] splits the spectrum above its crossover into, evenly
+pub const ENVELOPE_BANDS: usize = 8;
+/// Envelope levels are in steps of this many dB below the channel's scale factor
+const ENVELOPE_STEP_DB: f32 = 1.5;
+/// Envelope level of a silent band
+const ENVELOPE_SILENT: u8 = u8::MAX;
+/// With adaptive quality, a frame's masking thresholds are scaled by the ratio of the average
+/// perceptual entropy to its own raised to this power, within [`ADAPTIVE_MAX_SCALE`] either way
+const ADAPTIVE_STRENGTH: f32 = 0.5;
+const ADAPTIVE_MAX_SCALE: f32 = 2.0;
+/// With noise shaping, the share of the error power carried from one bin to the next (see [`compress_coefficients`])
+const NOISE_SHAPING_FEEDBACK: f32 = 0.5;
+/// Frames the average perceptual entropy is taken over (an exponential moving average once the
+/// stream is longer than this)
+const ADAPTIVE_AVERAGE_FRAMES: f32 = 128.0;
 const MIN_QUANTIZATION_BITS: u32 = 8;  // Use fewer bits for less important coefficients
 const MAX_QUANTIZATION_BITS: u32 = 16;  // Full resolution for important coefficients
 
+/// Gains a long-term prediction can scale the delayed signal by (the AAC-LTP codebook)
+pub const LTP_GAINS: [f32; 8] = [0.570829, 0.696616, 0.813004, 0.911304, 0.984900, 1.067894, 1.194601, 1.369533];
+/// Bands each channel switches long-term prediction on or off in, splitting the lower half of the
+/// spectrum evenly; the upper half is never predicted
+pub const LTP_BANDS: usize = 16;
+/// Frames from one prediction restart point to the next in an encoder's output: a frame at a
+/// restart point carries no prediction and clears the history, so decoding can start there
+pub const LTP_RESTART_INTERVAL: usize = 64;
+/// A band is predicted only if that leaves at most this fraction of its energy to code
+const LTP_MAX_RESIDUAL: f32 = 0.5;
+/// Decimation of the coarse lag search, and how many of its best lags are refined at full rate
+const LTP_SEARCH_DECIMATION: usize = 4;
+const LTP_SEARCH_CANDIDATES: usize = 3;
+
 // Per-frame compression threshold
 // If compressed frame would be >= this fraction of raw PCM size, use raw PCM
 const COMPRESSION_THRESHOLD: f32 = 0.85;
@@ -42,6 +122,124 @@ pub struct AudioHeader
     pub sample_rate: u32,
     pub channels: u16,
     pub total_samples: u64,
+    /// Samples per channel each frame advances by; frames span twice this
+    /// Kept in the container's stream header rather than serialized here, so footers stay the same
+    #[serde(skip, default = "default_hop_size")]
+    pub hop_size: u32,
+    /// Whether frames may carry long-term prediction (see [`Encoder::set_long_term_prediction`])
+    /// Implied by the container's format version rather than serialized here, like the hop size
+    #[serde(skip)]
+    pub long_term_prediction: bool,
+    /// Cutoff of the high-pass filter applied before encoding, in Hz (see [`Encoder::set_highpass`])
+    /// Appended to the container's footer rather than serialized here, like the hop size
+    #[serde(skip)]
+    pub highpass: Option<f32>,
+    /// Crossover above which frames keep only a spectral envelope, in Hz (see
+    /// [`Encoder::set_bandwidth_extension`]); appended to the footer like the high-pass cutoff
+    #[serde(skip)]
+    pub bandwidth_extension: Option<f32>,
+}
+
+fn default_hop_size() -> u32
+{
+    HOP_SIZE as u32
+}
+
+/// Why a stream's header was turned away on load
+/// Loading functions return these inside their `anyhow::Error`; match on one with
+/// `error.downcast_ref::<GlcError>()`
+#[derive(Debug, Clone, PartialEq)]
+pub enum GlcError
+{
+    /// A container format version this build can't read
+    UnsupportedVersion(u16),
+    /// No channels, or more than [`MAX_CHANNELS`]
+    UnsupportedChannels(u16),
+    /// 0 Hz, or faster than [`MAX_SAMPLE_RATE`]
+    UnsupportedSampleRate(u32),
+    /// A hop size other than a power of two from 64 to 1024
+    UnsupportedHopSize(usize),
+    /// The footer describes a different stream than the stream header
+    HeaderMismatch,
+    /// The interleaved length is not a whole number of samples per channel
+    RaggedLength { length: u64, channels: u16 },
+    /// The encoder delay and length (samples per channel) need more audio than the frames hold
+    LengthMismatch { delay: u32, length: u64, frames: usize, hop_size: u32 },
+}
+
+impl std::fmt::Display for GlcError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            GlcError::UnsupportedVersion(version) =>
+                write!(f, "Unsupported GLC format version {} (this build reads up to {})", version, crate::container::FORMAT_VERSION),
+            GlcError::UnsupportedChannels(channels) =>
+                write!(f, "Unsupported channel count {} (expected 1 to {})", channels, MAX_CHANNELS),
+            GlcError::UnsupportedSampleRate(sample_rate) =>
+                write!(f, "Unsupported sample rate {} Hz (expected 1 to {})", sample_rate, MAX_SAMPLE_RATE),
+            GlcError::UnsupportedHopSize(hop_size) =>
+                write!(f, "Unsupported hop size {} (expected a power of two from {} to {})", hop_size, MIN_HOP_SIZE, HOP_SIZE),
+            GlcError::HeaderMismatch => write!(f, "GLC footer does not match the stream header"),
+            GlcError::RaggedLength { length, channels } =>
+                write!(f, "Header length of {} samples does not divide into {} channels", length, channels),
+            GlcError::LengthMismatch { delay, length, frames, hop_size } =>
+                write!(f, "Header claims {} samples per channel after a delay of {}, but {} frames of hop size {} hold at most {}",
+                       length, delay, frames, hop_size, (*frames as u64 + 1) * *hop_size as u64),
+        }
+    }
+}
+
+impl std::error::Error for GlcError {}
+
+/// Check that `hop_size` is one a stream can use: a power of two from 64 to 1024
+pub(crate) fn check_hop_size(hop_size: usize) -> Result<()>
+{
+    if hop_size.is_power_of_two() && (MIN_HOP_SIZE..=HOP_SIZE).contains(&hop_size)
+    {
+        Ok(())
+    }
+    else
+    {
+        Err(GlcError::UnsupportedHopSize(hop_size).into())
+    }
+}
+
+/// Check that a stream of `channels` channels at `sample_rate` is one a decoder can be set up for:
+/// 1 to [`MAX_CHANNELS`] channels at 1 Hz to [`MAX_SAMPLE_RATE`]
+pub(crate) fn check_stream_format(sample_rate: u32, channels: u16) -> Result<()>
+{
+    if channels == 0 || channels > MAX_CHANNELS
+    {
+        return Err(GlcError::UnsupportedChannels(channels).into());
+    }
+    if sample_rate == 0 || sample_rate > MAX_SAMPLE_RATE
+    {
+        return Err(GlcError::UnsupportedSampleRate(sample_rate).into());
+    }
+    Ok(())
+}
+
+/// Check a loaded header against the `frame_count` frames that follow it: its format as
+/// [`check_stream_format`] does, and a length that splits evenly into channels and that,
+/// with the encoder delay, fits in what the frames decode to
+pub(crate) fn check_header(header: &AudioHeader, gapless_info: &GaplessInfo, frame_count: usize) -> Result<()>
+{
+    check_stream_format(header.sample_rate, header.channels)?;
+    check_hop_size(header.hop_size as usize)?;
+    let length = gapless_info.original_length;
+    if !length.is_multiple_of(header.channels as u64)
+    {
+        return Err(GlcError::RaggedLength { length, channels: header.channels }.into());
+    }
+    let capacity = (frame_count as u64 + 1) * header.hop_size as u64;
+    let delay = gapless_info.encoder_delay;
+    if (length / header.channels as u64).checked_add(delay as u64).is_none_or(|needed| needed > capacity)
+    {
+        return Err(GlcError::LengthMismatch { delay, length: length / header.channels as u64, frames: frame_count, hop_size: header.hop_size }.into());
+    }
+    Ok(())
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -59,13 +257,121 @@ pub struct EncodedFrame
     /// Sparse representation: (index, value) pairs for non-zero coefficients
     /// Outer vec: channel index -> inner vec: sparse coefficient data
     /// Empty if raw_pcm is used
-    pub sparse_coeffs_per_channel: Vec<Vec<(u16, i16)>>,
+    pub sparse_coeffs_per_channel: Vec<Vec<(CoefficientIndex, QuantizedCoefficient)>>,
     /// scale factor per channel (empty if raw_pcm is used)
     pub scale_factors: Vec<f32>,
     /// Raw PCM data for this frame if compression is ineffective
-    /// Stores interleaved i16 samples for all channels
-    /// Length should be HOP_SIZE * channels
+    /// Stores the whole windowed frame (twice the hop size) as i16 samples for each channel in
+    /// turn, so its length is `2 * hop_size * channels`
     pub raw_pcm: Option<Vec<i16>>,
+    /// Long-term prediction per channel, None for channels coded without it
+    /// Empty for a frame that restarts prediction, as is every frame of a stream without it
+    /// Written by the container after the rest of the frame, so frames without it are unchanged
+    #[serde(skip)]
+    pub ltp: Vec<Option<LongTermPrediction>>,
+    /// The spectrum above the bandwidth-extension crossover, for a frame coded with one
+    /// Written by the container after the long-term predictions, so readers that predate it
+    /// decode the frame without the extended band
+    #[serde(skip)]
+    pub envelope: Option<SpectralEnvelope>,
+}
+
+/// How one channel of a frame is predicted from audio decoded before it: the `2 * hop` samples
+/// `lag` samples back, windowed and transformed like the frame, scaled by a gain and added to the
+/// decoded coefficients in the bands switched on
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct LongTermPrediction
+{
+    /// Samples per channel back from the frame, from two hops (the most recent audio the decoder
+    /// has in full) up to but not including four
+    pub lag: u16,
+    /// Index into [`LTP_GAINS`]
+    pub gain_index: u8,
+    /// Bit k set if band k of [`LTP_BANDS`] is predicted
+    pub bands: u16,
+}
+
+/// The spectrum of a frame above its bandwidth-extension crossover, kept as the level of each of
+/// [`ENVELOPE_BANDS`] bands rather than coefficient by coefficient; the decoder fills each band
+/// with the spectrum below the crossover, shifted up and scaled to its level
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SpectralEnvelope
+{
+    /// First bin filled in (the crossover)
+    pub start: CoefficientIndex,
+    /// Per channel, each band's RMS in 1.5 dB steps below the channel's scale factor, 255 for silence
+    pub levels: Vec<[u8; ENVELOPE_BANDS]>,
+}
+
+impl EncodedFrame
+{
+    /// Whether this frame restarts long-term prediction, i.e. decoding can begin here with a
+    /// cleared [`OverlapState`] and reproduce everything after this frame's own hop exactly
+    pub fn restarts_prediction(&self) -> bool
+    {
+        self.ltp.is_empty()
+    }
+
+    /// Check that this frame could belong to a stream of `channels` channels with hop size
+    /// `hop_size`, so corrupt or hostile input is turned away before it is decoded: raw PCM
+    /// holding a whole frame for every channel, or else one coefficient list and one finite scale
+    /// factor per channel, with every coefficient index inside the hop; any long-term
+    /// predictions one per channel, with lags and gains in range; and any spectral envelope
+    /// starting inside the hop, with levels for every channel
+    pub fn validate(&self, channels: usize, hop_size: usize) -> Result<()>
+    {
+        if let Some(envelope) = &self.envelope
+        {
+            if self.raw_pcm.is_some()
+            {
+                return Err(anyhow!("Raw PCM frame has a spectral envelope"));
+            }
+            if !(1..hop_size).contains(&(envelope.start as usize)) || envelope.levels.len() != channels
+            {
+                return Err(anyhow!("Invalid spectral envelope (from bin {}, {} channels)", envelope.start, envelope.levels.len()));
+            }
+        }
+        if !self.ltp.is_empty()
+        {
+            if self.ltp.len() != channels
+            {
+                return Err(anyhow!("Frame has {} long-term predictions for {} channels", self.ltp.len(), channels));
+            }
+            for ltp in self.ltp.iter().flatten()
+            {
+                if self.raw_pcm.is_some()
+                {
+                    return Err(anyhow!("Raw PCM frame has a long-term prediction"));
+                }
+                if !(2 * hop_size..4 * hop_size).contains(&(ltp.lag as usize)) || ltp.gain_index as usize >= LTP_GAINS.len()
+                {
+                    return Err(anyhow!("Invalid long-term prediction (lag {}, gain {})", ltp.lag, ltp.gain_index));
+                }
+            }
+        }
+        if let Some(pcm) = &self.raw_pcm
+        {
+            if pcm.len() != 2 * hop_size * channels
+            {
+                return Err(anyhow!("Frame holds {} PCM samples instead of a frame of {} channels", pcm.len(), channels));
+            }
+            return Ok(());
+        }
+        if self.sparse_coeffs_per_channel.len() != channels || self.scale_factors.len() != channels
+        {
+            return Err(anyhow!("Frame has {} coefficient lists and {} scale factors for {} channels",
+                               self.sparse_coeffs_per_channel.len(), self.scale_factors.len(), channels));
+        }
+        if let Some(scale) = self.scale_factors.iter().find(|scale| !scale.is_finite())
+        {
+            return Err(anyhow!("Frame has an invalid scale factor {}", scale));
+        }
+        if let Some(&(index, _)) = self.sparse_coeffs_per_channel.iter().flatten().find(|&&(index, _)| index as usize >= hop_size)
+        {
+            return Err(anyhow!("Coefficient index {} out of range (hop size {})", index, hop_size));
+        }
+        Ok(())
+    }
 }
 
 pub enum Progress 
@@ -84,6 +390,42 @@ pub struct AudioChunk
     pub is_last: bool,
 }
 
+/// Chunking parameters for [`Decoder::decode_streaming_with`]
+#[derive(Clone, Copy, Debug)]
+pub struct StreamOptions
+{
+    /// Frames (of HOP_SIZE samples per channel) per emitted chunk
+    pub frames_per_chunk: usize,
+    /// Number of chunks the decode thread may run ahead of the consumer
+    pub channel_depth: usize,
+}
+
+impl Default for StreamOptions
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            frames_per_chunk: FRAMES_PER_CHUNK,
+            channel_depth: STREAM_CHANNEL_DEPTH,
+        }
+    }
+}
+
+impl StreamOptions
+{
+    /// Options producing chunks of roughly `duration` (rounded up to whole frames)
+    pub fn with_chunk_duration(duration: std::time::Duration, sample_rate: u32) -> Self
+    {
+        let samples = (duration.as_secs_f64() * sample_rate as f64).ceil() as usize;
+        Self
+        {
+            frames_per_chunk: samples.div_ceil(HOP_SIZE).max(1),
+            ..Self::default()
+        }
+    }
+}
+
 //
 // Lossy compression helpers
 //
@@ -267,57 +609,113 @@ fn compute_quantization_bits_fast(
 }
 
 /// Apply noise floor and return sparse representation with fixed quantization denominator
+/// `coeffs` are quantized relative to `scale`; the thresholds and noise floor are relative to
+/// `reference`, the frame's own peak, which differs from `scale` when coding a prediction residual
+/// With a nonzero `feedback`, each bin's coding error power (its whole value if dropped), relative
+/// to what its threshold allows, is carried in that proportion into the decision on the next bin,
+/// up to the end of the critical band that starts at one of `band_edges`: bins each quiet enough
+/// to drop are dropped only until together they would stand out above the band's masking level
 fn compress_coefficients(
     coeffs: &[f32],
     scale: f32,
+    reference: f32,
     thresholds: &[f32],
     noise_floor_db: f32,
-) -> Vec<(u16, i16)>
+    feedback: f32,
+    band_edges: &[usize],
+) -> Vec<(CoefficientIndex, QuantizedCoefficient)>
 {
-    let noise_floor_linear = 10.0_f32.powf(noise_floor_db / 20.0) * scale;
+    // Bins past the index range would wrap around onto others
+    assert!(coeffs.len() <= CoefficientIndex::MAX as usize + 1, "{} bins overflow coefficient indices", coeffs.len());
+    let noise_floor_linear = 10.0_f32.powf(noise_floor_db / 20.0) * reference;
     let global_max = coeffs.iter().map(|x| x.abs()).fold(0.0f32, f32::max).max(1e-10);
 
     // We use (1 << (QUANTIZATION_BITS-1)) to leave room for sign.
     let max_q = (1u32 << (QUANTIZATION_BITS - 1)) as f32;
 
     let mut sparse = Vec::with_capacity(coeffs.len() / 4);
+    // Error power carried over from the bins before in the band, relative to what each allows
+    let mut carried = 0.0f32;
 
     for (k, &coeff) in coeffs.iter().enumerate()
     {
-        let abs_val = coeff.abs();
-        let threshold = thresholds[k] * scale;
+        if band_edges.binary_search(&k).is_ok()
+        {
+            carried = 0.0;
+        }
+        let threshold = thresholds[k] * reference;
+        let allowed = threshold.max(noise_floor_linear).max(1e-10);
+        // Decide on the bin as though it held the error carried into it as well
+        let abs_val = if carried > 0.0 { (coeff * coeff + carried * allowed * allowed).sqrt() } else { coeff.abs() };
+        let mut decoded = 0.0;
 
         // Keep coefficient if above noise floor AND above perceptual threshold
         if abs_val > noise_floor_linear && abs_val > threshold
+            && compute_quantization_bits_fast(abs_val, threshold, global_max) > 0
         {
-            let importance_bits = compute_quantization_bits_fast(abs_val, threshold, global_max);
-            if importance_bits == 0
-            {
-                continue;
-            }
-
             let normalized = coeff / scale;
             let quantized = (normalized * max_q).round();
-            let q = quantized.clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+            let q = quantized.clamp(QuantizedCoefficient::MIN as f32, QuantizedCoefficient::MAX as f32) as QuantizedCoefficient;
 
             if q != 0
             {
-                sparse.push((k as u16, q));
+                sparse.push((k as CoefficientIndex, q));
+                decoded = q as f32 / max_q * scale;
             }
         }
+
+        // Capped at one bin's allowance, so a burst of error can't force a run of bins to be kept
+        if feedback > 0.0
+        {
+            carried = (feedback * (carried + ((coeff - decoded) / allowed).powi(2))).min(1.0);
+        }
     }
 
     sparse
 }
 
+/// Quantize the `budget` coefficients whose magnitudes are largest once multiplied by their
+/// perceptual `weights`, relative to `scale`, and drop the rest; ties go to the lower bin, so the
+/// choice is the same on every run
+fn keep_largest(
+    coeffs: &[f32],
+    scale: f32,
+    budget: usize,
+    weights: &[f32],
+) -> Vec<(CoefficientIndex, QuantizedCoefficient)>
+{
+    assert!(coeffs.len() <= CoefficientIndex::MAX as usize + 1, "{} bins overflow coefficient indices", coeffs.len());
+    let max_q = (1u32 << (QUANTIZATION_BITS - 1)) as f32;
+    let weighted = |k: usize| coeffs[k].abs() * weights[k];
+
+    let mut kept: Vec<usize> = (0..coeffs.len()).filter(|&k| coeffs[k] != 0.0).collect();
+    if kept.len() > budget
+    {
+        kept.select_nth_unstable_by(budget, |&a, &b| weighted(b).total_cmp(&weighted(a)).then(a.cmp(&b)));
+        kept.truncate(budget);
+        kept.sort_unstable();
+    }
+
+    kept.into_iter().filter_map(|k|
+    {
+        let q = (coeffs[k] / scale * max_q).round()
+                                          .clamp(QuantizedCoefficient::MIN as f32, QuantizedCoefficient::MAX as f32) as QuantizedCoefficient;
+        (q != 0).then_some((k as CoefficientIndex, q))
+    }).collect()
+}
+
 /// Pre-computed tables for Modified Discrete Cosine Transform (MDCT)
 /// See [https://en.wikipedia.org/wiki/Modified_discrete_cosine_transform]
+///
+/// The MDCT of a 2N block is computed as a TDAC fold down to N samples followed by a DCT-IV.
+/// Every DCT-IV kernel value cos(π/N·(n+½)(k+½)) equals cos(π·m/4N) with m = (2n+1)(2k+1) mod 8N,
+/// so a single 8N-entry table replaces the former N×2N one (32 KB instead of 8 MB for N = 1024).
 #[derive(Clone)]
-struct MdctTables 
+pub(crate) struct MdctTables 
 {
-    cos_table: Arc<Vec<f32>>, // length = N * FRAME_SIZE
-    window: Arc<Vec<f32>>,    // length = FRAME_SIZE
-    n: usize,                 // HOP_SIZE
+    cos_table: Arc<Vec<f32>>, // length = 8N, cos(π·m/4N)
+    window: Arc<Vec<f32>>,    // length = 2N
+    n: usize,                 // hop size
     norm: f32,                // normalization factor sqrt(2/N)
 }
 
@@ -325,20 +723,15 @@ impl MdctTables
 {
     fn new(n: usize) -> Self 
     {
-        // Pre-compute angles for cosine term
-        let block = FRAME_SIZE;
-        let mut table = Vec::with_capacity(n * block);
-        for k in 0..n 
-        {
-            for i in 0..block 
-            {
-                let angle = PI / (n as f32) * (i as f32 + 0.5 + (n as f32) / 2.0) * (k as f32 + 0.5);
-                table.push(angle.cos());
-            }
-        }
+        // Pre-compute one full period of the DCT-IV kernel (in f64 for accuracy)
+        let period = 8 * n;
+        let table = (0..period)
+            .map(|m| (std::f64::consts::PI * m as f64 / (4 * n) as f64).cos() as f32)
+            .collect();
 
-        // Use sine window function with FRAME_SIZE as the window length
+        // Use sine window function with the whole frame (2N) as the window length
         // (this avoids discontinuities at the frame boundaries)
+        let block = 2 * n;
         let window = (0..block)
             .map(|i| (PI * (i as f32 + 0.5) / (block as f32)).sin())
             .collect();
@@ -355,39 +748,174 @@ impl MdctTables
         }
     }
 
-    /// Modified Discrete Cosine Transform: block len FRAME_SIZE -> N coeffs
-    fn mdct_block(&self, block: &[f32], out: &mut [f32]) 
+    /// Tables for hop size `n`, computed on first use and shared by every
+    /// encoder and decoder afterwards
+    fn shared(n: usize) -> Arc<MdctTables>
+    {
+        static CACHE: OnceLock<Mutex<HashMap<usize, Arc<MdctTables>>>> = OnceLock::new();
+
+        let cache = CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+        // A panic while holding the lock cannot leave a half-built entry, so ignore poisoning
+        let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache.entry(n)
+             .or_insert_with(|| Arc::new(MdctTables::new(n)))
+             .clone()
+    }
+
+    /// Unnormalized DCT-IV: N inputs -> N outputs
+    fn dct_iv(&self, input: &[f32], out: &mut [f32])
     {
         let n = self.n;
-        let base = self.cos_table.as_ref();
-        for k in 0..n 
+        let period = 8 * n;
+        let table = self.cos_table.as_ref();
+        for (k, o) in out[..n].iter_mut().enumerate()
         {
+            // Walk m = (2i+1)(2k+1) mod 8N incrementally; the step is < 8N so one wrap suffices
+            let step = 2 * (2 * k + 1);
+            let mut m = 2 * k + 1;
             let mut s = 0.0f32;
-            let tb = &base[k * FRAME_SIZE .. k * FRAME_SIZE + FRAME_SIZE];
-            for i in 0..FRAME_SIZE 
+            for &x in &input[..n]
             {
-                s += block[i] * tb[i];
+                s += x * table[m];
+                m += step;
+                if m >= period
+                {
+                    m -= period;
+                }
             }
-            // apply normalization here so encoder and decoder use same factor
-            out[k] = s * self.norm;
+            *o = s;
         }
     }
 
-    /// Inverse Modified Discrete Cosine Transform: N coeffs -> FRAME_SIZE out
-    fn imdct_block(&self, coeffs: &[f32], out: &mut [f32]) 
+    /// Modified Discrete Cosine Transform: block len 2N -> N coeffs
+    /// `scratch` holds the folded block and is resized as needed
+    fn mdct_block(&self, block: &[f32], out: &mut [f32], scratch: &mut Vec<f32>) 
     {
-        let base = self.cos_table.as_ref();
-        for i in 0..FRAME_SIZE 
+        let n = self.n;
+        let h = n / 2;
+
+        // TDAC fold: with block = (a, b, c, d) in quarters, u = (-c_r - d, a - b_r)
+        scratch.resize(n, 0.0);
+        for i in 0..h
         {
-            let mut s = 0.0f32;
-            for k in 0..self.n 
+            scratch[i] = -block[3 * h - 1 - i] - block[3 * h + i];
+        }
+        for i in h..n
+        {
+            scratch[i] = block[i - h] - block[3 * h - 1 - i];
+        }
+
+        self.dct_iv(scratch, out);
+
+        // apply normalization here so encoder and decoder use same factor
+        for c in &mut out[..n]
+        {
+            *c *= self.norm;
+        }
+    }
+
+    /// Inverse Modified Discrete Cosine Transform: N coeffs -> 2N out
+    /// `scratch` holds the DCT-IV output and is resized as needed
+    fn imdct_block(&self, coeffs: &[f32], out: &mut [f32], scratch: &mut Vec<f32>) 
+    {
+        let n = self.n;
+        let h = n / 2;
+
+        scratch.resize(n, 0.0);
+        self.dct_iv(coeffs, scratch);
+
+        // Unfold: out[i] uses kernel phase j = i + N/2, which is v[j], -v[2N-1-j] or -v[j-2N]
+        // apply same normalization (symmetric)
+        for (i, o) in out[..2 * n].iter_mut().enumerate()
+        {
+            let j = i + h;
+            let v = if j < n
+            {
+                scratch[j]
+            }
+            else if j < 2 * n
             {
-                s += coeffs[k] * base[k * FRAME_SIZE + i];
+                -scratch[2 * n - 1 - j]
             }
-            // apply same normalization (symmetric)
-            out[i] = s * self.norm;
+            else
+            {
+                -scratch[j - 2 * n]
+            };
+            *o = v * self.norm;
+        }
+    }
+}
+
+/// Second-order Butterworth high-pass run over each channel before the MDCT (see
+/// [`Encoder::set_highpass`]), keeping its state from one call to the next so the input can arrive
+/// in pieces
+#[derive(Clone)]
+pub(crate) struct HighPass
+{
+    b: [f64; 3],
+    a: [f64; 2],
+    /// Previous two inputs and outputs of each channel
+    state: Vec<([f64; 2], [f64; 2])>,
+}
+
+impl HighPass
+{
+    pub(crate) fn new(cutoff: f32, sample_rate: u32, channels: usize) -> Self
+    {
+        let k = (std::f64::consts::PI * cutoff as f64 / sample_rate as f64).tan();
+        let q = std::f64::consts::FRAC_1_SQRT_2;
+        let a0 = 1.0 + k / q + k * k;
+        Self
+        {
+            b: [1.0 / a0, -2.0 / a0, 1.0 / a0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            state: vec![([0.0; 2], [0.0; 2]); channels],
         }
     }
+
+    /// Filter the next sample of channel `ch`
+    pub(crate) fn process(&mut self, ch: usize, sample: f32) -> f32
+    {
+        let (x, y) = &mut self.state[ch];
+        let input = sample as f64;
+        let output = self.b[0] * input + self.b[1] * x[0] + self.b[2] * x[1] - self.a[0] * y[0] - self.a[1] * y[1];
+        *x = [input, x[0]];
+        *y = [output, y[0]];
+        output as f32
+    }
+}
+
+/// Frame count and padding (in samples per channel after the last one) of a stream of `length`
+/// samples per channel with a hop of `hop`
+/// The input is preceded by a whole hop of zeros (the encoder delay) and followed by zeros up to a
+/// whole hop and then another hop, so every sample lies where two frames overlap and is
+/// reconstructed in full; empty input has no frames at all
+pub(crate) fn frame_layout(length: usize, hop: usize) -> (usize, usize)
+{
+    if length == 0
+    {
+        return (0, 0);
+    }
+    let padded_len = hop + length.div_ceil(hop) * hop + hop;
+    (padded_len / hop - 1, padded_len - hop - length)
+}
+
+/// A frame (`2 * hop` samples) of one channel starting at `start` in the padded timeline,
+/// i.e. with `hop` zeros (the encoder delay) before the first sample and zeros past the last
+pub(crate) fn padded_block(samples: &[f32], start: usize, hop: usize) -> Vec<f32>
+{
+    let lead = hop;
+    let frame_size = 2 * hop;
+    let mut block = vec![0.0f32; frame_size];
+
+    let src_start = start.saturating_sub(lead);
+    let src_end = (start + frame_size - lead).min(samples.len());
+    if src_start < src_end
+    {
+        let dst_start = lead.saturating_sub(start);
+        block[dst_start .. dst_start + (src_end - src_start)].copy_from_slice(&samples[src_start..src_end]);
+    }
+    block
 }
 
 //
@@ -399,179 +927,1125 @@ pub struct Encoder
     window: Arc<Vec<f32>>,
     perceptual: Arc<PerceptualWeights>,
     sample_rate: u32,
+    quality: f32,
+    /// Coefficients from this one up are dropped (the hop size for none)
+    cutoff_bin: usize,
+    /// Coefficients from this one up are kept only as a spectral envelope (the hop size for none)
+    extension_bin: usize,
+    highpass: Option<f32>,
+    long_term_prediction: bool,
+    adaptive_quality: bool,
+    /// Target bitrate in kbit/s, if coefficients are kept to a fixed budget per frame
+    bitrate: Option<u32>,
+    noise_shaping: bool,
 }
 
 impl Encoder 
 {
     pub fn new(sample_rate: u32) -> Self
     {
-        let n = HOP_SIZE;
-        let tables = Arc::new(MdctTables::new(n));
+        Self::for_hop_size(sample_rate, HOP_SIZE)
+    }
+
+    /// An encoder whose frames advance by `hop_size` samples instead of 1024
+    ///
+    /// Shorter frames cut the delay between audio arriving and the frame covering it being
+    /// ready (see [`LOW_DELAY_HOP_SIZE`]), at the cost of coarser frequency resolution and more
+    /// per-frame overhead, so larger files at the same quality
+    pub fn with_hop_size(sample_rate: u32, hop_size: usize) -> Result<Self>
+    {
+        check_hop_size(hop_size)?;
+        Ok(Self::for_hop_size(sample_rate, hop_size))
+    }
+
+    fn for_hop_size(sample_rate: u32, n: usize) -> Self
+    {
+        let tables = MdctTables::shared(n);
         let perceptual = Arc::new(PerceptualWeights::new(n, sample_rate));
         Self 
         {
             window: tables.window.clone(),
             tables,
             perceptual,
-            sample_rate
+            sample_rate,
+            quality: DEFAULT_QUALITY,
+            cutoff_bin: n,
+            extension_bin: n,
+            highpass: None,
+            long_term_prediction: false,
+            adaptive_quality: false,
+            bitrate: None,
+            noise_shaping: false,
         }
     }
 
-    /// Encode PCM `samples` (interleaved if multichannel) to our GLC format
-    pub fn encode(&mut self, samples: &[f32], channels: u16) -> Result<EncodedAudio>
+    /// Samples per channel each frame advances by
+    pub fn hop_size(&self) -> usize
     {
-        let total_samples = samples.len() as u64;
-        let ch = channels as usize;
+        self.tables.n
+    }
 
-        // Deinterleave channels
-        let mut per_chan: Vec<Vec<f32>> = vec![Vec::with_capacity(samples.len() / ch + 8); ch];
-        for (i, &s) in samples.iter().enumerate()
-        {
-            per_chan[i % ch].push(s);
-        }
+    /// Set the quality from 0.1 (smallest files) to 1.0 (best quality): higher values lower the
+    /// masking thresholds so more coefficients are kept; values outside [`QUALITY_RANGE`] are clamped
+    pub fn set_quality(&mut self, quality: f32)
+    {
+        self.quality = quality.clamp(*QUALITY_RANGE.start(), *QUALITY_RANGE.end());
+    }
 
-        // Pad per-channel
-        let mut padded: Vec<Vec<f32>> = Vec::with_capacity(ch);
-        for c in 0..ch
-        {
-            let mut v = Vec::with_capacity(per_chan[c].len() + HOP_SIZE);
-            v.extend(std::iter::repeat(0.0f32).take(HOP_SIZE / 2));
-            v.extend_from_slice(&per_chan[c]);
-            let rem = v.len() % HOP_SIZE;
-            if rem != 0
-            {
-                v.extend(std::iter::repeat(0.0f32).take(HOP_SIZE - rem));
-            }
-            v.extend(std::iter::repeat(0.0f32).take(HOP_SIZE / 2));
-            padded.push(v);
-        }
+    pub fn quality(&self) -> f32
+    {
+        self.quality
+    }
 
-        let num_frames = if padded[0].len() < FRAME_SIZE
-        {
-            1usize
-        } else
+    /// Drop everything above `cutoff` Hz, or keep the full band for None, trading treble for
+    /// smaller files; cutoffs are kept at or above [`MIN_CUTOFF`]
+    pub fn set_cutoff(&mut self, cutoff: Option<f32>)
+    {
+        let n = self.tables.n;
+        self.cutoff_bin = match cutoff
         {
-            (padded[0].len() - FRAME_SIZE) / HOP_SIZE + 1
+            // Bin k of the MDCT is centred on (k + 0.5) * sample_rate / (2n) Hz
+            Some(cutoff) => ((cutoff.max(MIN_CUTOFF) * 2.0 * n as f32 / self.sample_rate as f32).ceil() as usize).min(n),
+            None => n,
         };
+    }
 
-        let tables = self.tables.clone();
-        let window = self.window.clone();
-        let perceptual = self.perceptual.clone();
+    /// Highest frequency kept in Hz, or None for the full band
+    pub fn cutoff(&self) -> Option<f32>
+    {
+        (self.cutoff_bin < self.tables.n).then(|| self.cutoff_bin as f32 * self.sample_rate as f32 / (2 * self.tables.n) as f32)
+    }
 
-        // Encode frames in parallel, deciding per-frame whether to use compression
-        let frames: Vec<EncodedFrame> = (0..num_frames).into_par_iter().map(|fi|
-        {
-            let mut sparse_coeffs_per_channel: Vec<Vec<(u16, i16)>> = Vec::with_capacity(ch);
-            let mut scale_factors: Vec<f32> = Vec::with_capacity(ch);
+    /// Scale each frame's masking thresholds by its perceptual entropy relative to earlier frames, so
+    /// simple frames are squeezed harder and complex ones keep more detail at about the same size
+    pub fn set_adaptive_quality(&mut self, enabled: bool)
+    {
+        self.adaptive_quality = enabled;
+    }
 
-            // Extract raw frame samples for fallback consideration
-            // IMPORTANT: Store FRAME_SIZE samples to maintain overlap-add structure
-            let mut raw_frame_samples: Vec<i16> = Vec::with_capacity(FRAME_SIZE * ch);
+    pub fn adaptive_quality(&self) -> bool
+    {
+        self.adaptive_quality
+    }
 
-            for c in 0..ch
-            {
-                let start = fi * HOP_SIZE;
-                let slice = &padded[c][start .. start + FRAME_SIZE];
+    /// Carry each coefficient's coding error, relative to its masking threshold, into the decision on
+    /// the next one in its critical band; has no effect with [`set_bitrate`](Self::set_bitrate)
+    pub fn set_noise_shaping(&mut self, enabled: bool)
+    {
+        self.noise_shaping = enabled;
+    }
 
-                // Apply window
-                let mut block = vec![0.0f32; FRAME_SIZE];
-                for i in 0..FRAME_SIZE
-                {
-                    block[i] = slice[i] * window[i];
-                }
+    pub fn noise_shaping(&self) -> bool
+    {
+        self.noise_shaping
+    }
 
-                // Compute MDCT
-                let mut coeffs = vec![0.0f32; tables.n];
-                tables.mdct_block(&block, &mut coeffs);
+    /// Keep the [`coefficient_budget`](Self::coefficient_budget) for `kbps` kbit/s (clamped to [`BITRATE_RANGE`])
+    /// in each frame instead of selecting by quality and masking threshold; None selects by threshold
+    pub fn set_bitrate(&mut self, kbps: Option<u32>)
+    {
+        self.bitrate = kbps.map(|kbps| kbps.clamp(*BITRATE_RANGE.start(), *BITRATE_RANGE.end()));
+    }
 
-                // Find per-channel scale
-                let max_val = coeffs.iter().map(|x| x.abs()).fold(0.0f32, f32::max).max(1e-10);
-                scale_factors.push(max_val);
+    /// Target bitrate in kbit/s, or None if coefficients are selected by masking threshold
+    pub fn bitrate(&self) -> Option<u32>
+    {
+        self.bitrate
+    }
 
-                // Compute masking thresholds and compress
-                let thresholds = compute_masking_thresholds(&coeffs, QUALITY_FACTOR, &perceptual);
-                let sparse = compress_coefficients(&coeffs, max_val, &thresholds, NOISE_FLOOR_DB);
-                sparse_coeffs_per_channel.push(sparse);
+    /// Coefficients kept per channel in each frame of a `channels`-channel stream under the target
+    /// bitrate, or None without one: the bytes a frame may take at that bitrate, less the frame's
+    /// other data and container overhead (allowing for a prediction and an envelope if enabled),
+    /// divided among the channels
+    /// Frames of raw PCM, or with fewer nonzero coefficients than this, come out smaller
+    pub fn coefficient_budget(&self, channels: usize) -> Option<usize>
+    {
+        let kbps = self.bitrate? as usize;
+        let ch = channels.max(1);
+        let frame_bytes = kbps * 125 * self.tables.n / self.sample_rate as usize;
+        let coded_bins = self.cutoff_bin.min(self.extension_bin);
+        Some((frame_bytes.saturating_sub(self.side_bytes(ch)) / (ch * size_of::<(CoefficientIndex, QuantizedCoefficient)>())).min(coded_bins))
+    }
 
-                // Collect raw samples for this channel (ENTIRE FRAME_SIZE with window applied)
-                // This maintains the overlap-add structure
-                for i in 0..FRAME_SIZE
-                {
-                    let sample = slice[i] * window[i];
-                    raw_frame_samples.push((sample * 32767.0).clamp(-32768.0, 32767.0) as i16);
-                }
-            }
+    /// Lowest bitrate in kbit/s whose [`coefficient_budget`](Self::coefficient_budget) keeps at
+    /// least one coefficient per channel in a `channels`-channel stream with these settings
+    pub fn min_bitrate(&self, channels: usize) -> u32
+    {
+        let ch = channels.max(1);
+        let frame_bytes = self.side_bytes(ch) + ch * size_of::<(CoefficientIndex, QuantizedCoefficient)>();
+        (frame_bytes * self.sample_rate as usize).div_ceil(125 * self.tables.n) as u32
+    }
 
-            // Estimate compressed size for this frame
-            let mut compressed_size = 0usize;
-            for sparse_channel in &sparse_coeffs_per_channel
-            {
-                // Vec length (8 bytes) + sparse entries (4 bytes each)
-                compressed_size += 8 + sparse_channel.len() * 4;
-            }
-            // Add scale factors: Vec length + f32 per channel
-            compressed_size += 8 + scale_factors.len() * 4;
-            // Add frame overhead
-            compressed_size += 64;
+    /// Fail if the target bitrate is too low for any coefficients in a `channels`-channel stream
+    pub(crate) fn check_bitrate(&self, channels: usize) -> Result<()>
+    {
+        match self.bitrate
+        {
+            Some(kbps) if kbps < self.min_bitrate(channels) =>
+                Err(anyhow!("{} kbit/s leaves no room for coefficients; this stream needs at least {} kbit/s",
+                            kbps, self.min_bitrate(channels))),
+            _ => Ok(()),
+        }
+    }
 
-            // Raw PCM size for this frame (i16 samples, interleaved, FRAME_SIZE per channel)
-            let raw_size = FRAME_SIZE * ch * 2; // 2 bytes per i16
+    /// Bytes each frame takes besides its coefficients under a coefficient budget
+    fn side_bytes(&self, ch: usize) -> usize
+    {
+        // Record length and CRC, seek table entry, coefficient and scale factor vectors, no raw PCM
+        let mut side_bytes = 8 + 8 + (8 + ch * 8) + (8 + ch * 4) + 1;
+        let extended = self.extension_bin < self.cutoff_bin;
+        if self.long_term_prediction || extended
+        {
+            side_bytes += 8;
+        }
+        if self.long_term_prediction
+        {
+            // Each channel's Option<LongTermPrediction>: a tag, the lag, gain index, and band mask
+            side_bytes += ch * (1 + 2 + 1 + 2);
+        }
+        if extended
+        {
+            side_bytes += 1 + size_of::<CoefficientIndex>() + 8 + ch * ENVELOPE_BANDS;
+        }
+        side_bytes
+    }
 
-            // Decide: use compression or raw PCM?
-            if compressed_size as f32 >= (raw_size as f32 * COMPRESSION_THRESHOLD)
-            {
-                // Use raw PCM fallback for this frame
-                EncodedFrame
-                {
-                    sparse_coeffs_per_channel: Vec::new(),
-                    scale_factors: Vec::new(),
-                    raw_pcm: Some(raw_frame_samples),
-                }
-            }
-            else
+    /// Code the spectrum only up to `crossover` Hz and keep the band above it as a coarse envelope,
+    /// which decoders fill in from the band below; None codes the full band
+    /// Gives usable quality at very low bitrates, mostly for speech; crossovers are clamped to
+    /// [`BANDWIDTH_EXTENSION_RANGE`], and one past the Nyquist frequency leaves the full band coded
+    pub fn set_bandwidth_extension(&mut self, crossover: Option<f32>)
+    {
+        let n = self.tables.n;
+        self.extension_bin = match crossover
+        {
+            Some(crossover) =>
             {
-                // Use compression
-                EncodedFrame
-                {
-                    sparse_coeffs_per_channel,
-                    scale_factors,
-                    raw_pcm: None,
-                }
+                let crossover = crossover.clamp(*BANDWIDTH_EXTENSION_RANGE.start(), *BANDWIDTH_EXTENSION_RANGE.end());
+                ((crossover * 2.0 * n as f32 / self.sample_rate as f32).ceil() as usize).min(n)
             }
-        }).collect();
-
-        // Compute padding metadata
-        let padded_len = padded[0].len();
-        let orig_len = per_chan[0].len();
-        let padding = (padded_len - orig_len - (HOP_SIZE / 2)) as u32;
-        let encoder_delay = (HOP_SIZE / 2) as u32;
+            None => n,
+        };
+    }
+
+    /// Bandwidth-extension crossover in Hz, or None if the full band is coded
+    pub fn bandwidth_extension(&self) -> Option<f32>
+    {
+        (self.extension_bin < self.tables.n).then(|| self.extension_bin as f32 * self.sample_rate as f32 / (2 * self.tables.n) as f32)
+    }
+
+    /// Filter out everything below `cutoff` Hz before encoding, or nothing for None, so DC offset
+    /// and subsonic rumble don't spend bits on inaudible bins; cutoffs are clamped to [`HIGHPASS_RANGE`]
+    /// The cutoff is recorded in the stream's header
+    pub fn set_highpass(&mut self, cutoff: Option<f32>)
+    {
+        self.highpass = cutoff.map(|cutoff| cutoff.clamp(*HIGHPASS_RANGE.start(), *HIGHPASS_RANGE.end()));
+    }
+
+    /// High-pass cutoff in Hz, or None if the input is encoded unfiltered
+    pub fn highpass(&self) -> Option<f32>
+    {
+        self.highpass
+    }
+
+    /// Predict each frame from the audio decoded two to four hops before it wherever that leaves
+    /// less to code, which shrinks sustained tonal music; frames are then encoded one after another
+    /// rather than in parallel, and decoders spend an extra transform on each predicted channel
+    /// Prediction restarts every [`LTP_RESTART_INTERVAL`] frames so decoding can start there
+    pub fn set_long_term_prediction(&mut self, enabled: bool)
+    {
+        self.long_term_prediction = enabled;
+    }
+
+    pub fn long_term_prediction(&self) -> bool
+    {
+        self.long_term_prediction
+    }
+
+    /// A fresh high-pass filter for `channels` channels, if one is set
+    pub(crate) fn highpass_filter(&self, channels: usize) -> Option<HighPass>
+    {
+        self.highpass.map(|cutoff| HighPass::new(cutoff, self.sample_rate, channels))
+    }
+
+    /// Encode PCM `samples` (interleaved if multichannel) to our GLC format
+    pub fn encode(&mut self, samples: &[f32], channels: u16) -> Result<EncodedAudio>
+    {
+        let ch = channels as usize;
+        if ch == 0 || channels > MAX_CHANNELS
+        {
+            return Err(GlcError::UnsupportedChannels(channels).into());
+        }
+
+        // Deinterleave channels
+        let mut per_chan: Vec<Vec<f32>> = vec![Vec::with_capacity(samples.len() / ch + 8); ch];
+        for (i, &s) in samples.iter().enumerate()
+        {
+            per_chan[i % ch].push(s);
+        }
+
+        let planar: Vec<&[f32]> = per_chan.iter().map(|c| c.as_slice()).collect();
+        self.encode_planar(&planar)
+    }
+
+    /// Encode planar PCM (one slice per channel, all the same length) to our GLC format
+    /// Avoids the deinterleaving copy of [`Encoder::encode`] for callers that already have planar audio
+    pub fn encode_planar(&mut self, channels: &[&[f32]]) -> Result<EncodedAudio>
+    {
+        let ch = channels.len();
+        if ch == 0 || ch > MAX_CHANNELS as usize
+        {
+            return Err(GlcError::UnsupportedChannels(ch.min(u16::MAX as usize) as u16).into());
+        }
+        let orig_len = channels[0].len();
+        if channels.iter().any(|c| c.len() != orig_len)
+        {
+            return Err(anyhow!("All channels must have the same number of samples"));
+        }
+        self.check_bitrate(ch)?;
+        let total_samples = (orig_len * ch) as u64;
+        let hop = self.hop_size();
+        let (num_frames, padding) = frame_layout(orig_len, hop);
+
+        // With a high-pass set, the frames are cut from a filtered copy of the input
+        let filtered: Vec<Vec<f32>> = match self.highpass_filter(ch)
+        {
+            Some(mut filter) => channels.iter().enumerate()
+                                        .map(|(c, samples)| samples.iter().map(|&s| filter.process(c, s)).collect())
+                                        .collect(),
+            None => Vec::new(),
+        };
+        let filtered_slices: Vec<&[f32]> = filtered.iter().map(Vec::as_slice).collect();
+        let channels = if filtered.is_empty() { channels } else { &filtered_slices[..] };
+
+        // Encode frames (in parallel if enabled), deciding per-frame whether to use compression
+        // Each frame copies only its own window out of the input
+        let this = &*self;
+        let frame_blocks = |fi: usize| -> Vec<Vec<f32>>
+        {
+            channels.iter().map(|c| padded_block(c, fi * hop, hop)).collect()
+        };
+        let scales = self.threshold_scales(&mut AdaptiveQuality::default(), num_frames, 1.0, |fi|
+        {
+            let owned = frame_blocks(fi);
+            let blocks: Vec<&[f32]> = owned.iter().map(|b| b.as_slice()).collect();
+            this.perceptual_entropy(&blocks)
+        });
+        let frames: Vec<EncodedFrame> = if self.long_term_prediction
+        {
+            // Each frame is predicted from the ones before it as decoded, so they go in order
+            let mut state = OverlapState::for_hop_size(ch, hop);
+            (0..num_frames).map(|fi|
+            {
+                let owned = frame_blocks(fi);
+                let blocks: Vec<&[f32]> = owned.iter().map(|b| b.as_slice()).collect();
+                this.encode_block_predicted(&blocks, &mut state, fi.is_multiple_of(LTP_RESTART_INTERVAL), scales[fi])
+            }).collect()
+        }
+        else
+        {
+            let encode_frame = |fi: usize| -> EncodedFrame
+            {
+                let owned = frame_blocks(fi);
+                let blocks: Vec<&[f32]> = owned.iter().map(|b| b.as_slice()).collect();
+                this.encode_block_scaled(&blocks, scales[fi])
+            };
+
+            #[cfg(feature = "parallel")]
+            let frames = (0..num_frames).into_par_iter().map(encode_frame).collect();
+            #[cfg(not(feature = "parallel"))]
+            let frames = (0..num_frames).map(encode_frame).collect();
+            frames
+        };
+
+        let encoder_delay = hop as u32;
 
         Ok(EncodedAudio
         {
             header: AudioHeader
             {
                 sample_rate: self.sample_rate,
-                channels,
+                channels: ch as u16,
                 total_samples,
+                hop_size: hop as u32,
+                highpass: self.highpass,
+                long_term_prediction: self.long_term_prediction,
+                bandwidth_extension: self.bandwidth_extension(),
             },
             frames,
             gapless_info: GaplessInfo
             {
                 encoder_delay,
-                padding,
+                padding: padding as u32,
                 original_length: total_samples,
             },
         })
     }
+
+    /// Encode one frame from `2 * hop_size` padded (unwindowed) samples per channel
+    /// Decides per-frame whether to use compression or fall back to raw PCM
+    pub(crate) fn encode_block(&self, blocks: &[&[f32]]) -> EncodedFrame
+    {
+        self.encode_block_with(blocks, None, 1.0)
+    }
+
+    /// Encode one frame as [`encode_block`](Self::encode_block) does, with its masking thresholds
+    /// scaled by `threshold_scale` (see [`threshold_scales`](Self::threshold_scales))
+    pub(crate) fn encode_block_scaled(&self, blocks: &[&[f32]], threshold_scale: f32) -> EncodedFrame
+    {
+        self.encode_block_with(blocks, None, threshold_scale)
+    }
+
+    /// Encode one frame as [`encode_block_scaled`](Self::encode_block_scaled) does, but predicted
+    /// from the history in `state` wherever that helps, or restarting prediction if `restart`; then
+    /// decode it into `state` exactly as a decoder will, ready for the next frame
+    pub(crate) fn encode_block_predicted(&self, blocks: &[&[f32]], state: &mut OverlapState, restart: bool, threshold_scale: f32)
+        -> EncodedFrame
+    {
+        let frame = self.encode_block_with(blocks, (!restart).then_some(&*state), threshold_scale);
+        let mut decoded = decode_frame_blocks(&self.tables, &self.window, &frame, blocks.len());
+        let mut out = Vec::with_capacity(self.tables.n * blocks.len());
+        state.add(&self.tables, &frame, &mut decoded, &mut out);
+        frame
+    }
+
+    /// Masking threshold scale for each of `count` frames in stream order, all 1 unless adaptive
+    /// quality is on: then `entropy` gives each frame's [perceptual entropy](Self::perceptual_entropy)
+    /// (computed in parallel if enabled), and `adaptive` carries the average from one call to the
+    /// next, each frame counting as `weight` frames towards it
+    pub(crate) fn threshold_scales<F>(&self, adaptive: &mut AdaptiveQuality, count: usize, weight: f32, entropy: F) -> Vec<f32>
+    where
+        F: Fn(usize) -> f32 + Sync + Send,
+    {
+        if !self.adaptive_quality || self.bitrate.is_some()
+        {
+            return vec![1.0; count];
+        }
+
+        #[cfg(feature = "parallel")]
+        let entropies: Vec<f32> = (0..count).into_par_iter().map(entropy).collect();
+        #[cfg(not(feature = "parallel"))]
+        let entropies: Vec<f32> = (0..count).map(entropy).collect();
+        entropies.into_iter().map(|e| adaptive.threshold_scale(e, weight)).collect()
+    }
+
+    /// Perceptual entropy of one frame, summed over its channels: for each coded bin, the log2 of
+    /// how far it stands above its masking threshold or the noise floor, which estimates the bits needed to code the
+    /// frame without audible loss
+    pub(crate) fn perceptual_entropy(&self, blocks: &[&[f32]]) -> f32
+    {
+        let mut fold_scratch = Vec::with_capacity(self.tables.n);
+        blocks.iter().map(|slice|
+        {
+            let mut coeffs = self.frame_spectrum(slice, &mut fold_scratch);
+            // The band above a crossover costs only its envelope
+            coeffs[self.extension_bin.min(self.cutoff_bin)..].fill(0.0);
+            let max_val = coeffs.iter().map(|x| x.abs()).fold(0.0f32, f32::max).max(1e-10);
+            let noise_floor = 10.0_f32.powf(NOISE_FLOOR_DB / 20.0);
+            let thresholds = compute_masking_thresholds(&coeffs, self.quality, &self.perceptual);
+            coeffs.iter().zip(&thresholds)
+                  .map(|(c, t)| (1.0 + c.abs() / (t.max(noise_floor) * max_val)).log2())
+                  .sum::<f32>()
+        }).sum()
+    }
+
+    /// Windowed MDCT of one channel's `2 * hop_size` samples, with the bins above the cutoff cleared
+    fn frame_spectrum(&self, slice: &[f32], fold_scratch: &mut Vec<f32>) -> Vec<f32>
+    {
+        let block: Vec<f32> = slice.iter().zip(self.window.iter()).map(|(s, w)| s * w).collect();
+        let mut coeffs = vec![0.0f32; self.tables.n];
+        self.tables.mdct_block(&block, &mut coeffs, fold_scratch);
+        coeffs[self.cutoff_bin..].fill(0.0);
+        coeffs
+    }
+
+    /// Encode one frame, predicting it from `history` if given (which makes it a frame that
+    /// carries prediction even where none helps) or else restarting prediction, with its masking
+    /// thresholds scaled by `threshold_scale`
+    fn encode_block_with(&self, blocks: &[&[f32]], history: Option<&OverlapState>, threshold_scale: f32) -> EncodedFrame
+    {
+        let ch = blocks.len();
+        let tables = &self.tables;
+        let window = &self.window;
+        let perceptual = &self.perceptual;
+        let frame_size = 2 * tables.n;
+
+        let mut sparse_coeffs_per_channel: Vec<Vec<(CoefficientIndex, QuantizedCoefficient)>> = Vec::with_capacity(ch);
+        let mut scale_factors: Vec<f32> = Vec::with_capacity(ch);
+        let mut ltp: Vec<Option<LongTermPrediction>> = Vec::with_capacity(ch);
+        // Past a cutoff below the crossover there is nothing left to extend
+        let extension = (self.extension_bin < self.cutoff_bin).then_some(self.extension_bin);
+        let mut levels: Vec<[u8; ENVELOPE_BANDS]> = Vec::with_capacity(ch);
+        let budget = self.coefficient_budget(ch);
+        let feedback = if self.noise_shaping { NOISE_SHAPING_FEEDBACK } else { 0.0 };
+
+        // Extract raw frame samples for fallback consideration
+        // IMPORTANT: Store the whole frame to maintain overlap-add structure
+        let mut raw_frame_samples: Vec<i16> = Vec::with_capacity(frame_size * ch);
+
+        let mut fold_scratch = Vec::with_capacity(tables.n);
+        for (c, slice) in blocks.iter().enumerate()
+        {
+            let mut coeffs = self.frame_spectrum(slice, &mut fold_scratch);
+
+            // Find per-channel scale
+            let max_val = coeffs.iter().map(|x| x.abs()).fold(0.0f32, f32::max).max(1e-10);
+
+            // Above the crossover only the envelope is kept
+            let high_rms = extension.map(|start|
+            {
+                let rms = envelope_rms(&coeffs, start);
+                coeffs[start..].fill(0.0);
+                rms
+            });
+
+            // Compute masking thresholds and compress, less the prediction if there is one;
+            // the thresholds stay those of the frame itself
+            let mut thresholds = compute_masking_thresholds(&coeffs, self.quality, perceptual);
+            if threshold_scale != 1.0
+            {
+                thresholds.iter_mut().for_each(|t| *t *= threshold_scale);
+            }
+            let noise_floor_db = NOISE_FLOOR_DB + 20.0 * threshold_scale.log10();
+            let select = |coeffs: &[f32], scale: f32| match budget
+            {
+                Some(budget) => keep_largest(coeffs, scale, budget, &perceptual.weights),
+                None => compress_coefficients(coeffs, scale, max_val, &thresholds, noise_floor_db, feedback, &perceptual.critical_bands),
+            };
+            let prediction = history.and_then(|state| state.history(c))
+                                    .and_then(|history| self.choose_prediction(slice, &coeffs, history));
+            match &prediction
+            {
+                Some((_, predicted)) =>
+                {
+                    let residual: Vec<f32> = coeffs.iter().zip(predicted).map(|(c, p)| c - p).collect();
+                    // The scale covers the envelope too, whose levels are relative to it
+                    let scale = residual.iter().chain(high_rms.iter().flatten())
+                                        .map(|x| x.abs()).fold(0.0f32, f32::max).max(1e-10);
+                    sparse_coeffs_per_channel.push(select(&residual, scale));
+                    scale_factors.push(scale);
+                }
+                None =>
+                {
+                    sparse_coeffs_per_channel.push(select(&coeffs, max_val));
+                    scale_factors.push(max_val);
+                }
+            }
+            ltp.push(prediction.map(|(prediction, _)| prediction));
+            if let (Some(rms), Some(&scale)) = (&high_rms, scale_factors.last())
+            {
+                levels.push(envelope_levels(rms, scale));
+            }
+
+            // Collect raw samples for this channel (ENTIRE frame with window applied)
+            // This maintains the overlap-add structure
+            for i in 0..frame_size
+            {
+                let sample = slice[i] * window[i];
+                raw_frame_samples.push((sample * 32767.0).clamp(-32768.0, 32767.0) as i16);
+            }
+        }
+
+        // Estimate compressed size for this frame
+        let mut compressed_size = 0usize;
+        for sparse_channel in &sparse_coeffs_per_channel
+        {
+            // Vec length (8 bytes) + sparse entries (an index and a quantized value each)
+            compressed_size += 8 + sparse_channel.len() * size_of::<(CoefficientIndex, QuantizedCoefficient)>();
+        }
+        // Add scale factors: Vec length + f32 per channel
+        compressed_size += 8 + scale_factors.len() * 4;
+        // Add frame overhead
+        compressed_size += 64;
+        if history.is_some()
+        {
+            compressed_size += 8 + ch * size_of::<Option<LongTermPrediction>>();
+        }
+        if extension.is_some()
+        {
+            compressed_size += size_of::<CoefficientIndex>() + 8 + ch * ENVELOPE_BANDS;
+        }
+
+        // Raw PCM size for this frame (i16 samples, a whole frame per channel)
+        let raw_size = frame_size * ch * 2; // 2 bytes per i16
+
+        // Decide: use compression or raw PCM?
+        if compressed_size as f32 >= (raw_size as f32 * COMPRESSION_THRESHOLD)
+        {
+            // Use raw PCM fallback for this frame
+            // Its prediction goes, but it still carries (no) prediction so as not to restart it
+            EncodedFrame
+            {
+                sparse_coeffs_per_channel: Vec::new(),
+                scale_factors: Vec::new(),
+                raw_pcm: Some(raw_frame_samples),
+                ltp: if history.is_some() { vec![None; ch] } else { Vec::new() },
+                envelope: None,
+            }
+        }
+        else
+        {
+            // Use compression
+            EncodedFrame
+            {
+                sparse_coeffs_per_channel,
+                scale_factors,
+                raw_pcm: None,
+                ltp: if history.is_some() { ltp } else { Vec::new() },
+                envelope: extension.map(|start| SpectralEnvelope { start: start as CoefficientIndex, levels }),
+            }
+        }
+    }
+
+    /// Pick the long-term prediction for one channel of a frame: the lag at which `history` (the
+    /// `4 * hop` samples decoded before the frame) best matches `block` (the frame's unwindowed
+    /// input), the gain, and the bands where it leaves at most [`LTP_MAX_RESIDUAL`] of `coeffs`
+    /// (the frame's MDCT) to code
+    /// Returns the prediction with its coefficients, or None if it helps nowhere
+    fn choose_prediction(&self, block: &[f32], coeffs: &[f32], history: &[f32]) -> Option<(LongTermPrediction, Vec<f32>)>
+    {
+        let n = self.tables.n;
+        let lag = best_lag(block, history, &self.window)?;
+        let mut spectrum = vec![0.0f32; n];
+        ltp_spectrum(&self.tables, history, lag, &mut spectrum, &mut Vec::new());
+
+        // Only bands wholly below the cutoff and crossover are predicted; the gain is fitted over the bands
+        // predicted, starting from all of them, and the bands are then chosen again for that gain
+        let width = n / (2 * LTP_BANDS);
+        let band = |b: usize| b * width..(b + 1) * width;
+        let coded_bins = self.cutoff_bin.min(self.extension_bin);
+        let usable: Vec<usize> = (0..LTP_BANDS).filter(|&b| band(b).end <= coded_bins).collect();
+        let mut bands: u16 = usable.iter().fold(0, |mask, &b| mask | 1 << b);
+        let mut gain_index = 0;
+        for _ in 0..2
+        {
+            let predicted = usable.iter().filter(|&&b| bands & 1 << b != 0);
+            let (cross, power) = predicted.fold((0.0f32, 0.0f32), |(cross, power), &b|
+            {
+                (cross + dot(&coeffs[band(b)], &spectrum[band(b)]), power + dot(&spectrum[band(b)], &spectrum[band(b)]))
+            });
+            if cross <= 0.0 || power <= 0.0
+            {
+                return None;
+            }
+            let gain = cross / power;
+            gain_index = (0..LTP_GAINS.len()).min_by(|&a, &b| (LTP_GAINS[a] - gain).abs().total_cmp(&(LTP_GAINS[b] - gain).abs()))?;
+            let gain = LTP_GAINS[gain_index];
+            bands = usable.iter()
+                          .filter(|&&b|
+                          {
+                              let energy = dot(&coeffs[band(b)], &coeffs[band(b)]);
+                              let left: f32 = coeffs[band(b)].iter().zip(&spectrum[band(b)]).map(|(c, p)| (c - gain * p).powi(2)).sum();
+                              energy > 0.0 && left <= LTP_MAX_RESIDUAL * energy
+                          })
+                          .fold(0, |mask, &b| mask | 1 << b);
+            if bands == 0
+            {
+                return None;
+            }
+        }
+
+        let prediction = LongTermPrediction { lag: lag as u16, gain_index: gain_index as u8, bands };
+        apply_ltp(&prediction, &mut spectrum);
+        Some((prediction, spectrum))
+    }
+
+    pub fn sample_rate(&self) -> u32
+    {
+        self.sample_rate
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32
+{
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Lag, from two hops up to four, at which `history` (the `4 * hop` samples before a frame)
+/// correlates best with `block` (the frame's `2 * hop` samples) once both are windowed
+/// Searched coarsely on decimated signals, then at full rate around the best few lags found;
+/// None if nothing correlates positively
+fn best_lag(block: &[f32], history: &[f32], window: &[f32]) -> Option<usize>
+{
+    let hop = block.len() / 2;
+    let d = LTP_SEARCH_DECIMATION;
+    // The MDCT sees both signals windowed, so the correlation weights each sample by the window twice
+    let target: Vec<f32> = block.iter().zip(window).map(|(x, w)| x * w * w).collect();
+    let weight: Vec<f32> = window.iter().map(|w| w * w).collect();
+    let power: Vec<f32> = history.iter().map(|h| h * h).collect();
+    let score = |correlation: f32, energy: f32| if correlation > 0.0 && energy > 1e-12 { correlation * correlation / energy } else { 0.0 };
+
+    let decimate = |x: &[f32]| -> Vec<f32> { x.chunks(d).map(|chunk| chunk.iter().sum()).collect() };
+    let (coarse_target, coarse_weight) = (decimate(&target), decimate(&weight));
+    let (coarse_history, coarse_power) = (decimate(history), decimate(&power));
+    let len = coarse_target.len();
+    let mut coarse: Vec<(f32, usize)> = (2 * hop / d..4 * hop / d).map(|lag|
+    {
+        let start = coarse_history.len() - lag;
+        let correlation = dot(&coarse_target, &coarse_history[start..start + len]);
+        (score(correlation, dot(&coarse_weight, &coarse_power[start..start + len])), lag * d)
+    }).collect();
+    coarse.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut best: Option<(f32, usize)> = None;
+    for &(_, centre) in coarse.iter().take(LTP_SEARCH_CANDIDATES)
+    {
+        for lag in (centre + 1).saturating_sub(d).max(2 * hop)..(centre + d).min(4 * hop)
+        {
+            let start = history.len() - lag;
+            let correlation = dot(&target, &history[start..start + 2 * hop]);
+            let found = score(correlation, dot(&weight, &power[start..start + 2 * hop]));
+            if found > 0.0 && best.is_none_or(|(score, _)| found > score)
+            {
+                best = Some((found, lag));
+            }
+        }
+    }
+    best.map(|(_, lag)| lag)
+}
+
+/// Windowed MDCT of the `2 * hop` samples `lag` back from the end of `history`, which holds the
+/// `4 * hop` samples decoded before a frame: the long-term prediction of the frame before its
+/// gain and bands are applied
+fn ltp_spectrum(tables: &MdctTables, history: &[f32], lag: usize, out: &mut [f32], scratch: &mut Vec<f32>)
+{
+    let start = history.len() - lag;
+    let block: Vec<f32> = history[start..start + 2 * tables.n].iter().zip(tables.window.iter()).map(|(h, w)| h * w).collect();
+    tables.mdct_block(&block, out, scratch);
+}
+
+/// Scale a prediction's spectrum by its gain, silencing the bands it leaves off
+fn apply_ltp(prediction: &LongTermPrediction, spectrum: &mut [f32])
+{
+    let width = spectrum.len() / (2 * LTP_BANDS);
+    let gain = LTP_GAINS[prediction.gain_index as usize];
+    for (k, coeff) in spectrum.iter_mut().enumerate()
+    {
+        let band = k / width;
+        *coeff = if band < LTP_BANDS && prediction.bands & 1 << band != 0 { *coeff * gain } else { 0.0 };
+    }
+}
+
+/// Running average of an encoder's per-frame perceptual entropy, for adaptive quality
+/// Each frame counts in proportion to its entropy, i.e. to the bits it takes, so the frames that
+/// make up most of the stream's size sit near the average and keep about their fixed-quality size
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AdaptiveQuality
+{
+    /// Frames seen so far
+    frames: f32,
+    /// Averages of the entropy and of its square
+    mean: f32,
+    square: f32,
+}
+
+impl AdaptiveQuality
+{
+    /// Fold a frame's perceptual entropy into the average, counting it as `weight` frames, and
+    /// return the factor its masking thresholds are scaled by: above 1 (coarser) for frames simpler
+    /// than the average, below 1 for more complex ones
+    pub(crate) fn threshold_scale(&mut self, entropy: f32, weight: f32) -> f32
+    {
+        // A plain mean until enough frames have been seen for the moving average
+        self.frames += weight;
+        let rate = weight / self.frames.min(ADAPTIVE_AVERAGE_FRAMES).max(weight);
+        self.mean += (entropy - self.mean) * rate;
+        self.square += (entropy * entropy - self.square) * rate;
+        let average = self.square / self.mean.max(1e-10);
+        ((average + 1.0) / (entropy + 1.0)).powf(ADAPTIVE_STRENGTH).clamp(1.0 / ADAPTIVE_MAX_SCALE, ADAPTIVE_MAX_SCALE)
+    }
+}
+
+/// Bins band `b` of a spectral envelope from bin `start` covers in a spectrum of `n` bins
+fn envelope_band(start: usize, n: usize, b: usize) -> std::ops::Range<usize>
+{
+    start + (n - start) * b / ENVELOPE_BANDS..start + (n - start) * (b + 1) / ENVELOPE_BANDS
+}
+
+/// RMS of each spectral envelope band of `coeffs` from bin `start`
+fn envelope_rms(coeffs: &[f32], start: usize) -> [f32; ENVELOPE_BANDS]
+{
+    std::array::from_fn(|b|
+    {
+        let band = &coeffs[envelope_band(start, coeffs.len(), b)];
+        (dot(band, band) / band.len().max(1) as f32).sqrt()
+    })
+}
+
+/// Envelope levels of bands with RMS `rms`, relative to `scale`
+fn envelope_levels(rms: &[f32; ENVELOPE_BANDS], scale: f32) -> [u8; ENVELOPE_BANDS]
+{
+    rms.map(|rms|
+    {
+        if rms <= 0.0
+        {
+            return ENVELOPE_SILENT;
+        }
+        let steps = (20.0 * (scale / rms).log10() / ENVELOPE_STEP_DB).round();
+        steps.clamp(0.0, (ENVELOPE_SILENT - 1) as f32) as u8
+    })
+}
+
+/// Fill `coeffs` from bin `start` up with copies of the spectrum in the octave below `start`,
+/// then scale each envelope band to its level in `levels` relative to `scale`
+/// Bins the copy leaves empty get pseudo-random noise seeded by `seed`, so sparsely coded
+/// material doesn't turn into a few loud tones
+fn extend_bandwidth(start: usize, levels: &[u8; ENVELOPE_BANDS], scale: f32, seed: u32, coeffs: &mut [f32])
+{
+    let n = coeffs.len();
+    let width = start - start / 2;
+    for k in start..n
+    {
+        coeffs[k] = coeffs[start - width + (k - start) % width];
+    }
+
+    for (b, &level) in levels.iter().enumerate()
+    {
+        let band = envelope_band(start, n, b);
+        if level == ENVELOPE_SILENT || band.is_empty()
+        {
+            coeffs[band].fill(0.0);
+            continue;
+        }
+
+        let (count, sum) = coeffs[band.clone()].iter().filter(|c| **c != 0.0).fold((0, 0.0f32), |(count, sum), c| (count + 1, sum + c.abs()));
+        let floor = if count > 0 { sum / count as f32 } else { 1.0 };
+        for k in band.clone()
+        {
+            if coeffs[k] == 0.0
+            {
+                let x = (seed ^ (k as u32).wrapping_mul(0x9E37_79B9)).wrapping_mul(0x85EB_CA6B);
+                coeffs[k] = floor * (((x ^ (x >> 15)) >> 8) as f32 / (1u32 << 23) as f32 - 1.0);
+            }
+        }
+
+        let band = &mut coeffs[band];
+        let rms = (dot(band, band) / band.len() as f32).sqrt();
+        let target = scale * 10f32.powf(-(level as f32) * ENVELOPE_STEP_DB / 20.0);
+        if rms > 0.0
+        {
+            band.iter_mut().for_each(|c| *c *= target / rms);
+        }
+    }
 }
 
 //
 // Decoder: per-channel overlap buffers, batch-parallel decode
 //
+
+/// Decode one frame into a windowed block (`2 * tables.n` samples) per channel
+fn decode_frame_blocks(
+    tables: &MdctTables,
+    window: &[f32],
+    frame: &EncodedFrame,
+    channels: usize,
+) -> Vec<Vec<f32>>
+{
+    let mut scratch = DecodeScratch::default();
+    let mut per_channel_blocks = Vec::with_capacity(channels);
+    decode_frame_into(tables, window, frame, channels, &mut scratch, &mut per_channel_blocks);
+    per_channel_blocks
+}
+
+/// Per-worker scratch buffers for [`decode_frame_into`]
+#[derive(Default)]
+struct DecodeScratch
+{
+    /// Dequantized MDCT coefficients
+    coeffs: Vec<f32>,
+    /// DCT-IV output before unfolding
+    dct: Vec<f32>,
+}
+
+/// Decode one frame into `per_channel_blocks`, reusing its allocations and the `scratch` buffers
+fn decode_frame_into(
+    tables: &MdctTables,
+    window: &[f32],
+    frame: &EncodedFrame,
+    channels: usize,
+    scratch: &mut DecodeScratch,
+    per_channel_blocks: &mut Vec<Vec<f32>>,
+)
+{
+    let coeffs = &mut scratch.coeffs;
+    let frame_size = 2 * tables.n;
+    per_channel_blocks.resize_with(channels, Vec::new);
+    for block in per_channel_blocks.iter_mut()
+    {
+        block.resize(frame_size, 0.0);
+    }
+
+    // Check if this frame uses raw PCM
+    if let Some(ref raw_pcm) = frame.raw_pcm
+    {
+        // Decode raw PCM: a whole frame per channel, one channel after another
+        // It was stored windowed once; windowing it again makes overlapping frames sum back to the input
+        for (ch, channel_block) in per_channel_blocks.iter_mut().enumerate()
+        {
+            for i in 0..frame_size
+            {
+                let sample_idx = ch * frame_size + i;
+                channel_block[i] = if sample_idx < raw_pcm.len()
+                {
+                    raw_pcm[sample_idx] as f32 / 32767.0 * window[i]
+                }
+                else
+                {
+                    0.0
+                };
+            }
+        }
+    }
+    else
+    {
+        // use same denominator as encoder
+        let max_q = (1u32 << (QUANTIZATION_BITS - 1)) as f32;
+
+        // Decode using MDCT
+        for (ch, out_block) in per_channel_blocks.iter_mut().enumerate()
+        {
+            // Reconstruct coefficients from sparse representation
+            coeffs.clear();
+            coeffs.resize(tables.n, 0.0);
+            // A malformed frame decodes as silence in the channels it lacks rather than panicking
+            let sparse_data = frame.sparse_coeffs_per_channel.get(ch).map_or(&[][..], Vec::as_slice);
+            let scale = frame.scale_factors.get(ch).copied().filter(|scale| scale.is_finite()).unwrap_or(0.0).max(1e-12);
+
+            // Fill in non-zero coefficients
+            for &(index, quantized_val) in sparse_data
+            {
+                if (index as usize) < tables.n
+                {
+                    coeffs[index as usize] = (quantized_val as f32 / max_q) * scale;
+                }
+            }
+
+            // Then the band above a bandwidth-extension crossover, from the one below
+            if let Some(envelope) = &frame.envelope
+                && let Some(levels) = envelope.levels.get(ch)
+                && (1..tables.n).contains(&(envelope.start as usize))
+            {
+                extend_bandwidth(envelope.start as usize, levels, scale, scale.to_bits(), coeffs);
+            }
+
+            // IMDCT to a whole frame
+            tables.imdct_block(coeffs, out_block, &mut scratch.dct);
+
+            // Apply window
+            for i in 0..frame_size
+            {
+                out_block[i] *= window[i];
+            }
+        }
+    }
+}
+
+/// Decode a batch of frames into the first `frames.len()` entries of `blocks`,
+/// in parallel if the `parallel` feature is enabled
+/// `blocks` is kept between batches so its buffers are reused, and each worker gets its own scratch buffers
+fn decode_batch(
+    tables: &MdctTables,
+    window: &[f32],
+    frames: &[EncodedFrame],
+    channels: usize,
+    blocks: &mut Vec<Vec<Vec<f32>>>,
+)
+{
+    if blocks.len() < frames.len()
+    {
+        blocks.resize_with(frames.len(), Vec::new);
+    }
+    let blocks = &mut blocks[..frames.len()];
+
+    #[cfg(feature = "parallel")]
+    {
+        blocks.par_iter_mut()
+              .zip(frames.par_iter())
+              .for_each_init(DecodeScratch::default, |scratch, (frame_blocks, frame)|
+              {
+                  decode_frame_into(tables, window, frame, channels, scratch, frame_blocks);
+              });
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        let mut scratch = DecodeScratch::default();
+        for (frame_blocks, frame) in blocks.iter_mut().zip(frames.iter())
+        {
+            decode_frame_into(tables, window, frame, channels, &mut scratch, frame_blocks);
+        }
+    }
+}
+
+/// Overlap-add state carried from one frame to the next: the second half of the
+/// previous frame's windowed block for each channel, and for streams with long-term prediction
+/// the audio decoded lately
+///
+/// Frames must be fed through [`Decoder::decode_frame`] in stream order; call
+/// [`OverlapState::reset`] after seeking, and [`OverlapState::flush`] after the last frame.
+/// With long-term prediction, seek to a frame that [restarts it](EncodedFrame::restarts_prediction).
+#[derive(Clone, Debug)]
+pub struct OverlapState
+{
+    buffers: Vec<Vec<f32>>,
+    /// The last `4 * hop` samples output for each channel, which long-term prediction draws on;
+    /// empty until a frame carrying prediction arrives, and emptied by one that restarts it
+    history: Vec<Vec<f32>>,
+    hop: usize,
+}
+
+impl OverlapState
+{
+    pub fn new(channels: usize) -> Self
+    {
+        Self::for_hop_size(channels, HOP_SIZE)
+    }
+
+    /// State for a stream whose frames advance by `hop_size` samples, as in [`AudioHeader::hop_size`]
+    pub fn with_hop_size(channels: usize, hop_size: usize) -> Result<Self>
+    {
+        check_hop_size(hop_size)?;
+        Ok(Self::for_hop_size(channels, hop_size))
+    }
+
+    pub(crate) fn for_hop_size(channels: usize, hop: usize) -> Self
+    {
+        Self
+        {
+            buffers: vec![vec![0.0f32; hop]; channels],
+            history: Vec::new(),
+            hop,
+        }
+    }
+
+    pub fn channels(&self) -> usize
+    {
+        self.buffers.len()
+    }
+
+    /// Samples per channel each frame adds
+    pub fn hop_size(&self) -> usize
+    {
+        self.hop
+    }
+
+    /// Clear the overlap and history, e.g. before decoding from a new position
+    pub fn reset(&mut self)
+    {
+        for ch in &mut self.buffers
+        {
+            ch.fill(0.0);
+        }
+        self.history.clear();
+    }
+
+    /// Channel `ch`'s history for long-term prediction, if a frame carrying it has been added
+    /// since the last restart
+    pub(crate) fn history(&self, ch: usize) -> Option<&[f32]>
+    {
+        self.history.get(ch).map(Vec::as_slice)
+    }
+
+    /// Return the tail of the last frame as one hop of interleaved samples and clear the state
+    pub fn flush(&mut self) -> Vec<f32>
+    {
+        let mut out = Vec::with_capacity(self.hop * self.channels());
+        self.flush_into(&mut out);
+        self.reset();
+        out
+    }
+
+    /// Overlap-add one decoded frame, appending one hop of interleaved samples to `out`
+    /// and keeping the second half of each block as the next overlap
+    /// If `frame` carries long-term prediction, its prediction from the history is added to its
+    /// blocks first; the output then extends the history, unless `frame` restarts prediction
+    pub(crate) fn add(&mut self, tables: &MdctTables, frame: &EncodedFrame, per_channel_blocks: &mut [Vec<f32>], out: &mut Vec<f32>)
+    {
+        let hop = self.hop;
+        let channels = self.buffers.len();
+        if frame.restarts_prediction()
+        {
+            self.history.clear();
+        }
+        else
+        {
+            if self.history.is_empty()
+            {
+                self.history = vec![vec![0.0f32; 4 * hop]; channels];
+            }
+            self.add_prediction(tables, &frame.ltp, per_channel_blocks);
+        }
+        let start = out.len();
+        let overlap = &mut self.buffers;
+
+        // Overlap-add and interleave
+        for i in 0..hop
+        {
+            for ch in 0..channels
+            {
+                out.push(overlap[ch][i] + per_channel_blocks[ch][i]);
+            }
+        }
+
+        // Update overlap buffers
+        for ch in 0..channels
+        {
+            let second_half = &per_channel_blocks[ch][hop..2 * hop];
+            overlap[ch].copy_from_slice(second_half);
+        }
+
+        for (ch, history) in self.history.iter_mut().enumerate()
+        {
+            history.copy_within(hop.., 0);
+            for i in 0..hop
+            {
+                history[3 * hop + i] = out[start + i * channels + ch];
+            }
+        }
+    }
+
+    /// Add each channel's long-term prediction from the history to its decoded block
+    /// Predictions out of range (which [`EncodedFrame::validate`] turns away) are ignored
+    fn add_prediction(&self, tables: &MdctTables, ltp: &[Option<LongTermPrediction>], per_channel_blocks: &mut [Vec<f32>])
+    {
+        let hop = self.hop;
+        let mut spectrum = vec![0.0f32; hop];
+        let mut predicted = vec![0.0f32; 2 * hop];
+        let mut scratch = Vec::new();
+        for ((prediction, history), block) in ltp.iter().zip(&self.history).zip(per_channel_blocks.iter_mut())
+        {
+            let Some(prediction) = prediction.filter(|p| (2 * hop..4 * hop).contains(&(p.lag as usize))
+                                                         && (p.gain_index as usize) < LTP_GAINS.len())
+            else
+            {
+                continue;
+            };
+            ltp_spectrum(tables, history, prediction.lag as usize, &mut spectrum, &mut scratch);
+            apply_ltp(&prediction, &mut spectrum);
+            tables.imdct_block(&spectrum, &mut predicted, &mut scratch);
+            for ((sample, p), w) in block.iter_mut().zip(&predicted).zip(tables.window.iter())
+            {
+                *sample += p * w;
+            }
+        }
+    }
+
+    /// Append the remaining overlap (tail of the last frame) as interleaved samples
+    pub(crate) fn flush_into(&self, out: &mut Vec<f32>)
+    {
+        for i in 0..self.hop
+        {
+            for ch in &self.buffers
+            {
+                out.push(ch[i]);
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Decoder 
 {
     tables: Arc<MdctTables>,
-    window: Arc<Vec<f32>>,
     sample_rate: u32, // informational (for playback)
     channels: usize,
 }
@@ -580,208 +2054,261 @@ impl Decoder
 {
     pub fn new(channels: usize, sample_rate: u32) -> Self
     {
-        let tables = Arc::new(MdctTables::new(HOP_SIZE));
-        let window = tables.window.clone();
+        let tables = MdctTables::shared(HOP_SIZE);
         Self 
         {
             tables,
-            window,
             sample_rate,
             channels,
         }
     }
 
-    /// Decode frames in batch-parallel fashion, producing interleaved chunks
+    /// Decode a single frame, returning one hop of interleaved samples (untrimmed)
+    ///
+    /// This is the building block behind [`Decoder::decode`] and [`Decoder::decode_streaming`],
+    /// for callers that want to do their own scheduling, seeking, or mixing.
+    /// The number of channels and the hop size are taken from `state`; channels missing from `frame`
+    /// decode as silence (see [`EncodedFrame::validate`]).
+    pub fn decode_frame(&self, frame: &EncodedFrame, state: &mut OverlapState) -> Vec<f32>
+    {
+        let channels = state.channels();
+        let tables = self.tables_for(state.hop_size());
+        let mut blocks = decode_frame_blocks(&tables, &tables.window, frame, channels);
+        let mut out = Vec::with_capacity(state.hop_size() * channels);
+        state.add(&tables, frame, &mut blocks, &mut out);
+        out
+    }
+
+    /// Tables for streams with hop size `hop`: this decoder's own, or shared ones for another size
+    fn tables_for(&self, hop: usize) -> Arc<MdctTables>
+    {
+        if hop == self.tables.n { self.tables.clone() } else { MdctTables::shared(hop) }
+    }
+
+    /// Decode frames in batch-parallel fashion on a background thread, producing interleaved chunks
+    /// Not available on wasm32, which has no `std::thread` support; use [`Decoder::decode`] there
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn decode_streaming(&mut self, encoded: Arc<EncodedAudio>, progress_sender: Option<Sender<Progress>>) -> Receiver<AudioChunk>
     {
-        let (tx, rx) = bounded(5);
-        let channels = encoded.header.channels as usize;
-        let tables = self.tables.clone();
-        let window = self.window.clone();
-        let mut overlap = vec![vec![0.0f32; HOP_SIZE]; channels];
+        self.decode_streaming_with(encoded, progress_sender, StreamOptions::default())
+    }
+
+    /// Like [`Decoder::decode_streaming`], with custom chunk size and channel depth
+    /// Players should use short chunks (e.g. [`StreamOptions::with_chunk_duration`] with ~100 ms)
+    /// so playback starts and stops promptly
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn decode_streaming_with(&mut self, encoded: Arc<EncodedAudio>, progress_sender: Option<Sender<Progress>>, options: StreamOptions) -> Receiver<AudioChunk>
+    {
+        let (tx, rx) = bounded(options.channel_depth);
+        let decoder = self.clone();
 
         std::thread::spawn(move ||
         {
-            let start_time = Instant::now();
-            let total_frames = encoded.frames.len();
-            if let Some(ref s) = progress_sender
-            {
-                let _ = s.send(Progress::Status(format!("Starting streaming decode of {} frames", total_frames)));
-            }
-
-            let mut chunk_samples: Vec<f32> = Vec::with_capacity(FRAMES_PER_CHUNK * HOP_SIZE * channels);
-            let mut idx = 0usize;
+            decoder.decode_chunks(&encoded, progress_sender, options.frames_per_chunk, |chunk| tx.send(chunk).is_ok());
+        });
 
-            while idx < total_frames
-            {
-                let batch_end = (idx + DECODE_BATCH).min(total_frames);
+        rx
+    }
 
-                // Decode frames in parallel
-                let batch_results: Vec<(usize, Vec<Vec<f32>>)> = (idx..batch_end).into_par_iter().map(|fi|
-                {
-                    let frame = &encoded.frames[fi];
-                    let mut per_channel_blocks: Vec<Vec<f32>> = Vec::with_capacity(channels);
+    /// Run the chunked decode loop, handing each interleaved chunk to `emit` on the calling thread
+    /// Stops early if `emit` returns false (e.g. because the receiving end hung up)
+    ///
+    /// Decoding is pipelined: a helper thread decodes up to DECODE_LOOKAHEAD batches ahead
+    /// (each batch in parallel if enabled) while this thread overlap-adds and emits the previous ones.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn decode_chunks<F>(&self, encoded: &EncodedAudio, progress_sender: Option<Sender<Progress>>, frames_per_chunk: usize, mut emit: F)
+    where
+        F: FnMut(AudioChunk) -> bool,
+    {
+        let start_time = Instant::now();
+        let channels = encoded.header.channels as usize;
+        let total_frames = encoded.frames.len();
+        // Chunks are counted in default-sized frames whatever the stream's hop, so they last as long
+        let chunk_len = frames_per_chunk.max(1) * HOP_SIZE * channels;
+        let hop = encoded.header.hop_size as usize;
+        let mut overlap = OverlapState::for_hop_size(channels, hop);
 
-                    // Check if this frame uses raw PCM
-                    if let Some(ref raw_pcm) = frame.raw_pcm
-                    {
-                        // Decode raw PCM: deinterleave and convert i16 to f32
-                        for ch in 0..channels
-                        {
-                            let mut channel_block = vec![0.0f32; FRAME_SIZE];
-                            // Fill first FRAME_SIZE with decoded samples
-                            for i in 0..FRAME_SIZE
-                            {
-                                let sample_idx = i * channels + ch;
-                                if sample_idx < raw_pcm.len()
-                                {
-                                    channel_block[i] = raw_pcm[sample_idx] as f32 / 32767.0;
-                                }
-                            }
-
-                            per_channel_blocks.push(channel_block);
-                        }
-                    }
-                    else
-                    {
-                        // Decode using MDCT
-                        for ch in 0..channels
-                        {
-                            // Reconstruct coefficients from sparse representation
-                            let mut coeffs = vec![0.0f32; tables.n];
-                            let sparse_data = &frame.sparse_coeffs_per_channel[ch];
-                            let scale = frame.scale_factors[ch].max(1e-12);
-
-                            // use same denominator as encoder
-                            let max_q = (1u32 << (QUANTIZATION_BITS - 1)) as f32;
-
-                            // Fill in non-zero coefficients
-                            for &(index, quantized_val) in sparse_data
-                            {
-                                if (index as usize) < tables.n
-                                {
-                                    coeffs[index as usize] = (quantized_val as f32 / max_q) * scale;
-                                }
-                            }
-
-                            // IMDCT to FRAME_SIZE
-                            let mut out_block = vec![0.0f32; FRAME_SIZE];
-                            tables.imdct_block(&coeffs, &mut out_block);
-
-                            // Apply window
-                            for i in 0..FRAME_SIZE
-                            {
-                                out_block[i] *= window[i];
-                            }
-
-                            per_channel_blocks.push(out_block);
-                        }
-                    }
+        if let Some(ref s) = progress_sender
+        {
+            let _ = s.send(Progress::Status(format!("Starting streaming decode of {} frames", total_frames)));
+        }
 
-                    (fi, per_channel_blocks)
-                }).collect();
+        let tables = self.tables_for(hop);
+        let tables: &MdctTables = &tables;
+        let window: &[f32] = &tables.window;
+        let frames = &encoded.frames;
 
-                // sort by frame index to preserve time order (par_iter may produce out-of-order)
-                let mut batch_results = batch_results;
-                batch_results.sort_unstable_by_key(|(fi, _)| *fi);
+        std::thread::scope(|scope|
+        {
+            // Decoded batches flow to this thread; their buffers flow back to be reused
+            let (batch_tx, batch_rx) = bounded::<(usize, Vec<Vec<Vec<f32>>>)>(DECODE_LOOKAHEAD);
+            let (recycle_tx, recycle_rx) = bounded::<Vec<Vec<Vec<f32>>>>(DECODE_LOOKAHEAD + 1);
 
-                for (_fi, per_channel_blocks) in batch_results.into_iter()
+            scope.spawn(move ||
+            {
+                let mut idx = 0usize;
+                while idx < total_frames
                 {
-                    // Overlap-add and interleave
-                    for i in 0..HOP_SIZE
-                    {
-                        for ch in 0..channels
-                        {
-                            let val = overlap[ch][i] + per_channel_blocks[ch][i];
-                            chunk_samples.push(val);
-                        }
-                    }
+                    let batch_end = (idx + DECODE_BATCH).min(total_frames);
+                    let mut batch_blocks = recycle_rx.try_recv().unwrap_or_default();
+                    decode_batch(tables, window, &frames[idx..batch_end], channels, &mut batch_blocks);
 
-                    // Update overlap buffers
-                    for ch in 0..channels
+                    // Fails once the consumer has stopped
+                    if batch_tx.send((batch_end - idx, batch_blocks)).is_err()
                     {
-                        let second_half = &per_channel_blocks[ch][HOP_SIZE..FRAME_SIZE];
-                        overlap[ch].copy_from_slice(second_half);
+                        return;
                     }
+                    idx = batch_end;
+                }
+            });
+
+            let mut chunk_samples: Vec<f32> = Vec::with_capacity(chunk_len);
+            let mut idx = 0usize;
+
+            for (batch_len, mut batch_blocks) in batch_rx.iter()
+            {
+                for (per_channel_blocks, frame) in batch_blocks[..batch_len].iter_mut().zip(&frames[idx..])
+                {
+                    overlap.add(tables, frame, per_channel_blocks, &mut chunk_samples);
 
                     // periodically flush chunk
-                    if chunk_samples.len() >= FRAMES_PER_CHUNK * HOP_SIZE * channels
+                    if chunk_samples.len() >= chunk_len
                     {
                         if let Some(ref s) = progress_sender
                         {
                             let progress = (idx as f32) / (total_frames as f32) * 100.0;
                             let _ = s.send(Progress::Decoding(progress));
                         }
-                        let _ = tx.send(AudioChunk { samples: chunk_samples.clone(), is_last: false });
-                        chunk_samples.clear();
+                        let chunk = AudioChunk { samples: std::mem::take(&mut chunk_samples), is_last: false };
+                        if !emit(chunk)
+                        {
+                            // Dropping batch_rx makes the decode thread stop at its next send
+                            return;
+                        }
+                        chunk_samples.reserve(chunk_len);
                     }
                     idx += 1;
                 }
+                let _ = recycle_tx.try_send(batch_blocks);
             }
 
             // Final overlap
-            for i in 0..HOP_SIZE
-            {
-                for ch in 0..channels
-                {
-                    chunk_samples.push(overlap[ch][i]);
-                }
-            }
+            overlap.flush_into(&mut chunk_samples);
 
             // send last chunk
-            let _ = tx.send(AudioChunk { samples: chunk_samples.clone(), is_last: true });
+            emit(AudioChunk { samples: chunk_samples, is_last: true });
 
             if let Some(ref s) = progress_sender
             {
                 let _ = s.send(Progress::Complete(format!("Decoded {} frames in {:.2}s", total_frames, start_time.elapsed().as_secs_f32())));
             }
         });
-
-        rx
     }
 
-    /// convenience decode (synchronous)
+    /// convenience decode (synchronous, runs on the calling thread)
     pub fn decode(&mut self, encoded: &EncodedAudio, progress_sender: Option<Sender<Progress>>) -> Result<Vec<f32>> 
     {
-        let arc = Arc::new(encoded.clone());
-        let rx = self.decode_streaming(arc, progress_sender);
         let mut all = Vec::new();
-        while let Ok(chunk) = rx.recv() 
+        self.decode_into(encoded, progress_sender, &mut all)?;
+        Ok(all)
+    }
+
+    /// Decode `encoded` and append the (gapless-trimmed) samples to `out`
+    /// Lets callers concatenate several files into one buffer without an intermediate copy per file
+    pub fn decode_into(&mut self, encoded: &EncodedAudio, progress_sender: Option<Sender<Progress>>, out: &mut Vec<f32>) -> Result<()>
+    {
+        let channels = encoded.header.channels as usize;
+        let total_frames = encoded.frames.len();
+        let hop = encoded.header.hop_size as usize;
+        let tables = self.tables_for(hop);
+        let mut overlap = OverlapState::for_hop_size(channels, hop);
+        let base = out.len();
+        out.reserve((total_frames + 1) * hop * channels);
+
+        // gapless trimming: the delay is dropped as soon as it has been decoded,
+        // so only the first batch is shifted rather than the whole file
+        // (the delay counts samples per channel, the output is interleaved)
+        let delay = encoded.gapless_info.encoder_delay as usize * channels;
+        let original_length = encoded.gapless_info.original_length as usize;
+        let mut delay_trimmed = false;
+        let mut trim_delay = |out: &mut Vec<f32>|
+        {
+            if !delay_trimmed && out.len() - base > delay
+            {
+                out.drain(base..base + delay);
+                delay_trimmed = true;
+            }
+        };
+
+        let mut batch_blocks = Vec::new();
+        let mut idx = 0usize;
+        while idx < total_frames
         {
-            all.extend(chunk.samples);
-            if chunk.is_last { break; }
+            let batch_end = (idx + DECODE_BATCH).min(total_frames);
+            decode_batch(&tables, &tables.window, &encoded.frames[idx..batch_end], channels, &mut batch_blocks);
+            for (per_channel_blocks, frame) in batch_blocks[..batch_end - idx].iter_mut().zip(&encoded.frames[idx..batch_end])
+            {
+                overlap.add(&tables, frame, per_channel_blocks, out);
+            }
+            trim_delay(out);
+            idx = batch_end;
+
+            if let Some(ref s) = progress_sender
+            {
+                let _ = s.send(Progress::Decoding((idx as f32) / (total_frames as f32) * 100.0));
+            }
         }
+        overlap.flush_into(out);
+        trim_delay(out);
 
-        // gapless trimming
-        let delay = encoded.gapless_info.encoder_delay as usize;
-        let original_length = encoded.gapless_info.original_length as usize;
-        if all.len() > delay 
+        if out.len() - base > original_length 
         {
-            all.drain(0..delay);
+            out.truncate(base + original_length);
         }
-        if all.len() > original_length 
+
+        if let Some(ref s) = progress_sender
         {
-            all.truncate(original_length);
+            let _ = s.send(Progress::Complete(format!("Decoded {} frames", total_frames)));
         }
 
-        Ok(all)
+        Ok(())
     }
 }
 
 //
-// Save / load binary
+// Save / load binary (see the container module for the file layout)
 //
+
+/// Serialize `encoded` to an in-memory GLC byte buffer
+pub fn encoded_to_bytes(encoded: &EncodedAudio) -> Result<Vec<u8>>
+{
+    crate::container::write_encoded(Vec::new(), encoded)
+}
+
+/// Deserialize a GLC byte buffer (as produced by [`encoded_to_bytes`])
+pub fn encoded_from_bytes(data: &[u8]) -> Result<EncodedAudio>
+{
+    crate::container::read_encoded(std::io::Cursor::new(data))
+}
+
 pub fn save_encoded(encoded: &EncodedAudio, path: &std::path::Path) -> Result<()> 
 {
-    let data = bincode::serialize(encoded)?;
-    std::fs::write(path, data)?;
+    let file = std::fs::File::create(path)?;
+    crate::container::write_encoded(std::io::BufWriter::new(file), encoded)?;
     Ok(())
 }
 
 pub fn load_encoded(path: &std::path::Path) -> Result<EncodedAudio> 
 {
-    let data = std::fs::read(path)?;
-    let encoded: EncodedAudio = bincode::deserialize(&data)?;
-    Ok(encoded)
+    let file = std::fs::File::open(path)?;
+    crate::container::read_encoded(std::io::BufReader::new(file))
 }
 
+/// Load a `.glc` file like [`load_encoded`], with `options` (see [`crate::container::read_encoded_with`])
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_encoded_with(path: &std::path::Path, options: &crate::container::DecodeOptions)
+    -> Result<(EncodedAudio, Option<crate::container::RepairReport>)>
+{
+    let file = std::fs::File::open(path)?;
+    crate::container::read_encoded_with(std::io::BufReader::new(file), options)
+}
@@ -0,0 +1,164 @@
+//! Objective quality measurements between an original signal and its decoded version
+use serde::Serialize;
+use crate::metrics::{measure, power_ratio_db, Metrics};
+
+/// Centre frequencies of the octave bands reported by [`compare`]
+/// Bands at or above 40% of the sample rate are left out
+pub const BAND_CENTERS_HZ: &[f32] = &[63.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0];
+
+/// SNR within one octave band
+#[derive(Clone, Debug, Serialize)]
+pub struct BandSnr
+{
+    pub center_hz: f32,
+    pub snr_db: f64,
+}
+
+/// Differences between an original and a decoded signal
+/// SNRs are infinite when there is no error (serialized to JSON as null)
+#[derive(Clone, Debug, Serialize)]
+pub struct QualityReport
+{
+    /// Samples per channel compared, i.e. the length of the shorter signal
+    pub compared_samples: u64,
+    /// Decoded length minus original length, in samples per channel
+    pub length_difference: i64,
+    pub snr_db: f64,
+    pub bands: Vec<BandSnr>,
+    /// Largest absolute sample difference
+    pub peak_difference: f32,
+    /// `peak_difference` relative to full scale
+    pub peak_difference_dbfs: f64,
+    /// RMS level of the decoded signal relative to the original
+    pub loudness_difference_db: f64,
+    /// SNR per critical band, A-weighted SNR, and spectral distortion
+    pub metrics: Metrics,
+}
+
+/// Compare interleaved `decoded` against `original`, sample by sample
+/// Only the overlapping part is measured; a length mismatch is reported in `length_difference`
+pub fn compare(original: &[f32], decoded: &[f32], sample_rate: u32, channels: u16) -> QualityReport
+{
+    let channels = channels.max(1) as usize;
+    let frames = original.len().min(decoded.len()) / channels;
+    let length_difference = (decoded.len() / channels) as i64 - (original.len() / channels) as i64;
+    let original = &original[..frames * channels];
+    let decoded = &decoded[..frames * channels];
+
+    let mut signal = 0.0f64;
+    let mut noise = 0.0f64;
+    let mut decoded_energy = 0.0f64;
+    let mut peak = 0.0f32;
+    for (&a, &b) in original.iter().zip(decoded)
+    {
+        let error = (a - b) as f64;
+        signal += a as f64 * a as f64;
+        noise += error * error;
+        decoded_energy += b as f64 * b as f64;
+        peak = peak.max((a - b).abs());
+    }
+
+    let bands = BAND_CENTERS_HZ.iter()
+                               .filter(|&&center| center < sample_rate as f32 * 0.4)
+                               .map(|&center| BandSnr
+                               {
+                                   center_hz: center,
+                                   snr_db: band_snr(original, decoded, channels, center, sample_rate),
+                               })
+                               .collect();
+
+    let loudness_difference_db = if signal == 0.0 && decoded_energy == 0.0
+    {
+        0.0
+    }
+    else
+    {
+        10.0 * (decoded_energy / signal).log10()
+    };
+
+    QualityReport
+    {
+        compared_samples: frames as u64,
+        length_difference,
+        snr_db: power_ratio_db(signal, noise),
+        bands,
+        peak_difference: peak,
+        peak_difference_dbfs: 20.0 * (peak as f64).log10(),
+        loudness_difference_db,
+        metrics: measure(original, decoded, sample_rate, channels as u16),
+    }
+}
+
+/// SNR after passing the original and the error through an octave band-pass filter
+/// (two cascaded sections, so a band rejects content four octaves away by about 54 dB)
+fn band_snr(original: &[f32], decoded: &[f32], channels: usize, center: f32, sample_rate: u32) -> f64
+{
+    let section = Biquad::band_pass(center as f64, sample_rate as f64);
+    let filter = [section.clone(), section];
+    let mut signal_filters = vec![filter.clone(); channels];
+    let mut error_filters = vec![filter; channels];
+
+    let mut signal = 0.0f64;
+    let mut noise = 0.0f64;
+    for (a_frame, b_frame) in original.chunks_exact(channels).zip(decoded.chunks_exact(channels))
+    {
+        for (ch, (&a, &b)) in a_frame.iter().zip(b_frame).enumerate()
+        {
+            let [s1, s2] = &mut signal_filters[ch];
+            let s = s2.process(s1.process(a as f64));
+            let [e1, e2] = &mut error_filters[ch];
+            let e = e2.process(e1.process((a - b) as f64));
+            signal += s * s;
+            noise += e * e;
+        }
+    }
+
+    power_ratio_db(signal, noise)
+}
+
+/// Direct form I biquad
+#[derive(Clone)]
+struct Biquad
+{
+    b0: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    x1: f64,
+    x2: f64,
+    y1: f64,
+    y2: f64,
+}
+
+impl Biquad
+{
+    /// Band-pass with 0 dB peak gain and a one-octave bandwidth (RBJ cookbook), b1 = 0
+    fn band_pass(center: f64, sample_rate: f64) -> Self
+    {
+        let w0 = 2.0 * std::f64::consts::PI * center / sample_rate;
+        let alpha = w0.sin() * (std::f64::consts::LN_2 / 2.0 * w0 / w0.sin()).sinh();
+        let a0 = 1.0 + alpha;
+
+        Self
+        {
+            b0: alpha / a0,
+            b2: -alpha / a0,
+            a1: -2.0 * w0.cos() / a0,
+            a2: (1.0 - alpha) / a0,
+            x1: 0.0,
+            x2: 0.0,
+            y1: 0.0,
+            y2: 0.0,
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64
+    {
+        let y = self.b0 * x + self.b2 * self.x2 - self.a1 * self.y1 - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
@@ -0,0 +1,93 @@
+//! Default options for the `glc` binary, read from `~/.config/glc/config.toml` (or `--config FILE`)
+//! Options given on the command line always take precedence over the config file
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use crate::codec::QUALITY_RANGE;
+
+/// Every key is optional; unknown keys are an error so typos don't go unnoticed
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Config
+{
+    /// Encoder quality, 0.1 to 1.0
+    pub quality: Option<f32>,
+    /// Directory to write encoded and decoded files to (`~/` is expanded)
+    pub out_dir: Option<PathBuf>,
+    /// FLAC compression level when decoding, 0 to 8
+    pub flac_level: Option<u8>,
+    /// Worker threads for encoding and decoding (0 = one per core)
+    pub threads: Option<usize>,
+    /// Audio output device for `glc play`, by name
+    pub device: Option<String>,
+}
+
+impl Config
+{
+    /// Parse and validate the contents of a config file
+    pub fn parse(text: &str) -> Result<Self>
+    {
+        let mut config: Config = toml::from_str(text)?;
+
+        if let Some(quality) = config.quality
+            && !QUALITY_RANGE.contains(&quality)
+        {
+            return Err(anyhow!("quality must be between {} and {}, got {}", QUALITY_RANGE.start(), QUALITY_RANGE.end(), quality));
+        }
+        if let Some(level) = config.flac_level
+            && level > 8
+        {
+            return Err(anyhow!("flac_level must be between 0 and 8, got {}", level));
+        }
+        config.out_dir = config.out_dir.map(|dir| expand_home(&dir));
+
+        Ok(config)
+    }
+
+    pub fn load(path: &Path) -> Result<Self>
+    {
+        let text = std::fs::read_to_string(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| anyhow!("{}: {}", path.display(), e))
+    }
+
+    /// Load `path` if given (it must exist), otherwise the file at [`Config::default_path`] if there is one
+    pub fn load_or_default(path: Option<&Path>) -> Result<Self>
+    {
+        match path
+        {
+            Some(path) => Self::load(path),
+            None => match Self::default_path()
+            {
+                Some(path) if path.is_file() => Self::load(&path),
+                _ => Ok(Self::default()),
+            },
+        }
+    }
+
+    /// `$XDG_CONFIG_HOME/glc/config.toml`, falling back to `~/.config/glc/config.toml`
+    /// (`%APPDATA%\glc\config.toml` on Windows)
+    pub fn default_path() -> Option<PathBuf>
+    {
+        let env_dir = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+
+        let base = if cfg!(windows)
+        {
+            env_dir("APPDATA")
+        }
+        else
+        {
+            env_dir("XDG_CONFIG_HOME").or_else(|| env_dir("HOME").map(|home| home.join(".config")))
+        };
+        base.map(|dir| dir.join("glc").join("config.toml"))
+    }
+}
+
+/// Replace a leading `~` with the home directory
+fn expand_home(path: &Path) -> PathBuf
+{
+    match (path.strip_prefix("~"), std::env::var_os("HOME"))
+    {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
@@ -0,0 +1,1813 @@
+//! GLC container format and `std::io` based streaming reader/writer
+//!
+//! Layout (all integers little-endian):
+//! - Signature `GLC\0`, format version (u16)
+//! - Stream info: sample rate (u32), channels (u16), and from version 3 the hop size (u16)
+//! - Frame records: payload length (u32), CRC-32 of payload (u32), bincode [`EncodedFrame`],
+//!   then in version 4 its long-term predictions if it carries any, then its spectral envelope
+//!   if it has one (after empty predictions if need be), which older readers skip
+//! - End-of-frames marker (u32::MAX), then a footer record (length, CRC-32, bincode [`Footer`])
+//!   holding the full header, gapless info, and a seek table of frame offsets, each stored as its
+//!   distance from the previous one (u32)
+//! - Optional tag record (length, CRC-32, bincode [`Tags`]), omitted when there are no tags or tracks
+//! - Optional track record (length, CRC-32, bincode `Vec<`[`Track`]`>`) for chained streams
+//!   such as whole albums, omitted when the stream is a single track
+//! - Trailer: byte offset of the end-of-frames marker (u64), signature `GLCF`
+//!
+//! Because the footer is written last, [`GlcEncoder`] only needs `Write` (sockets, pipes, ...),
+//! while [`GlcDecoder`] uses `Seek` to jump to the footer and to individual frames.
+//! Tags and tracks sit between the footer and the trailer, so [`rewrite_tags`] can replace them
+//! without touching the frame data.
+//! [`MappedGlcFile`] does the same over a memory-mapped file, deserializing frames in place.
+use anyhow::{anyhow, Result};
+use serde::{Serialize, Deserialize};
+use std::io::{Read, Write, Seek, SeekFrom};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs::{File, OpenOptions}, io::Cursor, path::Path};
+use crate::codec::{check_header, check_hop_size, check_stream_format, frame_layout, AdaptiveQuality, Encoder, GlcError, HighPass, OverlapState, EncodedAudio, EncodedFrame, AudioHeader, GaplessInfo, HOP_SIZE, LTP_RESTART_INTERVAL, MAX_CHANNELS};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// GLC file signature
+pub const GLC_SIGNATURE: [u8; 4] = *b"GLC\0";
+
+/// Trailer signature marking the end of a complete GLC stream
+const FOOTER_SIGNATURE: [u8; 4] = *b"GLCF";
+
+/// Newest container format version this crate reads and writes
+/// Version 2 added the channel mask to the footer; version 1 streams are still read.
+/// Version 3 added the hop size to the stream header and is only written for streams whose hop
+/// isn't the default (low-delay ones), so everything else stays readable by version 2 readers.
+/// Version 4 added long-term prediction to frames and is only written for streams that use it,
+/// which older readers would otherwise decode wrongly
+pub const FORMAT_VERSION: u16 = 4;
+
+/// Length prefix used in place of a frame record to mark the end of the frame data
+pub(crate) const END_OF_FRAMES: u32 = u32::MAX;
+
+/// Size of signature + version + stream info, before version 3 added the hop size
+const STREAM_HEADER_SIZE: u64 = 4 + 2 + 4 + 2;
+
+/// Size of the trailer (footer offset + signature)
+const TRAILER_SIZE: u64 = 8 + 4;
+
+/// Upper bound on a single record's payload, to avoid huge allocations on corrupt input
+const MAX_RECORD_SIZE: u32 = 1 << 28;
+
+/// Metadata stored after the last frame
+#[derive(Serialize, Debug, Clone)]
+struct Footer
+{
+    header: AudioHeader,
+    gapless_info: GaplessInfo,
+    /// Byte offset of every frame record, for random access
+    #[serde(with = "seek_table")]
+    frame_offsets: Vec<u64>,
+    /// Speaker layout as a WAVE_FORMAT_EXTENSIBLE channel mask (0 = unspecified)
+    channel_mask: u32,
+    /// The header's high-pass cutoff; appended after the other fields, so readers that predate it
+    /// ignore it and footers written before it simply end at the channel mask
+    highpass: Option<f32>,
+    /// The header's bandwidth-extension crossover, appended after the high-pass cutoff in the same
+    /// way; left out when there is none, so such footers are unchanged
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bandwidth_extension: Option<f32>,
+}
+
+/// Footer of format versions 2 and 3 before the high-pass cutoff was appended
+#[derive(Deserialize)]
+struct FooterV2
+{
+    header: AudioHeader,
+    gapless_info: GaplessInfo,
+    #[serde(with = "seek_table")]
+    frame_offsets: Vec<u64>,
+    channel_mask: u32,
+}
+
+/// Footer of format version 1, before the channel mask was added
+#[derive(Deserialize)]
+struct FooterV1
+{
+    header: AudioHeader,
+    gapless_info: GaplessInfo,
+    #[serde(with = "seek_table")]
+    frame_offsets: Vec<u64>,
+}
+
+/// Stores frame offsets as distances from the previous offset (the first from the start of the
+/// stream), which fit in a u32 because records are capped at `MAX_RECORD_SIZE`
+mod seek_table
+{
+    use serde::{Deserialize, Deserializer, Serialize, Serializer, ser::Error};
+
+    pub fn serialize<S: Serializer>(offsets: &[u64], serializer: S) -> Result<S::Ok, S::Error>
+    {
+        let mut previous = 0;
+        let deltas = offsets.iter().map(|&offset|
+        {
+            let delta = offset.checked_sub(previous).and_then(|delta| u32::try_from(delta).ok());
+            previous = offset;
+            delta.ok_or_else(|| S::Error::custom("frame offsets out of order"))
+        }).collect::<Result<Vec<u32>, _>>()?;
+        deltas.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u64>, D::Error>
+    {
+        let mut offset = 0u64;
+        Ok(Vec::<u32>::deserialize(deserializer)?.into_iter().map(|delta|
+        {
+            offset += delta as u64;
+            offset
+        }).collect())
+    }
+}
+
+/// Key/value metadata (artist, title, ...) stored alongside the audio
+/// Keys are compared case-insensitively and stored lowercase; a key may hold several values,
+/// as with multi-valued Vorbis comments
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Tags
+{
+    entries: Vec<(String, String)>,
+}
+
+impl Tags
+{
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    /// First value stored under `key`
+    pub fn get(&self, key: &str) -> Option<&str>
+    {
+        let key = key.to_lowercase();
+        self.entries.iter().find(|(k, _)| *k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// Replace every value of `key` with `value`
+    pub fn set(&mut self, key: &str, value: impl Into<String>)
+    {
+        self.remove(key);
+        self.add(key, value);
+    }
+
+    /// Append another value for `key`, keeping existing ones
+    pub fn add(&mut self, key: &str, value: impl Into<String>)
+    {
+        self.entries.push((key.to_lowercase(), value.into()));
+    }
+
+    /// Remove every value of `key`, returning whether any were present
+    pub fn remove(&mut self, key: &str) -> bool
+    {
+        let key = key.to_lowercase();
+        let before = self.entries.len();
+        self.entries.retain(|(k, _)| *k != key);
+        self.entries.len() != before
+    }
+
+    pub fn clear(&mut self)
+    {
+        self.entries.clear();
+    }
+
+    /// All `(key, value)` pairs in insertion order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)>
+    {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.entries.is_empty()
+    }
+}
+
+/// One track of a chained stream, e.g. an album encoded as a single gapless file
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Track
+{
+    /// First sample of the track in the decoded stream, per channel
+    pub start: u64,
+    /// Length in samples per channel
+    pub length: u64,
+    pub tags: Tags,
+}
+
+/// CRC-32 (IEEE 802.3, reflected polynomial 0xEDB88320) lookup table
+const CRC32_TABLE: [u32; 256] = build_crc32_table();
+
+const fn build_crc32_table() -> [u32; 256]
+{
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256
+    {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8
+        {
+            if crc & 1 != 0
+            {
+                crc = (crc >> 1) ^ 0xEDB88320;
+            }
+            else
+            {
+                crc >>= 1;
+            }
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Compute the CRC-32 checksum of `data`
+pub fn crc32(data: &[u8]) -> u32
+{
+    let mut crc = 0xFFFFFFFFu32;
+    for &byte in data
+    {
+        crc = CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+/// Writes the container structure: stream header, frame records, footer
+/// Tracks the byte position itself so the underlying writer doesn't need `Seek`
+/// The stream header goes out with the first record, so the format version can still change until then
+struct FrameWriter<W: Write>
+{
+    writer: W,
+    sample_rate: u32,
+    channels: u16,
+    hop_size: usize,
+    /// Whether frames may carry long-term prediction, which needs format version 4
+    long_term_prediction: bool,
+    /// Whether the stream header has been written
+    started: bool,
+    position: u64,
+    frame_offsets: Vec<u64>,
+}
+
+impl<W: Write> FrameWriter<W>
+{
+    fn new(writer: W, sample_rate: u32, channels: u16, hop_size: usize) -> Result<Self>
+    {
+        check_stream_format(sample_rate, channels)?;
+        Ok(Self
+        {
+            writer,
+            sample_rate,
+            channels,
+            hop_size,
+            long_term_prediction: false,
+            started: false,
+            position: 0,
+            frame_offsets: Vec::new(),
+        })
+    }
+
+    /// Write the stream header if it hasn't been yet
+    fn start(&mut self) -> Result<()>
+    {
+        if self.started
+        {
+            return Ok(());
+        }
+        let version = version_for(self.hop_size, self.long_term_prediction);
+        self.writer.write_all(&GLC_SIGNATURE)?;
+        self.writer.write_all(&version.to_le_bytes())?;
+        self.writer.write_all(&self.sample_rate.to_le_bytes())?;
+        self.writer.write_all(&self.channels.to_le_bytes())?;
+        if version >= 3
+        {
+            self.writer.write_all(&(self.hop_size as u16).to_le_bytes())?;
+        }
+        self.started = true;
+        self.position = stream_header_size(version);
+        Ok(())
+    }
+
+    /// Write a length + CRC-32 prefixed record
+    fn write_record(&mut self, payload: &[u8]) -> Result<()>
+    {
+        self.start()?;
+        let len = u32::try_from(payload.len())
+            .ok()
+            .filter(|&len| len <= MAX_RECORD_SIZE)
+            .ok_or_else(|| anyhow!("Record too large: {} bytes", payload.len()))?;
+
+        self.writer.write_all(&len.to_le_bytes())?;
+        self.writer.write_all(&crc32(payload).to_le_bytes())?;
+        self.writer.write_all(payload)?;
+        self.position += 8 + payload.len() as u64;
+        Ok(())
+    }
+
+    fn write_frame(&mut self, frame: &EncodedFrame) -> Result<()>
+    {
+        self.write_frame_bytes(&serialize_frame(frame)?)
+    }
+
+    fn write_frame_bytes(&mut self, payload: &[u8]) -> Result<()>
+    {
+        self.start()?;
+        self.frame_offsets.push(self.position);
+        self.write_record(payload)
+    }
+
+    fn finish(mut self, header: AudioHeader, gapless_info: GaplessInfo, channel_mask: u32, tags: &Tags, tracks: &[Track])
+        -> Result<W>
+    {
+        self.start()?;
+        let footer_offset = self.position;
+        self.writer.write_all(&END_OF_FRAMES.to_le_bytes())?;
+        self.position += 4;
+
+        let footer = Footer
+        {
+            highpass: header.highpass,
+            bandwidth_extension: header.bandwidth_extension,
+            header,
+            gapless_info,
+            frame_offsets: std::mem::take(&mut self.frame_offsets),
+            channel_mask,
+        };
+        let payload = bincode::serialize(&footer)?;
+        self.write_record(&payload)?;
+
+        write_metadata_and_trailer(&mut self.writer, tags, tracks, footer_offset)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Format version to write for a stream with `hop_size`, with or without long-term prediction:
+/// the oldest one that can describe it
+fn version_for(hop_size: usize, long_term_prediction: bool) -> u16
+{
+    if long_term_prediction { 4 } else if hop_size == HOP_SIZE { 2 } else { 3 }
+}
+
+/// Serialize a frame as a frame record's payload, with its long-term predictions after it if it
+/// carries any, and then its spectral envelope if it has one
+pub(crate) fn serialize_frame(frame: &EncodedFrame) -> Result<Vec<u8>>
+{
+    let mut payload = bincode::serialize(frame)?;
+    if !frame.ltp.is_empty() || frame.envelope.is_some()
+    {
+        bincode::serialize_into(&mut payload, &frame.ltp)?;
+    }
+    if let Some(envelope) = &frame.envelope
+    {
+        bincode::serialize_into(&mut payload, envelope)?;
+    }
+    Ok(payload)
+}
+
+/// Deserialize a frame record's payload as written by [`serialize_frame`]
+pub(crate) fn deserialize_frame(payload: &[u8]) -> Result<EncodedFrame>
+{
+    let mut rest = payload;
+    let mut frame: EncodedFrame = bincode::deserialize_from(&mut rest)?;
+    if !rest.is_empty()
+    {
+        frame.ltp = bincode::deserialize_from(&mut rest)?;
+    }
+    if !rest.is_empty()
+    {
+        frame.envelope = Some(bincode::deserialize(rest)?);
+    }
+    Ok(frame)
+}
+
+/// Size of the stream header in format `version`
+fn stream_header_size(version: u16) -> u64
+{
+    if version >= 3 { STREAM_HEADER_SIZE + 2 } else { STREAM_HEADER_SIZE }
+}
+
+/// Write the optional tag and track records followed by the trailer
+/// The tag record is written (possibly empty) whenever there are tracks, since the track record follows it
+fn write_metadata_and_trailer<W: Write>(writer: &mut W, tags: &Tags, tracks: &[Track], footer_offset: u64) -> Result<()>
+{
+    let mut write_metadata_record = |payload: Vec<u8>, what: &str| -> Result<()>
+    {
+        let len = u32::try_from(payload.len())
+            .ok()
+            .filter(|&len| len <= MAX_RECORD_SIZE)
+            .ok_or_else(|| anyhow!("{} too large: {} bytes", what, payload.len()))?;
+        writer.write_all(&len.to_le_bytes())?;
+        writer.write_all(&crc32(&payload).to_le_bytes())?;
+        writer.write_all(&payload)?;
+        Ok(())
+    };
+
+    if !tags.is_empty() || !tracks.is_empty()
+    {
+        write_metadata_record(bincode::serialize(tags)?, "Tags")?;
+    }
+    if !tracks.is_empty()
+    {
+        write_metadata_record(bincode::serialize(tracks)?, "Track list")?;
+    }
+
+    writer.write_all(&footer_offset.to_le_bytes())?;
+    writer.write_all(&FOOTER_SIGNATURE)?;
+    Ok(())
+}
+
+/// Write an already encoded stream to `writer`
+pub fn write_encoded<W: Write>(writer: W, encoded: &EncodedAudio) -> Result<W>
+{
+    check_hop_size(encoded.header.hop_size as usize)?;
+    let mut frame_writer = FrameWriter::new(writer, encoded.header.sample_rate, encoded.header.channels, encoded.header.hop_size as usize)?;
+    frame_writer.long_term_prediction = encoded.header.long_term_prediction || encoded.frames.iter().any(|f| !f.restarts_prediction());
+    for frame in &encoded.frames
+    {
+        frame_writer.write_frame(frame)?;
+    }
+    frame_writer.finish(encoded.header.clone(), encoded.gapless_info.clone(), 0, &Tags::default(), &[])
+}
+
+/// Read a complete stream from `reader`
+/// Also accepts the plain bincode files written by version 0.5.0 and earlier
+pub fn read_encoded<R: Read + Seek>(mut reader: R) -> Result<EncodedAudio>
+{
+    let start = reader.stream_position()?;
+    let mut signature = [0u8; 4];
+    let has_signature = reader.read_exact(&mut signature).is_ok() && signature == GLC_SIGNATURE;
+    reader.seek(SeekFrom::Start(start))?;
+
+    if has_signature
+    {
+        GlcDecoder::new(reader)?.read_all()
+    }
+    else
+    {
+        let encoded: EncodedAudio = bincode::deserialize_from(reader)?;
+        check_header(&encoded.header, &encoded.gapless_info, encoded.frames.len())?;
+        for (index, frame) in encoded.frames.iter().enumerate()
+        {
+            frame.validate(encoded.header.channels as usize, HOP_SIZE).map_err(|e| anyhow!("Frame {}: {}", index, e))?;
+        }
+        Ok(encoded)
+    }
+}
+
+/// Settings for reading a stream with [`read_encoded_with`]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DecodeOptions
+{
+    /// Read a stream that was cut off partway (an interrupted copy or download) up to its last
+    /// complete frame, instead of failing because its footer is missing
+    pub allow_truncated: bool,
+}
+
+/// Read a complete stream from `reader` like [`read_encoded`], with `options`
+/// Returns the stream and, if it was truncated and read anyway because of `allow_truncated`,
+/// a report of what was recovered; streams damaged in other ways still fail
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_encoded_with<R: Read + Seek>(mut reader: R, options: &DecodeOptions) -> Result<(EncodedAudio, Option<RepairReport>)>
+{
+    let start = reader.stream_position()?;
+    let error = match read_encoded(&mut reader)
+    {
+        Ok(encoded) => return Ok((encoded, None)),
+        Err(error) if !options.allow_truncated => return Err(error),
+        Err(error) => error,
+    };
+
+    // Salvage the frames before the cut as `repair` would, but only if the footer is what's missing
+    let mut data = Vec::new();
+    reader.seek(SeekFrom::Start(start))?;
+    reader.read_to_end(&mut data)?;
+    match repair(&data, Vec::new())
+    {
+        Ok((repaired, report)) if report.truncated => Ok((read_encoded(Cursor::new(repaired))?, Some(report))),
+        _ => Err(error),
+    }
+}
+
+/// Streaming encoder: accepts interleaved PCM in arbitrary-sized pieces and writes
+/// GLC frames to `W` as soon as each one is complete
+///
+/// The output is identical to encoding the whole signal with [`Encoder::encode`]
+/// and writing it with [`write_encoded`].
+pub struct GlcEncoder<W: Write>
+{
+    frame_writer: FrameWriter<W>,
+    encoder: Encoder,
+    channels: u16,
+    /// Per-channel samples not yet covered by an emitted frame (starts with the encoder delay)
+    pending: Vec<Vec<f32>>,
+    /// Interleaved samples received so far
+    total_samples: u64,
+    /// Filter state carried between writes, if the encoder has a high-pass set
+    highpass: Option<HighPass>,
+    /// The decoder's view of the stream so far, if frames are predicted from it
+    prediction: Option<OverlapState>,
+    /// Average perceptual entropy so far, for adaptive quality
+    adaptive: AdaptiveQuality,
+    channel_mask: u32,
+    tags: Tags,
+    /// Tracks started so far; lengths are filled in by `finish`
+    tracks: Vec<Track>,
+}
+
+impl<W: Write> GlcEncoder<W>
+{
+    /// Start a new stream; the stream header is written with the first frame
+    pub fn new(writer: W, sample_rate: u32, channels: u16) -> Result<Self>
+    {
+        Self::with_hop_size(writer, sample_rate, channels, HOP_SIZE)
+    }
+
+    /// Start a new stream whose frames advance by `hop_size` samples (see [`Encoder::with_hop_size`])
+    ///
+    /// With [`LOW_DELAY_HOP_SIZE`](crate::codec::LOW_DELAY_HOP_SIZE), each frame is encoded and
+    /// written as soon as its last sample arrives instead of in parallel batches, so a frame is out
+    /// within one hop of the audio it covers
+    pub fn with_hop_size(writer: W, sample_rate: u32, channels: u16, hop_size: usize) -> Result<Self>
+    {
+        Self::with_encoder(writer, Encoder::with_hop_size(sample_rate, hop_size)?, channels)
+    }
+
+    /// Start a new stream encoded by `encoder`, taking its sample rate, hop size, and settings
+    /// (see [`EncodeOptions::encoder`](crate::audio::EncodeOptions::encoder))
+    /// With long-term prediction, frames are encoded one at a time as they become complete
+    pub fn with_encoder(writer: W, encoder: Encoder, channels: u16) -> Result<Self>
+    {
+        if channels == 0
+        {
+            return Err(anyhow!("Channel count must be at least 1"));
+        }
+
+        let ch = channels as usize;
+        encoder.check_bitrate(ch)?;
+        let hop_size = encoder.hop_size();
+        let mut frame_writer = FrameWriter::new(writer, encoder.sample_rate(), channels, hop_size)?;
+        frame_writer.long_term_prediction = encoder.long_term_prediction();
+
+        Ok(Self
+        {
+            frame_writer,
+            channels,
+            pending: vec![vec![0.0f32; hop_size]; ch],
+            total_samples: 0,
+            highpass: encoder.highpass_filter(ch),
+            prediction: encoder.long_term_prediction().then(|| OverlapState::for_hop_size(ch, hop_size)),
+            adaptive: AdaptiveQuality::default(),
+            channel_mask: 0,
+            tags: Tags::default(),
+            tracks: Vec::new(),
+            encoder,
+        })
+    }
+
+    /// Set the speaker layout recorded in the footer, as a WAVE_FORMAT_EXTENSIBLE channel mask
+    pub fn set_channel_mask(&mut self, channel_mask: u32)
+    {
+        self.channel_mask = channel_mask;
+    }
+
+    /// Set the tags written with the footer when the stream is finished
+    pub fn set_tags(&mut self, tags: Tags)
+    {
+        self.tags = tags;
+    }
+
+    /// Start a new track at the current position: samples written from here on belong to it
+    /// Streams with tracks record them in a track list, so they can be split apart again
+    pub fn start_track(&mut self, tags: Tags)
+    {
+        self.tracks.push(Track
+        {
+            start: self.total_samples / self.channels as u64,
+            length: 0,
+            tags,
+        });
+    }
+
+    /// Append interleaved samples, encoding and writing every frame that becomes complete
+    pub fn write_samples(&mut self, samples: &[f32]) -> Result<()>
+    {
+        let ch = self.channels as usize;
+        if !samples.len().is_multiple_of(ch)
+        {
+            return Err(anyhow!("Sample count {} is not a multiple of the channel count {}", samples.len(), ch));
+        }
+
+        push_samples(&mut self.pending, self.highpass.as_mut(), samples);
+        self.total_samples += samples.len() as u64;
+
+        self.flush_frames()
+    }
+
+    /// Encode and write all frames that are fully available in `pending`
+    /// Frames are encoded in parallel if the `parallel` feature is enabled, then written in order;
+    /// low-delay streams write each frame as soon as it is encoded and flush the writer after
+    fn flush_frames(&mut self) -> Result<()>
+    {
+        let hop = self.encoder.hop_size();
+        let available = self.pending[0].len();
+        if available < 2 * hop
+        {
+            return Ok(());
+        }
+        let num_frames = (available - 2 * hop) / hop + 1;
+
+        let pending = &self.pending;
+        let encoder = &self.encoder;
+        let frame_blocks = |fi: usize| -> Vec<&[f32]>
+        {
+            let start = fi * hop;
+            pending.iter().map(|p| &p[start .. start + 2 * hop]).collect()
+        };
+        let scales = encoder.threshold_scales(&mut self.adaptive, num_frames, 1.0, |fi| encoder.perceptual_entropy(&frame_blocks(fi)));
+        let encode_frame = |fi: usize| -> EncodedFrame
+        {
+            encoder.encode_block_scaled(&frame_blocks(fi), scales[fi])
+        };
+
+        if let Some(state) = &mut self.prediction
+        {
+            // Each frame is predicted from the ones before it, so they are encoded in order
+            for (fi, &scale) in scales.iter().enumerate()
+            {
+                let restart = self.frame_writer.frame_offsets.len().is_multiple_of(LTP_RESTART_INTERVAL);
+                let frame = encoder.encode_block_predicted(&frame_blocks(fi), state, restart, scale);
+                self.frame_writer.write_frame(&frame)?;
+            }
+            if hop < HOP_SIZE
+            {
+                self.frame_writer.writer.flush()?;
+            }
+        }
+        else if hop < HOP_SIZE
+        {
+            for fi in 0..num_frames
+            {
+                self.frame_writer.write_frame(&encode_frame(fi))?;
+            }
+            self.frame_writer.writer.flush()?;
+        }
+        else
+        {
+            #[cfg(feature = "parallel")]
+            let frames: Vec<EncodedFrame> = (0..num_frames).into_par_iter().map(encode_frame).collect();
+            #[cfg(not(feature = "parallel"))]
+            let frames: Vec<EncodedFrame> = (0..num_frames).map(encode_frame).collect();
+
+            for frame in &frames
+            {
+                self.frame_writer.write_frame(frame)?;
+            }
+        }
+
+        let consumed = num_frames * hop;
+        for p in &mut self.pending
+        {
+            p.drain(0..consumed);
+        }
+        Ok(())
+    }
+
+    /// Pad and encode the remaining samples, write the footer, and return the underlying writer
+    pub fn finish(mut self) -> Result<W>
+    {
+        let ch = self.channels as usize;
+        let orig_len = (self.total_samples / ch as u64) as usize;
+        let hop = self.encoder.hop_size();
+
+        // Same padding as Encoder::encode
+        let (_, padding) = frame_layout(orig_len, hop);
+        for p in &mut self.pending
+        {
+            p.extend(std::iter::repeat_n(0.0f32, padding));
+        }
+        self.flush_frames()?;
+        let header = AudioHeader
+        {
+            sample_rate: self.encoder.sample_rate(),
+            channels: self.channels,
+            total_samples: self.total_samples,
+            hop_size: hop as u32,
+            highpass: self.encoder.highpass(),
+            long_term_prediction: self.encoder.long_term_prediction(),
+            bandwidth_extension: self.encoder.bandwidth_extension(),
+        };
+        let gapless_info = GaplessInfo
+        {
+            encoder_delay: hop as u32,
+            padding: padding as u32,
+            original_length: self.total_samples,
+        };
+
+        // Each track runs until the next one starts
+        let end = orig_len as u64;
+        let starts: Vec<u64> = self.tracks.iter().skip(1).map(|t| t.start).chain(std::iter::once(end)).collect();
+        for (track, next) in self.tracks.iter_mut().zip(starts)
+        {
+            track.length = next - track.start;
+        }
+
+        self.frame_writer.finish(header, gapless_info, self.channel_mask, &self.tags, &self.tracks)
+    }
+}
+
+/// Deinterleave `samples` onto the ends of the per-channel `pending` buffers, through `highpass` if set
+fn push_samples(pending: &mut [Vec<f32>], mut highpass: Option<&mut HighPass>, samples: &[f32])
+{
+    let ch = pending.len();
+    for (i, &s) in samples.iter().enumerate()
+    {
+        let s = match highpass.as_deref_mut()
+        {
+            Some(filter) => filter.process(i % ch, s),
+            None => s,
+        };
+        pending[i % ch].push(s);
+    }
+}
+
+/// Writes a stream from frames that are already encoded, e.g. when cutting or joining files
+/// without a decode/re-encode round trip
+/// The caller describes the frames' encoder delay, padding, and length when finishing
+pub struct GlcFrameWriter<W: Write>
+{
+    frame_writer: FrameWriter<W>,
+    sample_rate: u32,
+    channels: u16,
+    hop_size: usize,
+    channel_mask: u32,
+    highpass: Option<f32>,
+    bandwidth_extension: Option<f32>,
+    tags: Tags,
+    tracks: Vec<Track>,
+}
+
+impl<W: Write> GlcFrameWriter<W>
+{
+    /// Start a new stream; the stream header is written with the first frame
+    pub fn new(writer: W, sample_rate: u32, channels: u16) -> Result<Self>
+    {
+        Self::with_hop_size(writer, sample_rate, channels, HOP_SIZE)
+    }
+
+    /// Start a new stream for frames that advance by `hop_size` samples, e.g. copied from a low-delay stream
+    pub fn with_hop_size(writer: W, sample_rate: u32, channels: u16, hop_size: usize) -> Result<Self>
+    {
+        if channels == 0
+        {
+            return Err(anyhow!("Channel count must be at least 1"));
+        }
+        check_hop_size(hop_size)?;
+
+        Ok(Self
+        {
+            frame_writer: FrameWriter::new(writer, sample_rate, channels, hop_size)?,
+            sample_rate,
+            channels,
+            hop_size,
+            channel_mask: 0,
+            highpass: None,
+            bandwidth_extension: None,
+            tags: Tags::default(),
+            tracks: Vec::new(),
+        })
+    }
+
+    /// Set the speaker layout recorded in the footer, as a WAVE_FORMAT_EXTENSIBLE channel mask
+    pub fn set_channel_mask(&mut self, channel_mask: u32)
+    {
+        self.channel_mask = channel_mask;
+    }
+
+    /// Record that the frames' audio was high-passed at `cutoff` Hz (see [`AudioHeader::highpass`])
+    pub fn set_highpass(&mut self, cutoff: Option<f32>)
+    {
+        self.highpass = cutoff;
+    }
+
+    /// Record that frames may carry long-term prediction (see [`AudioHeader::long_term_prediction`]),
+    /// e.g. when copying them from a stream that does; set it before writing any frames, as the
+    /// stream's format version depends on it
+    pub fn set_long_term_prediction(&mut self, enabled: bool)
+    {
+        self.frame_writer.long_term_prediction = enabled;
+    }
+
+    /// Record the frames' bandwidth-extension crossover in Hz (see [`AudioHeader::bandwidth_extension`])
+    pub fn set_bandwidth_extension(&mut self, crossover: Option<f32>)
+    {
+        self.bandwidth_extension = crossover;
+    }
+
+    /// Set the tags written with the footer when the stream is finished
+    pub fn set_tags(&mut self, tags: Tags)
+    {
+        self.tags = tags;
+    }
+
+    /// Set the track list written with the footer, for chained streams
+    pub fn set_tracks(&mut self, tracks: Vec<Track>)
+    {
+        self.tracks = tracks;
+    }
+
+    pub fn write_frame(&mut self, frame: &EncodedFrame) -> Result<()>
+    {
+        self.frame_writer.write_frame(frame)
+    }
+
+    /// Write a serialized frame as returned by [`GlcDecoder::read_frame_bytes`]
+    pub fn write_frame_bytes(&mut self, payload: &[u8]) -> Result<()>
+    {
+        self.frame_writer.write_frame_bytes(payload)
+    }
+
+    /// Frames written so far
+    pub fn frame_count(&self) -> usize
+    {
+        self.frame_writer.frame_offsets.len()
+    }
+
+    /// Write the footer and return the underlying writer
+    pub fn finish(self, gapless_info: GaplessInfo) -> Result<W>
+    {
+        let header = AudioHeader
+        {
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            total_samples: gapless_info.original_length,
+            hop_size: self.hop_size as u32,
+            highpass: self.highpass,
+            long_term_prediction: self.frame_writer.long_term_prediction,
+            bandwidth_extension: self.bandwidth_extension,
+        };
+        self.frame_writer.finish(header, gapless_info, self.channel_mask, &self.tags, &self.tracks)
+    }
+}
+
+/// Predicted size of a stream, from [`SizeEstimator`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SizeEstimate
+{
+    /// Predicted size of the whole stream in bytes, including the footer
+    pub bytes: u64,
+    /// Frames the stream would have
+    pub frames: u64,
+    /// Frames actually encoded to make the prediction
+    pub sampled_frames: u64,
+    /// Length in seconds
+    pub duration: f64,
+}
+
+impl SizeEstimate
+{
+    /// Average bitrate in kbit/s
+    pub fn kbps(&self) -> f64
+    {
+        if self.duration > 0.0 { self.bytes as f64 * 8.0 / self.duration / 1000.0 } else { 0.0 }
+    }
+}
+
+/// Predicts how large [`GlcEncoder`] would make a stream without writing it
+/// Takes the same input, but only encodes every `stride`-th frame and extrapolates the average
+/// frame size to the rest; the container overhead is computed exactly
+pub struct SizeEstimator
+{
+    encoder: Encoder,
+    channels: u16,
+    stride: u64,
+    /// Per-channel samples not yet covered by a frame (starts with the encoder delay)
+    pending: Vec<Vec<f32>>,
+    /// Interleaved samples received so far
+    total_samples: u64,
+    highpass: Option<HighPass>,
+    adaptive: AdaptiveQuality,
+    frames: u64,
+    sampled_frames: u64,
+    sampled_bytes: u64,
+    tags: Tags,
+}
+
+impl SizeEstimator
+{
+    /// `stride` 1 encodes every frame, giving the exact size
+    pub fn new(sample_rate: u32, channels: u16, stride: usize) -> Result<Self>
+    {
+        Self::with_hop_size(sample_rate, channels, stride, HOP_SIZE)
+    }
+
+    /// Predict the size of a stream from [`GlcEncoder::with_hop_size`]
+    pub fn with_hop_size(sample_rate: u32, channels: u16, stride: usize, hop_size: usize) -> Result<Self>
+    {
+        Self::with_encoder(Encoder::with_hop_size(sample_rate, hop_size)?, channels, stride)
+    }
+
+    /// Predict the size of a stream from [`GlcEncoder::with_encoder`], leaving out long-term prediction
+    /// With adaptive quality, the average perceptual entropy is taken over the sampled frames, each standing for `stride`
+    pub fn with_encoder(mut encoder: Encoder, channels: u16, stride: usize) -> Result<Self>
+    {
+        if channels == 0
+        {
+            return Err(anyhow!("Channel count must be at least 1"));
+        }
+
+        encoder.set_long_term_prediction(false);
+        encoder.check_bitrate(channels as usize)?;
+        Ok(Self
+        {
+            channels,
+            stride: stride.max(1) as u64,
+            pending: vec![vec![0.0f32; encoder.hop_size()]; channels as usize],
+            total_samples: 0,
+            highpass: encoder.highpass_filter(channels as usize),
+            adaptive: AdaptiveQuality::default(),
+            frames: 0,
+            sampled_frames: 0,
+            sampled_bytes: 0,
+            tags: Tags::default(),
+            encoder,
+        })
+    }
+
+    /// Tags that would be written, since they count towards the size
+    pub fn set_tags(&mut self, tags: Tags)
+    {
+        self.tags = tags;
+    }
+
+    /// Append interleaved samples, as with [`GlcEncoder::write_samples`]
+    pub fn write_samples(&mut self, samples: &[f32]) -> Result<()>
+    {
+        let ch = self.channels as usize;
+        if !samples.len().is_multiple_of(ch)
+        {
+            return Err(anyhow!("Sample count {} is not a multiple of the channel count {}", samples.len(), ch));
+        }
+
+        push_samples(&mut self.pending, self.highpass.as_mut(), samples);
+        self.total_samples += samples.len() as u64;
+
+        self.flush_frames()
+    }
+
+    /// Encode the sampled frames among those fully available in `pending`, and skip the rest
+    fn flush_frames(&mut self) -> Result<()>
+    {
+        let hop = self.encoder.hop_size();
+        let available = self.pending[0].len();
+        if available < 2 * hop
+        {
+            return Ok(());
+        }
+        let num_frames = (available - 2 * hop) / hop + 1;
+
+        let sampled: Vec<usize> = (0..num_frames).filter(|&fi| (self.frames + fi as u64).is_multiple_of(self.stride)).collect();
+        let pending = &self.pending;
+        let encoder = &self.encoder;
+        let frame_blocks = |fi: usize| -> Vec<&[f32]>
+        {
+            let start = fi * hop;
+            pending.iter().map(|p| &p[start .. start + 2 * hop]).collect()
+        };
+        let scales = encoder.threshold_scales(&mut self.adaptive, sampled.len(), self.stride as f32,
+                                              |i| encoder.perceptual_entropy(&frame_blocks(sampled[i])));
+        let frame_size = |i: usize| -> Result<u64>
+        {
+            Ok(serialize_frame(&encoder.encode_block_scaled(&frame_blocks(sampled[i]), scales[i]))?.len() as u64)
+        };
+
+        #[cfg(feature = "parallel")]
+        let sizes: Vec<u64> = (0..sampled.len()).into_par_iter().map(frame_size).collect::<Result<_>>()?;
+        #[cfg(not(feature = "parallel"))]
+        let sizes: Vec<u64> = (0..sampled.len()).map(frame_size).collect::<Result<_>>()?;
+
+        self.sampled_frames += sizes.len() as u64;
+        self.sampled_bytes += sizes.iter().sum::<u64>();
+        self.frames += num_frames as u64;
+
+        let consumed = num_frames * hop;
+        for p in &mut self.pending
+        {
+            p.drain(0..consumed);
+        }
+        Ok(())
+    }
+
+    /// Pad the remaining samples as [`GlcEncoder::finish`] does and predict the stream size
+    pub fn finish(mut self) -> Result<SizeEstimate>
+    {
+        let ch = self.channels as usize;
+        let orig_len = (self.total_samples / ch as u64) as usize;
+        let hop = self.encoder.hop_size();
+
+        let (_, padding) = frame_layout(orig_len, hop);
+        for p in &mut self.pending
+        {
+            p.extend(std::iter::repeat_n(0.0f32, padding));
+        }
+        self.flush_frames()?;
+
+        let frame_bytes = if self.sampled_frames > 0
+        {
+            (self.sampled_bytes as f64 * self.frames as f64 / self.sampled_frames as f64).round() as u64
+        }
+        else
+        {
+            0
+        };
+
+        // Everything but the frame payloads is known exactly: record prefixes, the seek table, ...
+        let footer = Footer
+        {
+            header: AudioHeader
+            {
+                sample_rate: self.encoder.sample_rate(),
+                channels: self.channels,
+                total_samples: self.total_samples,
+                hop_size: hop as u32,
+                highpass: self.encoder.highpass(),
+                long_term_prediction: false,
+                bandwidth_extension: self.encoder.bandwidth_extension(),
+            },
+            gapless_info: GaplessInfo { encoder_delay: hop as u32, padding: 0, original_length: self.total_samples },
+            frame_offsets: Vec::new(),
+            channel_mask: 0,
+            highpass: self.encoder.highpass(),
+            bandwidth_extension: self.encoder.bandwidth_extension(),
+        };
+        let footer_bytes = bincode::serialized_size(&footer)? + 4 * self.frames;
+        let tag_bytes = if self.tags.is_empty() { 0 } else { 8 + bincode::serialized_size(&self.tags)? };
+
+        Ok(SizeEstimate
+        {
+            bytes: stream_header_size(version_for(hop, false)) + 8 * self.frames + frame_bytes + 4 + 8 + footer_bytes + tag_bytes + TRAILER_SIZE,
+            frames: self.frames,
+            sampled_frames: self.sampled_frames,
+            duration: orig_len as f64 / self.encoder.sample_rate().max(1) as f64,
+        })
+    }
+}
+
+/// Random-access reader over a complete GLC stream
+/// Reads only the stream header and footer up front; frames are read on demand
+pub struct GlcDecoder<R: Read + Seek>
+{
+    reader: R,
+    /// Offset of the stream start within `reader`
+    base: u64,
+    header: AudioHeader,
+    gapless_info: GaplessInfo,
+    frame_offsets: Vec<u64>,
+    channel_mask: u32,
+    tags: Tags,
+    tracks: Vec<Track>,
+    /// Offsets of the end-of-frames marker and of the end of the footer record, relative to `base`
+    footer_offset: u64,
+    tags_offset: u64,
+}
+
+impl<R: Read + Seek> GlcDecoder<R>
+{
+    /// Open a stream starting at the current position of `reader`
+    pub fn new(mut reader: R) -> Result<Self>
+    {
+        let base = reader.stream_position()?;
+
+        let mut signature = [0u8; 4];
+        reader.read_exact(&mut signature)?;
+        if signature != GLC_SIGNATURE
+        {
+            return Err(anyhow!("Not a GLC stream (bad signature)"));
+        }
+        let version = read_u16(&mut reader)?;
+        if version == 0 || version > FORMAT_VERSION
+        {
+            return Err(GlcError::UnsupportedVersion(version).into());
+        }
+        let sample_rate = read_u32(&mut reader)?;
+        let channels = read_u16(&mut reader)?;
+        let hop_size = if version >= 3 { read_u16(&mut reader)? as usize } else { HOP_SIZE };
+        check_stream_format(sample_rate, channels)?;
+        check_hop_size(hop_size)?;
+
+        // Locate the footer via the trailer
+        let end = reader.seek(SeekFrom::End(0))?;
+        if end < base + stream_header_size(version) + TRAILER_SIZE
+        {
+            return Err(anyhow!("GLC stream is truncated (no footer)"));
+        }
+        reader.seek(SeekFrom::Start(end - TRAILER_SIZE))?;
+        let footer_offset = read_u64(&mut reader)?;
+        reader.read_exact(&mut signature)?;
+        if signature != FOOTER_SIGNATURE || footer_offset >= end - base
+        {
+            return Err(anyhow!("GLC stream is truncated or corrupt (bad trailer)"));
+        }
+
+        reader.seek(SeekFrom::Start(base + footer_offset))?;
+        if read_u32(&mut reader)? != END_OF_FRAMES
+        {
+            return Err(anyhow!("GLC footer is corrupt (missing end-of-frames marker)"));
+        }
+        let mut footer = parse_footer(version, &read_record(&mut reader)?)?;
+        footer.header.hop_size = hop_size as u32;
+        footer.header.long_term_prediction = version >= 4;
+
+        // Anything between the footer and the trailer is the tag record, then the track record
+        let tags_offset = reader.stream_position()? - base;
+        let tags = if base + tags_offset < end - TRAILER_SIZE
+        {
+            let payload = read_record(&mut reader).map_err(|e| anyhow!("GLC tags: {}", e))?;
+            bincode::deserialize(&payload)?
+        }
+        else
+        {
+            Tags::default()
+        };
+        let tracks = if reader.stream_position()? < end - TRAILER_SIZE
+        {
+            let payload = read_record(&mut reader).map_err(|e| anyhow!("GLC track list: {}", e))?;
+            bincode::deserialize(&payload)?
+        }
+        else
+        {
+            Vec::new()
+        };
+
+        if footer.header.sample_rate != sample_rate || footer.header.channels != channels
+        {
+            return Err(GlcError::HeaderMismatch.into());
+        }
+        check_footer(&footer, &tracks, stream_header_size(version), footer_offset)?;
+
+        Ok(Self
+        {
+            reader,
+            base,
+            header: footer.header,
+            gapless_info: footer.gapless_info,
+            frame_offsets: footer.frame_offsets,
+            channel_mask: footer.channel_mask,
+            tags,
+            tracks,
+            footer_offset,
+            tags_offset,
+        })
+    }
+
+    pub fn header(&self) -> &AudioHeader
+    {
+        &self.header
+    }
+
+    pub fn gapless_info(&self) -> &GaplessInfo
+    {
+        &self.gapless_info
+    }
+
+    /// Speaker layout as a WAVE_FORMAT_EXTENSIBLE channel mask (0 = unspecified)
+    pub fn channel_mask(&self) -> u32
+    {
+        self.channel_mask
+    }
+
+    pub fn tags(&self) -> &Tags
+    {
+        &self.tags
+    }
+
+    /// Tracks of a chained stream (empty for a single track)
+    pub fn tracks(&self) -> &[Track]
+    {
+        &self.tracks
+    }
+
+    pub fn frame_count(&self) -> usize
+    {
+        self.frame_offsets.len()
+    }
+
+    /// Read and verify a single frame
+    pub fn read_frame(&mut self, index: usize) -> Result<EncodedFrame>
+    {
+        let payload = self.read_frame_bytes(index)?;
+        parse_frame(&payload, &self.header).map_err(|e| anyhow!("Frame {}: {}", index, e))
+    }
+
+    /// The nearest frame at or before `index` that restarts long-term prediction, where decoding
+    /// must begin for the output of frame `index` onward to be exact
+    /// Every frame restarts prediction in a stream without it
+    pub fn restart_frame(&mut self, index: usize) -> Result<usize>
+    {
+        let mut index = index.min(self.frame_count().saturating_sub(1));
+        while self.header.long_term_prediction && index > 0 && !self.read_frame(index)?.restarts_prediction()
+        {
+            index -= 1;
+        }
+        Ok(index)
+    }
+
+    /// Read the CRC-verified serialized payload of a single frame without deserializing it
+    pub fn read_frame_bytes(&mut self, index: usize) -> Result<Vec<u8>>
+    {
+        let offset = *self.frame_offsets.get(index)
+                                        .ok_or_else(|| anyhow!("Frame index {} out of range", index))?;
+        self.reader.seek(SeekFrom::Start(self.base + offset))?;
+        read_record(&mut self.reader).map_err(|e| anyhow!("Frame {}: {}", index, e))
+    }
+
+    /// Read every frame into memory
+    pub fn read_all(&mut self) -> Result<EncodedAudio>
+    {
+        let mut frames = Vec::with_capacity(self.frame_count());
+        for index in 0..self.frame_count()
+        {
+            frames.push(self.read_frame(index)?);
+        }
+
+        Ok(EncodedAudio
+        {
+            header: self.header.clone(),
+            frames,
+            gapless_info: self.gapless_info.clone(),
+        })
+    }
+
+    pub fn into_inner(self) -> R
+    {
+        self.reader
+    }
+}
+
+/// Memory-mapped, read-only view of a `.glc` file
+/// Opening only parses the stream header and footer; frames are deserialized on demand
+/// straight from the mapping, so memory use does not grow with the file length
+#[cfg(not(target_arch = "wasm32"))]
+pub struct MappedGlcFile
+{
+    map: memmap2::Mmap,
+    header: AudioHeader,
+    gapless_info: GaplessInfo,
+    frame_offsets: Vec<u64>,
+    channel_mask: u32,
+    tags: Tags,
+    tracks: Vec<Track>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl MappedGlcFile
+{
+    pub fn open(path: &Path) -> Result<Self>
+    {
+        let file = File::open(path)?;
+        // SAFETY: the mapping is read-only. As with any mmap, truncating the file from
+        // another process while it is mapped makes later accesses fault.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+
+        let (header, gapless_info, frame_offsets, channel_mask, tags, tracks) =
+        {
+            let decoder = GlcDecoder::new(Cursor::new(&map[..]))?;
+            (decoder.header, decoder.gapless_info, decoder.frame_offsets, decoder.channel_mask, decoder.tags, decoder.tracks)
+        };
+
+        Ok(Self
+        {
+            map,
+            header,
+            gapless_info,
+            frame_offsets,
+            channel_mask,
+            tags,
+            tracks,
+        })
+    }
+
+    pub fn header(&self) -> &AudioHeader
+    {
+        &self.header
+    }
+
+    pub fn gapless_info(&self) -> &GaplessInfo
+    {
+        &self.gapless_info
+    }
+
+    /// Speaker layout as a WAVE_FORMAT_EXTENSIBLE channel mask (0 = unspecified)
+    pub fn channel_mask(&self) -> u32
+    {
+        self.channel_mask
+    }
+
+    pub fn tags(&self) -> &Tags
+    {
+        &self.tags
+    }
+
+    /// Tracks of a chained stream (empty for a single track)
+    pub fn tracks(&self) -> &[Track]
+    {
+        &self.tracks
+    }
+
+    pub fn frame_count(&self) -> usize
+    {
+        self.frame_offsets.len()
+    }
+
+    /// Deserialize and verify a single frame
+    pub fn read_frame(&self, index: usize) -> Result<EncodedFrame>
+    {
+        let offset = *self.frame_offsets.get(index)
+                                        .ok_or_else(|| anyhow!("Frame index {} out of range", index))?;
+        record_payload(&self.map, offset).and_then(|payload| parse_frame(payload, &self.header))
+                                         .map_err(|e| anyhow!("Frame {}: {}", index, e))
+    }
+
+    /// The nearest frame at or before `index` that restarts long-term prediction
+    /// (see [`GlcDecoder::restart_frame`])
+    pub fn restart_frame(&self, index: usize) -> Result<usize>
+    {
+        let mut index = index.min(self.frame_count().saturating_sub(1));
+        while self.header.long_term_prediction && index > 0 && !self.read_frame(index)?.restarts_prediction()
+        {
+            index -= 1;
+        }
+        Ok(index)
+    }
+
+    /// Iterate over every frame in stream order
+    pub fn frames(&self) -> impl Iterator<Item = Result<EncodedFrame>> + '_
+    {
+        (0..self.frame_count()).map(move |index| self.read_frame(index))
+    }
+}
+
+/// Replace the tags of the `.glc` file at `path` in place, keeping its track list
+/// Only the records and trailer after the footer are rewritten; frame data is left untouched
+#[cfg(not(target_arch = "wasm32"))]
+pub fn rewrite_tags(path: &Path, tags: &Tags) -> Result<()>
+{
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let (footer_offset, tags_offset, tracks) =
+    {
+        let decoder = GlcDecoder::new(&mut file)?;
+        (decoder.footer_offset, decoder.tags_offset, decoder.tracks)
+    };
+
+    let mut tail = Vec::new();
+    write_metadata_and_trailer(&mut tail, tags, &tracks, footer_offset)?;
+
+    file.seek(SeekFrom::Start(tags_offset))?;
+    file.write_all(&tail)?;
+    file.set_len(tags_offset + tail.len() as u64)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// What [`repair`] salvaged from a damaged stream
+#[derive(Clone, Debug, PartialEq)]
+pub struct RepairReport
+{
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Frames in the repaired stream, including the silent ones standing in for lost frames
+    pub frames: usize,
+    /// Frames that could not be read and were replaced with silence
+    pub lost_frames: usize,
+    /// Length of the repaired stream in samples per channel
+    pub length: u64,
+    /// Audio lost (silent in the repaired stream), in samples per channel from the start
+    pub lost_ranges: Vec<std::ops::Range<u64>>,
+    /// The footer could not be found, so the repaired stream ends after the last readable frame;
+    /// anything after that is gone, including any tags, and the original length is unknown
+    pub truncated: bool,
+    /// Whether frame positions came from the seek table; if not, the number of frames lost in
+    /// each damaged region is estimated from its size, and so are the lost ranges
+    pub exact: bool,
+    /// The tag or track record could not be read
+    pub metadata_lost: bool,
+}
+
+impl RepairReport
+{
+    /// Nothing was lost: the repaired stream holds the same audio and metadata
+    pub fn is_intact(&self) -> bool
+    {
+        self.lost_frames == 0 && !self.truncated && !self.metadata_lost
+    }
+}
+
+/// Salvage every intact frame of a damaged stream in `data` and write a consistent stream to `writer`
+/// With the footer intact, each frame's position comes from the seek table and lost frames are
+/// replaced with silence, so the rest keeps its timing. Otherwise the frame records are scanned in
+/// order, skipping over corrupt bytes, and the stream is cut after the last readable frame
+#[cfg(not(target_arch = "wasm32"))]
+pub fn repair<W: Write>(data: &[u8], writer: W) -> Result<(W, RepairReport)>
+{
+    if data.len() < STREAM_HEADER_SIZE as usize || data[0..4] != GLC_SIGNATURE
+    {
+        return Err(anyhow!("Not a GLC stream (bad signature)"));
+    }
+    let version = u16::from_le_bytes([data[4], data[5]]);
+    if version == 0 || version > FORMAT_VERSION
+    {
+        return Err(anyhow!("Unsupported GLC format version {} (this build reads up to {})", version, FORMAT_VERSION));
+    }
+    let header_size = stream_header_size(version) as usize;
+    if data.len() < header_size
+    {
+        return Err(anyhow!("GLC stream header is truncated"));
+    }
+    let hop = if version >= 3 { u16::from_le_bytes([data[12], data[13]]) as usize } else { HOP_SIZE };
+    check_hop_size(hop).map_err(|e| anyhow!("GLC stream header is corrupt: {}", e))?;
+
+    let (footer, frames, metadata, exact) = match find_footer(data, version)
+    {
+        Some((footer, metadata_offset)) =>
+        {
+            let channels = footer.header.channels as usize;
+            let frames = footer.frame_offsets.iter().map(|&offset| frame_payload(data, offset, channels, hop)).collect();
+            let metadata = read_metadata(data, metadata_offset);
+            (Some(footer), frames, metadata, true)
+        }
+        None =>
+        {
+            let channels = u16::from_le_bytes([data[10], data[11]]) as usize;
+            if channels == 0 || channels > MAX_CHANNELS as usize
+            {
+                return Err(anyhow!("GLC stream header is corrupt and the footer is missing"));
+            }
+            (None, scan_frames(data, header_size, channels, hop), (Tags::default(), Vec::new(), false), false)
+        }
+    };
+
+    let (sample_rate, channels) = match &footer
+    {
+        // The footer is checksummed, unlike the stream header
+        Some(footer) => (footer.header.sample_rate, footer.header.channels),
+        None => (u32::from_le_bytes([data[6], data[7], data[8], data[9]]), u16::from_le_bytes([data[10], data[11]])),
+    };
+    // An intact footer vouches for an empty stream, which has no frames at all
+    let empty = footer.as_ref().is_some_and(|footer| footer.gapless_info.original_length == 0) && frames.is_empty();
+    if !empty && frames.iter().all(|frame| frame.is_none())
+    {
+        return Err(anyhow!("No intact frames found"));
+    }
+
+    let ch = channels as u64;
+    let gapless_info = match &footer
+    {
+        Some(footer) => footer.gapless_info.clone(),
+        // Keep only the audio the frames fully reconstruct: the first and last half frames have no
+        // overlap partner, and the first is the encoder delay anyway
+        None => GaplessInfo
+        {
+            encoder_delay: hop as u32,
+            padding: hop as u32,
+            original_length: (frames.len().saturating_sub(1) * hop) as u64 * ch,
+        },
+    };
+
+    let (tags, tracks, metadata_lost) = metadata;
+    let mut frame_writer = GlcFrameWriter::with_hop_size(writer, sample_rate, channels, hop)?;
+    frame_writer.set_channel_mask(footer.as_ref().map_or(0, |footer| footer.channel_mask));
+    frame_writer.set_highpass(footer.as_ref().and_then(|footer| footer.highpass));
+    frame_writer.set_long_term_prediction(version >= 4);
+    frame_writer.set_bandwidth_extension(footer.as_ref().and_then(|footer| footer.bandwidth_extension));
+    frame_writer.set_tags(tags);
+    frame_writer.set_tracks(tracks);
+
+    let silence = EncodedFrame
+    {
+        sparse_coeffs_per_channel: vec![Vec::new(); channels as usize],
+        scale_factors: vec![0.0; channels as usize],
+        raw_pcm: None,
+        ltp: Vec::new(),
+        envelope: None,
+    };
+    let delay = gapless_info.encoder_delay as u64;
+    let length = gapless_info.original_length / ch.max(1);
+    let mut lost_ranges: Vec<std::ops::Range<u64>> = Vec::new();
+    let mut mark_lost = |index: usize|
+    {
+        // A frame contributes to two hops of audio
+        let start = ((index * hop) as u64).saturating_sub(delay).min(length);
+        let end = (((index + 2) * hop) as u64).saturating_sub(delay).min(length);
+        match lost_ranges.last_mut()
+        {
+            Some(last) if last.end >= start => last.end = last.end.max(end),
+            _ if start < end => lost_ranges.push(start..end),
+            _ => {}
+        }
+    };
+
+    // With long-term prediction, the frames after a lost one are predicted from audio that is
+    // gone, so the damage runs on until a frame restarts prediction
+    let mut mispredicted = false;
+    for (index, frame) in frames.iter().enumerate()
+    {
+        match frame
+        {
+            Some(payload) =>
+            {
+                frame_writer.write_frame_bytes(payload)?;
+                mispredicted &= !deserialize_frame(payload).is_ok_and(|frame| frame.restarts_prediction());
+                if mispredicted
+                {
+                    mark_lost(index);
+                }
+            }
+            None =>
+            {
+                frame_writer.write_frame(&silence)?;
+                mark_lost(index);
+                mispredicted = version >= 4;
+            }
+        }
+    }
+
+    let report = RepairReport
+    {
+        sample_rate,
+        channels,
+        frames: frames.len(),
+        length,
+        lost_frames: frames.iter().filter(|frame| frame.is_none()).count(),
+        lost_ranges,
+        truncated: footer.is_none(),
+        exact,
+        metadata_lost,
+    };
+    Ok((frame_writer.finish(gapless_info)?, report))
+}
+
+/// Find a readable footer, via the trailer or else by searching for the end-of-frames marker
+/// Returns the footer and the offset of the records after it
+#[cfg(not(target_arch = "wasm32"))]
+fn find_footer(data: &[u8], version: u16) -> Option<(Footer, u64)>
+{
+    let footer_at = |offset: u64| -> Option<(Footer, u64)>
+    {
+        let start = usize::try_from(offset).ok()?;
+        if data.get(start..start + 4)? != END_OF_FRAMES.to_le_bytes()
+        {
+            return None;
+        }
+        let payload = record_payload(data, offset + 4).ok()?;
+        let footer = parse_footer(version, payload).ok()?;
+        Some((footer, offset + 4 + 8 + payload.len() as u64))
+    };
+
+    let trailer = data.len().checked_sub(TRAILER_SIZE as usize).map(|start| &data[start..]);
+    if let Some(trailer) = trailer
+        && trailer[8..] == FOOTER_SIGNATURE
+        && let Some(found) = footer_at(u64::from_le_bytes(trailer[..8].try_into().ok()?))
+    {
+        return Some(found);
+    }
+
+    // The last marker followed by a valid footer record wins, in case a frame happens to contain one
+    (stream_header_size(version) as usize..data.len().saturating_sub(3)).rev()
+                                                                          .filter(|&i| data[i..i + 4] == END_OF_FRAMES.to_le_bytes())
+                                                                          .find_map(|i| footer_at(i as u64))
+}
+
+/// Read the tag and track records starting at `offset`, as far as they are intact
+/// Returns the tags, the tracks, and whether a record was damaged
+#[cfg(not(target_arch = "wasm32"))]
+fn read_metadata(data: &[u8], offset: u64) -> (Tags, Vec<Track>, bool)
+{
+    let end = data.len() as u64;
+    // A complete trailer directly after the footer means there were no records
+    let trailer_follows = |offset: u64| end.saturating_sub(offset) <= TRAILER_SIZE;
+    if trailer_follows(offset)
+    {
+        return (Tags::default(), Vec::new(), false);
+    }
+
+    let Some(tags_payload) = record_payload(data, offset).ok()
+    else
+    {
+        return (Tags::default(), Vec::new(), true);
+    };
+    let Ok(tags) = bincode::deserialize::<Tags>(tags_payload)
+    else
+    {
+        return (Tags::default(), Vec::new(), true);
+    };
+
+    let offset = offset + 8 + tags_payload.len() as u64;
+    if trailer_follows(offset)
+    {
+        return (tags, Vec::new(), false);
+    }
+    match record_payload(data, offset).ok().and_then(|payload| bincode::deserialize::<Vec<Track>>(payload).ok())
+    {
+        Some(tracks) => (tags, tracks, false),
+        None => (tags, Vec::new(), true),
+    }
+}
+
+/// Payload of the frame record at `offset`, if it is intact and holds a frame for `channels` channels
+/// of a stream with hop size `hop`
+#[cfg(not(target_arch = "wasm32"))]
+fn frame_payload(data: &[u8], offset: u64, channels: usize, hop: usize) -> Option<&[u8]>
+{
+    let payload = record_payload(data, offset).ok()?;
+    let frame = deserialize_frame(payload).ok()?;
+    frame.validate(channels, hop).ok().map(|()| payload)
+}
+
+/// Read frame records in order from `start`, the end of the stream header, without a seek table
+/// After a damaged region, the next intact record is found by trying every byte offset; the
+/// number of frames the region held is estimated from the average size of the intact ones
+#[cfg(not(target_arch = "wasm32"))]
+fn scan_frames(data: &[u8], start: usize, channels: usize, hop: usize) -> Vec<Option<&[u8]>>
+{
+    // Lost regions are recorded as `Err(bytes)` until the average frame size is known
+    let mut segments: Vec<std::result::Result<&[u8], usize>> = Vec::new();
+    let mut position = start;
+    let mut gap_start = None;
+    // No frame is larger than its raw PCM fallback plus a little framing, which rules out most
+    // garbage lengths before checksumming anything
+    let max_payload = 64 + channels * (2 * hop * 2 + hop * 4 + 16);
+
+    while position + 8 <= data.len()
+    {
+        let len = u32::from_le_bytes([data[position], data[position + 1], data[position + 2], data[position + 3]]) as usize;
+        let frame = if len <= max_payload { frame_payload(data, position as u64, channels, hop) } else { None };
+        match frame
+        {
+            Some(payload) =>
+            {
+                if let Some(start) = gap_start.take()
+                {
+                    segments.push(Err(position - start));
+                }
+                segments.push(Ok(payload));
+                position += 8 + payload.len();
+            }
+            None =>
+            {
+                gap_start.get_or_insert(position);
+                position += 1;
+            }
+        }
+    }
+    // Whatever follows the last intact frame is the truncated end of the stream, not a gap
+
+    let intact: Vec<usize> = segments.iter().filter_map(|s| s.as_ref().ok().map(|p| 8 + p.len())).collect();
+    let average = (intact.iter().sum::<usize>() as f64 / intact.len().max(1) as f64).max(1.0);
+
+    let mut frames = Vec::new();
+    for segment in segments
+    {
+        match segment
+        {
+            Ok(payload) => frames.push(Some(payload)),
+            Err(bytes) =>
+            {
+                let lost = ((bytes as f64 / average).round() as usize).max(1);
+                frames.extend(std::iter::repeat_n(None, lost));
+            }
+        }
+    }
+    frames
+}
+
+/// Borrow the CRC-verified payload of the record starting at `offset` in `data`
+#[cfg(not(target_arch = "wasm32"))]
+fn record_payload(data: &[u8], offset: u64) -> Result<&[u8]>
+{
+    let start = usize::try_from(offset)?;
+    let prefix = data.get(start..start + 8).ok_or_else(|| anyhow!("Record is truncated"))?;
+    let len = u32::from_le_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]);
+    let expected_crc = u32::from_le_bytes([prefix[4], prefix[5], prefix[6], prefix[7]]);
+    if len > MAX_RECORD_SIZE
+    {
+        return Err(anyhow!("Record length {} exceeds limit", len));
+    }
+
+    let payload = data.get(start + 8..start + 8 + len as usize).ok_or_else(|| anyhow!("Record is truncated"))?;
+    if crc32(payload) != expected_crc
+    {
+        return Err(anyhow!("CRC mismatch"));
+    }
+    Ok(payload)
+}
+
+/// Deserialize a frame record's payload and check that it fits the stream described by `header`
+fn parse_frame(payload: &[u8], header: &AudioHeader) -> Result<EncodedFrame>
+{
+    let frame = deserialize_frame(payload)?;
+    frame.validate(header.channels as usize, header.hop_size as usize)?;
+    Ok(frame)
+}
+
+/// Check that a footer's seek table points into the frame data (between the stream header of
+/// `header_size` bytes and the end-of-frames marker at `footer_offset`), that its header agrees
+/// with its frames (see [`check_header`]), and that every track lies within the stream, so
+/// nothing computed from them later can overflow
+fn check_footer(footer: &Footer, tracks: &[Track], header_size: u64, footer_offset: u64) -> Result<()>
+{
+    if footer.frame_offsets.iter().any(|offset| !(header_size..footer_offset).contains(offset))
+    {
+        return Err(anyhow!("GLC seek table is corrupt (frame offset outside the frame data)"));
+    }
+    check_header(&footer.header, &footer.gapless_info, footer.frame_offsets.len())?;
+    let length = footer.gapless_info.original_length / footer.header.channels.max(1) as u64;
+    if tracks.iter().any(|track| track.start.checked_add(track.length).is_none_or(|end| end > length))
+    {
+        return Err(anyhow!("GLC track list is corrupt (track outside the stream)"));
+    }
+    Ok(())
+}
+
+/// Deserialize a footer record written by format `version`
+fn parse_footer(version: u16, payload: &[u8]) -> Result<Footer>
+{
+    if version == 1
+    {
+        let footer: FooterV1 = bincode::deserialize(payload)?;
+        Ok(Footer
+        {
+            header: footer.header,
+            gapless_info: footer.gapless_info,
+            frame_offsets: footer.frame_offsets,
+            channel_mask: 0,
+            highpass: None,
+            bandwidth_extension: None,
+        })
+    }
+    else
+    {
+        let mut rest = payload;
+        let footer: FooterV2 = bincode::deserialize_from(&mut rest)?;
+        let highpass: Option<f32> = if rest.is_empty() { None } else { bincode::deserialize_from(&mut rest)? };
+        let bandwidth_extension: Option<f32> = if rest.is_empty() { None } else { bincode::deserialize(rest)? };
+        Ok(Footer
+        {
+            header: AudioHeader { highpass, bandwidth_extension, ..footer.header },
+            gapless_info: footer.gapless_info,
+            frame_offsets: footer.frame_offsets,
+            channel_mask: footer.channel_mask,
+            highpass,
+            bandwidth_extension,
+        })
+    }
+}
+
+/// Read a length + CRC-32 prefixed record, verifying the checksum
+fn read_record<R: Read>(reader: &mut R) -> Result<Vec<u8>>
+{
+    let len = read_u32(reader)?;
+    if len > MAX_RECORD_SIZE
+    {
+        return Err(anyhow!("Record length {} exceeds limit", len));
+    }
+    let expected_crc = read_u32(reader)?;
+
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload)?;
+    if crc32(&payload) != expected_crc
+    {
+        return Err(anyhow!("CRC mismatch"));
+    }
+    Ok(payload)
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> Result<u16>
+{
+    let mut buf = [0u8; 2];
+    reader.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32>
+{
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64>
+{
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
@@ -0,0 +1,376 @@
+//! Playback straight to a cpal device, without rodio's mixer in between
+//!
+//! A decode thread pulls samples from a [`PlaylistSource`] into a [`SampleRing`] and the device
+//! callback copies them out, so the only buffering is the ring itself and whatever the device
+//! asks for. Whenever the ring runs dry before the playlist ends, the callback plays silence and
+//! counts it in [`UnderrunStats`].
+use anyhow::{anyhow, Result};
+use rodio::cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use rodio::cpal::{self, FromSample, SampleFormat, SizedSample, StreamConfig};
+use rodio::Source;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use crate::audio::FormatConverter;
+use crate::playback::{find_output_device, PlaylistSource};
+
+/// Frames the decode thread reads from the playlist at a time
+const FEED_FRAMES: usize = 512;
+
+/// Fixed-size queue of samples for one producer thread and one consumer thread, neither of which
+/// ever blocks or allocates
+pub struct SampleRing
+{
+    /// Sample bits, as `f32::to_bits`
+    samples: Box<[AtomicU32]>,
+    /// Total samples ever read and written; their difference is how many are waiting
+    read: AtomicUsize,
+    write: AtomicUsize,
+}
+
+impl SampleRing
+{
+    pub fn new(capacity: usize) -> Self
+    {
+        Self
+        {
+            samples: (0..capacity.max(1)).map(|_| AtomicU32::new(0)).collect(),
+            read: AtomicUsize::new(0),
+            write: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn capacity(&self) -> usize
+    {
+        self.samples.len()
+    }
+
+    /// Samples waiting to be read
+    pub fn len(&self) -> usize
+    {
+        self.write.load(Ordering::Acquire).wrapping_sub(self.read.load(Ordering::Acquire))
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.len() == 0
+    }
+
+    /// Append as many of `samples` as there is room for, returning how many that was
+    /// Only one thread may push
+    pub fn push(&self, samples: &[f32]) -> usize
+    {
+        let write = self.write.load(Ordering::Relaxed);
+        let free = self.capacity() - write.wrapping_sub(self.read.load(Ordering::Acquire));
+        let count = samples.len().min(free);
+        for (i, sample) in samples[..count].iter().enumerate()
+        {
+            self.samples[write.wrapping_add(i) % self.capacity()].store(sample.to_bits(), Ordering::Relaxed);
+        }
+        self.write.store(write.wrapping_add(count), Ordering::Release);
+        count
+    }
+
+    /// Fill the start of `out` with the oldest waiting samples, returning how many there were
+    /// Only one thread may pop
+    pub fn pop(&self, out: &mut [f32]) -> usize
+    {
+        let read = self.read.load(Ordering::Relaxed);
+        let count = out.len().min(self.write.load(Ordering::Acquire).wrapping_sub(read));
+        for (i, sample) in out[..count].iter_mut().enumerate()
+        {
+            *sample = f32::from_bits(self.samples[read.wrapping_add(i) % self.capacity()].load(Ordering::Relaxed));
+        }
+        self.read.store(read.wrapping_add(count), Ordering::Release);
+        count
+    }
+}
+
+/// How often the device asked for audio the decode thread hadn't supplied yet
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UnderrunStats
+{
+    /// Times the device callback ran
+    pub callbacks: u64,
+    /// Callbacks that ran out of audio partway, before the playlist ended
+    pub underruns: u64,
+    /// Frames of silence played in their place
+    pub silent_frames: u64,
+}
+
+/// State shared by the decode thread, the device callback, and the [`CpalOutput`] owning them
+struct Shared
+{
+    ring: SampleRing,
+    /// Set by the decode thread once the ring is half full, so the callback doesn't count the
+    /// silence before the first audio as underruns
+    primed: AtomicBool,
+    paused: AtomicBool,
+    /// Tells the decode thread to give up
+    stop: AtomicBool,
+    /// Set by the decode thread once the playlist has ended
+    fed: AtomicBool,
+    /// Set by the callback once everything the decode thread fed has been played
+    drained: AtomicBool,
+    callbacks: AtomicU64,
+    underruns: AtomicU64,
+    silent_frames: AtomicU64,
+}
+
+impl Shared
+{
+    fn new(capacity: usize) -> Self
+    {
+        Self
+        {
+            ring: SampleRing::new(capacity),
+            primed: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            stop: AtomicBool::new(false),
+            fed: AtomicBool::new(false),
+            drained: AtomicBool::new(false),
+            callbacks: AtomicU64::new(0),
+            underruns: AtomicU64::new(0),
+            silent_frames: AtomicU64::new(0),
+        }
+    }
+}
+
+/// A playlist playing on a cpal output stream
+pub struct CpalOutput
+{
+    stream: cpal::Stream,
+    shared: Arc<Shared>,
+    feeder: Option<JoinHandle<()>>,
+    sample_rate: u32,
+    channels: u16,
+}
+
+impl CpalOutput
+{
+    /// Start playing `playlist` on the device named `device`, or the default one, keeping up to
+    /// `buffer` of audio decoded ahead of the device
+    /// The device plays the playlist's own format when it can; otherwise the playlist is converted
+    /// to the device's default format
+    pub fn start(playlist: PlaylistSource, device: Option<&str>, buffer: Duration) -> Result<Self>
+    {
+        let device = match device
+        {
+            Some(name) => find_output_device(name)?,
+            None => cpal::default_host().default_output_device().ok_or_else(|| anyhow!("No default audio output"))?,
+        };
+
+        let (sample_rate, channels) = (playlist.sample_rate(), playlist.channels());
+        let wanted = cpal::SampleRate(sample_rate);
+        let matching = device.supported_output_configs()
+                             .map_err(|e| anyhow!("Failed to query audio output: {}", e))?
+                             .filter(|range| range.channels() == channels
+                                             && (range.min_sample_rate()..=range.max_sample_rate()).contains(&wanted))
+                             .max_by_key(|range| range.sample_format() == SampleFormat::F32)
+                             .map(|range| range.with_sample_rate(wanted));
+        let supported = match matching
+        {
+            Some(config) => config,
+            None => device.default_output_config().map_err(|e| anyhow!("Failed to query audio output: {}", e))?,
+        };
+        let config: StreamConfig = supported.config();
+
+        let capacity = (buffer.as_secs_f64() * config.sample_rate.0 as f64) as usize * config.channels as usize;
+        let shared = Arc::new(Shared::new(capacity.max(FEED_FRAMES * config.channels as usize)));
+
+        let stream = match supported.sample_format()
+        {
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, shared.clone()),
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, shared.clone()),
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, shared.clone()),
+            SampleFormat::I32 => build_stream::<i32>(&device, &config, shared.clone()),
+            format => Err(anyhow!("Unsupported audio output sample format {:?}", format)),
+        }?;
+
+        let converter = ((config.sample_rate.0, config.channels) != (sample_rate, channels))
+            .then(|| FormatConverter::new(config.sample_rate.0, config.channels));
+        let feeder_shared = shared.clone();
+        let feeder = thread::spawn(move || feed(playlist, converter, &feeder_shared));
+
+        stream.play().map_err(|e| anyhow!("Failed to start audio output: {}", e))?;
+        Ok(Self { stream, shared, feeder: Some(feeder), sample_rate: config.sample_rate.0, channels: config.channels })
+    }
+
+    /// Sample rate the device plays at
+    pub fn sample_rate(&self) -> u32
+    {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u16
+    {
+        self.channels
+    }
+
+    /// Seconds of audio decoded but not yet handed to the device
+    pub fn buffered(&self) -> f64
+    {
+        self.shared.ring.len() as f64 / self.channels.max(1) as f64 / self.sample_rate as f64
+    }
+
+    pub fn pause(&self)
+    {
+        self.shared.paused.store(true, Ordering::Relaxed);
+    }
+
+    pub fn play(&self)
+    {
+        self.shared.paused.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool
+    {
+        self.shared.paused.load(Ordering::Relaxed)
+    }
+
+    /// Whether the playlist has played to its end or been stopped
+    pub fn is_finished(&self) -> bool
+    {
+        self.shared.drained.load(Ordering::Acquire)
+    }
+
+    /// Block until the playlist finishes
+    pub fn sleep_until_end(&self)
+    {
+        while !self.is_finished()
+        {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Silence the device and end the playlist
+    pub fn stop(&mut self)
+    {
+        self.shared.stop.store(true, Ordering::Relaxed);
+        self.shared.drained.store(true, Ordering::Release);
+        self.stream.pause().ok();
+        if let Some(feeder) = self.feeder.take()
+        {
+            feeder.join().ok();
+        }
+    }
+
+    pub fn stats(&self) -> UnderrunStats
+    {
+        UnderrunStats
+        {
+            callbacks: self.shared.callbacks.load(Ordering::Relaxed),
+            underruns: self.shared.underruns.load(Ordering::Relaxed),
+            silent_frames: self.shared.silent_frames.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Drop for CpalOutput
+{
+    fn drop(&mut self)
+    {
+        self.stop();
+    }
+}
+
+/// Open an output stream of sample type `T` whose callback plays what `shared.ring` holds
+fn build_stream<T>(device: &cpal::Device, config: &StreamConfig, shared: Arc<Shared>) -> Result<cpal::Stream>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = config.channels.max(1) as u64;
+    let mut scratch = vec![0.0f32; shared.ring.capacity()];
+    let callback = move |data: &mut [T], _: &cpal::OutputCallbackInfo|
+    {
+        shared.callbacks.fetch_add(1, Ordering::Relaxed);
+        if !shared.primed.load(Ordering::Acquire) || shared.paused.load(Ordering::Relaxed)
+           || shared.drained.load(Ordering::Acquire)
+        {
+            data.fill(T::EQUILIBRIUM);
+            return;
+        }
+
+        // The device may ask for more than the ring holds; this only allocates if so
+        if scratch.len() < data.len()
+        {
+            scratch.resize(data.len(), 0.0);
+        }
+        // Check before popping, so samples fed just after the check still count as an underrun
+        let fed = shared.fed.load(Ordering::Acquire);
+        let read = shared.ring.pop(&mut scratch[..data.len()]);
+        for (out, &sample) in data.iter_mut().zip(&scratch[..read])
+        {
+            *out = T::from_sample(sample);
+        }
+        data[read..].fill(T::EQUILIBRIUM);
+
+        if read < data.len()
+        {
+            if fed
+            {
+                shared.drained.store(true, Ordering::Release);
+            }
+            else
+            {
+                shared.underruns.fetch_add(1, Ordering::Relaxed);
+                shared.silent_frames.fetch_add((data.len() - read) as u64 / channels, Ordering::Relaxed);
+            }
+        }
+    };
+    device.build_output_stream(config, callback, |e| eprintln!("Audio output error: {}", e), None)
+          .map_err(|e| anyhow!("Failed to open audio output stream: {}", e))
+}
+
+/// Decode `playlist` into `shared.ring` as fast as the device drains it, converting it with
+/// `converter` if the device plays another format
+fn feed(mut playlist: PlaylistSource, mut converter: Option<FormatConverter>, shared: &Shared)
+{
+    let (sample_rate, channels) = (playlist.sample_rate(), playlist.channels());
+    let mut block = Vec::with_capacity(FEED_FRAMES * channels as usize);
+    let mut converted = Vec::new();
+    let mut ended = false;
+    while !ended && !shared.stop.load(Ordering::Relaxed)
+    {
+        block.clear();
+        block.extend(playlist.by_ref().take(FEED_FRAMES * channels as usize));
+        ended = block.is_empty();
+
+        let samples = match &mut converter
+        {
+            Some(converter) =>
+            {
+                converted.clear();
+                if ended
+                {
+                    converter.finish(&mut converted);
+                }
+                else
+                {
+                    converter.push(&block, sample_rate, channels, &mut converted);
+                }
+                &converted
+            }
+            None => &block,
+        };
+
+        // Wait for room, a fraction of the ring at a time
+        let mut pending = &samples[..];
+        while !pending.is_empty() && !shared.stop.load(Ordering::Relaxed)
+        {
+            let written = shared.ring.push(pending);
+            pending = &pending[written..];
+            if shared.ring.len() >= shared.ring.capacity() / 2
+            {
+                shared.primed.store(true, Ordering::Release);
+            }
+            if !pending.is_empty()
+            {
+                thread::sleep(Duration::from_millis(2));
+            }
+        }
+    }
+    shared.fed.store(true, Ordering::Release);
+    shared.primed.store(true, Ordering::Release);
+}
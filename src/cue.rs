@@ -0,0 +1,127 @@
+//! Cue sheet parsing, for splitting a single-file album into tracks
+use anyhow::{anyhow, Result};
+use crate::container::{Tags, Track};
+
+/// Cue sheet positions are in CD frames of 1/75 s
+const CUE_FRAMES_PER_SECOND: u64 = 75;
+
+/// Album-level tags and the tracks described by cue sheet `text`
+/// Track boundaries come from each track's `INDEX 01`; the last track runs to `total_length`
+/// (samples per channel). `TITLE`/`PERFORMER` become `title`/`artist` on tracks and
+/// `album`/`albumartist` at the top level; `SONGWRITER` becomes `composer`
+pub fn parse_cue(text: &str, sample_rate: u32, total_length: u64) -> Result<(Tags, Vec<Track>)>
+{
+    let mut album = Tags::default();
+    let mut tracks: Vec<Track> = Vec::new();
+    // INDEX 01 of each track, in samples
+    let mut starts: Vec<Option<u64>> = Vec::new();
+
+    for (number, line) in text.lines().enumerate()
+    {
+        let line = line.trim();
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        let error = |message: &str| anyhow!("Cue sheet line {}: {}", number + 1, message);
+
+        match command.to_uppercase().as_str()
+        {
+            "TRACK" =>
+            {
+                let mut tags = Tags::default();
+                if let Some(n) = rest.split_whitespace().next()
+                {
+                    let n: u32 = n.parse().map_err(|_| error("bad track number"))?;
+                    tags.set("tracknumber", n.to_string());
+                }
+                tracks.push(Track { start: 0, length: 0, tags });
+                starts.push(None);
+            }
+            "INDEX" =>
+            {
+                let mut fields = rest.split_whitespace();
+                let index = fields.next().ok_or_else(|| error("missing index number"))?;
+                let time = fields.next().ok_or_else(|| error("missing index time"))?;
+                let start = starts.last_mut().ok_or_else(|| error("INDEX before TRACK"))?;
+                if index.parse::<u32>().ok() == Some(1)
+                {
+                    *start = Some(parse_cue_time(time, sample_rate).ok_or_else(|| error("bad index time"))?);
+                }
+            }
+            "TITLE" | "PERFORMER" | "SONGWRITER" =>
+            {
+                let value = unquote(rest);
+                let key = match (command.to_uppercase().as_str(), tracks.is_empty())
+                {
+                    ("TITLE", true) => "album",
+                    ("TITLE", false) => "title",
+                    ("PERFORMER", true) => "albumartist",
+                    ("PERFORMER", false) => "artist",
+                    _ => "composer",
+                };
+                match tracks.last_mut()
+                {
+                    Some(track) => track.tags.set(key, value),
+                    None => album.set(key, value),
+                }
+            }
+            "REM" =>
+            {
+                // Common extensions: REM DATE 1999, REM GENRE Rock
+                let (key, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                let key = key.to_lowercase();
+                if matches!(key.as_str(), "date" | "genre" | "comment") && !value.trim().is_empty()
+                {
+                    match tracks.last_mut()
+                    {
+                        Some(track) => track.tags.set(&key, unquote(value.trim())),
+                        None => album.set(&key, unquote(value.trim())),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if tracks.is_empty()
+    {
+        return Err(anyhow!("Cue sheet has no tracks"));
+    }
+    for (i, (track, start)) in tracks.iter_mut().zip(&starts).enumerate()
+    {
+        track.start = start.ok_or_else(|| anyhow!("Cue sheet track {} has no INDEX 01", i + 1))?;
+    }
+
+    let ends: Vec<u64> = tracks.iter().skip(1).map(|t| t.start).chain(std::iter::once(total_length)).collect();
+    for (i, (track, end)) in tracks.iter_mut().zip(ends).enumerate()
+    {
+        if end <= track.start || track.start >= total_length
+        {
+            return Err(anyhow!("Cue sheet track {} is empty or starts past the end of the audio", i + 1));
+        }
+        track.length = end - track.start;
+    }
+
+    Ok((album, tracks))
+}
+
+/// Parse `mm:ss:ff` (minutes, seconds, 1/75 s frames) into samples
+fn parse_cue_time(time: &str, sample_rate: u32) -> Option<u64>
+{
+    let mut parts = time.split(':').map(|p| p.parse::<u64>().ok());
+    let (minutes, seconds, frames) = (parts.next()??, parts.next()??, parts.next()??);
+    if parts.next().is_some() || seconds >= 60 || frames >= CUE_FRAMES_PER_SECOND
+    {
+        return None;
+    }
+
+    let rate = sample_rate as u64;
+    Some((minutes * 60 + seconds) * rate + frames * rate / CUE_FRAMES_PER_SECOND)
+}
+
+fn unquote(value: &str) -> String
+{
+    value.strip_prefix('"')
+         .and_then(|v| v.strip_suffix('"'))
+         .unwrap_or(value)
+         .to_string()
+}
@@ -0,0 +1,509 @@
+//! Frame-level editing of GLC streams without a decode/re-encode round trip
+//!
+//! Frames overlap by half their length, so a cut keeps every frame needed to reconstruct the
+//! samples in range and records the surplus at either end as encoder delay and padding.
+//! Decoders then trim the output to exactly the requested samples.
+use anyhow::{anyhow, Result};
+use std::io::{Cursor, Read, Seek, Write};
+use crate::codec::{Decoder, Encoder, GaplessInfo, OverlapState};
+use crate::container::{read_encoded, GlcDecoder, GlcFrameWriter, Tags, Track};
+use crate::loudness::{Loudness, LoudnessMeter};
+
+/// Level ReplayGain gains bring tracks to (ReplayGain 2.0), in LUFS
+pub const REPLAYGAIN_REFERENCE_LUFS: f64 = -18.0;
+
+/// Copy the part of `source` from `start` for `length` samples per channel (in the decoded
+/// timeline) to a new stream on `writer`, tagged with `tags`; returns the writer
+pub fn extract_range<R: Read + Seek, W: Write>(source: &mut GlcDecoder<R>, start: u64, length: u64, tags: Tags, writer: W)
+    -> Result<W>
+{
+    extract_frames(source, start, length, tags, Vec::new(), writer)
+}
+
+/// Cut `source` down to `start..start + length` (samples per channel), keeping its tags and
+/// the parts of its tracks that fall inside the range; returns the writer
+pub fn trim<R: Read + Seek, W: Write>(source: &mut GlcDecoder<R>, start: u64, length: u64, writer: W) -> Result<W>
+{
+    let end = start + length;
+    let tracks = source.tracks()
+                       .iter()
+                       .filter(|t| t.start < end && t.start + t.length > start)
+                       .map(|t|
+                       {
+                           let from = t.start.max(start);
+                           Track { start: from - start, length: (t.start + t.length).min(end) - from, tags: t.tags.clone() }
+                       })
+                       .collect();
+    let tags = source.tags().clone();
+    extract_frames(source, start, length, tags, tracks, writer)
+}
+
+fn extract_frames<R: Read + Seek, W: Write>(source: &mut GlcDecoder<R>, start: u64, length: u64, tags: Tags, tracks: Vec<Track>, writer: W)
+    -> Result<W>
+{
+    let channels = source.header().channels.max(1) as u64;
+    let delay = source.gapless_info().encoder_delay as u64;
+    let total = source.gapless_info().original_length / channels;
+    if length == 0 || start + length > total
+    {
+        return Err(anyhow!("Range {}..{} is empty or outside the stream (0..{})", start, start + length, total));
+    }
+
+    let hop = source.header().hop_size as u64;
+    let (first, _) = seek_point(start, delay, hop);
+    // Predicted frames need the audio before them, so copy from where prediction restarts
+    let first = source.restart_frame(first as usize)? as u64;
+    let encoder_delay = start + delay - first * hop;
+    let end = (start + length + delay).div_ceil(hop).min(source.frame_count() as u64);
+
+    let header = source.header();
+    let mut out = GlcFrameWriter::with_hop_size(writer, header.sample_rate, header.channels, header.hop_size as usize)?;
+    out.set_channel_mask(source.channel_mask());
+    out.set_highpass(header.highpass);
+    out.set_long_term_prediction(header.long_term_prediction);
+    out.set_bandwidth_extension(header.bandwidth_extension);
+    out.set_tags(tags);
+    out.set_tracks(tracks);
+    for index in first..end
+    {
+        out.write_frame_bytes(&source.read_frame_bytes(index as usize)?)?;
+    }
+
+    // Untrimmed output is one hop per frame plus the final overlap flush
+    let padding = (end - first + 1) * hop - encoder_delay - length;
+    out.finish(GaplessInfo
+    {
+        encoder_delay: encoder_delay as u32,
+        padding: padding as u32,
+        original_length: length * channels,
+    })
+}
+
+/// Parse a time given as seconds (`83.5`), `m:ss` (`1:23.5`) or `h:mm:ss` (`1:02:03`)
+pub fn parse_time(text: &str) -> Result<f64>
+{
+    let error = || anyhow!("Invalid time '{}': expected seconds, m:ss or h:mm:ss", text);
+    let parts: Vec<&str> = text.trim().split(':').collect();
+    if parts.len() > 3
+    {
+        return Err(error());
+    }
+
+    let mut seconds = 0.0;
+    for (i, part) in parts.iter().enumerate()
+    {
+        let last = i == parts.len() - 1;
+        let value: f64 = if last { part.parse().map_err(|_| error())? } else { part.parse::<u32>().map_err(|_| error())? as f64 };
+        if !value.is_finite() || value < 0.0 || (i > 0 && value >= 60.0)
+        {
+            return Err(error());
+        }
+        seconds = seconds * 60.0 + value;
+    }
+    Ok(seconds)
+}
+
+/// Parse a gain in decibels such as `-3dB`, `+1.5 dB` or `-3`
+pub fn parse_gain(text: &str) -> Result<f64>
+{
+    let trimmed = text.trim();
+    let number = match trimmed.len().checked_sub(2)
+    {
+        Some(split) if trimmed.is_char_boundary(split) && trimmed[split..].eq_ignore_ascii_case("db") => &trimmed[..split],
+        _ => trimmed,
+    };
+    number.trim()
+          .parse::<f64>()
+          .ok()
+          .filter(|g| g.is_finite())
+          .ok_or_else(|| anyhow!("Invalid gain '{}': expected decibels, e.g. -3dB", text))
+}
+
+/// Which ReplayGain tag to go by
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum ReplayGainMode
+{
+    Track,
+    Album,
+}
+
+/// The `replaygain_<scope>_gain` tag in dB (`scope` being "track" or "album"), if present and valid
+pub fn replaygain_gain(tags: &Tags, scope: &str) -> Option<f64>
+{
+    tags.get(&format!("replaygain_{}_gain", scope)).and_then(|gain| parse_gain(gain).ok())
+}
+
+/// ReplayGain gain in dB for audio measured as `loudness`; 0 for silence
+pub fn replaygain_db(loudness: &Loudness) -> f64
+{
+    if loudness.integrated_lufs.is_finite() { REPLAYGAIN_REFERENCE_LUFS - loudness.integrated_lufs } else { 0.0 }
+}
+
+/// Set the `replaygain_<scope>_gain` and `replaygain_<scope>_peak` tags (`scope` being "track" or
+/// "album") for audio measured as `loudness`
+pub fn set_replaygain(tags: &mut Tags, scope: &str, loudness: &Loudness)
+{
+    tags.set(&format!("replaygain_{}_gain", scope), format!("{:.2} dB", replaygain_db(loudness)));
+    tags.set(&format!("replaygain_{}_peak", scope), format!("{:.6}", loudness.peak));
+}
+
+/// Decode `source` into a loudness meter, leaving out its encoder delay and padding
+/// Pool several files' meters with [`combined_loudness`](crate::loudness::combined_loudness) for album gain
+pub fn measure_loudness<R: Read + Seek>(source: &mut GlcDecoder<R>) -> Result<LoudnessMeter>
+{
+    let header = source.header().clone();
+    let channels = header.channels as usize;
+    let decoder = Decoder::new(channels, header.sample_rate);
+    let mut state = OverlapState::with_hop_size(channels, header.hop_size as usize)?;
+    let mut meter = LoudnessMeter::new(header.sample_rate, header.channels);
+
+    // Interleaved samples still to skip at the start, and to measure after that
+    let mut skip = source.gapless_info().encoder_delay as usize * channels;
+    let mut remaining = source.gapless_info().original_length as usize;
+    let mut measure = |block: &[f32]|
+    {
+        let start = skip.min(block.len());
+        skip -= start;
+        let end = start + remaining.min(block.len() - start);
+        remaining -= end - start;
+        meter.add_samples(&block[start..end]);
+    };
+    for index in 0..source.frame_count()
+    {
+        measure(&decoder.decode_frame(&source.read_frame(index)?, &mut state));
+    }
+    measure(&state.flush());
+    Ok(meter)
+}
+
+/// Change the level of `source` by `gain_db` by scaling each frame's scale factors (and the
+/// samples of raw PCM frames) instead of re-encoding; returns the writer and how many raw PCM
+/// samples clipped. ReplayGain tags are adjusted so that they still give the same playback level.
+/// Compressed frames cannot clip here, but a positive gain can push the decoded output past full scale.
+pub fn apply_gain<R: Read + Seek, W: Write>(source: &mut GlcDecoder<R>, gain_db: f64, writer: W) -> Result<(W, usize)>
+{
+    let factor = 10f64.powf(gain_db / 20.0);
+
+    let header = source.header();
+    let mut out = GlcFrameWriter::with_hop_size(writer, header.sample_rate, header.channels, header.hop_size as usize)?;
+    out.set_channel_mask(source.channel_mask());
+    out.set_highpass(header.highpass);
+    out.set_long_term_prediction(header.long_term_prediction);
+    out.set_bandwidth_extension(header.bandwidth_extension);
+    out.set_tags(adjust_replaygain(source.tags(), gain_db));
+    out.set_tracks(source.tracks()
+                         .iter()
+                         .map(|t| Track { tags: adjust_replaygain(&t.tags, gain_db), ..t.clone() })
+                         .collect());
+
+    let mut clipped = 0;
+    for index in 0..source.frame_count()
+    {
+        let mut frame = source.read_frame(index)?;
+        for scale in &mut frame.scale_factors
+        {
+            *scale = (*scale as f64 * factor) as f32;
+        }
+        if let Some(ref mut raw) = frame.raw_pcm
+        {
+            for sample in raw.iter_mut()
+            {
+                let scaled = (*sample as f64 * factor).round();
+                if !(i16::MIN as f64..=i16::MAX as f64).contains(&scaled)
+                {
+                    clipped += 1;
+                }
+                *sample = scaled.clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+            }
+        }
+        out.write_frame(&frame)?;
+    }
+
+    let gapless_info = source.gapless_info().clone();
+    Ok((out.finish(gapless_info)?, clipped))
+}
+
+/// `tags` with ReplayGain gains lowered and peaks raised to account for a `gain_db` change in level
+fn adjust_replaygain(tags: &Tags, gain_db: f64) -> Tags
+{
+    let mut adjusted = tags.clone();
+    for scope in ["track", "album"]
+    {
+        let gain_key = format!("replaygain_{}_gain", scope);
+        if let Some(gain) = tags.get(&gain_key).and_then(|g| parse_gain(g).ok())
+        {
+            adjusted.set(&gain_key, format!("{:.2} dB", gain - gain_db));
+        }
+
+        let peak_key = format!("replaygain_{}_peak", scope);
+        if let Some(peak) = tags.get(&peak_key).and_then(|p| p.trim().parse::<f64>().ok())
+        {
+            adjusted.set(&peak_key, format!("{:.6}", peak * 10f64.powf(gain_db / 20.0)));
+        }
+    }
+    adjusted
+}
+
+/// Where decoding has to start to reach sample `start` (per channel) of a stream with `delay`
+/// samples of encoder delay and frames advancing by `hop`: the frame to decode first, with a
+/// cleared overlap, and how many samples per channel of the output to discard
+///
+/// The sample at padded position p is reconstructed from frames p / hop - 1 and p / hop, so
+/// decoding starts one frame early to prime the overlap.
+pub fn seek_point(start: u64, delay: u64, hop: u64) -> (u64, u64)
+{
+    let first = ((start + delay) / hop).saturating_sub(1);
+    (first, start + delay - first * hop)
+}
+
+/// Decode `length` samples per channel of `source` from `start`, decoding only the frames involved
+pub fn decode_range<R: Read + Seek>(source: &mut GlcDecoder<R>, start: u64, length: u64) -> Result<Vec<f32>>
+{
+    let bytes = extract_range(source, start, length, Tags::default(), Vec::new())?;
+    let encoded = read_encoded(Cursor::new(bytes))?;
+    Decoder::new(encoded.header.channels as usize, encoded.header.sample_rate).decode(&encoded, None)
+}
+
+/// How many frames [`chain`] copied and how many it had to re-encode
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ChainSummary
+{
+    pub copied_frames: usize,
+    pub reencoded_frames: usize,
+}
+
+/// Where one source of a chain lands in the output
+struct Span
+{
+    /// Position of the source's first sample in the output's padded timeline
+    position: u64,
+    /// Samples per channel
+    length: u64,
+    /// Output frame index of the source's frame 0, if the source lies on the output's frame grid
+    frame_offset: Option<i64>,
+    frame_count: u64,
+}
+
+/// Join `sources` end to end into one gapless stream on `writer`
+///
+/// Frames are copied wherever a source lines up with the output's frame grid and the frame lies
+/// entirely within that source; only the frames spanning each join are re-encoded from decoded
+/// audio. A source that does not line up (its start is not a whole number of hops from where
+/// its frames expect it) is re-encoded whole, and frames of a source with long-term prediction
+/// are re-encoded after a join until the next frame that restarts prediction. Each source becomes a track, or contributes its
+/// own tracks if it is already chained; tags shared by every source become the stream's tags.
+pub fn chain<R: Read + Seek, W: Write>(sources: &mut [GlcDecoder<R>], writer: W) -> Result<(W, ChainSummary)>
+{
+    let first = sources.first().ok_or_else(|| anyhow!("Nothing to chain"))?;
+    let sample_rate = first.header().sample_rate;
+    let channels = first.header().channels;
+    let channel_mask = first.channel_mask();
+    let delay = first.gapless_info().encoder_delay as u64;
+    let hop_size = first.header().hop_size;
+    let hop = hop_size as u64;
+
+    let mut spans = Vec::with_capacity(sources.len());
+    let mut position = delay;
+    for (i, source) in sources.iter().enumerate()
+    {
+        if source.header().sample_rate != sample_rate || source.header().channels != channels
+        {
+            return Err(anyhow!("Source {} is {} Hz, {} channels but source 1 is {} Hz, {} channels",
+                               i + 1, source.header().sample_rate, source.header().channels, sample_rate, channels));
+        }
+        if source.header().hop_size != hop_size
+        {
+            return Err(anyhow!("Source {} has {}-sample frames but source 1 has {}-sample frames",
+                               i + 1, 2 * source.header().hop_size, 2 * hop_size));
+        }
+
+        let length = source.gapless_info().original_length / channels.max(1) as u64;
+        let shift = position as i64 - source.gapless_info().encoder_delay as i64;
+        spans.push(Span
+        {
+            position,
+            length,
+            frame_offset: (shift.rem_euclid(hop as i64) == 0).then_some(shift / hop as i64),
+            frame_count: source.frame_count() as u64,
+        });
+        position += length;
+    }
+
+    let total = position - delay;
+    if total == 0
+    {
+        return Err(anyhow!("Nothing to chain: every source is empty"));
+    }
+
+    // End where the last source's own frames end, so it decodes exactly as it did alone;
+    // otherwise use enough frames that every sample has both frames it is reconstructed from
+    let last = spans.len() - 1;
+    let frame_count = match spans[last].frame_offset
+    {
+        Some(offset) if spans[last].length > 0 => (offset + spans[last].frame_count as i64) as u64,
+        _ => (delay + total - 1) / hop + 1,
+    };
+    let padding = (frame_count + 1) * hop - delay - total;
+
+    // The source frame that can be copied as output frame `m`, if any
+    let copyable = |m: u64| -> Option<(usize, usize)>
+    {
+        let block_start = m * hop;
+        let block_end = block_start + 2 * hop;
+        spans.iter().enumerate().find_map(|(k, span)|
+        {
+            let j = m as i64 - span.frame_offset?;
+            let inside = (k == 0 || block_start >= span.position) && (k == last || block_end <= span.position + span.length);
+            (j >= 0 && (j as u64) < span.frame_count && inside).then_some((k, j as usize))
+        })
+    };
+
+    let mut tracks = Vec::new();
+    for (source, span) in sources.iter().zip(&spans)
+    {
+        let offset = span.position - delay;
+        if source.tracks().is_empty()
+        {
+            tracks.push(Track { start: offset, length: span.length, tags: source.tags().clone() });
+        }
+        for track in source.tracks()
+        {
+            tracks.push(Track { start: offset + track.start, ..track.clone() });
+        }
+    }
+
+    let mut out = GlcFrameWriter::with_hop_size(writer, sample_rate, channels, hop as usize)?;
+    out.set_tags(common_tags(sources.iter().map(|s| s.tags())));
+    out.set_tracks(tracks);
+    if sources.iter().all(|s| s.channel_mask() == channel_mask)
+    {
+        out.set_channel_mask(channel_mask);
+    }
+    if sources.iter().all(|s| s.header().highpass == first.header().highpass)
+    {
+        out.set_highpass(first.header().highpass);
+    }
+    if sources.iter().all(|s| s.header().bandwidth_extension == first.header().bandwidth_extension)
+    {
+        out.set_bandwidth_extension(first.header().bandwidth_extension);
+    }
+    out.set_long_term_prediction(sources.iter().any(|s| s.header().long_term_prediction));
+
+    // A copyable frame predicted from earlier audio is only copied if it restarts prediction or
+    // follows its own predecessor, so that the audio it predicts from is the same as in its source
+    let next_copy = |sources: &mut [GlcDecoder<R>], m: u64, previous: Option<(usize, usize)>| -> Result<Option<(usize, usize)>>
+    {
+        match copyable(m)
+        {
+            Some((k, j)) if !sources[k].header().long_term_prediction
+                            || (j > 0 && previous == Some((k, j - 1)))
+                            || sources[k].read_frame(j)?.restarts_prediction() => Ok(Some((k, j))),
+            _ => Ok(None),
+        }
+    };
+
+    let encoder = Encoder::with_hop_size(sample_rate, hop as usize)?;
+    let ch = channels as usize;
+    let mut summary = ChainSummary::default();
+    let mut previous = None;
+    let mut m = 0u64;
+    while m < frame_count
+    {
+        if let Some((k, j)) = next_copy(sources, m, previous)?
+        {
+            out.write_frame_bytes(&sources[k].read_frame_bytes(j)?)?;
+            summary.copied_frames += 1;
+            previous = Some((k, j));
+            m += 1;
+            continue;
+        }
+
+        // Re-encode the run of frames that cannot be copied from the decoded audio they cover
+        let run_start = m;
+        while m < frame_count && next_copy(sources, m, None)?.is_none()
+        {
+            m += 1;
+        }
+        previous = None;
+        let span_start = (run_start * hop) as i64 - delay as i64;
+        let span_len = (m - run_start + 1) * hop;
+        let samples = chained_samples(sources, &spans, delay, span_start, span_len, ch)?;
+
+        let planar: Vec<Vec<f32>> = (0..ch).map(|c| samples.iter().skip(c).step_by(ch).copied().collect()).collect();
+        for i in 0..(m - run_start) as usize
+        {
+            let block_start = i * hop as usize;
+            let blocks: Vec<&[f32]> = planar.iter().map(|p| &p[block_start..block_start + 2 * hop as usize]).collect();
+            out.write_frame(&encoder.encode_block(&blocks))?;
+            summary.reencoded_frames += 1;
+        }
+    }
+
+    let writer = out.finish(GaplessInfo
+    {
+        encoder_delay: delay as u32,
+        padding: padding as u32,
+        original_length: total * channels as u64,
+    })?;
+    Ok((writer, summary))
+}
+
+/// Decoded output samples `start..start + length` (per channel, may start before 0 or run past
+/// the end, where the output is silent), interleaved
+fn chained_samples<R: Read + Seek>(sources: &mut [GlcDecoder<R>], spans: &[Span], delay: u64, start: i64, length: u64, channels: usize)
+    -> Result<Vec<f32>>
+{
+    let mut samples = vec![0.0f32; length as usize * channels];
+    let end = start + length as i64;
+
+    for (source, span) in sources.iter_mut().zip(spans)
+    {
+        let span_start = (span.position - delay) as i64;
+        let from = start.max(span_start);
+        let to = end.min(span_start + span.length as i64);
+        if from >= to
+        {
+            continue;
+        }
+
+        let decoded = decode_range(source, (from - span_start) as u64, (to - from) as u64)?;
+        let offset = (from - start) as usize * channels;
+        samples[offset..offset + decoded.len()].copy_from_slice(&decoded);
+    }
+
+    Ok(samples)
+}
+
+/// Tags present with the same value in every one of `all` (empty if `all` is empty)
+pub fn common_tags<'a>(all: impl IntoIterator<Item = &'a Tags>) -> Tags
+{
+    let all: Vec<&Tags> = all.into_iter().collect();
+    let mut common = Tags::default();
+    if let Some(first) = all.first()
+    {
+        for (key, value) in first.iter()
+        {
+            if all.iter().all(|t| t.iter().any(|(k, v)| k == key && v == value))
+            {
+                common.add(key, value);
+            }
+        }
+    }
+    common
+}
+
+/// `base` (e.g. a stream's album, artist, ...) with every key that `overrides` defines
+/// replaced by its values there (e.g. a track's title and number)
+pub fn merge_tags(base: &Tags, overrides: &Tags) -> Tags
+{
+    let mut tags = base.clone();
+    for (key, _) in overrides.iter()
+    {
+        tags.remove(key);
+    }
+    for (key, value) in overrides.iter()
+    {
+        tags.add(key, value);
+    }
+    tags
+}
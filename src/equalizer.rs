@@ -0,0 +1,312 @@
+//! Biquad equalizer applied to decoded samples on their way to the output
+//!
+//! Bands are [RBJ cookbook](https://www.w3.org/TR/audio-eq-cookbook/) filters: peaking bands for a
+//! parametric or 10-band graphic EQ, and shelves for simple bass and treble controls. An
+//! [`Equalizer`] holds the settings another thread can change while the audio thread runs
+//! samples through a [`FilterBank`] built from them.
+use anyhow::{anyhow, Result};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use crate::edit::parse_gain;
+
+/// Centre frequencies of the 10-band graphic EQ, an octave apart
+pub const GRAPHIC_FREQUENCIES: [f32; 10] = [31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0];
+
+/// Q of a band about an octave wide, used by the graphic EQ and by bands given without a Q
+pub const OCTAVE_Q: f32 = 1.41;
+
+/// Corner frequencies of the `bass` and `treble` shelves
+pub const BASS_FREQUENCY: f32 = 100.0;
+pub const TREBLE_FREQUENCY: f32 = 10000.0;
+
+/// Largest boost or cut a band accepts, in dB
+pub const MAX_BAND_GAIN: f32 = 24.0;
+
+/// Shape of one band's filter
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BandKind
+{
+    /// Boosts or cuts around `frequency`, over a width set by `q`
+    Peak,
+    /// Boosts or cuts everything below `frequency`
+    LowShelf,
+    /// Boosts or cuts everything above `frequency`
+    HighShelf,
+}
+
+/// One band of an [`Equalizer`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EqBand
+{
+    pub kind: BandKind,
+    /// Centre (peak) or corner (shelf) frequency in Hz
+    pub frequency: f32,
+    pub gain_db: f32,
+    pub q: f32,
+}
+
+impl EqBand
+{
+    pub fn peak(frequency: f32, gain_db: f32, q: f32) -> Self
+    {
+        Self { kind: BandKind::Peak, frequency, gain_db, q }
+    }
+
+    pub fn bass(gain_db: f32) -> Self
+    {
+        Self { kind: BandKind::LowShelf, frequency: BASS_FREQUENCY, gain_db, q: std::f32::consts::FRAC_1_SQRT_2 }
+    }
+
+    pub fn treble(gain_db: f32) -> Self
+    {
+        Self { kind: BandKind::HighShelf, frequency: TREBLE_FREQUENCY, gain_db, q: std::f32::consts::FRAC_1_SQRT_2 }
+    }
+}
+
+/// Bands of a 10-band graphic EQ with `gains` (in dB) at [`GRAPHIC_FREQUENCIES`]
+pub fn graphic_bands(gains: &[f32; 10]) -> Vec<EqBand>
+{
+    GRAPHIC_FREQUENCIES.iter().zip(gains).map(|(&frequency, &gain)| EqBand::peak(frequency, gain, OCTAVE_Q)).collect()
+}
+
+/// Parse comma-separated bands, each as [`parse_band`] takes them
+pub fn parse_bands(text: &str) -> Result<Vec<EqBand>>
+{
+    text.split(',').map(str::trim).filter(|entry| !entry.is_empty()).map(parse_band).collect()
+}
+
+/// Parse one band: `FREQ:GAIN` or `FREQ:GAIN:Q` for a peaking band (`60:+3`, `1k:-2`,
+/// `2.5k:+1.5dB:0.7`), or `bass:GAIN` / `treble:GAIN` for a shelf
+pub fn parse_band(text: &str) -> Result<EqBand>
+{
+    let fields: Vec<&str> = text.split(':').map(str::trim).collect();
+    let (frequency, gain, q) = match fields[..]
+    {
+        [frequency, gain] => (frequency, gain, None),
+        [frequency, gain, q] => (frequency, gain, Some(q)),
+        _ => return Err(anyhow!("Invalid EQ band '{}': expected FREQ:GAIN, FREQ:GAIN:Q, bass:GAIN or treble:GAIN", text)),
+    };
+
+    let gain = parse_gain(gain)? as f32;
+    if gain.abs() > MAX_BAND_GAIN
+    {
+        return Err(anyhow!("EQ gain '{}' is outside ±{} dB", fields[1], MAX_BAND_GAIN));
+    }
+    let q = q.map(|q| q.parse::<f32>()
+                       .ok()
+                       .filter(|q| q.is_finite() && *q > 0.0)
+                       .ok_or_else(|| anyhow!("Invalid EQ Q '{}': expected a positive number", q)))
+             .transpose()?;
+
+    // Shelves keep their gentle default slope unless given a Q
+    let band = match frequency.to_ascii_lowercase().as_str()
+    {
+        "bass" => EqBand::bass(gain),
+        "treble" => EqBand::treble(gain),
+        frequency => EqBand::peak(parse_frequency(frequency)?, gain, OCTAVE_Q),
+    };
+    Ok(EqBand { q: q.unwrap_or(band.q), ..band })
+}
+
+/// A frequency in Hz, with an optional `k` (kHz) or `Hz` suffix: `60`, `1k`, `2.5kHz`
+fn parse_frequency(text: &str) -> Result<f32>
+{
+    let lower = text.to_ascii_lowercase();
+    let number = lower.strip_suffix("hz").unwrap_or(&lower).trim();
+    let (number, scale) = match number.strip_suffix('k')
+    {
+        Some(number) => (number, 1000.0),
+        None => (number, 1.0),
+    };
+    number.parse::<f32>()
+          .ok()
+          .map(|frequency| frequency * scale)
+          .filter(|frequency| frequency.is_finite() && *frequency > 0.0)
+          .ok_or_else(|| anyhow!("Invalid EQ frequency '{}': expected Hz, e.g. 60 or 1k", text))
+}
+
+/// Settings shared between whoever adjusts the EQ and the [`FilterBank`] playing through it
+///
+/// Each change bumps a generation counter, so the audio thread can tell without locking whether
+/// it needs to rebuild its filters.
+#[derive(Debug)]
+pub struct Equalizer
+{
+    bands: Mutex<Vec<EqBand>>,
+    enabled: AtomicBool,
+    generation: AtomicU64,
+}
+
+impl Default for Equalizer
+{
+    /// Flat (no bands), and enabled so that setting bands takes effect at once
+    fn default() -> Self
+    {
+        Self { bands: Mutex::new(Vec::new()), enabled: AtomicBool::new(true), generation: AtomicU64::new(0) }
+    }
+}
+
+impl Equalizer
+{
+    pub fn bands(&self) -> Vec<EqBand>
+    {
+        self.bands.lock().unwrap().clone()
+    }
+
+    /// Replace the bands; takes effect from the next block of audio
+    pub fn set_bands(&self, bands: Vec<EqBand>)
+    {
+        *self.bands.lock().unwrap() = bands;
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+
+    pub fn enabled(&self) -> bool
+    {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Bypass the EQ without forgetting its bands
+    pub fn set_enabled(&self, enabled: bool)
+    {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Bumped by every [`Equalizer::set_bands`]
+    pub fn generation(&self) -> u64
+    {
+        self.generation.load(Ordering::Acquire)
+    }
+}
+
+/// Normalized biquad coefficients (a0 = 1)
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Biquad
+{
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+}
+
+impl Biquad
+{
+    fn new(band: &EqBand, sample_rate: u32) -> Self
+    {
+        // Keep the band below Nyquist, where the formulas stop making sense
+        let frequency = (band.frequency as f64).min(sample_rate as f64 * 0.49);
+        let w0 = std::f64::consts::TAU * frequency / sample_rate as f64;
+        let (sin, cos) = w0.sin_cos();
+        let a = 10f64.powf(band.gain_db as f64 / 40.0);
+        let alpha = sin / (2.0 * band.q.max(0.01) as f64);
+        let shelf = 2.0 * a.sqrt() * alpha;
+
+        let (b0, b1, b2, a0, a1, a2) = match band.kind
+        {
+            BandKind::Peak => (1.0 + alpha * a, -2.0 * cos, 1.0 - alpha * a, 1.0 + alpha / a, -2.0 * cos, 1.0 - alpha / a),
+            BandKind::LowShelf => (a * ((a + 1.0) - (a - 1.0) * cos + shelf),
+                                   2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                                   a * ((a + 1.0) - (a - 1.0) * cos - shelf),
+                                   (a + 1.0) + (a - 1.0) * cos + shelf,
+                                   -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                                   (a + 1.0) + (a - 1.0) * cos - shelf),
+            BandKind::HighShelf => (a * ((a + 1.0) + (a - 1.0) * cos + shelf),
+                                    -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                                    a * ((a + 1.0) + (a - 1.0) * cos - shelf),
+                                    (a + 1.0) - (a - 1.0) * cos + shelf,
+                                    2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                                    (a + 1.0) - (a - 1.0) * cos - shelf),
+        };
+        Self { b0: b0 / a0, b1: b1 / a0, b2: b2 / a0, a1: a1 / a0, a2: a2 / a0 }
+    }
+
+    /// Gain at `frequency` in dB
+    fn response_db(&self, frequency: f64, sample_rate: u32) -> f64
+    {
+        let w = std::f64::consts::TAU * frequency / sample_rate as f64;
+        // |H(e^jw)|² from the numerator and denominator evaluated on the unit circle
+        let magnitude = |c0: f64, c1: f64, c2: f64|
+        {
+            let re = c0 + c1 * w.cos() + c2 * (2.0 * w).cos();
+            let im = -(c1 * w.sin() + c2 * (2.0 * w).sin());
+            re * re + im * im
+        };
+        10.0 * (magnitude(self.b0, self.b1, self.b2) / magnitude(1.0, self.a1, self.a2)).log10()
+    }
+}
+
+/// The filters for a set of bands, run over interleaved audio of one format
+///
+/// Each band filters every channel in turn (transposed direct form II, in f64 so low bands at high
+/// sample rates stay stable), and remembers where each channel left off, so blocks can be passed
+/// in one at a time.
+#[derive(Clone, Debug)]
+pub struct FilterBank
+{
+    filters: Vec<Biquad>,
+    /// Two delay elements per band and channel, band-major
+    state: Vec<[f64; 2]>,
+    sample_rate: u32,
+    channels: usize,
+}
+
+impl FilterBank
+{
+    pub fn new(bands: &[EqBand], sample_rate: u32, channels: u16) -> Self
+    {
+        let mut bank = Self { filters: Vec::new(), state: Vec::new(), sample_rate, channels: channels.max(1) as usize };
+        bank.set_bands(bands);
+        bank
+    }
+
+    /// Switch to `bands`, keeping the filters' memory if the number of active bands is unchanged
+    /// so the change doesn't click
+    pub fn set_bands(&mut self, bands: &[EqBand])
+    {
+        // Flat bands do nothing but cost time
+        self.filters = bands.iter().filter(|band| band.gain_db != 0.0).map(|band| Biquad::new(band, self.sample_rate)).collect();
+        if self.state.len() != self.filters.len() * self.channels
+        {
+            self.state = vec![[0.0; 2]; self.filters.len() * self.channels];
+        }
+    }
+
+    /// Whether the bank leaves audio unchanged
+    pub fn is_flat(&self) -> bool
+    {
+        self.filters.is_empty()
+    }
+
+    /// Forget the audio filtered so far, e.g. after a seek
+    pub fn reset(&mut self)
+    {
+        self.state.iter_mut().for_each(|state| *state = [0.0; 2]);
+    }
+
+    /// Filter interleaved `samples` in place
+    pub fn process(&mut self, samples: &mut [f32])
+    {
+        let channels = self.channels;
+        for (band, filter) in self.filters.iter().enumerate()
+        {
+            let state = &mut self.state[band * channels..(band + 1) * channels];
+            for frame in samples.chunks_mut(channels)
+            {
+                for (sample, [z1, z2]) in frame.iter_mut().zip(state.iter_mut())
+                {
+                    let input = *sample as f64;
+                    let output = filter.b0 * input + *z1;
+                    *z1 = filter.b1 * input - filter.a1 * output + *z2;
+                    *z2 = filter.b2 * input - filter.a2 * output;
+                    *sample = output as f32;
+                }
+            }
+        }
+    }
+
+    /// Combined gain of every band at `frequency`, in dB
+    pub fn response_db(&self, frequency: f32) -> f64
+    {
+        self.filters.iter().map(|filter| filter.response_db(frequency as f64, self.sample_rate)).sum()
+    }
+}
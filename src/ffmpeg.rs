@@ -0,0 +1,181 @@
+//! Input through an external `ffmpeg` for formats `glc` can't read itself (`glc encode --allow-ffmpeg`)
+//!
+//! `ffprobe` reports the stream format and tags, then `ffmpeg` decodes the first audio stream to
+//! 32-bit float PCM on a pipe, the same way `glc play --ffplay` hands PCM to ffplay.
+use anyhow::{anyhow, Result};
+use std::io::{BufReader, ErrorKind, Read};
+use std::path::Path;
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::thread::JoinHandle;
+use crate::container::Tags;
+
+/// Streaming reader for any file ffmpeg can decode, yielding interleaved f32 samples block by block
+pub struct FfmpegReader
+{
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+    /// Collects ffmpeg's error output so a full pipe never stalls it
+    stderr: Option<JoinHandle<String>>,
+    sample_rate: u32,
+    channels: u16,
+    tags: Tags,
+    buffer: Vec<u8>,
+    done: bool,
+}
+
+impl FfmpegReader
+{
+    /// Probe `path` with ffprobe and start ffmpeg decoding its first audio stream
+    /// Both must be on the `PATH`
+    pub fn open(path: &Path) -> Result<Self>
+    {
+        let (sample_rate, channels, tags) = probe(path)?;
+        let (rate, channel_count) = (sample_rate.to_string(), channels.to_string());
+
+        let mut child = Command::new("ffmpeg")
+            .args(["-v", "error", "-nostdin", "-i"])
+            .arg(path)
+            .args([
+                "-map", "0:a:0",                  // first audio stream only
+                "-f", "f32le",                    // 32-bit float PCM
+                "-ar", rate.as_str(),             // keep the probed format
+                "-ac", channel_count.as_str(),
+                "-",                              // write to stdout
+            ])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow!("Failed to run ffmpeg: {}", e))?;
+
+        let stdout = BufReader::new(child.stdout.take().ok_or_else(|| anyhow!("Failed to open ffmpeg's output"))?);
+        let stderr = child.stderr.take().map(|mut stderr| std::thread::spawn(move ||
+        {
+            let mut text = String::new();
+            stderr.read_to_string(&mut text).ok();
+            text
+        }));
+
+        Ok(Self { child, stdout, stderr, sample_rate, channels, tags, buffer: Vec::new(), done: false })
+    }
+
+    pub fn sample_rate(&self) -> u32
+    {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u16
+    {
+        self.channels
+    }
+
+    /// Metadata reported by ffprobe (container tags, or the stream's if the container has none)
+    pub fn tags(&self) -> &Tags
+    {
+        &self.tags
+    }
+
+    /// Append up to `max_frames` frames of interleaved samples to `out`
+    /// Returns false (appending nothing) once ffmpeg has finished; fails if ffmpeg did
+    pub fn read_block(&mut self, out: &mut Vec<f32>, max_frames: usize) -> Result<bool>
+    {
+        if self.done
+        {
+            return Ok(false);
+        }
+
+        let frame_bytes = 4 * self.channels as usize;
+        self.buffer.resize(max_frames.max(1) * frame_bytes, 0);
+        let mut filled = 0;
+        while filled < self.buffer.len()
+        {
+            match self.stdout.read(&mut self.buffer[filled..])
+            {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        // A short read means the pipe was closed; a trailing partial frame is dropped
+        let whole = filled / frame_bytes * frame_bytes;
+        out.extend(self.buffer[..whole].chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])));
+        if filled < self.buffer.len()
+        {
+            self.finish()?;
+        }
+        Ok(whole > 0)
+    }
+
+    /// Wait for ffmpeg to exit, turning a failure into an error carrying its message
+    fn finish(&mut self) -> Result<()>
+    {
+        self.done = true;
+        let status = self.child.wait()?;
+        let message = self.stderr.take().and_then(|handle| handle.join().ok()).unwrap_or_default();
+        if !status.success()
+        {
+            return Err(anyhow!("ffmpeg failed ({}): {}", status, message.trim()));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for FfmpegReader
+{
+    /// Stop ffmpeg if the reader is dropped before the end of the input
+    fn drop(&mut self)
+    {
+        if !self.done
+        {
+            self.child.kill().ok();
+            self.child.wait().ok();
+        }
+    }
+}
+
+/// Sample rate, channel count, and tags of the first audio stream in `path`
+fn probe(path: &Path) -> Result<(u32, u16, Tags)>
+{
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "a:0", "-show_entries",
+               "stream=sample_rate,channels:stream_tags:format_tags", "-of", "json"])
+        .arg(path)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| anyhow!("Failed to run ffprobe: {}", e))?;
+    if !output.status.success()
+    {
+        return Err(anyhow!("ffprobe could not read the file: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    let info: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let stream = info["streams"].get(0).ok_or_else(|| anyhow!("No audio stream found"))?;
+    // ffprobe prints the sample rate as a string
+    let sample_rate = stream["sample_rate"].as_str()
+                                           .and_then(|rate| rate.parse::<u32>().ok())
+                                           .filter(|&rate| rate > 0)
+                                           .ok_or_else(|| anyhow!("ffprobe reported no sample rate"))?;
+    let channels = stream["channels"].as_u64()
+                                     .and_then(|channels| u16::try_from(channels).ok())
+                                     .filter(|&channels| channels > 0)
+                                     .ok_or_else(|| anyhow!("ffprobe reported no channel count"))?;
+
+    // Ogg and some other containers keep their tags on the stream rather than the container
+    let mut tags = Tags::default();
+    let source = match info["format"]["tags"].as_object()
+    {
+        Some(format_tags) if !format_tags.is_empty() => Some(format_tags),
+        _ => stream["tags"].as_object(),
+    };
+    for (key, value) in source.into_iter().flatten()
+    {
+        if let Some(value) = value.as_str()
+        {
+            tags.add(key, value);
+        }
+    }
+
+    Ok((sample_rate, channels, tags))
+}
@@ -2,7 +2,8 @@
 //! Currently supports compression level 5 with 16-bit samples
 
 use anyhow::{anyhow, Result};
-use std::io::Write;
+use std::fs::File;
+use std::io::{BufWriter, Cursor, Seek, SeekFrom, Write};
 use std::path::Path;
 
 /// FLAC file signature
@@ -301,22 +302,6 @@ impl MD5Context
     }
 }
 
-/// Compute MD5 checksum of audio samples
-fn compute_md5(samples: &[i16], channels: u16, bits_per_sample: u8) -> [u8; 16]
-{
-    let mut ctx = MD5Context::new();
-
-    // Process samples in little-endian byte order
-    // For FLAC, samples are interleaved and sign-extended if needed
-    for &sample in samples
-    {
-        let bytes = sample.to_le_bytes();
-        ctx.update(&bytes);
-    }
-
-    ctx.finalize()
-}
-
 /// Bit writer for FLAC encoding
 struct BitWriter
 {
@@ -384,14 +369,6 @@ impl BitWriter
         self.write_bits(byte as u64, 8);
     }
 
-    fn write_bytes(&mut self, bytes: &[u8])
-    {
-        for &byte in bytes
-        {
-            self.write_byte(byte);
-        }
-    }
-
     fn write_unary(&mut self, value: u32)
     {
         // Write 'value' zeros followed by a one
@@ -943,112 +920,171 @@ fn write_streaminfo(
     }
 }
 
-/// Main FLAC encoding function with compression level
-pub fn encode_flac_with_level(
-    samples: &[f32],
+/// Streaming FLAC encoder: takes interleaved samples a chunk at a time and writes each frame as
+/// soon as its block fills, then goes back to fill in STREAMINFO's length and MD5 on [`finish`]
+///
+/// [`finish`]: FlacWriter::finish
+pub struct FlacWriter<W: Write + Seek>
+{
+    writer: W,
+    /// Where the STREAMINFO block starts, rewritten once the length and MD5 are known
+    streaminfo_offset: u64,
     sample_rate: u32,
     channels: u16,
     compression_level: u8,
-) -> Result<Vec<u8>>
+    block_size: usize,
+    /// Interleaved samples short of a whole block
+    pending: Vec<i16>,
+    md5: MD5Context,
+    /// Samples per channel taken so far
+    total_samples: u64,
+    frame_number: u32,
+}
+
+impl<W: Write + Seek> FlacWriter<W>
 {
-    // Convert f32 samples to i16
-    let i16_samples: Vec<i16> = samples
-        .iter()
-        .map(|&s| (s * 32767.0).clamp(-32768.0, 32767.0) as i16)
-        .collect();
+    pub fn new(mut writer: W, sample_rate: u32, channels: u16, compression_level: u8) -> Result<Self>
+    {
+        // Validate compression level
+        if compression_level > 8
+        {
+            return Err(anyhow!(
+                "Invalid compression level {}, must be 0-8",
+                compression_level
+            ));
+        }
+        if !(1..=8).contains(&channels)
+        {
+            return Err(anyhow!("FLAC supports 1 to 8 channels, got {}", channels));
+        }
 
-    let total_samples = i16_samples.len() / channels as usize;
+        // Choose block size based on compression level
+        let block_size = match compression_level
+        {
+            0..=2 => 1152, // Fast encoding
+            _ => 4096,
+        };
 
-    // FLAC requires at least 16 samples per channel
-    if total_samples < 16
-    {
-        return Err(anyhow!(
-            "FLAC requires at least 16 samples per channel, got {}",
-            total_samples
-        ));
+        writer.write_all(&FLAC_SIGNATURE)?;
+        let streaminfo_offset = writer.stream_position()?;
+        let mut flac = Self
+        {
+            writer,
+            streaminfo_offset,
+            sample_rate,
+            channels,
+            compression_level,
+            block_size,
+            pending: Vec::with_capacity(block_size * channels as usize),
+            md5: MD5Context::new(),
+            total_samples: 0,
+            frame_number: 0,
+        };
+        // A placeholder until the length and MD5 are known
+        flac.write_streaminfo(flac.block_size, 0, [0; 16])?;
+        Ok(flac)
     }
 
-    // Validate compression level
-    if compression_level > 8
+    /// Encode interleaved `samples`, writing every block they complete
+    pub fn write_samples(&mut self, samples: &[f32]) -> Result<()>
     {
-        return Err(anyhow!(
-            "Invalid compression level {}, must be 0-8",
-            compression_level
-        ));
+        let block_len = self.block_size * self.channels as usize;
+        for &sample in samples
+        {
+            let sample = (sample * 32767.0).clamp(-32768.0, 32767.0) as i16;
+            self.md5.update(&sample.to_le_bytes());
+            self.pending.push(sample);
+            if self.pending.len() == block_len
+            {
+                self.write_block()?;
+            }
+        }
+        Ok(())
     }
 
-    let bits_per_sample = 16u8;
-
-    // Choose block size based on compression level
-    let block_size = match compression_level
+    /// Write the last, shorter block and the final STREAMINFO, and return the writer
+    pub fn finish(mut self) -> Result<W>
     {
-        0 => 1152,  // Fast encoding
-        1 => 1152,
-        2 => 1152,
-        3 => 4096,
-        4 => 4096,
-        5 => 4096,  // Default
-        6 => 4096,
-        7 => 4096,
-        8 => 4096,  // Maximum compression
-        _ => 4096,
-    }.min(total_samples).max(16);
-
-
-    let mut writer = BitWriter::new();
-
-    // Write FLAC signature
-    writer.write_bytes(&FLAC_SIGNATURE);
-
-    // Calculate MD5 checksum of audio data
-    let md5 = compute_md5(&i16_samples, channels, bits_per_sample);
-
-    // Write streaminfo
-    write_streaminfo(
-        &mut writer,
-        block_size as u16,
-        block_size as u16,
-        0, // Unknown min frame size
-        0, // Unknown max frame size
-        sample_rate,
-        channels,
-        bits_per_sample,
-        total_samples as u64,
-        md5,
-    );
-
-    // Encode frames
-    let mut sample_offset = 0;
-    let mut frame_number = 0u32;
-
-    while sample_offset < i16_samples.len()
-    {
-        let remaining = i16_samples.len() - sample_offset;
-        let current_block_size = block_size.min(remaining / channels as usize);
+        self.total_samples += (self.pending.len() / self.channels as usize) as u64;
 
-        if current_block_size == 0
+        // FLAC requires at least 16 samples per channel
+        if self.total_samples < 16
         {
-            break;
+            return Err(anyhow!(
+                "FLAC requires at least 16 samples per channel, got {}",
+                self.total_samples
+            ));
+        }
+        // Streams shorter than one block are a single block of their own length
+        let block_size = self.block_size.min(self.total_samples as usize);
+        if !self.pending.is_empty()
+        {
+            self.total_samples -= (self.pending.len() / self.channels as usize) as u64;
+            self.write_block()?;
         }
 
-        let frame_samples = &i16_samples[sample_offset..sample_offset + current_block_size * channels as usize];
+        let md5 = self.md5.finalize();
+        let end = self.writer.stream_position()?;
+        self.writer.seek(SeekFrom::Start(self.streaminfo_offset))?;
+        self.write_streaminfo(block_size, self.total_samples, md5)?;
+        self.writer.seek(SeekFrom::Start(end))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
 
+    /// Encode the pending samples as one frame
+    fn write_block(&mut self) -> Result<()>
+    {
+        let block_size = self.pending.len() / self.channels as usize;
+        let mut frame = BitWriter::new();
         encode_frame(
-            &mut writer,
-            frame_samples,
-            channels,
-            sample_rate,
-            bits_per_sample,
-            frame_number,
-            current_block_size,
-            compression_level,
+            &mut frame,
+            &self.pending,
+            self.channels,
+            self.sample_rate,
+            16,
+            self.frame_number,
+            block_size,
+            self.compression_level,
         )?;
+        self.writer.write_all(&frame.get_bytes())?;
+        self.pending.clear();
+        self.total_samples += block_size as u64;
+        self.frame_number += 1;
+        Ok(())
+    }
 
-        sample_offset += current_block_size * channels as usize;
-        frame_number += 1;
+    fn write_streaminfo(&mut self, block_size: usize, total_samples: u64, md5: [u8; 16]) -> Result<()>
+    {
+        let mut streaminfo = BitWriter::new();
+        write_streaminfo(
+            &mut streaminfo,
+            block_size as u16,
+            block_size as u16,
+            0, // Unknown min frame size
+            0, // Unknown max frame size
+            self.sample_rate,
+            self.channels,
+            16,
+            total_samples,
+            md5,
+        );
+        self.writer.write_all(&streaminfo.get_bytes())?;
+        Ok(())
     }
+}
 
-    Ok(writer.get_bytes())
+/// Main FLAC encoding function with compression level
+pub fn encode_flac_with_level(
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u16,
+    compression_level: u8,
+) -> Result<Vec<u8>>
+{
+    let mut flac = FlacWriter::new(Cursor::new(Vec::new()), sample_rate, channels, compression_level)?;
+    flac.write_samples(samples)?;
+    Ok(flac.finish()?.into_inner())
 }
 
 /// Main FLAC encoding function with default compression level 5
@@ -1070,9 +1106,9 @@ pub fn export_to_flac_with_level(
     compression_level: u8,
 ) -> Result<()>
 {
-    let flac_data = encode_flac_with_level(samples, sample_rate, channels, compression_level)?;
-    let mut file = std::fs::File::create(path)?;
-    file.write_all(&flac_data)?;
+    let mut flac = FlacWriter::new(BufWriter::new(File::create(path)?), sample_rate, channels, compression_level)?;
+    flac.write_samples(samples)?;
+    flac.finish()?;
     Ok(())
 }
 
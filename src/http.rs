@@ -0,0 +1,736 @@
+//! Streaming `.glc` files over HTTP: `glc serve-http` and `glc play http://...`
+//!
+//! [`serve_files`] answers `GET` and `HEAD` requests for the files under a directory, honouring
+//! byte ranges (`Range: bytes=...`) so a client can fetch only the parts of a file it needs.
+//! [`HttpReader`] is the other end: a `Read + Seek` over a URL that fetches blocks with range
+//! requests as they are read, so a [`GlcDecoder`](crate::container::GlcDecoder) on top of it reads
+//! the trailer and seek table first and then only the frames being played. Nothing is transcoded
+//! on the server.
+//!
+//! Only plain `http://` is supported, with no authentication; put a reverse proxy in front of
+//! `glc serve-http` to expose it beyond a trusted network.
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::net::{TcpListener, TcpStream};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// Bytes fetched by each range request an [`HttpReader`] makes
+const FETCH_BLOCK: u64 = 64 * 1024;
+
+/// Longest request or status line, header line, or header block accepted
+const MAX_LINE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 100;
+
+/// How long either side waits on a quiet connection before giving up on it
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Whether `input` names an HTTP URL rather than a local file
+pub fn is_url(input: &str) -> bool
+{
+    input.get(..7).is_some_and(|scheme| scheme.eq_ignore_ascii_case("http://"))
+}
+
+/// The parts of an `http://` URL needed to fetch it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Url
+{
+    pub host: String,
+    pub port: u16,
+    /// Path and query, starting with `/`
+    pub path: String,
+    /// `user:password` given before the host, if any
+    pub userinfo: Option<String>,
+}
+
+impl Url
+{
+    pub fn parse(url: &str) -> Result<Self>
+    {
+        if !is_url(url)
+        {
+            return Err(anyhow!("Only http:// URLs are supported, got '{}'", url));
+        }
+        let rest = &url[7..];
+        let (authority, path) = match rest.find(['/', '?', '#'])
+        {
+            Some(index) => rest.split_at(index),
+            None => (rest, "/"),
+        };
+        // Fragments are never sent to the server
+        let path = path.split('#').next().unwrap_or_default();
+        let path = if path.starts_with('/') { path.to_string() } else { format!("/{}", path) };
+
+        let (userinfo, authority) = match authority.rsplit_once('@')
+        {
+            Some((userinfo, host)) => (Some(percent_decode(userinfo).unwrap_or_else(|| userinfo.to_string())), host),
+            None => (None, authority),
+        };
+        let (host, port) = match authority.rsplit_once(':')
+        {
+            // A bracketed IPv6 address holds colons of its own
+            Some((host, port)) if !port.contains(']') =>
+            {
+                let port = port.parse().map_err(|_| anyhow!("Invalid port '{}' in '{}'", port, url))?;
+                (host, port)
+            }
+            _ => (authority, 80),
+        };
+        if host.is_empty()
+        {
+            return Err(anyhow!("No host in '{}'", url));
+        }
+        Ok(Self { host: host.to_string(), port, path, userinfo })
+    }
+
+    /// Value for the `Host` header
+    pub fn authority(&self) -> String
+    {
+        if self.port == 80 { self.host.clone() } else { format!("{}:{}", self.host, self.port) }
+    }
+}
+
+/// What a `Range` header asks of a file of a given length
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RangeRequest
+{
+    /// No range, or one this server doesn't honour (several ranges, other units): send it all
+    Whole,
+    /// These bytes
+    Partial(Range<u64>),
+    /// A range that lies past the end of the file
+    Unsatisfiable,
+}
+
+impl RangeRequest
+{
+    /// Interpret the `Range` header `value` against a file of `length` bytes
+    pub fn parse(value: &str, length: u64) -> Self
+    {
+        let Some(spec) = value.trim().strip_prefix("bytes=")
+        else
+        {
+            return Self::Whole;
+        };
+        if spec.contains(',')
+        {
+            return Self::Whole;
+        }
+        let Some((start, end)) = spec.trim().split_once('-')
+        else
+        {
+            return Self::Whole;
+        };
+
+        let range = match (start.trim(), end.trim())
+        {
+            ("", "") => return Self::Whole,
+            // The last `suffix` bytes
+            ("", suffix) => match suffix.parse::<u64>()
+            {
+                Ok(0) => return Self::Unsatisfiable,
+                Ok(suffix) => length.saturating_sub(suffix)..length,
+                Err(_) => return Self::Whole,
+            },
+            (start, end) =>
+            {
+                let Ok(start) = start.parse::<u64>()
+                else
+                {
+                    return Self::Whole;
+                };
+                let end = match end
+                {
+                    "" => length,
+                    end => match end.parse::<u64>()
+                    {
+                        Ok(end) if end >= start => end.saturating_add(1).min(length),
+                        _ => return Self::Whole,
+                    },
+                };
+                start..end
+            }
+        };
+
+        if range.start >= length
+        {
+            Self::Unsatisfiable
+        }
+        else
+        {
+            Self::Partial(range)
+        }
+    }
+}
+
+/// Serve the files under `root` to clients of `listener` until the process is killed, each client
+/// on a thread of its own
+/// Directories are listed as plain text, one entry per line, with subdirectories ending in `/`.
+pub fn serve_files(listener: TcpListener, root: PathBuf) -> Result<()>
+{
+    let root = root.canonicalize().map_err(|e| anyhow!("{}: {}", root.display(), e))?;
+    for stream in listener.incoming()
+    {
+        match stream
+        {
+            Ok(stream) =>
+            {
+                let root = root.clone();
+                thread::spawn(move || serve_client(stream, &root));
+            }
+            Err(e) => eprintln!("Error accepting a client: {}", e),
+        }
+    }
+    Ok(())
+}
+
+/// A request line and the headers that matter here
+struct Request
+{
+    method: String,
+    path: String,
+    range: Option<String>,
+    keep_alive: bool,
+}
+
+/// Answer requests on `stream` until the client disconnects or asks to close
+fn serve_client(stream: TcpStream, root: &Path)
+{
+    stream.set_read_timeout(Some(TIMEOUT)).ok();
+    let Ok(writer) = stream.try_clone()
+    else
+    {
+        return;
+    };
+    let mut writer = io::BufWriter::new(writer);
+    let mut reader = BufReader::new(stream);
+
+    loop
+    {
+        let request = match read_request(&mut reader)
+        {
+            Ok(Some(request)) => request,
+            Ok(None) => return,
+            // Anything but a malformed request (a timeout, a reset) means the client is gone
+            Err(e) if e.kind() == io::ErrorKind::InvalidData =>
+            {
+                respond_error(&mut writer, 400, "Bad Request", &e.to_string()).ok();
+                return;
+            }
+            Err(_) => return,
+        };
+
+        let result = respond(&mut writer, root, &request).and_then(|()| writer.flush());
+        if result.is_err() || !request.keep_alive
+        {
+            return;
+        }
+    }
+}
+
+/// Read one request, or None if the client hung up before sending one
+fn read_request<R: BufRead>(reader: &mut R) -> io::Result<Option<Request>>
+{
+    let mut line = String::new();
+    // Tolerate blank lines between requests
+    while line.trim().is_empty()
+    {
+        line.clear();
+        if read_line(reader, &mut line)? == 0
+        {
+            return Ok(None);
+        }
+    }
+
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target), version) = (parts.next(), parts.next(), parts.next())
+    else
+    {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Malformed request line"));
+    };
+    let mut request = Request
+    {
+        method: method.to_string(),
+        path: target.to_string(),
+        range: None,
+        // HTTP/1.1 keeps the connection open unless told otherwise; HTTP/1.0 closes it
+        keep_alive: version == Some("HTTP/1.1"),
+    };
+
+    for (name, value) in read_headers(reader)?
+    {
+        if name.eq_ignore_ascii_case("range")
+        {
+            request.range = Some(value);
+        }
+        else if name.eq_ignore_ascii_case("connection")
+        {
+            request.keep_alive = !value.eq_ignore_ascii_case("close")
+                && (request.keep_alive || value.eq_ignore_ascii_case("keep-alive"));
+        }
+    }
+    Ok(Some(request))
+}
+
+/// Send the response to `request`: the file or listing it names, or an error status
+fn respond<W: Write>(writer: &mut W, root: &Path, request: &Request) -> io::Result<()>
+{
+    let head = match request.method.as_str()
+    {
+        "GET" => false,
+        "HEAD" => true,
+        _ => return respond_error(writer, 405, "Method Not Allowed", "Only GET and HEAD are supported"),
+    };
+    let Some(path) = resolve_path(root, &request.path)
+    else
+    {
+        return respond_error(writer, 404, "Not Found", "No such file");
+    };
+
+    if path.is_dir()
+    {
+        let listing = list_directory(&path)?;
+        write!(writer, "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\n\r\n", listing.len())?;
+        if !head
+        {
+            writer.write_all(listing.as_bytes())?;
+        }
+        return Ok(());
+    }
+
+    let Ok(mut file) = File::open(&path)
+    else
+    {
+        return respond_error(writer, 404, "Not Found", "No such file");
+    };
+    let length = file.metadata()?.len();
+    let content_type = match path.extension().and_then(|ext| ext.to_str())
+    {
+        Some(ext) if ext.eq_ignore_ascii_case("glc") => "audio/x-glc",
+        _ => "application/octet-stream",
+    };
+
+    let range = match request.range.as_deref().map_or(RangeRequest::Whole, |value| RangeRequest::parse(value, length))
+    {
+        RangeRequest::Whole =>
+        {
+            write!(writer, "HTTP/1.1 200 OK\r\n")?;
+            0..length
+        }
+        RangeRequest::Partial(range) =>
+        {
+            write!(writer, "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\n", range.start, range.end - 1, length)?;
+            range
+        }
+        RangeRequest::Unsatisfiable =>
+        {
+            return write!(writer, "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */{}\r\nContent-Length: 0\r\n\r\n", length);
+        }
+    };
+    write!(writer, "Content-Type: {}\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\n\r\n", content_type, range.end - range.start)?;
+
+    if !head
+    {
+        file.seek(SeekFrom::Start(range.start))?;
+        let sent = io::copy(&mut file.take(range.end - range.start), writer)?;
+        if sent < range.end - range.start
+        {
+            // The file shrank while being sent; the client can't be told any other way
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "File was truncated while sending"));
+        }
+    }
+    Ok(())
+}
+
+fn respond_error<W: Write>(writer: &mut W, code: u16, reason: &str, message: &str) -> io::Result<()>
+{
+    write!(writer, "HTTP/1.1 {} {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\n\r\n{}\n",
+           code, reason, message.len() + 1, message)?;
+    writer.flush()
+}
+
+/// The file under `root` that the request target `target` names, or None if it names something
+/// outside `root` (or nothing at all)
+fn resolve_path(root: &Path, target: &str) -> Option<PathBuf>
+{
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+    let decoded = percent_decode(path)?;
+
+    let mut resolved = root.to_path_buf();
+    for component in Path::new(decoded.trim_start_matches('/')).components()
+    {
+        match component
+        {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    // Symlinks may still lead out of the root
+    let resolved = resolved.canonicalize().ok()?;
+    resolved.starts_with(root).then_some(resolved)
+}
+
+/// Decode `%XX` escapes in a URL path; None if they don't make valid UTF-8
+fn percent_decode(path: &str) -> Option<String>
+{
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len()
+    {
+        if bytes[i] == b'%'
+        {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        }
+        else
+        {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Escape the characters of `name` that can't appear as they are in a URL path
+fn percent_encode(name: &str) -> String
+{
+    let mut encoded = String::with_capacity(name.len());
+    for byte in name.bytes()
+    {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte)
+        {
+            encoded.push(byte as char);
+        }
+        else
+        {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// The `.glc` files and subdirectories of `dir`, in name order, escaped for use in URLs
+fn list_directory(dir: &Path) -> io::Result<String>
+{
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(dir)?
+    {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.')
+        {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir()
+        {
+            entries.push(format!("{}/", percent_encode(&name)));
+        }
+        else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("glc"))
+        {
+            entries.push(percent_encode(&name));
+        }
+    }
+    entries.sort();
+
+    let mut listing = entries.join("\n");
+    if !listing.is_empty()
+    {
+        listing.push('\n');
+    }
+    Ok(listing)
+}
+
+/// `Authorization` header value for the `user:password` in `userinfo`
+pub fn basic_auth(userinfo: &str) -> String
+{
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::from("Basic ");
+    for chunk in userinfo.as_bytes().chunks(3)
+    {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| bits | (byte as u32) << (16 - 8 * i));
+        for i in 0..4
+        {
+            if i <= chunk.len()
+            {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+            }
+            else
+            {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Read a CRLF- (or LF-) terminated line, refusing lines longer than [`MAX_LINE`]
+pub(crate) fn read_line<R: BufRead>(reader: &mut R, line: &mut String) -> io::Result<usize>
+{
+    let read = reader.by_ref().take(MAX_LINE as u64 + 1).read_line(line)?;
+    if line.len() > MAX_LINE
+    {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Line too long"));
+    }
+    Ok(read)
+}
+
+/// Read header lines up to the blank line ending them
+pub(crate) fn read_headers<R: BufRead>(reader: &mut R) -> io::Result<Vec<(String, String)>>
+{
+    let mut headers = Vec::new();
+    loop
+    {
+        let mut line = String::new();
+        if read_line(reader, &mut line)? == 0
+        {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed in the headers"));
+        }
+        let line = line.trim_end();
+        if line.is_empty()
+        {
+            return Ok(headers);
+        }
+        if headers.len() == MAX_HEADERS
+        {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Too many headers"));
+        }
+        if let Some((name, value)) = line.split_once(':')
+        {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+}
+
+/// A file served over HTTP, read through range requests a block at a time
+///
+/// Only the block last fetched is kept, and one connection is reused for every request while the
+/// server allows it. A server that ignores ranges sends the whole file in reply to the first
+/// request, which is then kept in memory instead.
+pub struct HttpReader
+{
+    url: Url,
+    connection: Option<BufReader<TcpStream>>,
+    length: u64,
+    position: u64,
+    /// The bytes fetched last, starting at `block_start`
+    block: Vec<u8>,
+    block_start: u64,
+}
+
+impl HttpReader
+{
+    /// Open `url`, fetching its first block to learn its length
+    pub fn open(url: &str) -> Result<Self>
+    {
+        let mut reader = Self
+        {
+            url: Url::parse(url)?,
+            connection: None,
+            length: 0,
+            position: 0,
+            block: Vec::new(),
+            block_start: 0,
+        };
+        let fetched = reader.fetch(0..FETCH_BLOCK).map_err(|e| anyhow!("{}: {}", url, e))?;
+        reader.length = fetched.length;
+        reader.block_start = fetched.start;
+        reader.block = fetched.bytes;
+        Ok(reader)
+    }
+
+    /// Length of the file in bytes
+    pub fn len(&self) -> u64
+    {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.length == 0
+    }
+
+    /// Fetch `range`, which the server clamps to the end of the file
+    /// A kept-alive connection the server has since closed is replaced once before giving up
+    fn fetch(&mut self, range: Range<u64>) -> io::Result<Fetched>
+    {
+        if self.connection.is_some()
+        {
+            if let Ok(fetched) = self.try_fetch(range.clone())
+            {
+                return Ok(fetched);
+            }
+            self.connection = None;
+        }
+        self.try_fetch(range)
+    }
+
+    fn try_fetch(&mut self, range: Range<u64>) -> io::Result<Fetched>
+    {
+        let mut connection = match self.connection.take()
+        {
+            Some(connection) => connection,
+            None =>
+            {
+                let stream = TcpStream::connect((self.url.host.as_str(), self.url.port))?;
+                stream.set_read_timeout(Some(TIMEOUT))?;
+                stream.set_nodelay(true)?;
+                BufReader::new(stream)
+            }
+        };
+
+        let authorization = self.url.userinfo.as_deref().map_or(String::new(), |userinfo| format!("Authorization: {}\r\n", basic_auth(userinfo)));
+        write!(connection.get_mut(), "GET {} HTTP/1.1\r\nHost: {}\r\nRange: bytes={}-{}\r\n{}User-Agent: glc/{}\r\n\r\n",
+               self.url.path, self.url.authority(), range.start, range.end.saturating_sub(1), authorization, env!("CARGO_PKG_VERSION"))?;
+
+        let mut status_line = String::new();
+        if read_line(&mut connection, &mut status_line)? == 0
+        {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Server closed the connection"));
+        }
+        let status_line = status_line.trim_end().to_string();
+        let status = status_line.split_whitespace().nth(1).and_then(|code| code.parse::<u16>().ok())
+                                .ok_or_else(|| invalid_data(format!("Malformed status line '{}'", status_line)))?;
+
+        let mut content_length = None;
+        let mut content_range = None;
+        let mut keep_alive = status_line.starts_with("HTTP/1.1");
+        for (name, value) in read_headers(&mut connection)?
+        {
+            if name.eq_ignore_ascii_case("content-length")
+            {
+                content_length = Some(value.parse::<u64>().map_err(|_| invalid_data(format!("Bad Content-Length '{}'", value)))?);
+            }
+            else if name.eq_ignore_ascii_case("content-range")
+            {
+                content_range = Some(value);
+            }
+            else if name.eq_ignore_ascii_case("transfer-encoding") && !value.eq_ignore_ascii_case("identity")
+            {
+                return Err(invalid_data(format!("Unsupported transfer encoding '{}'", value)));
+            }
+            else if name.eq_ignore_ascii_case("connection")
+            {
+                keep_alive = !value.eq_ignore_ascii_case("close");
+            }
+        }
+        let content_length = content_length.ok_or_else(|| invalid_data("Response has no Content-Length".to_string()))?;
+        if status == 206 && content_length > range.end - range.start
+        {
+            return Err(invalid_data(format!("Asked for {} bytes but got {}", range.end - range.start, content_length)));
+        }
+
+        let mut bytes = Vec::new();
+        connection.by_ref().take(content_length).read_to_end(&mut bytes)?;
+        if (bytes.len() as u64) < content_length
+        {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Response body was cut short"));
+        }
+        if keep_alive
+        {
+            self.connection = Some(connection);
+        }
+
+        match status
+        {
+            206 =>
+            {
+                // bytes <first>-<last>/<length>
+                let (start, length) = content_range.as_deref()
+                    .and_then(|value| value.strip_prefix("bytes "))
+                    .and_then(|value| value.split_once('/'))
+                    .and_then(|(span, length)| Some((span.split_once('-')?.0.parse::<u64>().ok()?, length.parse::<u64>().ok()?)))
+                    .ok_or_else(|| invalid_data("Partial response has no usable Content-Range".to_string()))?;
+                if start != range.start
+                {
+                    return Err(invalid_data(format!("Asked for bytes from {} but got them from {}", range.start, start)));
+                }
+                Ok(Fetched { start, bytes, length })
+            }
+            // The server ignored the range and sent the whole file
+            200 =>
+            {
+                let length = bytes.len() as u64;
+                Ok(Fetched { start: 0, bytes, length })
+            }
+            // Past the end, which for an empty file is everywhere; bytes */<length>
+            416 =>
+            {
+                let length = content_range.as_deref()
+                    .and_then(|value| value.strip_prefix("bytes */"))
+                    .and_then(|length| length.parse::<u64>().ok())
+                    .unwrap_or(self.length);
+                Ok(Fetched { start: range.start, bytes: Vec::new(), length })
+            }
+            404 => Err(io::Error::new(io::ErrorKind::NotFound, "Not found on the server")),
+            _ => Err(io::Error::other(format!("Server replied '{}'", status_line))),
+        }
+    }
+}
+
+/// Bytes an [`HttpReader`] fetched, from `start`, out of a file of `length` bytes
+struct Fetched
+{
+    start: u64,
+    bytes: Vec<u8>,
+    length: u64,
+}
+
+fn invalid_data(message: String) -> io::Error
+{
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl Read for HttpReader
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>
+    {
+        if buf.is_empty() || self.position >= self.length
+        {
+            return Ok(0);
+        }
+
+        let block_end = self.block_start + self.block.len() as u64;
+        if self.position < self.block_start || self.position >= block_end
+        {
+            let start = self.position / FETCH_BLOCK * FETCH_BLOCK;
+            let fetched = self.fetch(start..(start + FETCH_BLOCK).min(self.length))?;
+            let fetched_end = fetched.start + fetched.bytes.len() as u64;
+            if self.position < fetched.start || self.position >= fetched_end
+            {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "The file on the server has shrunk"));
+            }
+            self.block = fetched.bytes;
+            self.block_start = fetched.start;
+        }
+
+        let offset = (self.position - self.block_start) as usize;
+        let available = &self.block[offset..];
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl Seek for HttpReader
+{
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64>
+    {
+        let target = match position
+        {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.length.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = target.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Seek before the start of the file"))?;
+        Ok(self.position)
+    }
+}
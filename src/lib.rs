@@ -1,5 +1,57 @@
 pub mod codec;
 pub mod audio;
 pub mod flac;
+pub mod alac;
+pub mod pcm;
+pub mod container;
+pub mod batch;
+pub mod compare;
+pub mod metrics;
+pub mod analysis;
+pub mod edit;
+pub mod cue;
+pub mod loudness;
+pub mod config;
+pub mod ffmpeg;
+pub mod mixer;
+pub mod equalizer;
+pub mod tempo;
+pub mod capture;
+pub mod waveform;
+pub mod spectrogram;
+pub mod playlist;
+pub mod abx;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod http;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod broadcast;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod library;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod shootout;
+#[cfg(feature = "playback")]
+pub mod playback;
+#[cfg(feature = "playback")]
+pub mod player;
+#[cfg(feature = "playback")]
+pub mod server;
+#[cfg(feature = "playback-cpal")]
+pub mod cpal_output;
+#[cfg(feature = "record")]
+pub mod record;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "async")]
+pub mod async_codec;
+#[cfg(feature = "symphonia")]
+pub mod symphonia_glc;
+#[cfg(feature = "transcode")]
+pub mod transcode;
+#[cfg(feature = "opus")]
+pub mod ogg_opus;
+#[cfg(feature = "mp3")]
+pub mod mp3;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
 
 pub use codec::*;
@@ -0,0 +1,128 @@
+//! Music folders scanned for tracks, for browsing a collection
+//!
+//! [`scan`] walks a folder and everything under it for `.glc` files and the source files they
+//! could be encoded from, and reads each with [`LibraryTrack::read`], which looks only at headers
+//! and tags (a `.glc` file's footer through [`MappedGlcFile`]), so no audio is decoded.
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use crate::audio::{is_native_input, LosslessReader};
+use crate::codec::load_encoded;
+use crate::container::{MappedGlcFile, Tags};
+
+/// What a library shows of one file
+#[derive(Clone, Debug, PartialEq)]
+pub struct LibraryTrack
+{
+    pub path: PathBuf,
+    /// The `title` tag, or the file name without its extension
+    pub title: String,
+    /// The `artist` and `album` tags, empty if untagged
+    pub artist: String,
+    pub album: String,
+    /// Length in seconds, if the file's header gives it
+    pub duration: Option<f64>,
+    /// Average bitrate of the whole file in kbps, if its length is known
+    pub kbps: Option<f64>,
+    /// Whether this is a `.glc` file, rather than a source file to encode
+    pub encoded: bool,
+}
+
+impl LibraryTrack
+{
+    /// Read the header and tags of the `.glc` or source file at `path`
+    pub fn read(path: &Path) -> Result<Self>
+    {
+        let (tags, duration, encoded) = if is_glc(path)
+        {
+            let (tags, header, gapless_info) = match MappedGlcFile::open(path)
+            {
+                Ok(glc) => (glc.tags().clone(), glc.header().clone(), glc.gapless_info().clone()),
+                // Files from before the frame-delimited container, which hold no tags
+                Err(_) =>
+                {
+                    let encoded = load_encoded(path)?;
+                    (Tags::new(), encoded.header, encoded.gapless_info)
+                }
+            };
+            let frames = gapless_info.original_length as f64 / header.channels.max(1) as f64;
+            (tags, Some(frames / header.sample_rate.max(1) as f64), true)
+        }
+        else
+        {
+            let reader = LosslessReader::open(path)?;
+            let duration = reader.frames().map(|frames| frames as f64 / reader.sample_rate().max(1) as f64);
+            (reader.tags().clone(), duration, false)
+        };
+
+        let file_size = std::fs::metadata(path)?.len();
+        let tag = |key: &str| tags.get(key).unwrap_or_default().to_string();
+        Ok(Self
+        {
+            path: path.to_path_buf(),
+            title: tags.get("title").map_or_else(|| path.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+                                                 str::to_string),
+            artist: tag("artist"),
+            album: tag("album"),
+            duration,
+            kbps: duration.filter(|&duration| duration > 0.0).map(|duration| file_size as f64 * 8.0 / duration / 1000.0),
+            encoded,
+        })
+    }
+}
+
+/// Whether `path` is a file a library lists: a `.glc` file, or a source file it could be encoded from
+pub fn is_library_file(path: &Path) -> bool
+{
+    is_glc(path) || is_native_input(path)
+}
+
+/// Read every library file in `dir` and the folders under it, in path order, passing each (or
+/// why it couldn't be read) to `found`; hidden files and folders are skipped. Scanning stops
+/// early if `found` returns false. Returns how many files were found.
+pub fn scan(dir: &Path, mut found: impl FnMut(&Path, Result<LibraryTrack>) -> bool) -> Result<usize>
+{
+    let mut count = 0;
+    scan_dir(dir, &mut found, &mut count)?;
+    Ok(count)
+}
+
+/// [`scan`] of one folder, returning false once `found` asks to stop
+fn scan_dir(dir: &Path, found: &mut impl FnMut(&Path, Result<LibraryTrack>) -> bool, count: &mut usize) -> Result<bool>
+{
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(dir)?
+    {
+        let entry = entry?;
+        if !entry.file_name().to_string_lossy().starts_with('.')
+        {
+            entries.push(entry.path());
+        }
+    }
+    entries.sort();
+
+    for path in entries
+    {
+        if path.is_dir()
+        {
+            // A folder that can't be listed is skipped rather than ending the scan
+            if !scan_dir(&path, found, count).unwrap_or(true)
+            {
+                return Ok(false);
+            }
+        }
+        else if is_library_file(&path)
+        {
+            *count += 1;
+            if !found(&path, LibraryTrack::read(&path))
+            {
+                return Ok(false);
+            }
+        }
+    }
+    Ok(true)
+}
+
+fn is_glc(path: &Path) -> bool
+{
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("glc"))
+}
@@ -0,0 +1,246 @@
+//! Integrated loudness (ITU-R BS.1770 / EBU R128) and sample peak measurement,
+//! for level-matching files at encode time and for ReplayGain scanning
+use anyhow::{anyhow, Result};
+
+/// Gating blocks are 400 ms long and start every 100 ms
+const STEPS_PER_BLOCK: usize = 4;
+/// Blocks quieter than this never count towards the integrated loudness
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+/// Blocks more than this far below the absolute-gated loudness are dropped too
+const RELATIVE_GATE_LU: f64 = 10.0;
+
+/// How to level a file while encoding it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Normalize
+{
+    /// Bring the integrated loudness to this many LUFS (without letting the peak exceed full scale)
+    Lufs(f64),
+    /// Bring the sample peak to this many dBFS
+    Peak(f64),
+}
+
+impl Normalize
+{
+    /// Parse `lufs=-16`, `peak` (0 dBFS) or `peak=-1`
+    pub fn parse(text: &str) -> Result<Self>
+    {
+        let error = || anyhow!("Invalid normalization '{}': expected lufs=<LUFS>, peak or peak=<dBFS>", text);
+        let (kind, value) = match text.trim().split_once('=')
+        {
+            Some((kind, value)) => (kind.trim().to_lowercase(), Some(value.trim())),
+            None => (text.trim().to_lowercase(), None),
+        };
+        let value = value.map(|v| v.parse::<f64>().ok().filter(|v| v.is_finite()).ok_or_else(error)).transpose()?;
+
+        match (kind.as_str(), value)
+        {
+            ("lufs", Some(target)) => Ok(Normalize::Lufs(target)),
+            ("peak", target) => Ok(Normalize::Peak(target.unwrap_or(0.0))),
+            _ => Err(error()),
+        }
+    }
+
+    /// Gain in dB that brings a signal measured as `loudness` to this target
+    /// A loudness target is lowered if reaching it would push the peak past full scale;
+    /// input too short or quiet to have a gated loudness is left alone
+    pub fn gain_db(&self, loudness: &Loudness) -> f64
+    {
+        let peak_db = 20.0 * (loudness.peak as f64).log10();
+        let gain = match *self
+        {
+            Normalize::Lufs(target) if loudness.integrated_lufs.is_finite() => (target - loudness.integrated_lufs).min(-peak_db),
+            Normalize::Lufs(_) => 0.0,
+            Normalize::Peak(target) => target - peak_db,
+        };
+        // Silence stays silence
+        if gain.is_finite() { gain } else { 0.0 }
+    }
+}
+
+/// Result of a [`LoudnessMeter`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Loudness
+{
+    /// Gated integrated loudness; negative infinity for silence
+    pub integrated_lufs: f64,
+    /// Largest absolute sample value
+    pub peak: f32,
+}
+
+/// Streaming loudness meter: feed interleaved samples with [`LoudnessMeter::add_samples`]
+pub struct LoudnessMeter
+{
+    channels: usize,
+    step_len: usize,
+    filters: Vec<[Biquad; 2]>,
+    weights: Vec<f64>,
+    /// Weighted sum of squares so far in the current 100 ms step, and its sample count
+    step_energy: f64,
+    step_count: usize,
+    /// Mean weighted energy of each completed 100 ms step
+    steps: Vec<f64>,
+    peak: f32,
+}
+
+impl LoudnessMeter
+{
+    pub fn new(sample_rate: u32, channels: u16) -> Self
+    {
+        let channels = channels.max(1) as usize;
+        let rate = sample_rate.max(1) as f64;
+        Self
+        {
+            channels,
+            step_len: (sample_rate as usize / 10).max(1),
+            filters: vec![[Biquad::high_shelf(rate), Biquad::high_pass(rate)]; channels],
+            weights: (0..channels).map(|ch| channel_weight(ch, channels)).collect(),
+            step_energy: 0.0,
+            step_count: 0,
+            steps: Vec::new(),
+            peak: 0.0,
+        }
+    }
+
+    /// Add interleaved samples; a trailing partial frame is ignored
+    pub fn add_samples(&mut self, samples: &[f32])
+    {
+        for frame in samples.chunks_exact(self.channels)
+        {
+            for (ch, &s) in frame.iter().enumerate()
+            {
+                self.peak = self.peak.max(s.abs());
+                let [shelf, high_pass] = &mut self.filters[ch];
+                let k = high_pass.process(shelf.process(s as f64));
+                self.step_energy += self.weights[ch] * k * k;
+            }
+
+            self.step_count += 1;
+            if self.step_count == self.step_len
+            {
+                self.steps.push(self.step_energy / self.step_len as f64);
+                self.step_energy = 0.0;
+                self.step_count = 0;
+            }
+        }
+    }
+
+    /// Loudness of everything added so far
+    /// Input shorter than one 400 ms block has no gated loudness and reads as silence
+    pub fn loudness(&self) -> Loudness
+    {
+        Loudness { integrated_lufs: gated_loudness(self.blocks().collect()), peak: self.peak }
+    }
+
+    /// Mean weighted energy of each 400 ms gating block above the absolute gate
+    fn blocks(&self) -> impl Iterator<Item = f64> + '_
+    {
+        self.steps
+            .windows(STEPS_PER_BLOCK)
+            .map(|w| w.iter().sum::<f64>() / STEPS_PER_BLOCK as f64)
+            .filter(|&e| block_lufs(e) > ABSOLUTE_GATE_LUFS)
+    }
+}
+
+/// Loudness of the input of several meters played one after another, e.g. the tracks of an album
+/// Their gating blocks are pooled, so the meters may run at different sample rates and channel counts
+pub fn combined_loudness<'a>(meters: impl IntoIterator<Item = &'a LoudnessMeter>) -> Loudness
+{
+    let (mut blocks, mut peak) = (Vec::new(), 0.0f32);
+    for meter in meters
+    {
+        blocks.extend(meter.blocks());
+        peak = peak.max(meter.peak);
+    }
+    Loudness { integrated_lufs: gated_loudness(blocks), peak }
+}
+
+/// Integrated loudness of gating `blocks` already past the absolute gate
+fn gated_loudness(blocks: Vec<f64>) -> f64
+{
+    if blocks.is_empty()
+    {
+        return f64::NEG_INFINITY;
+    }
+    let gate = block_lufs(mean(&blocks)) - RELATIVE_GATE_LU;
+    let gated: Vec<f64> = blocks.into_iter().filter(|&e| block_lufs(e) > gate).collect();
+    block_lufs(mean(&gated))
+}
+
+/// Measure interleaved `samples` in one go
+pub fn measure(samples: &[f32], sample_rate: u32, channels: u16) -> Loudness
+{
+    let mut meter = LoudnessMeter::new(sample_rate, channels);
+    meter.add_samples(samples);
+    meter.loudness()
+}
+
+fn block_lufs(energy: f64) -> f64
+{
+    -0.691 + 10.0 * energy.log10()
+}
+
+fn mean(values: &[f64]) -> f64
+{
+    values.iter().sum::<f64>() / values.len().max(1) as f64
+}
+
+/// BS.1770 channel weights: surround channels count 1.41x and LFE not at all
+/// Only 5.1 layouts (L R C LFE Ls Rs, optionally with more channels after) are recognized
+fn channel_weight(channel: usize, channels: usize) -> f64
+{
+    match (channels >= 6, channel)
+    {
+        (true, 3) => 0.0,
+        (true, 4 | 5) => 1.41,
+        _ => 1.0,
+    }
+}
+
+/// Direct form I biquad with coefficients normalized by a0
+#[derive(Clone)]
+struct Biquad
+{
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad
+{
+    /// First K-weighting stage: +4 dB high shelf modelling the head (BS.1770, any sample rate)
+    fn high_shelf(sample_rate: f64) -> Self
+    {
+        let (f0, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+        let k = (std::f64::consts::PI * f0 / sample_rate).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+
+        Self::new([(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+                  [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0])
+    }
+
+    /// Second K-weighting stage: the RLB high-pass at about 38 Hz
+    fn high_pass(sample_rate: f64) -> Self
+    {
+        let (f0, q) = (38.13547087602444, 0.5003270373238773);
+        let k = (std::f64::consts::PI * f0 / sample_rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+
+        Self::new([1.0, -2.0, 1.0], [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0])
+    }
+
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self
+    {
+        Self { b, a, x: [0.0; 2], y: [0.0; 2] }
+    }
+
+    fn process(&mut self, x: f64) -> f64
+    {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1] - self.a[0] * self.y[0] - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
@@ -1,70 +1,227 @@
 mod codec;
 #[cfg(feature = "ui")]
 mod ui;
+#[cfg(feature = "ui")]
+mod waveform;
+#[cfg(feature = "ui")]
+mod abx;
+#[cfg(feature = "ui")]
+mod library;
 mod audio;
 mod flac;
-
-use std::path::PathBuf;
+mod alac;
+mod pcm;
+mod container;
+mod batch;
+mod cli;
+mod compare;
+mod metrics;
+mod spectrogram;
+mod analysis;
+mod edit;
+mod equalizer;
+mod tempo;
+mod cue;
+mod loudness;
+mod config;
+mod ffmpeg;
+mod http;
+mod broadcast;
+mod shootout;
+#[cfg(feature = "transcode")]
+mod transcode;
+#[cfg(feature = "opus")]
+mod ogg_opus;
+#[cfg(feature = "mp3")]
+mod mp3;
+
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::io::Write;
 use std::sync::Arc;
-
-#[cfg(feature = "ui")]
-use eframe::egui;
+use clap::Parser;
+use serde::Serialize;
+use cli::{AnalyzeReport, Cli, CompareReport, DecodeReport, EncodeReport, EstimateReport, InfoReport, Outcomes, ShootoutReport};
 
 #[cfg(feature = "playback")]
 mod playback;
 #[cfg(feature = "playback")]
-use playback::SamplesSource;
+mod mixer;
+#[cfg(feature = "playback")]
+mod capture;
+#[cfg(feature = "playback")]
+mod playlist;
+#[cfg(feature = "playback")]
+mod player;
+#[cfg(feature = "playback")]
+mod server;
+#[cfg(feature = "playback-cpal")]
+mod cpal_output;
+#[cfg(feature = "tui")]
+mod tui;
+#[cfg(feature = "record")]
+mod record;
+#[cfg(feature = "playback")]
+use player::Player;
+#[cfg(feature = "playback")]
+use playback::Track;
 
 /// Encode a single audio file (WAV or FLAC) to GLC format
-fn encode_file(input_path: PathBuf) -> Result<(), anyhow::Error>
+/// The input is streamed block by block, so memory use does not depend on its length
+/// Progress is printed unless `quiet` is set
+fn encode_file(input_path: PathBuf, output_path: PathBuf, options: &audio::EncodeOptions, quiet: bool)
+    -> Result<EncodeReport, anyhow::Error>
 {
-    use codec::{Encoder, save_encoded};
-    use audio::load_audio_file_lossless;
+    use audio::{LosslessReader, encode_lossless_with};
 
-    println!("Loading: {:?}", input_path.file_name().unwrap());
+    if !quiet
+    {
+        println!("Loading: {:?}", input_path.file_name().unwrap());
+    }
 
-    // Load the input file
-    let (samples, sample_rate, channels) = load_audio_file_lossless(&input_path)?;
+    if !quiet
+    {
+        // Open the input file (reads only the header)
+        let reader = LosslessReader::open_with(&input_path, options.allow_ffmpeg)?;
+        println!("Encoding: {} Hz, {} channels", reader.sample_rate(), reader.channels());
+    }
 
-    println!("Encoding: {} Hz, {} channels, {} samples", sample_rate, channels, samples.len());
+    // Encode and save
+    let writer = std::io::BufWriter::new(std::fs::File::create(&output_path)?);
+    let (mut writer, gain_db) = encode_lossless_with(&input_path, options, writer)?;
+    writer.flush()?;
 
-    // Create encoder and encode
-    let mut encoder = Encoder::new(sample_rate);
-    let encoded = encoder.encode(&samples, channels)?;
+    let input_size = std::fs::metadata(&input_path)?.len();
+    let mut report = encode_report(output_path, input_size)?;
+    report.gain_db = gain_db;
 
-    // Generate output path
-    let mut output_path = input_path.clone();
-    output_path.set_extension("glc");
+    if !quiet
+    {
+        print_saved(&report);
+    }
 
-    // Save encoded file
-    save_encoded(&encoded, &output_path)?;
+    Ok(report)
+}
 
-    let input_size = std::fs::metadata(&input_path)?.len();
-    let output_size = std::fs::metadata(&output_path)?.len();
-    let ratio = (output_size as f64 / input_size as f64) * 100.0;
+/// Build the report for a finished encode from the output file's footer
+fn encode_report(output_path: PathBuf, input_size: u64) -> Result<EncodeReport, anyhow::Error>
+{
+    let info = read_info(&output_path)?;
 
-    println!("Saved: {:?} ({} bytes, {:.1}% of original)",
-             output_path.file_name().unwrap(), output_size, ratio);
+    Ok(EncodeReport
+    {
+        sample_rate: info.sample_rate,
+        channels: info.channels,
+        duration_secs: info.duration_secs,
+        input_size,
+        output_size: info.file_size,
+        ratio: (info.file_size as f64 / input_size as f64) * 100.0,
+        output: output_path,
+        gain_db: None,
+        report: None,
+    })
+}
 
-    Ok(())
+/// Print the result of encoding one file
+fn print_saved(report: &EncodeReport)
+{
+    match report.gain_db
+    {
+        Some(gain_db) => println!("Saved: {:?} ({} bytes, {:.1}% of original, normalized by {:+.2} dB)",
+                                  report.output.file_name().unwrap(), report.output_size, report.ratio, gain_db),
+        None => println!("Saved: {:?} ({} bytes, {:.1}% of original)",
+                         report.output.file_name().unwrap(), report.output_size, report.ratio),
+    }
 }
 
-/// Decode a GLC file to a lossless format (FLAC or WAV)
-fn decode_file(input_path: PathBuf, output_format: &str, flac_level: u8) -> Result<(), anyhow::Error>
+/// Encode several audio files concurrently, printing progress as each one finishes unless `quiet` is set
+/// Returns each input with its outcome, in the order given
+fn encode_files(jobs: Vec<(PathBuf, PathBuf)>, options: &audio::EncodeOptions, quiet: bool)
+    -> Vec<(PathBuf, Result<EncodeReport, anyhow::Error>)>
 {
-    use codec::{Decoder, load_encoded};
+    use batch::BatchEncoder;
+    use codec::Progress;
+
+    let mut batch = BatchEncoder::new();
+    for (input, output) in jobs
+    {
+        batch.add(input, output);
+    }
+    batch.options(*options);
+
+    let results = if quiet
+    {
+        batch.run(None)
+    }
+    else
+    {
+        println!("Encoding {} files...", batch.len());
+
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let printer = std::thread::spawn(move ||
+        {
+            while let Ok(progress) = rx.recv()
+            {
+                if let Progress::Status(msg) | Progress::Complete(msg) = progress
+                {
+                    println!("{}", msg);
+                }
+            }
+        });
+
+        let results = batch.run(Some(tx));
+        let _ = printer.join();
+        results
+    };
+
+    results.into_iter().map(|result|
+    {
+        let report = result.result.and_then(|sizes|
+        {
+            let mut report = encode_report(result.output, sizes.input_size)?;
+            report.gain_db = sizes.gain_db;
+            Ok(report)
+        });
+        if let (false, Ok(report)) = (quiet, &report)
+        {
+            print_saved(report);
+        }
+        (result.input, report)
+    }).collect()
+}
+
+/// Decode a GLC file to `output_format` with the settings in `args`
+/// Progress is printed unless JSON output was asked for
+fn decode_file(input_path: PathBuf, output_path: PathBuf, output_format: &'static str, args: &cli::DecodeArgs)
+    -> Result<DecodeReport, anyhow::Error>
+{
+    use codec::{Decoder, load_encoded_with};
     use audio::export_to_wav;
     use flac::export_to_flac_with_level;
 
-    println!("Loading: {:?}", input_path.file_name().unwrap());
+    let quiet = args.json;
+    let flac_level = args.flac_level.unwrap_or(cli::DEFAULT_FLAC_LEVEL);
+    let opus_bitrate = args.opus_bitrate.unwrap_or(cli::DEFAULT_OPUS_BITRATE);
+    let mp3_mode = args.mp3_bitrate.unwrap_or_default();
+    let options = container::DecodeOptions { allow_truncated: args.allow_truncated };
+
+    if !quiet
+    {
+        println!("Loading: {:?}", input_path.file_name().unwrap());
+    }
 
     // Load the encoded file
-    let encoded = load_encoded(&input_path)?;
+    let (encoded, truncation) = load_encoded_with(&input_path, &options)?;
+    if let Some(report) = &truncation
+    {
+        warn_truncated(&input_path, report);
+    }
 
-    println!("Decoding: {} Hz, {} channels",
-             encoded.header.sample_rate, encoded.header.channels);
+    if !quiet
+    {
+        println!("Decoding: {} Hz, {} channels",
+                 encoded.header.sample_rate, encoded.header.channels);
+    }
 
     // Create decoder and decode
     let mut decoder = Decoder::new(
@@ -73,16 +230,15 @@ fn decode_file(input_path: PathBuf, output_format: &str, flac_level: u8) -> Resu
     );
     let samples = decoder.decode(&encoded, None)?;
 
-    println!("Decoded {} samples", samples.len());
-
-    // Generate output path
-    let mut output_path = input_path.clone();
+    if !quiet
+    {
+        println!("Decoded {} samples", samples.len());
+    }
 
     match output_format
     {
         "flac" =>
         {
-            output_path.set_extension("flac");
             export_to_flac_with_level(
                 &output_path,
                 &samples,
@@ -90,83 +246,461 @@ fn decode_file(input_path: PathBuf, output_format: &str, flac_level: u8) -> Resu
                 encoded.header.channels,
                 flac_level,
             )?;
-            println!("Saved: {:?} (FLAC, level {})", output_path.file_name().unwrap(), flac_level);
+            if !quiet
+            {
+                println!("Saved: {:?} (FLAC, level {})", output_path.file_name().unwrap(), flac_level);
+            }
+        }
+        "opus" =>
+        {
+            export_to_opus(&output_path, &samples, encoded.header.sample_rate, encoded.header.channels, opus_bitrate)?;
+            if !quiet
+            {
+                println!("Saved: {:?} (Opus, {} kbit/s)", output_path.file_name().unwrap(), opus_bitrate);
+            }
+        }
+        "mp3" =>
+        {
+            export_to_mp3(&output_path, &samples, encoded.header.sample_rate, encoded.header.channels, mp3_mode)?;
+            if !quiet
+            {
+                println!("Saved: {:?} (MP3, {})", output_path.file_name().unwrap(), mp3_mode);
+            }
         }
         "wav" =>
         {
-            output_path.set_extension("wav");
             export_to_wav(
                 &output_path,
                 &samples,
                 encoded.header.sample_rate,
                 encoded.header.channels,
             )?;
-            println!("Saved: {:?} (WAV)", output_path.file_name().unwrap());
+            if !quiet
+            {
+                println!("Saved: {:?} (WAV)", output_path.file_name().unwrap());
+            }
+        }
+        "aiff" =>
+        {
+            audio::export_to_aiff(&output_path, &samples, encoded.header.sample_rate, encoded.header.channels)?;
+            if !quiet
+            {
+                println!("Saved: {:?} (AIFF)", output_path.file_name().unwrap());
+            }
+        }
+        "alac" =>
+        {
+            alac::export_to_alac(&output_path, &samples, encoded.header.sample_rate, encoded.header.channels)?;
+            if !quiet
+            {
+                println!("Saved: {:?} (ALAC)", output_path.file_name().unwrap());
+            }
         }
-        _ =>
+        format =>
         {
-            return Err(anyhow::anyhow!("Unsupported output format: {}", output_format));
+            let raw = audio::RawFormat::parse(format)
+                .map_err(|_| anyhow::anyhow!("Unsupported output format: {}", output_format))?;
+            let mut writer = std::io::BufWriter::new(std::fs::File::create(&output_path)?);
+            audio::write_raw_pcm(&mut writer, &samples, raw)?;
+            writer.flush()?;
+            if !quiet
+            {
+                println!("Saved: {:?} (raw {}, {} Hz, {} channels)", output_path.file_name().unwrap(),
+                         raw.name(), encoded.header.sample_rate, encoded.header.channels);
+            }
         }
     }
 
-    Ok(())
+    let frames = samples.len() / encoded.header.channels.max(1) as usize;
+
+    Ok(DecodeReport
+    {
+        format: output_format,
+        sample_rate: encoded.header.sample_rate,
+        channels: encoded.header.channels,
+        duration_secs: frames as f64 / encoded.header.sample_rate.max(1) as f64,
+        input_size: std::fs::metadata(&input_path)?.len(),
+        output_size: std::fs::metadata(&output_path)?.len(),
+        output: output_path,
+        truncated: truncation.is_some(),
+    })
 }
 
-/// Play multiple GLC files gaplessly using rodio
-#[cfg(feature = "playback")]
-fn play_files_gapless(file_paths: Vec<PathBuf>) -> Result<(), anyhow::Error>
+/// Warn that `path` was cut off and say how much of it could be decoded
+fn warn_truncated(path: &Path, report: &container::RepairReport)
 {
-    use codec::{Decoder, load_encoded};
-    use rodio::{OutputStream, Sink};
+    eprintln!("Warning: {} is truncated; decoding the first {:.3} s ({} frames), the rest is lost",
+              path.display(), report.length as f64 / report.sample_rate.max(1) as f64, report.frames);
+    if report.lost_frames > 0
+    {
+        eprintln!("Warning: {} damaged frames before the cut are decoded as silence", report.lost_frames);
+    }
+}
+
+/// Export decoded audio to an Ogg Opus file
+#[cfg(feature = "opus")]
+fn export_to_opus(path: &Path, samples: &[f32], sample_rate: u32, channels: u16, bitrate_kbps: u32) -> Result<(), anyhow::Error>
+{
+    ogg_opus::export_to_opus(path, samples, sample_rate, channels, bitrate_kbps)
+}
 
+/// Opus export stub when the opus feature is not available
+#[cfg(not(feature = "opus"))]
+fn export_to_opus(_path: &Path, _samples: &[f32], _sample_rate: u32, _channels: u16, _bitrate_kbps: u32) -> Result<(), anyhow::Error>
+{
+    Err(anyhow::anyhow!("Opus export not compiled in (build with --features opus)"))
+}
+
+/// Export decoded audio to an MP3 file
+#[cfg(feature = "mp3")]
+fn export_to_mp3(path: &Path, samples: &[f32], sample_rate: u32, channels: u16, mode: audio::Mp3Mode) -> Result<(), anyhow::Error>
+{
+    mp3::export_to_mp3(path, samples, sample_rate, channels, mode)
+}
+
+/// MP3 export stub when the mp3 feature is not available
+#[cfg(not(feature = "mp3"))]
+fn export_to_mp3(_path: &Path, _samples: &[f32], _sample_rate: u32, _channels: u16, _mode: audio::Mp3Mode) -> Result<(), anyhow::Error>
+{
+    Err(anyhow::anyhow!("MP3 export not compiled in (build with --features mp3)"))
+}
+
+/// How `glc play` plays its files
+struct PlayOptions
+{
+    /// Audio output device, by name, or None for the default
+    device: Option<String>,
+    /// Starting volume as a linear gain
+    volume: f32,
+    limiter: bool,
+    /// Play tagged files at their ReplayGain gain plus `preamp` dB
+    replaygain: Option<cli::ReplayGainMode>,
+    preamp: f64,
+    /// Seconds each track fades into the next, 0 for gapless
+    crossfade: f64,
+    /// EQ bands, none for a flat response
+    eq: Vec<equalizer::EqBand>,
+    /// Playback speed with the pitch kept, 1.0 being unchanged
+    tempo: f64,
+    /// File to save the played output to
+    capture: Option<PathBuf>,
+    repeat: Option<cli::RepeatMode>,
+    shuffle: bool,
+    backend: cli::OutputBackend,
+    /// Seconds of audio decoded ahead of the device with the cpal backend
+    buffer: f64,
+    /// Log playback telemetry every second
+    verbose: bool,
+    /// Show the full-screen terminal player instead of a status line
+    tui: bool,
+}
+
+impl Default for PlayOptions
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            device: None,
+            volume: 1.0,
+            limiter: true,
+            replaygain: None,
+            preamp: 0.0,
+            crossfade: 0.0,
+            eq: Vec::new(),
+            tempo: 1.0,
+            capture: None,
+            repeat: None,
+            shuffle: false,
+            backend: cli::OutputBackend::Rodio,
+            buffer: 0.1,
+            verbose: false,
+            tui: false,
+        }
+    }
+}
+
+/// Play multiple GLC files gaplessly using rodio, as one playlist
+#[cfg(feature = "playback")]
+fn play_files_gapless(file_paths: Vec<PathBuf>, options: &PlayOptions) -> Result<(), anyhow::Error>
+{
     if file_paths.is_empty()
     {
         return Err(anyhow::anyhow!("No files to play"));
     }
 
-    // Create audio output stream
-    let (_stream, stream_handle) = OutputStream::try_default()
-        .map_err(|e| anyhow::anyhow!("Failed to get default audio output: {}", e))?;
-
-    let sink = Sink::try_new(&stream_handle)
-        .map_err(|e| anyhow::anyhow!("Failed to create audio sink: {}", e))?;
+    let backend = match options.backend
+    {
+        cli::OutputBackend::Rodio => player::Backend::Rodio,
+        #[cfg(feature = "playback-cpal")]
+        cli::OutputBackend::Cpal => player::Backend::Cpal { buffer: std::time::Duration::from_secs_f64(options.buffer) },
+        #[cfg(not(feature = "playback-cpal"))]
+        cli::OutputBackend::Cpal =>
+        {
+            return Err(anyhow::anyhow!("The cpal backend is not compiled in; build with --features playback-cpal"));
+        }
+    };
+    if options.tui && cfg!(not(feature = "tui"))
+    {
+        return Err(anyhow::anyhow!("The terminal player is not compiled in; build with --features tui"));
+    }
+    let mut player = Player::with_backend(options.device.as_deref(), backend)?;
 
-    // Load and queue all files
+    // Gather all files; frames are decoded lazily as the player pulls samples
+    let mut tracks = Vec::with_capacity(file_paths.len());
     for path in &file_paths
     {
         println!("Loading: {:?}", path.file_name().unwrap());
 
-        let encoded = load_encoded(&path)?;
-        let encoded = Arc::new(encoded);
+        let track = Track::open_input(path)?;
+        println!("Queueing: {} Hz, {} channels", track.sample_rate(), track.channels());
+        tracks.push(track);
+    }
+
+    // Everything plays as one playlist, so tracks that differ in format still join without gaps
+    let (sample_rate, channels) = playback::output_format(&tracks);
+    let converted = tracks.iter().filter(|t| (t.sample_rate(), t.channels()) != (sample_rate, channels)).count();
+    if converted > 0
+    {
+        println!("Tracks differ in format; playing all at {} Hz, {} channels ({} converted)",
+                 sample_rate, channels, converted);
+    }
+    for track in tracks
+    {
+        player.queue_track(track);
+    }
+
+    let control = player.control();
+    control.mixer().set_volume(options.volume);
+    control.mixer().set_limiter(options.limiter);
+    control.set_replay_gain(playback::ReplayGain { mode: options.replaygain, preamp: options.preamp });
+    control.set_crossfade(options.crossfade);
+    control.equalizer().set_bands(options.eq.clone());
+    control.set_tempo(options.tempo);
+    control.set_repeat(match options.repeat
+    {
+        Some(cli::RepeatMode::Track) => playlist::Repeat::Track,
+        Some(cli::RepeatMode::Playlist) => playlist::Repeat::Playlist,
+        None => playlist::Repeat::Off,
+    });
+    control.set_shuffle(options.shuffle);
+    if let Some(ref path) = options.capture
+    {
+        control.start_capture(path)?;
+    }
+    player.play()?;
+
+    if options.crossfade > 0.0 && file_paths.len() > 1
+    {
+        println!("Playing {} files with a {} s crossfade.", file_paths.len(), options.crossfade);
+    }
+    else
+    {
+        println!("Playing {} files gaplessly.", file_paths.len());
+    }
+
+    // Wait for playback to finish
+    let names: Vec<String> = file_paths.iter()
+                                       .map(|p| p.file_name().unwrap_or_default().to_string_lossy().into_owned())
+                                       .collect();
+    #[cfg(feature = "tui")]
+    if options.tui
+    {
+        tui::run(&mut player, &names)?;
+    }
+    if !options.tui
+    {
+        run_transport_controls(&mut player, &names, options.verbose)?;
+    }
+    if let Some(capture) = player.control().stop_capture()?
+    {
+        println!("Captured {:.1} s of output to {}", capture.duration(), capture.path.display());
+    }
+
+    #[cfg(feature = "playback-cpal")]
+    if let Some(stats) = player.underruns()
+    {
+        println!("{} underruns ({} frames of silence) in {} device callbacks",
+                 stats.underruns, stats.silent_frames, stats.callbacks);
+    }
+    println!("Playback finished");
+    Ok(())
+}
 
-        let sample_rate = encoded.header.sample_rate;
-        let channels = encoded.header.channels;
+/// Volume change per `+`/`-` key press
+#[cfg(feature = "playback")]
+const VOLUME_STEP: f32 = 0.1;
 
-        println!("Queueing: {} Hz, {} channels", sample_rate, channels);
+/// Tempo change per `[`/`]` key press
+#[cfg(feature = "playback")]
+const TEMPO_STEP: f64 = 0.1;
 
-        // Create decoder and get streaming receiver
-        let mut decoder = Decoder::new(channels as usize, sample_rate);
-        let rx = decoder.decode_streaming(encoded, None);
+/// Raw terminal mode, for reading single key presses, left however the holder returns
+#[cfg(feature = "playback")]
+struct RawMode;
 
-        // Receive and queue all chunks
-        while let Ok(chunk) = rx.recv()
+#[cfg(feature = "playback")]
+impl RawMode
+{
+    fn enable() -> Result<Self, anyhow::Error>
+    {
+        crossterm::terminal::enable_raw_mode()?;
+        Ok(Self)
+    }
+}
+
+#[cfg(feature = "playback")]
+impl Drop for RawMode
+{
+    fn drop(&mut self)
+    {
+        crossterm::terminal::disable_raw_mode().ok();
+    }
+}
+
+/// How often `glc play -v` logs playback telemetry
+#[cfg(feature = "playback")]
+const TELEMETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// One line of playback telemetry for `glc play -v`
+#[cfg(feature = "playback")]
+fn format_stats(stats: &playback::PlaybackStats) -> String
+{
+    format!("decoding at {:.1}x, {:.3} s buffered, {} of {} blocks slow, {} underruns",
+            stats.speed, stats.buffered, stats.slow_blocks, stats.blocks, stats.underruns)
+}
+
+/// Wait for `player` to finish while handling the player's keys, keeping a status line with the
+/// current track, position, and volume up to date, with telemetry logged above it if `verbose`
+/// Without a terminal to read keys from, this just waits (still logging if `verbose`)
+#[cfg(feature = "playback")]
+fn run_transport_controls(player: &mut Player, names: &[String], verbose: bool) -> Result<(), anyhow::Error>
+{
+    use crossterm::cursor::MoveToColumn;
+    use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+    use crossterm::execute;
+    use crossterm::style::Print;
+    use crossterm::terminal::{Clear, ClearType};
+    use std::io::IsTerminal;
+    use std::time::{Duration, Instant};
+
+    if !std::io::stdin().is_terminal()
+    {
+        println!("Press Ctrl+C to stop.");
+        if !verbose
+        {
+            player.sleep_until_end();
+            return Ok(());
+        }
+        while !player.is_finished()
+        {
+            std::thread::sleep(TELEMETRY_INTERVAL);
+            println!("{}", format_stats(&player.stats()));
+        }
+        return Ok(());
+    }
+
+    println!("Keys: space pause/resume, left/right seek 5 s, down/up seek 30 s, n/p next/previous track, +/- volume, [/] tempo, l limiter, e EQ on/off, r repeat, s shuffle, q quit");
+    let _raw_mode = RawMode::enable()?;
+
+    let mut stdout = std::io::stdout();
+    let control = player.control().clone();
+    let mixer = control.mixer();
+    let step = |by: f32| mixer.set_volume(((mixer.volume() + by) * 10.0).round() / 10.0);
+    let mut last_logged = Instant::now();
+    while !player.is_finished()
+    {
+        if verbose && last_logged.elapsed() >= TELEMETRY_INTERVAL
+        {
+            // Scrolls up above the status line, which is redrawn below
+            let log = format!("{}\r\n", format_stats(&player.stats()));
+            execute!(stdout, MoveToColumn(0), Clear(ClearType::CurrentLine), Print(log))?;
+            last_logged = Instant::now();
+        }
+
+        let position = control.position();
+        #[cfg(feature = "playback-cpal")]
+        let underruns = player.underruns().map_or(String::new(), |stats| format!(", {} underruns", stats.underruns));
+        #[cfg(not(feature = "playback-cpal"))]
+        let underruns = "";
+        let line = format!("[{}/{}] {}  {} / {}  (playlist {} / {})  volume {:.0}%{}{}{}{}{}{}{}",
+                           position.track + 1,
+                           names.len(),
+                           names.get(position.track).map_or("", String::as_str),
+                           playback::format_time(position.elapsed),
+                           playback::format_time(position.duration),
+                           playback::format_time(position.total_elapsed),
+                           playback::format_time(position.total_duration),
+                           mixer.volume() * 100.0,
+                           if control.tempo() == 1.0 { String::new() } else { format!(", tempo {:.2}x", control.tempo()) },
+                           if mixer.limiter() { "" } else { ", no limiter" },
+                           if control.equalizer().bands().is_empty() { "" }
+                           else if control.equalizer().enabled() { ", eq" }
+                           else { ", eq off" },
+                           match control.repeat()
+                           {
+                               playlist::Repeat::Off => "",
+                               playlist::Repeat::Track => ", repeat track",
+                               playlist::Repeat::Playlist => ", repeat playlist",
+                           },
+                           if control.shuffle() { ", shuffle" } else { "" },
+                           underruns,
+                           if player.is_paused() { "  (paused)" } else { "" });
+        execute!(stdout, MoveToColumn(0), Clear(ClearType::CurrentLine), Print(line))?;
+
+        if !event::poll(Duration::from_millis(100))?
         {
-            let source = SamplesSource::new(chunk.samples.clone(), sample_rate, channels);
-            sink.append(source);
+            continue;
+        }
+        let Event::Key(key) = event::read()?
+        else
+        {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press
+        {
+            continue;
+        }
 
-            if chunk.is_last
+        match key.code
+        {
+            KeyCode::Char(' ') if player.is_paused() => player.play()?,
+            KeyCode::Char(' ') => player.pause(),
+            KeyCode::Left => control.seek_by(-5.0),
+            KeyCode::Right => control.seek_by(5.0),
+            KeyCode::Down => control.seek_by(-30.0),
+            KeyCode::Up => control.seek_by(30.0),
+            KeyCode::Char('n') => control.next_track(),
+            KeyCode::Char('p') => control.previous_track(),
+            KeyCode::Char('+') | KeyCode::Char('=') => step(VOLUME_STEP),
+            KeyCode::Char('-') => step(-VOLUME_STEP),
+            KeyCode::Char('[') => control.set_tempo(((control.tempo() - TEMPO_STEP) * 10.0).round() / 10.0),
+            KeyCode::Char(']') => control.set_tempo(((control.tempo() + TEMPO_STEP) * 10.0).round() / 10.0),
+            KeyCode::Char('l') => mixer.set_limiter(!mixer.limiter()),
+            KeyCode::Char('e') => control.equalizer().set_enabled(!control.equalizer().enabled()),
+            KeyCode::Char('r') => control.set_repeat(match control.repeat()
+            {
+                playlist::Repeat::Off => playlist::Repeat::Track,
+                playlist::Repeat::Track => playlist::Repeat::Playlist,
+                playlist::Repeat::Playlist => playlist::Repeat::Off,
+            }),
+            KeyCode::Char('s') => control.set_shuffle(!control.shuffle()),
+            KeyCode::Char('q') =>
+            {
+                player.stop();
+                break;
+            }
+            // Raw mode delivers Ctrl+C as a key press rather than a signal
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) =>
             {
+                player.stop();
                 break;
             }
+            _ => {}
         }
     }
 
-    println!("Playing {} files gaplessly. Press Ctrl+C to stop.", file_paths.len());
-
-    // Wait for playback to finish
-    sink.sleep_until_end();
-
-    println!("Playback finished");
+    execute!(stdout, Print("\r\n"))?;
     Ok(())
 }
 
@@ -174,16 +708,16 @@ fn play_files_gapless(file_paths: Vec<PathBuf>) -> Result<(), anyhow::Error>
 #[cfg(feature = "playback")]
 fn play_file(input_path: PathBuf) -> Result<(), anyhow::Error>
 {
-    play_files_gapless(vec![input_path])
+    play_files_gapless(vec![input_path], &PlayOptions::default())
 }
 
 /// Play files stub when playback feature is not available
 #[cfg(not(feature = "playback"))]
-fn play_files_gapless(_file_paths: Vec<PathBuf>) -> Result<(), anyhow::Error>
+fn play_files_gapless(_file_paths: Vec<PathBuf>, _options: &PlayOptions) -> Result<(), anyhow::Error>
 {
     eprintln!("Error: Playback support not compiled in");
     eprintln!("Build with: cargo build --release --no-default-features --features playback");
-    eprintln!("Or run glc -p --ffplay <file.glc> to use ffplay instead");
+    eprintln!("Or run glc play --ffplay <file.glc> to use ffplay instead");
     Err(anyhow::anyhow!("Playback not available"))
 }
 
@@ -193,35 +727,63 @@ fn play_file(_input_path: PathBuf) -> Result<(), anyhow::Error>
 {
     eprintln!("Error: Playback support not compiled in");
     eprintln!("Build with: cargo build --release --no-default-features --features playback");
-    eprintln!("Or run glc -p --ffplay <file.glc> to use ffplay instead");
+    eprintln!("Or run glc play --ffplay <file.glc> to use ffplay instead");
     Err(anyhow::anyhow!("Playback not available"))
 }
 
-/// Play a GLC file using ffplay (alternative method)
-fn play_file_with_ffplay(input_path: PathBuf) -> Result<(), anyhow::Error>
+/// Play GLC files back to back through a single ffplay process (alternative method)
+/// Every file is decoded into one continuous stream of raw PCM on ffplay's stdin, so the joins stay
+/// gapless; that stream has one format, so every file must share the first file's sample rate and
+/// channel count
+fn play_files_with_ffplay(file_paths: Vec<PathBuf>) -> Result<(), anyhow::Error>
 {
-    use codec::{Decoder, load_encoded};
-
-    println!("Loading: {:?}", input_path.file_name().unwrap());
-
-    // Load the encoded file
-    let encoded = load_encoded(&input_path)?;
-    let encoded = Arc::new(encoded);
-
-    let sample_rate = encoded.header.sample_rate;
-    let channels = encoded.header.channels;
+    use codec::{Decoder, StreamOptions, load_encoded};
+    use container::GlcDecoder;
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::time::Duration;
+
+    // ffplay's input format is fixed once it starts, so check every file first
+    let mut formats = Vec::with_capacity(file_paths.len());
+    for path in &file_paths
+    {
+        let format = match GlcDecoder::new(BufReader::new(File::open(path)?))
+        {
+            Ok(glc) => (glc.header().sample_rate, glc.header().channels),
+            // Files written before the frame-delimited container have no footer, so load them whole
+            Err(_) =>
+            {
+                let encoded = load_encoded(path)?;
+                (encoded.header.sample_rate, encoded.header.channels)
+            }
+        };
+        formats.push(format);
+    }
+    let Some(&(sample_rate, channels)) = formats.first()
+    else
+    {
+        return Err(anyhow::anyhow!("No files to play"));
+    };
+    if let Some((path, (rate, count))) = file_paths.iter().zip(&formats).find(|(_, format)| **format != (sample_rate, channels))
+    {
+        return Err(anyhow::anyhow!("{} is {} Hz, {} channels but {} is {} Hz, {} channels; ffplay can only play one format \
+                                    gaplessly (play without --ffplay to convert between them)",
+                                   path.display(), rate, count, file_paths[0].display(), sample_rate, channels));
+    }
 
-    println!("Playing: {} Hz, {} channels (via ffplay)", sample_rate, channels);
+    println!("Playing {} files: {} Hz, {} channels (via ffplay)", file_paths.len(), sample_rate, channels);
     println!("Press Ctrl+C or close ffplay window to stop");
 
-    // Spawn ffplay process with stderr captured
-    let mut child = Command::new("ffplay")
-        .args(&[
+    // Spawn ffplay process with stderr captured; its status line would fill the pipe, so keep it quiet
+    let child = Command::new("ffplay")
+        .args([
             "-f", "f32le",                    // 32-bit float PCM
             "-ar", &sample_rate.to_string(),  // sample rate
             "-ac", &channels.to_string(),     // channels
             "-nodisp",                         // no video display
             "-autoexit",                       // exit when done
+            "-nostats",                        // no status line
+            "-loglevel", "error",              // only report problems
             "-",                               // read from stdin
         ])
         .stdin(Stdio::piped())
@@ -243,30 +805,58 @@ fn play_file_with_ffplay(input_path: PathBuf) -> Result<(), anyhow::Error>
     let mut stdin = child.stdin.take().ok_or_else(||
         anyhow::anyhow!("Failed to open stdin for ffplay"))?;
 
-    // Create decoder and stream
-    let mut decoder = Decoder::new(channels as usize, sample_rate);
-    let rx = decoder.decode_streaming(encoded, None);
-
-    // Stream audio chunks to ffplay
+    // Stream each file's audio chunks to ffplay in turn
     let mut chunks_sent = 0;
-    while let Ok(chunk) = rx.recv()
+    let mut failure = None;
+    'files: for path in &file_paths
     {
-        chunks_sent += 1;
+        println!("Playing: {:?}", path.file_name().unwrap_or_default());
+        let encoded = match load_encoded(path)
+        {
+            Ok(encoded) => Arc::new(encoded),
+            Err(e) =>
+            {
+                failure = Some(anyhow::anyhow!("{}: {}", path.display(), e));
+                break;
+            }
+        };
 
-        // Convert f32 samples to bytes
-        let bytes: Vec<u8> = chunk.samples.iter()
-                                  .flat_map(|&f| f.to_le_bytes())
-                                  .collect();
+        // The stream is untrimmed, so drop the encoder delay and the padding here; otherwise
+        // they would be heard as a gap between files
+        let mut to_skip = encoded.gapless_info.encoder_delay as usize * channels as usize;
+        let mut remaining = encoded.gapless_info.original_length as usize;
 
-        if let Err(e) = stdin.write_all(&bytes)
+        let mut decoder = Decoder::new(channels as usize, sample_rate);
+        let options = StreamOptions::with_chunk_duration(Duration::from_millis(100), sample_rate);
+        let rx = decoder.decode_streaming_with(encoded, None, options);
+        while let Ok(chunk) = rx.recv()
         {
-            eprintln!("Error writing to ffplay: {}", e);
-            break;
-        }
+            let skip = to_skip.min(chunk.samples.len());
+            to_skip -= skip;
+            let samples = &chunk.samples[skip..];
+            let samples = &samples[..samples.len().min(remaining)];
+            remaining -= samples.len();
+
+            // Convert f32 samples to bytes
+            let bytes: Vec<u8> = samples.iter()
+                                        .flat_map(|&f| f.to_le_bytes())
+                                        .collect();
+
+            if let Err(e) = stdin.write_all(&bytes)
+            {
+                // A broken pipe means ffplay was closed, which stops playback rather than failing it
+                if e.kind() != std::io::ErrorKind::BrokenPipe
+                {
+                    eprintln!("Error writing to ffplay: {}", e);
+                }
+                break 'files;
+            }
+            chunks_sent += 1;
 
-        if chunk.is_last
-        {
-            break;
+            if chunk.is_last || remaining == 0
+            {
+                break;
+            }
         }
     }
 
@@ -292,12 +882,12 @@ fn play_file_with_ffplay(input_path: PathBuf) -> Result<(), anyhow::Error>
             eprintln!("{}", String::from_utf8_lossy(&output.stdout));
         }
     }
-    else
+    else if failure.is_none()
     {
         println!("Playback finished");
     }
 
-    Ok(())
+    failure.map_or(Ok(()), Err)
 }
 
 /// Check if a path has a supported lossless audio file extension
@@ -308,12 +898,30 @@ fn is_lossless_audio_file(path: &PathBuf) -> bool
         if let Some(ext_str) = ext.to_str()
         {
             let ext_lower = ext_str.to_lowercase();
-            return ext_lower == "wav" || ext_lower == "flac";
+            return matches!(ext_lower.as_str(), "wav" | "flac" | "aif" | "aiff" | "aifc" | "caf");
         }
     }
     false
 }
 
+/// Input formats accepted by `glc encode`, for error messages
+#[cfg(not(feature = "transcode"))]
+const ENCODE_INPUT_FORMATS: &str = "WAV, FLAC, AIFF, CAF";
+#[cfg(feature = "transcode")]
+const ENCODE_INPUT_FORMATS: &str = "WAV, FLAC, AIFF, CAF, MP3, Ogg Vorbis, AAC/M4A";
+
+/// Check if a path can be encoded: lossless audio, plus lossy formats with the `transcode` feature
+fn is_encodable_file(path: &PathBuf) -> bool
+{
+    audio::is_native_input(path)
+}
+
+/// Input check for `glc encode`: with `--allow-ffmpeg` any file is accepted and left for ffmpeg to reject
+fn encodable_check(allow_ffmpeg: bool) -> fn(&PathBuf) -> bool
+{
+    if allow_ffmpeg { |_: &PathBuf| true } else { is_encodable_file }
+}
+
 /// Check if a path has a .glc extension
 fn is_glc_file(path: &PathBuf) -> bool
 {
@@ -327,255 +935,1743 @@ fn is_glc_file(path: &PathBuf) -> bool
     false
 }
 
-fn print_usage()
-{
-    eprintln!("Usage:");
-    eprintln!("  glc <file.wav|file.flac> ...                    Encode audio files to .glc");
-    eprintln!("  glc -d <file.glc> ... [--wav] [--flac-level N]  Decode .glc files");
-    eprintln!("  glc -p <file.glc> ... [--ffplay]                Play .glc files (gapless)");
-    eprintln!("  glc                                              Launch GUI (if ui feature enabled)");
-    eprintln!();
-    eprintln!("Options:");
-    eprintln!("  -d, --decode       Decode .glc files to FLAC (default) or WAV");
-    eprintln!("  -p, --play         Play .glc files using audio system (gapless for multiple files)");
-    eprintln!("      --ffplay       Use ffplay for playback (sequential for multiple files)");
-    eprintln!("      --wav          Output WAV format instead of FLAC");
-    eprintln!("      --flac-level   Set FLAC compression level 0-8 (default: 5)");
-    eprintln!();
-    eprintln!("Examples:");
-    eprintln!("  glc audio.wav                         # Encode to audio.glc");
-    eprintln!("  glc -d file1.glc file2.glc --wav      # Decode multiple files to WAV");
-    eprintln!("  glc -d file.glc --flac-level 8        # Decode with maximum FLAC compression");
-    eprintln!("  glc -p track1.glc track2.glc          # Play multiple files gaplessly");
-    eprintln!();
-    eprintln!("Supported formats: WAV, FLAC (input), GLC (decode/play)");
-}
-
-fn main() -> Result<(), Box<dyn std::error::Error>>
+/// Read header and gapless information for a GLC file without decoding it
+fn read_info(input_path: &PathBuf) -> Result<InfoReport, anyhow::Error>
 {
-    let args: Vec<String> = std::env::args().collect();
+    use codec::load_encoded;
+    use container::GlcDecoder;
+    use std::fs::File;
+    use std::io::BufReader;
 
-    // Check if we have command-line arguments (skip program name)
-    if args.len() > 1
-    {
-        let first_arg = args[1].as_str();
+    let file_size = std::fs::metadata(input_path)?.len();
 
-        // Check for decode flag
-        if first_arg == "-d" || first_arg == "--decode"
+    let (header, gapless_info, frames, channel_mask, tracks, format) = match GlcDecoder::new(BufReader::new(File::open(input_path)?))
+    {
+        Ok(glc) => (glc.header().clone(), glc.gapless_info().clone(), glc.frame_count(), glc.channel_mask(),
+                    glc.tracks().len(), "GLC container"),
+        Err(_) =>
+        {
+            // Files written before the frame-delimited container have no footer, so load them whole
+            let encoded = load_encoded(input_path)?;
+            let frame_count = encoded.frames.len();
+            (encoded.header, encoded.gapless_info, frame_count, 0, 0, "GLC (legacy)")
+        }
+    };
+
+    let samples_per_channel = gapless_info.original_length / header.channels.max(1) as u64;
+    let duration_secs = samples_per_channel as f64 / header.sample_rate.max(1) as f64;
+
+    Ok(InfoReport
+    {
+        format,
+        sample_rate: header.sample_rate,
+        channels: header.channels,
+        channel_mask,
+        duration_secs,
+        samples_per_channel,
+        frames,
+        frame_size: 2 * header.hop_size,
+        encoder_delay: gapless_info.encoder_delay,
+        padding: gapless_info.padding,
+        highpass_hz: header.highpass,
+        long_term_prediction: header.long_term_prediction,
+        bandwidth_extension_hz: header.bandwidth_extension,
+        tracks,
+        file_size,
+        bitrate_kbps: (duration_secs > 0.0).then(|| file_size as f64 * 8.0 / duration_secs / 1000.0),
+    })
+}
+
+/// Print `info` for `input_path` as indented text
+fn print_info(input_path: &PathBuf, info: &InfoReport)
+{
+    println!("{}", input_path.display());
+    println!("  Format:        {}", info.format);
+    println!("  Sample rate:   {} Hz", info.sample_rate);
+    println!("  Channels:      {}", info.channels);
+    if info.channel_mask != 0
+    {
+        println!("  Channel mask:  {:#x}", info.channel_mask);
+    }
+    println!("  Duration:      {:.3} s ({} samples per channel)", info.duration_secs, info.samples_per_channel);
+    println!("  Frames:        {} of {} samples", info.frames, info.frame_size);
+    println!("  Encoder delay: {} samples", info.encoder_delay);
+    println!("  Padding:       {} samples", info.padding);
+    if let Some(highpass) = info.highpass_hz
+    {
+        println!("  High-pass:     {} Hz", highpass);
+    }
+    if info.long_term_prediction
+    {
+        println!("  Prediction:    long-term");
+    }
+    if let Some(crossover) = info.bandwidth_extension_hz
+    {
+        println!("  Extended from: {} Hz", crossover);
+    }
+    if info.tracks > 0
+    {
+        println!("  Tracks:        {}", info.tracks);
+    }
+    match info.bitrate_kbps
+    {
+        Some(kbps) => println!("  File size:     {} bytes ({:.1} kbps)", info.file_size, kbps),
+        None => println!("  File size:     {} bytes", info.file_size),
+    }
+}
+
+/// Keep the paths that exist and pass `is_valid`, recording the rest as failures in `outcomes`
+fn check_inputs<T: Serialize>(
+    paths: Vec<PathBuf>,
+    is_valid: fn(&PathBuf) -> bool,
+    invalid_message: &str,
+    outcomes: &mut Outcomes<T>,
+) -> Vec<PathBuf>
+{
+    let mut valid = Vec::with_capacity(paths.len());
+
+    for path in paths
+    {
+        if !path.exists()
+        {
+            outcomes.push(path, Err(anyhow::anyhow!("File not found")));
+        }
+        else if !is_valid(&path)
+        {
+            outcomes.push(path, Err(anyhow::anyhow!("{}", invalid_message)));
+        }
+        else
+        {
+            valid.push(path);
+        }
+    }
+
+    valid
+}
+
+/// Pair each input with its output path, checking that `-o` is only used with a single input,
+/// creating `--out-dir` if needed, and rejecting inputs that would overwrite each other's output
+/// Inputs whose output already exists are skipped with `--skip-existing`, kept with `--force`,
+/// and recorded as failures in `outcomes` otherwise
+fn plan_outputs<T: Serialize>(
+    inputs: Vec<PathBuf>,
+    output: &cli::OutputArgs,
+    extension: &str,
+    outcomes: &mut Outcomes<T>,
+) -> Result<Vec<(PathBuf, PathBuf)>, anyhow::Error>
+{
+    if output.file.is_some() && inputs.len() > 1
+    {
+        return Err(anyhow::anyhow!("-o can only be used with a single input file; use --out-dir for several"));
+    }
+
+    if let Some(ref dir) = output.out_dir
+    {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| anyhow::anyhow!("Failed to create output directory {:?}: {}", dir, e))?;
+    }
+
+    let mut jobs: Vec<(PathBuf, PathBuf)> = Vec::with_capacity(inputs.len());
+    for input in inputs
+    {
+        let output_path = output.resolve(&input, extension);
+        if output_path == input
+        {
+            return Err(anyhow::anyhow!("Output would overwrite input: {:?}", input));
+        }
+        if let Some((other, _)) = jobs.iter().find(|(_, o)| *o == output_path)
+        {
+            return Err(anyhow::anyhow!("{:?} and {:?} would both be written to {:?}", other, input, output_path));
+        }
+
+        if output_path.exists() && !output.force
+        {
+            if output.skip_existing
+            {
+                outcomes.skip(input, &output_path);
+            }
+            else
+            {
+                let error = anyhow::anyhow!("Output {:?} already exists (use --force to overwrite or --skip-existing to skip)", output_path);
+                outcomes.push(input, Err(error));
+            }
+            continue;
+        }
+
+        jobs.push((input, output_path));
+    }
+
+    Ok(jobs)
+}
+
+/// Run the `encode` subcommand, returning true on success
+fn run_encode(args: cli::EncodeArgs) -> bool
+{
+    if args.estimate
+    {
+        return run_estimate(args);
+    }
+
+    let mut outcomes = Outcomes::new("encode", args.json);
+    let unsupported = format!("Unsupported file type (supported formats: {}; --allow-ffmpeg reads others through ffmpeg)",
+                              ENCODE_INPUT_FORMATS);
+    let files_to_encode = check_inputs(args.files, encodable_check(args.allow_ffmpeg), &unsupported, &mut outcomes);
+
+    #[cfg(feature = "transcode")]
+    for path in files_to_encode.iter().filter(|p| transcode::is_transcode_input(p))
+    {
+        eprintln!("Warning: {} is lossy; transcoding it to GLC adds GLC's artifacts on top of the source codec's",
+                  path.display());
+    }
+
+    let mut jobs = match plan_outputs(files_to_encode, &args.output, "glc", &mut outcomes)
+    {
+        Ok(jobs) => jobs,
+        Err(e) => return outcomes.abort(e),
+    };
+    let options = audio::EncodeOptions
+    {
+        quality: args.quality.unwrap_or(codec::DEFAULT_QUALITY),
+        normalize: args.normalize,
+        allow_ffmpeg: args.allow_ffmpeg,
+        hop_size: if args.low_delay { codec::LOW_DELAY_HOP_SIZE } else { codec::HOP_SIZE },
+        cutoff: args.cutoff,
+        highpass: args.highpass,
+        long_term_prediction: args.ltp,
+        bandwidth_extension: args.bandwidth_extension,
+        adaptive_quality: args.adaptive,
+        bitrate: args.bitrate,
+        noise_shaping: args.noise_shaping,
+    };
+
+    let results = if jobs.len() == 1
+    {
+        let (input, output) = jobs.remove(0);
+        let result = encode_file(input.clone(), output, &options, args.json);
+        vec![(input, result)]
+    }
+    else if !jobs.is_empty()
+    {
+        encode_files(jobs, &options, args.json)
+    }
+    else
+    {
+        Vec::new()
+    };
+
+    for (input, result) in results
+    {
+        let result = match args.report
+        {
+            Some(format) => result.and_then(|mut report|
+            {
+                let path = write_quality_report(&input, &report, &options, format)?;
+                if !args.json
+                {
+                    println!("Report: {:?}", path.file_name().unwrap());
+                }
+                report.report = Some(path);
+                Ok(report)
+            }),
+            None => result,
+        };
+        outcomes.push(input, result);
+    }
+
+    outcomes.finish()
+}
+
+/// Decode the output of an encode and write its quality report (see [`analysis::summarize`]) next to it,
+/// returning the report's path
+/// The source is compared at the level it was encoded at, after any `--normalize` gain
+fn write_quality_report(input: &Path, report: &EncodeReport, options: &audio::EncodeOptions, format: cli::StatsFormat)
+    -> Result<PathBuf, anyhow::Error>
+{
+    use codec::{Decoder, load_encoded};
+    use std::io::BufWriter;
+
+    let mut reader = audio::LosslessReader::open_with(input, options.allow_ffmpeg)?;
+    let mut original = Vec::new();
+    while reader.read_block(&mut original)? {}
+    if let Some(gain_db) = report.gain_db
+    {
+        let gain = 10f64.powf(gain_db / 20.0) as f32;
+        original.iter_mut().for_each(|s| *s *= gain);
+    }
+
+    let encoded = load_encoded(&report.output)?;
+    let decoded = Decoder::new(encoded.header.channels as usize, encoded.header.sample_rate).decode(&encoded, None)?;
+    let summary = analysis::summarize(&encoded, report.output_size, &original, &decoded);
+
+    let path = report.output.with_extension(match format
+    {
+        cli::StatsFormat::Json => "report.json",
+        cli::StatsFormat::Csv => "report.csv",
+    });
+    let mut writer = BufWriter::new(std::fs::File::create(&path)?);
+    match format
+    {
+        cli::StatsFormat::Json =>
+        {
+            serde_json::to_writer_pretty(&mut writer, &summary)?;
+            writeln!(writer)?;
+        }
+        cli::StatsFormat::Csv => analysis::write_summary_csv(&summary, &mut writer)?,
+    }
+    writer.flush()?;
+    Ok(path)
+}
+
+/// Run `encode --estimate`: predict each file's encoded size, then the total, returning true on success
+fn run_estimate(args: cli::EncodeArgs) -> bool
+{
+    let json = args.json;
+    let mut outcomes = Outcomes::new("encode", json);
+    let unsupported = format!("Unsupported file type (supported formats: {}; --allow-ffmpeg reads others through ffmpeg)",
+                              ENCODE_INPUT_FORMATS);
+    let files = check_inputs(args.files, encodable_check(args.allow_ffmpeg), &unsupported, &mut outcomes);
+    let options = audio::EncodeOptions
+    {
+        quality: args.quality.unwrap_or(codec::DEFAULT_QUALITY),
+        normalize: args.normalize,
+        allow_ffmpeg: args.allow_ffmpeg,
+        hop_size: if args.low_delay { codec::LOW_DELAY_HOP_SIZE } else { codec::HOP_SIZE },
+        cutoff: args.cutoff,
+        highpass: args.highpass,
+        long_term_prediction: args.ltp,
+        bandwidth_extension: args.bandwidth_extension,
+        adaptive_quality: args.adaptive,
+        bitrate: args.bitrate,
+        noise_shaping: args.noise_shaping,
+    };
+    let (mut total_input, mut total_estimated) = (0u64, 0u64);
+
+    for input in files
+    {
+        let result = estimate_file(&input, &options);
+        if let Ok(report) = &result
+        {
+            total_input += report.input_size;
+            total_estimated += report.estimated_size;
+            if !json
+            {
+                println!("{}: ~{} bytes, ~{:.0} kbps, {:.1}% of original ({} of {} frames sampled)",
+                         input.display(), report.estimated_size, report.kbps, report.ratio,
+                         report.sampled_frames, report.frames);
+            }
+        }
+        outcomes.push(input, result);
+    }
+
+    if !json && total_input > 0
+    {
+        println!("Total: ~{} bytes, {:.1}% of original", total_estimated, total_estimated as f64 / total_input as f64 * 100.0);
+    }
+    outcomes.finish()
+}
+
+fn estimate_file(input: &Path, options: &audio::EncodeOptions) -> Result<EstimateReport, anyhow::Error>
+{
+    let reader = audio::LosslessReader::open_with(input, options.allow_ffmpeg)?;
+    let (sample_rate, channels) = (reader.sample_rate(), reader.channels());
+    drop(reader);
+
+    let estimate = audio::estimate_size(input, options, audio::DEFAULT_ESTIMATE_STRIDE)?;
+    let input_size = std::fs::metadata(input)?.len();
+
+    Ok(EstimateReport
+    {
+        sample_rate,
+        channels,
+        duration_secs: estimate.duration,
+        input_size,
+        estimated_size: estimate.bytes,
+        ratio: estimate.bytes as f64 / input_size.max(1) as f64 * 100.0,
+        kbps: estimate.kbps(),
+        frames: estimate.frames,
+        sampled_frames: estimate.sampled_frames,
+    })
+}
+
+/// Run the `decode` subcommand, returning true on success
+fn run_decode(mut args: cli::DecodeArgs) -> bool
+{
+    if args.output.file.as_deref() == Some(Path::new("-"))
+    {
+        return match decode_to_stdout(&args)
+        {
+            Ok(()) => true,
+            Err(e) =>
+            {
+                eprintln!("Error: {}", e);
+                false
+            }
+        };
+    }
+
+    let mut outcomes = Outcomes::new("decode", args.json);
+    let files_to_decode = check_inputs(std::mem::take(&mut args.files), is_glc_file, "Not a .glc file", &mut outcomes);
+    let output_format = match args.raw
+    {
+        Some(raw) => raw.name(),
+        None if args.wav => "wav",
+        None if args.opus => "opus",
+        None if args.mp3 => "mp3",
+        None if args.aiff => "aiff",
+        None if args.alac => "alac",
+        None => "flac",
+    };
+    if args.opus && !cfg!(feature = "opus")
+    {
+        return outcomes.abort(anyhow::anyhow!("Opus export not compiled in (build with --features opus)"));
+    }
+    if args.mp3 && !cfg!(feature = "mp3")
+    {
+        return outcomes.abort(anyhow::anyhow!("MP3 export not compiled in (build with --features mp3)"));
+    }
+    let extension = match output_format
+    {
+        _ if args.raw.is_some() => "raw",
+        "alac" => "m4a",
+        format => format,
+    };
+
+    let jobs = match plan_outputs(files_to_decode, &args.output, extension, &mut outcomes)
+    {
+        Ok(jobs) => jobs,
+        Err(e) => return outcomes.abort(e),
+    };
+
+    // Decode all files with the same settings
+    for (input, output) in jobs
+    {
+        let result = decode_file(input.clone(), output, output_format, &args);
+        outcomes.push(input, result);
+    }
+
+    outcomes.finish()
+}
+
+/// Decode a single file to stdout as headerless PCM (`decode --raw FORMAT -o -`),
+/// describing the stream on stderr so it can be passed on to ffmpeg or sox
+fn decode_to_stdout(args: &cli::DecodeArgs) -> Result<(), anyhow::Error>
+{
+    use codec::{Decoder, load_encoded_with};
+
+    let format = args.raw.ok_or_else(|| anyhow::anyhow!("-o - writes headerless PCM; choose a sample format with --raw (e.g. --raw f32le)"))?;
+    if args.json
+    {
+        return Err(anyhow::anyhow!("--json can't be used with -o -, which writes audio to stdout"));
+    }
+    let [input] = args.files.as_slice()
+    else
+    {
+        return Err(anyhow::anyhow!("-o - can only be used with a single input file"));
+    };
+
+    let options = container::DecodeOptions { allow_truncated: args.allow_truncated };
+    let (encoded, truncation) = load_encoded_with(input, &options).map_err(|e| anyhow::anyhow!("{}: {}", input.display(), e))?;
+    if let Some(report) = &truncation
+    {
+        warn_truncated(input, report);
+    }
+    let (sample_rate, channels) = (encoded.header.sample_rate, encoded.header.channels);
+    let samples = Decoder::new(channels as usize, sample_rate).decode(&encoded, None)?;
+
+    eprintln!("Raw PCM: {}, {} Hz, {} channels (ffmpeg -f {} -ar {} -ac {} -i -)",
+              format.name(), sample_rate, channels, format.name(), sample_rate, channels);
+
+    let mut stdout = std::io::BufWriter::new(std::io::stdout().lock());
+    let mut result = audio::write_raw_pcm(&mut stdout, &samples, format);
+    if result.is_ok()
+    {
+        result = stdout.flush().map_err(Into::into);
+    }
+    match result
+    {
+        // The reader stopped early (e.g. `| head -c ...`); that's not an error
+        Err(e) if e.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe) => Ok(()),
+        result => result,
+    }
+}
+
+/// Run the `play` subcommand, returning true on success
+fn run_play(args: cli::PlayArgs) -> bool
+{
+    let mut outcomes = Outcomes::<()>::new("play", false);
+    // URLs are checked when they are opened; everything else must be a local .glc file
+    let mut files_to_play = Vec::with_capacity(args.files.len());
+    for path in args.files
+    {
+        if !path.to_str().is_some_and(http::is_url)
+        {
+            files_to_play.extend(check_inputs(vec![path], is_glc_file, "Not a .glc file", &mut outcomes));
+        }
+        else if args.ffplay
+        {
+            outcomes.push(path, Err(anyhow::anyhow!("--ffplay only plays local files")));
+        }
+        else
+        {
+            files_to_play.push(path);
+        }
+    }
+    if !outcomes.finish() || files_to_play.is_empty()
+    {
+        return false;
+    }
+
+    let options = PlayOptions
+    {
+        device: args.device,
+        volume: args.volume as f32 / 100.0,
+        limiter: !args.no_limiter,
+        replaygain: args.replaygain,
+        preamp: args.preamp,
+        crossfade: args.crossfade,
+        eq: args.eq,
+        tempo: args.tempo,
+        capture: args.capture,
+        repeat: args.repeat,
+        shuffle: args.shuffle,
+        backend: args.backend,
+        buffer: args.buffer,
+        verbose: args.verbose,
+        tui: args.tui,
+    };
+
+    if args.ffplay
+    {
+        // ffplay gets every file through one pipe, so it plays them gaplessly as well
+        if let Err(e) = play_files_with_ffplay(files_to_play)
+        {
+            eprintln!("Error playing files: {}", e);
+            return false;
+        }
+    }
+    else if let Err(e) = play_files_gapless(files_to_play, &options)
+    {
+        // For native playback, play gaplessly
+        eprintln!("Error playing files: {}", e);
+        return false;
+    }
+
+    true
+}
+
+/// Run the `serve` subcommand, returning true on success (it only returns on failure)
+fn run_serve(args: cli::ServeArgs) -> bool
+{
+    match serve(args)
+    {
+        Ok(()) => true,
+        Err(e) =>
+        {
+            eprintln!("Error serving: {}", e);
+            false
+        }
+    }
+}
+
+/// Open the player and answer clients on the socket `args` names
+#[cfg(feature = "playback")]
+fn serve(args: cli::ServeArgs) -> Result<(), anyhow::Error>
+{
+    let server = server::Server::new(Player::new(args.device.as_deref())?);
+
+    #[cfg(unix)]
+    if let Some(path) = args.socket
+    {
+        // A socket file left behind by an earlier run would stop this one from binding
+        if path.exists()
+        {
+            std::fs::remove_file(&path)?;
+        }
+        let listener = std::os::unix::net::UnixListener::bind(&path)?;
+        println!("Listening on {}", path.display());
+        return server::serve(server, move || listener.accept().map(|(stream, _)| stream));
+    }
+
+    let listener = std::net::TcpListener::bind(&args.listen)?;
+    println!("Listening on {}", listener.local_addr()?);
+    server::serve(server, move || listener.accept().map(|(stream, _)| stream))
+}
+
+/// `serve` stub when playback feature is not available
+#[cfg(not(feature = "playback"))]
+fn serve(_args: cli::ServeArgs) -> Result<(), anyhow::Error>
+{
+    eprintln!("Error: Playback support not compiled in");
+    eprintln!("Build with: cargo build --release --no-default-features --features playback");
+    Err(anyhow::anyhow!("Playback not available"))
+}
+
+/// Run the `serve-http` subcommand, returning true on success (it only returns on failure)
+fn run_serve_http(args: cli::ServeHttpArgs) -> bool
+{
+    let result = std::net::TcpListener::bind(&args.listen).map_err(anyhow::Error::from).and_then(|listener|
+    {
+        println!("Serving {} on http://{}/", args.dir.display(), listener.local_addr()?);
+        http::serve_files(listener, args.dir)
+    });
+    match result
+    {
+        Ok(()) => true,
+        Err(e) =>
+        {
+            eprintln!("Error serving: {}", e);
+            false
+        }
+    }
+}
+
+/// Run the `broadcast` subcommand, returning true on success
+fn run_broadcast(args: cli::BroadcastArgs) -> bool
+{
+    match broadcast_files(&args)
+    {
+        Ok(()) => true,
+        Err(e) =>
+        {
+            eprintln!("Error: {}", e);
+            false
+        }
+    }
+}
+
+/// Stream `args.inputs` to the server as one chained stream, in real time: .glc files have their
+/// frames copied (re-encoding only around the joins), anything else is encoded as an album
+fn broadcast_files(args: &cli::BroadcastArgs) -> Result<(), anyhow::Error>
+{
+    use broadcast::{connect, RealtimeWriter, StreamInfo};
+    use container::GlcDecoder;
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::time::Duration;
+
+    let info = StreamInfo
+    {
+        name: args.name.clone(),
+        description: args.description.clone(),
+        genre: args.genre.clone(),
+        public: args.public,
+    };
+    let glc_inputs = args.inputs.iter().filter(|path| is_glc_file(path)).count();
+    if glc_inputs > 0 && glc_inputs < args.inputs.len()
+    {
+        return Err(anyhow::anyhow!("Broadcast either .glc files (passed through) or tracks to encode, not a mix of both"));
+    }
+
+    let lead = Duration::from_secs_f64(args.lead);
+    let writer = if glc_inputs > 0
+    {
+        let mut sources = Vec::with_capacity(args.inputs.len());
+        for input in &args.inputs
+        {
+            let source = File::open(input).map_err(anyhow::Error::from)
+                                          .and_then(|f| GlcDecoder::new(BufReader::new(f)))
+                                          .map_err(|e| anyhow::anyhow!("{}: {}", input.display(), e))?;
+            sources.push(source);
+        }
+
+        println!("Broadcasting {} files to {}:", sources.len(), args.to);
+        for (number, input) in args.inputs.iter().enumerate()
+        {
+            println!("  {:>2}. {}", number + 1, input.display());
+        }
+        let (writer, summary) = edit::chain(&mut sources, RealtimeWriter::new(connect(&args.to, &info)?, lead))?;
+        println!("{} frames copied, {} re-encoded", summary.copied_frames, summary.reencoded_frames);
+        writer
+    }
+    else
+    {
+        let tracks = album_inputs(&args.inputs)?;
+        println!("Broadcasting {} tracks to {}:", tracks.len(), args.to);
+        for (number, track) in tracks.iter().enumerate()
+        {
+            println!("  {:>2}. {}", number + 1, track.display());
+        }
+        audio::encode_album(&tracks, RealtimeWriter::new(connect(&args.to, &info)?, lead))?
+    };
+
+    println!("Sent {:.1} s of audio in {} frames", writer.seconds(), writer.frames());
+    writer.into_inner().finish()?;
+    Ok(())
+}
+
+/// Run the `record` subcommand, returning true on success
+fn run_record(args: cli::RecordArgs) -> bool
+{
+    match record_to_file(&args)
+    {
+        Ok(()) => true,
+        Err(e) =>
+        {
+            eprintln!("Error recording to {}: {}", args.output.display(), e);
+            false
+        }
+    }
+}
+
+/// Width of each channel's bar in the `glc record` level meter
+#[cfg(feature = "record")]
+const METER_WIDTH: usize = 20;
+
+/// Quietest level the meter shows, in dBFS
+#[cfg(feature = "record")]
+const METER_FLOOR_DB: f32 = -60.0;
+
+/// A bar for one channel's peak, from -60 dBFS (empty) to full scale, marked if it clipped
+#[cfg(feature = "record")]
+fn level_meter(peak: f32) -> String
+{
+    let db = 20.0 * peak.max(1e-6).log10();
+    let filled = ((1.0 - db / METER_FLOOR_DB).clamp(0.0, 1.0) * METER_WIDTH as f32).round() as usize;
+    format!("[{}{}] {:>5}{}", "#".repeat(filled), "-".repeat(METER_WIDTH - filled),
+            if db <= METER_FLOOR_DB { "-inf".to_string() } else { format!("{:.1}", db) },
+            if peak >= 1.0 { " CLIP" } else { "" })
+}
+
+/// Record from the input device `args` names into a new `.glc`, showing the elapsed time and a
+/// level meter, until q or Ctrl+C is pressed or `--duration` has passed
+/// Without a terminal to read keys from, only `--duration` (or killing the process) stops it
+#[cfg(feature = "record")]
+fn record_to_file(args: &cli::RecordArgs) -> Result<(), anyhow::Error>
+{
+    use crossterm::cursor::MoveToColumn;
+    use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+    use crossterm::execute;
+    use crossterm::style::Print;
+    use crossterm::terminal::{Clear, ClearType};
+    use std::io::{BufWriter, IsTerminal};
+    use std::time::Duration;
+
+    if args.output.exists() && !args.force
+    {
+        return Err(anyhow::anyhow!("Output {:?} already exists (use --force to overwrite)", args.output));
+    }
+
+    let writer = BufWriter::new(std::fs::File::create(&args.output)?);
+    let options = audio::EncodeOptions
+    {
+        quality: args.quality.unwrap_or(codec::DEFAULT_QUALITY),
+        hop_size: if args.low_delay { codec::LOW_DELAY_HOP_SIZE } else { codec::HOP_SIZE },
+        ..Default::default()
+    };
+    let mut recorder = record::Recorder::start(args.device.as_deref(), &options, writer)?;
+    println!("Recording: {} Hz, {} channels", recorder.sample_rate(), recorder.channels());
+
+    let interactive = std::io::stdin().is_terminal();
+    let raw_mode = if interactive
+    {
+        println!("Press q to stop.");
+        Some(RawMode::enable()?)
+    }
+    else
+    {
+        if args.duration.is_none()
+        {
+            // A killed recording has no footer, but repairing it recovers the audio written before
+            println!("Recording until killed; run `glc repair` on the file afterwards");
+        }
+        None
+    };
+
+    let mut stdout = std::io::stdout();
+    loop
+    {
+        recorder.poll()?;
+        if args.duration.is_some_and(|duration| recorder.elapsed() >= duration)
+        {
+            break;
+        }
+        if !interactive
+        {
+            std::thread::sleep(Duration::from_millis(100));
+            continue;
+        }
+
+        let meters: Vec<String> = recorder.take_peaks().into_iter().map(level_meter).collect();
+        let line = format!("{}  {}", playback::format_time(recorder.elapsed()), meters.join("  "));
+        execute!(stdout, MoveToColumn(0), Clear(ClearType::CurrentLine), Print(line))?;
+
+        if event::poll(Duration::from_millis(100))?
+            && let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+            && (key.code == KeyCode::Char('q')
+                || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL)))
+        {
+            break;
+        }
+    }
+    if interactive
+    {
+        execute!(stdout, Print("\r\n"))?;
+    }
+    drop(raw_mode);
+
+    let stats = recorder.stats();
+    let elapsed = recorder.elapsed();
+    let sample_rate = recorder.sample_rate() as f64;
+    let mut writer = recorder.finish()?;
+    writer.flush()?;
+
+    println!("Saved: {:?} ({} of audio, {} bytes)", args.output.file_name().unwrap_or_default(),
+             playback::format_time(elapsed), std::fs::metadata(&args.output)?.len());
+    if stats.overruns > 0
+    {
+        eprintln!("Warning: encoding fell behind {} times, dropping {} frames ({:.3} s)",
+                  stats.overruns, stats.dropped_frames, stats.dropped_frames as f64 / sample_rate);
+    }
+    Ok(())
+}
+
+/// `record_to_file` stub when the record feature is not available
+#[cfg(not(feature = "record"))]
+fn record_to_file(_args: &cli::RecordArgs) -> Result<(), anyhow::Error>
+{
+    eprintln!("Error: Recording support not compiled in");
+    eprintln!("Build with: cargo build --release --features record");
+    Err(anyhow::anyhow!("Recording not available"))
+}
+
+/// Run the `info` subcommand, returning true on success
+fn run_info(args: cli::InfoArgs) -> bool
+{
+    let mut outcomes = Outcomes::new("info", args.json);
+    let files = check_inputs(args.files, is_glc_file, "Not a .glc file", &mut outcomes);
+
+    for path in files
+    {
+        let result = read_info(&path);
+        if let (false, Ok(info)) = (args.json, &result)
+        {
+            print_info(&path, info);
+        }
+        outcomes.push(path, result);
+    }
+
+    outcomes.finish()
+}
+
+/// Load any supported file as interleaved samples, decoding `.glc` files
+fn load_samples(path: &PathBuf) -> Result<(Vec<f32>, u32, u16), anyhow::Error>
+{
+    if !path.exists()
+    {
+        return Err(anyhow::anyhow!("{:?} not found", path));
+    }
+
+    if is_glc_file(path)
+    {
+        let (encoded, samples) = load_and_decode(path)?;
+        Ok((samples, encoded.header.sample_rate, encoded.header.channels))
+    }
+    else
+    {
+        audio::load_audio_file_lossless(path)
+    }
+}
+
+/// Load a `.glc` file whole and decode it
+fn load_and_decode(path: &PathBuf) -> Result<(codec::EncodedAudio, Vec<f32>), anyhow::Error>
+{
+    use codec::{Decoder, load_encoded};
+
+    let encoded = load_encoded(path)?;
+    let mut decoder = Decoder::new(encoded.header.channels as usize, encoded.header.sample_rate);
+    let samples = decoder.decode(&encoded, None)?;
+    Ok((encoded, samples))
+}
+
+/// Measure `decoded` against `original`
+fn compare_files(original: &PathBuf, decoded: &PathBuf) -> Result<CompareReport, anyhow::Error>
+{
+    let (original_samples, sample_rate, channels) = load_samples(original)?;
+    let (decoded_samples, decoded_rate, decoded_channels) = load_samples(decoded)?;
+
+    if decoded_rate != sample_rate || decoded_channels != channels
+    {
+        return Err(anyhow::anyhow!("Stream formats differ: {} Hz, {} channels vs {} Hz, {} channels",
+                                   sample_rate, channels, decoded_rate, decoded_channels));
+    }
+
+    Ok(CompareReport
+    {
+        original: original.clone(),
+        sample_rate,
+        channels,
+        quality: compare::compare(&original_samples, &decoded_samples, sample_rate, channels),
+    })
+}
+
+/// Print a comparison as indented text
+fn print_comparison(decoded: &PathBuf, report: &CompareReport)
+{
+    let quality = &report.quality;
+
+    println!("{} vs {}", decoded.display(), report.original.display());
+    println!("  Compared:            {} samples per channel", quality.compared_samples);
+    if quality.length_difference != 0
+    {
+        println!("  Length difference:   {:+} samples per channel", quality.length_difference);
+    }
+    println!("  SNR:                 {:.2} dB", quality.snr_db);
+    println!("  Peak difference:     {:.6} ({:.1} dBFS)", quality.peak_difference, quality.peak_difference_dbfs);
+    println!("  Loudness difference: {:+.2} dB", quality.loudness_difference_db);
+    println!("  Weighted SNR:        {:.2} dB (A-weighted)", quality.metrics.weighted_snr_db);
+    println!("  Spectral distortion: {:.2} dB", quality.metrics.spectral_distortion_db);
+    println!("  Noise-to-mask:       {:.2} dB ({:.0}% of frames disturbed)", quality.metrics.noise_to_mask_db,
+             quality.metrics.disturbed_frames * 100.0);
+    println!("  Perceptual grade:    {:.2} ODG (0 = transparent, -4 = very annoying)", quality.metrics.odg);
+    if let Some(worst) = quality.metrics.bands.iter().min_by(|a, b| a.snr_db.total_cmp(&b.snr_db))
+    {
+        println!("  Worst critical band: {}-{} Hz, {:.2} dB", worst.low_hz, worst.high_hz, worst.snr_db);
+    }
+    println!("  Band SNR:");
+    for band in &quality.bands
+    {
+        println!("    {:>7} Hz         {:.2} dB", band.center_hz, band.snr_db);
+    }
+}
+
+/// Run the `compare` subcommand, returning true on success
+fn run_compare(args: cli::CompareArgs) -> bool
+{
+    let mut outcomes = Outcomes::new("compare", args.json);
+
+    let result = compare_files(&args.original, &args.decoded);
+    if let (false, Ok(report)) = (args.json, &result)
+    {
+        print_comparison(&args.decoded, report);
+    }
+    outcomes.push(args.decoded, result);
+
+    outcomes.finish()
+}
+
+/// Run the `album` subcommand, returning true on success
+fn run_album(args: cli::AlbumArgs) -> bool
+{
+    match encode_album_file(&args)
+    {
+        Ok(()) => true,
+        Err(e) =>
+        {
+            eprintln!("Error: {}", e);
+            false
+        }
+    }
+}
+
+/// Tracks for `glc album` and `glc shootout`: the encodable files of a single directory argument in file name order,
+/// or the given files as they are
+fn album_inputs(paths: &[PathBuf]) -> Result<Vec<PathBuf>, anyhow::Error>
+{
+    if let [dir] = paths
+        && dir.is_dir()
+    {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| is_encodable_file(path))
+            .collect();
+        files.sort();
+
+        if files.is_empty()
+        {
+            return Err(anyhow::anyhow!("No {} files found in {:?}", ENCODE_INPUT_FORMATS, dir));
+        }
+        return Ok(files);
+    }
+
+    for path in paths
+    {
+        if !path.exists()
+        {
+            return Err(anyhow::anyhow!("{:?} not found", path));
+        }
+        if !is_encodable_file(path)
+        {
+            return Err(anyhow::anyhow!("{:?}: unsupported file type (supported formats: {})", path, ENCODE_INPUT_FORMATS));
+        }
+    }
+    Ok(paths.to_vec())
+}
+
+/// Encode the album's tracks into one chained `.glc` file
+fn encode_album_file(args: &cli::AlbumArgs) -> Result<(), anyhow::Error>
+{
+    use audio::encode_album;
+
+    let tracks = album_inputs(&args.inputs)?;
+    if tracks.contains(&args.output)
+    {
+        return Err(anyhow::anyhow!("Output would overwrite input: {:?}", args.output));
+    }
+    if args.output.exists() && !args.force
+    {
+        return Err(anyhow::anyhow!("Output {:?} already exists (use --force to overwrite)", args.output));
+    }
+
+    println!("Encoding {} tracks:", tracks.len());
+    for (number, track) in tracks.iter().enumerate()
+    {
+        println!("  {:>2}. {}", number + 1, track.display());
+    }
+
+    let writer = std::io::BufWriter::new(std::fs::File::create(&args.output)?);
+    encode_album(&tracks, writer)?.flush()?;
+
+    let mut input_size = 0;
+    for track in &tracks
+    {
+        input_size += std::fs::metadata(track)?.len();
+    }
+    let report = encode_report(args.output.clone(), input_size)?;
+    println!("Saved: {:?} ({:.3} s, {} bytes, {:.1}% of original)",
+             report.output.file_name().unwrap(), report.duration_secs, report.output_size, report.ratio);
+
+    Ok(())
+}
+
+/// Run the `split` subcommand, returning true on success
+fn run_split(args: cli::SplitArgs) -> bool
+{
+    match split_file(&args)
+    {
+        Ok(()) => true,
+        Err(e) =>
+        {
+            eprintln!("Error: {}: {}", args.file.display(), e);
+            false
+        }
+    }
+}
+
+/// File name for track `number` of a split: "NN - Title.ext", or "Track NN.ext" without a title
+fn track_file_name(number: usize, tags: &container::Tags, extension: &str) -> String
+{
+    match tags.get("title")
+    {
+        Some(title) =>
+        {
+            let title: String = title.chars()
+                                     .map(|c| if matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') { '_' } else { c })
+                                     .collect();
+            format!("{:02} - {}.{}", number, title.trim(), extension)
+        }
+        None => format!("Track {:02}.{}", number, extension),
+    }
+}
+
+/// Write each track of a `.glc` file to its own file, cutting on frame boundaries
+/// `.glc` tracks copy the frames they need; FLAC tracks are decoded from those same frames
+fn split_file(args: &cli::SplitArgs) -> Result<(), anyhow::Error>
+{
+    use codec::Decoder;
+    use container::{GlcDecoder, read_encoded};
+    use edit::{extract_range, merge_tags};
+    use flac::export_to_flac_with_level;
+    use std::fs::File;
+    use std::io::{BufReader, BufWriter, Cursor};
+
+    let mut glc = GlcDecoder::new(BufReader::new(File::open(&args.file)?))?;
+    let sample_rate = glc.header().sample_rate;
+    let channels = glc.header().channels;
+    let total = glc.gapless_info().original_length / channels.max(1) as u64;
+
+    let (album_tags, tracks) = match args.cue
+    {
+        Some(ref cue) =>
+        {
+            let (cue_tags, tracks) = cue::parse_cue(&std::fs::read_to_string(cue)?, sample_rate, total)?;
+            (merge_tags(glc.tags(), &cue_tags), tracks)
+        }
+        None => (glc.tags().clone(), glc.tracks().to_vec()),
+    };
+    if tracks.is_empty()
+    {
+        return Err(anyhow::anyhow!("No track list in the file; pass --cue to split it with a cue sheet"));
+    }
+
+    let dir = match args.out_dir
+    {
+        Some(ref dir) => dir.clone(),
+        None => args.file.parent().map(|p| p.to_path_buf()).unwrap_or_default(),
+    };
+    if args.out_dir.is_some()
+    {
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| anyhow::anyhow!("Failed to create output directory {:?}: {}", dir, e))?;
+    }
+
+    let extension = if args.flac { "flac" } else { "glc" };
+    let jobs: Vec<(PathBuf, container::Tags)> = tracks.iter()
+        .enumerate()
+        .map(|(i, track)|
+        {
+            let tags = merge_tags(&album_tags, &track.tags);
+            (dir.join(track_file_name(i + 1, &tags, extension)), tags)
+        })
+        .collect();
+
+    // Check every output before writing any, so a failed split leaves nothing half done
+    for (output, _) in &jobs
+    {
+        if *output == args.file
+        {
+            return Err(anyhow::anyhow!("Output would overwrite input: {:?}", output));
+        }
+        if output.exists() && !args.force
+        {
+            return Err(anyhow::anyhow!("Output {:?} already exists (use --force to overwrite)", output));
+        }
+    }
+
+    println!("Splitting {} tracks:", tracks.len());
+    for (track, (output, tags)) in tracks.iter().zip(jobs)
+    {
+        if args.flac
+        {
+            let bytes = extract_range(&mut glc, track.start, track.length, tags, Vec::new())?;
+            let encoded = read_encoded(Cursor::new(bytes))?;
+            let samples = Decoder::new(channels as usize, sample_rate).decode(&encoded, None)?;
+            export_to_flac_with_level(&output, &samples, sample_rate, channels, cli::DEFAULT_FLAC_LEVEL)?;
+        }
+        else
+        {
+            let writer = BufWriter::new(File::create(&output)?);
+            extract_range(&mut glc, track.start, track.length, tags, writer)?.flush()?;
+        }
+
+        println!("  {} ({:.3} s)", output.display(), track.length as f64 / sample_rate.max(1) as f64);
+    }
+
+    Ok(())
+}
+
+/// Run the `analyze` subcommand, returning true on success
+fn run_analyze(args: cli::AnalyzeArgs) -> bool
+{
+    match analyze_file(&args)
+    {
+        Ok(()) => true,
+        Err(e) =>
+        {
+            eprintln!("Error: {}: {}", args.file.display(), e);
+            false
+        }
+    }
+}
+
+/// Print per-frame statistics for a `.glc` file (transients detected on its decoded output),
+/// or for a lossless file encoded in memory (transients detected on the source)
+fn analyze_file(args: &cli::AnalyzeArgs) -> Result<(), anyhow::Error>
+{
+    use analysis::{analyze, write_csv};
+    use codec::Encoder;
+
+    if !args.file.exists()
+    {
+        return Err(anyhow::anyhow!("File not found"));
+    }
+
+    let (encoded, samples) = if is_glc_file(&args.file)
+    {
+        load_and_decode(&args.file)?
+    }
+    else if is_lossless_audio_file(&args.file)
+    {
+        let (samples, sample_rate, channels) = audio::load_audio_file_lossless(&args.file)?;
+        let encoded = Encoder::new(sample_rate).encode(&samples, channels)?;
+        (encoded, samples)
+    }
+    else
+    {
+        return Err(anyhow::anyhow!("Unsupported file type (expected .glc, WAV, FLAC, AIFF, or CAF)"));
+    };
+
+    let frames = analyze(&encoded, &samples)?;
+
+    let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+    match args.format
+    {
+        cli::StatsFormat::Csv => write_csv(&frames, &mut out)?,
+        cli::StatsFormat::Json =>
+        {
+            let report = AnalyzeReport
+            {
+                input: args.file.clone(),
+                sample_rate: encoded.header.sample_rate,
+                channels: encoded.header.channels,
+                frames,
+            };
+            serde_json::to_writer_pretty(&mut out, &report)?;
+            writeln!(out)?;
+        }
+    }
+    out.flush()?;
+
+    Ok(())
+}
+
+/// Run the `tag` subcommand, returning true on success
+fn run_tag(args: cli::TagArgs) -> bool
+{
+    match tag_file(&args)
+    {
+        Ok(()) => true,
+        Err(e) =>
+        {
+            eprintln!("Error: {}: {}", args.file.display(), e);
+            false
+        }
+    }
+}
+
+/// Apply `--from-source`, `--delete`, and `--set` (in that order) to a file's tags, then print them
+fn tag_file(args: &cli::TagArgs) -> Result<(), anyhow::Error>
+{
+    use audio::LosslessReader;
+    use container::{GlcDecoder, Tags, rewrite_tags};
+    use std::fs::File;
+    use std::io::BufReader;
+
+    let read_glc_tags = |path: &PathBuf| -> Result<Tags, anyhow::Error>
+    {
+        Ok(GlcDecoder::new(BufReader::new(File::open(path)?))?.tags().clone())
+    };
+
+    let mut tags = read_glc_tags(&args.file)?;
+
+    if let Some(ref source) = args.from_source
+    {
+        let source_tags = if is_glc_file(source)
         {
-            if args.len() < 3
-            {
-                eprintln!("Error: -d requires at least one .glc file");
-                print_usage();
-                std::process::exit(1);
-            }
+            read_glc_tags(source)?
+        }
+        else
+        {
+            LosslessReader::open(source)?.tags().clone()
+        };
 
-            let mut has_errors = false;
-            let mut files_to_decode: Vec<PathBuf> = Vec::new();
-            let mut output_format = "flac";
-            let mut flac_level = 5u8;
-            let mut arg_idx = 2;
+        // Source values replace existing ones key by key, keeping multi-valued tags intact
+        tags = edit::merge_tags(&tags, &source_tags);
+    }
 
-            // First pass: collect files and parse options
-            while arg_idx < args.len()
-            {
-                match args[arg_idx].as_str()
-                {
-                    "--wav" =>
-                    {
-                        output_format = "wav";
-                        arg_idx += 1;
-                    }
-                    "--flac-level" =>
-                    {
-                        if arg_idx + 1 >= args.len()
-                        {
-                            eprintln!("Error: --flac-level requires a value (0-8)");
-                            std::process::exit(1);
-                        }
-                        flac_level = args[arg_idx + 1].parse::<u8>().unwrap_or_else(|_| {
-                            eprintln!("Error: Invalid FLAC level, must be 0-8");
-                            std::process::exit(1);
-                        });
-                        if flac_level > 8
-                        {
-                            eprintln!("Error: FLAC level must be 0-8");
-                            std::process::exit(1);
-                        }
-                        arg_idx += 2;
-                    }
-                    _ =>
-                    {
-                        // This should be a file path
-                        let path = PathBuf::from(&args[arg_idx]);
-
-                        if !path.exists()
-                        {
-                            eprintln!("Error: File not found: {:?}", path);
-                            has_errors = true;
-                        }
-                        else if !is_glc_file(&path)
-                        {
-                            eprintln!("Error: Not a .glc file: {:?}", path);
-                            has_errors = true;
-                        }
-                        else
-                        {
-                            files_to_decode.push(path);
-                        }
-                        arg_idx += 1;
-                    }
-                }
-            }
+    for key in &args.delete
+    {
+        tags.remove(key);
+    }
 
-            if files_to_decode.is_empty()
-            {
-                eprintln!("Error: No valid .glc files to decode");
-                std::process::exit(1);
-            }
+    for (key, value) in &args.set
+    {
+        tags.set(key, value.as_str());
+    }
 
-            // Decode all files with the same settings
-            for path in files_to_decode
-            {
-                match decode_file(path, output_format, flac_level)
-                {
-                    Ok(()) => {},
-                    Err(e) =>
-                    {
-                        eprintln!("Error decoding file: {}", e);
-                        has_errors = true;
-                    }
-                }
-            }
+    if args.from_source.is_some() || !args.delete.is_empty() || !args.set.is_empty()
+    {
+        rewrite_tags(&args.file, &tags)?;
+    }
 
-            if has_errors
-            {
-                std::process::exit(1);
-            }
+    if tags.is_empty()
+    {
+        println!("{}: no tags", args.file.display());
+    }
+    for (key, value) in tags.iter()
+    {
+        println!("{}={}", key, value);
+    }
 
-            return Ok(());
+    Ok(())
+}
+
+fn run_chain(args: cli::ChainArgs) -> bool
+{
+    match chain_files(&args)
+    {
+        Ok(()) => true,
+        Err(e) =>
+        {
+            eprintln!("Error: {}", e);
+            false
+        }
+    }
+}
+
+/// Join `.glc` files into one gapless stream, re-encoding only the frames around each join
+fn chain_files(args: &cli::ChainArgs) -> Result<(), anyhow::Error>
+{
+    use container::GlcDecoder;
+    use edit::chain;
+    use std::fs::File;
+    use std::io::{BufReader, BufWriter};
+
+    if args.inputs.contains(&args.output)
+    {
+        return Err(anyhow::anyhow!("Output would overwrite input: {:?}", args.output));
+    }
+    if args.output.exists() && !args.force
+    {
+        return Err(anyhow::anyhow!("Output {:?} already exists (use --force to overwrite)", args.output));
+    }
+
+    let mut sources = Vec::with_capacity(args.inputs.len());
+    for input in &args.inputs
+    {
+        let source = File::open(input).map_err(anyhow::Error::from)
+                                      .and_then(|f| GlcDecoder::new(BufReader::new(f)))
+                                      .map_err(|e| anyhow::anyhow!("{}: {}", input.display(), e))?;
+        sources.push(source);
+    }
+
+    println!("Chaining {} files:", sources.len());
+    for (number, input) in args.inputs.iter().enumerate()
+    {
+        println!("  {:>2}. {}", number + 1, input.display());
+    }
+
+    let writer = BufWriter::new(File::create(&args.output)?);
+    let (mut writer, summary) = chain(&mut sources, writer)?;
+    writer.flush()?;
+
+    let mut input_size = 0;
+    for input in &args.inputs
+    {
+        input_size += std::fs::metadata(input)?.len();
+    }
+    let report = encode_report(args.output.clone(), input_size)?;
+    println!("Saved: {:?} ({:.3} s, {} frames copied, {} re-encoded)",
+             report.output.file_name().unwrap(), report.duration_secs, summary.copied_frames, summary.reencoded_frames);
+
+    Ok(())
+}
+
+fn run_trim(args: cli::TrimArgs) -> bool
+{
+    match trim_file(&args)
+    {
+        Ok(()) => true,
+        Err(e) =>
+        {
+            eprintln!("Error: {}: {}", args.file.display(), e);
+            false
         }
+    }
+}
+
+/// Cut a `.glc` file to `--start`..`--end`, copying the frames the range overlaps
+fn trim_file(args: &cli::TrimArgs) -> Result<(), anyhow::Error>
+{
+    use container::GlcDecoder;
+    use edit::trim;
+    use std::fs::File;
+    use std::io::{BufReader, BufWriter};
 
-        // Check for play flag
-        if first_arg == "-p" || first_arg == "--play"
+    let output = args.output.clone().unwrap_or_else(|| args.file.with_extension("trimmed.glc"));
+    if output == args.file
+    {
+        return Err(anyhow::anyhow!("Output would overwrite input: {:?}", output));
+    }
+    if output.exists() && !args.force
+    {
+        return Err(anyhow::anyhow!("Output {:?} already exists (use --force to overwrite)", output));
+    }
+
+    let mut glc = GlcDecoder::new(BufReader::new(File::open(&args.file)?))?;
+    let rate = glc.header().sample_rate as f64;
+    let total = glc.gapless_info().original_length / glc.header().channels.max(1) as u64;
+    let to_samples = |seconds: f64| (seconds * rate).round() as u64;
+
+    let start = args.start.map_or(0, to_samples);
+    let end = args.end.map_or(total, to_samples);
+    if end > total
+    {
+        return Err(anyhow::anyhow!("--end is past the end of the file ({:.3} s)", total as f64 / rate.max(1.0)));
+    }
+    if start >= end
+    {
+        return Err(anyhow::anyhow!("Nothing left: --start must be before --end"));
+    }
+
+    let writer = BufWriter::new(File::create(&output)?);
+    trim(&mut glc, start, end - start, writer)?.flush()?;
+
+    println!("Saved: {:?} ({:.3} s to {:.3} s, {:.3} s)",
+             output.file_name().unwrap(), start as f64 / rate, end as f64 / rate, (end - start) as f64 / rate);
+    Ok(())
+}
+
+fn run_gain(args: cli::GainArgs) -> bool
+{
+    match gain_file(&args)
+    {
+        Ok(()) => true,
+        Err(e) =>
         {
-            if args.len() < 3
-            {
-                eprintln!("Error: -p requires at least one .glc file");
-                print_usage();
-                std::process::exit(1);
-            }
+            eprintln!("Error: {}: {}", args.file.display(), e);
+            false
+        }
+    }
+}
 
-            let mut use_ffplay = false;
-            let mut files_to_play: Vec<PathBuf> = Vec::new();
-            let mut arg_idx = 2;
+/// Apply a fixed or ReplayGain gain to a `.glc` file, in place unless `--output` is given
+fn gain_file(args: &cli::GainArgs) -> Result<(), anyhow::Error>
+{
+    use container::GlcDecoder;
+    use edit::{apply_gain, parse_gain};
+    use std::fs::File;
+    use std::io::{BufReader, BufWriter};
+
+    if let Some(ref output) = args.output
+        && output.exists()
+        && !args.force
+    {
+        return Err(anyhow::anyhow!("Output {:?} already exists (use --force to overwrite)", output));
+    }
 
-            // Parse play options and collect files
-            while arg_idx < args.len()
+    let mut glc = GlcDecoder::new(BufReader::new(File::open(&args.file)?))?;
+    let gain = match (args.gain, args.replaygain)
+    {
+        (Some(gain), _) => gain,
+        (None, Some(mode)) =>
+        {
+            let key = match mode
             {
-                match args[arg_idx].as_str()
-                {
-                    "--ffplay" =>
-                    {
-                        use_ffplay = true;
-                        arg_idx += 1;
-                    }
-                    _ =>
-                    {
-                        let path = PathBuf::from(&args[arg_idx]);
-
-                        if !path.exists()
-                        {
-                            eprintln!("Error: File not found: {:?}", path);
-                            std::process::exit(1);
-                        }
-
-                        if !is_glc_file(&path)
-                        {
-                            eprintln!("Error: Not a .glc file: {:?}", path);
-                            std::process::exit(1);
-                        }
-
-                        files_to_play.push(path);
-                        arg_idx += 1;
-                    }
-                }
-            }
+                cli::ReplayGainMode::Track => "replaygain_track_gain",
+                cli::ReplayGainMode::Album => "replaygain_album_gain",
+            };
+            let value = glc.tags().get(key).ok_or_else(|| anyhow::anyhow!("No {} tag", key.to_uppercase()))?;
+            parse_gain(value)?
+        }
+        (None, None) => return Err(anyhow::anyhow!("Give a gain or --replaygain")),
+    };
 
-            if files_to_play.is_empty()
-            {
-                eprintln!("Error: No valid .glc files to play");
-                std::process::exit(1);
-            }
+    // Write next to the destination and rename over it, so a failure never leaves a half-written file
+    let destination = args.output.clone().unwrap_or_else(|| args.file.clone());
+    let temp = destination.with_extension("glc.tmp");
+    let writer = BufWriter::new(File::create(&temp)?);
+    let result = apply_gain(&mut glc, gain, writer).and_then(|(mut writer, clipped)|
+    {
+        writer.flush()?;
+        Ok(clipped)
+    });
+    drop(glc);
+    let clipped = match result
+    {
+        Ok(clipped) => clipped,
+        Err(e) =>
+        {
+            let _ = std::fs::remove_file(&temp);
+            return Err(e);
+        }
+    };
+    std::fs::rename(&temp, &destination)?;
+
+    println!("Applied {:+.2} dB to {}", gain, destination.display());
+    if clipped > 0
+    {
+        println!("Warning: {} raw PCM samples clipped", clipped);
+    }
+    if gain > 0.0
+    {
+        println!("Note: a positive gain can make the decoded output clip");
+    }
+    Ok(())
+}
+
+fn run_gain_scan(args: cli::GainScanArgs) -> bool
+{
+    match gain_scan(&args)
+    {
+        Ok(()) => true,
+        Err(e) =>
+        {
+            eprintln!("Error: {}", e);
+            false
+        }
+    }
+}
+
+/// Decode each file once to measure it, then write its ReplayGain tags (and the album's, with
+/// `--album`); nothing is written unless every file could be measured
+fn gain_scan(args: &cli::GainScanArgs) -> Result<(), anyhow::Error>
+{
+    use container::{rewrite_tags, GlcDecoder};
+    use edit::{measure_loudness, replaygain_db, set_replaygain};
+    use std::fs::File;
+    use std::io::BufReader;
+
+    let mut scanned = Vec::with_capacity(args.files.len());
+    for path in &args.files
+    {
+        let measured = File::open(path).map_err(anyhow::Error::from)
+            .and_then(|file| GlcDecoder::new(BufReader::new(file)))
+            .and_then(|mut glc| Ok((glc.tags().clone(), measure_loudness(&mut glc)?)));
+        scanned.push(measured.map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?);
+    }
+
+    let album = args.album.then(|| loudness::combined_loudness(scanned.iter().map(|(_, meter)| meter)));
+    for (path, (mut tags, meter)) in args.files.iter().zip(scanned)
+    {
+        let track = meter.loudness();
+        set_replaygain(&mut tags, "track", &track);
+        if let Some(ref album) = album
+        {
+            set_replaygain(&mut tags, "album", album);
+        }
+        rewrite_tags(path, &tags).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+        println!("{}: {:.1} LUFS, gain {:+.2} dB, peak {:.6}", path.display(), track.integrated_lufs, replaygain_db(&track), track.peak);
+    }
+    if let Some(album) = album
+    {
+        println!("Album: {:.1} LUFS, gain {:+.2} dB, peak {:.6}", album.integrated_lufs, replaygain_db(&album), album.peak);
+    }
+    Ok(())
+}
+
+fn run_repair(args: cli::RepairArgs) -> bool
+{
+    match repair_file(&args)
+    {
+        Ok(()) => true,
+        Err(e) =>
+        {
+            eprintln!("Error: {}: {}", args.file.display(), e);
+            false
+        }
+    }
+}
+
+/// Salvage a damaged `.glc` file into a new one and list the time ranges that were lost
+fn repair_file(args: &cli::RepairArgs) -> Result<(), anyhow::Error>
+{
+    use std::io::BufWriter;
+
+    let output = args.output.clone().unwrap_or_else(|| args.file.with_extension("repaired.glc"));
+    if output == args.file
+    {
+        return Err(anyhow::anyhow!("Output would overwrite input: {:?}", output));
+    }
+    if output.exists() && !args.force
+    {
+        return Err(anyhow::anyhow!("Output {:?} already exists (use --force to overwrite)", output));
+    }
+
+    let data = std::fs::read(&args.file)?;
+    let writer = BufWriter::new(std::fs::File::create(&output)?);
+    let (mut writer, report) = container::repair(&data, writer)?;
+    writer.flush()?;
+
+    let rate = report.sample_rate.max(1) as f64;
+    println!("Salvaged {} of {} frames", report.frames - report.lost_frames, report.frames);
+    let approx = if report.exact { "" } else { "about " };
+    for range in &report.lost_ranges
+    {
+        println!("Lost {}{:.3} s to {:.3} s (replaced with silence)", approx, range.start as f64 / rate, range.end as f64 / rate);
+    }
+    if report.truncated
+    {
+        println!("Footer missing: the file is cut off after {:.3} s of audio; the rest is lost, along with any tags",
+                 report.length as f64 / rate);
+    }
+    if report.metadata_lost
+    {
+        println!("Tags or track list damaged and dropped");
+    }
+    if report.is_intact()
+    {
+        println!("Nothing was lost");
+    }
+    println!("Saved: {:?}", output.file_name().unwrap());
+    Ok(())
+}
+
+/// Run the `shootout` subcommand, returning true on success
+/// Contenders whose tools aren't installed are skipped with a warning rather than failing the run
+fn run_shootout(args: cli::ShootoutArgs) -> bool
+{
+    use shootout::{combine, DEFAULT_CONTENDERS};
+
+    let mut outcomes = Outcomes::new("shootout", args.json);
+    let tracks = match album_inputs(&args.inputs)
+    {
+        Ok(tracks) => tracks,
+        Err(e) => return outcomes.abort(e),
+    };
+
+    let requested = if args.contenders.is_empty() { DEFAULT_CONTENDERS.to_vec() } else { args.contenders };
+    let mut contenders = Vec::new();
+    for contender in requested
+    {
+        if contender.is_available()
+        {
+            contenders.push(contender);
+        }
+        else
+        {
+            eprintln!("Skipping {}: needs {} on the PATH", contender, contender.tools().join(" and "));
+        }
+    }
+    if contenders.is_empty()
+    {
+        return outcomes.abort(anyhow::anyhow!("No contenders can be run"));
+    }
+
+    let work_dir = std::env::temp_dir().join(format!("glc-shootout-{}", std::process::id()));
+    if let Err(e) = std::fs::create_dir_all(&work_dir)
+    {
+        return outcomes.abort(e.into());
+    }
 
-            // Play files
-            if use_ffplay
+    let mut corpus = vec![Vec::new(); contenders.len()];
+    for track in &tracks
+    {
+        let result = shootout_track(track, &contenders, &work_dir);
+        if let Ok(report) = &result
+        {
+            for (scores, score) in corpus.iter_mut().zip(&report.scores)
             {
-                // For ffplay, we need to play files sequentially
-                for path in files_to_play
-                {
-                    match play_file_with_ffplay(path)
-                    {
-                        Ok(()) => {},
-                        Err(e) =>
-                        {
-                            eprintln!("Error playing file: {}", e);
-                            std::process::exit(1);
-                        }
-                    }
-                }
+                scores.push(score.clone());
             }
-            else
+            if !args.json
             {
-                // For native playback, play gaplessly
-                match play_files_gapless(files_to_play)
-                {
-                    Ok(()) => {},
-                    Err(e) =>
-                    {
-                        eprintln!("Error playing files: {}", e);
-                        std::process::exit(1);
-                    }
-                }
+                println!("{} ({} Hz, {} channels)", track.display(), report.sample_rate, report.channels);
+                print_scores(&report.scores);
             }
+        }
+        outcomes.push(track.clone(), result);
+    }
+    std::fs::remove_dir_all(&work_dir).ok();
 
-            return Ok(());
+    if !args.json && tracks.len() > 1
+    {
+        println!("Whole corpus (bitrates over all tracks, other measures averaged)");
+        print_scores(&corpus.iter().filter_map(|scores| combine(scores)).collect::<Vec<_>>());
+    }
+    outcomes.finish()
+}
+
+/// Run every contender on one track
+fn shootout_track(track: &PathBuf, contenders: &[shootout::Contender], work_dir: &Path) -> Result<ShootoutReport, anyhow::Error>
+{
+    let (samples, sample_rate, channels) = audio::load_audio_file_lossless(track)?;
+    let scores = contenders.iter()
+                           .map(|contender| contender.run(&samples, sample_rate, channels, work_dir)
+                                                     .map_err(|e| anyhow::anyhow!("{}: {}", contender, e)))
+                           .collect::<Result<Vec<_>, _>>()?;
+    Ok(ShootoutReport { sample_rate, channels, scores })
+}
+
+/// Print shootout scores as an indented table
+fn print_scores(scores: &[shootout::Score])
+{
+    println!("  {:<12} {:>10} {:>8} {:>8} {:>8} {:>8} {:>6}", "Contender", "Bytes", "kbps", "SNR", "W. SNR", "NMR", "ODG");
+    for score in scores
+    {
+        println!("  {:<12} {:>10} {:>8.1} {:>8.2} {:>8.2} {:>8.2} {:>6.2}", score.contender, score.bytes, score.kbps,
+                 score.snr_db, score.weighted_snr_db, score.noise_to_mask_db, score.odg);
+    }
+}
+
+/// Fill in options not given on the command line from the config file, and set up the thread pool
+fn apply_config(cli: &mut Cli, config: &config::Config)
+{
+    let fill_out_dir = |output: &mut cli::OutputArgs|
+    {
+        if output.file.is_none() && output.out_dir.is_none()
+        {
+            output.out_dir = config.out_dir.clone();
         }
+    };
 
-        // CLI mode: encode files
-        let mut has_errors = false;
+    match cli.command
+    {
+        cli::Command::Encode(ref mut args) =>
+        {
+            args.quality = args.quality.or(config.quality);
+            fill_out_dir(&mut args.output);
+        }
+        cli::Command::Decode(ref mut args) =>
+        {
+            args.flac_level = args.flac_level.or(config.flac_level);
+            fill_out_dir(&mut args.output);
+        }
+        cli::Command::Play(ref mut args) if !args.ffplay =>
+        {
+            args.device = args.device.take().or_else(|| config.device.clone());
+        }
+        cli::Command::Serve(ref mut args) =>
+        {
+            args.device = args.device.take().or_else(|| config.device.clone());
+        }
+        // The configured device is an output, so it doesn't apply here
+        cli::Command::Record(ref mut args) =>
+        {
+            args.quality = args.quality.or(config.quality);
+        }
+        _ => {}
+    }
 
-        for arg in &args[1..]
+    if let Some(threads) = cli.threads.or(config.threads)
+    {
+        #[cfg(feature = "parallel")]
+        if let Err(e) = rayon::ThreadPoolBuilder::new().num_threads(threads).build_global()
         {
-            let path = PathBuf::from(arg);
+            eprintln!("Warning: could not set the thread count: {}", e);
+        }
+        #[cfg(not(feature = "parallel"))]
+        if threads > 1
+        {
+            eprintln!("Warning: built without the `parallel` feature; running single-threaded");
+        }
+    }
+}
 
-            if !path.exists()
-            {
-                eprintln!("Error: File not found: {:?}", path);
-                has_errors = true;
-                continue;
-            }
+fn main() -> Result<(), Box<dyn std::error::Error>>
+{
+    let args: Vec<String> = std::env::args().collect();
 
-            if !is_lossless_audio_file(&path)
-            {
-                eprintln!("Error: Unsupported file type: {:?}", path);
-                eprintln!("Supported formats: WAV, FLAC");
-                has_errors = true;
-                continue;
-            }
+    // Check if we have command-line arguments (skip program name)
+    if args.len() > 1
+    {
+        let mut cli = Cli::parse_from(cli::normalize_legacy_args(args));
 
-            match encode_file(path)
+        let config = match config::Config::load_or_default(cli.config.as_deref())
+        {
+            Ok(config) => config,
+            Err(e) =>
             {
-                Ok(()) => {},
-                Err(e) =>
-                    {
-                        eprintln!("Error encoding file: {}", e);
-                        has_errors = true;
-                    }
+                eprintln!("Error: config file {}", e);
+                std::process::exit(1);
             }
-        }
+        };
+        apply_config(&mut cli, &config);
 
-        if has_errors
+        let ok = match cli.command
+        {
+            cli::Command::Encode(args) => run_encode(args),
+            cli::Command::Decode(args) => run_decode(args),
+            cli::Command::Play(args) => run_play(args),
+            cli::Command::Serve(args) => run_serve(args),
+            cli::Command::ServeHttp(args) => run_serve_http(args),
+            cli::Command::Broadcast(args) => run_broadcast(args),
+            cli::Command::Record(args) => run_record(args),
+            cli::Command::Info(args) => run_info(args),
+            cli::Command::Tag(args) => run_tag(args),
+            cli::Command::Compare(args) => run_compare(args),
+            cli::Command::Analyze(args) => run_analyze(args),
+            cli::Command::Album(args) => run_album(args),
+            cli::Command::Split(args) => run_split(args),
+            cli::Command::Chain(args) => run_chain(args),
+            cli::Command::Trim(args) => run_trim(args),
+            cli::Command::Gain(args) => run_gain(args),
+            cli::Command::GainScan(args) => run_gain_scan(args),
+            cli::Command::Repair(args) => run_repair(args),
+            cli::Command::Shootout(args) => run_shootout(args),
+        };
+
+        if !ok
         {
             std::process::exit(1);
         }
@@ -589,9 +2685,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>>
         {
             let options = eframe::NativeOptions
             {
-                viewport: egui::ViewportBuilder::default()
-                    .with_inner_size([900.0, 700.0])
-                    .with_title("Gapless Lossy Codec"),
+                viewport: ui::viewport(),
                 ..Default::default()
             };
 
@@ -604,7 +2698,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>>
 
         #[cfg(not(feature = "ui"))]
         {
-            print_usage();
+            use clap::CommandFactory;
+
+            Cli::command().print_help()?;
             std::process::exit(1);
         }
 
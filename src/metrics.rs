@@ -0,0 +1,244 @@
+//! Perceptually grouped error measurements between an original signal and its decoded version
+//!
+//! [`measure`] splits both signals into Hann-windowed FFT frames per channel and compares them
+//! bin by bin: SNR within each critical band of hearing, an overall SNR with the bins
+//! A-weighted by how loud the ear finds them, and the log-spectral distance between the two.
+//! It also grades the decoded signal the way PEAQ does, much simplified: the error in each
+//! critical band is set against what the original masks there (spread across neighbouring bands,
+//! and no lower than the threshold of hearing), and the resulting noise-to-mask ratio is mapped
+//! onto PEAQ's objective difference grade, from 0 (no audible difference) to -4 (very annoying).
+//! [`snr_db`] is the plain sample-by-sample SNR the rest of the crate and its tests report.
+use serde::Serialize;
+use crate::spectrogram::{fft, hann_window, level_db, SPECTROGRAM_FFT_SIZE};
+
+/// Edges of the critical bands (Zwicker's Bark scale) in Hz, the last closing at the top of
+/// hearing; bands above Nyquist are left out, and one straddling it is cut short there
+pub const CRITICAL_BAND_EDGES_HZ: &[f32] = &[
+    0.0, 100.0, 200.0, 300.0, 400.0, 510.0, 630.0, 770.0, 920.0, 1080.0, 1270.0, 1480.0, 1720.0, 2000.0, 2320.0,
+    2700.0, 3150.0, 3700.0, 4400.0, 5300.0, 6400.0, 7700.0, 9500.0, 12000.0, 15500.0, 20000.0,
+];
+
+/// How far masking spreads from a critical band to the bands above it, and to those below, in
+/// dB per band (bands are about one Bark wide)
+const SPREAD_UP_DB: f64 = 10.0;
+const SPREAD_DOWN_DB: f64 = 27.0;
+
+/// Level of a full-scale sine, in dB SPL, for placing the threshold of hearing (as PEAQ assumes)
+const FULL_SCALE_SPL: f64 = 92.0;
+
+/// Noise-to-mask ratio, in dB, above which a frame counts as audibly disturbed (PEAQ's 1.5 dB)
+const DISTURBED_NMR_DB: f64 = 1.5;
+
+/// Noise-to-mask ratio at which the grade is halfway between its best and worst, and how many
+/// dB it takes to move most of the way from one to the other
+const GRADE_MIDPOINT_NMR_DB: f64 = -2.0;
+const GRADE_SPREAD_DB: f64 = 3.0;
+
+/// SNR within one critical band
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BandMetric
+{
+    pub low_hz: f32,
+    pub high_hz: f32,
+    pub snr_db: f64,
+}
+
+/// Error between an original and a decoded signal, by band and weighted for hearing
+/// SNRs are infinite when there is no error (serialized to JSON as null)
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Metrics
+{
+    pub bands: Vec<BandMetric>,
+    /// SNR of all audible bins, each weighted by the A-weighting curve
+    pub weighted_snr_db: f64,
+    /// Log-spectral distance: the RMS difference between the two spectra in dB, averaged over
+    /// the frames where the original isn't silent
+    pub spectral_distortion_db: f64,
+    /// Noise-to-mask ratio over all frames with sound in them; negative when the error is masked
+    pub noise_to_mask_db: f64,
+    /// Share of those frames where the error rises above the mask in some band
+    pub disturbed_frames: f64,
+    /// Objective difference grade: 0 for no audible difference, down to -4 for very annoying
+    pub odg: f64,
+}
+
+/// Measure interleaved `decoded` against `original` over the part both cover
+pub fn measure(original: &[f32], decoded: &[f32], sample_rate: u32, channels: u16) -> Metrics
+{
+    let channels = channels.max(1) as usize;
+    let frames = original.len().min(decoded.len()) / channels;
+    let bin_hz = sample_rate as f32 / SPECTROGRAM_FFT_SIZE as f32;
+    let nyquist = sample_rate as f32 / 2.0;
+    let band_edges: Vec<(f32, f32)> = CRITICAL_BAND_EDGES_HZ.windows(2)
+        .filter(|edges| edges[0] < nyquist)
+        .map(|edges| (edges[0], edges[1].min(nyquist)))
+        .collect();
+    let top = *CRITICAL_BAND_EDGES_HZ.last().unwrap_or(&0.0);
+    // Bins from the first above DC up to the top of hearing or Nyquist, whichever is lower
+    let audible = 1..((top / bin_hz).ceil() as usize).min(SPECTROGRAM_FFT_SIZE / 2);
+    let band_of: Vec<Option<usize>> = (0..SPECTROGRAM_FFT_SIZE / 2).map(|bin|
+    {
+        let frequency = bin as f32 * bin_hz;
+        audible.contains(&bin).then(|| band_edges.iter().position(|&(low, high)| (low..high).contains(&frequency))).flatten()
+    }).collect();
+    let (window, reference) = hann_window();
+    // The quietest noise heard in each band, in the same units as the bins' powers
+    let threshold: Vec<f64> = band_edges.iter()
+        .map(|&(low, high)| reference * 10f64.powf((threshold_in_quiet_spl((low + high) as f64 / 2.0) - FULL_SCALE_SPL) / 10.0))
+        .collect();
+
+    let mut signal = vec![0.0f64; SPECTROGRAM_FFT_SIZE / 2];
+    let mut noise = vec![0.0f64; SPECTROGRAM_FFT_SIZE / 2];
+    let (mut distortion, mut distortion_frames) = (0.0f64, 0usize);
+    let (mut noise_to_mask, mut disturbed, mut graded_frames) = (0.0f64, 0usize, 0usize);
+    let (mut re, mut im) = (vec![0.0; SPECTROGRAM_FFT_SIZE], vec![0.0; SPECTROGRAM_FFT_SIZE]);
+    let (mut error_re, mut error_im) = (vec![0.0; SPECTROGRAM_FFT_SIZE], vec![0.0; SPECTROGRAM_FFT_SIZE]);
+    let (mut band_signal, mut band_noise) = (vec![0.0f64; band_edges.len()], vec![0.0f64; band_edges.len()]);
+    for channel in 0..channels
+    {
+        // Half-overlapped frames, the last zero-padded
+        let mut start = 0;
+        while start < frames
+        {
+            for i in 0..SPECTROGRAM_FFT_SIZE
+            {
+                let index = (start + i < frames).then_some((start + i) * channels + channel);
+                let a = index.map_or(0.0, |index| original[index] as f64);
+                let b = index.map_or(0.0, |index| decoded[index] as f64);
+                (re[i], im[i]) = (a * window[i], 0.0);
+                (error_re[i], error_im[i]) = ((a - b) * window[i], 0.0);
+            }
+            fft(&mut re, &mut im);
+            fft(&mut error_re, &mut error_im);
+
+            let mut squared_difference = 0.0f64;
+            let mut original_power = 0.0f64;
+            band_signal.fill(0.0);
+            band_noise.fill(0.0);
+            for bin in audible.clone()
+            {
+                let power = re[bin] * re[bin] + im[bin] * im[bin];
+                let error = error_re[bin] * error_re[bin] + error_im[bin] * error_im[bin];
+                // The decoded spectrum is the original's less the error's
+                let (decoded_re, decoded_im) = (re[bin] - error_re[bin], im[bin] - error_im[bin]);
+                let decoded_power = decoded_re * decoded_re + decoded_im * decoded_im;
+                signal[bin] += power;
+                noise[bin] += error;
+                if let Some(band) = band_of[bin]
+                {
+                    band_signal[band] += power;
+                    band_noise[band] += error;
+                }
+                original_power += power;
+                let difference = (level_db(power / reference) - level_db(decoded_power / reference)) as f64;
+                squared_difference += difference * difference;
+            }
+            if original_power > 0.0 && !audible.is_empty()
+            {
+                distortion += (squared_difference / audible.len() as f64).sqrt();
+                distortion_frames += 1;
+            }
+            if (original_power > 0.0 || band_noise.iter().any(|&noise| noise > 0.0)) && !band_edges.is_empty()
+            {
+                let ratios: Vec<f64> = mask(&band_signal, &threshold).iter().zip(&band_noise).map(|(mask, noise)| noise / mask).collect();
+                noise_to_mask += ratios.iter().sum::<f64>() / ratios.len() as f64;
+                let loudest = ratios.iter().copied().fold(0.0, f64::max);
+                disturbed += (10.0 * loudest.log10() > DISTURBED_NMR_DB) as usize;
+                graded_frames += 1;
+            }
+            start += SPECTROGRAM_FFT_SIZE / 2;
+        }
+    }
+
+    let bands = band_edges.iter().enumerate().map(|(band, &(low, high))|
+    {
+        let in_band = audible.clone().filter(|&bin| band_of[bin] == Some(band));
+        let (signal, noise) = in_band.fold((0.0, 0.0), |(s, n), bin| (s + signal[bin], n + noise[bin]));
+        BandMetric { low_hz: low, high_hz: high, snr_db: power_ratio_db(signal, noise) }
+    }).collect();
+
+    let (weighted_signal, weighted_noise) = audible.fold((0.0, 0.0), |(s, n), bin|
+    {
+        let weight = a_weighting(bin as f64 * bin_hz as f64);
+        (s + signal[bin] * weight, n + noise[bin] * weight)
+    });
+
+    let noise_to_mask_db = 10.0 * (noise_to_mask / graded_frames.max(1) as f64).log10();
+    Metrics
+    {
+        bands,
+        weighted_snr_db: power_ratio_db(weighted_signal, weighted_noise),
+        spectral_distortion_db: if distortion_frames > 0 { distortion / distortion_frames as f64 } else { 0.0 },
+        noise_to_mask_db,
+        disturbed_frames: disturbed as f64 / graded_frames.max(1) as f64,
+        odg: difference_grade(noise_to_mask_db),
+    }
+}
+
+/// The level below which each critical band's noise goes unheard, given the original's power
+/// in each band: the original's masking spread across bands and lowered by PEAQ's offset, or the
+/// threshold of hearing where that is higher
+fn mask(band_signal: &[f64], threshold: &[f64]) -> Vec<f64>
+{
+    (0..band_signal.len()).map(|band|
+    {
+        let spread: f64 = band_signal.iter().enumerate().map(|(masker, &power)|
+        {
+            let distance = band.abs_diff(masker) as f64;
+            let slope = if masker < band { SPREAD_UP_DB } else { SPREAD_DOWN_DB };
+            power * 10f64.powf(-slope * distance / 10.0)
+        }).sum();
+        // PEAQ's masking offset: 3 dB up to 12 Bark, rising a quarter of a dB per Bark above
+        let offset_db = if band < 12 { 3.0 } else { 0.25 * band as f64 };
+        (spread * 10f64.powf(-offset_db / 10.0)).max(threshold[band])
+    }).collect()
+}
+
+/// Threshold of hearing at `frequency` Hz in dB SPL (Terhardt's approximation)
+fn threshold_in_quiet_spl(frequency: f64) -> f64
+{
+    let khz = (frequency / 1000.0).max(0.02);
+    3.64 * khz.powf(-0.8) - 6.5 * (-0.6 * (khz - 3.3).powi(2)).exp() + 1e-3 * khz.powi(4)
+}
+
+/// PEAQ's objective difference grade for a noise-to-mask ratio in dB, a logistic curve from 0
+/// (noise well under the mask) to -4 (noise well over it)
+pub fn difference_grade(noise_to_mask_db: f64) -> f64
+{
+    -4.0 + 4.0 / (1.0 + ((noise_to_mask_db - GRADE_MIDPOINT_NMR_DB) / GRADE_SPREAD_DB).exp())
+}
+
+/// Sample-by-sample SNR of `decoded` against `original` in dB, over the samples both have;
+/// infinite when they match
+pub fn snr_db(original: &[f32], decoded: &[f32]) -> f64
+{
+    let (signal, noise) = original.iter().zip(decoded).fold((0.0f64, 0.0f64), |(signal, noise), (&a, &b)|
+    {
+        let error = (a - b) as f64;
+        (signal + a as f64 * a as f64, noise + error * error)
+    });
+    power_ratio_db(signal, noise)
+}
+
+/// Ratio of a signal power to a noise power in dB; infinite with no noise
+pub fn power_ratio_db(signal: f64, noise: f64) -> f64
+{
+    if noise == 0.0
+    {
+        f64::INFINITY
+    }
+    else
+    {
+        10.0 * (signal / noise).log10()
+    }
+}
+
+/// Power gain of the A-weighting curve at `frequency` Hz (IEC 61672), unnormalized since only
+/// ratios of weighted powers are taken
+fn a_weighting(frequency: f64) -> f64
+{
+    let f2 = frequency * frequency;
+    let gain = 12194.0f64.powi(2) * f2 * f2
+        / ((f2 + 20.6f64.powi(2)) * ((f2 + 107.7f64.powi(2)) * (f2 + 737.9f64.powi(2))).sqrt() * (f2 + 12194.0f64.powi(2)));
+    gain * gain
+}
@@ -0,0 +1,100 @@
+//! Volume and soft limiting applied to decoded samples on their way to the output
+//!
+//! MDCT ringing can push decoded samples past ±1.0, which the output would clip hard; the
+//! limiter rounds those peaks off instead, which also leaves room to raise the volume past 100%.
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+/// Highest volume [`Mixer::set_volume`] accepts, as a linear gain
+pub const MAX_VOLUME: f32 = 2.0;
+
+/// Level above which [`soft_limit`] starts bending samples toward ±1.0
+pub const LIMITER_THRESHOLD: f32 = 0.9;
+
+/// Pass `sample` through unchanged up to [`LIMITER_THRESHOLD`], and bend anything louder smoothly
+/// toward ±1.0 (a tanh curve matching the slope at the threshold), so the result never exceeds full scale
+pub fn soft_limit(sample: f32) -> f32
+{
+    let level = sample.abs();
+    if level <= LIMITER_THRESHOLD
+    {
+        return sample;
+    }
+
+    let headroom = 1.0 - LIMITER_THRESHOLD;
+    (LIMITER_THRESHOLD + headroom * ((level - LIMITER_THRESHOLD) / headroom).tanh()).copysign(sample)
+}
+
+/// A gain stage followed by an optional [`soft_limit`]
+///
+/// Both settings are atomics, so another thread can change them while the audio thread is
+/// running samples through [`Mixer::apply`].
+#[derive(Debug)]
+pub struct Mixer
+{
+    /// Bits of the linear gain as an f32
+    volume: AtomicU32,
+    limiter: AtomicBool,
+}
+
+impl Default for Mixer
+{
+    /// Full volume with the limiter on
+    fn default() -> Self
+    {
+        Self::new(1.0, true)
+    }
+}
+
+impl Mixer
+{
+    pub fn new(volume: f32, limiter: bool) -> Self
+    {
+        let mixer = Self { volume: AtomicU32::new(0), limiter: AtomicBool::new(limiter) };
+        mixer.set_volume(volume);
+        mixer
+    }
+
+    /// Linear gain, 1.0 being unchanged
+    pub fn volume(&self) -> f32
+    {
+        f32::from_bits(self.volume.load(Ordering::Relaxed))
+    }
+
+    /// Set the linear gain, clamped to 0.0..=[`MAX_VOLUME`]
+    pub fn set_volume(&self, volume: f32)
+    {
+        let volume = if volume.is_nan() { 1.0 } else { volume.clamp(0.0, MAX_VOLUME) };
+        self.volume.store(volume.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn limiter(&self) -> bool
+    {
+        self.limiter.load(Ordering::Relaxed)
+    }
+
+    pub fn set_limiter(&self, enabled: bool)
+    {
+        self.limiter.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Scale one sample by the volume, then limit it if the limiter is on
+    pub fn apply(&self, sample: f32) -> f32
+    {
+        let sample = sample * self.volume();
+        if self.limiter() { soft_limit(sample) } else { sample }
+    }
+
+    /// [`Mixer::apply`] to every sample of a block in place
+    pub fn process(&self, samples: &mut [f32])
+    {
+        let (volume, limiter) = (self.volume(), self.limiter());
+        for sample in samples
+        {
+            *sample *= volume;
+            if limiter
+            {
+                *sample = soft_limit(*sample);
+            }
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! MP3 export of decoded audio through LAME, for players and devices that only take MP3
+//!
+//! LAME writes its Xing/LAME header, so players that read it trim the encoder delay and padding.
+use anyhow::{anyhow, Result};
+use mp3lame_encoder::{max_required_buffer_size, Bitrate, Builder, DualPcm, FlushNoGap, Quality, VbrMode};
+use std::borrow::Cow;
+use std::io::Write;
+use std::path::Path;
+use crate::audio::{resample, Mp3Mode};
+
+/// Sample rates MP3 supports; anything else is resampled first
+const MP3_RATES: [u32; 9] = [8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000];
+
+/// Frames handed to LAME per call
+const CHUNK_FRAMES: usize = 8192;
+
+/// Encode interleaved `samples` to an MP3 stream
+/// Only mono and stereo are supported
+pub fn encode_mp3(samples: &[f32], sample_rate: u32, channels: u16, mode: Mp3Mode) -> Result<Vec<u8>>
+{
+    if !(1..=2).contains(&channels)
+    {
+        return Err(anyhow!("MP3 export supports mono and stereo, not {} channels", channels));
+    }
+    let ch = channels as usize;
+
+    // Resample to the closest rate in the same family (44.1 kHz multiples stay in it)
+    let rate = if MP3_RATES.contains(&sample_rate)
+    {
+        sample_rate
+    }
+    else if sample_rate % 11025 == 0
+    {
+        44100
+    }
+    else
+    {
+        48000
+    };
+    let samples = if rate == sample_rate
+    {
+        Cow::Borrowed(samples)
+    }
+    else
+    {
+        Cow::Owned(resample(samples, channels, sample_rate, rate))
+    };
+
+    let lame_error = |e| anyhow!("LAME: {:?}", e);
+    let mut builder = Builder::new().ok_or_else(|| anyhow!("LAME: failed to allocate an encoder"))?;
+    builder.set_num_channels(channels as u8).map_err(lame_error)?;
+    builder.set_sample_rate(rate).map_err(lame_error)?;
+    builder.set_quality(Quality::Best).map_err(lame_error)?;
+    match mode
+    {
+        Mp3Mode::Cbr(kbps) => builder.set_brate(bitrate(kbps)?).map_err(lame_error)?,
+        Mp3Mode::Vbr(quality) =>
+        {
+            builder.set_vbr_mode(VbrMode::Mtrh).map_err(lame_error)?;
+            builder.set_vbr_quality(vbr_quality(quality)?).map_err(lame_error)?;
+        }
+    }
+    let mut encoder = builder.build().map_err(lame_error)?;
+
+    let mut out = Vec::new();
+    let mut left = Vec::with_capacity(CHUNK_FRAMES);
+    let mut right = Vec::with_capacity(CHUNK_FRAMES);
+    for chunk in samples.chunks(CHUNK_FRAMES * ch)
+    {
+        left.clear();
+        right.clear();
+        for frame in chunk.chunks_exact(ch)
+        {
+            // For mono, LAME only reads the left channel
+            left.push(to_i16(frame[0]));
+            right.push(to_i16(frame[ch - 1]));
+        }
+
+        out.reserve(max_required_buffer_size(left.len()));
+        let written = encoder.encode(DualPcm { left: &left, right: &right }, out.spare_capacity_mut())
+                             .map_err(|e| anyhow!("LAME: {:?}", e))?;
+        // SAFETY: LAME initialized the first `written` bytes of the spare capacity
+        unsafe { out.set_len(out.len() + written) };
+    }
+
+    out.reserve(max_required_buffer_size(0));
+    let written = encoder.flush::<FlushNoGap>(out.spare_capacity_mut()).map_err(|e| anyhow!("LAME: {:?}", e))?;
+    // SAFETY: as above
+    unsafe { out.set_len(out.len() + written) };
+
+    Ok(out)
+}
+
+/// Export interleaved `samples` to an `.mp3` file (see [`encode_mp3`])
+pub fn export_to_mp3(path: &Path, samples: &[f32], sample_rate: u32, channels: u16, mode: Mp3Mode) -> Result<()>
+{
+    let data = encode_mp3(samples, sample_rate, channels, mode)?;
+    let mut file = std::fs::File::create(path)?;
+    file.write_all(&data)?;
+    Ok(())
+}
+
+/// Same conversion as the WAV and FLAC exporters
+fn to_i16(sample: f32) -> i16
+{
+    (sample * 32767.0).clamp(-32768.0, 32767.0) as i16
+}
+
+fn bitrate(kbps: u32) -> Result<Bitrate>
+{
+    Ok(match kbps
+    {
+        32 => Bitrate::Kbps32,
+        40 => Bitrate::Kbps40,
+        48 => Bitrate::Kbps48,
+        64 => Bitrate::Kbps64,
+        80 => Bitrate::Kbps80,
+        96 => Bitrate::Kbps96,
+        112 => Bitrate::Kbps112,
+        128 => Bitrate::Kbps128,
+        160 => Bitrate::Kbps160,
+        192 => Bitrate::Kbps192,
+        224 => Bitrate::Kbps224,
+        256 => Bitrate::Kbps256,
+        320 => Bitrate::Kbps320,
+        _ => return Err(anyhow!("Unsupported MP3 bitrate: {} kbit/s", kbps)),
+    })
+}
+
+fn vbr_quality(quality: u8) -> Result<Quality>
+{
+    Ok(match quality
+    {
+        0 => Quality::Best,
+        1 => Quality::SecondBest,
+        2 => Quality::NearBest,
+        3 => Quality::VeryNice,
+        4 => Quality::Nice,
+        5 => Quality::Good,
+        6 => Quality::Decent,
+        7 => Quality::Ok,
+        8 => Quality::SecondWorst,
+        9 => Quality::Worst,
+        _ => return Err(anyhow!("Unsupported VBR quality: V{}", quality)),
+    })
+}
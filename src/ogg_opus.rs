@@ -0,0 +1,123 @@
+//! Ogg Opus export (RFC 7845) of decoded audio, for players and devices that don't take FLAC
+//!
+//! Opus always runs at 48 kHz, so other sample rates are resampled first; the original rate is
+//! recorded in the header for players that want to convert back.
+use anyhow::{anyhow, Result};
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+use opus::{Application, Bitrate, Channels};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use crate::audio::resample;
+
+/// Opus works at 48 kHz; granule positions count 48 kHz samples
+const OPUS_RATE: u32 = 48000;
+
+/// Samples per channel in each packet (20 ms)
+const PACKET_SAMPLES: usize = 960;
+
+/// Packets per Ogg page (about one second)
+const PACKETS_PER_PAGE: u64 = 50;
+
+/// Largest packet the encoder may produce (the buffer size recommended by libopus)
+const MAX_PACKET_SIZE: usize = 4000;
+
+/// Stream serial number; there is only one logical stream per file
+const SERIAL: u32 = 1;
+
+/// Encode interleaved `samples` to an Ogg Opus stream on `writer` at `bitrate_kbps`
+/// Only mono and stereo are supported
+pub fn encode_ogg_opus<W: Write>(writer: W, samples: &[f32], sample_rate: u32, channels: u16, bitrate_kbps: u32)
+    -> Result<W>
+{
+    let opus_channels = match channels
+    {
+        1 => Channels::Mono,
+        2 => Channels::Stereo,
+        _ => return Err(anyhow!("Opus export supports mono and stereo, not {} channels", channels)),
+    };
+    let ch = channels as usize;
+    let samples = if sample_rate == OPUS_RATE
+    {
+        Cow::Borrowed(samples)
+    }
+    else
+    {
+        Cow::Owned(resample(samples, channels, sample_rate, OPUS_RATE))
+    };
+
+    let mut encoder = opus::Encoder::new(OPUS_RATE, opus_channels, Application::Audio)?;
+    let bitrate = i32::try_from(bitrate_kbps.saturating_mul(1000)).unwrap_or(i32::MAX);
+    encoder.set_bitrate(Bitrate::Bits(bitrate))?;
+    let pre_skip = encoder.get_lookahead()?.max(0) as u64;
+
+    let mut packets = PacketWriter::new(writer);
+    packets.write_packet(opus_head(channels, pre_skip as u16, sample_rate), SERIAL, PacketWriteEndInfo::EndPage, 0)?;
+    packets.write_packet(opus_tags(), SERIAL, PacketWriteEndInfo::EndPage, 0)?;
+
+    // The encoder's output lags its input by `pre_skip`, so encode that much extra silence to flush it
+    let length = (samples.len() / ch) as u64;
+    let packet_count = (length + pre_skip).div_ceil(PACKET_SAMPLES as u64).max(1);
+    let mut block = vec![0.0f32; PACKET_SAMPLES * ch];
+    let mut packet = vec![0u8; MAX_PACKET_SIZE];
+
+    for index in 0..packet_count
+    {
+        let start = (index as usize * PACKET_SAMPLES * ch).min(samples.len());
+        let available = (samples.len() - start).min(block.len());
+        block[..available].copy_from_slice(&samples[start..start + available]);
+        block[available..].fill(0.0);
+        let len = encoder.encode_float(&block, &mut packet)?;
+
+        // The final granule position marks where the audio ends, so players drop the padding
+        let last = index + 1 == packet_count;
+        let (granule, end) = if last
+        {
+            (pre_skip + length, PacketWriteEndInfo::EndStream)
+        }
+        else if (index + 1) % PACKETS_PER_PAGE == 0
+        {
+            ((index + 1) * PACKET_SAMPLES as u64, PacketWriteEndInfo::EndPage)
+        }
+        else
+        {
+            ((index + 1) * PACKET_SAMPLES as u64, PacketWriteEndInfo::NormalPacket)
+        };
+        packets.write_packet(packet[..len].to_vec(), SERIAL, end, granule)?;
+    }
+
+    Ok(packets.into_inner())
+}
+
+/// Export interleaved `samples` to an `.opus` file at `bitrate_kbps` (see [`encode_ogg_opus`])
+pub fn export_to_opus(path: &Path, samples: &[f32], sample_rate: u32, channels: u16, bitrate_kbps: u32) -> Result<()>
+{
+    let writer = BufWriter::new(File::create(path)?);
+    encode_ogg_opus(writer, samples, sample_rate, channels, bitrate_kbps)?.flush()?;
+    Ok(())
+}
+
+/// Identification header: version 1, channel mapping family 0 (mono/stereo), no output gain
+fn opus_head(channels: u16, pre_skip: u16, input_rate: u32) -> Vec<u8>
+{
+    let mut head = b"OpusHead".to_vec();
+    head.push(1);
+    head.push(channels as u8);
+    head.extend_from_slice(&pre_skip.to_le_bytes());
+    head.extend_from_slice(&input_rate.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes());
+    head.push(0);
+    head
+}
+
+/// Comment header with the vendor string and no comments
+fn opus_tags() -> Vec<u8>
+{
+    let vendor = concat!("gapless-lossy-codec ", env!("CARGO_PKG_VERSION"));
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor.as_bytes());
+    tags.extend_from_slice(&0u32.to_le_bytes());
+    tags
+}
@@ -0,0 +1,683 @@
+//! Readers for uncompressed PCM in WAV (including WAVE_FORMAT_EXTENSIBLE and RF64/BW64),
+//! AIFF/AIFF-C, and CAF files
+//!
+//! Only the stream description and a few text chunks are parsed up front; sample data is
+//! read block by block through [`PcmReader::read_block`].
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use crate::container::Tags;
+
+/// How samples are laid out in the data chunk
+#[derive(Clone, Copy, Debug)]
+struct SampleEncoding
+{
+    /// Bytes per sample in the file
+    width: usize,
+    float: bool,
+    big_endian: bool,
+    /// Unsigned integers are offset by half their range (8-bit CAF only)
+    signed: bool,
+    /// Divisor mapping integer samples to [-1, 1)
+    scale: f32,
+}
+
+/// Streaming reader for PCM sample data in a WAV, AIFF/AIFF-C, or CAF file
+pub struct PcmReader
+{
+    reader: BufReader<File>,
+    encoding: SampleEncoding,
+    sample_rate: u32,
+    channels: u16,
+    /// WAVE_FORMAT_EXTENSIBLE speaker mask (0 = unspecified)
+    channel_mask: u32,
+    tags: Tags,
+    /// Length of the sample data in frames
+    frames: u64,
+    /// Bytes of sample data not read yet
+    remaining: u64,
+    bytes: Vec<u8>,
+}
+
+impl PcmReader
+{
+    /// Open a RIFF WAV, RF64, or BW64 file holding integer or float PCM
+    /// WAVE_FORMAT_EXTENSIBLE headers are accepted and their channel mask is kept
+    pub fn open_wav(path: &Path) -> Result<Self>
+    {
+        const FORMAT_PCM: u16 = 1;
+        const FORMAT_IEEE_FLOAT: u16 = 3;
+        const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+        let mut reader = BufReader::new(File::open(path)?);
+        let file_len = reader.get_ref().metadata()?.len();
+
+        let mut id = [0u8; 4];
+        reader.read_exact(&mut id)?;
+        if id != *b"RIFF" && id != *b"RF64" && id != *b"BW64"
+        {
+            return Err(anyhow!("Not a WAV file (missing RIFF chunk)"));
+        }
+        let _riff_size = read_u32_le(&mut reader)?;
+        reader.read_exact(&mut id)?;
+        if id != *b"WAVE"
+        {
+            return Err(anyhow!("Not a WAV file (form type {:?})", String::from_utf8_lossy(&id)));
+        }
+
+        // (encoding, sample rate, channels, channel mask) from fmt; (start, length) from data
+        let mut fmt: Option<(SampleEncoding, u32, u16, u32)> = None;
+        let mut data: Option<(u64, u64)> = None;
+        // RF64 keeps the real data size in ds64 and writes 0xFFFFFFFF in the data chunk header
+        let mut ds64_data_size: Option<u64> = None;
+        let mut tags = Tags::default();
+
+        while let Some((chunk_id, size)) = read_chunk_header_le32(&mut reader)?
+        {
+            let start = reader.stream_position()?;
+            let mut size = size;
+
+            match &chunk_id
+            {
+                b"ds64" =>
+                {
+                    let _riff_size = read_u64_le(&mut reader)?;
+                    ds64_data_size = Some(read_u64_le(&mut reader)?);
+                }
+                b"fmt " =>
+                {
+                    let mut format_tag = read_u16_le(&mut reader)?;
+                    let channels = read_u16_le(&mut reader)?;
+                    let sample_rate = read_u32_le(&mut reader)?;
+                    let _byte_rate = read_u32_le(&mut reader)?;
+                    let block_align = read_u16_le(&mut reader)?;
+                    let _bits = read_u16_le(&mut reader)?;
+
+                    let mut channel_mask = 0;
+                    if format_tag == FORMAT_EXTENSIBLE
+                    {
+                        if size < 40
+                        {
+                            return Err(anyhow!("WAVE_FORMAT_EXTENSIBLE header is truncated"));
+                        }
+                        let _extension_size = read_u16_le(&mut reader)?;
+                        let _valid_bits = read_u16_le(&mut reader)?;
+                        channel_mask = read_u32_le(&mut reader)?;
+                        // The sub-format GUID starts with the plain format tag
+                        format_tag = read_u16_le(&mut reader)?;
+                    }
+
+                    if channels == 0
+                    {
+                        return Err(anyhow!("WAV file has no channels"));
+                    }
+                    // Samples are left-justified in their container, so scale by the container width
+                    let width = (block_align / channels) as usize;
+                    let encoding = match (format_tag, width)
+                    {
+                        (FORMAT_PCM, 1..=4) => SampleEncoding
+                        {
+                            width,
+                            float: false,
+                            big_endian: false,
+                            signed: width > 1,
+                            scale: (1u64 << (width * 8 - 1)) as f32,
+                        },
+                        (FORMAT_IEEE_FLOAT, 4 | 8) => SampleEncoding { width, float: true, big_endian: false, signed: true, scale: 1.0 },
+                        _ => return Err(anyhow!("Unsupported WAV sample format (tag {:#06x}, {} bytes per sample)", format_tag, width)),
+                    };
+                    fmt = Some((encoding, sample_rate, channels, channel_mask));
+                }
+                b"data" =>
+                {
+                    if size == u32::MAX as u64
+                    {
+                        size = ds64_data_size.unwrap_or(file_len.saturating_sub(start));
+                    }
+                    // Tolerate files whose header promises more data than was written
+                    size = size.min(file_len.saturating_sub(start));
+                    data = Some((start, size));
+                }
+                // Skip oversized lists (e.g. large `adtl` cue data) rather than failing
+                b"LIST" if size <= MAX_TEXT_CHUNK =>
+                {
+                    let list = read_bytes(&mut reader, size)?;
+                    read_riff_info(&list, &mut tags);
+                }
+                _ => {}
+            }
+            // Chunks are padded to an even length
+            reader.seek(SeekFrom::Start(start + size + (size & 1)))?;
+        }
+
+        let (encoding, sample_rate, channels, channel_mask) = fmt.ok_or_else(|| anyhow!("WAV file has no fmt chunk"))?;
+        let (data_start, data_len) = data.ok_or_else(|| anyhow!("WAV file has no data chunk"))?;
+
+        let mut reader = Self::new(reader, encoding, sample_rate, channels, tags, data_start, data_len)?;
+        reader.channel_mask = channel_mask;
+        Ok(reader)
+    }
+
+    /// Open an AIFF or AIFF-C file
+    /// AIFF-C files must be uncompressed (`NONE`, `twos`, `sowt`, `in24`, `in32`, `fl32`, `fl64`)
+    pub fn open_aiff(path: &Path) -> Result<Self>
+    {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut id = [0u8; 4];
+        reader.read_exact(&mut id)?;
+        if id != *b"FORM"
+        {
+            return Err(anyhow!("Not an AIFF file (missing FORM chunk)"));
+        }
+        let _form_size = read_u32_be(&mut reader)?;
+        reader.read_exact(&mut id)?;
+        let is_aifc = match &id
+        {
+            b"AIFF" => false,
+            b"AIFC" => true,
+            _ => return Err(anyhow!("Not an AIFF file (form type {:?})", String::from_utf8_lossy(&id))),
+        };
+
+        // (channels, frames, encoding, sample rate) from COMM; (start, length) from SSND
+        let mut comm: Option<(u16, u64, SampleEncoding, u32)> = None;
+        let mut ssnd: Option<(u64, u64)> = None;
+        let mut tags = Tags::default();
+
+        while let Some((chunk_id, size)) = read_chunk_header_be32(&mut reader)?
+        {
+            let start = reader.stream_position()?;
+            match &chunk_id
+            {
+                b"COMM" =>
+                {
+                    let channels = read_u16_be(&mut reader)?;
+                    let frames = read_u32_be(&mut reader)? as u64;
+                    let bits = read_u16_be(&mut reader)?;
+                    let mut rate = [0u8; 10];
+                    reader.read_exact(&mut rate)?;
+                    let compression = if is_aifc
+                    {
+                        let mut compression = [0u8; 4];
+                        reader.read_exact(&mut compression)?;
+                        compression
+                    }
+                    else
+                    {
+                        *b"NONE"
+                    };
+                    let encoding = aiff_encoding(&compression, bits)?;
+                    comm = Some((channels, frames, encoding, extended_to_f64(&rate).round() as u32));
+                }
+                b"SSND" =>
+                {
+                    let offset = read_u32_be(&mut reader)? as u64;
+                    let _block_size = read_u32_be(&mut reader)?;
+                    let data_start = start + 8 + offset;
+                    ssnd = Some((data_start, size.saturating_sub(8 + offset)));
+                }
+                b"NAME" => tags.add("title", read_text(&mut reader, size)?),
+                b"AUTH" => tags.add("artist", read_text(&mut reader, size)?),
+                b"(c) " => tags.add("copyright", read_text(&mut reader, size)?),
+                b"ANNO" => tags.add("comment", read_text(&mut reader, size)?),
+                _ => {}
+            }
+            // Chunks are padded to an even length
+            reader.seek(SeekFrom::Start(start + size + (size & 1)))?;
+        }
+
+        let (channels, frames, encoding, sample_rate) = comm.ok_or_else(|| anyhow!("AIFF file has no COMM chunk"))?;
+        let (data_start, data_len) = ssnd.ok_or_else(|| anyhow!("AIFF file has no SSND chunk"))?;
+        let declared_len = frames * channels as u64 * encoding.width as u64;
+
+        Self::new(reader, encoding, sample_rate, channels, tags, data_start, data_len.min(declared_len))
+    }
+
+    /// Open a CAF file containing linear PCM (`lpcm`)
+    pub fn open_caf(path: &Path) -> Result<Self>
+    {
+        let mut reader = BufReader::new(File::open(path)?);
+        let file_len = reader.get_ref().metadata()?.len();
+
+        let mut id = [0u8; 4];
+        reader.read_exact(&mut id)?;
+        if id != *b"caff"
+        {
+            return Err(anyhow!("Not a CAF file (bad signature)"));
+        }
+        let _version = read_u16_be(&mut reader)?;
+        let _flags = read_u16_be(&mut reader)?;
+
+        // (encoding, sample rate, channels) from desc; (start, length) from data
+        let mut desc: Option<(SampleEncoding, u32, u16)> = None;
+        let mut data: Option<(u64, u64)> = None;
+        let mut tags = Tags::default();
+
+        loop
+        {
+            let mut chunk_id = [0u8; 4];
+            if !read_exact_or_eof(&mut reader, &mut chunk_id)?
+            {
+                break;
+            }
+            let size = read_u64_be(&mut reader)? as i64;
+            let start = reader.stream_position()?;
+            // Only the data chunk may have an unknown (-1) size, meaning it runs to the end of the file
+            let size = if size < 0 { file_len.saturating_sub(start) } else { size as u64 };
+
+            match &chunk_id
+            {
+                b"desc" =>
+                {
+                    let sample_rate = f64::from_bits(read_u64_be(&mut reader)?);
+                    let mut format_id = [0u8; 4];
+                    reader.read_exact(&mut format_id)?;
+                    let format_flags = read_u32_be(&mut reader)?;
+                    let bytes_per_packet = read_u32_be(&mut reader)?;
+                    let frames_per_packet = read_u32_be(&mut reader)?;
+                    let channels = read_u32_be(&mut reader)?;
+                    let bits = read_u32_be(&mut reader)?;
+
+                    if format_id != *b"lpcm"
+                    {
+                        return Err(anyhow!("Unsupported CAF format {:?} (only linear PCM is supported)",
+                                           String::from_utf8_lossy(&format_id)));
+                    }
+                    if channels == 0 || channels > u16::MAX as u32 || frames_per_packet != 1
+                    {
+                        return Err(anyhow!("Unsupported CAF stream description"));
+                    }
+                    let encoding = caf_encoding(format_flags, bytes_per_packet / channels, bits)?;
+                    desc = Some((encoding, sample_rate.round() as u32, channels as u16));
+                }
+                b"data" =>
+                {
+                    let _edit_count = read_u32_be(&mut reader)?;
+                    data = Some((start + 4, size.saturating_sub(4)));
+                }
+                b"info" =>
+                {
+                    let text = read_bytes(&mut reader, size)?;
+                    read_caf_info(&text, &mut tags);
+                }
+                _ => {}
+            }
+            reader.seek(SeekFrom::Start(start + size))?;
+        }
+
+        let (encoding, sample_rate, channels) = desc.ok_or_else(|| anyhow!("CAF file has no desc chunk"))?;
+        let (data_start, data_len) = data.ok_or_else(|| anyhow!("CAF file has no data chunk"))?;
+
+        Self::new(reader, encoding, sample_rate, channels, tags, data_start, data_len)
+    }
+
+    fn new(
+        mut reader: BufReader<File>,
+        encoding: SampleEncoding,
+        sample_rate: u32,
+        channels: u16,
+        tags: Tags,
+        data_start: u64,
+        data_len: u64,
+    ) -> Result<Self>
+    {
+        if channels == 0 || sample_rate == 0
+        {
+            return Err(anyhow!("Invalid stream format ({} Hz, {} channels)", sample_rate, channels));
+        }
+
+        // Ignore a trailing partial frame
+        let frame_bytes = encoding.width as u64 * channels as u64;
+        let remaining = data_len - data_len % frame_bytes;
+
+        reader.seek(SeekFrom::Start(data_start))?;
+        Ok(Self
+        {
+            reader,
+            encoding,
+            sample_rate,
+            channels,
+            channel_mask: 0,
+            tags,
+            frames: remaining / frame_bytes,
+            remaining,
+            bytes: Vec::new(),
+        })
+    }
+
+    pub fn sample_rate(&self) -> u32
+    {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u16
+    {
+        self.channels
+    }
+
+    /// WAVE_FORMAT_EXTENSIBLE speaker mask, or 0 if the file does not specify one
+    pub fn channel_mask(&self) -> u32
+    {
+        self.channel_mask
+    }
+
+    /// Length of the sample data in frames, from the header
+    pub fn frames(&self) -> u64
+    {
+        self.frames
+    }
+
+    /// Metadata from text chunks (WAV `LIST`/`INFO`, AIFF `NAME`/`AUTH`/`(c) `/`ANNO`, CAF `info`)
+    pub fn tags(&self) -> &Tags
+    {
+        &self.tags
+    }
+
+    /// Append up to `max_frames` interleaved frames to `out`
+    /// Returns false (appending nothing) once the end of the data is reached
+    pub fn read_block(&mut self, out: &mut Vec<f32>, max_frames: usize) -> Result<bool>
+    {
+        let frame_bytes = self.encoding.width * self.channels as usize;
+        let len = self.remaining.min((max_frames * frame_bytes) as u64) as usize;
+        if len == 0
+        {
+            return Ok(false);
+        }
+
+        self.bytes.resize(len, 0);
+        self.reader.read_exact(&mut self.bytes)?;
+        self.remaining -= len as u64;
+
+        let encoding = self.encoding;
+        out.extend(self.bytes.chunks_exact(encoding.width).map(|sample| decode_sample(sample, &encoding)));
+        Ok(true)
+    }
+}
+
+/// Map an AIFF-C compression type (`NONE` for plain AIFF) to a sample encoding
+fn aiff_encoding(compression: &[u8; 4], bits: u16) -> Result<SampleEncoding>
+{
+    let int = |big_endian: bool, bits: u16| -> Result<SampleEncoding>
+    {
+        if bits == 0 || bits > 32
+        {
+            return Err(anyhow!("Unsupported AIFF sample size: {} bits", bits));
+        }
+        // AIFF samples are left-justified in whole bytes, so scale by the container width
+        let width = (bits as usize).div_ceil(8);
+        Ok(SampleEncoding { width, float: false, big_endian, signed: true, scale: (1u64 << (width * 8 - 1)) as f32 })
+    };
+    let float = |width: usize| SampleEncoding { width, float: true, big_endian: true, signed: true, scale: 1.0 };
+
+    match compression
+    {
+        b"NONE" | b"twos" => int(true, bits),
+        b"sowt" => int(false, bits),
+        b"in24" => int(true, 24),
+        b"in32" => int(true, 32),
+        b"fl32" | b"FL32" => Ok(float(4)),
+        b"fl64" | b"FL64" => Ok(float(8)),
+        _ => Err(anyhow!("Unsupported AIFF-C compression type {:?}", String::from_utf8_lossy(compression))),
+    }
+}
+
+/// Map CAF linear PCM format flags to a sample encoding
+fn caf_encoding(format_flags: u32, width: u32, bits: u32) -> Result<SampleEncoding>
+{
+    const IS_FLOAT: u32 = 1 << 0;
+    const IS_LITTLE_ENDIAN: u32 = 1 << 1;
+    const IS_SIGNED_INTEGER: u32 = 1 << 2;
+    const IS_ALIGNED_HIGH: u32 = 1 << 4;
+
+    let width = width as usize;
+    let big_endian = format_flags & IS_LITTLE_ENDIAN == 0;
+
+    if format_flags & IS_FLOAT != 0
+    {
+        if width != 4 && width != 8
+        {
+            return Err(anyhow!("Unsupported CAF float width: {} bytes", width));
+        }
+        return Ok(SampleEncoding { width, float: true, big_endian, signed: true, scale: 1.0 });
+    }
+
+    if bits == 0 || bits > 32 || width == 0 || width > 4 || bits as usize > width * 8
+    {
+        return Err(anyhow!("Unsupported CAF sample size: {} bits in {} bytes", bits, width));
+    }
+    // Samples narrower than their container are low-aligned unless flagged otherwise
+    let significant_bits = if format_flags & IS_ALIGNED_HIGH != 0 { width as u32 * 8 } else { bits };
+
+    Ok(SampleEncoding
+    {
+        width,
+        float: false,
+        big_endian,
+        signed: format_flags & IS_SIGNED_INTEGER != 0 || width > 1,
+        scale: (1u64 << (significant_bits - 1)) as f32,
+    })
+}
+
+/// Convert one stored sample to f32
+fn decode_sample(bytes: &[u8], encoding: &SampleEncoding) -> f32
+{
+    let accumulate = |raw: u64, &byte: &u8| (raw << 8) | byte as u64;
+    let raw = if encoding.big_endian
+    {
+        bytes.iter().fold(0, accumulate)
+    }
+    else
+    {
+        bytes.iter().rev().fold(0, accumulate)
+    };
+
+    if encoding.float
+    {
+        return match encoding.width
+        {
+            4 => f32::from_bits(raw as u32),
+            _ => f64::from_bits(raw) as f32,
+        };
+    }
+
+    let bits = encoding.width as u32 * 8;
+    let value = if encoding.signed
+    {
+        // Sign-extend from the container width
+        ((raw << (64 - bits)) as i64 >> (64 - bits)) as f32
+    }
+    else
+    {
+        raw as f32 - (1u64 << (bits - 1)) as f32
+    };
+    value / encoding.scale
+}
+
+/// Convert an 80-bit IEEE 754 extended precision number (the AIFF sample rate) to f64
+fn extended_to_f64(bytes: &[u8; 10]) -> f64
+{
+    let exponent = (((bytes[0] & 0x7F) as i32) << 8) | bytes[1] as i32;
+    let mut mantissa = [0u8; 8];
+    mantissa.copy_from_slice(&bytes[2..10]);
+    let mantissa = u64::from_be_bytes(mantissa);
+
+    if exponent == 0 && mantissa == 0
+    {
+        return 0.0;
+    }
+    let value = mantissa as f64 * 2f64.powi(exponent - 16383 - 63);
+    if bytes[0] & 0x80 != 0 { -value } else { value }
+}
+
+/// Encode as an 80-bit IEEE 754 extended float (AIFF's sample rate field), the inverse of [`extended_to_f64`]
+pub(crate) fn f64_to_extended(value: f64) -> [u8; 10]
+{
+    let mut bytes = [0u8; 10];
+    if value == 0.0 || !value.is_normal()
+    {
+        return bytes;
+    }
+
+    let bits = value.to_bits();
+    let exponent = ((bits >> 52) & 0x7FF) as u16 + 16383 - 1023;
+    let sign = if value < 0.0 { 0x8000 } else { 0 };
+    let mantissa = (1u64 << 63) | ((bits & ((1u64 << 52) - 1)) << 11);
+    bytes[0..2].copy_from_slice(&(exponent | sign).to_be_bytes());
+    bytes[2..10].copy_from_slice(&mantissa.to_be_bytes());
+    bytes
+}
+
+/// Parse a CAF `info` chunk: a count, then that many NUL-terminated key/value string pairs
+fn read_caf_info(data: &[u8], tags: &mut Tags)
+{
+    let mut strings = data.get(4..).unwrap_or_default()
+                          .split(|&b| b == 0)
+                          .map(|s| String::from_utf8_lossy(s).into_owned());
+    while let (Some(key), Some(value)) = (strings.next(), strings.next())
+    {
+        if !key.is_empty()
+        {
+            tags.add(&key, value);
+        }
+    }
+}
+
+/// Parse a RIFF `LIST` chunk of type `INFO` into tags, ignoring other list types
+fn read_riff_info(data: &[u8], tags: &mut Tags)
+{
+    if data.get(..4) != Some(&b"INFO"[..])
+    {
+        return;
+    }
+
+    let mut pos = 4;
+    while pos + 8 <= data.len()
+    {
+        let id = &data[pos..pos + 4];
+        let len = u32::from_le_bytes([data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]]) as usize;
+        let Some(value) = data.get(pos + 8..pos + 8 + len)
+        else
+        {
+            break;
+        };
+
+        let key = match id
+        {
+            b"INAM" => "title",
+            b"IART" => "artist",
+            b"IPRD" => "album",
+            b"ICRD" => "date",
+            b"IGNR" => "genre",
+            b"ICMT" => "comment",
+            b"ICOP" => "copyright",
+            b"ITRK" | b"IPRT" => "tracknumber",
+            _ => "",
+        };
+        if !key.is_empty()
+        {
+            tags.add(key, String::from_utf8_lossy(value).trim_end_matches('\0').to_string());
+        }
+        pos += 8 + len + (len & 1);
+    }
+}
+
+/// Read the next RIFF chunk header (id, little-endian u32 size), or None at the end of the file
+fn read_chunk_header_le32<R: Read>(reader: &mut R) -> Result<Option<([u8; 4], u64)>>
+{
+    let mut id = [0u8; 4];
+    if !read_exact_or_eof(reader, &mut id)?
+    {
+        return Ok(None);
+    }
+    Ok(Some((id, read_u32_le(reader)? as u64)))
+}
+
+/// Read the next IFF chunk header (id, big-endian u32 size), or None at the end of the file
+fn read_chunk_header_be32<R: Read>(reader: &mut R) -> Result<Option<([u8; 4], u64)>>
+{
+    let mut id = [0u8; 4];
+    if !read_exact_or_eof(reader, &mut id)?
+    {
+        return Ok(None);
+    }
+    Ok(Some((id, read_u32_be(reader)? as u64)))
+}
+
+/// Fill `buf`, returning false if the reader was already at its end
+fn read_exact_or_eof<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<bool>
+{
+    let mut filled = 0;
+    while filled < buf.len()
+    {
+        match reader.read(&mut buf[filled..])?
+        {
+            0 if filled == 0 => return Ok(false),
+            0 => return Err(anyhow!("Unexpected end of file inside a chunk header")),
+            n => filled += n,
+        }
+    }
+    Ok(true)
+}
+
+/// Largest text/metadata chunk read into memory
+const MAX_TEXT_CHUNK: u64 = 1 << 20;
+
+fn read_bytes<R: Read>(reader: &mut R, len: u64) -> Result<Vec<u8>>
+{
+    if len > MAX_TEXT_CHUNK
+    {
+        return Err(anyhow!("Text chunk too large: {} bytes", len));
+    }
+    let mut bytes = vec![0u8; len as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_text<R: Read>(reader: &mut R, len: u64) -> Result<String>
+{
+    let bytes = read_bytes(reader, len)?;
+    Ok(String::from_utf8_lossy(&bytes).trim_end_matches('\0').to_string())
+}
+
+fn read_u16_be<R: Read>(reader: &mut R) -> Result<u16>
+{
+    let mut buf = [0u8; 2];
+    reader.read_exact(&mut buf)?;
+    Ok(u16::from_be_bytes(buf))
+}
+
+fn read_u32_be<R: Read>(reader: &mut R) -> Result<u32>
+{
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+fn read_u64_be<R: Read>(reader: &mut R) -> Result<u64>
+{
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+fn read_u16_le<R: Read>(reader: &mut R) -> Result<u16>
+{
+    let mut buf = [0u8; 2];
+    reader.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32_le<R: Read>(reader: &mut R) -> Result<u32>
+{
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64_le<R: Read>(reader: &mut R) -> Result<u64>
+{
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
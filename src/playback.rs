@@ -1,4 +1,76 @@
-//! Audio source implementation for rodio playback
+//! Audio source implementations for rodio playback
+//!
+//! [`PlaylistSource`] is the playback engine behind [`Player`](crate::player::Player): it decodes,
+//! converts, and crossfades tracks as the output pulls samples, steered through a [`PlaybackControl`].
+use anyhow::{anyhow, Result};
+use crossbeam_channel::{bounded, Receiver, Sender};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use crate::audio::FormatConverter;
+use crate::capture::{Capture, CaptureSummary};
+use crate::codec::{AudioHeader, Decoder, EncodedAudio, EncodedFrame, GaplessInfo, OverlapState, load_encoded};
+use crate::container::GlcDecoder;
+use crate::edit::{replaygain_gain, seek_point, ReplayGainMode};
+use crate::http::{is_url, HttpReader};
+use crate::equalizer::{Equalizer, FilterBank};
+use crate::mixer::Mixer;
+use crate::tempo::{TimeStretch, MAX_TEMPO, MIN_TEMPO};
+use crate::playlist::{PlayOrder, Repeat};
+
+/// Open an audio output stream on the device named `device`, or the default device
+pub fn open_output(device: Option<&str>) -> Result<(rodio::OutputStream, rodio::OutputStreamHandle)>
+{
+    match device
+    {
+        Some(name) => rodio::OutputStream::try_from_device(&find_output_device(name)?)
+            .map_err(|e| anyhow!("Failed to open audio output '{}': {}", name, e)),
+        None => rodio::OutputStream::try_default().map_err(|e| anyhow!("Failed to get default audio output: {}", e)),
+    }
+}
+
+/// The output device named `name`, or an error listing the ones there are
+pub fn find_output_device(name: &str) -> Result<rodio::Device>
+{
+    use rodio::cpal::traits::DeviceTrait;
+
+    let devices = output_devices()?;
+    let names: Vec<String> = devices.iter().filter_map(|d| d.name().ok()).collect();
+    devices.into_iter()
+           .find(|d| d.name().is_ok_and(|n| n == name))
+           .ok_or_else(|| anyhow!("No audio output named '{}' (available: {})", name, names.join(", ")))
+}
+
+/// Names of the audio outputs there are, as [`open_output`] takes them
+pub fn output_device_names() -> Result<Vec<String>>
+{
+    use rodio::cpal::traits::DeviceTrait;
+
+    Ok(output_devices()?.iter().filter_map(|d| d.name().ok()).collect())
+}
+
+fn output_devices() -> Result<Vec<rodio::Device>>
+{
+    use rodio::cpal::traits::HostTrait;
+
+    Ok(rodio::cpal::default_host().output_devices()
+                                  .map_err(|e| anyhow!("Failed to list audio outputs: {}", e))?
+                                  .collect())
+}
+
+/// A source that can restart from any position, so a [`PlaylistSource`] can seek within it
+pub trait SeekableSource: rodio::Source<Item = f32> + Send
+{
+    /// Length in samples per channel
+    fn length(&self) -> u64;
+
+    /// Continue from sample `position` (per channel), clamped to the length
+    fn seek(&mut self, position: u64);
+}
 
 /// Audio source for rodio that plays from a Vec<f32> of samples
 pub struct SamplesSource
@@ -29,20 +101,227 @@ impl Iterator for SamplesSource
 
     fn next(&mut self) -> Option<Self::Item>
     {
-        if self.position < self.samples.len()
+        let sample = self.samples.get(self.position).copied();
+        self.position += 1;
+        sample
+    }
+}
+
+impl rodio::Source for SamplesSource
+{
+    fn current_frame_len(&self) -> Option<usize>
+    {
+        None
+    }
+
+    fn channels(&self) -> u16
+    {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32
+    {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration>
+    {
+        None
+    }
+}
+
+impl SeekableSource for SamplesSource
+{
+    fn length(&self) -> u64
+    {
+        (self.samples.len() / self.channels.max(1) as usize) as u64
+    }
+
+    fn seek(&mut self, position: u64)
+    {
+        self.position = position.min(self.length()) as usize * self.channels as usize;
+    }
+}
+
+/// Encoded frames a [`GlcSource`] decodes from, read one at a time by index
+pub trait FrameReader
+{
+    fn header(&self) -> &AudioHeader;
+    fn gapless_info(&self) -> &GaplessInfo;
+    fn frame_count(&self) -> usize;
+    fn read_frame(&mut self, index: usize) -> Result<EncodedFrame>;
+}
+
+/// Frames read from a `.glc` stream through its seek table
+impl<R: Read + Seek> FrameReader for GlcDecoder<R>
+{
+    fn header(&self) -> &AudioHeader
+    {
+        GlcDecoder::header(self)
+    }
+
+    fn gapless_info(&self) -> &GaplessInfo
+    {
+        GlcDecoder::gapless_info(self)
+    }
+
+    fn frame_count(&self) -> usize
+    {
+        GlcDecoder::frame_count(self)
+    }
+
+    fn read_frame(&mut self, index: usize) -> Result<EncodedFrame>
+    {
+        GlcDecoder::read_frame(self, index)
+    }
+}
+
+/// Frames already in memory, e.g. from a file written before the frame-delimited container
+impl FrameReader for EncodedAudio
+{
+    fn header(&self) -> &AudioHeader
+    {
+        &self.header
+    }
+
+    fn gapless_info(&self) -> &GaplessInfo
+    {
+        &self.gapless_info
+    }
+
+    fn frame_count(&self) -> usize
+    {
+        self.frames.len()
+    }
+
+    fn read_frame(&mut self, index: usize) -> Result<EncodedFrame>
+    {
+        self.frames.get(index).cloned().ok_or_else(|| anyhow!("Frame {} is out of range", index))
+    }
+}
+
+/// Audio source for rodio that decodes a GLC stream lazily, one frame at a time,
+/// as the sink pulls samples, so playback starts without pre-decoding the whole file
+/// and only the frame being played is held in memory
+///
+/// Output is gapless-trimmed the same way as [`Decoder::decode`](crate::codec::Decoder::decode).
+pub struct GlcSource<F: FrameReader>
+{
+    glc: F,
+    decoder: Decoder,
+    sample_rate: u32,
+    channels: u16,
+    overlap: OverlapState,
+    /// Interleaved samples of the most recently decoded frame
+    buffer: Vec<f32>,
+    position: usize,
+    next_frame: usize,
+    flushed: bool,
+    /// Leading (encoder delay) samples still to be discarded
+    to_skip: usize,
+    /// Samples left to play before the trailing padding
+    remaining: usize,
+}
+
+impl<F: FrameReader> GlcSource<F>
+{
+    pub fn new(glc: F) -> Result<Self>
+    {
+        let sample_rate = glc.header().sample_rate;
+        let channels = glc.header().channels;
+        if channels == 0
+        {
+            return Err(anyhow!("GLC stream has no channels"));
+        }
+
+        // The delay counts samples per channel; the buffer is interleaved
+        let to_skip = glc.gapless_info().encoder_delay as usize * channels as usize;
+        let remaining = glc.gapless_info().original_length as usize;
+        let overlap = OverlapState::with_hop_size(channels as usize, glc.header().hop_size as usize)?;
+
+        Ok(Self
+        {
+            glc,
+            decoder: Decoder::new(channels as usize, sample_rate),
+            sample_rate,
+            channels,
+            overlap,
+            buffer: Vec::new(),
+            position: 0,
+            next_frame: 0,
+            flushed: false,
+            to_skip,
+            remaining,
+        })
+    }
+
+    /// Decode the next frame (or the final overlap) into `buffer`
+    /// Returns false once the stream is exhausted or a frame fails to decode
+    fn refill(&mut self) -> bool
+    {
+        self.buffer.clear();
+        self.position = 0;
+
+        if self.next_frame < self.glc.frame_count()
+        {
+            let frame = match self.glc.read_frame(self.next_frame)
+            {
+                Ok(frame) => frame,
+                Err(e) =>
+                {
+                    eprintln!("Error decoding frame {}: {}", self.next_frame, e);
+                    return false;
+                }
+            };
+            self.buffer = self.decoder.decode_frame(&frame, &mut self.overlap);
+            self.next_frame += 1;
+        }
+        else if !self.flushed
         {
-            let sample = self.samples[self.position];
-            self.position += 1;
-            Some(sample)
+            self.buffer = self.overlap.flush();
+            self.flushed = true;
         }
         else
         {
-            None
+            return false;
         }
+
+        // Drop the encoder delay at the start of the stream
+        let skip = self.to_skip.min(self.buffer.len());
+        self.position = skip;
+        self.to_skip -= skip;
+        true
     }
 }
 
-impl rodio::Source for SamplesSource
+impl<F: FrameReader> Iterator for GlcSource<F>
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item>
+    {
+        if self.remaining == 0
+        {
+            return None;
+        }
+
+        while self.position >= self.buffer.len()
+        {
+            if !self.refill()
+            {
+                self.remaining = 0;
+                return None;
+            }
+        }
+
+        let sample = self.buffer[self.position];
+        self.position += 1;
+        self.remaining -= 1;
+        Some(sample)
+    }
+}
+
+impl<F: FrameReader> rodio::Source for GlcSource<F>
 {
     fn current_frame_len(&self) -> Option<usize>
     {
@@ -59,8 +338,1152 @@ impl rodio::Source for SamplesSource
         self.sample_rate
     }
 
-    fn total_duration(&self) -> Option<std::time::Duration>
+    fn total_duration(&self) -> Option<Duration>
     {
-        None
+        let frames = self.glc.gapless_info().original_length / self.channels as u64;
+        Some(Duration::from_secs_f64(frames as f64 / self.sample_rate as f64))
+    }
+}
+
+impl<F: FrameReader + Send> SeekableSource for GlcSource<F>
+{
+    fn length(&self) -> u64
+    {
+        self.glc.gapless_info().original_length / self.channels as u64
+    }
+
+    /// Restart decoding a frame before the one holding `position`, the same way
+    /// [`decode_range`](crate::edit::decode_range) does, so the overlap (and any long-term
+    /// prediction) is primed
+    fn seek(&mut self, position: u64)
+    {
+        let channels = self.channels as usize;
+        let position = position.min(self.length());
+        let hop = self.glc.header().hop_size as u64;
+        let (mut frame, mut skip) = seek_point(position, self.glc.gapless_info().encoder_delay as u64, hop);
+        // Predicted frames need the audio before them, so back up to where prediction restarts
+        while self.glc.header().long_term_prediction && frame > 0
+              && self.glc.read_frame(frame as usize).is_ok_and(|f| !f.restarts_prediction())
+        {
+            frame -= 1;
+            skip += hop;
+        }
+
+        self.overlap.reset();
+        self.buffer.clear();
+        self.position = 0;
+        self.next_frame = frame as usize;
+        self.flushed = false;
+        self.to_skip = skip as usize * channels;
+        self.remaining = (self.length() - position) as usize * channels;
+    }
+}
+
+/// Frames pulled from a track at a time by [`PlaylistSource`]
+const PLAYLIST_BLOCK_FRAMES: usize = 4096;
+
+/// Output frames played between updates of a [`PlaybackControl`]'s position
+const POSITION_UPDATE_FRAMES: usize = 256;
+
+/// Output frames [`PlaybackControl::recent_output`] keeps, one FFT frame of a
+/// [`spectrum`](crate::spectrogram::spectrum)
+pub const RECENT_OUTPUT_FRAMES: usize = 2048;
+
+/// Events [`PlaybackControl::events`] holds before dropping new ones
+const EVENT_QUEUE: usize = 64;
+
+/// Share of [`PlaybackStats::speed`] carried over from the blocks before each new one
+const SPEED_DECAY: f64 = 0.9;
+
+/// One track of a [`PlaylistSource`]: a source's samples along with its format
+pub struct Track
+{
+    source: Box<dyn SeekableSource>,
+    sample_rate: u32,
+    channels: u16,
+    /// ReplayGain track gain in dB, if tagged
+    track_gain: Option<f64>,
+    /// ReplayGain album gain in dB, if tagged
+    album_gain: Option<f64>,
+}
+
+impl Track
+{
+    pub fn new<S: SeekableSource + 'static>(source: S) -> Self
+    {
+        let (sample_rate, channels) = (source.sample_rate(), source.channels());
+        Self { source: Box::new(source), sample_rate, channels, track_gain: None, album_gain: None }
+    }
+
+    /// Open a `.glc` file as a track whose frames are decoded as they are played
+    /// Files written before the frame-delimited container have no seek table (or tags), so their
+    /// encoded frames are loaded up front (but still decoded only as they are played)
+    pub fn open(path: &Path) -> Result<Self>
+    {
+        match GlcDecoder::new(BufReader::new(File::open(path)?))
+        {
+            Ok(glc) => Self::from_decoder(glc),
+            Err(_) => Ok(Self::new(GlcSource::new(load_encoded(path)?)?)),
+        }
+    }
+
+    /// Open a `.glc` file served over HTTP (e.g. by `glc serve-http`), fetching its frames with
+    /// range requests as they are played
+    pub fn open_url(url: &str) -> Result<Self>
+    {
+        Self::from_decoder(GlcDecoder::new(HttpReader::open(url)?).map_err(|e| anyhow!("{}: {}", url, e))?)
+    }
+
+    /// Open `input` with [`Track::open_url`] if it is an `http://` URL, or [`Track::open`] otherwise
+    pub fn open_input(input: &Path) -> Result<Self>
+    {
+        match input.to_str().filter(|input| is_url(input))
+        {
+            Some(url) => Self::open_url(url),
+            None => Self::open(input),
+        }
+    }
+
+    /// A track decoding from `glc`, at the ReplayGain its tags give
+    fn from_decoder<R: Read + Seek + Send + 'static>(glc: GlcDecoder<R>) -> Result<Self>
+    {
+        let (track_gain, album_gain) = (replaygain_gain(glc.tags(), "track"), replaygain_gain(glc.tags(), "album"));
+        Ok(Self { track_gain, album_gain, ..Self::new(GlcSource::new(glc)?) })
+    }
+
+    /// Linear gain for playing this track with `settings`; 1.0 if it has no ReplayGain tags
+    fn gain(&self, settings: ReplayGain) -> f32
+    {
+        let gain = match settings.mode
+        {
+            Some(ReplayGainMode::Track) => self.track_gain.or(self.album_gain),
+            Some(ReplayGainMode::Album) => self.album_gain.or(self.track_gain),
+            None => None,
+        };
+        gain.map_or(1.0, |gain| 10f64.powf((gain + settings.preamp) / 20.0) as f32)
+    }
+
+    pub fn sample_rate(&self) -> u32
+    {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u16
+    {
+        self.channels
+    }
+
+    /// Length in seconds
+    pub fn duration(&self) -> f64
+    {
+        self.source.length() as f64 / self.sample_rate as f64
+    }
+}
+
+/// The format a [`PlaylistSource`] plays `tracks` in: the highest sample rate and channel count
+/// among them (44.1 kHz stereo for none)
+pub fn output_format(tracks: &[Track]) -> (u32, u16)
+{
+    let sample_rate = tracks.iter().map(|t| t.sample_rate).max().unwrap_or(44100);
+    let channels = tracks.iter().map(|t| t.channels).max().unwrap_or(2);
+    (sample_rate, channels)
+}
+
+/// Where a [`PlaylistSource`] is in its playlist
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PlaybackPosition
+{
+    /// Index of the current track
+    pub track: usize,
+    /// Seconds into the current track
+    pub elapsed: f64,
+    /// Length of the current track in seconds
+    pub duration: f64,
+    /// Seconds into the whole playlist
+    pub total_elapsed: f64,
+    /// Length of the whole playlist in seconds
+    pub total_duration: f64,
+}
+
+/// How well decoding is keeping up with playback, for tracking down stutter
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PlaybackStats
+{
+    /// Blocks decoded (and converted to the output format) since the playlist started
+    pub blocks: u64,
+    /// Blocks that took longer to decode than they take to play; with rodio, which decodes on the
+    /// audio thread, each one is a likely dropout
+    pub slow_blocks: u64,
+    /// Seconds of audio decoded per second spent decoding, weighted towards recent blocks;
+    /// playback keeps up while this stays above 1
+    pub speed: f64,
+    /// Seconds of audio decoded but not yet played
+    pub buffered: f64,
+    /// Times the device ran out of audio before the playlist ended (only counted by the cpal backend)
+    pub underruns: u64,
+}
+
+/// Levels of each output channel over a stretch of playback, for meters
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OutputLevels
+{
+    /// Highest absolute sample on each channel
+    pub peaks: Vec<f32>,
+    /// Root mean square of each channel
+    pub rms: Vec<f32>,
+}
+
+/// How [`PlaylistSource`] uses ReplayGain tags
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ReplayGain
+{
+    /// Which gain to apply, or None to ignore the tags
+    pub mode: Option<ReplayGainMode>,
+    /// Extra gain in dB for tracks that have ReplayGain tags
+    pub preamp: f64,
+}
+
+/// Progress a [`PlaylistSource`] reports through [`PlaybackControl::events`]
+///
+/// Events are sent as the playlist reads ahead of the output, so they lead what is heard by up
+/// to a block of audio plus whatever the output buffers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlaybackEvent
+{
+    /// The track at this index started, from its beginning or from where a seek landed
+    TrackChanged(usize),
+    /// The last track ended, or was skipped past
+    Finished,
+}
+
+/// Seconds into a track after which "previous track" restarts it instead
+pub const RESTART_THRESHOLD: f64 = 3.0;
+
+/// A seek waiting for the playlist to pick it up
+#[derive(Clone, Copy, Debug)]
+enum SeekRequest
+{
+    /// Seconds from the start of the current track
+    To(f64),
+    /// Seconds relative to the current position
+    By(f64),
+    /// Tracks forward (or back) from the current one, starting at its beginning
+    Skip(i64),
+    /// A track by its index in the playlist, and seconds from its beginning
+    Track(usize, f64),
+}
+
+/// Output levels gathered since the last `take_levels`
+#[derive(Default)]
+struct Meter
+{
+    peaks: Vec<f32>,
+    /// Sum of the squared samples on each channel, over `frames` frames
+    squares: Vec<f64>,
+    frames: u64,
+}
+
+struct ControlState
+{
+    /// Set whenever `seek` holds a request, so the audio thread can check without locking
+    pending: AtomicBool,
+    seek: Mutex<Option<SeekRequest>>,
+    position: Mutex<PlaybackPosition>,
+    mixer: Mixer,
+    equalizer: Equalizer,
+    replay_gain: Mutex<ReplayGain>,
+    /// Seconds by which consecutive tracks overlap, 0 for gapless playback
+    crossfade: Mutex<f64>,
+    /// Playback speed with the pitch kept, 1.0 being unchanged
+    tempo: Mutex<f64>,
+    /// Which track plays next, under the repeat and shuffle modes
+    order: Mutex<PlayOrder>,
+    /// Tracks waiting to join the playlist
+    queued: Mutex<Vec<Track>>,
+    stats: Mutex<PlaybackStats>,
+    meter: Mutex<Meter>,
+    /// The last [`RECENT_OUTPUT_FRAMES`] frames played, mixed to mono
+    recent: Mutex<VecDeque<f32>>,
+    /// File the output is being saved to, if any; `capturing` says whether there is one without locking
+    capture: Mutex<Option<Capture>>,
+    capturing: AtomicBool,
+    events: Sender<PlaybackEvent>,
+    event_receiver: Receiver<PlaybackEvent>,
+}
+
+impl Default for ControlState
+{
+    fn default() -> Self
+    {
+        let (events, event_receiver) = bounded(EVENT_QUEUE);
+        Self
+        {
+            pending: AtomicBool::new(false),
+            seek: Mutex::new(None),
+            position: Mutex::new(PlaybackPosition::default()),
+            mixer: Mixer::default(),
+            equalizer: Equalizer::default(),
+            replay_gain: Mutex::new(ReplayGain::default()),
+            crossfade: Mutex::new(0.0),
+            tempo: Mutex::new(1.0),
+            order: Mutex::new(PlayOrder::new(0)),
+            queued: Mutex::new(Vec::new()),
+            stats: Mutex::new(PlaybackStats::default()),
+            meter: Mutex::new(Meter::default()),
+            recent: Mutex::new(VecDeque::with_capacity(RECENT_OUTPUT_FRAMES)),
+            capture: Mutex::new(None),
+            capturing: AtomicBool::new(false),
+            events,
+            event_receiver,
+        }
+    }
+}
+
+/// Handle for steering a [`PlaylistSource`] from another thread (a key handler or the GUI)
+/// while a sink plays it
+#[derive(Clone, Default)]
+pub struct PlaybackControl
+{
+    shared: Arc<ControlState>,
+}
+
+impl PlaybackControl
+{
+    /// Jump to `seconds` into the current track
+    pub fn seek_to(&self, seconds: f64)
+    {
+        self.request(SeekRequest::To(seconds));
+    }
+
+    /// Move `seconds` forward (or back, if negative) in the current track;
+    /// going past its end starts the next track
+    pub fn seek_by(&self, seconds: f64)
+    {
+        self.request(SeekRequest::By(seconds));
+    }
+
+    /// Start the next track; on the last track this ends playback, unless repeating the playlist
+    pub fn next_track(&self)
+    {
+        self.request(SeekRequest::Skip(1));
+    }
+
+    /// Start track `index` of the playlist (counting queued tracks after the rest), wherever it
+    /// falls in the play order
+    pub fn play_track(&self, index: usize)
+    {
+        self.seek_track(index, 0.0);
+    }
+
+    /// Jump to `seconds` into track `index` of the playlist, as [`PlaybackControl::play_track`]
+    /// does to its start; for a timeline covering the whole playlist
+    pub fn seek_track(&self, index: usize, seconds: f64)
+    {
+        self.request(SeekRequest::Track(index, seconds));
+    }
+
+    /// Go back to the start of the current track, or to the previous track if the current
+    /// one has played for less than [`RESTART_THRESHOLD`] seconds
+    pub fn previous_track(&self)
+    {
+        if self.position().elapsed >= RESTART_THRESHOLD
+        {
+            self.request(SeekRequest::To(0.0));
+        }
+        else
+        {
+            self.request(SeekRequest::Skip(-1));
+        }
+    }
+
+    /// Volume and limiter applied to the playlist's output
+    pub fn mixer(&self) -> &Mixer
+    {
+        &self.shared.mixer
     }
-}
\ No newline at end of file
+
+    /// EQ bands applied to the playlist's output before the mixer
+    pub fn equalizer(&self) -> &Equalizer
+    {
+        &self.shared.equalizer
+    }
+
+    pub fn replay_gain(&self) -> ReplayGain
+    {
+        *self.shared.replay_gain.lock().unwrap()
+    }
+
+    /// Change how ReplayGain tags are applied; takes effect from the next block of audio
+    pub fn set_replay_gain(&self, settings: ReplayGain)
+    {
+        *self.shared.replay_gain.lock().unwrap() = settings;
+    }
+
+    pub fn crossfade(&self) -> f64
+    {
+        *self.shared.crossfade.lock().unwrap()
+    }
+
+    /// Overlap consecutive tracks by `seconds` with equal-power fades (at most half of either
+    /// track), or play them gaplessly with 0
+    pub fn set_crossfade(&self, seconds: f64)
+    {
+        *self.shared.crossfade.lock().unwrap() = seconds.max(0.0);
+    }
+
+    pub fn tempo(&self) -> f64
+    {
+        *self.shared.tempo.lock().unwrap()
+    }
+
+    /// Play `tempo` times faster (or slower, below 1) without changing the pitch, clamped to
+    /// [`MIN_TEMPO`]..=[`MAX_TEMPO`]; takes effect from the next block of audio
+    pub fn set_tempo(&self, tempo: f64)
+    {
+        *self.shared.tempo.lock().unwrap() = if tempo.is_nan() { 1.0 } else { tempo.clamp(MIN_TEMPO, MAX_TEMPO) };
+    }
+
+    pub fn repeat(&self) -> Repeat
+    {
+        self.order().repeat()
+    }
+
+    /// Choose what happens when a track ends; see [`PlayOrder::advance`]
+    pub fn set_repeat(&self, repeat: Repeat)
+    {
+        self.order().set_repeat(repeat);
+    }
+
+    pub fn shuffle(&self) -> bool
+    {
+        self.order().shuffle()
+    }
+
+    /// Play the tracks in a random order, or go back to their own order, without interrupting
+    /// the current track; see [`PlayOrder::set_shuffle`]
+    pub fn set_shuffle(&self, shuffle: bool)
+    {
+        self.order().set_shuffle(shuffle);
+    }
+
+    /// The current track and how far into it playback is, updated as the sink pulls samples
+    pub fn position(&self) -> PlaybackPosition
+    {
+        *self.shared.position.lock().unwrap()
+    }
+
+    /// Decoding speed and buffer depth of the playlist playing, updated with its position
+    pub fn stats(&self) -> PlaybackStats
+    {
+        *self.shared.stats.lock().unwrap()
+    }
+
+    /// Highest absolute sample played on each output channel since the last call, for level meters
+    /// Empty until something has played.
+    pub fn take_peaks(&self) -> Vec<f32>
+    {
+        self.take_levels().peaks
+    }
+
+    /// Peak and RMS level of each output channel since the last call (or `take_peaks`)
+    /// Empty until something has played.
+    pub fn take_levels(&self) -> OutputLevels
+    {
+        let meter = std::mem::take(&mut *self.shared.meter.lock().unwrap());
+        let rms = meter.squares.iter().map(|&squares| (squares / meter.frames.max(1) as f64).sqrt() as f32).collect();
+        OutputLevels { peaks: meter.peaks, rms }
+    }
+
+    /// The last [`RECENT_OUTPUT_FRAMES`] frames played, mixed to mono, oldest first, for a spectrum
+    /// view; fewer at the start of a playlist
+    pub fn recent_output(&self) -> Vec<f32>
+    {
+        self.shared.recent.lock().unwrap().iter().copied().collect()
+    }
+
+    /// Save everything played from the next block on to `path`, a `.wav` or `.flac` file,
+    /// until [`PlaybackControl::stop_capture`]; the capture carries on from one playlist to the next
+    pub fn start_capture(&self, path: &Path) -> Result<()>
+    {
+        let mut capture = self.shared.capture.lock().unwrap();
+        if capture.is_some()
+        {
+            return Err(anyhow!("Already capturing; stop that capture first"));
+        }
+        *capture = Some(Capture::start(path)?);
+        self.shared.capturing.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// Finish the capture, if there is one, and say what it wrote
+    /// Audio played since the playlist's last block (at most a block) is left out.
+    pub fn stop_capture(&self) -> Result<Option<CaptureSummary>>
+    {
+        self.shared.capturing.store(false, Ordering::Release);
+        let capture = self.shared.capture.lock().unwrap().take();
+        capture.map(Capture::finish).transpose()
+    }
+
+    pub fn is_capturing(&self) -> bool
+    {
+        self.shared.capturing.load(Ordering::Acquire)
+    }
+
+    /// Hand `samples` (leaving it empty) to the capture, if there is one
+    fn capture(&self, samples: &mut Vec<f32>, sample_rate: u32, channels: u16)
+    {
+        if let Some(ref capture) = *self.shared.capture.lock().unwrap()
+        {
+            capture.send(std::mem::take(samples), sample_rate, channels);
+        }
+        samples.clear();
+    }
+
+    /// Add `track` to the end of the playlist; a playing [`PlaylistSource`] picks it up before
+    /// its next block, converting it to the output format if need be
+    pub fn queue(&self, track: Track)
+    {
+        self.shared.queued.lock().unwrap().push(track);
+    }
+
+    /// Drop the tracks queued since the playlist last picked any up
+    pub fn clear_queue(&self)
+    {
+        self.shared.queued.lock().unwrap().clear();
+    }
+
+    /// Track changes and the end of the playlist, as they happen
+    /// Every receiver takes from the same queue, which drops new events while full
+    pub fn events(&self) -> Receiver<PlaybackEvent>
+    {
+        self.shared.event_receiver.clone()
+    }
+
+    fn request(&self, request: SeekRequest)
+    {
+        let mut seek = self.shared.seek.lock().unwrap();
+        // Relative seeks made before the playlist catches up add to the one already waiting
+        *seek = match (*seek, request)
+        {
+            (Some(SeekRequest::To(to)), SeekRequest::By(by)) => Some(SeekRequest::To(to + by)),
+            (Some(SeekRequest::By(a)), SeekRequest::By(b)) => Some(SeekRequest::By(a + b)),
+            (Some(SeekRequest::Skip(a)), SeekRequest::Skip(b)) => Some(SeekRequest::Skip(a + b)),
+            _ => Some(request),
+        };
+        self.shared.pending.store(true, Ordering::Release);
+    }
+
+    fn take_seek(&self) -> Option<SeekRequest>
+    {
+        if !self.shared.pending.swap(false, Ordering::Acquire)
+        {
+            return None;
+        }
+        self.shared.seek.lock().unwrap().take()
+    }
+
+    fn set_position(&self, position: PlaybackPosition)
+    {
+        *self.shared.position.lock().unwrap() = position;
+    }
+
+    fn stats_mut(&self) -> MutexGuard<'_, PlaybackStats>
+    {
+        self.shared.stats.lock().unwrap()
+    }
+
+    fn order(&self) -> MutexGuard<'_, PlayOrder>
+    {
+        self.shared.order.lock().unwrap()
+    }
+
+    fn take_queued(&self) -> Vec<Track>
+    {
+        std::mem::take(&mut *self.shared.queued.lock().unwrap())
+    }
+
+    fn send_event(&self, event: PlaybackEvent)
+    {
+        // Nobody may be listening, so a full queue isn't worth blocking the audio thread over
+        self.shared.events.try_send(event).ok();
+    }
+}
+
+/// Audio source for rodio that plays tracks back to back as one stream, in the highest sample
+/// rate and channel count among them
+///
+/// Tracks play in the [`PlayOrder`] held by its [`PlaybackControl`], which starts (and shuffles,
+/// if asked to) when the sink first pulls a sample.
+///
+/// Rodio converts every queued source on its own, so tracks that differ in format leave gaps at
+/// their boundaries when queued separately; converting them here keeps the playlist continuous.
+/// Seeks requested through [`PlaylistSource::control`] drop the converted output not yet played
+/// and resume from the target position.
+pub struct PlaylistSource
+{
+    tracks: Vec<Track>,
+    /// Seconds from the start of the playlist (in play order) to the start of the current track
+    offset: f64,
+    total_duration: f64,
+    /// Index of the track being read; `tracks.len()` once the playlist has ended
+    current: usize,
+    /// Samples per channel read from the current track so far
+    consumed: u64,
+    /// Position in the current track (samples per channel) of the start of `buffer`
+    buffer_start: u64,
+    converter: FormatConverter,
+    control: PlaybackControl,
+    /// Tracks whose format differs from the output's
+    converted: usize,
+    /// Interleaved input read from the current track
+    block: Vec<f32>,
+    /// Converted output still to be played
+    buffer: Vec<f32>,
+    position: usize,
+    /// Highest absolute sample and sum of squares on each output channel, and the frames mixed to
+    /// mono, since they were last passed to the control
+    peaks: Vec<f32>,
+    squares: Vec<f64>,
+    recent: Vec<f32>,
+    /// Sum of the current frame's samples so far
+    frame_sum: f32,
+    /// Output handed out since the last block, kept while the control is capturing
+    captured: Vec<f32>,
+    capturing: bool,
+    /// Time-stretches converted output to the control's tempo
+    stretch: TimeStretch,
+    /// Converted output waiting for `stretch`
+    unstretched: Vec<f32>,
+    /// Tempo `buffer` was stretched to, for mapping its samples back to the track's
+    buffer_tempo: f64,
+    /// Filters for the control's EQ bands as of `eq_generation`
+    equalizer: FilterBank,
+    eq_generation: u64,
+    /// Seconds spent decoding and seconds of audio decoded, both decaying by [`SPEED_DECAY`] per block
+    decode_time: f64,
+    decoded_audio: f64,
+    started: bool,
+    finished: bool,
+}
+
+impl PlaylistSource
+{
+    pub fn new(tracks: Vec<Track>) -> Self
+    {
+        Self::with_control(tracks, PlaybackControl::default())
+    }
+
+    /// Play `tracks`, followed by any tracks already queued on `control`, steered by `control`
+    /// Its volume, EQ, tempo, ReplayGain, crossfade, repeat, and shuffle settings carry over, so one control
+    /// can steer one playlist after another (as a [`Player`](crate::player::Player) does)
+    pub fn with_control(mut tracks: Vec<Track>, control: PlaybackControl) -> Self
+    {
+        tracks.extend(control.take_queued());
+        let (sample_rate, channels) = output_format(&tracks);
+        let converted = tracks.iter().filter(|t| (t.sample_rate, t.channels) != (sample_rate, channels)).count();
+
+        let total_duration = tracks.iter().map(Track::duration).sum();
+
+        // Seeks meant for an earlier playlist don't apply to this one
+        control.take_seek();
+        control.shared.recent.lock().unwrap().clear();
+        control.order().reset(tracks.len());
+        *control.stats_mut() = PlaybackStats::default();
+        control.set_position(PlaybackPosition
+        {
+            duration: tracks.first().map_or(0.0, Track::duration),
+            total_duration,
+            ..PlaybackPosition::default()
+        });
+
+        let eq_generation = control.equalizer().generation();
+        let equalizer = FilterBank::new(&control.equalizer().bands(), sample_rate, channels);
+
+        Self
+        {
+            tracks,
+            offset: 0.0,
+            total_duration,
+            current: 0,
+            consumed: 0,
+            buffer_start: 0,
+            converter: FormatConverter::new(sample_rate, channels),
+            control,
+            converted,
+            block: Vec::new(),
+            buffer: Vec::new(),
+            position: 0,
+            peaks: vec![0.0; channels as usize],
+            squares: vec![0.0; channels as usize],
+            recent: Vec::with_capacity(POSITION_UPDATE_FRAMES),
+            frame_sum: 0.0,
+            captured: Vec::new(),
+            capturing: false,
+            stretch: TimeStretch::new(sample_rate, channels),
+            unstretched: Vec::new(),
+            buffer_tempo: 1.0,
+            equalizer,
+            eq_generation,
+            decode_time: 0.0,
+            decoded_audio: 0.0,
+            started: false,
+            finished: false,
+        }
+    }
+
+    /// Number of tracks that are resampled or remixed to the output format
+    pub fn converted_tracks(&self) -> usize
+    {
+        self.converted
+    }
+
+    /// A handle for seeking and following the position once the source is playing
+    pub fn control(&self) -> PlaybackControl
+    {
+        self.control.clone()
+    }
+
+    /// Convert the next block of the current track (moving on to the next track as each one ends)
+    /// Returns false once every track has been played
+    fn refill(&mut self) -> bool
+    {
+        self.flush_capture();
+        self.buffer.clear();
+        self.position = 0;
+        self.add_queued();
+        let started = Instant::now();
+
+        while self.buffer.is_empty()
+        {
+            let Some(track) = self.tracks.get(self.current)
+            else
+            {
+                // All that's left is the converter's tail, the first time round
+                if !self.finished
+                {
+                    self.finished = true;
+                    self.converter.finish(&mut self.buffer);
+                    self.apply_tempo();
+                    self.stretch.finish(&mut self.buffer);
+                    self.equalize();
+                }
+                self.publish_position();
+                return !self.buffer.is_empty();
+            };
+
+            // With a crossfade, stop reading blocks where the fade into the next track begins
+            let mut frames = PLAYLIST_BLOCK_FRAMES as u64;
+            let next = self.control.order().peek_next();
+            if let Some(next) = next
+            {
+                // Neither track spends more than half its length fading
+                let length = track.source.length();
+                let next = &self.tracks[next];
+                let next_length = next.source.length() * track.sample_rate as u64 / next.sample_rate.max(1) as u64;
+                let fade = ((self.control.crossfade() * track.sample_rate as f64) as u64).min(length / 2).min(next_length / 2);
+                let remaining = length.saturating_sub(self.consumed);
+                if fade > 0 && remaining <= fade
+                {
+                    self.crossfade();
+                    self.apply_tempo();
+                    continue;
+                }
+                if fade > 0
+                {
+                    frames = frames.min(remaining - fade);
+                }
+            }
+
+            let (sample_rate, channels) = (track.sample_rate, track.channels);
+            let read = self.read_block(self.current, frames as usize);
+            if read == 0
+            {
+                let next = self.control.order().advance();
+                self.start_track(next);
+                continue;
+            }
+
+            self.buffer_start = self.consumed;
+            self.consumed += read as u64;
+            self.converter.push(&self.block, sample_rate, channels, &mut self.buffer);
+            self.apply_tempo();
+        }
+
+        self.equalize();
+        self.record_block(started.elapsed().as_secs_f64());
+        self.publish_position();
+        true
+    }
+
+    /// Pass the output played since the last block on to the control's capture, and check
+    /// whether to keep capturing
+    fn flush_capture(&mut self)
+    {
+        if !self.captured.is_empty()
+        {
+            self.control.capture(&mut self.captured, self.converter.sample_rate(), self.converter.channels());
+        }
+        self.capturing = self.control.is_capturing();
+    }
+
+    /// Time-stretch the block just converted into `buffer` to the control's tempo; while the
+    /// stretcher fills up, this can leave `buffer` empty
+    fn apply_tempo(&mut self)
+    {
+        let tempo = self.control.tempo();
+        self.buffer_tempo = tempo;
+        if tempo == 1.0 && !self.stretch.is_active()
+        {
+            return;
+        }
+        std::mem::swap(&mut self.buffer, &mut self.unstretched);
+        self.stretch.process(&self.unstretched, tempo, &mut self.buffer);
+        self.unstretched.clear();
+
+        // The output lags the input by what the stretcher holds back, so it starts further back in the track
+        if let Some(track) = self.tracks.get(self.current)
+        {
+            let frames = (self.buffer.len() / self.converter.channels().max(1) as usize) as f64;
+            let behind = (self.stretch.latency() as f64 + frames * tempo) * track.sample_rate as f64
+                         / self.converter.sample_rate() as f64;
+            self.buffer_start = self.consumed.saturating_sub(behind as u64);
+        }
+    }
+
+    /// Run the EQ over the block just converted into `buffer`, first picking up any change to its bands
+    fn equalize(&mut self)
+    {
+        let equalizer = self.control.equalizer();
+        let generation = equalizer.generation();
+        if generation != self.eq_generation
+        {
+            self.eq_generation = generation;
+            self.equalizer.set_bands(&equalizer.bands());
+        }
+
+        if !equalizer.enabled() || self.equalizer.is_flat()
+        {
+            // Start from silence when switched back on, rather than from stale history
+            self.equalizer.reset();
+            return;
+        }
+        self.equalizer.process(&mut self.buffer);
+    }
+
+    /// Count the block just decoded into `buffer`, which took `seconds` to decode
+    fn record_block(&mut self, seconds: f64)
+    {
+        let audio = self.buffer.len() as f64 / self.converter.channels().max(1) as f64 / self.converter.sample_rate() as f64;
+        self.decode_time = self.decode_time * SPEED_DECAY + seconds;
+        self.decoded_audio = self.decoded_audio * SPEED_DECAY + audio;
+
+        let mut stats = self.control.stats_mut();
+        stats.blocks += 1;
+        stats.slow_blocks += (seconds > audio) as u64;
+        stats.speed = if self.decode_time > 0.0 { self.decoded_audio / self.decode_time } else { f64::INFINITY };
+    }
+
+    /// Add the tracks queued on the control since the last block to the end of the playlist,
+    /// carrying on into them if it had already ended
+    fn add_queued(&mut self)
+    {
+        let queued = self.control.take_queued();
+        if queued.is_empty()
+        {
+            return;
+        }
+
+        let ended = self.started && self.current >= self.tracks.len();
+        let format = (self.converter.sample_rate(), self.converter.channels());
+        self.converted += queued.iter().filter(|t| (t.sample_rate, t.channels) != format).count();
+        self.total_duration += queued.iter().map(Track::duration).sum::<f64>();
+        self.control.order().extend(queued.len());
+        self.tracks.extend(queued);
+
+        if ended
+        {
+            self.finished = false;
+            let current = self.control.order().current();
+            self.start_track(current);
+        }
+    }
+
+    /// Read up to `frames` frames of track `index` into `block`, with its ReplayGain applied
+    /// Returns the number of frames read
+    fn read_block(&mut self, index: usize, frames: usize) -> usize
+    {
+        let settings = self.control.replay_gain();
+        let track = &mut self.tracks[index];
+        self.block.clear();
+        self.block.extend(track.source.by_ref().take(frames * track.channels as usize));
+
+        let gain = track.gain(settings);
+        if gain != 1.0
+        {
+            self.block.iter_mut().for_each(|sample| *sample *= gain);
+        }
+        self.block.len() / track.channels.max(1) as usize
+    }
+
+    /// Mix the rest of the current track into the start of the next one with equal-power fades,
+    /// leaving the mix in `buffer` and the next track current
+    fn crossfade(&mut self)
+    {
+        let channels = self.converter.channels().max(1) as usize;
+
+        // Everything left of the outgoing track
+        let mut tail = Vec::new();
+        let outgoing = &self.tracks[self.current];
+        let (sample_rate, track_channels) = (outgoing.sample_rate, outgoing.channels);
+        while self.read_block(self.current, PLAYLIST_BLOCK_FRAMES) > 0
+        {
+            self.converter.push(&self.block, sample_rate, track_channels, &mut tail);
+        }
+        self.converter.finish(&mut tail);
+
+        // At least as much of the incoming track, which then carries on from where this leaves off
+        let next = self.control.order().advance();
+        self.start_track(next);
+        let Some(incoming) = self.tracks.get(self.current)
+        else
+        {
+            self.buffer = tail;
+            return;
+        };
+        let (sample_rate, track_channels) = (incoming.sample_rate, incoming.channels);
+        let mut head = Vec::new();
+        while head.len() < tail.len()
+        {
+            let read = self.read_block(self.current, PLAYLIST_BLOCK_FRAMES);
+            if read == 0
+            {
+                break;
+            }
+            self.consumed += read as u64;
+            self.converter.push(&self.block, sample_rate, track_channels, &mut head);
+        }
+
+        // The outgoing track fades out along a cosine while the incoming one fades in along a sine
+        let fade = (tail.len() / channels).max(1) as f32;
+        for (i, frame) in tail.chunks(channels).enumerate()
+        {
+            let angle = std::f32::consts::FRAC_PI_2 * (i as f32 + 0.5) / fade;
+            let (fade_out, fade_in) = (angle.cos(), angle.sin());
+            for (c, sample) in frame.iter().enumerate()
+            {
+                let incoming = head.get(i * channels + c).copied().unwrap_or(0.0);
+                self.buffer.push(sample * fade_out + incoming * fade_in);
+            }
+        }
+        self.buffer.extend_from_slice(head.get(tail.len()..).unwrap_or_default());
+    }
+
+    /// Make `track` current from its beginning, or end the playlist if None
+    fn start_track(&mut self, track: Option<usize>)
+    {
+        self.current = track.unwrap_or(self.tracks.len());
+        if let Some(track) = self.tracks.get_mut(self.current)
+        {
+            track.source.seek(0);
+        }
+        self.consumed = 0;
+        self.buffer_start = 0;
+        self.update_offset();
+        self.control.send_event(track.map_or(PlaybackEvent::Finished, PlaybackEvent::TrackChanged));
+    }
+
+    fn update_offset(&mut self)
+    {
+        self.offset = self.control.order().played().iter().map(|&track| self.tracks[track].duration()).sum();
+    }
+
+    /// How much of `buffer` has been played, in samples per channel of the current track
+    fn played(&self, track: &Track) -> f64
+    {
+        let frames = self.position / self.converter.channels().max(1) as usize;
+        frames as f64 * self.buffer_tempo * track.sample_rate as f64 / self.converter.sample_rate() as f64
+    }
+
+    /// Fold the levels and output since the last update into the control's, and start counting afresh
+    fn publish_levels(&mut self)
+    {
+        let mut meter = self.control.shared.meter.lock().unwrap();
+        if meter.peaks.len() != self.peaks.len()
+        {
+            *meter = Meter { peaks: vec![0.0; self.peaks.len()], squares: vec![0.0; self.peaks.len()], frames: 0 };
+        }
+        for (shared, peak) in meter.peaks.iter_mut().zip(&mut self.peaks)
+        {
+            *shared = shared.max(*peak);
+            *peak = 0.0;
+        }
+        for (shared, squares) in meter.squares.iter_mut().zip(&mut self.squares)
+        {
+            *shared += *squares;
+            *squares = 0.0;
+        }
+        meter.frames += self.recent.len() as u64;
+        drop(meter);
+
+        let mut recent = self.control.shared.recent.lock().unwrap();
+        recent.extend(self.recent.drain(..));
+        let excess = recent.len().saturating_sub(RECENT_OUTPUT_FRAMES);
+        recent.drain(..excess);
+    }
+
+    fn publish_position(&self)
+    {
+        let unplayed = self.buffer.len().saturating_sub(self.position);
+        self.control.stats_mut().buffered =
+            unplayed as f64 / self.converter.channels().max(1) as f64 / self.converter.sample_rate() as f64;
+
+        if let Some(track) = self.tracks.get(self.current)
+        {
+            let elapsed = (self.buffer_start as f64 + self.played(track)) / track.sample_rate as f64;
+            self.control.set_position(PlaybackPosition
+            {
+                track: self.current,
+                elapsed,
+                duration: track.duration(),
+                total_elapsed: self.offset + elapsed,
+                total_duration: self.total_duration,
+            });
+        }
+    }
+
+    /// Drop the output not yet played and continue from the requested position
+    fn seek(&mut self, request: SeekRequest)
+    {
+        let Some(track) = self.tracks.get(self.current)
+        else
+        {
+            return;
+        };
+
+        let rate = track.sample_rate as f64;
+        let played = self.played(track);
+        let (index, target) = match request
+        {
+            SeekRequest::To(seconds) => (Some(self.current), seconds * rate),
+            SeekRequest::By(seconds) => (Some(self.current), self.buffer_start as f64 + played + seconds * rate),
+            SeekRequest::Skip(tracks) => (self.control.order().skip(tracks), 0.0),
+            // No such track restarts the current one
+            SeekRequest::Track(index, seconds) => match self.control.order().jump_to(index)
+            {
+                Some(index) => (Some(index), seconds * self.tracks[index].sample_rate as f64),
+                None => (Some(self.current), 0.0),
+            },
+        };
+        let (mut index, mut target) = (index, target.max(0.0) as u64);
+        if index == Some(self.current) && target >= track.source.length()
+        {
+            index = self.control.order().skip(1);
+            target = 0;
+        }
+
+        // Skipping past the last track leaves nothing to play
+        if index != Some(self.current)
+        {
+            self.control.send_event(index.map_or(PlaybackEvent::Finished, PlaybackEvent::TrackChanged));
+        }
+        self.current = index.unwrap_or(self.tracks.len());
+        if let Some(track) = self.tracks.get_mut(self.current)
+        {
+            track.source.seek(target);
+        }
+        self.consumed = target;
+        self.buffer_start = target;
+        self.update_offset();
+        self.buffer.clear();
+        self.position = 0;
+        self.converter.reset();
+        self.stretch.reset();
+        self.equalizer.reset();
+        self.publish_position();
+    }
+}
+
+impl Iterator for PlaylistSource
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item>
+    {
+        if !self.started
+        {
+            self.started = true;
+            let first = self.control.order().start();
+            self.start_track(first);
+        }
+
+        if let Some(request) = self.control.take_seek()
+        {
+            self.seek(request);
+        }
+
+        while self.position >= self.buffer.len()
+        {
+            if !self.refill()
+            {
+                return None;
+            }
+        }
+
+        let channels = self.converter.channels().max(1) as usize;
+        let sample = self.control.mixer().apply(self.buffer[self.position]);
+        let channel = self.position % channels;
+        self.peaks[channel] = self.peaks[channel].max(sample.abs());
+        self.squares[channel] += sample as f64 * sample as f64;
+        self.frame_sum += sample;
+        if channel + 1 == channels
+        {
+            self.recent.push(self.frame_sum / channels as f32);
+            self.frame_sum = 0.0;
+        }
+        self.position += 1;
+        if self.position.is_multiple_of(POSITION_UPDATE_FRAMES * channels)
+        {
+            self.publish_levels();
+            self.publish_position();
+        }
+        if self.capturing
+        {
+            self.captured.push(sample);
+        }
+        Some(sample)
+    }
+}
+
+impl rodio::Source for PlaylistSource
+{
+    fn current_frame_len(&self) -> Option<usize>
+    {
+        None
+    }
+
+    fn channels(&self) -> u16
+    {
+        self.converter.channels()
+    }
+
+    fn sample_rate(&self) -> u32
+    {
+        self.converter.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration>
+    {
+        None
+    }
+}
+
+/// `m:ss` (or `h:mm:ss` from an hour up) for a position in seconds
+pub fn format_time(seconds: f64) -> String
+{
+    let whole = seconds.max(0.0) as u64;
+    if whole >= 3600
+    {
+        return format!("{}:{:02}:{:02}", whole / 3600, whole / 60 % 60, whole % 60);
+    }
+    format!("{}:{:02}", whole / 60, whole % 60)
+}
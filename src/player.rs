@@ -0,0 +1,323 @@
+//! A music player for `.glc` files, independent of any front end
+//!
+//! [`Player`] owns an audio output and plays a playlist through it gaplessly, with the same
+//! engine (and so the same seeking, crossfading, ReplayGain, repeat, and shuffle) whether it is
+//! driven by `glc play`, the GUI, or another crate:
+//!
+//! ```ignore
+//! use gapless_lossy_codec::playback::PlaybackEvent;
+//! use gapless_lossy_codec::player::Player;
+//! use std::path::Path;
+//!
+//! let mut player = Player::new(None)?;
+//! player.queue(Path::new("first.glc"))?;
+//! player.queue(Path::new("second.glc"))?;
+//! player.play()?;
+//! for event in player.events()
+//! {
+//!     match event
+//!     {
+//!         PlaybackEvent::TrackChanged(track) => println!("Now playing track {}", track + 1),
+//!         PlaybackEvent::Finished => break,
+//!     }
+//! }
+//! ```
+use anyhow::{anyhow, Result};
+use crossbeam_channel::Receiver;
+use rodio::{OutputStream, OutputStreamHandle, Sink};
+use std::path::Path;
+#[cfg(feature = "playback-cpal")]
+use std::time::Duration;
+#[cfg(feature = "playback-cpal")]
+use crate::cpal_output::{CpalOutput, UnderrunStats};
+use crate::playback::{open_output, PlaybackControl, PlaybackEvent, PlaybackPosition, PlaybackStats, PlaylistSource, Track};
+
+/// How a [`Player`] gets its audio to the device
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Backend
+{
+    /// Through a rodio sink, which converts to the device's format and buffers as it sees fit
+    #[default]
+    Rodio,
+    /// Straight to the device through cpal, with `buffer` of audio decoded ahead of it
+    #[cfg(feature = "playback-cpal")]
+    Cpal
+    {
+        buffer: Duration,
+    },
+}
+
+/// The open output behind a [`Player`]
+enum Output
+{
+    Rodio
+    {
+        _stream: OutputStream,
+        handle: OutputStreamHandle,
+        sink: Sink,
+    },
+    /// The device is only opened while a playlist plays
+    #[cfg(feature = "playback-cpal")]
+    Cpal
+    {
+        device: Option<String>,
+        buffer: Duration,
+        playing: Option<CpalOutput>,
+    },
+}
+
+/// Plays queued tracks back to back on one audio output
+///
+/// The output stream belongs to the thread that opened it, so a `Player` stays on that thread;
+/// other threads steer playback through a clone of [`Player::control`].
+pub struct Player
+{
+    output: Output,
+    control: PlaybackControl,
+}
+
+impl Player
+{
+    /// Open the audio output named `device`, or the default one, with nothing queued
+    pub fn new(device: Option<&str>) -> Result<Self>
+    {
+        Self::with_backend(device, Backend::Rodio)
+    }
+
+    /// Like [`Player::new`], playing through `backend`
+    pub fn with_backend(device: Option<&str>, backend: Backend) -> Result<Self>
+    {
+        let output = match backend
+        {
+            Backend::Rodio =>
+            {
+                let (stream, handle) = open_output(device)?;
+                let sink = Sink::try_new(&handle).map_err(|e| anyhow!("Failed to create audio sink: {}", e))?;
+                Output::Rodio { _stream: stream, handle, sink }
+            }
+            #[cfg(feature = "playback-cpal")]
+            Backend::Cpal { buffer } => Output::Cpal { device: device.map(str::to_string), buffer, playing: None },
+        };
+        Ok(Self { output, control: PlaybackControl::default() })
+    }
+
+    /// Move to the audio output named `device`, or the default one, ending the playlist playing
+    /// Volume and the other settings on [`Player::control`] carry over to the new device
+    pub fn set_device(&mut self, device: Option<&str>) -> Result<()>
+    {
+        self.stop();
+        match &mut self.output
+        {
+            Output::Rodio { .. } =>
+            {
+                let (stream, handle) = open_output(device)?;
+                let sink = Sink::try_new(&handle).map_err(|e| anyhow!("Failed to create audio sink: {}", e))?;
+                self.output = Output::Rodio { _stream: stream, handle, sink };
+            }
+            #[cfg(feature = "playback-cpal")]
+            Output::Cpal { device: current, playing, .. } =>
+            {
+                // The device is only opened to play, so check now that there is one by that name
+                if let Some(name) = device
+                {
+                    crate::playback::find_output_device(name)?;
+                }
+                *playing = None;
+                *current = device.map(str::to_string);
+            }
+        }
+        Ok(())
+    }
+
+    /// Open the `.glc` file at `path` (or an `http://` URL) and add it to the end of the playlist
+    pub fn queue(&self, path: &Path) -> Result<()>
+    {
+        self.queue_track(Track::open_input(path)?);
+        Ok(())
+    }
+
+    /// Add `track` to the end of the playlist; a playing playlist picks it up without a gap
+    pub fn queue_track(&self, track: Track)
+    {
+        self.control.queue(track);
+    }
+
+    /// Resume if paused; otherwise, once the last playlist has finished (or before the first),
+    /// start a new one from the tracks queued since
+    pub fn play(&mut self) -> Result<()>
+    {
+        let finished = self.is_finished();
+        match &mut self.output
+        {
+            Output::Rodio { handle, sink, .. } =>
+            {
+                if finished
+                {
+                    // A fresh sink, so nothing a stopped one was left with carries over
+                    *sink = Sink::try_new(handle).map_err(|e| anyhow!("Failed to create audio sink: {}", e))?;
+                    sink.append(PlaylistSource::with_control(Vec::new(), self.control.clone()));
+                }
+                sink.play();
+            }
+            #[cfg(feature = "playback-cpal")]
+            Output::Cpal { device, buffer, playing } =>
+            {
+                match playing
+                {
+                    Some(output) if !finished => output.play(),
+                    _ =>
+                    {
+                        // Close the last playlist's stream before opening the device again
+                        *playing = None;
+                        let playlist = PlaylistSource::with_control(Vec::new(), self.control.clone());
+                        *playing = Some(CpalOutput::start(playlist, device.as_deref(), *buffer)?);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn pause(&self)
+    {
+        match &self.output
+        {
+            Output::Rodio { sink, .. } => sink.pause(),
+            #[cfg(feature = "playback-cpal")]
+            Output::Cpal { playing, .. } => playing.iter().for_each(CpalOutput::pause),
+        }
+    }
+
+    pub fn is_paused(&self) -> bool
+    {
+        match &self.output
+        {
+            Output::Rodio { sink, .. } => sink.is_paused(),
+            #[cfg(feature = "playback-cpal")]
+            Output::Cpal { playing, .. } => playing.as_ref().is_some_and(CpalOutput::is_paused),
+        }
+    }
+
+    /// End the playlist, dropping any tracks queued for it
+    pub fn stop(&mut self)
+    {
+        match &mut self.output
+        {
+            Output::Rodio { sink, .. } =>
+            {
+                sink.stop();
+                // Wait for the playlist to see the stop, so tracks queued after this start a new one
+                sink.sleep_until_end();
+            }
+            #[cfg(feature = "playback-cpal")]
+            Output::Cpal { playing, .. } => playing.iter_mut().for_each(CpalOutput::stop),
+        }
+        self.control.clear_queue();
+    }
+
+    /// Whether the playlist has played to its end or been stopped (true before the first one, too)
+    pub fn is_finished(&self) -> bool
+    {
+        match &self.output
+        {
+            Output::Rodio { sink, .. } => sink.empty(),
+            #[cfg(feature = "playback-cpal")]
+            Output::Cpal { playing, .. } => playing.as_ref().is_none_or(CpalOutput::is_finished),
+        }
+    }
+
+    /// Block until the playlist finishes
+    pub fn sleep_until_end(&self)
+    {
+        match &self.output
+        {
+            Output::Rodio { sink, .. } => sink.sleep_until_end(),
+            #[cfg(feature = "playback-cpal")]
+            Output::Cpal { playing, .. } => playing.iter().for_each(CpalOutput::sleep_until_end),
+        }
+    }
+
+    /// Underruns of the playlist playing (or last played) through the cpal backend; None with rodio,
+    /// which doesn't report them
+    #[cfg(feature = "playback-cpal")]
+    pub fn underruns(&self) -> Option<UnderrunStats>
+    {
+        match &self.output
+        {
+            Output::Rodio { .. } => None,
+            Output::Cpal { playing, .. } => playing.as_ref().map(CpalOutput::stats),
+        }
+    }
+
+    /// Jump to `seconds` into the current track
+    pub fn seek(&self, seconds: f64)
+    {
+        self.control.seek_to(seconds);
+    }
+
+    /// Move `seconds` forward (or back, if negative) in the current track
+    pub fn seek_by(&self, seconds: f64)
+    {
+        self.control.seek_by(seconds);
+    }
+
+    /// See [`PlaybackControl::next_track`]
+    pub fn next_track(&self)
+    {
+        self.control.next_track();
+    }
+
+    /// See [`PlaybackControl::previous_track`]
+    pub fn previous_track(&self)
+    {
+        self.control.previous_track();
+    }
+
+    pub fn position(&self) -> PlaybackPosition
+    {
+        self.control.position()
+    }
+
+    /// Decoding speed, buffer depth, and (with the cpal backend) underruns of the playlist playing
+    pub fn stats(&self) -> PlaybackStats
+    {
+        let stats = self.control.stats();
+        #[cfg(feature = "playback-cpal")]
+        if let Output::Cpal { playing: Some(output), .. } = &self.output
+        {
+            // Audio waiting in the ring has been decoded but not played, too
+            return PlaybackStats
+            {
+                buffered: stats.buffered + output.buffered(),
+                underruns: output.stats().underruns,
+                ..stats
+            };
+        }
+        stats
+    }
+
+    /// Track changes and the end of each playlist, as they happen
+    pub fn events(&self) -> Receiver<PlaybackEvent>
+    {
+        self.control.events()
+    }
+
+    /// Volume, ReplayGain, crossfade, repeat, and shuffle, which carry over from one playlist to the
+    /// next; clone it to steer playback from another thread
+    pub fn control(&self) -> &PlaybackControl
+    {
+        &self.control
+    }
+
+    /// The rodio output the player plays through, for playing other sounds on the same device
+    /// None with the cpal backend, which only holds the device while playing
+    pub fn output(&self) -> Option<&OutputStreamHandle>
+    {
+        match &self.output
+        {
+            Output::Rodio { handle, .. } => Some(handle),
+            #[cfg(feature = "playback-cpal")]
+            Output::Cpal { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! Which track of a playlist plays after which, under the repeat and shuffle modes
+//!
+//! [`PlayOrder`] is the one place playback decides what comes next, so `glc play` and the GUI
+//! step through a playlist the same way whether a track ends, is skipped, or is crossfaded into.
+//! [`read_m3u`] and [`write_m3u`] keep a playlist's files in an M3U file between sessions.
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What happens when a track ends
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Repeat
+{
+    /// Move on to the next track, and stop after the last one
+    #[default]
+    Off,
+    /// Play the current track again
+    Track,
+    /// Start the playlist over after the last track (reshuffled, when shuffling)
+    Playlist,
+}
+
+/// The order a playlist's tracks play in, and how far through it playback is
+#[derive(Clone, Debug, Default)]
+pub struct PlayOrder
+{
+    /// Track indices in the order they play
+    order: Vec<usize>,
+    /// Index into `order` of the current track; `order.len()` once the playlist has ended
+    position: usize,
+    repeat: Repeat,
+    shuffle: bool,
+    /// State of the random number generator behind shuffling
+    seed: u64,
+}
+
+impl PlayOrder
+{
+    /// `len` tracks played in order, with shuffling seeded from the clock
+    pub fn new(len: usize) -> Self
+    {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        Self::with_seed(len, nanos)
+    }
+
+    /// Like [`PlayOrder::new`], but shuffling the same way every time for a given `seed`
+    pub fn with_seed(len: usize, seed: u64) -> Self
+    {
+        Self { order: (0..len).collect(), position: 0, repeat: Repeat::Off, shuffle: false, seed }
+    }
+
+    /// Start over with `len` tracks in their own order, keeping the repeat and shuffle settings
+    pub fn reset(&mut self, len: usize)
+    {
+        self.order = (0..len).collect();
+        self.position = 0;
+    }
+
+    /// Add `count` tracks, numbered after the existing ones, to the end of the playlist
+    /// When shuffling, each lands at a random place among the tracks still to come instead
+    pub fn extend(&mut self, count: usize)
+    {
+        let first = self.order.len();
+        for track in first..first + count
+        {
+            let at = if self.shuffle && self.position < self.order.len()
+            {
+                let upcoming = (self.order.len() - self.position) as u64;
+                self.position + 1 + (next_random(&mut self.seed) % upcoming) as usize
+            }
+            else
+            {
+                self.order.len()
+            };
+            self.order.insert(at, track);
+        }
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.order.is_empty()
+    }
+
+    /// The track playing now, or None once the playlist has ended
+    pub fn current(&self) -> Option<usize>
+    {
+        self.order.get(self.position).copied()
+    }
+
+    /// Tracks played before the current one since the playlist (last) started over
+    pub fn played(&self) -> &[usize]
+    {
+        &self.order[..self.position.min(self.order.len())]
+    }
+
+    pub fn repeat(&self) -> Repeat
+    {
+        self.repeat
+    }
+
+    pub fn set_repeat(&mut self, repeat: Repeat)
+    {
+        self.repeat = repeat;
+    }
+
+    pub fn shuffle(&self) -> bool
+    {
+        self.shuffle
+    }
+
+    /// Turn shuffling on or off without interrupting the current track
+    /// Turned on, every other track follows it in a random order; turned off, the playlist
+    /// carries on in its original order from the current track
+    pub fn set_shuffle(&mut self, shuffle: bool)
+    {
+        if shuffle == self.shuffle
+        {
+            return;
+        }
+        self.shuffle = shuffle;
+
+        let Some(current) = self.current()
+        else
+        {
+            return;
+        };
+        if shuffle
+        {
+            let mut rest: Vec<usize> = (0..self.order.len()).filter(|&track| track != current).collect();
+            shuffle_tracks(&mut rest, &mut self.seed);
+            self.order = std::iter::once(current).chain(rest).collect();
+            self.position = 0;
+        }
+        else
+        {
+            self.order = (0..self.order.len()).collect();
+            self.position = current;
+        }
+    }
+
+    /// Go to the first track, shuffling the whole playlist first if shuffle is on
+    pub fn start(&mut self) -> Option<usize>
+    {
+        if self.shuffle
+        {
+            self.order = shuffled(self.order.len(), None, &mut self.seed);
+        }
+        self.position = 0;
+        self.current()
+    }
+
+    /// The track [`PlayOrder::advance`] would move to, without moving
+    pub fn peek_next(&self) -> Option<usize>
+    {
+        if self.repeat == Repeat::Track
+        {
+            return self.current();
+        }
+        let (position, order, _) = self.step(1);
+        order.as_ref().unwrap_or(&self.order).get(position).copied()
+    }
+
+    /// Move on from a track that has played to its end: to the same track when repeating it,
+    /// otherwise to the next one
+    /// Returns the new current track, or None if the playlist has ended
+    pub fn advance(&mut self) -> Option<usize>
+    {
+        if self.repeat == Repeat::Track
+        {
+            return self.current();
+        }
+        self.skip(1)
+    }
+
+    /// Move `tracks` forward (or back, if negative) in the order, as the next and previous track
+    /// controls do, even when repeating a track
+    /// Going back from the first track stays there, unless repeating the playlist wraps it around
+    /// to the last; going forward past the last ends the playlist the same way
+    /// Returns the new current track, or None if the playlist has ended
+    pub fn skip(&mut self, tracks: i64) -> Option<usize>
+    {
+        let (position, order, seed) = self.step(tracks);
+        if let Some(order) = order
+        {
+            self.order = order;
+        }
+        self.position = position;
+        self.seed = seed;
+        self.current()
+    }
+
+    /// Make `track` current wherever it falls in the order, as picking it from a playlist does
+    /// Returns the new current track, or None (leaving the order as it was) if there is no such track
+    pub fn jump_to(&mut self, track: usize) -> Option<usize>
+    {
+        self.position = self.order.iter().position(|&t| t == track)?;
+        self.current()
+    }
+
+    /// Where moving `steps` through the order lands: the position, the order it is in if passing
+    /// the end starts a new, reshuffled pass, and the generator state after shuffling it
+    fn step(&self, steps: i64) -> (usize, Option<Vec<usize>>, u64)
+    {
+        let len = self.order.len() as i64;
+        let target = self.position as i64 + steps;
+        if (0..len).contains(&target)
+        {
+            return (target as usize, None, self.seed);
+        }
+        if self.repeat != Repeat::Playlist || len == 0
+        {
+            return (target.clamp(0, len) as usize, None, self.seed);
+        }
+
+        let position = target.rem_euclid(len) as usize;
+        if target < 0 || !self.shuffle
+        {
+            return (position, None, self.seed);
+        }
+        let mut seed = self.seed;
+        let order = shuffled(self.order.len(), self.current(), &mut seed);
+        (position, Some(order), seed)
+    }
+}
+
+/// A random order of `len` tracks that doesn't start with `last`, so passes through a repeated
+/// playlist never play the same track twice in a row
+fn shuffled(len: usize, last: Option<usize>, seed: &mut u64) -> Vec<usize>
+{
+    let mut order: Vec<usize> = (0..len).collect();
+    shuffle_tracks(&mut order, seed);
+    if len > 1 && order.first().copied() == last
+    {
+        order.swap(0, len - 1);
+    }
+    order
+}
+
+/// Fisher-Yates shuffle drawing from [`next_random`]
+fn shuffle_tracks(tracks: &mut [usize], seed: &mut u64)
+{
+    for i in (1..tracks.len()).rev()
+    {
+        let j = (next_random(seed) % (i as u64 + 1)) as usize;
+        tracks.swap(i, j);
+    }
+}
+
+/// SplitMix64: plenty for picking track orders, and any seed (even 0) works
+pub(crate) fn next_random(seed: &mut u64) -> u64
+{
+    *seed = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *seed;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Files listed in the M3U playlist at `path`, in order; comment and `#EXT` lines are skipped,
+/// and relative entries are taken relative to the playlist's directory
+pub fn read_m3u(path: &Path) -> Result<Vec<PathBuf>>
+{
+    let text = std::fs::read_to_string(path)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    Ok(text.trim_start_matches('\u{feff}')
+           .lines()
+           .map(str::trim)
+           .filter(|line| !line.is_empty() && !line.starts_with('#'))
+           .map(|line| dir.join(line))
+           .collect())
+}
+
+/// Save `files` as an M3U playlist at `path`, writing those under the playlist's directory
+/// relative to it so the playlist can move along with them
+pub fn write_m3u(path: &Path, files: &[PathBuf]) -> Result<()>
+{
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut text = String::from("#EXTM3U\n");
+    for file in files
+    {
+        let entry = if dir.as_os_str().is_empty() { file } else { file.strip_prefix(dir).unwrap_or(file) };
+        text.push_str(&entry.to_string_lossy());
+        text.push('\n');
+    }
+    std::fs::write(path, text)?;
+    Ok(())
+}
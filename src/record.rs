@@ -0,0 +1,223 @@
+//! Live capture from a cpal input device, encoded to GLC as it arrives
+//!
+//! The device callback copies whole frames into a [`SampleRing`] and never blocks; [`Recorder::poll`]
+//! drains the ring into a [`GlcEncoder`] on the caller's thread. If encoding falls further behind
+//! than the ring holds, the callback drops the frames that don't fit and counts them in
+//! [`OverrunStats`].
+use anyhow::{anyhow, Result};
+use rodio::cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use rodio::cpal::{self, FromSample, Sample, SampleFormat, SizedSample, StreamConfig};
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use crate::audio::EncodeOptions;
+use crate::container::GlcEncoder;
+use crate::cpal_output::SampleRing;
+
+/// Audio the ring holds for the encoder, so a slow poll or a slow frame doesn't lose any
+const RING_DURATION: Duration = Duration::from_secs(2);
+
+/// The input device named `name`, listing the available ones if there is no such device
+pub fn find_input_device(name: &str) -> Result<cpal::Device>
+{
+    let host = cpal::default_host();
+    let devices: Vec<cpal::Device> = host.input_devices()
+                                         .map_err(|e| anyhow!("Failed to list audio inputs: {}", e))?
+                                         .collect();
+    let names: Vec<String> = devices.iter().filter_map(|d| d.name().ok()).collect();
+    devices.into_iter()
+           .find(|d| d.name().is_ok_and(|n| n == name))
+           .ok_or_else(|| anyhow!("No audio input named '{}' (available: {})", name, names.join(", ")))
+}
+
+/// How often the encoder fell too far behind the device to keep everything it captured
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OverrunStats
+{
+    /// Times the device callback ran
+    pub callbacks: u64,
+    /// Callbacks that found the ring too full for all they captured
+    pub overruns: u64,
+    /// Frames dropped from the recording as a result
+    pub dropped_frames: u64,
+}
+
+/// State shared by the device callback and the [`Recorder`] owning it
+struct Shared
+{
+    ring: SampleRing,
+    callbacks: AtomicU64,
+    overruns: AtomicU64,
+    dropped_frames: AtomicU64,
+}
+
+/// A recording in progress from a cpal input stream into a GLC stream on `W`
+pub struct Recorder<W: Write>
+{
+    stream: cpal::Stream,
+    shared: Arc<Shared>,
+    encoder: GlcEncoder<W>,
+    /// Samples taken from the ring, reused between polls
+    block: Vec<f32>,
+    sample_rate: u32,
+    channels: u16,
+    /// Frames handed to the encoder so far
+    frames: u64,
+    /// Highest absolute sample on each channel since the last [`Recorder::take_peaks`]
+    peaks: Vec<f32>,
+}
+
+impl<W: Write> Recorder<W>
+{
+    /// Start capturing from the input device named `device`, or the default one, in its default
+    /// format, writing the GLC stream header to `writer` straight away
+    /// The encoder is set up from `options` (see [`EncodeOptions::encoder`]); their normalization
+    /// and ffmpeg settings don't apply
+    pub fn start(device: Option<&str>, options: &EncodeOptions, writer: W) -> Result<Self>
+    {
+        let device = match device
+        {
+            Some(name) => find_input_device(name)?,
+            None => cpal::default_host().default_input_device().ok_or_else(|| anyhow!("No default audio input"))?,
+        };
+        let supported = device.default_input_config().map_err(|e| anyhow!("Failed to query audio input: {}", e))?;
+        let config: StreamConfig = supported.config();
+        let (sample_rate, channels) = (config.sample_rate.0, config.channels);
+
+        let encoder = GlcEncoder::with_encoder(writer, options.encoder(sample_rate)?, channels)?;
+
+        let capacity = (RING_DURATION.as_secs_f64() * sample_rate as f64) as usize * channels as usize;
+        let shared = Arc::new(Shared
+        {
+            ring: SampleRing::new(capacity),
+            callbacks: AtomicU64::new(0),
+            overruns: AtomicU64::new(0),
+            dropped_frames: AtomicU64::new(0),
+        });
+
+        let stream = match supported.sample_format()
+        {
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, shared.clone()),
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, shared.clone()),
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, shared.clone()),
+            SampleFormat::I32 => build_stream::<i32>(&device, &config, shared.clone()),
+            format => Err(anyhow!("Unsupported audio input sample format {:?}", format)),
+        }?;
+        stream.play().map_err(|e| anyhow!("Failed to start audio input: {}", e))?;
+
+        Ok(Self
+        {
+            stream,
+            shared,
+            encoder,
+            block: vec![0.0; capacity],
+            sample_rate,
+            channels,
+            frames: 0,
+            peaks: vec![0.0; channels as usize],
+        })
+    }
+
+    /// The encoder the captured audio goes to, for setting its tags
+    pub fn encoder_mut(&mut self) -> &mut GlcEncoder<W>
+    {
+        &mut self.encoder
+    }
+
+    /// Sample rate the device captures at
+    pub fn sample_rate(&self) -> u32
+    {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u16
+    {
+        self.channels
+    }
+
+    /// Seconds of audio encoded so far
+    pub fn elapsed(&self) -> f64
+    {
+        self.frames as f64 / self.sample_rate as f64
+    }
+
+    /// Encode whatever the device has captured since the last poll
+    /// Call this at least every second or so; the ring only holds a couple of seconds
+    pub fn poll(&mut self) -> Result<()>
+    {
+        // The callback only pushes whole frames, so this always takes whole frames too
+        let read = self.shared.ring.pop(&mut self.block);
+        let samples = &self.block[..read];
+        for frame in samples.chunks_exact(self.channels as usize)
+        {
+            for (peak, sample) in self.peaks.iter_mut().zip(frame)
+            {
+                *peak = peak.max(sample.abs());
+            }
+        }
+        self.frames += (read / self.channels as usize) as u64;
+        self.encoder.write_samples(samples)
+    }
+
+    /// The loudest sample on each channel since the last call, for a level meter
+    pub fn take_peaks(&mut self) -> Vec<f32>
+    {
+        std::mem::replace(&mut self.peaks, vec![0.0; self.channels as usize])
+    }
+
+    pub fn stats(&self) -> OverrunStats
+    {
+        OverrunStats
+        {
+            callbacks: self.shared.callbacks.load(Ordering::Relaxed),
+            overruns: self.shared.overruns.load(Ordering::Relaxed),
+            dropped_frames: self.shared.dropped_frames.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Stop capturing, encode what is left, write the footer, and return the underlying writer
+    pub fn finish(mut self) -> Result<W>
+    {
+        self.stream.pause().ok();
+        self.poll()?;
+        self.encoder.finish()
+    }
+}
+
+/// Open an input stream of sample type `T` whose callback copies what it captures into `shared.ring`
+fn build_stream<T>(device: &cpal::Device, config: &StreamConfig, shared: Arc<Shared>) -> Result<cpal::Stream>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = config.channels.max(1) as usize;
+    let mut scratch = vec![0.0f32; shared.ring.capacity()];
+    let callback = move |data: &[T], _: &cpal::InputCallbackInfo|
+    {
+        shared.callbacks.fetch_add(1, Ordering::Relaxed);
+
+        // The device may hand over more than the ring holds; this only allocates if so
+        if scratch.len() < data.len()
+        {
+            scratch.resize(data.len(), 0.0);
+        }
+        for (out, &sample) in scratch.iter_mut().zip(data)
+        {
+            *out = f32::from_sample(sample);
+        }
+
+        // Only whole frames go in, so the channels stay in step after a drop; the encoder only ever
+        // makes more room meanwhile, never less
+        let room = shared.ring.capacity() - shared.ring.len();
+        let count = data.len().min(room - room % channels);
+        shared.ring.push(&scratch[..count]);
+        if count < data.len()
+        {
+            shared.overruns.fetch_add(1, Ordering::Relaxed);
+            shared.dropped_frames.fetch_add(((data.len() - count) / channels) as u64, Ordering::Relaxed);
+        }
+    };
+    device.build_input_stream(config, callback, |e| eprintln!("Audio input error: {}", e), None)
+          .map_err(|e| anyhow!("Failed to open audio input stream: {}", e))
+}
@@ -0,0 +1,259 @@
+//! `glc serve`: the player run headless, steered by JSON commands over a socket
+//!
+//! Each line a client sends is one [`Request`], and each gets one line back: a [`Response`] holding
+//! the [`Status`] after the request, or an error. For example, with `nc localhost 7700`:
+//!
+//! ```text
+//! {"command": "enqueue", "path": "/music/01.glc"}
+//! {"command": "play"}
+//! {"command": "seek", "seconds": 30}
+//! {"command": "status"}
+//! ```
+use anyhow::Result;
+use crossbeam_channel::{unbounded, Sender};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::thread;
+use crate::player::Player;
+
+/// A command from a client
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request
+{
+    /// Add a `.glc` file (a path or an `http://` URL) to the end of the playlist
+    Enqueue
+    {
+        path: PathBuf,
+    },
+    /// Start the tracks enqueued so far, or resume if paused
+    Play,
+    Pause,
+    /// End playback and forget the playlist
+    Stop,
+    /// Jump to `seconds` into the current track, or move that far from the current position if `relative`
+    Seek
+    {
+        seconds: f64,
+        #[serde(default)]
+        relative: bool,
+    },
+    Next,
+    Previous,
+    Status,
+}
+
+/// Whether anything is playing
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum State
+{
+    Playing,
+    Paused,
+    Stopped,
+}
+
+/// What the player is doing, sent back after every request
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Status
+{
+    pub state: State,
+    /// Index of the current track in `playlist`, or None when stopped
+    pub track: Option<usize>,
+    /// Seconds into the current track
+    pub elapsed: f64,
+    pub duration: f64,
+    /// Seconds into the playlist
+    pub total_elapsed: f64,
+    pub total_duration: f64,
+    /// The tracks playing now, in the order they were enqueued
+    pub playlist: Vec<PathBuf>,
+    /// Tracks enqueued while stopped, which start playing on the next `play`
+    pub queued: Vec<PathBuf>,
+}
+
+/// One line of reply to a client
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Response
+{
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Response
+{
+    pub fn ok(status: Status) -> Self
+    {
+        Self { ok: true, status: Some(status), error: None }
+    }
+
+    pub fn error(message: String) -> Self
+    {
+        Self { ok: false, status: None, error: Some(message) }
+    }
+}
+
+/// A [`Player`] plus the paths behind its tracks, for reporting them back to clients
+pub struct Server
+{
+    player: Player,
+    playlist: Vec<PathBuf>,
+    queued: Vec<PathBuf>,
+}
+
+impl Server
+{
+    pub fn new(player: Player) -> Self
+    {
+        Self { player, playlist: Vec::new(), queued: Vec::new() }
+    }
+
+    /// Carry out `request`, returning the status afterwards
+    pub fn handle(&mut self, request: Request) -> Result<Status>
+    {
+        match request
+        {
+            Request::Enqueue { path } =>
+            {
+                self.player.queue(&path)?;
+                // A playing playlist takes the track on; otherwise it waits for the next one
+                if self.player.is_finished()
+                {
+                    self.queued.push(path);
+                }
+                else
+                {
+                    self.playlist.push(path);
+                }
+            }
+            Request::Play =>
+            {
+                if self.player.is_finished()
+                {
+                    self.playlist = std::mem::take(&mut self.queued);
+                }
+                self.player.play()?;
+            }
+            Request::Pause => self.player.pause(),
+            Request::Stop =>
+            {
+                self.player.stop();
+                self.playlist.clear();
+                self.queued.clear();
+            }
+            Request::Seek { seconds, relative: false } => self.player.seek(seconds),
+            Request::Seek { seconds, relative: true } => self.player.seek_by(seconds),
+            Request::Next => self.player.next_track(),
+            Request::Previous => self.player.previous_track(),
+            Request::Status => {}
+        }
+        Ok(self.status())
+    }
+
+    pub fn status(&self) -> Status
+    {
+        let playing = !self.player.is_finished();
+        let position = self.player.position();
+        Status
+        {
+            state: match (playing, self.player.is_paused())
+            {
+                (false, _) => State::Stopped,
+                (true, true) => State::Paused,
+                (true, false) => State::Playing,
+            },
+            track: playing.then_some(position.track),
+            elapsed: if playing { position.elapsed } else { 0.0 },
+            duration: if playing { position.duration } else { 0.0 },
+            total_elapsed: if playing { position.total_elapsed } else { 0.0 },
+            total_duration: if playing { position.total_duration } else { 0.0 },
+            playlist: if playing { self.playlist.clone() } else { Vec::new() },
+            queued: self.queued.clone(),
+        }
+    }
+}
+
+/// Serve the clients `accept` waits for until the process is killed, each on a thread of its own
+/// The player can't leave this thread, so requests come back here to be carried out in turn
+pub fn serve<S, A>(mut server: Server, mut accept: A) -> Result<()>
+where
+    S: Read + Write + Send + 'static,
+    A: FnMut() -> io::Result<S> + Send + 'static,
+{
+    let (requests, receiver) = unbounded::<(Request, Sender<Response>)>();
+    thread::spawn(move ||
+    {
+        loop
+        {
+            match accept()
+            {
+                Ok(stream) =>
+                {
+                    let requests = requests.clone();
+                    thread::spawn(move || serve_client(stream, requests));
+                }
+                Err(e) => eprintln!("Error accepting a client: {}", e),
+            }
+        }
+    });
+
+    for (request, reply) in receiver
+    {
+        let response = match server.handle(request)
+        {
+            Ok(status) => Response::ok(status),
+            Err(e) => Response::error(e.to_string()),
+        };
+        // The client may have hung up meanwhile
+        reply.send(response).ok();
+    }
+    Ok(())
+}
+
+/// Answer each line `stream` sends until it disconnects
+fn serve_client<S: Read + Write>(stream: S, requests: Sender<(Request, Sender<Response>)>)
+{
+    let mut stream = BufReader::new(stream);
+    let mut line = String::new();
+    loop
+    {
+        line.clear();
+        match stream.read_line(&mut line)
+        {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        if line.trim().is_empty()
+        {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Request>(&line)
+        {
+            Ok(request) =>
+            {
+                let (reply, response) = unbounded();
+                if requests.send((request, reply)).is_err()
+                {
+                    return;
+                }
+                response.recv().unwrap_or_else(|_| Response::error("The player has stopped".to_string()))
+            }
+            Err(e) => Response::error(format!("Invalid request: {}", e)),
+        };
+
+        let Ok(json) = serde_json::to_string(&response)
+        else
+        {
+            return;
+        };
+        if writeln!(stream.get_mut(), "{}", json).is_err()
+        {
+            return;
+        }
+    }
+}
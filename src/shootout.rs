@@ -0,0 +1,225 @@
+//! Encoder tuning harness: GLC at several qualities against reference codecs (`glc shootout`)
+//!
+//! Each [`Contender`] encodes a track and decodes it again, and [`Contender::run`] scores the
+//! result against the original with [`compare`]: its size and bitrate beside its SNR, A-weighted
+//! SNR, noise-to-mask ratio, and perceptual grade. GLC runs in memory; Opus and Vorbis run through
+//! their reference command-line tools (`opusenc`/`opusdec` and `oggenc`/`oggdec`), which must be on
+//! the `PATH`.
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use crate::audio::{export_to_wav, load_audio_file_lossless};
+use crate::codec::{Decoder, Encoder};
+use crate::compare::compare;
+use crate::container::write_encoded;
+
+/// What `glc shootout` compares when no contenders are given
+pub const DEFAULT_CONTENDERS: &[Contender] = &[
+    Contender::Glc(0.3), Contender::Glc(0.5), Contender::Glc(0.7), Contender::Glc(1.0),
+    Contender::Opus(64), Contender::Opus(96), Contender::Opus(128),
+    Contender::Vorbis(2.0), Contender::Vorbis(4.0), Contender::Vorbis(6.0),
+];
+
+/// A codec at one setting
+/// Written and parsed as `codec:setting`, e.g. `glc:0.5`, `opus:96`, or `vorbis:4`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Contender
+{
+    /// GLC at a quality (see [`Encoder::set_quality`])
+    Glc(f32),
+    /// Opus at a bitrate in kbit/s
+    Opus(u32),
+    /// Vorbis at an `oggenc` quality, -1 to 10
+    Vorbis(f32),
+}
+
+/// How one contender did on one track
+/// SNRs are infinite when there is no error (serialized to JSON as null)
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Score
+{
+    /// The contender, as `codec:setting`
+    pub contender: String,
+    /// Size of the encoded file
+    pub bytes: u64,
+    /// Length of the original in seconds
+    pub seconds: f64,
+    pub kbps: f64,
+    pub snr_db: f64,
+    pub weighted_snr_db: f64,
+    pub noise_to_mask_db: f64,
+    /// Objective difference grade, 0 (transparent) to -4 (very annoying)
+    pub odg: f64,
+}
+
+impl Contender
+{
+    /// External programs this contender runs, none for GLC
+    pub fn tools(&self) -> &'static [&'static str]
+    {
+        match self
+        {
+            Contender::Glc(_) => &[],
+            Contender::Opus(_) => &["opusenc", "opusdec"],
+            Contender::Vorbis(_) => &["oggenc", "oggdec"],
+        }
+    }
+
+    /// Whether every tool this contender needs can be run
+    pub fn is_available(&self) -> bool
+    {
+        self.tools().iter().all(|tool|
+        {
+            Command::new(tool).arg("--version").stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null())
+                              .status().is_ok()
+        })
+    }
+
+    /// Encode interleaved `samples` and decode them again, returning the encoded size in bytes
+    /// and the decoded samples; the reference codecs' files are written to `work_dir`
+    pub fn encode_decode(&self, samples: &[f32], sample_rate: u32, channels: u16, work_dir: &Path) -> Result<(u64, Vec<f32>)>
+    {
+        if let Contender::Glc(quality) = *self
+        {
+            let mut encoder = Encoder::new(sample_rate);
+            encoder.set_quality(quality);
+            let encoded = encoder.encode(samples, channels)?;
+            let bytes = write_encoded(Vec::new(), &encoded)?.len() as u64;
+            let decoded = Decoder::new(channels as usize, sample_rate).decode(&encoded, None)?;
+            return Ok((bytes, decoded));
+        }
+
+        let stem = self.to_string().replace(':', "-");
+        let source = work_dir.join(format!("{}-source.wav", stem));
+        let encoded = work_dir.join(format!("{}.{}", stem, if matches!(self, Contender::Opus(_)) { "opus" } else { "ogg" }));
+        let decoded = work_dir.join(format!("{}-decoded.wav", stem));
+        let [encoder, decoder] = self.tools() else { unreachable!("reference codecs have an encoder and a decoder") };
+        let (mut encode, mut decode) = (Command::new(encoder), Command::new(decoder));
+        match *self
+        {
+            Contender::Glc(_) => {}
+            Contender::Opus(kbps) =>
+            {
+                encode.args(["--quiet", "--bitrate", &kbps.to_string()]).arg(&source).arg(&encoded);
+                // Opus always decodes at 48 kHz unless told otherwise
+                decode.args(["--quiet", "--rate", &sample_rate.to_string()]).arg(&encoded).arg(&decoded);
+            }
+            Contender::Vorbis(quality) =>
+            {
+                encode.args(["--quiet", "-q", &quality.to_string(), "-o"]).arg(&encoded).arg(&source);
+                decode.args(["--quiet", "-o"]).arg(&decoded).arg(&encoded);
+            }
+        }
+
+        export_to_wav(&source, samples, sample_rate, channels)?;
+        run_tool(&mut encode)?;
+        run_tool(&mut decode)?;
+
+        let bytes = std::fs::metadata(&encoded)?.len();
+        let (output, output_rate, output_channels) = load_audio_file_lossless(&decoded)?;
+        for path in [&source, &encoded, &decoded]
+        {
+            std::fs::remove_file(path).ok();
+        }
+        if output_rate != sample_rate || output_channels != channels
+        {
+            return Err(anyhow!("{} decoded to {} Hz, {} channels instead of {} Hz, {} channels",
+                               decoder, output_rate, output_channels, sample_rate, channels));
+        }
+        Ok((bytes, output))
+    }
+
+    /// Encode and decode interleaved `samples` as [`encode_decode`](Self::encode_decode) does,
+    /// and score the result
+    pub fn run(&self, samples: &[f32], sample_rate: u32, channels: u16, work_dir: &Path) -> Result<Score>
+    {
+        let (bytes, decoded) = self.encode_decode(samples, sample_rate, channels, work_dir)?;
+        let quality = compare(samples, &decoded, sample_rate, channels);
+        let seconds = samples.len() as f64 / channels.max(1) as f64 / sample_rate.max(1) as f64;
+        Ok(Score
+        {
+            contender: self.to_string(),
+            bytes,
+            seconds,
+            kbps: if seconds > 0.0 { bytes as f64 * 8.0 / seconds / 1000.0 } else { 0.0 },
+            snr_db: quality.snr_db,
+            weighted_snr_db: quality.metrics.weighted_snr_db,
+            noise_to_mask_db: quality.metrics.noise_to_mask_db,
+            odg: quality.metrics.odg,
+        })
+    }
+}
+
+impl fmt::Display for Contender
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self
+        {
+            Contender::Glc(quality) => write!(f, "glc:{}", quality),
+            Contender::Opus(kbps) => write!(f, "opus:{}", kbps),
+            Contender::Vorbis(quality) => write!(f, "vorbis:{}", quality),
+        }
+    }
+}
+
+impl FromStr for Contender
+{
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self>
+    {
+        let (codec, setting) = text.split_once(':').ok_or_else(|| anyhow!("expected CODEC:SETTING, got '{}'", text))?;
+        let number = |range: std::ops::RangeInclusive<f32>|
+        {
+            setting.parse::<f32>().ok().filter(|value| range.contains(value))
+                   .ok_or_else(|| anyhow!("{} setting must be a number from {} to {}, got '{}'", codec, range.start(), range.end(), setting))
+        };
+        match codec.to_ascii_lowercase().as_str()
+        {
+            "glc" => Ok(Contender::Glc(number(crate::codec::QUALITY_RANGE)?)),
+            "opus" => Ok(Contender::Opus(number(6.0..=510.0)? as u32)),
+            "vorbis" => Ok(Contender::Vorbis(number(-1.0..=10.0)?)),
+            _ => Err(anyhow!("unknown codec '{}' (expected glc, opus, or vorbis)", codec)),
+        }
+    }
+}
+
+/// Scores for one contender over a whole corpus: sizes and lengths summed, so the bitrate is
+/// that of the corpus as a whole, and the other measures averaged over its tracks
+/// Returns None if `scores` is empty
+pub fn combine(scores: &[Score]) -> Option<Score>
+{
+    let first = scores.first()?;
+    let mean = |measure: fn(&Score) -> f64| scores.iter().map(measure).sum::<f64>() / scores.len() as f64;
+    let bytes = scores.iter().map(|score| score.bytes).sum::<u64>();
+    let seconds = scores.iter().map(|score| score.seconds).sum::<f64>();
+    Some(Score
+    {
+        contender: first.contender.clone(),
+        bytes,
+        seconds,
+        kbps: if seconds > 0.0 { bytes as f64 * 8.0 / seconds / 1000.0 } else { 0.0 },
+        snr_db: mean(|score| score.snr_db),
+        weighted_snr_db: mean(|score| score.weighted_snr_db),
+        noise_to_mask_db: mean(|score| score.noise_to_mask_db),
+        odg: mean(|score| score.odg),
+    })
+}
+
+/// Run an external tool, failing with its error output if it fails
+fn run_tool(command: &mut Command) -> Result<()>
+{
+    let name = command.get_program().to_string_lossy().into_owned();
+    let output = command.stdin(Stdio::null())
+                        .output()
+                        .map_err(|e| anyhow!("Failed to run {}: {}", name, e))?;
+    if !output.status.success()
+    {
+        return Err(anyhow!("{} failed: {}", name, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
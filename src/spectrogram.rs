@@ -0,0 +1,159 @@
+//! Spectrograms of whole tracks, for seeing what the encoder kept and what it threw away
+//!
+//! A [`Spectrogram`] averages the power of Hann-windowed FFT frames of the mono mix into a
+//! fixed number of columns across the track, so an original and its decoded version computed
+//! the same way line up column for column and bin for bin.
+use std::f64::consts::TAU;
+
+/// FFT length, giving 1024 bins about 21.5 Hz apart at 44.1 kHz
+pub const SPECTROGRAM_FFT_SIZE: usize = 2048;
+
+/// Lowest level a bin is given, in dB relative to a full-scale sine; silence sits here
+pub const SPECTROGRAM_FLOOR_DB: f32 = -120.0;
+
+/// Power spectra in columns across a track
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spectrogram
+{
+    /// Level of each bin in dB, `bins()` per column, lowest frequency first
+    pub columns: Vec<Vec<f32>>,
+    pub sample_rate: u32,
+}
+
+impl Spectrogram
+{
+    /// Spectrogram of interleaved `samples` in `columns` columns, each averaging the FFT frames
+    /// (half overlapped) that start within its share of the track
+    pub fn new(samples: &[f32], sample_rate: u32, channels: u16, columns: usize) -> Self
+    {
+        let channels = channels.max(1) as usize;
+        let mono: Vec<f32> = samples.chunks_exact(channels).map(|frame| frame.iter().sum::<f32>() / channels as f32).collect();
+        let bins = SPECTROGRAM_FFT_SIZE / 2;
+        let (window, reference) = hann_window();
+
+        let mut power = vec![vec![0.0f64; bins]; columns];
+        let mut counts = vec![0usize; columns];
+        let (mut re, mut im) = (vec![0.0; SPECTROGRAM_FFT_SIZE], vec![0.0; SPECTROGRAM_FFT_SIZE]);
+        let mut start = 0;
+        while columns > 0 && start < mono.len()
+        {
+            for i in 0..SPECTROGRAM_FFT_SIZE
+            {
+                re[i] = mono.get(start + i).copied().unwrap_or(0.0) as f64 * window[i];
+                im[i] = 0.0;
+            }
+            fft(&mut re, &mut im);
+
+            let column = start * columns / mono.len();
+            for (bin, power) in power[column].iter_mut().enumerate()
+            {
+                *power += re[bin] * re[bin] + im[bin] * im[bin];
+            }
+            counts[column] += 1;
+            start += SPECTROGRAM_FFT_SIZE / 2;
+        }
+
+        let columns = power.into_iter().zip(counts).map(|(column, count)|
+        {
+            column.into_iter().map(|power| level_db(if count > 0 { power / count as f64 / reference } else { 0.0 })).collect()
+        }).collect();
+        Self { columns, sample_rate }
+    }
+
+    pub fn bins(&self) -> usize
+    {
+        SPECTROGRAM_FFT_SIZE / 2
+    }
+
+    /// Centre frequency of `bin` in Hz
+    pub fn bin_frequency(&self, bin: usize) -> f32
+    {
+        bin as f32 * self.sample_rate as f32 / SPECTROGRAM_FFT_SIZE as f32
+    }
+
+    /// How far each bin of `decoded` falls below this one, in dB: what encoding took away
+    /// (negative where it added noise); both must have the same number of columns
+    pub fn difference(&self, decoded: &Spectrogram) -> Vec<Vec<f32>>
+    {
+        self.columns.iter().zip(&decoded.columns)
+            .map(|(original, decoded)| original.iter().zip(decoded).map(|(a, b)| a - b).collect())
+            .collect()
+    }
+}
+
+/// Level of each bin of a single FFT frame over the last [`SPECTROGRAM_FFT_SIZE`] samples of mono
+/// `samples` (zero-padded at the start if there are fewer), in dB as in a spectrogram; for live
+/// spectrum views
+pub fn spectrum(samples: &[f32]) -> Vec<f32>
+{
+    let (window, reference) = hann_window();
+    let samples = &samples[samples.len().saturating_sub(SPECTROGRAM_FFT_SIZE)..];
+    let padding = SPECTROGRAM_FFT_SIZE - samples.len();
+    let mut re: Vec<f64> = (0..SPECTROGRAM_FFT_SIZE)
+        .map(|i| if i < padding { 0.0 } else { samples[i - padding] as f64 * window[i] })
+        .collect();
+    let mut im = vec![0.0; SPECTROGRAM_FFT_SIZE];
+    fft(&mut re, &mut im);
+    re.iter().zip(&im).take(SPECTROGRAM_FFT_SIZE / 2).map(|(re, im)| level_db((re * re + im * im) / reference)).collect()
+}
+
+/// Hann window of the FFT's length, and the power a full-scale sine reaches in one bin through it
+pub(crate) fn hann_window() -> (Vec<f64>, f64)
+{
+    let window: Vec<f64> = (0..SPECTROGRAM_FFT_SIZE)
+        .map(|i| 0.5 - 0.5 * (TAU * i as f64 / SPECTROGRAM_FFT_SIZE as f64).cos())
+        .collect();
+    // A full-scale sine peaks at a quarter of the window's sum in one bin
+    let reference = (window.iter().sum::<f64>() / 2.0).powi(2);
+    (window, reference)
+}
+
+/// Power relative to a full-scale sine in dB, no lower than [`SPECTROGRAM_FLOOR_DB`]
+pub(crate) fn level_db(power: f64) -> f32
+{
+    (10.0 * power.max(1e-30).log10()).max(SPECTROGRAM_FLOOR_DB as f64) as f32
+}
+
+/// In-place radix-2 FFT; the length must be a power of two
+pub(crate) fn fft(re: &mut [f64], im: &mut [f64])
+{
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n
+    {
+        let mut bit = n >> 1;
+        while j & bit != 0
+        {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j
+        {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n
+    {
+        let (step_re, step_im) = ((TAU / len as f64).cos(), -(TAU / len as f64).sin());
+        for start in (0..n).step_by(len)
+        {
+            let (mut w_re, mut w_im) = (1.0, 0.0);
+            for k in 0..len / 2
+            {
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * w_re - im[b] * w_im;
+                let t_im = re[b] * w_im + im[b] * w_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+                (w_re, w_im) = (w_re * step_re - w_im * step_im, w_re * step_im + w_im * step_re);
+            }
+        }
+        len <<= 1;
+    }
+}
@@ -0,0 +1,359 @@
+//! Symphonia `FormatReader` and `Decoder` implementations for GLC
+//!
+//! Register them alongside Symphonia's defaults so any Symphonia-based player can open `.glc` files:
+//! ```ignore
+//! let mut codecs = symphonia::core::codecs::CodecRegistry::new();
+//! symphonia::default::register_enabled_codecs(&mut codecs);
+//! gapless_lossy_codec::symphonia_glc::register_codecs(&mut codecs);
+//!
+//! let mut probe = symphonia::core::probe::Probe::default();
+//! symphonia::default::register_enabled_formats(&mut probe);
+//! gapless_lossy_codec::symphonia_glc::register_formats(&mut probe);
+//! ```
+//!
+//! Each GLC frame becomes one packet of one hop (1024 samples, or fewer for low-delay streams),
+//! followed by one empty packet that flushes the final overlap. The hop is passed to the decoder as
+//! `CodecParameters::max_frames_per_packet`. The encoder delay and padding are reported through
+//! `CodecParameters::delay`/`padding`, and applied as packet trims when gapless mode is enabled.
+use std::io;
+use symphonia::core::audio::{AsAudioBufferRef, AudioBuffer, AudioBufferRef, Channels, Signal, SignalSpec};
+use symphonia::core::codecs::{
+    CodecDescriptor, CodecParameters, CodecRegistry, CodecType, Decoder as SymphoniaDecoder,
+    DecoderOptions, FinalizeResult, decl_codec_type,
+};
+use symphonia::core::errors::{Error, Result, unsupported_error};
+use symphonia::core::formats::{Cue, FormatOptions, FormatReader, Packet, SeekMode, SeekTo, SeekedTo, Track};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{Metadata, MetadataLog};
+use symphonia::core::probe::{Descriptor, Instantiate, Probe, QueryDescriptor};
+use symphonia::core::sample::SampleFormat;
+use symphonia::core::units::TimeBase;
+use crate::codec::{Decoder, OverlapState, HOP_SIZE};
+use crate::container::{deserialize_frame, GlcDecoder, GLC_SIGNATURE};
+
+/// Symphonia codec type for GLC
+pub const CODEC_TYPE_GLC: CodecType = decl_codec_type(b"glc");
+
+/// Register the GLC decoder with a Symphonia codec registry
+pub fn register_codecs(registry: &mut CodecRegistry)
+{
+    registry.register_all::<GlcSymphoniaDecoder>();
+}
+
+/// Register the GLC format reader with a Symphonia probe
+pub fn register_formats(probe: &mut Probe)
+{
+    probe.register_all::<GlcFormatReader>();
+}
+
+fn to_symphonia_error(e: anyhow::Error) -> Error
+{
+    Error::IoError(io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+/// Symphonia `FormatReader` that turns GLC frames into packets
+pub struct GlcFormatReader
+{
+    glc: GlcDecoder<MediaSourceStream>,
+    tracks: Vec<Track>,
+    metadata: MetadataLog,
+    /// Index of the next frame to return; `frame_count()` means the flush packet is next
+    next_frame: usize,
+    gapless: bool,
+}
+
+impl GlcFormatReader
+{
+    /// Encoder delay in samples per channel
+    fn delay(&self) -> u64
+    {
+        self.glc.gapless_info().encoder_delay as u64
+    }
+
+    /// Samples per channel in each packet
+    fn hop(&self) -> u64
+    {
+        self.glc.header().hop_size as u64
+    }
+
+    /// Original length in samples per channel
+    fn original_frames(&self) -> u64
+    {
+        let channels = self.glc.header().channels.max(1) as u64;
+        self.glc.gapless_info().original_length / channels
+    }
+
+    /// Convert an untrimmed timestamp into the timeline exposed to Symphonia
+    fn to_public_ts(&self, ts: u64) -> u64
+    {
+        if self.gapless { ts.saturating_sub(self.delay()) } else { ts }
+    }
+}
+
+impl QueryDescriptor for GlcFormatReader
+{
+    fn query() -> &'static [Descriptor]
+    {
+        &[Descriptor
+        {
+            short_name: "glc",
+            long_name: "Gapless Lossy Codec",
+            extensions: &["glc"],
+            mime_types: &["audio/x-glc"],
+            markers: &[&GLC_SIGNATURE],
+            score: Self::score,
+            inst: Instantiate::Format(|source, options| Ok(Box::new(GlcFormatReader::try_new(source, options)?))),
+        }]
+    }
+
+    fn score(_context: &[u8]) -> u8
+    {
+        255
+    }
+}
+
+impl FormatReader for GlcFormatReader
+{
+    fn try_new(source: MediaSourceStream, options: &FormatOptions) -> Result<Self>
+    {
+        let glc = GlcDecoder::new(source).map_err(to_symphonia_error)?;
+
+        let header = glc.header().clone();
+        let gapless_info = glc.gapless_info().clone();
+        let ch = header.channels.max(1) as u64;
+
+        // Untrimmed output is one hop per frame plus the final overlap flush
+        let total_frames = (glc.frame_count() as u64 + 1) * header.hop_size as u64;
+        let original_frames = gapless_info.original_length / ch;
+        let delay = gapless_info.encoder_delay;
+        let padding = total_frames.saturating_sub(delay as u64 + original_frames) as u32;
+        let n_frames = if options.enable_gapless { original_frames } else { total_frames };
+
+        let channels = Channels::from_bits_truncate((1u32 << header.channels.min(31)) - 1);
+        let mut params = CodecParameters::new();
+        params.for_codec(CODEC_TYPE_GLC)
+              .with_sample_rate(header.sample_rate)
+              .with_time_base(TimeBase::new(1, header.sample_rate))
+              .with_sample_format(SampleFormat::F32)
+              .with_channels(channels)
+              .with_n_frames(n_frames)
+              .with_delay(delay)
+              .with_padding(padding)
+              .with_max_frames_per_packet(header.hop_size as u64);
+
+        Ok(Self
+        {
+            glc,
+            tracks: vec![Track::new(0, params)],
+            metadata: MetadataLog::default(),
+            next_frame: 0,
+            gapless: options.enable_gapless,
+        })
+    }
+
+    fn cues(&self) -> &[Cue]
+    {
+        &[]
+    }
+
+    fn metadata(&mut self) -> Metadata<'_>
+    {
+        self.metadata.metadata()
+    }
+
+    fn seek(&mut self, _mode: SeekMode, to: SeekTo) -> Result<SeekedTo>
+    {
+        let required_ts = match to
+        {
+            SeekTo::TimeStamp { ts, .. } => ts,
+            SeekTo::Time { time, .. } => TimeBase::new(1, self.glc.header().sample_rate).calc_timestamp(time),
+        };
+        let untrimmed = if self.gapless { required_ts + self.delay() } else { required_ts };
+
+        // Start one frame early so the overlap for the target frame is primed;
+        // the caller discards output before `required_ts`
+        let target_frame = (untrimmed / self.hop()) as usize;
+        if target_frame > self.glc.frame_count()
+        {
+            return unsupported_error("glc: seek past end of stream");
+        }
+        self.next_frame = target_frame.saturating_sub(1);
+        if self.glc.header().long_term_prediction
+        {
+            // Predicted frames need the audio before them, so start where prediction restarts
+            self.next_frame = self.glc.restart_frame(self.next_frame).map_err(to_symphonia_error)?;
+        }
+
+        Ok(SeekedTo
+        {
+            track_id: 0,
+            required_ts,
+            actual_ts: self.to_public_ts(self.next_frame as u64 * self.hop()),
+        })
+    }
+
+    fn tracks(&self) -> &[Track]
+    {
+        &self.tracks
+    }
+
+    fn next_packet(&mut self) -> Result<Packet>
+    {
+        let frame_count = self.glc.frame_count();
+        if self.next_frame > frame_count
+        {
+            return Err(Error::IoError(io::Error::new(io::ErrorKind::UnexpectedEof, "end of stream")));
+        }
+
+        // The packet after the last frame is empty and flushes the final overlap
+        let data = if self.next_frame < frame_count
+        {
+            self.glc.read_frame_bytes(self.next_frame).map_err(to_symphonia_error)?
+        }
+        else
+        {
+            Vec::new()
+        };
+
+        let hop = self.hop();
+        let start = self.next_frame as u64 * hop;
+        let mut packet = Packet::new_from_boxed_slice(0, self.to_public_ts(start), hop, data.into_boxed_slice());
+
+        if self.gapless
+        {
+            // Trim everything outside [delay, delay + original) in the untrimmed timeline
+            let content_start = self.delay();
+            let content_end = content_start + self.original_frames();
+            let end = start + hop;
+            let trim_start = content_start.saturating_sub(start).min(hop);
+            let trim_end = end.saturating_sub(content_end).min(hop - trim_start);
+            packet.trim_start = trim_start as u32;
+            packet.trim_end = trim_end as u32;
+        }
+
+        self.next_frame += 1;
+        Ok(packet)
+    }
+
+    fn into_inner(self: Box<Self>) -> MediaSourceStream
+    {
+        self.glc.into_inner()
+    }
+}
+
+/// Symphonia `Decoder` for GLC packets produced by [`GlcFormatReader`]
+pub struct GlcSymphoniaDecoder
+{
+    params: CodecParameters,
+    decoder: Decoder,
+    channels: usize,
+    overlap: OverlapState,
+    interleaved: Vec<f32>,
+    buf: AudioBuffer<f32>,
+}
+
+impl GlcSymphoniaDecoder
+{
+    fn decode_packet(&mut self, packet: &Packet) -> Result<()>
+    {
+        if packet.buf().is_empty()
+        {
+            self.interleaved = self.overlap.flush();
+        }
+        else
+        {
+            let frame = deserialize_frame(packet.buf()).map_err(to_symphonia_error)?;
+            if frame.validate(self.channels, self.overlap.hop_size()).is_err()
+            {
+                return Err(Error::DecodeError("glc: invalid frame"));
+            }
+            self.interleaved = self.decoder.decode_frame(&frame, &mut self.overlap);
+        }
+
+        // Deinterleave into the planar output buffer
+        let frames = self.interleaved.len() / self.channels;
+        self.buf.clear();
+        self.buf.render_reserved(Some(frames));
+        for ch in 0..self.channels
+        {
+            let plane = self.buf.chan_mut(ch);
+            for (i, sample) in plane.iter_mut().enumerate()
+            {
+                *sample = self.interleaved[i * self.channels + ch];
+            }
+        }
+        self.buf.trim(packet.trim_start() as usize, packet.trim_end() as usize);
+        Ok(())
+    }
+}
+
+impl SymphoniaDecoder for GlcSymphoniaDecoder
+{
+    fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> Result<Self>
+    {
+        if params.codec != CODEC_TYPE_GLC
+        {
+            return unsupported_error("glc: invalid codec type");
+        }
+        let sample_rate = params.sample_rate.ok_or(Error::DecodeError("glc: missing sample rate"))?;
+        let channel_layout = params.channels.ok_or(Error::DecodeError("glc: missing channels"))?;
+        let channels = channel_layout.count();
+        if channels == 0
+        {
+            return Err(Error::DecodeError("glc: zero channels"));
+        }
+        let hop = params.max_frames_per_packet.unwrap_or(HOP_SIZE as u64);
+        let overlap = OverlapState::with_hop_size(channels, hop as usize).map_err(|_| Error::DecodeError("glc: invalid hop size"))?;
+
+        Ok(Self
+        {
+            params: params.clone(),
+            decoder: Decoder::new(channels, sample_rate),
+            channels,
+            overlap,
+            interleaved: Vec::new(),
+            buf: AudioBuffer::new(hop, SignalSpec::new(sample_rate, channel_layout)),
+        })
+    }
+
+    fn supported_codecs() -> &'static [CodecDescriptor]
+    {
+        &[CodecDescriptor
+        {
+            codec: CODEC_TYPE_GLC,
+            short_name: "glc",
+            long_name: "Gapless Lossy Codec",
+            inst_func: |params, options| Ok(Box::new(GlcSymphoniaDecoder::try_new(params, options)?)),
+        }]
+    }
+
+    fn reset(&mut self)
+    {
+        self.overlap.reset();
+    }
+
+    fn codec_params(&self) -> &CodecParameters
+    {
+        &self.params
+    }
+
+    fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef<'_>>
+    {
+        if let Err(e) = self.decode_packet(packet)
+        {
+            self.buf.clear();
+            return Err(e);
+        }
+        Ok(self.buf.as_audio_buffer_ref())
+    }
+
+    fn finalize(&mut self) -> FinalizeResult
+    {
+        FinalizeResult::default()
+    }
+
+    fn last_decoded(&self) -> AudioBufferRef<'_>
+    {
+        self.buf.as_audio_buffer_ref()
+    }
+}
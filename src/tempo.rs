@@ -0,0 +1,220 @@
+//! Tempo change without a pitch change, for auditioning material faster or slower
+//!
+//! [`TimeStretch`] uses WSOLA (waveform-similarity overlap-add): it cuts the input into
+//! Hann-windowed frames spaced at the output rate times the tempo, and overlap-adds them at the
+//! output rate. Each frame is nudged within a small tolerance to where it best lines up with the
+//! frame before it, so periodic sounds carry on in phase instead of beating or doubling.
+use std::ops::RangeInclusive;
+
+/// Slowest and fastest tempo [`TimeStretch`] is meant for
+pub const MIN_TEMPO: f64 = 0.5;
+pub const MAX_TEMPO: f64 = 2.0;
+
+/// Tempos [`PlaybackControl::set_tempo`](crate::playback::PlaybackControl::set_tempo) accepts
+pub const TEMPO_RANGE: RangeInclusive<f64> = MIN_TEMPO..=MAX_TEMPO;
+
+/// Length of each overlap-added frame in seconds; long enough to hold a couple of periods of a
+/// low voice, short enough that drums don't smear
+const FRAME_SECONDS: f64 = 0.04;
+
+/// Streaming time-stretcher for interleaved audio of one format
+///
+/// At a tempo of exactly 1 audio passes straight through until another tempo is first asked
+/// for; from then on the stretcher stays in the signal path (at 1 it carries on at the original
+/// speed, holding back a frame) until [`TimeStretch::reset`], so changing tempo never clicks.
+#[derive(Clone, Debug)]
+pub struct TimeStretch
+{
+    channels: usize,
+    /// Frame length and synthesis hop (half a frame), in frames of audio
+    frame: usize,
+    hop: usize,
+    /// How far a frame may be moved from its nominal position to line up with the previous one
+    tolerance: usize,
+    /// Periodic Hann window, whose halves sum to 1 at 50% overlap
+    window: Vec<f32>,
+    /// Input not yet done with, interleaved, starting at `input_start`
+    input: Vec<f32>,
+    input_start: u64,
+    /// Nominal input position of the next frame
+    analysis: f64,
+    /// Input position of the last frame used
+    previous: Option<u64>,
+    /// Second half of the last frame, windowed, waiting for the next to be added to it
+    overlap: Vec<f32>,
+    active: bool,
+    tempo: f64,
+}
+
+impl TimeStretch
+{
+    pub fn new(sample_rate: u32, channels: u16) -> Self
+    {
+        let hop = ((sample_rate as f64 * FRAME_SECONDS / 2.0) as usize).max(16);
+        let frame = hop * 2;
+        let window = (0..frame).map(|i| (0.5 - 0.5 * (std::f64::consts::TAU * i as f64 / frame as f64).cos()) as f32).collect();
+        let channels = channels.max(1) as usize;
+        Self
+        {
+            channels,
+            frame,
+            hop,
+            tolerance: hop / 4,
+            window,
+            input: Vec::new(),
+            input_start: 0,
+            analysis: 0.0,
+            previous: None,
+            overlap: vec![0.0; hop * channels],
+            active: false,
+            tempo: 1.0,
+        }
+    }
+
+    /// Whether audio is going through the stretcher rather than straight past it
+    pub fn is_active(&self) -> bool
+    {
+        self.active
+    }
+
+    /// Input held back for frames still to come, in frames of audio
+    pub fn latency(&self) -> u64
+    {
+        if self.active { self.input_end().saturating_sub(self.analysis.round() as u64) } else { 0 }
+    }
+
+    /// Forget all buffered audio and go back to passing it straight through, e.g. after a seek
+    pub fn reset(&mut self)
+    {
+        self.input.clear();
+        self.input_start = 0;
+        self.analysis = 0.0;
+        self.previous = None;
+        self.overlap.iter_mut().for_each(|sample| *sample = 0.0);
+        self.active = false;
+    }
+
+    /// Stretch interleaved `input` to play at `tempo` times its speed, appending what is ready to `output`
+    pub fn process(&mut self, input: &[f32], tempo: f64, output: &mut Vec<f32>)
+    {
+        let tempo = if tempo.is_finite() { tempo.clamp(MIN_TEMPO, MAX_TEMPO) } else { 1.0 };
+        if tempo == 1.0 && !self.active
+        {
+            output.extend_from_slice(input);
+            return;
+        }
+        self.active = true;
+        self.tempo = tempo;
+        self.input.extend_from_slice(input);
+        self.run(None, output);
+    }
+
+    /// Stretch what is still buffered, as if followed by silence, then reset
+    pub fn finish(&mut self, output: &mut Vec<f32>)
+    {
+        if !self.active
+        {
+            return;
+        }
+        let end = self.input_end();
+        let padding = (self.frame + self.hop + 2 * self.tolerance) * self.channels;
+        self.input.resize(self.input.len() + padding, 0.0);
+        self.run(Some(end), output);
+        output.extend_from_slice(&self.overlap);
+        self.reset();
+    }
+
+    /// Input position just past the buffered input
+    fn input_end(&self) -> u64
+    {
+        self.input_start + (self.input.len() / self.channels) as u64
+    }
+
+    /// Overlap-add frames while there is input for them (and, given `limit`, while they start before it)
+    fn run(&mut self, limit: Option<u64>, output: &mut Vec<f32>)
+    {
+        let (frame, hop, tolerance, channels) = (self.frame as u64, self.hop as u64, self.tolerance as u64, self.channels);
+        loop
+        {
+            let nominal = (self.analysis.round() as u64).max(self.input_start);
+            if limit.is_some_and(|limit| nominal >= limit)
+            {
+                break;
+            }
+            let first = nominal.saturating_sub(tolerance).max(self.input_start);
+            let last = nominal + tolerance;
+            let needed = match self.previous
+            {
+                Some(previous) => (last + frame).max(previous + hop + frame),
+                None => nominal + frame,
+            };
+            if needed > self.input_end()
+            {
+                break;
+            }
+
+            let start = match self.previous
+            {
+                Some(previous) => self.best_match(previous + hop, first..=last),
+                None => nominal,
+            };
+            let offset = (start - self.input_start) as usize * channels;
+            let samples = &self.input[offset..offset + self.frame * channels];
+            for i in 0..self.hop
+            {
+                // The very first frame starts at full level rather than fading in
+                let gain = if self.previous.is_some() { self.window[i] } else { 1.0 };
+                for c in 0..channels
+                {
+                    output.push(self.overlap[i * channels + c] + gain * samples[i * channels + c]);
+                    self.overlap[i * channels + c] = self.window[self.hop + i] * samples[(self.hop + i) * channels + c];
+                }
+            }
+            self.previous = Some(start);
+            self.analysis += self.hop as f64 * self.tempo;
+
+            // Drop input that no later frame can reach
+            let keep = ((self.analysis.round() as u64).saturating_sub(tolerance)).min(start + hop).max(self.input_start);
+            let drop = (keep - self.input_start) as usize * channels;
+            self.input.drain(..drop);
+            self.input_start = keep;
+        }
+    }
+
+    /// Start in `candidates` of the frame whose first half most resembles the input following on
+    /// from the previous frame at `natural`, by normalized cross-correlation
+    fn best_match(&self, natural: u64, candidates: RangeInclusive<u64>) -> u64
+    {
+        let channels = self.channels;
+        let at = |position: u64| (position - self.input_start) as usize * channels;
+        let reference = &self.input[at(natural)..at(natural) + self.hop * channels];
+        let score = |candidate: u64|
+        {
+            let samples = &self.input[at(candidate)..at(candidate) + self.hop * channels];
+            // Every other frame is plenty to find the best alignment, at half the cost
+            let (mut product, mut energy) = (0.0f64, 0.0f64);
+            for (a, b) in reference.chunks(channels).zip(samples.chunks(channels)).step_by(2)
+            {
+                for (&a, &b) in a.iter().zip(b)
+                {
+                    product += a as f64 * b as f64;
+                    energy += b as f64 * b as f64;
+                }
+            }
+            if energy > 0.0 { product / energy.sqrt() } else { 0.0 }
+        };
+
+        // The natural continuation wins ties, so a steady tempo of 1 reproduces the input exactly
+        let mut best = natural.clamp(*candidates.start(), *candidates.end());
+        let mut best_score = score(best);
+        for candidate in candidates
+        {
+            let candidate_score = score(candidate);
+            if candidate_score > best_score
+            {
+                (best, best_score) = (candidate, candidate_score);
+            }
+        }
+        best
+    }
+}
@@ -0,0 +1,232 @@
+//! Test signals, SNR helpers, and round-trip acceptance checks (the `test-utils` feature)
+//!
+//! These are the generators and criteria the crate's own tests use, for applications embedding
+//! the codec to hold their builds to the same standard. [`check_round_trip`] encodes and decodes
+//! a signal and checks the properties every round trip must have; [`strategies`] generates
+//! signals for it with proptest.
+use anyhow::{anyhow, Result};
+use std::f32::consts::PI;
+use crate::codec::{encoded_to_bytes, Decoder, Encoder};
+use crate::metrics::snr_db;
+
+/// Re-exported so downstream tests use the same proptest as [`strategies`]
+pub use proptest;
+
+/// Generate a sine wave
+pub fn generate_sine_wave(frequency: f32, sample_rate: u32, channels: u16, duration_seconds: f32) -> Vec<f32>
+{
+    let total_samples = (sample_rate as f32 * duration_seconds) as usize;
+    let mut samples = Vec::with_capacity(total_samples * channels as usize);
+    
+    for i in 0..total_samples
+    {
+        let t = i as f32 / sample_rate as f32;
+        let sample = (2.0 * PI * frequency * t).sin() * 0.5;
+        
+        for _ in 0..channels
+        {
+            samples.push(sample);
+        }
+    }
+    
+    samples
+}
+
+/// Generate a square wave
+pub fn generate_square_wave(frequency: f32, sample_rate: u32, channels: u16, duration_seconds: f32) -> Vec<f32>
+{
+    let total_samples = (sample_rate as f32 * duration_seconds) as usize;
+    let mut samples = Vec::with_capacity(total_samples * channels as usize);
+    
+    for i in 0..total_samples
+    {
+        let t = i as f32 / sample_rate as f32;
+        let phase = 2.0 * PI * frequency * t;
+        let sample = if phase.sin() >= 0.0 { 0.3 } else { -0.3 };
+        
+        for _ in 0..channels
+        {
+            samples.push(sample);
+        }
+    }
+    
+    samples
+}
+
+/// Generate a sawtooth wave
+pub fn generate_sawtooth_wave(frequency: f32, sample_rate: u32, channels: u16, duration_seconds: f32) -> Vec<f32>
+{
+    let total_samples = (sample_rate as f32 * duration_seconds) as usize;
+    let mut samples = Vec::with_capacity(total_samples * channels as usize);
+    
+    for i in 0..total_samples
+    {
+        let t = i as f32 / sample_rate as f32;
+        let phase = (2.0 * PI * frequency * t) % (2.0 * PI);
+        let sample = ((phase / PI) - 1.0) * 0.3;
+        
+        for _ in 0..channels
+        {
+            samples.push(sample);
+        }
+    }
+    
+    samples
+}
+
+/// Generate a frequency sweep
+pub fn generate_frequency_sweep(start_freq: f32, end_freq: f32, sample_rate: u32, channels: u16, duration_seconds: f32) -> Vec<f32>
+{
+    let total_samples = (sample_rate as f32 * duration_seconds) as usize;
+    let mut samples = Vec::with_capacity(total_samples * channels as usize);
+    
+    for i in 0..total_samples
+    {
+        let t = i as f32 / sample_rate as f32;
+        let progress = t / duration_seconds;
+        let frequency = start_freq + (end_freq - start_freq) * progress;
+        let sample = (2.0 * PI * frequency * t).sin() * 0.3;
+        
+        for _ in 0..channels
+        {
+            samples.push(sample);
+        }
+    }
+    
+    samples
+}
+
+/// Generate white noise (random samples)
+pub fn generate_white_noise(sample_rate: u32, channels: u16, duration_seconds: f32, seed: u64) -> Vec<f32>
+{
+    // Simple LCG pseudorandom number generator for deterministic noise
+    let mut state = seed;
+    let mut next_random = || -> f32
+        {
+            // LCG parameters from Numerical Recipes
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            // Convert to float in range [-0.3, 0.3]
+            let normalized = (state as f32) / (u64::MAX as f32);
+            (normalized - 0.5) * 0.6
+        };
+
+    let total_samples = (sample_rate as f32 * duration_seconds) as usize;
+    let mut samples = Vec::with_capacity(total_samples * channels as usize);
+
+    for _ in 0..total_samples
+    {
+        for _ in 0..channels
+        {
+            samples.push(next_random());
+        }
+    }
+
+    samples
+}
+
+/// Calculate Signal-to-Noise Ratio between original and decoded audio
+/// Skips initial and final transients to avoid edge effects
+pub fn calculate_snr(original: &[f32], decoded: &[f32]) -> f32
+{
+    let min_len = original.len().min(decoded.len());
+    if min_len < 2000 { return 0.0; }
+    
+    // Skip the initial and final transients
+    calculate_snr_range(original, decoded, 1000, min_len - 1000)
+}
+
+/// Calculate SNR for a specific range of samples; 0 dB for a silent original
+pub fn calculate_snr_range(original: &[f32], decoded: &[f32], start_idx: usize, end_idx: usize) -> f32
+{
+    let snr = snr_db(&original[start_idx..end_idx], &decoded[start_idx..end_idx]);
+    if snr == f64::NEG_INFINITY { 0.0 } else { snr as f32 }
+}
+
+
+/// SNR a round trip must reach by default, in dB; the codec discards what it judges inaudible,
+/// so this is a sanity floor, not a measure of quality
+pub const DEFAULT_MIN_SNR_DB: f32 = -10.0;
+
+/// Encode interleaved `samples` at `quality` and decode them again, checking that
+/// - the decoded signal is exactly as long as the original (the round trip is gapless),
+/// - its SNR, away from the first and last 1000 samples, is at least `min_snr_db`, and
+/// - encoding and decoding a second time gives the same bytes and samples (the codec is deterministic)
+///
+/// Returns the decoded samples
+pub fn check_round_trip(samples: &[f32], sample_rate: u32, channels: u16, quality: f32, min_snr_db: f32) -> Result<Vec<f32>>
+{
+    let round_trip = ||
+    {
+        let mut encoder = Encoder::new(sample_rate);
+        encoder.set_quality(quality);
+        let encoded = encoder.encode(samples, channels)?;
+        let decoded = Decoder::new(channels as usize, sample_rate).decode(&encoded, None)?;
+        Ok::<_, anyhow::Error>((encoded_to_bytes(&encoded)?, decoded))
+    };
+    let (bytes, decoded) = round_trip()?;
+
+    if decoded.len() != samples.len()
+    {
+        return Err(anyhow!("Decoded {} samples from {}", decoded.len(), samples.len()));
+    }
+    let snr = calculate_snr(samples, &decoded);
+    if snr < min_snr_db
+    {
+        return Err(anyhow!("SNR {:.2} dB is below {:.2} dB", snr, min_snr_db));
+    }
+    if round_trip()? != (bytes, decoded.clone())
+    {
+        return Err(anyhow!("Encoding the same signal twice gave different results"));
+    }
+    Ok(decoded)
+}
+
+/// Proptest strategies for test signals
+pub mod strategies
+{
+    use proptest::prelude::*;
+    use super::*;
+
+    /// A generated signal and the format it is in
+    #[derive(Clone, Debug)]
+    pub struct TestSignal
+    {
+        pub samples: Vec<f32>,
+        pub sample_rate: u32,
+        pub channels: u16,
+    }
+
+    /// Sample rates signals are generated at
+    pub const SAMPLE_RATES: &[u32] = &[8000, 22050, 32000, 44100, 48000, 96000];
+
+    /// Most channels a generated signal has: up to 5.1
+    pub const MAX_TEST_CHANNELS: u16 = 6;
+
+    /// One of the generators at a random frequency, rate, channel count, and length of up to
+    /// `max_seconds`; lengths are in whole samples, so frames are seldom filled exactly
+    pub fn signal(max_seconds: f32) -> impl Strategy<Value = TestSignal>
+    {
+        (0..5usize, 20.0f32..8000.0, proptest::sample::select(SAMPLE_RATES), 1u16..=MAX_TEST_CHANNELS, 0.0f32..=1.0, any::<u64>())
+            .prop_map(move |(kind, frequency, sample_rate, channels, length, seed)|
+            {
+                let seconds = length * max_seconds;
+                // Keep tones below Nyquist at low rates
+                let frequency = frequency.min(sample_rate as f32 * 0.45);
+                let samples = match kind
+                {
+                    0 => generate_sine_wave(frequency, sample_rate, channels, seconds),
+                    1 => generate_square_wave(frequency, sample_rate, channels, seconds),
+                    2 => generate_sawtooth_wave(frequency, sample_rate, channels, seconds),
+                    3 => generate_frequency_sweep(frequency / 10.0, frequency, sample_rate, channels, seconds),
+                    _ => generate_white_noise(sample_rate, channels, seconds, seed),
+                };
+                TestSignal { samples, sample_rate, channels }
+            })
+    }
+
+    /// An encoder quality from the whole accepted range
+    pub fn quality() -> impl Strategy<Value = f32>
+    {
+        crate::codec::QUALITY_RANGE
+    }
+}
@@ -0,0 +1,205 @@
+//! Lossy input (MP3, Ogg Vorbis, AAC/M4A) decoded through Symphonia, so existing lossy
+//! libraries can be encoded to GLC for comparison
+//!
+//! This is a lossy-to-lossy transcode: the artifacts of the source codec are encoded on top of GLC's own.
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey};
+use symphonia::core::probe::Hint;
+use crate::container::Tags;
+
+/// File extensions routed through Symphonia by [`LosslessReader`](crate::audio::LosslessReader)
+pub const TRANSCODE_EXTENSIONS: &[&str] = &["mp3", "ogg", "oga", "m4a", "mp4", "aac"];
+
+/// Whether `path` has an extension handled by [`TranscodeReader`]
+pub fn is_transcode_input(path: &Path) -> bool
+{
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| TRANSCODE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+/// Streaming decoder for the first audio track of a Symphonia-supported file,
+/// yielding interleaved f32 samples one packet at a time
+///
+/// Gapless mode is enabled, so encoder delay and padding signalled by the source
+/// (LAME/iTunes headers) are trimmed before the samples reach the GLC encoder.
+pub struct TranscodeReader
+{
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    sample_rate: u32,
+    channels: u16,
+    tags: Tags,
+    buffer: Option<SampleBuffer<f32>>,
+    /// Frames `buffer` can hold
+    buffer_capacity: usize,
+    /// Samples decoded while opening, returned by the first `read_block`
+    pending: Vec<f32>,
+}
+
+impl TranscodeReader
+{
+    /// Open a file and decode its first packet to learn the stream format
+    pub fn open(path: &Path) -> Result<Self>
+    {
+        let source = MediaSourceStream::new(Box::new(File::open(path)?), Default::default());
+        let mut hint = Hint::new();
+        if let Some(ext) = path.extension().and_then(|e| e.to_str())
+        {
+            hint.with_extension(ext);
+        }
+
+        let format_options = FormatOptions { enable_gapless: true, ..Default::default() };
+        let mut probed = symphonia::default::get_probe()
+            .format(&hint, source, &format_options, &MetadataOptions::default())?;
+
+        let mut tags = Tags::default();
+        if let Some(metadata) = probed.metadata.get()
+            && let Some(revision) = metadata.current()
+        {
+            collect_tags(revision, &mut tags);
+        }
+
+        let mut format = probed.format;
+        if let Some(revision) = format.metadata().current()
+        {
+            collect_tags(revision, &mut tags);
+        }
+
+        let track = format.tracks()
+                          .iter()
+                          .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+                          .ok_or_else(|| anyhow!("No audio track found"))?;
+        let track_id = track.id;
+        let decoder = symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+        let mut reader = Self
+        {
+            format,
+            decoder,
+            track_id,
+            sample_rate: 0,
+            channels: 0,
+            tags,
+            buffer: None,
+            buffer_capacity: 0,
+            pending: Vec::new(),
+        };
+
+        let mut first = Vec::new();
+        if !reader.read_block(&mut first)?
+        {
+            return Err(anyhow!("No audio could be decoded"));
+        }
+        reader.pending = first;
+        Ok(reader)
+    }
+
+    pub fn sample_rate(&self) -> u32
+    {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u16
+    {
+        self.channels
+    }
+
+    /// Metadata read from the file (ID3, Vorbis comments, or iTunes atoms)
+    pub fn tags(&self) -> &Tags
+    {
+        &self.tags
+    }
+
+    /// Append the next decoded packet of interleaved samples to `out`
+    /// Returns false (appending nothing) once the end of the file is reached
+    pub fn read_block(&mut self, out: &mut Vec<f32>) -> Result<bool>
+    {
+        if !self.pending.is_empty()
+        {
+            out.append(&mut self.pending);
+            return Ok(true);
+        }
+
+        loop
+        {
+            let packet = match self.format.next_packet()
+            {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(ref e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(false),
+                // The track list changed (chained Ogg streams); only the first stream is transcoded
+                Err(SymphoniaError::ResetRequired) => return Ok(false),
+                Err(e) => return Err(e.into()),
+            };
+            if packet.track_id() != self.track_id
+            {
+                continue;
+            }
+
+            let decoded = match self.decoder.decode(&packet)
+            {
+                Ok(decoded) => decoded,
+                // Skip corrupt packets, as players do
+                Err(SymphoniaError::DecodeError(_)) => continue,
+                Err(e) => return Err(e.into()),
+            };
+            if decoded.frames() == 0
+            {
+                continue;
+            }
+
+            let spec = *decoded.spec();
+            let channels = spec.channels.count() as u16;
+            if self.channels == 0
+            {
+                self.sample_rate = spec.rate;
+                self.channels = channels;
+            }
+            else if spec.rate != self.sample_rate || channels != self.channels
+            {
+                return Err(anyhow!("Stream format changed mid-file ({} Hz, {} channels)", spec.rate, channels));
+            }
+
+            if self.buffer.is_none() || self.buffer_capacity < decoded.capacity()
+            {
+                self.buffer_capacity = decoded.capacity();
+                self.buffer = Some(SampleBuffer::new(self.buffer_capacity as u64, spec));
+            }
+            let buffer = self.buffer.as_mut().expect("sample buffer allocated above");
+            buffer.copy_interleaved_ref(decoded);
+            out.extend_from_slice(buffer.samples());
+            return Ok(true);
+        }
+    }
+}
+
+/// Add a metadata revision's tags, using Vorbis comment names for the standard keys
+fn collect_tags(revision: &MetadataRevision, tags: &mut Tags)
+{
+    for tag in revision.tags()
+    {
+        let key = match tag.std_key
+        {
+            Some(StandardTagKey::Artist) => "artist",
+            Some(StandardTagKey::AlbumArtist) => "albumartist",
+            Some(StandardTagKey::Album) => "album",
+            Some(StandardTagKey::TrackTitle) => "title",
+            Some(StandardTagKey::TrackNumber) => "tracknumber",
+            Some(StandardTagKey::DiscNumber) => "discnumber",
+            Some(StandardTagKey::Date) => "date",
+            Some(StandardTagKey::Genre) => "genre",
+            Some(StandardTagKey::Composer) => "composer",
+            Some(StandardTagKey::Comment) => "comment",
+            _ => tag.key.as_str(),
+        };
+        tags.add(key, tag.value.to_string());
+    }
+}
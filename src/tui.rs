@@ -0,0 +1,299 @@
+//! Full-screen terminal player for `glc play --tui`: the playlist, progress through the current
+//! track and the whole playlist, level meters, and the same transport keys as the plain status line
+use anyhow::{anyhow, Result};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Gauge, LineGauge, List, ListItem, ListState, Paragraph};
+use ratatui::{Frame, Terminal};
+use std::io::{IsTerminal, Stdout};
+use std::time::{Duration, Instant};
+use crate::playback::{format_time, PlaybackControl};
+use crate::player::Player;
+use crate::playlist::Repeat;
+use crate::{TEMPO_STEP, VOLUME_STEP};
+
+/// Time between redraws, which is also how often the meters move
+const FRAME_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Level at the bottom of the meters, in dBFS
+const METER_FLOOR_DB: f64 = -60.0;
+
+/// How fast the meters fall back after a peak, in dB per second
+const METER_FALL_DB: f64 = 30.0;
+
+/// Most channels given a meter of their own; the rest are left off
+const MAX_METERS: usize = 8;
+
+/// The alternate screen in raw mode, put back however the holder returns
+struct Screen
+{
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+}
+
+impl Screen
+{
+    fn enter() -> Result<Self>
+    {
+        enable_raw_mode()?;
+        let mut stdout = std::io::stdout();
+        if let Err(e) = execute!(stdout, EnterAlternateScreen)
+        {
+            disable_raw_mode().ok();
+            return Err(e.into());
+        }
+        Ok(Self { terminal: Terminal::new(CrosstermBackend::new(stdout))? })
+    }
+}
+
+impl Drop for Screen
+{
+    fn drop(&mut self)
+    {
+        execute!(self.terminal.backend_mut(), LeaveAlternateScreen).ok();
+        disable_raw_mode().ok();
+        self.terminal.show_cursor().ok();
+    }
+}
+
+/// What the screen shows that isn't read straight off the player each frame
+struct View<'a>
+{
+    names: &'a [String],
+    list: ListState,
+    /// Meter levels in dB, falling back gradually from each peak
+    levels: Vec<f64>,
+    /// Channels that have clipped since playback started
+    clipped: Vec<bool>,
+}
+
+impl View<'_>
+{
+    /// Take the peaks played since the last frame, `elapsed` seconds ago, into the meters
+    fn update_levels(&mut self, peaks: &[f32], elapsed: f64)
+    {
+        if peaks.is_empty()
+        {
+            return;
+        }
+        self.levels.resize(peaks.len(), METER_FLOOR_DB);
+        self.clipped.resize(peaks.len(), false);
+        for ((level, clipped), &peak) in self.levels.iter_mut().zip(&mut self.clipped).zip(peaks)
+        {
+            let db = 20.0 * (peak.max(1e-6) as f64).log10();
+            *level = db.max(*level - METER_FALL_DB * elapsed).max(METER_FLOOR_DB);
+            *clipped |= peak >= 1.0;
+        }
+    }
+}
+
+/// Run the full-screen player until `player` finishes or the user quits
+pub fn run(player: &mut Player, names: &[String]) -> Result<()>
+{
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal()
+    {
+        return Err(anyhow!("--tui needs a terminal"));
+    }
+
+    let mut screen = Screen::enter()?;
+    let control = player.control().clone();
+    let mut view = View { names, list: ListState::default().with_selected(Some(0)), levels: Vec::new(), clipped: Vec::new() };
+    let mut last_frame = Instant::now();
+    while !player.is_finished()
+    {
+        view.update_levels(&control.take_peaks(), last_frame.elapsed().as_secs_f64());
+        last_frame = Instant::now();
+        screen.terminal.draw(|frame| draw(frame, player, &control, &mut view))?;
+
+        if !event::poll(FRAME_INTERVAL)?
+        {
+            continue;
+        }
+        let Event::Key(key) = event::read()?
+        else
+        {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press
+        {
+            continue;
+        }
+
+        let mixer = control.mixer();
+        let selected = view.list.selected().unwrap_or(0);
+        match key.code
+        {
+            KeyCode::Char(' ') if player.is_paused() => player.play()?,
+            KeyCode::Char(' ') => player.pause(),
+            KeyCode::Left => control.seek_by(-5.0),
+            KeyCode::Right => control.seek_by(5.0),
+            KeyCode::PageDown => control.seek_by(-30.0),
+            KeyCode::PageUp => control.seek_by(30.0),
+            KeyCode::Up | KeyCode::Char('k') => view.list.select(Some(selected.saturating_sub(1))),
+            KeyCode::Down | KeyCode::Char('j') => view.list.select(Some((selected + 1).min(names.len().saturating_sub(1)))),
+            KeyCode::Enter => control.play_track(selected),
+            KeyCode::Char('n') => control.next_track(),
+            KeyCode::Char('p') => control.previous_track(),
+            KeyCode::Char('+') | KeyCode::Char('=') => mixer.set_volume(((mixer.volume() + VOLUME_STEP) * 10.0).round() / 10.0),
+            KeyCode::Char('-') => mixer.set_volume(((mixer.volume() - VOLUME_STEP) * 10.0).round() / 10.0),
+            KeyCode::Char('[') => control.set_tempo(((control.tempo() - TEMPO_STEP) * 10.0).round() / 10.0),
+            KeyCode::Char(']') => control.set_tempo(((control.tempo() + TEMPO_STEP) * 10.0).round() / 10.0),
+            KeyCode::Char('l') => mixer.set_limiter(!mixer.limiter()),
+            KeyCode::Char('e') => control.equalizer().set_enabled(!control.equalizer().enabled()),
+            KeyCode::Char('r') => control.set_repeat(match control.repeat()
+            {
+                Repeat::Off => Repeat::Track,
+                Repeat::Track => Repeat::Playlist,
+                Repeat::Playlist => Repeat::Off,
+            }),
+            KeyCode::Char('s') => control.set_shuffle(!control.shuffle()),
+            KeyCode::Char('q') | KeyCode::Esc =>
+            {
+                player.stop();
+                break;
+            }
+            // Raw mode delivers Ctrl+C as a key press rather than a signal
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                player.stop();
+                break;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn draw(frame: &mut Frame, player: &Player, control: &PlaybackControl, view: &mut View)
+{
+    let meters = view.levels.len().min(MAX_METERS) as u16;
+    let areas = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Length(meters.max(1) + 2),
+            Constraint::Min(3),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .split(frame.size());
+
+    let position = control.position();
+    let state = if player.is_paused() { "⏸ Paused" } else { "▶ Playing" };
+    let name = view.names.get(position.track).map_or("", String::as_str);
+    let now_playing = Paragraph::new(Line::from(vec![
+        Span::styled(format!("{}  ", state), Style::default().fg(Color::Cyan)),
+        Span::styled(name, Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(format!("  [{}/{}]", position.track + 1, view.names.len())),
+    ])).block(Block::default().borders(Borders::ALL).title(" glc "));
+    frame.render_widget(now_playing, areas[0]);
+
+    let progress = Gauge::default()
+        .block(Block::default().borders(Borders::ALL)
+                               .title(format!(" Playlist {} / {} ", format_time(position.total_elapsed),
+                                              format_time(position.total_duration))))
+        .gauge_style(Style::default().fg(Color::Blue))
+        .ratio(fraction(position.elapsed, position.duration))
+        .label(format!("{} / {}", format_time(position.elapsed), format_time(position.duration)));
+    frame.render_widget(progress, areas[1]);
+
+    draw_meters(frame, areas[2], view);
+
+    let current = position.track;
+    let items: Vec<ListItem> = view.names.iter().enumerate().map(|(i, name)|
+    {
+        let marker = if i == current { "▶ " } else { "  " };
+        let style = if i == current { Style::default().fg(Color::Cyan) } else { Style::default() };
+        ListItem::new(format!("{}{:>3}. {}", marker, i + 1, name)).style(style)
+    }).collect();
+    let playlist = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(" Playlist "))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(playlist, areas[3], &mut view.list);
+
+    frame.render_widget(Paragraph::new(status_line(player, control)), areas[4]);
+    frame.render_widget(Paragraph::new("space pause  ←/→ 5 s  PgUp/PgDn 30 s  ↑/↓ Enter pick track  n/p next/prev  \
+                                        +/- volume  [/] tempo  l limiter  e EQ  r repeat  s shuffle  q quit")
+                            .style(Style::default().fg(Color::DarkGray)),
+                        areas[5]);
+}
+
+/// One bar per channel, green up to -12 dBFS, yellow to -3, then red
+fn draw_meters(frame: &mut Frame, area: Rect, view: &View)
+{
+    let block = Block::default().borders(Borders::ALL).title(" Levels ");
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    for (channel, (&level, &clipped)) in view.levels.iter().zip(&view.clipped).take(MAX_METERS).enumerate()
+    {
+        let color = match level
+        {
+            level if level >= -3.0 => Color::Red,
+            level if level >= -12.0 => Color::Yellow,
+            _ => Color::Green,
+        };
+        let value = if level <= METER_FLOOR_DB { "-inf".to_string() } else { format!("{:.1}", level) };
+        let gauge = LineGauge::default()
+            .ratio(((level - METER_FLOOR_DB) / -METER_FLOOR_DB).clamp(0.0, 1.0))
+            .label(format!("{:>2} {:>5} dB{}", channel + 1, value, if clipped { " CLIP" } else { "     " }))
+            .gauge_style(Style::default().fg(color));
+        let row = Rect { y: inner.y + channel as u16, height: 1, ..inner };
+        if row.y < inner.y + inner.height
+        {
+            frame.render_widget(gauge, row);
+        }
+    }
+}
+
+/// Volume, tempo, and the other settings, with decoding health
+fn status_line(player: &Player, control: &PlaybackControl) -> String
+{
+    let mixer = control.mixer();
+    let stats = player.stats();
+    let mut status = format!("volume {:.0}%", mixer.volume() * 100.0);
+    if control.tempo() != 1.0
+    {
+        status.push_str(&format!("  tempo {:.2}x", control.tempo()));
+    }
+    if !mixer.limiter()
+    {
+        status.push_str("  no limiter");
+    }
+    if !control.equalizer().bands().is_empty()
+    {
+        status.push_str(if control.equalizer().enabled() { "  eq" } else { "  eq off" });
+    }
+    match control.repeat()
+    {
+        Repeat::Off => {}
+        Repeat::Track => status.push_str("  repeat track"),
+        Repeat::Playlist => status.push_str("  repeat playlist"),
+    }
+    if control.shuffle()
+    {
+        status.push_str("  shuffle");
+    }
+    if control.is_capturing()
+    {
+        status.push_str("  capturing");
+    }
+    status.push_str(&format!("  │ decoding {:.1}x, {:.0} ms buffered", stats.speed, stats.buffered * 1000.0));
+    #[cfg(feature = "playback-cpal")]
+    if let Some(underruns) = player.underruns()
+    {
+        status.push_str(&format!(", {} underruns", underruns.underruns));
+    }
+    status
+}
+
+/// `part / whole` within 0..=1, or 0 for an empty whole
+fn fraction(part: f64, whole: f64) -> f64
+{
+    if whole > 0.0 { (part / whole).clamp(0.0, 1.0) } else { 0.0 }
+}
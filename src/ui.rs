@@ -1,300 +1,3625 @@
-use crate::codec::{Encoder, Decoder, EncodedAudio, save_encoded, load_encoded, Progress};
-use crate::audio::load_audio_file_lossless;
-use crate::playback::SamplesSource;
+use crate::codec::{Decoder, EncodedAudio, Encoder, load_encoded, Progress, DEFAULT_QUALITY, HOP_SIZE, LOW_DELAY_HOP_SIZE,
+                   MIN_CUTOFF, QUALITY_RANGE};
+use crate::audio::{is_native_input, load_audio_file_lossless, measure_loudness_with, EncodeOptions, LosslessReader, Mp3Mode};
+use crate::config::Config;
+use crate::loudness::Normalize;
+use crate::compare::compare;
+use crate::metrics::Metrics;
+use crate::analysis::{analyze, FrameStats};
+use crate::abx::{excerpt, AbxTest};
+use crate::library::{scan, LibraryTrack};
+use crate::container::{rewrite_tags, GlcDecoder, GlcEncoder, MappedGlcFile, Tags};
+use crate::flac::FlacWriter;
+use crate::equalizer::{graphic_bands, GRAPHIC_FREQUENCIES};
+use crate::mixer::MAX_VOLUME;
+use crate::tempo::TEMPO_RANGE;
+use crate::waveform::Waveform;
+use crate::spectrogram::{spectrum, Spectrogram, SPECTROGRAM_FFT_SIZE, SPECTROGRAM_FLOOR_DB};
+use crate::cli::ReplayGainMode;
+use crate::playback::{format_time, output_device_names, output_format, GlcSource, OutputLevels, PlaybackStats, ReplayGain,
+                      SeekableSource, Track};
+use crate::player::Player;
+use crate::playlist::{read_m3u, write_m3u, Repeat};
 use eframe::egui;
-use std::path::PathBuf;
+use std::collections::{BTreeSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use rodio::{Sink, OutputStream, OutputStreamHandle, Source, Decoder as RodioDecoder};
-use std::time::{Duration, Instant};
-use crossbeam_channel::{bounded, Sender, Receiver};
+use rodio::{Sink, Decoder as RodioDecoder};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use crossbeam_channel::{bounded, unbounded, Sender, Receiver};
 use std::fs::File;
 use std::io::BufReader;
-use std::io::BufWriter;
+use std::io::{BufWriter, Write};
 use hound;
+use serde::{Deserialize, Serialize};
+
+/// Top-level views of the window, one shown at a time
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+enum Tab
+{
+    #[default]
+    Encode,
+    Library,
+    Play,
+    Analyze,
+}
+
+impl Tab
+{
+    const ALL: [Self; 4] = [Self::Encode, Self::Library, Self::Play, Self::Analyze];
+
+    fn name(self) -> &'static str
+    {
+        match self
+        {
+            Self::Encode => "Encode",
+            Self::Library => "Library",
+            Self::Play => "Play",
+            Self::Analyze => "Analyze",
+        }
+    }
+}
+
+/// Keyboard shortcuts, which work whenever no text field is being typed in
+const PLAY_PAUSE: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::NONE, egui::Key::Space);
+const SEEK_BACK: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::NONE, egui::Key::ArrowLeft);
+const SEEK_FORWARD: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::NONE, egui::Key::ArrowRight);
+const PREVIOUS_TRACK: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::ArrowLeft);
+const NEXT_TRACK: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::ArrowRight);
+/// Opens whatever the open tab's file picker opens
+const OPEN: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::O);
+/// Removes the selected playlist entries, on the Play tab
+const REMOVE: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::NONE, egui::Key::Delete);
+
+/// How far the arrow keys seek, in seconds
+const SEEK_STEP: f64 = 5.0;
+
+/// State of the Encode tab
+#[derive(Default)]
+struct EncodeView
+{
+    /// Files picked to encode next
+    files: Vec<PathBuf>,
+    /// Files to encode, shared with the workers encoding them
+    queue: Arc<Mutex<Vec<EncodeJob>>>,
+    workers: Arc<AtomicUsize>,
+    next_id: u64,
+}
+
+/// State of the Library tab
+#[derive(Default)]
+struct LibraryView
+{
+    files: Vec<(PathBuf, EncodedAudio)>,
+    /// `.glc` file whose tags and details are shown, and the read underway
+    details: Option<FileDetails>,
+    details_loading: Option<Receiver<anyhow::Result<FileDetails>>>,
+    /// Frame statistics of the picked file, and the decode underway
+    frames: Option<FrameGraphs>,
+    frames_loading: Option<Receiver<anyhow::Result<FrameGraphs>>>,
+    /// Files found in the music folder, in the order shown, and the scan still finding more
+    tracks: Vec<LibraryTrack>,
+    scan: Option<Receiver<LibraryTrack>>,
+    sort: LibraryColumn,
+    descending: bool,
+    /// Only tracks whose title, artist, or album contain this are shown
+    filter: String,
+}
+
+/// Columns of the music folder table, each of which it can be sorted by
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum LibraryColumn
+{
+    #[default]
+    Title,
+    Artist,
+    Album,
+    Duration,
+    Bitrate,
+}
+
+impl LibraryColumn
+{
+    const ALL: [Self; 5] = [Self::Title, Self::Artist, Self::Album, Self::Duration, Self::Bitrate];
+
+    fn name(self) -> &'static str
+    {
+        match self
+        {
+            Self::Title => "Title",
+            Self::Artist => "Artist",
+            Self::Album => "Album",
+            Self::Duration => "Duration",
+            Self::Bitrate => "Bitrate",
+        }
+    }
+
+    fn width(self) -> f32
+    {
+        match self
+        {
+            Self::Title => 220.0,
+            Self::Artist | Self::Album => 150.0,
+            Self::Duration | Self::Bitrate => 70.0,
+        }
+    }
+
+    /// Order of two tracks by this column (text ignoring case, unknown lengths first), then by path
+    fn compare(self, a: &LibraryTrack, b: &LibraryTrack) -> std::cmp::Ordering
+    {
+        let text = |a: &str, b: &str| a.to_lowercase().cmp(&b.to_lowercase());
+        let number = |a: Option<f64>, b: Option<f64>| a.unwrap_or(-1.0).total_cmp(&b.unwrap_or(-1.0));
+        match self
+        {
+            Self::Title => text(&a.title, &b.title),
+            Self::Artist => text(&a.artist, &b.artist),
+            Self::Album => text(&a.album, &b.album),
+            Self::Duration => number(a.duration, b.duration),
+            Self::Bitrate => number(a.kbps, b.kbps),
+        }.then_with(|| a.path.cmp(&b.path))
+    }
+}
+
+/// State of the Analyze tab: the file to analyze, the quality to encode it at, and the analysis
+/// underway or done
+struct AnalyzeView
+{
+    file: Option<PathBuf>,
+    quality: f32,
+    loading: Option<Receiver<anyhow::Result<Analysis>>>,
+    analysis: Option<Analysis>,
+    /// Original, decoded, and difference spectrograms, uploaded for drawing
+    textures: Option<[egui::TextureHandle; 3]>,
+    difference: bool,
+}
+
+impl AnalyzeView
+{
+    fn new(quality: f32) -> Self
+    {
+        Self { file: None, quality, loading: None, analysis: None, textures: None, difference: false }
+    }
+}
+
+/// State of the ABX listening test on the Analyze tab
+struct AbxView
+{
+    original: Option<PathBuf>,
+    /// `.glc` encode of the original to test; without one, the original is encoded at `quality`
+    encode: Option<PathBuf>,
+    quality: f32,
+    segment_seconds: f64,
+    trials: usize,
+    loading: Option<Receiver<anyhow::Result<AbxClips>>>,
+    clips: Option<AbxClips>,
+    test: Option<AbxTest>,
+    /// Excerpt playing, on the player's output
+    sink: Option<Sink>,
+}
+
+impl AbxView
+{
+    fn new(quality: f32) -> Self
+    {
+        Self
+        {
+            original: None,
+            encode: None,
+            quality,
+            segment_seconds: 4.0,
+            trials: 16,
+            loading: None,
+            clips: None,
+            test: None,
+            sink: None,
+        }
+    }
+}
 
 pub struct CodecApp 
 {
-    selected_files: Vec<PathBuf>,
-    encoded_files: Vec<(PathBuf, EncodedAudio)>,
+    encode: EncodeView,
+    library: LibraryView,
+    analyze: AnalyzeView,
+    abx: AbxView,
     playlist: Vec<PathBuf>,
+    /// Playlist entries picked for removal, by index
+    playlist_selected: BTreeSet<usize>,
+    /// Playlist entry being dragged to a new place
+    playlist_drag: Option<usize>,
     status: Arc<Mutex<String>>,
     detailed_status: Arc<Mutex<String>>,
+    /// Warnings and errors from everything the GUI runs, for the console
+    log: Log,
     is_playing: bool,
     is_testing: bool,
     current_track: usize,
-    audio_sink: Option<Arc<Mutex<Sink>>>,
+    player: Player,
+    /// Tracks being opened for the player, which arrive here once all of them are ready
+    loading_tracks: Option<Receiver<Vec<Track>>>,
+    /// Slider position while the user is dragging it, in seconds
+    seek_drag: Option<f64>,
+    /// Lengths of the tracks playing, in playlist order, for the timeline
+    track_durations: Vec<f64>,
+    /// Playback volume as a linear gain
+    volume: f32,
+    /// Round off peaks past full scale during playback
+    limiter: bool,
+    /// ReplayGain mode and pre-amp for playback
+    replay_gain: ReplayGain,
+    /// Seconds each track fades into the next, 0 for gapless
+    crossfade: f64,
+    /// Playback speed with the pitch kept, 1.0 being unchanged
+    tempo: f64,
+    /// Gains of the 10-band graphic EQ in dB, at `GRAPHIC_FREQUENCIES`
+    eq_gains: [f32; 10],
+    eq_enabled: bool,
+    repeat: Repeat,
+    shuffle: bool,
+    /// Recent playback telemetry, oldest first, sampled every `HEALTH_INTERVAL`
+    health: VecDeque<PlaybackStats>,
+    last_health_sample: Instant,
+    meters: Meters,
     test_sink: Option<Sink>,
-    _stream: Option<OutputStream>,
-    stream_handle: Option<OutputStreamHandle>,
     
     // Progress tracking
     export_progress: Arc<Mutex<Option<f32>>>,
-    encoding_progress: Arc<Mutex<Option<f32>>>,
+    /// Set to stop the playlist export before its next file
+    export_cancel: Arc<AtomicBool>,
+    /// The window was asked to close while encodes or an export were still running
+    confirm_close: bool,
+    /// Closing was confirmed, so the next close request goes through
+    allow_close: bool,
+    settings: GuiSettings,
+    /// Playlist entry whose waveform is shown, the overview once decoded, and the decode underway
+    waveform_path: Option<PathBuf>,
+    waveform: Option<Waveform>,
+    waveform_loading: Option<Receiver<anyhow::Result<Waveform>>>,
+    /// Track playing when the waveform last followed playback to a new track
+    waveform_followed: Option<usize>,
     
     // Channels for background tasks
     progress_receiver: Option<Receiver<Progress>>,
 
     // Audio device testing
     test_file_path: Option<PathBuf>,
-    available_devices: Vec<String>,
-    selected_device: usize,
+    /// Audio outputs to pick from, as of the last refresh
+    output_devices: Vec<String>,
+    /// Where to pick playback up again once the playlist has reopened on a new output
+    resume_at: Option<(usize, f64)>,
 
     // FLAC compression level
     flac_compression_level: u8,
+
+    // MP3 bitrate, used when exporting to a .mp3 file
+    mp3_mode: Mp3Mode,
+
+    export_format: ExportFormat,
+    /// Export a file per playlist entry instead of one joining them all
+    export_per_track: bool,
 }
 
-impl CodecApp 
+/// MP3 settings offered in the export options
+const MP3_MODES: [Mp3Mode; 7] =
+    [Mp3Mode::Vbr(0), Mp3Mode::Vbr(2), Mp3Mode::Vbr(4), Mp3Mode::Cbr(128), Mp3Mode::Cbr(192), Mp3Mode::Cbr(256), Mp3Mode::Cbr(320)];
+
+/// How often playback telemetry is sampled for the health graphs
+const HEALTH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Samples kept for the health graphs, a minute's worth
+const HEALTH_HISTORY: usize = 240;
+
+/// Draw `values` as a line scaled so `max` reaches the top, with a dashed line at `mark` if given
+fn sparkline(ui: &mut egui::Ui, values: impl Iterator<Item = f64>, max: f64, mark: Option<f64>, color: egui::Color32)
 {
-    pub fn new() -> Self 
+    let size = egui::vec2(ui.available_width().min(480.0), 40.0);
+    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_stroke(rect, 2.0, ui.visuals().widgets.noninteractive.bg_stroke);
+
+    let max = max.max(f64::EPSILON);
+    let y = |value: f64| rect.bottom() - (value / max).clamp(0.0, 1.0) as f32 * rect.height();
+    let step = rect.width() / (HEALTH_HISTORY - 1) as f32;
+    let points: Vec<egui::Pos2> = values.enumerate()
+                                        .map(|(i, value)| egui::pos2(rect.left() + i as f32 * step, y(value)))
+                                        .collect();
+    if let Some(mark) = mark
+    {
+        let mark = [egui::pos2(rect.left(), y(mark)), egui::pos2(rect.right(), y(mark))];
+        painter.extend(egui::Shape::dashed_line(&mark, egui::Stroke::new(1.0, egui::Color32::GRAY), 4.0, 4.0));
+    }
+    if points.len() > 1
+    {
+        painter.add(egui::Shape::line(points, egui::Stroke::new(1.5, color)));
+    }
+}
+
+/// Bottom of the level meters, in dBFS
+const METER_FLOOR_DB: f32 = -60.0;
+
+/// Bottom of the live spectrum, in dB relative to a full-scale sine
+const SPECTRUM_FLOOR_DB: f32 = -90.0;
+
+/// How fast the meters and spectrum fall back after a peak, in dB per second
+const METER_FALL_DB: f32 = 30.0;
+
+/// Bars in the live spectrum, spaced evenly in log frequency from `SPECTRUM_LOW_HZ` to Nyquist
+const SPECTRUM_BARS: usize = 48;
+const SPECTRUM_LOW_HZ: f32 = 20.0;
+
+/// Level meters and spectrum of the output, falling back gradually from each peak
+#[derive(Default)]
+struct Meters
+{
+    sample_rate: u32,
+    /// Peak and RMS level of each channel in dBFS
+    peaks: Vec<f32>,
+    rms: Vec<f32>,
+    /// Channels that have clipped since playback started or the indicator was reset
+    clipped: Vec<bool>,
+    /// Level of each spectrum bar in dB, lowest frequency first
+    spectrum: Vec<f32>,
+    updated: Option<Instant>,
+}
+
+impl Meters
+{
+    fn new(sample_rate: u32) -> Self
+    {
+        Self { sample_rate, ..Self::default() }
+    }
+
+    /// Take the levels since the last update and the output played most recently
+    fn update(&mut self, levels: &OutputLevels, recent: &[f32])
     {
-        let (stream, stream_handle) = OutputStream::try_default().unwrap_or_else(|_| 
+        let fall = self.updated.map_or(0.0, |updated| updated.elapsed().as_secs_f32()) * METER_FALL_DB;
+        self.updated = Some(Instant::now());
+
+        let db = |level: f32| (20.0 * level.max(1e-6).log10()).max(METER_FLOOR_DB);
+        let channels = levels.peaks.len().max(self.peaks.len());
+        self.peaks.resize(channels, METER_FLOOR_DB);
+        self.rms.resize(channels, METER_FLOOR_DB);
+        self.clipped.resize(channels, false);
+        for channel in 0..channels
         {
-            panic!("Failed to get default audio output device");
-        });
-        
-        Self 
+            // Channels with nothing new (while paused, say) fall back to the floor
+            let peak = levels.peaks.get(channel).copied().unwrap_or(0.0);
+            let rms = levels.rms.get(channel).copied().unwrap_or(0.0);
+            self.peaks[channel] = db(peak).max(self.peaks[channel] - fall);
+            self.rms[channel] = db(rms).max(self.rms[channel] - fall);
+            self.clipped[channel] |= peak >= 1.0;
+        }
+
+        if self.sample_rate == 0
         {
-            selected_files: Vec::new(),
-            encoded_files: Vec::new(),
-            playlist: Vec::new(),
-            status: Arc::new(Mutex::new("Ready".to_string())),
-            detailed_status: Arc::new(Mutex::new(String::new())),
-            is_playing: false,
-            is_testing: false,
-            current_track: 0,
-            audio_sink: None,
-            test_sink: None,
-            _stream: Some(stream),
-            stream_handle: Some(stream_handle),
-            export_progress: Arc::new(Mutex::new(None)),
-            encoding_progress: Arc::new(Mutex::new(None)),
-            progress_receiver: None,
-            test_file_path: None,
-            available_devices: vec!["Default".to_string()],
-            selected_device: 0,
-            flac_compression_level: 5, // Default to level 5
+            return;
+        }
+        let bins = spectrum(recent);
+        let bin_hz = self.sample_rate as f32 / SPECTROGRAM_FFT_SIZE as f32;
+        let ratio = self.sample_rate as f32 / 2.0 / SPECTRUM_LOW_HZ;
+        self.spectrum.resize(SPECTRUM_BARS, SPECTRUM_FLOOR_DB);
+        let edge = |bar: usize| ((SPECTRUM_LOW_HZ * ratio.powf(bar as f32 / SPECTRUM_BARS as f32) / bin_hz) as usize).min(bins.len() - 1);
+        for (bar, level) in self.spectrum.iter_mut().enumerate()
+        {
+            let (low, high) = (edge(bar), edge(bar + 1));
+            let loudest = bins[low..=high.max(low)].iter().fold(SPECTRUM_FLOOR_DB, |loudest, &bin| loudest.max(bin));
+            *level = loudest.max(*level - fall);
         }
     }
-    
-    fn update_status(&self, msg: String) 
+
+    /// Low edge of spectrum bar `bar` in Hz
+    fn bar_frequency(&self, bar: usize) -> f32
     {
-        *self.status.lock().unwrap() = msg;
+        SPECTRUM_LOW_HZ * (self.sample_rate as f32 / 2.0 / SPECTRUM_LOW_HZ).powf(bar as f32 / SPECTRUM_BARS as f32)
     }
-    
-    fn update_detailed_status(&self, msg: String) 
+}
+
+/// Columns in a waveform overview, about as many as the window is wide
+const WAVEFORM_COLUMNS: usize = 800;
+
+/// Columns in the analysis spectrograms
+const ANALYSIS_COLUMNS: usize = 600;
+
+/// Range of the difference spectrogram's colours, in dB either way
+const DIFFERENCE_RANGE_DB: f32 = 30.0;
+
+/// An original file's spectrogram next to that of the same file encoded and decoded
+struct Analysis
+{
+    original: Spectrogram,
+    decoded: Spectrogram,
+    quality: f32,
+    snr_db: f64,
+    /// SNR by critical band, A-weighted SNR, and spectral distortion
+    metrics: Metrics,
+}
+
+impl Analysis
+{
+    /// Encode `path` at `quality` in memory, decode it again, and take both spectrograms
+    fn run(path: &Path, quality: f32) -> anyhow::Result<Self>
     {
-        *self.detailed_status.lock().unwrap() = msg;
+        let (samples, sample_rate, channels) = load_audio_file_lossless(path)?;
+        let mut encoder = Encoder::new(sample_rate);
+        encoder.set_quality(quality);
+        let encoded = encoder.encode(&samples, channels)?;
+        let decoded = Decoder::new(channels as usize, sample_rate).decode(&encoded, None)?;
+        let report = compare(&samples, &decoded, sample_rate, channels);
+        Ok(Self
+        {
+            original: Spectrogram::new(&samples, sample_rate, channels, ANALYSIS_COLUMNS),
+            decoded: Spectrogram::new(&decoded, sample_rate, channels, ANALYSIS_COLUMNS),
+            quality,
+            snr_db: report.snr_db,
+            metrics: report.metrics,
+        })
     }
-    
-    fn encode_file_async(&mut self, input_path: PathBuf) 
+}
+
+/// Range of excerpt lengths and trial counts offered for ABX tests
+const ABX_SEGMENT_RANGE: std::ops::RangeInclusive<f64> = 1.0..=15.0;
+const ABX_TRIALS_RANGE: std::ops::RangeInclusive<usize> = 8..=32;
+
+/// An original and its encode decoded, both in memory, for ABX excerpts to be cut from
+struct AbxClips
+{
+    original: Vec<f32>,
+    encoded: Vec<f32>,
+    sample_rate: u32,
+    channels: u16,
+    /// Frames both cover
+    frames: u64,
+    /// What B is, for the results
+    label: String,
+}
+
+impl AbxClips
+{
+    /// Decode `original` and `encode`, or encode `original` at `quality` in memory without one
+    fn load(original: &Path, encode: Option<&Path>, quality: f32) -> anyhow::Result<Self>
     {
-        let status = self.status.clone();
-        let detailed_status = self.detailed_status.clone();
-        let encoding_progress = self.encoding_progress.clone();
-        
-        thread::spawn(move || 
+        let (samples, sample_rate, channels) = load_audio_file_lossless(original)?;
+        let (encoded, label) = match encode
         {
-            let start_time = Instant::now();
-            *status.lock().unwrap() = format!("Loading: {:?}", input_path.file_name().unwrap());
-            *encoding_progress.lock().unwrap() = Some(0.0);
-            
-            let result = (|| -> anyhow::Result<(PathBuf, EncodedAudio, f32)> 
-            {
-                let load_start = Instant::now();
-                let (samples, sample_rate, channels) = load_audio_file_lossless(&input_path)?;
-                *detailed_status.lock().unwrap() = format!(
-                    "Loaded {} samples in {:.2}s", 
-                    samples.len(), 
-                    load_start.elapsed().as_secs_f32()
-                );
-                
-                *encoding_progress.lock().unwrap() = Some(50.0);
-                *status.lock().unwrap() = format!("Encoding: {:?}", input_path.file_name().unwrap());
-                
-                let encode_start = Instant::now();
-                let mut encoder = Encoder::new(sample_rate);
-                let encoded = encoder.encode(&samples, channels)?;
-                *detailed_status.lock().unwrap() = format!(
-                    "Encoded {} frames in {:.2}s", 
-                    encoded.frames.len(), 
-                    encode_start.elapsed().as_secs_f32()
-                );
-                
-                *encoding_progress.lock().unwrap() = Some(90.0);
-                let output_path = input_path.with_extension("glc");
-                save_encoded(&encoded, &output_path)?;
-                
-                let original_size = std::fs::metadata(&input_path)?.len();
-                let encoded_size = std::fs::metadata(&output_path)?.len();
-                let ratio = original_size as f32 / encoded_size as f32;
-                
-                *encoding_progress.lock().unwrap() = Some(100.0);
-                
-                Ok((output_path, encoded, ratio))
-            })();
-            
-            let total_time = start_time.elapsed();
-            match result 
+            Some(path) =>
             {
-                Ok((output_path, encoded, ratio)) => 
+                let encoded = load_encoded(path)?;
+                if (encoded.header.sample_rate, encoded.header.channels) != (sample_rate, channels)
                 {
-                    *status.lock().unwrap() = format!(
-                        "Encoded successfully! Ratio: {:.2}x, Time: {:.2}s", 
-                        ratio, 
-                        total_time.as_secs_f32()
-                    );
-                }
-                Err(e) => 
-                {
-                    *status.lock().unwrap() = format!("Encoding error: {}", e);
+                    anyhow::bail!("{:?} is {} Hz with {} channels, unlike the original ({} Hz, {} channels)",
+                                  path.file_name().unwrap_or_default(), encoded.header.sample_rate,
+                                  encoded.header.channels, sample_rate, channels);
                 }
+                let decoded = Decoder::new(channels as usize, sample_rate).decode(&encoded, None)?;
+                (decoded, format!("{:?}", path.file_name().unwrap_or_default()))
             }
-            
-            *encoding_progress.lock().unwrap() = None;
-        });
+            None =>
+            {
+                let mut encoder = Encoder::new(sample_rate);
+                encoder.set_quality(quality);
+                let encoded = encoder.encode(&samples, channels)?;
+                (Decoder::new(channels as usize, sample_rate).decode(&encoded, None)?, format!("quality {:.2}", quality))
+            }
+        };
+        let frames = (samples.len().min(encoded.len()) / channels.max(1) as usize) as u64;
+        Ok(Self { original: samples, encoded, sample_rate, channels, frames, label })
     }
-    
-    fn play_playlist_async(&mut self) 
+}
+
+/// Frame-by-frame statistics of a `.glc` file, for seeing where the encoder spends its bits
+struct FrameGraphs
+{
+    path: PathBuf,
+    stats: Vec<FrameStats>,
+    sample_rate: u32,
+    /// Bitrate of each frame in kbps, and the coefficients it kept across all channels
+    kbps: Vec<f64>,
+    coefficients: Vec<f64>,
+}
+
+impl FrameGraphs
+{
+    /// Decode `path` (transients are found in its decoded output) and gather its frames' statistics
+    fn read(path: &Path) -> anyhow::Result<Self>
     {
-        if self.playlist.is_empty() 
+        let encoded = load_encoded(path)?;
+        let (sample_rate, channels) = (encoded.header.sample_rate, encoded.header.channels);
+        let samples = Decoder::new(channels as usize, sample_rate).decode(&encoded, None)?;
+        let stats = analyze(&encoded, &samples)?;
+        let frame_seconds = encoded.header.hop_size as f64 / sample_rate.max(1) as f64;
+        Ok(Self
         {
-            self.update_status("Playlist is empty".to_string());
-            return;
+            path: path.to_path_buf(),
+            kbps: stats.iter().map(|frame| frame.bits as f64 / frame_seconds / 1000.0).collect(),
+            coefficients: stats.iter().map(|frame| frame.coefficients.iter().sum::<usize>() as f64).collect(),
+            stats,
+            sample_rate,
+        })
+    }
+
+    /// Highlight for frames that fell back to raw PCM, and for transients
+    fn flag(&self, frame: usize) -> Option<egui::Color32>
+    {
+        let stats = &self.stats[frame];
+        if stats.raw_pcm
+        {
+            Some(egui::Color32::RED)
         }
-        
-        // Stop any existing playback first
-        self.stop_playback();
-        
-        let playlist = self.playlist.clone();
-        let status = self.status.clone();
-        let detailed_status = self.detailed_status.clone();
-        let stream_handle = self.stream_handle.as_ref().unwrap().clone();
-        
-        let sink = match Sink::try_new(&stream_handle) 
+        else if stats.transient
+        {
+            Some(egui::Color32::YELLOW)
+        }
+        else
+        {
+            None
+        }
+    }
+}
+
+/// Draw one value per frame as bars across the available width, each column the mean of the
+/// frames it covers, coloured by `flag` when any of them is flagged; returns the frame under the pointer
+fn frame_graph(ui: &mut egui::Ui, values: &[f64], flag: impl Fn(usize) -> Option<egui::Color32>) -> (egui::Response, Option<usize>)
+{
+    let (rect, response) = ui.allocate_exact_size(egui::vec2(ui.available_width(), 80.0), egui::Sense::hover());
+    let visuals = ui.visuals();
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, visuals.extreme_bg_color);
+    if values.is_empty()
+    {
+        return (response, None);
+    }
+
+    let max = values.iter().copied().fold(f64::EPSILON, f64::max);
+    let columns = (rect.width() as usize).clamp(1, values.len());
+    let column_width = rect.width() / columns as f32;
+    for column in 0..columns
+    {
+        let frames = column * values.len() / columns..(column + 1) * values.len() / columns;
+        let mean = values[frames.clone()].iter().sum::<f64>() / frames.len() as f64;
+        let fill = frames.clone().find_map(&flag).unwrap_or(visuals.selection.bg_fill);
+        let left = rect.left() + column as f32 * column_width;
+        let top = rect.bottom() - (mean / max) as f32 * rect.height();
+        painter.rect_filled(egui::Rect::from_x_y_ranges(left..=left + column_width, top..=rect.bottom()), 0.0, fill);
+    }
+
+    let pointed = response.hover_pos().map(|pointer|
+    {
+        (((pointer.x - rect.left()) / rect.width() * values.len() as f32) as usize).min(values.len() - 1)
+    });
+    (response, pointed)
+}
+
+/// Spectrogram `columns` as an image, low frequencies at the bottom, coloured by `color`
+fn spectrogram_image(columns: &[Vec<f32>], color: impl Fn(f32) -> egui::Color32) -> egui::ColorImage
+{
+    let bins = columns.first().map_or(0, Vec::len);
+    let mut image = egui::ColorImage::new([columns.len(), bins], egui::Color32::BLACK);
+    for (x, column) in columns.iter().enumerate()
+    {
+        for (bin, &db) in column.iter().enumerate()
+        {
+            image[(x, bins - 1 - bin)] = color(db);
+        }
+    }
+    image
+}
+
+/// Black through blue, red, and yellow to white as a level rises from the floor to full scale
+fn heat_color(db: f32) -> egui::Color32
+{
+    let t = (1.0 - db / SPECTROGRAM_FLOOR_DB).clamp(0.0, 1.0);
+    let channel = |from: f32, to: f32| ((t - from) / (to - from)).clamp(0.0, 1.0);
+    egui::Color32::from_rgb((255.0 * channel(0.25, 0.6)) as u8,
+                            (255.0 * channel(0.55, 0.9)) as u8,
+                            (255.0 * (channel(0.0, 0.3) - channel(0.4, 0.6) + channel(0.85, 1.0)).clamp(0.0, 1.0)) as u8)
+}
+
+/// Red where encoding took energy away, blue where it added some, black where nothing changed
+fn difference_color(db: f32) -> egui::Color32
+{
+    let t = (db / DIFFERENCE_RANGE_DB).clamp(-1.0, 1.0);
+    if t >= 0.0
+    {
+        egui::Color32::from_rgb((255.0 * t) as u8, 0, 0)
+    }
+    else
+    {
+        egui::Color32::from_rgb(0, (96.0 * -t) as u8, (255.0 * -t) as u8)
+    }
+}
+
+/// Tags and technical details of a `.glc` file, with the tags as edited in the GUI
+struct FileDetails
+{
+    path: PathBuf,
+    /// Tag rows being edited, in file order; `None` for legacy files, which can't hold tags
+    tags: Option<Vec<(String, String)>>,
+    /// Whether `tags` have been changed since they were read or saved
+    edited: bool,
+    sample_rate: u32,
+    channels: u16,
+    duration: f64,
+    kbps: f64,
+    frames: usize,
+    /// Frames stored as raw PCM because compressing them would not have saved space
+    raw_frames: usize,
+    tracks: usize,
+}
+
+impl FileDetails
+{
+    /// Read the footer of `path` and scan its frames for raw PCM fallbacks
+    fn read(path: &Path) -> anyhow::Result<Self>
+    {
+        let file_size = std::fs::metadata(path)?.len();
+        let (header, gapless_info, tags, frames, raw_frames, tracks) = match MappedGlcFile::open(path)
         {
-            Ok(s) => Arc::new(Mutex::new(s)),
-            Err(e) => 
+            Ok(glc) =>
             {
-                self.update_status(format!("Failed to create audio sink: {}", e));
-                return;
+                let mut raw_frames = 0;
+                for frame in glc.frames()
+                {
+                    raw_frames += frame?.raw_pcm.is_some() as usize;
+                }
+                let tags = glc.tags().iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+                (glc.header().clone(), glc.gapless_info().clone(), Some(tags), glc.frame_count(), raw_frames,
+                 glc.tracks().len())
+            }
+            // Files from before the frame-delimited container
+            Err(_) =>
+            {
+                let encoded = load_encoded(path)?;
+                let raw_frames = encoded.frames.iter().filter(|frame| frame.raw_pcm.is_some()).count();
+                (encoded.header, encoded.gapless_info, None, encoded.frames.len(), raw_frames, 0)
             }
         };
-        
-        self.audio_sink = Some(sink.clone());
-        self.is_playing = true;
-        
-        let is_playing = Arc::new(Mutex::new(true));  // Add playing flag
-        let is_playing_clone = is_playing.clone();
-        
-        thread::spawn(move || 
+        let duration = gapless_info.original_length as f64 / header.channels.max(1) as f64 / header.sample_rate.max(1) as f64;
+        Ok(Self
         {
-            let start_time = Instant::now();
-            *status.lock().unwrap() = "Creating audio sink...".to_string();
-            
-            let mut sample_rate = 44100;
-            let mut channels = 2;
-            
-            // Stream decode and play each track
-            for (idx, path) in playlist.iter().enumerate() 
+            path: path.to_path_buf(),
+            tags,
+            edited: false,
+            sample_rate: header.sample_rate,
+            channels: header.channels,
+            duration,
+            kbps: if duration > 0.0 { file_size as f64 * 8.0 / duration / 1000.0 } else { 0.0 },
+            frames,
+            raw_frames,
+            tracks,
+        })
+    }
+
+    /// Write the edited tags back into the file; rows with no key are dropped
+    fn save_tags(&mut self) -> anyhow::Result<()>
+    {
+        let rows = self.tags.as_mut().ok_or_else(|| anyhow::anyhow!("Legacy .glc files have no room for tags"))?;
+        rows.retain(|(key, _)| !key.trim().is_empty());
+        let mut tags = Tags::new();
+        for (key, value) in rows.iter()
+        {
+            tags.add(key.trim(), value.as_str());
+        }
+        rewrite_tags(&self.path, &tags)?;
+        // Keys are stored lowercase, so show them as they now are in the file
+        *rows = tags.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+        self.edited = false;
+        Ok(())
+    }
+}
+
+/// Entries the console keeps before dropping the oldest
+const LOG_CAPACITY: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LogLevel
+{
+    Warning,
+    Error,
+}
+
+struct LogEntry
+{
+    time: SystemTime,
+    level: LogLevel,
+    message: String,
+}
+
+impl LogEntry
+{
+    /// Time of day in UTC, `HH:MM:SS`
+    fn clock(&self) -> String
+    {
+        let seconds = self.time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs()) % 86400;
+        format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    }
+
+    fn level_name(&self) -> &'static str
+    {
+        match self.level
+        {
+            LogLevel::Warning => "warning",
+            LogLevel::Error => "error",
+        }
+    }
+}
+
+/// Warnings and errors from the window and its background tasks, oldest first; clones share
+/// the same entries, so tasks on other threads can add to it
+#[derive(Clone, Default)]
+struct Log
+{
+    entries: Arc<Mutex<VecDeque<LogEntry>>>,
+}
+
+impl Log
+{
+    fn push(&self, level: LogLevel, message: String)
+    {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == LOG_CAPACITY
+        {
+            entries.pop_front();
+        }
+        entries.push_back(LogEntry { time: SystemTime::now(), level, message });
+    }
+
+    fn warn(&self, message: String)
+    {
+        self.push(LogLevel::Warning, message);
+    }
+
+    fn error(&self, message: String)
+    {
+        self.push(LogLevel::Error, message);
+    }
+
+    /// Warnings and errors logged so far
+    fn counts(&self) -> (usize, usize)
+    {
+        let entries = self.entries.lock().unwrap();
+        let errors = entries.iter().filter(|entry| entry.level == LogLevel::Error).count();
+        (entries.len() - errors, errors)
+    }
+
+    /// The whole log as text, headed by the version and platform, for pasting into a bug report
+    fn report(&self) -> String
+    {
+        let mut report = format!("glc {} on {} ({}), times in UTC\n", env!("CARGO_PKG_VERSION"), std::env::consts::OS,
+                                 std::env::consts::ARCH);
+        for entry in self.entries.lock().unwrap().iter()
+        {
+            report.push_str(&format!("{} {}: {}\n", entry.clock(), entry.level_name(), entry.message));
+        }
+        report
+    }
+}
+
+/// Settings chosen in the GUI and the state of the last session, kept in `gui.toml` beside the
+/// config file between runs
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct GuiSettings
+{
+    encoder: EncoderSettings,
+    /// Audio output to play through, by name; None for the system default
+    output_device: Option<String>,
+    /// The window as it was when last closed
+    window: Option<WindowGeometry>,
+    dirs: LastDirs,
+    playlist: Vec<PathBuf>,
+    /// Tab open when the window closed
+    tab: Tab,
+    /// Music folder the library browser scans
+    library_dir: Option<PathBuf>,
+}
+
+impl GuiSettings
+{
+    fn path() -> Option<PathBuf>
+    {
+        Config::default_path().map(|config| config.with_file_name("gui.toml"))
+    }
+
+    /// The saved settings, or the defaults if there are none (or they can't be read)
+    fn load() -> Self
+    {
+        Self::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|text| toml::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> anyhow::Result<()>
+    {
+        let path = Self::path().ok_or_else(|| anyhow::anyhow!("No config directory to save settings in"))?;
+        if let Some(dir) = path.parent()
+        {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, toml::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// Position and size of the window's contents, in points
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+struct WindowGeometry
+{
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    maximized: bool,
+}
+
+/// Folders the file dialogs last picked from, so each opens where it left off
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct LastDirs
+{
+    /// Audio files to encode or analyze
+    input: Option<PathBuf>,
+    /// `.glc` files
+    encoded: Option<PathBuf>,
+    /// M3U playlists
+    playlist: Option<PathBuf>,
+    /// Exports and captures
+    output: Option<PathBuf>,
+}
+
+/// A file dialog starting in `dir`, if there is one
+fn file_dialog(dir: &Option<PathBuf>) -> rfd::FileDialog
+{
+    match dir
+    {
+        Some(dir) => rfd::FileDialog::new().set_directory(dir),
+        None => rfd::FileDialog::new(),
+    }
+}
+
+/// Remember the folder `picked` (a folder, or a file in one) for the next dialog
+fn remember_dir(dir: &mut Option<PathBuf>, picked: &Path)
+{
+    let folder = if picked.is_dir() { Some(picked) } else { picked.parent() };
+    if let Some(folder) = folder.filter(|folder| !folder.as_os_str().is_empty())
+    {
+        *dir = Some(folder.to_path_buf());
+    }
+}
+
+/// The main window, placed and sized as it was when last closed
+pub fn viewport() -> egui::ViewportBuilder
+{
+    let viewport = egui::ViewportBuilder::default().with_title("Gapless Lossy Codec");
+    match GuiSettings::load().window
+    {
+        Some(window) => viewport.with_position([window.x, window.y])
+                                .with_inner_size([window.width, window.height])
+                                .with_maximized(window.maximized),
+        None => viewport.with_inner_size([900.0, 700.0]),
+    }
+}
+
+/// Encoder settings for files queued from the GUI
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct EncoderSettings
+{
+    quality: f32,
+    /// Lowpass cutoff in Hz, if any
+    cutoff: Option<f32>,
+    low_delay: bool,
+    /// Integrated loudness to level each file to, in LUFS, if any
+    normalize_lufs: Option<f64>,
+}
+
+impl Default for EncoderSettings
+{
+    fn default() -> Self
+    {
+        Self { quality: DEFAULT_QUALITY, cutoff: None, low_delay: false, normalize_lufs: None }
+    }
+}
+
+impl EncoderSettings
+{
+    fn options(&self) -> EncodeOptions
+    {
+        EncodeOptions
+        {
+            quality: self.quality,
+            normalize: self.normalize_lufs.map(Normalize::Lufs),
+            allow_ffmpeg: false,
+            hop_size: if self.low_delay { LOW_DELAY_HOP_SIZE } else { HOP_SIZE },
+            cutoff: self.cutoff,
+            highpass: None,
+            long_term_prediction: false,
+            bandwidth_extension: None,
+            adaptive_quality: false,
+            bitrate: None,
+            noise_shaping: false,
+        }
+    }
+}
+
+/// Files encoded at once from the encode queue; each encode also spreads its frames across threads
+const MAX_CONCURRENT_ENCODES: usize = 2;
+
+/// Samples per channel handed to the encoder between progress updates
+const ENCODE_PROGRESS_FRAMES: usize = 1 << 16;
+
+/// Where a file in the encode queue has got to
+#[derive(Clone, Debug, PartialEq)]
+enum EncodeState
+{
+    Queued,
+    Loading,
+    Encoding,
+    Done,
+    Failed(String),
+    Cancelled,
+}
+
+/// One file in the encode queue
+struct EncodeJob
+{
+    id: u64,
+    input: PathBuf,
+    output: PathBuf,
+    /// Settings when the file was queued
+    options: EncodeOptions,
+    state: EncodeState,
+    /// Share of the file encoded, from 0 to 1
+    progress: f32,
+    /// Input size over output size, once done
+    ratio: Option<f32>,
+    started: Option<Instant>,
+    /// Time taken, once finished one way or the other
+    elapsed: Option<Duration>,
+    /// Set to stop the encode at the next chunk
+    cancel: Arc<AtomicBool>,
+}
+
+impl EncodeJob
+{
+    fn new(id: u64, input: PathBuf, options: EncodeOptions) -> Self
+    {
+        let output = input.with_extension("glc");
+        Self
+        {
+            id, input, output, options,
+            state: EncodeState::Queued,
+            progress: 0.0,
+            ratio: None,
+            started: None,
+            elapsed: None,
+            cancel: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Drop the file if it is still queued, or stop its encode at the next chunk
+    fn cancel(&mut self)
+    {
+        if self.state == EncodeState::Queued
+        {
+            self.state = EncodeState::Cancelled;
+        }
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    /// Still waiting or underway
+    fn is_pending(&self) -> bool
+    {
+        matches!(self.state, EncodeState::Queued | EncodeState::Loading | EncodeState::Encoding)
+    }
+
+    /// Time spent on the file so far
+    fn time(&self) -> Option<Duration>
+    {
+        self.elapsed.or_else(|| self.started.map(|started| started.elapsed()))
+    }
+}
+
+/// Apply `change` to job `id` of `queue`, unless it has been cleared away
+fn update_job(queue: &Mutex<Vec<EncodeJob>>, id: u64, change: impl FnOnce(&mut EncodeJob))
+{
+    if let Some(job) = queue.lock().unwrap().iter_mut().find(|job| job.id == id)
+    {
+        change(job);
+    }
+}
+
+/// Take queued files off `queue` and encode them one at a time until none are left
+/// Failures go to `log` as well as the job's row
+fn run_encode_worker(queue: &Mutex<Vec<EncodeJob>>, workers: &AtomicUsize, log: &Log)
+{
+    loop
+    {
+        let (id, input, output, options, cancel) =
+        {
+            let mut queue = queue.lock().unwrap();
+            let Some(job) = queue.iter_mut().find(|job| job.state == EncodeState::Queued)
+            else
+            {
+                // Under the lock, so a file queued just now either is seen above or gets a worker of its own
+                workers.fetch_sub(1, Ordering::Relaxed);
+                return;
+            };
+            job.state = EncodeState::Loading;
+            job.started = Some(Instant::now());
+            (job.id, job.input.clone(), job.output.clone(), job.options, job.cancel.clone())
+        };
+
+        let result = encode_with_progress(&input, &output, &options, &cancel, &|state, progress|
+        {
+            update_job(queue, id, |job| (job.state, job.progress) = (state, progress));
+        });
+        if let Err(e) = &result
+        {
+            log.error(format!("Encoding {} failed: {}", input.display(), e));
+        }
+        update_job(queue, id, |job|
+        {
+            job.elapsed = job.started.map(|started| started.elapsed());
+            match result
+            {
+                Ok(Some(ratio)) => (job.state, job.progress, job.ratio) = (EncodeState::Done, 1.0, Some(ratio)),
+                Ok(None) => job.state = EncodeState::Cancelled,
+                Err(e) => job.state = EncodeState::Failed(e.to_string()),
+            }
+        });
+    }
+}
+
+/// Encode `input` to `output` with `options`, reporting each stage and how far it has got, and
+/// return the compression ratio, or None if `cancel` was set first (leaving no output behind)
+fn encode_with_progress(input: &Path, output: &Path, options: &EncodeOptions, cancel: &AtomicBool,
+                        report: &dyn Fn(EncodeState, f32)) -> anyhow::Result<Option<f32>>
+{
+    let mut reader = LosslessReader::open_with(input, options.allow_ffmpeg)?;
+    let mut samples = Vec::new();
+    while reader.read_block(&mut samples)?
+    {
+        if cancel.load(Ordering::Relaxed)
+        {
+            return Ok(None);
+        }
+    }
+    if let Some(normalize) = options.normalize
+    {
+        let gain = 10f64.powf(normalize.gain_db(&measure_loudness_with(input, options.allow_ffmpeg)?) / 20.0) as f32;
+        samples.iter_mut().for_each(|sample| *sample *= gain);
+    }
+
+    report(EncodeState::Encoding, 0.0);
+    let writer = BufWriter::new(File::create(output)?);
+    let mut encoder = GlcEncoder::with_encoder(writer, options.encoder(reader.sample_rate())?, reader.channels())?;
+    encoder.set_channel_mask(reader.channel_mask());
+    encoder.set_tags(reader.tags().clone());
+    let chunk = ENCODE_PROGRESS_FRAMES * reader.channels() as usize;
+    for (i, block) in samples.chunks(chunk).enumerate()
+    {
+        if cancel.load(Ordering::Relaxed)
+        {
+            drop(encoder);
+            std::fs::remove_file(output)?;
+            return Ok(None);
+        }
+        encoder.write_samples(block)?;
+        report(EncodeState::Encoding, ((i + 1) * chunk).min(samples.len()) as f32 / samples.len().max(1) as f32);
+    }
+    encoder.finish()?.flush()?;
+
+    Ok(Some(std::fs::metadata(input)?.len() as f32 / std::fs::metadata(output)?.len().max(1) as f32))
+}
+
+/// Samples per channel decoded between progress updates and cancellation checks during an export
+const EXPORT_CHUNK_FRAMES: usize = 1 << 16;
+
+/// Audio formats the playlist can be exported to
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ExportFormat
+{
+    Flac,
+    Wav,
+    Aiff,
+    Alac,
+    Mp3,
+}
+
+impl ExportFormat
+{
+    /// The formats this build can write
+    fn available() -> Vec<Self>
+    {
+        let mut formats = vec![Self::Flac, Self::Wav, Self::Aiff, Self::Alac];
+        if cfg!(feature = "mp3")
+        {
+            formats.push(Self::Mp3);
+        }
+        formats
+    }
+
+    fn extension(self) -> &'static str
+    {
+        match self
+        {
+            Self::Flac => "flac",
+            Self::Wav => "wav",
+            Self::Aiff => "aiff",
+            Self::Alac => "m4a",
+            Self::Mp3 => "mp3",
+        }
+    }
+
+    fn name(self) -> &'static str
+    {
+        match self
+        {
+            Self::Flac => "FLAC",
+            Self::Wav => "WAV",
+            Self::Aiff => "AIFF",
+            Self::Alac => "ALAC (.m4a)",
+            Self::Mp3 => "MP3",
+        }
+    }
+}
+
+/// Where exported audio goes as it decodes: FLAC and WAV straight into the file, a chunk at a
+/// time, and the other formats into memory, to be written once complete
+enum ExportWriter
+{
+    Flac(FlacWriter<BufWriter<File>>),
+    Wav(hound::WavWriter<BufWriter<File>>),
+    Buffer(Vec<f32>),
+}
+
+impl ExportWriter
+{
+    fn create(path: &Path, format: ExportFormat, sample_rate: u32, channels: u16, flac_level: u8) -> anyhow::Result<Self>
+    {
+        Ok(match format
+        {
+            ExportFormat::Flac => Self::Flac(FlacWriter::new(BufWriter::new(File::create(path)?), sample_rate, channels, flac_level)?),
+            ExportFormat::Wav =>
+            {
+                let spec = hound::WavSpec { channels, sample_rate, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+                Self::Wav(hound::WavWriter::create(path, spec)?)
+            }
+            _ => Self::Buffer(Vec::new()),
+        })
+    }
+
+    fn write(&mut self, samples: &[f32]) -> anyhow::Result<()>
+    {
+        match self
+        {
+            Self::Flac(flac) => flac.write_samples(samples)?,
+            Self::Wav(wav) =>
+            {
+                for &sample in samples
+                {
+                    wav.write_sample((sample * 32767.0).clamp(-32768.0, 32767.0) as i16)?;
+                }
+            }
+            Self::Buffer(buffer) => buffer.extend_from_slice(samples),
+        }
+        Ok(())
+    }
+
+    fn finish(self, path: &Path, format: ExportFormat, sample_rate: u32, channels: u16, mp3_mode: Mp3Mode) -> anyhow::Result<()>
+    {
+        match self
+        {
+            Self::Flac(flac) => { flac.finish()?; }
+            Self::Wav(wav) => wav.finalize()?,
+            Self::Buffer(samples) => match format
+            {
+                ExportFormat::Mp3 => crate::export_to_mp3(path, &samples, sample_rate, channels, mp3_mode)?,
+                ExportFormat::Aiff => crate::audio::export_to_aiff(path, &samples, sample_rate, channels)?,
+                ExportFormat::Alac => crate::alac::export_to_alac(path, &samples, sample_rate, channels)?,
+                ExportFormat::Flac | ExportFormat::Wav => unreachable!("{} is written as it decodes", format.name()),
+            },
+        }
+        Ok(())
+    }
+}
+
+/// How the GUI exports the playlist
+#[derive(Clone, Copy, Debug)]
+struct ExportSettings
+{
+    format: ExportFormat,
+    flac_level: u8,
+    mp3_mode: Mp3Mode,
+    /// Write each track to a file of its own, named after it, rather than joining them
+    per_track: bool,
+}
+
+/// File that track `index` (from `input`) of a playlist of `count` is exported to in `dir`: its
+/// number, so the files sort in playlist order and repeated tracks don't collide, then its name
+fn track_export_path(dir: &Path, index: usize, count: usize, input: &Path, format: ExportFormat) -> PathBuf
+{
+    let width = count.to_string().len().max(2);
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    dir.join(format!("{:0width$} {}.{}", index + 1, stem, format.extension(), width = width))
+}
+
+/// Decode `playlist` into `output`: one file joining every track, or with `per_track` a directory
+/// to write a file per track into. Reports the share done, from 0 to 1, with a status message, and
+/// returns the files written, or None if `cancel` was set first (removing the file being written)
+fn export_playlist(playlist: &[PathBuf], output: &Path, settings: ExportSettings, cancel: &AtomicBool,
+                   report: &dyn Fn(f32, String)) -> anyhow::Result<Option<usize>>
+{
+    // Every file is opened first, so the total length and with it the progress is exact
+    let mut sources = Vec::with_capacity(playlist.len());
+    for path in playlist
+    {
+        let source = match GlcDecoder::new(BufReader::new(File::open(path)?))
+        {
+            Ok(glc) => Box::new(GlcSource::new(glc)?) as Box<dyn SeekableSource>,
+            // Files from before the frame-delimited container
+            Err(_) => Box::new(GlcSource::new(load_encoded(path)?)?),
+        };
+        sources.push(source);
+    }
+    let Some(first) = sources.first()
+    else
+    {
+        anyhow::bail!("The playlist is empty");
+    };
+    let (sample_rate, channels) = (first.sample_rate(), first.channels());
+    // Tracks joined into one file have to share a format; files of their own needn't
+    if let Some(i) = sources.iter().position(|source| (source.sample_rate(), source.channels()) != (sample_rate, channels))
+                            .filter(|_| !settings.per_track)
+    {
+        anyhow::bail!("{:?} is {} Hz with {} channels, unlike the first file ({} Hz, {} channels)",
+                      playlist[i].file_name().unwrap_or_default(), sources[i].sample_rate(), sources[i].channels(),
+                      sample_rate, channels);
+    }
+    let total: u64 = sources.iter().map(|source| source.length()).sum();
+
+    let format = settings.format;
+    let mut writer = None;
+    let mut done = 0u64;
+    let mut chunk = Vec::new();
+    for (i, source) in sources.iter_mut().enumerate()
+    {
+        let (sample_rate, channels) = (source.sample_rate(), source.channels());
+        let path = if settings.per_track { track_export_path(output, i, playlist.len(), &playlist[i], format) } else { output.to_path_buf() };
+        if writer.is_none()
+        {
+            writer = Some(ExportWriter::create(&path, format, sample_rate, channels, settings.flac_level)?);
+        }
+        let length = source.length();
+        let mut decoded = 0u64;
+        loop
+        {
+            if cancel.load(Ordering::Relaxed)
+            {
+                if let Some(ExportWriter::Flac(_) | ExportWriter::Wav(_)) = writer.take()
+                {
+                    std::fs::remove_file(&path)?;
+                }
+                return Ok(None);
+            }
+            chunk.clear();
+            chunk.extend(source.by_ref().take(EXPORT_CHUNK_FRAMES * channels as usize));
+            if chunk.is_empty()
+            {
+                break;
+            }
+            writer.as_mut().unwrap().write(&chunk)?;
+            let frames = (chunk.len() / channels as usize) as u64;
+            decoded += frames;
+            done += frames;
+            report(done as f32 / total.max(1) as f32,
+                   format!("Decoding file {}/{}: {:?}", i + 1, playlist.len(), playlist[i].file_name().unwrap_or_default()));
+        }
+        // A frame that fails to decode ends the source early
+        if decoded < length
+        {
+            anyhow::bail!("{:?} ended after {} of {} samples", playlist[i].file_name().unwrap_or_default(), decoded, length);
+        }
+
+        if settings.per_track || i + 1 == playlist.len()
+        {
+            report(done as f32 / total.max(1) as f32, format!("Writing {:?}", path.file_name().unwrap_or_default()));
+            writer.take().unwrap().finish(&path, format, sample_rate, channels, settings.mp3_mode)?;
+        }
+    }
+    Ok(Some(if settings.per_track { playlist.len() } else { 1 }))
+}
+
+impl CodecApp 
+{
+    pub fn new() -> Self 
+    {
+        let mut settings = GuiSettings::load();
+        let log = Log::default();
+        // The device saved last time may have been unplugged since
+        let player = Player::new(settings.output_device.as_deref()).or_else(|e|
+        {
+            log.warn(format!("Can't open {}, using the default output: {}", settings.output_device.as_deref().unwrap_or_default(), e));
+            settings.output_device = None;
+            Player::new(None)
+        }).unwrap_or_else(|_| 
+        {
+            panic!("Failed to get default audio output device");
+        });
+
+        // Tracks from last time that have since been moved or deleted are left out
+        let (playlist, missing): (Vec<PathBuf>, Vec<PathBuf>) = settings.playlist.iter().cloned().partition(|path| path.exists());
+        for path in missing
+        {
+            log.warn(format!("{} from the last session's playlist is missing", path.display()));
+        }
+        
+        let mut app = Self
+        {
+            encode: EncodeView::default(),
+            library: LibraryView::default(),
+            analyze: AnalyzeView::new(settings.encoder.quality),
+            abx: AbxView::new(settings.encoder.quality),
+            playlist,
+            playlist_selected: BTreeSet::new(),
+            playlist_drag: None,
+            status: Arc::new(Mutex::new("Ready".to_string())),
+            detailed_status: Arc::new(Mutex::new(String::new())),
+            log,
+            is_playing: false,
+            is_testing: false,
+            current_track: 0,
+            player,
+            loading_tracks: None,
+            seek_drag: None,
+            track_durations: Vec::new(),
+            volume: 1.0,
+            limiter: true,
+            replay_gain: ReplayGain::default(),
+            crossfade: 0.0,
+            tempo: 1.0,
+            eq_gains: [0.0; 10],
+            eq_enabled: true,
+            repeat: Repeat::Off,
+            shuffle: false,
+            health: VecDeque::with_capacity(HEALTH_HISTORY),
+            last_health_sample: Instant::now(),
+            meters: Meters::default(),
+            test_sink: None,
+            export_progress: Arc::new(Mutex::new(None)),
+            export_cancel: Arc::new(AtomicBool::new(false)),
+            confirm_close: false,
+            allow_close: false,
+            settings,
+            waveform_path: None,
+            waveform: None,
+            waveform_loading: None,
+            waveform_followed: None,
+            progress_receiver: None,
+            test_file_path: None,
+            output_devices: output_device_names().unwrap_or_default(),
+            resume_at: None,
+            flac_compression_level: 5, // Default to level 5
+            mp3_mode: Mp3Mode::default(),
+            export_format: ExportFormat::Flac,
+            export_per_track: false,
+        };
+        // The music folder is scanned afresh each session, since it may have changed since
+        app.scan_library();
+        app
+    }
+    
+    fn update_status(&self, msg: String) 
+    {
+        *self.status.lock().unwrap() = msg;
+    }
+    
+    fn update_detailed_status(&self, msg: String) 
+    {
+        *self.detailed_status.lock().unwrap() = msg;
+    }
+
+    /// Show `msg` in the status bar and keep it in the console as an error
+    fn report_error(&self, msg: String)
+    {
+        self.log.error(msg.clone());
+        self.update_status(msg);
+    }
+
+    /// Show `msg` in the status bar and keep it in the console as a warning
+    fn report_warning(&self, msg: String)
+    {
+        self.log.warn(msg.clone());
+        self.update_status(msg);
+    }
+    
+    /// Encodes queued or underway, and whether a playlist export is running
+    fn running_tasks(&self) -> (usize, bool)
+    {
+        let encodes = self.encode.queue.lock().unwrap().iter().filter(|job| job.is_pending()).count();
+        (encodes, self.export_progress.lock().unwrap().is_some())
+    }
+
+    /// Ask whether to cancel the encodes and export still running and quit, closing once they have
+    /// stopped, or to keep them going
+    fn show_close_confirmation(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame)
+    {
+        let (encodes, exporting) = self.running_tasks();
+        if encodes == 0 && !exporting
+        {
+            self.allow_close = true;
+            self.save_session();
+            frame.close();
+            return;
+        }
+        egui::Window::new("Quit?")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui|
+        {
+            let mut running = Vec::new();
+            if encodes > 0
+            {
+                running.push(format!("{} file{} to encode", encodes, if encodes == 1 { "" } else { "s" }));
+            }
+            if exporting
+            {
+                running.push("a playlist export".to_string());
+            }
+            ui.label(format!("Still running: {}.", running.join(" and ")));
+            if self.allow_close
+            {
+                ui.label("Stopping…");
+                return;
+            }
+            ui.horizontal(|ui|
+            {
+                if ui.button("Cancel and Quit").clicked()
+                {
+                    self.encode.queue.lock().unwrap().iter_mut().filter(|job| job.is_pending()).for_each(EncodeJob::cancel);
+                    self.export_cancel.store(true, Ordering::Relaxed);
+                    // Closes once the tasks notice, so no half-written files are left behind
+                    self.allow_close = true;
+                }
+                if ui.button("Keep Running").clicked()
+                {
+                    self.confirm_close = false;
+                }
+            });
+        });
+    }
+
+    /// Add files dropped onto the window: audio to the encode list, `.glc` files to the playlist,
+    /// and shade the window while files are dragged over it
+    fn handle_dropped_files(&mut self, ctx: &egui::Context)
+    {
+        if ctx.input(|i| !i.raw.hovered_files.is_empty())
+        {
+            let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("file_drop_target")));
+            let screen = ctx.screen_rect();
+            painter.rect_filled(screen, 0.0, egui::Color32::from_black_alpha(192));
+            painter.text(screen.center(), egui::Align2::CENTER_CENTER,
+                         "Drop audio files to encode them, or .glc files to add them to the playlist",
+                         egui::TextStyle::Heading.resolve(&ctx.style()), egui::Color32::WHITE);
+        }
+
+        let dropped = ctx.input(|i| i.raw.dropped_files.clone());
+        if dropped.is_empty()
+        {
+            return;
+        }
+        let (mut to_encode, mut to_play, mut skipped) = (0, 0, 0);
+        for path in dropped.into_iter().filter_map(|file| file.path)
+        {
+            let is_glc = path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("glc"));
+            if is_glc
+            {
+                self.playlist.push(path);
+                to_play += 1;
+            }
+            else if is_native_input(&path)
+            {
+                if !self.encode.files.contains(&path)
+                {
+                    self.encode.files.push(path);
+                }
+                to_encode += 1;
+            }
+            else
+            {
+                skipped += 1;
+            }
+        }
+
+        let mut parts = Vec::new();
+        if to_encode > 0
+        {
+            parts.push(format!("{} file(s) to encode", to_encode));
+        }
+        if to_play > 0
+        {
+            parts.push(format!("{} file(s) to the playlist", to_play));
+        }
+        let mut message = if parts.is_empty() { "Nothing added".to_string() } else { format!("Added {}", parts.join(" and ")) };
+        if skipped > 0
+        {
+            message.push_str(&format!("; skipped {} unsupported file(s)", skipped));
+            self.report_warning(message);
+            return;
+        }
+        self.update_status(message);
+    }
+
+    /// Settings for files queued for encoding from now on, saved whenever they change
+    fn show_encoder_settings(&mut self, ui: &mut egui::Ui)
+    {
+        let settings = &mut self.settings.encoder;
+        let before = *settings;
+        ui.add(egui::Slider::new(&mut settings.quality, QUALITY_RANGE).text("Quality").step_by(0.05))
+          .on_hover_text("Higher keeps more of the signal, in larger files");
+        ui.horizontal(|ui|
+        {
+            let mut cut = settings.cutoff.is_some();
+            ui.checkbox(&mut cut, "Lowpass at");
+            let mut cutoff = settings.cutoff.unwrap_or(16000.0);
+            ui.add_enabled(cut, egui::Slider::new(&mut cutoff, MIN_CUTOFF..=20000.0).suffix(" Hz").logarithmic(true));
+            settings.cutoff = cut.then_some(cutoff);
+        });
+        ui.horizontal(|ui|
+        {
+            let mut level = settings.normalize_lufs.is_some();
+            ui.checkbox(&mut level, "Level to");
+            let mut lufs = settings.normalize_lufs.unwrap_or(-16.0);
+            ui.add_enabled(level, egui::Slider::new(&mut lufs, -30.0..=-5.0).suffix(" LUFS"));
+            settings.normalize_lufs = level.then_some(lufs);
+        });
+        ui.checkbox(&mut settings.low_delay, "Low delay (512-sample frames, for live use)");
+        if ui.button("Defaults").clicked()
+        {
+            *settings = EncoderSettings::default();
+        }
+
+        if *settings != before
+        {
+            self.save_settings();
+        }
+    }
+
+    /// Keep the playlist and the rest of this session in `gui.toml` for the next run
+    fn save_session(&mut self)
+    {
+        self.settings.playlist = self.playlist.clone();
+        self.save_settings();
+    }
+
+    /// Note where the window is, unless it's minimized; while maximized, the size it
+    /// returns to is kept
+    fn track_window(&mut self, ctx: &egui::Context)
+    {
+        let viewport = ctx.input(|input| input.viewport().clone());
+        if viewport.minimized == Some(true)
+        {
+            return;
+        }
+        let maximized = viewport.maximized == Some(true);
+        match (&mut self.settings.window, viewport.outer_rect, viewport.inner_rect)
+        {
+            (Some(window), _, _) if maximized => window.maximized = true,
+            (window, Some(outer), Some(inner)) => *window = Some(WindowGeometry
+            {
+                x: outer.left(),
+                y: outer.top(),
+                width: inner.width(),
+                height: inner.height(),
+                maximized,
+            }),
+            _ => {}
+        }
+    }
+
+    fn save_settings(&self)
+    {
+        if let Err(e) = self.settings.save()
+        {
+            self.report_error(format!("Couldn't save settings: {}", e));
+        }
+    }
+
+    /// Add `files` to the encode queue (skipping any already waiting or underway) and start
+    /// workers for them, up to [`MAX_CONCURRENT_ENCODES`]
+    fn queue_encodes(&mut self, files: Vec<PathBuf>)
+    {
+        let mut queue = self.encode.queue.lock().unwrap();
+        for input in files
+        {
+            if !queue.iter().any(|job| job.input == input && job.is_pending())
+            {
+                self.encode.next_id += 1;
+                queue.push(EncodeJob::new(self.encode.next_id, input, self.settings.encoder.options()));
+            }
+        }
+        let waiting = queue.iter().filter(|job| job.state == EncodeState::Queued).count();
+        let running = self.encode.workers.load(Ordering::Relaxed);
+        for _ in running..(running + waiting).min(MAX_CONCURRENT_ENCODES)
+        {
+            self.encode.workers.fetch_add(1, Ordering::Relaxed);
+            let (queue, workers, log) = (self.encode.queue.clone(), self.encode.workers.clone(), self.log.clone());
+            thread::spawn(move || run_encode_worker(&queue, &workers, &log));
+        }
+    }
+
+    /// One row per file queued for encoding, with its progress or outcome
+    fn show_encode_queue(&mut self, ui: &mut egui::Ui)
+    {
+        let mut to_play = Vec::new();
+        let mut queue = self.encode.queue.lock().unwrap();
+        if queue.is_empty()
+        {
+            return;
+        }
+        egui::ScrollArea::vertical()
+            .id_source("encode_queue_scroll")
+            .max_height(150.0)
+            .show(ui, |ui|
+        {
+            egui::Grid::new("encode_queue").num_columns(6).striped(true).show(ui, |ui|
+            {
+                for job in queue.iter_mut()
+                {
+                    ui.label(job.input.file_name().unwrap_or_default().to_string_lossy().into_owned());
+                    match job.state
+                    {
+                        EncodeState::Queued => { ui.label("Queued"); }
+                        EncodeState::Loading => { ui.add(egui::ProgressBar::new(0.0).text("Loading").animate(true)); }
+                        EncodeState::Encoding =>
+                        {
+                            ui.add(egui::ProgressBar::new(job.progress).text(format!("{:.0}%", job.progress * 100.0)));
+                        }
+                        EncodeState::Done => { ui.colored_label(egui::Color32::GREEN, "Done"); }
+                        EncodeState::Failed(ref e) =>
+                        {
+                            ui.colored_label(egui::Color32::RED, "Error").on_hover_text(e);
+                        }
+                        EncodeState::Cancelled => { ui.label("Cancelled"); }
+                    }
+                    ui.label(job.ratio.map(|ratio| format!("{:.2}x", ratio)).unwrap_or_default());
+                    ui.label(job.time().map(|time| format!("{:.1} s", time.as_secs_f32())).unwrap_or_default());
+                    if job.state == EncodeState::Done && ui.button("Add to Playlist").clicked()
+                    {
+                        to_play.push(job.output.clone());
+                    }
+                    if job.is_pending() && ui.add_enabled(!job.cancel.load(Ordering::Relaxed), egui::Button::new("Cancel")).clicked()
+                    {
+                        job.cancel();
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+
+        let pending = queue.iter().filter(|job| job.is_pending()).count();
+        ui.horizontal(|ui|
+        {
+            ui.label(format!("{} of {} files left to encode", pending, queue.len()));
+            if pending < queue.len() && ui.button("Clear Finished").clicked()
+            {
+                queue.retain(EncodeJob::is_pending);
+            }
+            if pending > 1 && ui.button("Cancel All").clicked()
+            {
+                queue.iter_mut().filter(|job| job.is_pending()).for_each(EncodeJob::cancel);
+            }
+        });
+        drop(queue);
+        self.playlist.extend(to_play);
+    }
+
+    /// The playlist: clicking an entry shows it below (Ctrl/Cmd-click picks several to remove),
+    /// dragging its handle moves it, and the whole list can be saved to or loaded from an M3U file
+    fn show_playlist(&mut self, ui: &mut egui::Ui)
+    {
+        ui.label("Playlist (for gapless playback test):");
+        let mut to_remove = None;
+        let mut to_show = None;
+        let mut rows = Vec::with_capacity(self.playlist.len());
+        egui::ScrollArea::vertical()
+            .id_source("playlist_scroll")
+            .max_height(120.0)
+            .show(ui, |ui|
+        {
+            for (i, path) in self.playlist.iter().enumerate()
+            {
+                let row = ui.horizontal(|ui|
+                {
+                    // Reordering the tracks playing would leave the player out of step with the list
+                    let handle = ui.add_enabled(!self.is_playing, egui::Label::new("☰").sense(egui::Sense::drag()))
+                                   .on_hover_cursor(egui::CursorIcon::Grab);
+                    if handle.drag_started()
+                    {
+                        self.playlist_drag = Some(i);
+                    }
+                    let selected = self.playlist_selected.contains(&i) || self.playlist_drag == Some(i);
+                    if ui.selectable_label(selected, format!("{}. {:?}", i + 1, path.file_name().unwrap())).clicked()
+                    {
+                        if !ui.input(|input| input.modifiers.command)
+                        {
+                            self.playlist_selected.clear();
+                            to_show = Some(path.clone());
+                        }
+                        if !self.playlist_selected.remove(&i)
+                        {
+                            self.playlist_selected.insert(i);
+                        }
+                    }
+                    if ui.button(format!("Remove##{}", i)).clicked()
+                    {
+                        to_remove = Some(i);
+                    }
+                });
+                rows.push(row.response.rect);
+            }
+        });
+
+        if let Some(from) = self.playlist_drag
+        {
+            if !ui.input(|input| input.pointer.any_down()) || from >= self.playlist.len()
+            {
+                self.playlist_drag = None;
+            }
+            else if let Some(pointer) = ui.ctx().pointer_interact_pos()
+            {
+                // The entry lands on whichever row the pointer is over, or the end it went past
+                let to = rows.iter().position(|rect| pointer.y < rect.bottom()).unwrap_or(rows.len() - 1);
+                if to != from
+                {
+                    self.move_playlist_entry(from, to);
+                    self.playlist_drag = Some(to);
+                }
+            }
+        }
+        if let Some(i) = to_remove
+        {
+            self.remove_playlist_entries(&BTreeSet::from([i]));
+        }
+        if let Some(path) = to_show
+        {
+            self.load_details(path.clone());
+            self.load_waveform(path);
+        }
+
+        ui.horizontal(|ui|
+        {
+            if !self.playlist.is_empty() && ui.button("Clear Playlist").clicked()
+            {
+                self.playlist.clear();
+                self.playlist_selected.clear();
+            }
+            if !self.playlist_selected.is_empty()
+                && ui.button(format!("Remove Selected ({})", self.playlist_selected.len()))
+                     .on_hover_text(ui.ctx().format_shortcut(&REMOVE))
+                     .clicked()
+            {
+                let selected = std::mem::take(&mut self.playlist_selected);
+                self.remove_playlist_entries(&selected);
+            }
+            if ui.button("Add Files…").on_hover_text(ui.ctx().format_shortcut(&OPEN)).clicked()
+            {
+                self.pick_playlist_files();
+            }
+            if ui.button("Load Playlist…").clicked()
+            {
+                if let Some(path) = file_dialog(&self.settings.dirs.playlist).add_filter("M3U playlists", &["m3u", "m3u8"]).pick_file()
+                {
+                    remember_dir(&mut self.settings.dirs.playlist, &path);
+                    match read_m3u(&path)
+                    {
+                        Ok(files) =>
+                        {
+                            let missing: Vec<&PathBuf> = files.iter().filter(|file| !file.exists()).collect();
+                            if missing.is_empty()
+                            {
+                                self.update_status(format!("Loaded {} tracks", files.len()));
+                            }
+                            else
+                            {
+                                for file in &missing
+                                {
+                                    self.log.warn(format!("{} in {} is missing", file.display(), path.display()));
+                                }
+                                self.update_status(format!("Loaded {} tracks, {} of them missing", files.len(), missing.len()));
+                            }
+                            self.playlist = files;
+                            self.playlist_selected.clear();
+                        }
+                        Err(e) => self.report_error(format!("Can't load the playlist: {}", e)),
+                    }
+                }
+            }
+            if !self.playlist.is_empty() && ui.button("Save Playlist…").clicked()
+            {
+                if let Some(path) = file_dialog(&self.settings.dirs.playlist)
+                    .add_filter("M3U playlists", &["m3u", "m3u8"])
+                    .set_file_name("playlist.m3u")
+                    .save_file()
+                {
+                    remember_dir(&mut self.settings.dirs.playlist, &path);
+                    match write_m3u(&path, &self.playlist)
+                    {
+                        Ok(()) => self.update_status(format!("Saved the playlist to {:?}", path.file_name().unwrap_or_default())),
+                        Err(e) => self.report_error(format!("Can't save the playlist: {}", e)),
+                    }
+                }
+            }
+        });
+    }
+
+    /// Export format and its settings, whether to write a file per track, and the export button
+    fn show_export_options(&mut self, ui: &mut egui::Ui)
+    {
+        ui.horizontal(|ui|
+        {
+            ui.label("Format:");
+            egui::ComboBox::from_id_source("export_format")
+                .selected_text(self.export_format.name())
+                .show_ui(ui, |ui|
+                {
+                    for format in ExportFormat::available()
+                    {
+                        ui.selectable_value(&mut self.export_format, format, format.name());
+                    }
+                });
+
+            match self.export_format
+            {
+                ExportFormat::Flac =>
+                {
+                    ui.label("Compression Level:");
+                    ui.add(egui::Slider::new(&mut self.flac_compression_level, 0..=8));
+                }
+                ExportFormat::Mp3 =>
+                {
+                    ui.label("Bitrate:");
+                    egui::ComboBox::from_id_source("mp3_mode")
+                        .selected_text(self.mp3_mode.to_string())
+                        .show_ui(ui, |ui|
+                        {
+                            for mode in MP3_MODES
+                            {
+                                ui.selectable_value(&mut self.mp3_mode, mode, mode.to_string());
+                            }
+                        });
+                }
+                _ => {}
+            }
+        });
+        ui.checkbox(&mut self.export_per_track, "One file per track");
+
+        let exporting = self.export_progress.lock().unwrap().is_some();
+        if ui.add_enabled(!exporting && !self.playlist.is_empty(), egui::Button::new("Export Playlist")).clicked()
+        {
+            let extension = self.export_format.extension();
+            let output = if self.export_per_track
+            {
+                file_dialog(&self.settings.dirs.output).set_title("Folder to export the tracks to").pick_folder()
+            }
+            else
+            {
+                file_dialog(&self.settings.dirs.output)
+                    .set_file_name(format!("output.{}", extension))
+                    .add_filter(self.export_format.name(), &[extension])
+                    .save_file()
+                    .map(|path| if path.extension().is_none() { path.with_extension(extension) } else { path })
+            };
+            if let Some(output) = output
+            {
+                remember_dir(&mut self.settings.dirs.output, &output);
+                self.export_playlist_async(output);
+            }
+        }
+    }
+
+    /// Move playlist entry `from` to index `to`, taking its selection with it
+    fn move_playlist_entry(&mut self, from: usize, to: usize)
+    {
+        let path = self.playlist.remove(from);
+        self.playlist.insert(to, path);
+        let mut selected: Vec<bool> = (0..self.playlist.len()).map(|i| self.playlist_selected.contains(&i)).collect();
+        let picked = selected.remove(from);
+        selected.insert(to, picked);
+        self.playlist_selected = selected.iter().enumerate().filter(|&(_, &picked)| picked).map(|(i, _)| i).collect();
+    }
+
+    /// Pick source files to encode, replacing those picked before
+    fn pick_files_to_encode(&mut self)
+    {
+        if let Some(paths) = file_dialog(&self.settings.dirs.input)
+            .add_filter("Audio files", &["wav", "flac", "aif", "aiff", "aifc", "caf"])
+            .pick_files()
+        {
+            if let Some(first) = paths.first()
+            {
+                remember_dir(&mut self.settings.dirs.input, first);
+            }
+            self.encode.files = paths;
+        }
+    }
+
+    /// Pick `.glc` files to list on the Library tab
+    fn pick_encoded_files(&mut self)
+    {
+        if let Some(paths) = file_dialog(&self.settings.dirs.encoded)
+            .add_filter("Encoded files", &["glc"])
+            .pick_files()
+        {
+            if let Some(first) = paths.first()
+            {
+                remember_dir(&mut self.settings.dirs.encoded, first);
+            }
+            for path in paths 
+            {
+                if let Ok(encoded) = load_encoded(&path) 
+                {
+                    self.library.files.push((path, encoded));
+                }
+            }
+        }
+    }
+
+    /// Pick `.glc` files to add to the end of the playlist
+    fn pick_playlist_files(&mut self)
+    {
+        if let Some(paths) = file_dialog(&self.settings.dirs.encoded)
+            .add_filter("Encoded files", &["glc"])
+            .pick_files()
+        {
+            if let Some(first) = paths.first()
+            {
+                remember_dir(&mut self.settings.dirs.encoded, first);
+            }
+            self.playlist.extend(paths);
+        }
+    }
+
+    fn pick_file_to_analyze(&mut self)
+    {
+        if let Some(path) = file_dialog(&self.settings.dirs.input)
+            .add_filter("Audio files", &["wav", "flac", "aif", "aiff", "aifc", "caf"])
+            .pick_file()
+        {
+            remember_dir(&mut self.settings.dirs.input, &path);
+            self.analyze.file = Some(path);
+        }
+    }
+
+    /// Act on the keyboard shortcuts pressed since the last frame, unless a text field has the
+    /// keyboard; the arrow keys and space are taken before any widget sees them
+    fn handle_shortcuts(&mut self, ctx: &egui::Context)
+    {
+        if ctx.wants_keyboard_input()
+        {
+            return;
+        }
+        let pressed = |shortcut: &egui::KeyboardShortcut| ctx.input_mut(|input| input.consume_shortcut(shortcut));
+        if pressed(&PLAY_PAUSE)
+        {
+            self.play_pause();
+        }
+        // Seeking and skipping only make sense once the tracks have been opened
+        let playing = self.is_playing && self.loading_tracks.is_none();
+        if pressed(&PREVIOUS_TRACK) && playing
+        {
+            self.player.previous_track();
+        }
+        if pressed(&NEXT_TRACK) && playing
+        {
+            self.player.next_track();
+        }
+        if pressed(&SEEK_BACK) && playing
+        {
+            self.player.seek_by(-SEEK_STEP);
+        }
+        if pressed(&SEEK_FORWARD) && playing
+        {
+            self.player.seek_by(SEEK_STEP);
+        }
+        if pressed(&OPEN)
+        {
+            match self.settings.tab
+            {
+                Tab::Encode => self.pick_files_to_encode(),
+                Tab::Library => self.pick_encoded_files(),
+                Tab::Play => self.pick_playlist_files(),
+                Tab::Analyze => self.pick_file_to_analyze(),
+            }
+        }
+        if self.settings.tab == Tab::Play && !self.playlist_selected.is_empty() && pressed(&REMOVE)
+        {
+            let selected = std::mem::take(&mut self.playlist_selected);
+            self.remove_playlist_entries(&selected);
+        }
+    }
+
+    /// Start the playlist if it's stopped, or pause or resume it if it's playing
+    fn play_pause(&mut self)
+    {
+        if !self.is_playing
+        {
+            self.play_playlist_async();
+        }
+        else if self.loading_tracks.is_some()
+        {
+            // Nothing is playing yet to pause
+        }
+        else if self.player.is_paused()
+        {
+            match self.player.play()
+            {
+                Ok(()) => self.update_status("Playing".to_string()),
+                Err(e) => self.report_error(format!("Failed to resume playback: {}", e)),
+            }
+        }
+        else
+        {
+            self.player.pause();
+            self.update_status("Paused".to_string());
+        }
+    }
+
+    /// Remove the playlist entries at `indices`; the selection is cleared, its indices no longer
+    /// pointing at the same entries
+    fn remove_playlist_entries(&mut self, indices: &BTreeSet<usize>)
+    {
+        let mut i = 0;
+        self.playlist.retain(|_|
+        {
+            i += 1;
+            !indices.contains(&(i - 1))
+        });
+        self.playlist_selected.clear();
+    }
+
+    /// Decode an overview of `path` in the background to show under the playlist
+    fn load_waveform(&mut self, path: PathBuf)
+    {
+        if self.waveform_path.as_ref() == Some(&path)
+        {
+            return;
+        }
+        let (sender, receiver) = bounded(1);
+        self.waveform_path = Some(path.clone());
+        self.waveform = None;
+        self.waveform_loading = Some(receiver);
+        thread::spawn(move ||
+        {
+            // Nobody is waiting any more if another track was picked meanwhile
+            sender.send(Waveform::from_glc(&path, WAVEFORM_COLUMNS)).ok();
+        });
+    }
+
+    /// The selected track's waveform, following playback onto each new track, with the playhead
+    /// drawn while it plays and clicks seeking within it
+    fn show_waveform(&mut self, ui: &mut egui::Ui)
+    {
+        let control = self.player.control().clone();
+        let position = control.position();
+        let playing = self.is_playing && self.loading_tracks.is_none();
+        if playing && self.waveform_followed != Some(position.track)
+        {
+            self.waveform_followed = Some(position.track);
+            if let Some(path) = self.playlist.get(position.track).cloned()
+            {
+                self.load_waveform(path);
+            }
+        }
+        if let Some(receiver) = &self.waveform_loading
+        {
+            match receiver.try_recv()
+            {
+                Ok(Ok(waveform)) =>
+                {
+                    self.waveform = Some(waveform);
+                    self.waveform_loading = None;
+                }
+                Ok(Err(e)) =>
+                {
+                    self.report_error(format!("Can't draw the waveform: {}", e));
+                    self.waveform_loading = None;
+                }
+                Err(_) => {}
+            }
+        }
+
+        let Some(path) = self.waveform_path.clone()
+        else
+        {
+            return;
+        };
+        let (rect, response) = ui.allocate_exact_size(egui::vec2(ui.available_width(), 80.0), egui::Sense::click());
+        let visuals = ui.visuals();
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, visuals.extreme_bg_color);
+        let Some(waveform) = &self.waveform
+        else
+        {
+            painter.text(rect.center(), egui::Align2::CENTER_CENTER, "Decoding…",
+                         egui::TextStyle::Body.resolve(ui.style()), visuals.weak_text_color());
+            return;
+        };
+
+        let y = |sample: f32| rect.center().y - sample.clamp(-1.0, 1.0) * rect.height() / 2.0;
+        let width = rect.width() / waveform.columns.len().max(1) as f32;
+        let stroke = egui::Stroke::new(width.max(1.0), visuals.selection.bg_fill);
+        for (i, &(min, max)) in waveform.columns.iter().enumerate()
+        {
+            let x = rect.left() + (i as f32 + 0.5) * width;
+            painter.line_segment([egui::pos2(x, y(max)), egui::pos2(x, y(min))], stroke);
+        }
+        painter.hline(rect.x_range(), rect.center().y, visuals.widgets.noninteractive.bg_stroke);
+
+        // The playhead, and seeking by clicking, only while this track is the one playing
+        let current = playing && self.playlist.get(position.track) == Some(&path);
+        if current && waveform.duration > 0.0
+        {
+            let x = rect.left() + (position.elapsed / waveform.duration).clamp(0.0, 1.0) as f32 * rect.width();
+            painter.vline(x, rect.y_range(), egui::Stroke::new(2.0, egui::Color32::RED));
+            if let Some(pointer) = response.interact_pointer_pos().filter(|_| response.clicked())
+            {
+                let seconds = ((pointer.x - rect.left()) / rect.width()).clamp(0.0, 1.0) as f64 * waveform.duration;
+                control.seek_to(seconds);
+            }
+        }
+        ui.label(format!("{:?}  {}", path.file_name().unwrap_or_default(), format_time(waveform.duration)));
+    }
+
+    /// Read the tags and details of `path` in the background to show in the metadata panel
+    fn load_details(&mut self, path: PathBuf)
+    {
+        if self.library.details.as_ref().is_some_and(|details| details.path == path)
+        {
+            return;
+        }
+        let (sender, receiver) = bounded(1);
+        self.library.details = None;
+        self.library.details_loading = Some(receiver);
+        thread::spawn(move ||
+        {
+            sender.send(FileDetails::read(&path)).ok();
+        });
+    }
+
+    /// Bitrate and coefficients kept frame by frame for the file picked in the library, with raw
+    /// PCM fallbacks and transients highlighted
+    fn show_frame_graphs(&mut self, ui: &mut egui::Ui)
+    {
+        if let Some(receiver) = &self.library.frames_loading
+        {
+            match receiver.try_recv()
+            {
+                Ok(Ok(graphs)) =>
+                {
+                    self.library.frames = Some(graphs);
+                    self.library.frames_loading = None;
+                }
+                Ok(Err(e)) =>
+                {
+                    self.report_error(format!("Can't read the frames: {}", e));
+                    self.library.frames_loading = None;
+                }
+                Err(_) => {}
+            }
+        }
+
+        let Some(path) = self.library.details.as_ref().map(|details| details.path.clone())
+        else
+        {
+            ui.label("Select an encoded file");
+            return;
+        };
+        let Some(graphs) = self.library.frames.as_ref().filter(|graphs| graphs.path == path)
+        else
+        {
+            if self.library.frames_loading.is_some()
+            {
+                ui.horizontal(|ui|
+                {
+                    ui.spinner();
+                    ui.label("Decoding…");
+                });
+            }
+            else if ui.button("Analyze Frames").on_hover_text("Decodes the whole file to find its transients").clicked()
+            {
+                let (sender, receiver) = bounded(1);
+                self.library.frames_loading = Some(receiver);
+                thread::spawn(move ||
+                {
+                    sender.send(FrameGraphs::read(&path)).ok();
+                });
+            }
+            return;
+        };
+
+        let raw = graphs.stats.iter().filter(|frame| frame.raw_pcm).count();
+        let transients = graphs.stats.iter().filter(|frame| frame.transient).count();
+        let mean = graphs.kbps.iter().sum::<f64>() / graphs.kbps.len().max(1) as f64;
+        let peak = graphs.kbps.iter().copied().fold(0.0, f64::max);
+        ui.label(format!("{} frames, {} raw PCM (red), {} transients (yellow); {:.0} kbps on average, {:.0} kbps at most",
+                         graphs.stats.len(), raw, transients, mean, peak));
+
+        // Hovering either graph describes the frame under the pointer
+        let describe = |frame: usize|
+        {
+            let stats = &graphs.stats[frame];
+            format!("Frame {} at {}\n{:.0} kbps, {} coefficients{}{}", frame,
+                    format_time(stats.start_sample.max(0) as f64 / graphs.sample_rate.max(1) as f64),
+                    graphs.kbps[frame], graphs.coefficients[frame],
+                    if stats.raw_pcm { ", raw PCM" } else { "" }, if stats.transient { ", transient" } else { "" })
+        };
+        ui.label("Bitrate per frame");
+        let (response, pointed) = frame_graph(ui, &graphs.kbps, |frame| graphs.flag(frame));
+        if let Some(frame) = pointed
+        {
+            response.on_hover_text(describe(frame));
+        }
+        ui.label("Coefficients kept per frame, all channels");
+        let (response, pointed) = frame_graph(ui, &graphs.coefficients, |frame| graphs.flag(frame));
+        if let Some(frame) = pointed
+        {
+            response.on_hover_text(describe(frame));
+        }
+    }
+
+    /// Technical details of the selected `.glc` file, and its tags for editing and saving
+    fn show_details(&mut self, ui: &mut egui::Ui)
+    {
+        if let Some(receiver) = &self.library.details_loading
+        {
+            match receiver.try_recv()
+            {
+                Ok(Ok(details)) =>
+                {
+                    self.library.details = Some(details);
+                    self.library.details_loading = None;
+                }
+                Ok(Err(e)) =>
+                {
+                    self.report_error(format!("Can't read the file: {}", e));
+                    self.library.details_loading = None;
+                }
+                Err(_) => {}
+            }
+        }
+
+        let Some(details) = &mut self.library.details
+        else
+        {
+            ui.label(if self.library.details_loading.is_some() { "Reading…" } else { "Select an encoded file or playlist entry" });
+            return;
+        };
+
+        ui.label(format!("{:?}", details.path.file_name().unwrap_or_default()));
+        egui::Grid::new("details_grid").num_columns(2).show(ui, |ui|
+        {
+            ui.label("Duration:");
+            ui.label(format_time(details.duration));
+            ui.end_row();
+            ui.label("Format:");
+            ui.label(format!("{} Hz, {} channels", details.sample_rate, details.channels));
+            ui.end_row();
+            ui.label("Bitrate:");
+            ui.label(format!("{:.1} kbps", details.kbps));
+            ui.end_row();
+            ui.label("Raw PCM frames:");
+            ui.label(format!("{} of {} ({:.1}%)", details.raw_frames, details.frames,
+                             100.0 * details.raw_frames as f64 / details.frames.max(1) as f64));
+            ui.end_row();
+            if details.tracks > 0
+            {
+                ui.label("Tracks:");
+                ui.label(details.tracks.to_string());
+                ui.end_row();
+            }
+        });
+
+        ui.separator();
+        let Some(rows) = &mut details.tags
+        else
+        {
+            ui.label("Legacy file: re-encode it to add tags");
+            return;
+        };
+        let mut edited = false;
+        let mut to_remove = None;
+        egui::Grid::new("tags_grid").num_columns(3).show(ui, |ui|
+        {
+            for (i, (key, value)) in rows.iter_mut().enumerate()
+            {
+                edited |= ui.add(egui::TextEdit::singleline(key).hint_text("key").desired_width(100.0)).changed();
+                edited |= ui.add(egui::TextEdit::singleline(value).desired_width(240.0)).changed();
+                if ui.small_button("✖").on_hover_text("Remove this tag").clicked()
+                {
+                    to_remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = to_remove
+        {
+            rows.remove(i);
+            edited = true;
+        }
+        details.edited |= edited;
+
+        let mut save = false;
+        let mut revert = false;
+        ui.horizontal(|ui|
+        {
+            if ui.button("Add Tag").clicked()
+            {
+                rows.push((String::new(), String::new()));
+            }
+            save = ui.add_enabled(details.edited, egui::Button::new("Save Tags")).clicked();
+            revert = ui.add_enabled(details.edited, egui::Button::new("Revert")).clicked();
+        });
+        if save
+        {
+            match details.save_tags()
+            {
+                Ok(()) => self.update_status(format!("Saved tags to {:?}", details.path.file_name().unwrap_or_default())),
+                Err(e) => self.report_error(format!("Saving tags failed: {}", e)),
+            }
+        }
+        else if revert
+        {
+            let path = details.path.clone();
+            self.library.details = None;
+            self.load_details(path);
+        }
+    }
+
+    /// Pick a file, encode and decode it at a chosen quality, and show the spectrograms of the
+    /// original and decoded audio side by side, or what changed between them
+    fn show_analysis(&mut self, ui: &mut egui::Ui)
+    {
+        if let Some(receiver) = &self.analyze.loading
+        {
+            match receiver.try_recv()
+            {
+                Ok(Ok(analysis)) =>
+                {
+                    let difference = analysis.original.difference(&analysis.decoded);
+                    let options = egui::TextureOptions::LINEAR;
+                    self.analyze.textures = Some([
+                        ui.ctx().load_texture("analysis_original", spectrogram_image(&analysis.original.columns, heat_color), options),
+                        ui.ctx().load_texture("analysis_decoded", spectrogram_image(&analysis.decoded.columns, heat_color), options),
+                        ui.ctx().load_texture("analysis_difference", spectrogram_image(&difference, difference_color), options),
+                    ]);
+                    self.analyze.analysis = Some(analysis);
+                    self.analyze.loading = None;
+                }
+                Ok(Err(e)) =>
+                {
+                    self.report_error(format!("Analysis failed: {}", e));
+                    self.analyze.loading = None;
+                }
+                Err(_) => {}
+            }
+        }
+
+        ui.horizontal(|ui|
+        {
+            if ui.button("Select File to Analyze").on_hover_text(ui.ctx().format_shortcut(&OPEN)).clicked()
+            {
+                self.pick_file_to_analyze();
+            }
+            if let Some(ref path) = self.analyze.file
+            {
+                ui.label(format!("{:?}", path.file_name().unwrap_or_default()));
+            }
+        });
+        ui.horizontal(|ui|
+        {
+            ui.add(egui::Slider::new(&mut self.analyze.quality, QUALITY_RANGE).text("Quality").step_by(0.05));
+            let ready = self.analyze.file.is_some() && self.analyze.loading.is_none();
+            if ui.add_enabled(ready, egui::Button::new("Analyze")).clicked()
+            {
+                if let Some(path) = self.analyze.file.clone()
+                {
+                    let quality = self.analyze.quality;
+                    let (sender, receiver) = bounded(1);
+                    self.analyze.loading = Some(receiver);
+                    thread::spawn(move || sender.send(Analysis::run(&path, quality)).ok());
+                }
+            }
+            if self.analyze.loading.is_some()
+            {
+                ui.spinner();
+                ui.label("Encoding, decoding, and analyzing…");
+            }
+        });
+
+        let (Some(analysis), Some(textures)) = (&self.analyze.analysis, &self.analyze.textures)
+        else
+        {
+            return;
+        };
+        ui.horizontal(|ui|
+        {
+            ui.label(format!("Quality {:.2}, SNR {:.1} dB, up to {:.0} Hz", analysis.quality, analysis.snr_db,
+                             analysis.original.bin_frequency(analysis.original.bins())));
+            ui.selectable_value(&mut self.analyze.difference, false, "Side by Side");
+            ui.selectable_value(&mut self.analyze.difference, true, "Difference");
+        });
+        ui.label(format!("A-weighted SNR {:.1} dB, spectral distortion {:.2} dB", analysis.metrics.weighted_snr_db,
+                         analysis.metrics.spectral_distortion_db));
+        ui.label(format!("Perceptual grade {:.2} ODG, noise-to-mask {:.1} dB", analysis.metrics.odg,
+                         analysis.metrics.noise_to_mask_db))
+            .on_hover_text("Objective difference grade: 0 is transparent, -1 perceptible but not annoying, \
+                            -4 very annoying");
+        ui.collapsing("SNR by Critical Band", |ui|
+        {
+            egui::Grid::new("critical_bands").striped(true).show(ui, |ui|
+            {
+                for band in &analysis.metrics.bands
+                {
+                    ui.label(format!("{}–{} Hz", band.low_hz, band.high_hz));
+                    ui.label(format!("{:.1} dB", band.snr_db));
+                    ui.end_row();
+                }
+            });
+        });
+        let height = 256.0;
+        if self.analyze.difference
+        {
+            let size = egui::vec2(ui.available_width(), height);
+            ui.image(egui::load::SizedTexture::new(textures[2].id(), size));
+            ui.label(format!("Red: energy removed by encoding, blue: noise added (±{} dB)", DIFFERENCE_RANGE_DB));
+        }
+        else
+        {
+            let size = egui::vec2((ui.available_width() - ui.spacing().item_spacing.x) / 2.0, height);
+            ui.horizontal(|ui|
+            {
+                ui.vertical(|ui|
+                {
+                    ui.label("Original");
+                    ui.image(egui::load::SizedTexture::new(textures[0].id(), size));
+                });
+                ui.vertical(|ui|
+                {
+                    ui.label("Decoded");
+                    ui.image(egui::load::SizedTexture::new(textures[1].id(), size));
+                });
+            });
+        }
+    }
+
+    /// What encoding at a given quality keeps and discards, and whether anyone can hear it
+    fn show_analyze_tab(&mut self, ui: &mut egui::Ui)
+    {
+        egui::CollapsingHeader::new("Spectrograms").default_open(true).show(ui, |ui| self.show_analysis(ui));
+        ui.collapsing("ABX Listening Test", |ui| self.show_abx(ui));
+    }
+
+    /// Blind comparisons of excerpts of an original and its encode, scored as they're answered
+    fn show_abx(&mut self, ui: &mut egui::Ui)
+    {
+        if let Some(receiver) = &self.abx.loading
+        {
+            match receiver.try_recv()
+            {
+                Ok(Ok(clips)) =>
+                {
+                    let segment = (self.abx.segment_seconds * clips.sample_rate as f64) as u64;
+                    self.abx.test = Some(AbxTest::new(clips.frames, segment, self.abx.trials));
+                    self.abx.clips = Some(clips);
+                    self.abx.loading = None;
+                }
+                Ok(Err(e)) =>
+                {
+                    self.report_error(format!("Can't prepare the ABX test: {}", e));
+                    self.abx.loading = None;
+                }
+                Err(_) => {}
+            }
+        }
+
+        // The setup stays put while a test runs
+        let running = self.abx.test.as_ref().is_some_and(|test| !test.finished());
+        ui.add_enabled_ui(!running && self.abx.loading.is_none(), |ui|
+        {
+            ui.horizontal(|ui|
+            {
+                if ui.button("Select Original").clicked()
+                {
+                    if let Some(path) = file_dialog(&self.settings.dirs.input)
+                        .add_filter("Audio files", &["wav", "flac", "aif", "aiff", "aifc", "caf"])
+                        .pick_file()
+                    {
+                        remember_dir(&mut self.settings.dirs.input, &path);
+                        self.abx.original = Some(path);
+                    }
+                }
+                if let Some(ref path) = self.abx.original
+                {
+                    ui.label(format!("A: {:?}", path.file_name().unwrap_or_default()));
+                }
+            });
+            ui.horizontal(|ui|
+            {
+                if ui.button("Select Encode (.glc)").clicked()
+                {
+                    if let Some(path) = file_dialog(&self.settings.dirs.encoded).add_filter("Encoded files", &["glc"]).pick_file()
+                    {
+                        remember_dir(&mut self.settings.dirs.encoded, &path);
+                        self.abx.encode = Some(path);
+                    }
+                }
+                match self.abx.encode
+                {
+                    Some(ref path) =>
+                    {
+                        ui.label(format!("B: {:?}", path.file_name().unwrap_or_default()));
+                        if ui.button("Clear").clicked()
+                        {
+                            self.abx.encode = None;
+                        }
+                    }
+                    None => { ui.label("B: the original, encoded at the quality below"); }
+                }
+            });
+            ui.horizontal(|ui|
+            {
+                ui.add_enabled(self.abx.encode.is_none(),
+                               egui::Slider::new(&mut self.abx.quality, QUALITY_RANGE).text("Quality").step_by(0.05));
+                ui.add(egui::Slider::new(&mut self.abx.segment_seconds, ABX_SEGMENT_RANGE).text("Seconds per excerpt").step_by(0.5));
+                ui.add(egui::Slider::new(&mut self.abx.trials, ABX_TRIALS_RANGE).text("Trials"));
+            });
+        });
+
+        ui.horizontal(|ui|
+        {
+            if running
+            {
+                if ui.button("Abandon Test").clicked()
+                {
+                    self.stop_abx();
+                    self.abx.test = None;
+                }
+            }
+            else if ui.add_enabled(self.abx.original.is_some() && self.abx.loading.is_none(), egui::Button::new("Start Test")).clicked()
+            {
+                if let Some(original) = self.abx.original.clone()
+                {
+                    self.stop_abx();
+                    self.abx.test = None;
+                    self.abx.clips = None;
+                    let (encode, quality) = (self.abx.encode.clone(), self.abx.quality);
+                    let (sender, receiver) = bounded(1);
+                    self.abx.loading = Some(receiver);
+                    thread::spawn(move || sender.send(AbxClips::load(&original, encode.as_deref(), quality)).ok());
+                }
+            }
+            if self.abx.loading.is_some()
+            {
+                ui.spinner();
+                ui.label("Decoding both versions…");
+            }
+        });
+
+        let (Some(test), Some(clips)) = (&self.abx.test, &self.abx.clips)
+        else
+        {
+            return;
+        };
+        if test.finished()
+        {
+            let p = test.p_value();
+            ui.label(format!("{} of {} correct against {}: a {:.1}% chance of doing at least this well by guessing",
+                             test.correct(), test.trials(), clips.label, p * 100.0));
+            ui.label(if p < 0.05 { "The encode can be told apart from the original" }
+                     else { "No better than guessing: no sign the encode can be told apart from the original" });
+            return;
+        }
+
+        ui.label(format!("Trial {} of {}: listen to A, B, and X as often as you like, then say which one X is",
+                         test.answered() + 1, test.trials()));
+        let (mut play, mut answer) = (None, None);
+        ui.horizontal(|ui|
+        {
+            for (name, which) in [("A", Some(true)), ("B", Some(false)), ("X", None)]
+            {
+                if ui.button(format!("▶ {}", name)).clicked()
+                {
+                    play = Some(which);
+                }
+            }
+            if ui.add_enabled(self.abx.sink.is_some(), egui::Button::new("⏹")).clicked()
+            {
+                self.stop_abx();
+            }
+            ui.separator();
+            if ui.button("X is A").clicked()
+            {
+                answer = Some(true);
+            }
+            if ui.button("X is B").clicked()
+            {
+                answer = Some(false);
+            }
+        });
+        if let Some(which) = play
+        {
+            self.play_abx(which);
+        }
+        if let Some(x_is_a) = answer
+        {
+            self.stop_abx();
+            if let (Some(test), Some(clips)) = (&mut self.abx.test, &self.abx.clips)
+            {
+                test.answer(x_is_a);
+                if test.finished()
+                {
+                    let message = format!("ABX against {}: {} of {} correct (p = {:.3})", clips.label, test.correct(),
+                                          test.trials(), test.p_value());
+                    self.update_status(message);
+                }
+            }
+        }
+    }
+
+    /// Play the current ABX trial's excerpt of A (`Some(true)`), B (`Some(false)`), or X (None)
+    fn play_abx(&mut self, which: Option<bool>)
+    {
+        let (Some(test), Some(clips)) = (&self.abx.test, &self.abx.clips)
+        else
+        {
+            return;
+        };
+        let Some(trial) = test.trial()
+        else
+        {
+            return;
+        };
+        let samples = if which.unwrap_or(trial.x_is_a) { &clips.original } else { &clips.encoded };
+        let segment = test.segment();
+        let (channels, sample_rate) = (clips.channels, clips.sample_rate);
+        let clip = excerpt(samples, channels, sample_rate, segment.start, segment.end - segment.start);
+
+        // Nothing else may play over the excerpts
+        self.stop_abx();
+        self.stop_test_playback();
+        if self.is_playing
+        {
+            self.stop_playback();
+        }
+        let Some(output) = self.player.output()
+        else
+        {
+            self.update_status("ABX tests need the rodio backend".to_string());
+            return;
+        };
+        match Sink::try_new(output)
+        {
+            Ok(sink) =>
+            {
+                sink.append(rodio::buffer::SamplesBuffer::new(channels.max(1), sample_rate.max(1), clip));
+                self.abx.sink = Some(sink);
+            }
+            Err(e) => self.report_error(format!("Failed to create sink: {}", e)),
+        }
+    }
+
+    fn stop_abx(&mut self)
+    {
+        if let Some(sink) = self.abx.sink.take()
+        {
+            sink.stop();
+        }
+    }
+
+    fn play_playlist_async(&mut self) 
+    {
+        if self.playlist.is_empty() 
+        {
+            self.update_status("Playlist is empty".to_string());
+            return;
+        }
+        
+        // Stop any existing playback first
+        self.stop_playback();
+        
+        let playlist = self.playlist.clone();
+        let status = self.status.clone();
+        let log = self.log.clone();
+        let (sender, receiver) = bounded(1);
+        self.loading_tracks = Some(receiver);
+        self.is_playing = true;
+        
+        // The player can't leave this thread, so open the tracks elsewhere and hand them back
+        thread::spawn(move || 
+        {
+            let start_time = Instant::now();
+
+            // Frames are decoded as the player pulls samples, so playback can seek anywhere
+            let mut tracks = Vec::with_capacity(playlist.len());
+            for (idx, path) in playlist.iter().enumerate() 
+            {
+                *status.lock().unwrap() = format!("Loading file {}/{}", idx + 1, playlist.len());
+                
+                match Track::open(path) 
+                {
+                    Ok(track) => tracks.push(track),
+                    Err(e) => 
+                    {
+                        let message = format!("Error loading {}: {}", path.display(), e);
+                        log.error(message.clone());
+                        *status.lock().unwrap() = message;
+                        return;
+                    }
+                }
+            }
+
+            *status.lock().unwrap() = format!("Playing playlist (prepared in {:.2}s)", start_time.elapsed().as_secs_f32());
+            // Nobody is waiting for the tracks any more if playback was stopped meanwhile
+            sender.send(tracks).ok();
+        });
+    }
+
+    /// Start the playlist once its tracks are open, and notice when it has finished
+    fn poll_playback(&mut self)
+    {
+        if let Some(receiver) = &self.loading_tracks
+        {
+            match receiver.try_recv()
+            {
+                Ok(tracks) =>
+                {
+                    self.loading_tracks = None;
+
+                    // Everything plays as one playlist so tracks that differ in format still join without gaps
+                    let (sample_rate, channels) = output_format(&tracks);
+                    let converted = tracks.iter().filter(|t| (t.sample_rate(), t.channels()) != (sample_rate, channels)).count();
+                    self.update_detailed_status(format!("Streaming at {} Hz, {} channels ({} tracks converted)",
+                                                        sample_rate, channels, converted));
+                    self.track_durations = tracks.iter().map(Track::duration).collect();
+                    for track in tracks
+                    {
+                        self.player.queue_track(track);
+                    }
+                    self.health.clear();
+                    self.meters = Meters::new(sample_rate);
+                    if let Err(e) = self.player.play()
+                    {
+                        self.report_error(format!("Failed to start playback: {}", e));
+                        self.is_playing = false;
+                    }
+                    else if let Some((track, seconds)) = self.resume_at.take()
+                    {
+                        self.player.control().seek_track(track, seconds);
+                    }
+                }
+                Err(crossbeam_channel::TryRecvError::Empty) => {}
+                // Loading failed, and the loader has said why
+                Err(crossbeam_channel::TryRecvError::Disconnected) =>
+                {
+                    self.loading_tracks = None;
+                    self.is_playing = false;
+                }
+            }
+        }
+        else if self.is_playing && self.player.is_finished()
+        {
+            self.is_playing = false;
+            self.seek_drag = None;
+            self.update_status("Playback finished".to_string());
+        }
+        else if self.is_playing && self.last_health_sample.elapsed() >= HEALTH_INTERVAL
+        {
+            if self.health.len() == HEALTH_HISTORY
+            {
+                self.health.pop_front();
+            }
+            self.health.push_back(self.player.stats());
+            self.last_health_sample = Instant::now();
+        }
+    }
+
+    /// A seek slider for the current track, then the whole playlist as a bar of tracks in order,
+    /// where clicking or dragging jumps to that point of that track
+    fn show_timeline(&mut self, ui: &mut egui::Ui)
+    {
+        let control = self.player.control().clone();
+        let position = control.position();
+        ui.horizontal(|ui|
+        {
+            let mut seconds = self.seek_drag.unwrap_or(position.elapsed);
+            let slider = egui::Slider::new(&mut seconds, 0.0..=position.duration.max(0.001)).show_value(false);
+            let response = ui.add(slider);
+            if response.dragged()
+            {
+                self.seek_drag = Some(seconds);
+            }
+            else if response.drag_released() || response.changed()
+            {
+                control.seek_to(seconds);
+                self.seek_drag = None;
+            }
+            ui.label(format!("Track {}/{}  {} / {}", position.track + 1, self.track_durations.len(),
+                             format_time(seconds), format_time(position.duration)));
+        });
+
+        let total: f64 = self.track_durations.iter().sum();
+        if total <= 0.0
+        {
+            return;
+        }
+        let (rect, response) = ui.allocate_exact_size(egui::vec2(ui.available_width(), 18.0), egui::Sense::click_and_drag());
+        let x_at = |seconds: f64| rect.left() + (seconds / total) as f32 * rect.width();
+        let visuals = ui.visuals();
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, visuals.extreme_bg_color);
+
+        // Tracks in playlist order, the current one filled as far as it has played
+        let mut start = 0.0;
+        for (i, &duration) in self.track_durations.iter().enumerate()
+        {
+            let segment = egui::Rect::from_x_y_ranges(x_at(start)..=x_at(start + duration), rect.y_range());
+            if i == position.track
+            {
+                painter.rect_filled(segment, 0.0, visuals.selection.bg_fill.linear_multiply(0.4));
+                let played = egui::Rect::from_x_y_ranges(segment.left()..=x_at(start + position.elapsed.min(duration)),
+                                                         rect.y_range());
+                painter.rect_filled(played, 0.0, visuals.selection.bg_fill);
+            }
+            if i > 0
+            {
+                painter.vline(segment.left(), rect.y_range(), visuals.widgets.noninteractive.bg_stroke);
+            }
+            start += duration;
+        }
+
+        // The track and time under the pointer, for hovering and seeking
+        let pointed = response.interact_pointer_pos().or(response.hover_pos()).map(|pointer|
+        {
+            let seconds = ((pointer.x - rect.left()) / rect.width()).clamp(0.0, 1.0) as f64 * total;
+            let mut start = 0.0;
+            for (i, &duration) in self.track_durations.iter().enumerate()
+            {
+                if seconds < start + duration || i + 1 == self.track_durations.len()
+                {
+                    return (i, (seconds - start).clamp(0.0, duration));
+                }
+                start += duration;
+            }
+            (0, 0.0)
+        });
+        if let Some((track, seconds)) = pointed
+        {
+            let name = self.playlist.get(track).and_then(|path| path.file_name()).map(|name| name.to_string_lossy());
+            response.clone().on_hover_text(format!("{}. {}  {}", track + 1, name.unwrap_or_default(), format_time(seconds)));
+            if response.clicked() || response.dragged()
+            {
+                control.seek_track(track, seconds);
+            }
+        }
+        ui.label(format!("Playlist {} / {}", format_time(position.total_elapsed), format_time(position.total_duration)));
+    }
+
+    /// Files to encode, the encoder settings, and the encode queue
+    fn show_encode_tab(&mut self, ui: &mut egui::Ui)
+    {
+        // File selection section
+        ui.horizontal(|ui|
+        {
+            let select = ui.button("Select Audio Files (WAV/FLAC/AIFF/CAF)").on_hover_text(ui.ctx().format_shortcut(&OPEN));
+            if select.clicked()
+            {
+                self.pick_files_to_encode();
+            }
+            
+            if !self.encode.files.is_empty() 
+            {
+                ui.label(format!("{} files selected", self.encode.files.len()));
+            }
+        });
+        
+        ui.collapsing("Encoder Settings", |ui| self.show_encoder_settings(ui));
+
+        // Encode button
+        if !self.encode.files.is_empty() 
+        {
+            if ui.button("Encode Selected Files").clicked() 
+            {
+                self.queue_encodes(self.encode.files.clone());
+            }
+        }
+
+        self.show_encode_queue(ui);
+    }
+
+    /// `.glc` files loaded for browsing, and the details and tags of the one picked
+    fn show_library_tab(&mut self, ui: &mut egui::Ui)
+    {
+        // Load encoded files
+        if ui.button("Load Encoded Files (.glc)").on_hover_text(ui.ctx().format_shortcut(&OPEN)).clicked()
+        {
+            self.pick_encoded_files();
+        }
+        
+        // Encoded files list - with unique ID
+        ui.label("Encoded Files:");
+        egui::ScrollArea::vertical()
+            .id_source("encoded_files_scroll")
+            .max_height(120.0)
+            .show(ui, |ui| 
+        {
+            let mut files_to_add = Vec::new();
+            let mut to_show = None;
+            for (path, _) in &self.library.files
+            {
+                ui.horizontal(|ui|
+                {
+                    let selected = self.library.details.as_ref().is_some_and(|details| details.path == *path);
+                    if ui.selectable_label(selected, format!("{:?}", path.file_name().unwrap())).clicked()
+                    {
+                        to_show = Some(path.clone());
+                    }
+                    if ui.button(format!("Add##{:?}", path)).clicked()
+                    {
+                        files_to_add.push(path.clone());
+                    }
+                });
+            }
+            for path in files_to_add
+            {
+                self.playlist.push(path);
+            }
+            if let Some(path) = to_show
+            {
+                self.load_details(path);
+            }
+        });
+
+        egui::CollapsingHeader::new("Music Folder").default_open(true).show(ui, |ui| self.show_music_folder(ui));
+
+        ui.separator();
+        ui.collapsing("Metadata", |ui| self.show_details(ui));
+        ui.collapsing("Bit Allocation", |ui| self.show_frame_graphs(ui));
+    }
+
+    /// Scan the music folder in the background, replacing the tracks found before
+    fn scan_library(&mut self)
+    {
+        let Some(dir) = self.settings.library_dir.clone()
+        else
+        {
+            return;
+        };
+        self.library.tracks.clear();
+        let (sender, receiver) = unbounded();
+        self.library.scan = Some(receiver);
+        let log = self.log.clone();
+        thread::spawn(move ||
+        {
+            // The scan stops once its receiver is dropped, when it is cancelled or started over
+            let result = scan(&dir, |path, track| match track
+            {
+                Ok(track) => sender.send(track).is_ok(),
+                Err(e) =>
+                {
+                    log.warn(format!("Skipped {} in the music folder: {}", path.display(), e));
+                    true
+                }
+            });
+            if let Err(e) = result
+            {
+                log.error(format!("Can't scan {}: {}", dir.display(), e));
+            }
+        });
+    }
+
+    /// Take in the tracks the music folder scan has found since the last frame
+    fn poll_library_scan(&mut self)
+    {
+        let Some(receiver) = &self.library.scan
+        else
+        {
+            return;
+        };
+        let found = self.library.tracks.len();
+        let finished = loop
+        {
+            match receiver.try_recv()
+            {
+                Ok(track) => self.library.tracks.push(track),
+                Err(crossbeam_channel::TryRecvError::Empty) => break false,
+                Err(crossbeam_channel::TryRecvError::Disconnected) => break true,
+            }
+        };
+        if self.library.tracks.len() != found
+        {
+            self.sort_library();
+        }
+        if finished
+        {
+            self.library.scan = None;
+            self.update_status(format!("Found {} tracks in the music folder", self.library.tracks.len()));
+        }
+    }
+
+    fn sort_library(&mut self)
+    {
+        let (column, descending) = (self.library.sort, self.library.descending);
+        self.library.tracks.sort_by(|a, b|
+        {
+            let order = column.compare(a, b);
+            if descending { order.reverse() } else { order }
+        });
+    }
+
+    /// The music folder's tracks in a table that sorts by whichever column heading is clicked;
+    /// `.glc` files can be queued to play, and source files queued to encode
+    fn show_music_folder(&mut self, ui: &mut egui::Ui)
+    {
+        ui.horizontal(|ui|
+        {
+            if ui.button("Choose Music Folder").clicked()
+            {
+                if let Some(dir) = file_dialog(&self.settings.library_dir).pick_folder()
+                {
+                    self.settings.library_dir = Some(dir);
+                    self.save_settings();
+                    self.scan_library();
+                }
+            }
+            if let Some(ref dir) = self.settings.library_dir
+            {
+                ui.label(dir.display().to_string());
+                if self.library.scan.is_some()
+                {
+                    ui.spinner();
+                    ui.label(format!("Scanning… {} found", self.library.tracks.len()));
+                    if ui.button("Cancel").clicked()
+                    {
+                        self.library.scan = None;
+                    }
+                }
+                else if ui.button("Rescan").clicked()
+                {
+                    self.scan_library();
+                }
+            }
+        });
+        if self.library.tracks.is_empty()
+        {
+            return;
+        }
+
+        let filter = self.library.filter.to_lowercase();
+        let shown: Vec<usize> = (0..self.library.tracks.len()).filter(|&i|
+        {
+            let track = &self.library.tracks[i];
+            filter.is_empty() || [&track.title, &track.artist, &track.album].iter().any(|text| text.to_lowercase().contains(&filter))
+        }).collect();
+        ui.horizontal(|ui|
+        {
+            ui.label("Filter:");
+            ui.text_edit_singleline(&mut self.library.filter);
+            ui.label(format!("{} of {} tracks", shown.len(), self.library.tracks.len()));
+            if ui.button("Queue All").on_hover_text("Add every .glc file shown to the playlist").clicked()
+            {
+                let paths: Vec<PathBuf> = shown.iter().map(|&i| &self.library.tracks[i]).filter(|track| track.encoded)
+                    .map(|track| track.path.clone()).collect();
+                self.playlist.extend(paths);
+            }
+        });
+
+        let row_height = ui.spacing().interact_size.y;
+        ui.horizontal(|ui|
+        {
+            for column in LibraryColumn::ALL
+            {
+                let sorted = self.library.sort == column;
+                let arrow = match (sorted, self.library.descending)
+                {
+                    (false, _) => "",
+                    (true, false) => " ⏶",
+                    (true, true) => " ⏷",
+                };
+                let heading = egui::SelectableLabel::new(sorted, format!("{}{}", column.name(), arrow));
+                if ui.add_sized([column.width(), row_height], heading).clicked()
+                {
+                    self.library.descending = sorted && !self.library.descending;
+                    self.library.sort = column;
+                    self.sort_library();
+                }
+            }
+        });
+
+        let (mut to_play, mut to_encode, mut to_show) = (None, None, None);
+        egui::ScrollArea::vertical()
+            .id_source("music_folder_scroll")
+            .max_height(300.0)
+            .show_rows(ui, row_height, shown.len(), |ui, rows|
+        {
+            for &i in &shown[rows]
+            {
+                let track = &self.library.tracks[i];
+                ui.horizontal(|ui|
+                {
+                    let selected = self.library.details.as_ref().is_some_and(|details| details.path == track.path);
+                    let title = egui::SelectableLabel::new(selected, &track.title);
+                    let response = ui.add_sized([LibraryColumn::Title.width(), row_height], title)
+                        .on_hover_text(track.path.display().to_string());
+                    if response.clicked() && track.encoded
+                    {
+                        to_show = Some(track.path.clone());
+                    }
+                    let cells = [
+                        track.artist.clone(),
+                        track.album.clone(),
+                        track.duration.map(format_time).unwrap_or_default(),
+                        track.kbps.map(|kbps| format!("{:.0} kbps", kbps)).unwrap_or_default(),
+                    ];
+                    for (column, text) in LibraryColumn::ALL[1..].iter().zip(cells)
+                    {
+                        ui.add_sized([column.width(), row_height], egui::Label::new(text).truncate(true));
+                    }
+                    if track.encoded
+                    {
+                        if ui.button("Queue").on_hover_text("Add to the playlist").clicked()
+                        {
+                            to_play = Some(track.path.clone());
+                        }
+                    }
+                    else if ui.button("Encode").on_hover_text("Add to the encode queue").clicked()
+                    {
+                        to_encode = Some(track.path.clone());
+                    }
+                });
+            }
+        });
+        if let Some(path) = to_play
+        {
+            self.playlist.push(path);
+        }
+        if let Some(path) = to_encode
+        {
+            self.queue_encodes(vec![path]);
+        }
+        if let Some(path) = to_show
+        {
+            self.load_details(path);
+        }
+    }
+
+    /// The output device, the playlist and its transport, EQ, and export, and how playback is going
+    fn show_play_tab(&mut self, ui: &mut egui::Ui)
+    {
+        // Audio Device Testing Section
+        ui.collapsing("Audio Device Testing", |ui| 
+        {
+            self.show_output_device(ui);
+            ui.horizontal(|ui| 
+            {
+                if ui.button("Select FLAC Test File").clicked() 
+                {
+                    if let Some(path) = file_dialog(&self.settings.dirs.input)
+                        .add_filter("FLAC files", &["flac"])
+                        .pick_file()
+                    {
+                        remember_dir(&mut self.settings.dirs.input, &path);
+                        self.test_file_path = Some(path);
+                    }
+                }
+                
+                if let Some(ref path) = self.test_file_path 
+                {
+                    ui.label(format!("Test file: {:?}", path.file_name().unwrap()));
+                }
+            });
+            
+            if self.test_file_path.is_some()
+            {
+                ui.horizontal(|ui| 
+                {
+                    if !self.is_testing 
+                    {
+                        if ui.button("▶ Test Audio Output").clicked()
+                        {
+                            self.test_audio_device();
+                        }
+                    }
+                    else
+                    {
+                        if ui.button("⏹ Stop Test").clicked() 
+                        {
+                            self.stop_test_playback();
+                            self.update_status("Test playback stopped".to_string());
+                        }
+                    }
+                });
+            }
+        });
+
+        ui.separator();
+
+        self.show_playlist(ui);
+
+        // Waveform of the selected track, with the playhead while it plays
+        self.show_waveform(ui);
+        ui.collapsing("Metadata", |ui| self.show_details(ui));
+        
+        ui.separator();
+        
+        // Playback controls
+        ui.horizontal(|ui| 
+        {
+            let play_pause = ui.ctx().format_shortcut(&PLAY_PAUSE);
+            if !self.is_playing 
+            {
+                if ui.button("▶ Play Playlist (Gapless)").on_hover_text(play_pause).clicked() 
+                {
+                    self.play_playlist_async();
+                }
+            }
+            else
+            {
+                let label = if self.player.is_paused() { "▶ Resume" } else { "⏸ Pause" };
+                if ui.add_enabled(self.loading_tracks.is_none(), egui::Button::new(label)).on_hover_text(play_pause).clicked()
+                {
+                    self.play_pause();
+                }
+                if ui.button("⏹ Stop").clicked() 
+                {
+                    self.stop_playback();
+                }
+            }
+
+            // Repeat and shuffle, applied to the playlist as it plays
+            let control = self.player.control().clone();
+            let repeat_label = match self.repeat
+            {
+                Repeat::Off => "🔁 Repeat: Off",
+                Repeat::Track => "🔂 Repeat: Track",
+                Repeat::Playlist => "🔁 Repeat: Playlist",
+            };
+            if ui.selectable_label(self.repeat != Repeat::Off, repeat_label).clicked()
+            {
+                self.repeat = match self.repeat
+                {
+                    Repeat::Off => Repeat::Track,
+                    Repeat::Track => Repeat::Playlist,
+                    Repeat::Playlist => Repeat::Off,
+                };
+                control.set_repeat(self.repeat);
+            }
+            if ui.toggle_value(&mut self.shuffle, "🔀 Shuffle").changed()
+            {
+                control.set_shuffle(self.shuffle);
+            }
+
+            // Volume and limiter, applied to the playlist as it plays
+            ui.label("Volume:");
+            let volume = ui.add(egui::Slider::new(&mut self.volume, 0.0..=MAX_VOLUME)
+                                    .custom_formatter(|v, _| format!("{:.0}%", v * 100.0)));
+            let limiter = ui.checkbox(&mut self.limiter, "Limiter");
+
+            // ReplayGain, applied to tagged tracks from the next block of audio
+            let mut replay_gain_changed = false;
+            egui::ComboBox::from_id_source("replay_gain")
+                .selected_text(match self.replay_gain.mode
+                {
+                    Some(ReplayGainMode::Track) => "ReplayGain: Track",
+                    Some(ReplayGainMode::Album) => "ReplayGain: Album",
+                    None => "ReplayGain: Off",
+                })
+                .show_ui(ui, |ui|
+                {
+                    for (mode, label) in [(None, "Off"), (Some(ReplayGainMode::Track), "Track"), (Some(ReplayGainMode::Album), "Album")]
+                    {
+                        replay_gain_changed |= ui.selectable_value(&mut self.replay_gain.mode, mode, label).changed();
+                    }
+                });
+            if self.replay_gain.mode.is_some()
+            {
+                replay_gain_changed |= ui.add(egui::Slider::new(&mut self.replay_gain.preamp, -15.0..=15.0)
+                                                  .text("Pre-amp")
+                                                  .suffix(" dB"))
+                                         .changed();
+            }
+
+            // Crossfade, taking effect from the next track change
+            let crossfade = ui.add(egui::Slider::new(&mut self.crossfade, 0.0..=12.0)
+                                       .text("Crossfade")
+                                       .suffix(" s"));
+
+            // Tempo, keeping the pitch, from the next block of audio
+            let tempo = ui.add(egui::Slider::new(&mut self.tempo, TEMPO_RANGE)
+                                   .text("Tempo")
+                                   .step_by(0.05)
+                                   .custom_formatter(|v, _| format!("{:.2}×", v)));
+
+            if volume.changed() || limiter.changed()
+            {
+                control.mixer().set_volume(self.volume);
+                control.mixer().set_limiter(self.limiter);
+            }
+            if replay_gain_changed
+            {
+                control.set_replay_gain(self.replay_gain);
+            }
+            if crossfade.changed()
+            {
+                control.set_crossfade(self.crossfade);
+            }
+            if tempo.changed()
+            {
+                control.set_tempo(self.tempo);
+            }
+
+            // Save exactly what is played, to check transitions at the output
+            if control.is_capturing()
+            {
+                if ui.button("⏹ Stop Capture").clicked()
+                {
+                    match control.stop_capture()
+                    {
+                        Ok(Some(capture)) => self.update_status(format!("Captured {:.1} s of output to {}",
+                                                                       capture.duration(), capture.path.display())),
+                        Ok(None) => {}
+                        Err(e) => self.report_error(format!("Capture failed: {}", e)),
+                    }
+                }
+            }
+            else if ui.button("⏺ Capture Output").clicked()
+            {
+                if let Some(path) = file_dialog(&self.settings.dirs.output)
+                    .set_file_name("capture.wav")
+                    .add_filter("Audio files", &["wav", "flac"])
+                    .save_file()
+                {
+                    remember_dir(&mut self.settings.dirs.output, &path);
+                    match control.start_capture(&path)
+                    {
+                        Ok(()) => self.update_status(format!("Capturing output to {}", path.display())),
+                        Err(e) => self.report_error(format!("Capture failed: {}", e)),
+                    }
+                }
+            }
+
+        });
+
+        ui.collapsing("Export", |ui| self.show_export_options(ui));
+        
+        // Where playback is in the current track and the whole playlist, both clickable to seek
+        if self.is_playing && self.loading_tracks.is_none()
+        {
+            self.show_timeline(ui);
+            self.show_meters(ui);
+        }
+
+        // 10-band graphic EQ, applied to the playlist as it plays
+        ui.collapsing("Equalizer", |ui|
+        {
+            let control = self.player.control().clone();
+            let mut changed = false;
+            ui.horizontal(|ui|
+            {
+                if ui.checkbox(&mut self.eq_enabled, "Enabled").changed()
+                {
+                    control.equalizer().set_enabled(self.eq_enabled);
+                }
+                if ui.button("Flat").clicked()
+                {
+                    self.eq_gains = [0.0; 10];
+                    changed = true;
+                }
+            });
+            ui.horizontal(|ui|
+            {
+                for (gain, frequency) in self.eq_gains.iter_mut().zip(GRAPHIC_FREQUENCIES)
+                {
+                    ui.vertical(|ui|
+                    {
+                        changed |= ui.add(egui::Slider::new(gain, -12.0..=12.0)
+                                              .vertical()
+                                              .step_by(0.5)
+                                              .suffix(" dB"))
+                                     .changed();
+                        ui.label(if frequency >= 1000.0 { format!("{}k", frequency / 1000.0) } else { format!("{}", frequency) });
+                    });
+                }
+            });
+            if changed
+            {
+                control.equalizer().set_bands(graphic_bands(&self.eq_gains));
+            }
+        });
+
+        // Decoding speed and buffer depth over the last minute, for tracking down stutter
+        ui.collapsing("Playback Health", |ui|
+        {
+            let Some(latest) = self.health.back()
+            else
+            {
+                ui.label("Nothing played yet");
+                return;
+            };
+            ui.label(format!("{} of {} blocks decoded slower than real time, {} underruns",
+                             latest.slow_blocks, latest.blocks, latest.underruns));
+
+            // Playback keeps up while decoding runs faster than real time, above the dashed line
+            let max_speed = self.health.iter().map(|stats| stats.speed).fold(2.0, f64::max);
+            ui.label(format!("Decoding speed: {:.1}x real time", latest.speed));
+            sparkline(ui, self.health.iter().map(|stats| stats.speed), max_speed, Some(1.0), egui::Color32::LIGHT_GREEN);
+
+            let max_buffered = self.health.iter().map(|stats| stats.buffered).fold(0.1, f64::max);
+            ui.label(format!("Buffered: {:.0} ms", latest.buffered * 1000.0));
+            sparkline(ui, self.health.iter().map(|stats| stats.buffered), max_buffered, None, egui::Color32::LIGHT_BLUE);
+        });
+    }
+
+    /// Background export progress, the console, and the status lines, shown under every tab
+    fn show_status_bar(&mut self, ui: &mut egui::Ui)
+    {
+        // Export progress bar
+        if let Some(progress) = *self.export_progress.lock().unwrap() 
+        {
+            ui.horizontal(|ui|
+            {
+                ui.add(egui::ProgressBar::new(progress / 100.0)
+                    .desired_width(ui.available_width() - 80.0)
+                    .text(format!("Exporting: {:.0}%", progress)));
+                let cancelling = self.export_cancel.load(Ordering::Relaxed);
+                if ui.add_enabled(!cancelling, egui::Button::new("Cancel")).clicked()
+                {
+                    self.export_cancel.store(true, Ordering::Relaxed);
+                }
+            });
+        }
+
+        // Every warning and error so far, which the status bar only shows until the next message
+        let (warnings, errors) = self.log.counts();
+        egui::CollapsingHeader::new(format!("Console ({} errors, {} warnings)", errors, warnings))
+            .id_source("console")
+            .show(ui, |ui| self.show_console(ui));
+
+        ui.separator();
+
+        // Status bars
+        ui.horizontal(|ui| 
+        {
+            ui.label("Status:");
+            ui.label(self.status.lock().unwrap().as_str());
+        });
+        
+        // Detailed status
+        let detailed = self.detailed_status.lock().unwrap().clone();
+        if !detailed.is_empty() 
+        {
+            ui.horizontal(|ui|
+            {
+                ui.label("Details:");
+                ui.label(detailed);
+            });
+        }
+    }
+
+    /// The log with the newest entries at the bottom, and buttons to copy it for a bug report or clear it
+    fn show_console(&self, ui: &mut egui::Ui)
+    {
+        ui.horizontal(|ui|
+        {
+            if ui.button("Copy to Clipboard").clicked()
+            {
+                ui.ctx().copy_text(self.log.report());
+            }
+            if ui.button("Clear").clicked()
+            {
+                self.log.entries.lock().unwrap().clear();
+            }
+        });
+
+        let entries = self.log.entries.lock().unwrap();
+        if entries.is_empty()
+        {
+            ui.label("No warnings or errors");
+            return;
+        }
+        egui::ScrollArea::vertical().max_height(160.0).stick_to_bottom(true).show(ui, |ui|
+        {
+            for entry in entries.iter()
+            {
+                ui.horizontal_wrapped(|ui|
+                {
+                    ui.monospace(entry.clock());
+                    let color = if entry.level == LogLevel::Error { egui::Color32::RED } else { egui::Color32::YELLOW };
+                    ui.colored_label(color, entry.level_name());
+                    ui.label(&entry.message);
+                });
+            }
+        });
+    }
+
+    /// A peak and RMS meter for each output channel, with a clip indicator, and the spectrum of
+    /// the last few thousand samples played
+    fn show_meters(&mut self, ui: &mut egui::Ui)
+    {
+        let control = self.player.control();
+        self.meters.update(&control.take_levels(), &control.recent_output());
+
+        let width = ui.available_width().min(480.0);
+        let visuals = ui.visuals().clone();
+        for channel in 0..self.meters.peaks.len()
+        {
+            let (peak, rms) = (self.meters.peaks[channel], self.meters.rms[channel]);
+            let clipped = &mut self.meters.clipped[channel];
+            ui.horizontal(|ui|
             {
-                // Check if we should stop
-                if !*is_playing_clone.lock().unwrap() 
+                let (rect, response) = ui.allocate_exact_size(egui::vec2(width, 10.0), egui::Sense::click());
+                let x = |db: f32| rect.left() + (1.0 - db / METER_FLOOR_DB) * rect.width();
+                let painter = ui.painter_at(rect);
+                painter.rect_filled(rect, 2.0, visuals.extreme_bg_color);
+                painter.rect_filled(rect.with_max_x(x(rms)), 2.0, egui::Color32::from_rgb(80, 170, 80));
+                let peak_color = if *clipped { egui::Color32::RED } else { egui::Color32::YELLOW };
+                painter.vline(x(peak), rect.y_range(), egui::Stroke::new(2.0, peak_color));
+                if response.on_hover_text("Peak (line) and RMS (bar); click to reset the clip indicator").clicked()
                 {
-                    break;
+                    *clipped = false;
                 }
-                
-                *status.lock().unwrap() = format!("Loading file {}/{}", idx + 1, playlist.len());
-                
-                match load_encoded(path) 
+
+                let level = if peak <= METER_FLOOR_DB { "-inf".to_string() } else { format!("{:.1}", peak) };
+                ui.monospace(format!("{:>5} dB", level));
+                if *clipped
                 {
-                    Ok(encoded) => 
-                    {
-                        *detailed_status.lock().unwrap() = format!(
-                            "Streaming {:?}: {} frames",
-                            path.file_name().unwrap(),
-                            encoded.frames.len()
-                        );
-                        
-                        sample_rate = encoded.header.sample_rate;
-                        channels = encoded.header.channels;
-                        let mut decoder = Decoder::new(channels as usize, sample_rate);
-                        let arc_encoded = Arc::new(encoded);
-                        
-                        let (tx, rx) = bounded(10);
-                        let chunk_receiver = decoder.decode_streaming(arc_encoded, Some(tx));
-                        
-                        let mut first_chunk = true;
-                        
-                        while let Ok(chunk) = chunk_receiver.recv() 
-                        {
-                            // Check if we should stop
-                            if !*is_playing_clone.lock().unwrap() 
-                            {
-                                break;
-                            }
-                            
-                            while let Ok(progress) = rx.try_recv() 
-                            {
-                                match progress 
-                                {
-                                    Progress::Status(msg) => 
-                                    {
-                                        *detailed_status.lock().unwrap() = msg;
-                                    }
-                                    Progress::Decoding(p) => 
-                                    {
-                                        *status.lock().unwrap() = format!(
-                                            "Playing track {}/{} ({:.0}%)", 
-                                            idx + 1, 
-                                            playlist.len(), 
-                                            p
-                                        );
-                                    }
-                                    _ => {}
-                                }
-                            }
-                            
-                            if first_chunk 
-                            {
-                                *status.lock().unwrap() = format!("Started playback of track {}/{}", idx + 1, playlist.len());
-                                first_chunk = false;
-                            }
-                            
-                            let source = SamplesSource::new(chunk.samples, sample_rate, channels);
-                            sink.lock().unwrap().append(source);
-                            
-                            if chunk.is_last 
-                            {
-                                break;
-                            }
-                        }
-                    }
-                    Err(e) => 
-                    {
-                        *status.lock().unwrap() = format!("Error loading file: {}", e);
-                        return;
-                    }
+                    ui.colored_label(egui::Color32::RED, "CLIP");
                 }
-            }
-            
-            let total_time = start_time.elapsed();
-            *status.lock().unwrap() = format!("Playing playlist (prepared in {:.2}s)", total_time.as_secs_f32());
-            
-            sink.lock().unwrap().sleep_until_end();
-            
-            *is_playing_clone.lock().unwrap() = false;
-            *status.lock().unwrap() = "Playback finished".to_string();
-        });
+            });
+        }
+
+        if self.meters.spectrum.is_empty()
+        {
+            return;
+        }
+        let (rect, response) = ui.allocate_exact_size(egui::vec2(width, 60.0), egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, visuals.extreme_bg_color);
+        let bar_width = rect.width() / SPECTRUM_BARS as f32;
+        for (bar, &level) in self.meters.spectrum.iter().enumerate()
+        {
+            let height = (1.0 - level / SPECTRUM_FLOOR_DB).clamp(0.0, 1.0) * rect.height();
+            let left = rect.left() + bar as f32 * bar_width;
+            let bar_rect = egui::Rect::from_x_y_ranges(left + 1.0..=left + bar_width - 1.0, rect.bottom() - height..=rect.bottom());
+            painter.rect_filled(bar_rect, 0.0, visuals.selection.bg_fill);
+        }
+        if let Some(pointer) = response.hover_pos()
+        {
+            let bar = (((pointer.x - rect.left()) / bar_width) as usize).min(SPECTRUM_BARS - 1);
+            response.on_hover_text(format!("{:.0}–{:.0} Hz: {:.0} dB", self.meters.bar_frequency(bar),
+                                           self.meters.bar_frequency(bar + 1), self.meters.spectrum[bar]));
+        }
     }
 
     fn export_playlist_async(&mut self, output_path: PathBuf)
     {
         let playlist = self.playlist.clone();
         let status = self.status.clone();
-        let detailed_status = self.detailed_status.clone();
+        let log = self.log.clone();
         let export_progress = self.export_progress.clone();
-        let flac_level = self.flac_compression_level;
+        let settings = ExportSettings
+        {
+            format: self.export_format,
+            flac_level: self.flac_compression_level,
+            mp3_mode: self.mp3_mode,
+            per_track: self.export_per_track,
+        };
+        let cancel = self.export_cancel.clone();
+        cancel.store(false, Ordering::Relaxed);
 
         std::thread::spawn(move ||
         {
@@ -302,106 +3627,27 @@ impl CodecApp
             *export_progress.lock().unwrap() = Some(0.0);
             *status.lock().unwrap() = "Starting export...".to_string();
 
-            // Collect all decoded samples first, then write to FLAC at once
-            let mut all_samples: Vec<f32> = Vec::new();
-            let mut sample_rate = 0u32;
-            let mut channels = 0u16;
-            let total_files = playlist.len();
-
-            for (file_idx, path) in playlist.iter().enumerate()
+            let result = export_playlist(&playlist, &output_path, settings, &cancel, &|progress, message|
             {
-                let base_progress = (file_idx as f32 / total_files as f32) * 100.0;
-                *export_progress.lock().unwrap() = Some(base_progress);
-                *status.lock().unwrap() = format!("Loading file {}/{}", file_idx + 1, total_files);
-
-                match crate::codec::load_encoded(path)
-                {
-                    Ok(encoded) =>
-                    {
-                        *detailed_status.lock().unwrap() = format!(
-                            "Processing {:?}: {} frames",
-                            path.file_name().unwrap(),
-                            encoded.frames.len()
-                        );
-
-                        // Get sample rate and channels from first file
-                        if file_idx == 0
-                        {
-                            sample_rate = encoded.header.sample_rate;
-                            channels = encoded.header.channels;
-                        }
-
-                        // Create decoder for this file using its native channels & sample_rate
-                        let mut decoder = crate::codec::Decoder::new(
-                            encoded.header.channels as usize,
-                            encoded.header.sample_rate,
-                        );
-
-                        // Use synchronous decode convenience (it internally uses streaming)
-                        match decoder.decode(&encoded, None)
-                        {
-                            Ok(samples) =>
-                            {
-                                all_samples.extend_from_slice(&samples);
-                                *status.lock().unwrap() = format!(
-                                    "Decoded file {}/{} ({} samples)",
-                                    file_idx + 1,
-                                    total_files,
-                                    samples.len()
-                                );
-                            }
-                            Err(e) =>
-                            {
-                                *status.lock().unwrap() = format!("Decoding error: {}", e);
-                                *export_progress.lock().unwrap() = None;
-                                return;
-                            }
-                        }
-                    }
-                    Err(e) =>
-                    {
-                        *status.lock().unwrap() = format!("Error loading file: {}", e);
-                        *export_progress.lock().unwrap() = None;
-                        return;
-                    }
-                }
-            }
-
-            // Export all samples to FLAC
-            *status.lock().unwrap() = "Writing audio file...".to_string();
-            *export_progress.lock().unwrap() = Some(95.0);
-
-            let export_result = crate::flac::export_to_flac_with_level(
-                &output_path,
-                &all_samples,
-                sample_rate,
-                channels,
-                flac_level
-            );
-
-            match export_result
+                *export_progress.lock().unwrap() = Some(progress * 100.0);
+                *status.lock().unwrap() = message;
+            });
+            *status.lock().unwrap() = match result
             {
-                Ok(()) =>
-                {
-                    let elapsed = start_time.elapsed();
-                    *status.lock().unwrap() = format!(
-                        "Exported {} samples to {:?} in {:.2}s",
-                        all_samples.len(),
-                        output_path.file_name().unwrap(),
-                        elapsed.as_secs_f32()
-                    );
-                }
+                Ok(Some(files)) => format!("Exported {} file{} to {:?} in {:.2}s", files, if files == 1 { "" } else { "s" },
+                                           output_path.file_name().unwrap_or_default(), start_time.elapsed().as_secs_f32()),
+                Ok(None) => "Export cancelled".to_string(),
                 Err(e) =>
                 {
-                    *status.lock().unwrap() = format!("Error exporting audio: {}", e);
-                    *export_progress.lock().unwrap() = None;
-                    return;
+                    let message = format!("Error exporting audio: {}", e);
+                    log.error(message.clone());
+                    message
                 }
-            }
+            };
+            *export_progress.lock().unwrap() = None;
         });
     }
 
-
     
     fn test_audio_device(&mut self) 
     {
@@ -409,40 +3655,44 @@ impl CodecApp
         {
             self.stop_test_playback();
             
-            if let Some(ref stream_handle) = self.stream_handle 
+            // The test file plays on the player's output, alongside (or instead of) the playlist
+            let Some(output) = self.player.output()
+            else
+            {
+                self.update_status("The test file needs the rodio backend".to_string());
+                return;
+            };
+            match Sink::try_new(output) 
             {
-                match Sink::try_new(stream_handle) 
+                Ok(sink) => 
                 {
-                    Ok(sink) => 
+                    // Try to play the test file
+                    if let Ok(file) = File::open(&path) 
                     {
-                        // Try to play the test file
-                        if let Ok(file) = File::open(&path) 
+                        let source = match RodioDecoder::new(BufReader::new(file)) 
                         {
-                            let source = match RodioDecoder::new(BufReader::new(file)) 
+                            Ok(decoder) => decoder,
+                            Err(e) => 
                             {
-                                Ok(decoder) => decoder,
-                                Err(e) => 
-                                {
-                                    self.update_status(format!("Failed to decode test file: {}", e));
-                                    return;
-                                }
-                            };
-                            
-                            sink.append(source);
-                            self.test_sink = Some(sink);
-                            self.is_testing = true;
-                            self.update_status(format!("Playing test file: {:?}", path.file_name().unwrap()));
-                        }
-                        else
-                        {
-                            self.update_status("Failed to open test file".to_string());
-                        }
+                                self.report_error(format!("Failed to decode test file: {}", e));
+                                return;
+                            }
+                        };
+                        
+                        sink.append(source);
+                        self.test_sink = Some(sink);
+                        self.is_testing = true;
+                        self.update_status(format!("Playing test file: {:?}", path.file_name().unwrap()));
                     }
-                    Err(e) => 
+                    else
                     {
-                        self.update_status(format!("Failed to create sink: {}", e));
+                        self.report_error("Failed to open test file".to_string());
                     }
                 }
+                Err(e) => 
+                {
+                    self.report_error(format!("Failed to create sink: {}", e));
+                }
             }
         }
     }
@@ -456,14 +3706,68 @@ impl CodecApp
         self.is_testing = false;
     }
     
-    fn stop_playback(&mut self) 
+    /// The audio output picker, with a refresh for outputs plugged in since
+    fn show_output_device(&mut self, ui: &mut egui::Ui)
+    {
+        ui.horizontal(|ui|
+        {
+            ui.label("Output device:");
+            let mut device = self.settings.output_device.clone();
+            egui::ComboBox::from_id_source("output_device")
+                .selected_text(device.as_deref().unwrap_or("System default"))
+                .width(240.0)
+                .show_ui(ui, |ui|
+                {
+                    ui.selectable_value(&mut device, None, "System default");
+                    for name in &self.output_devices
+                    {
+                        ui.selectable_value(&mut device, Some(name.clone()), name);
+                    }
+                });
+            if ui.button("⟳").on_hover_text("Look for outputs again").clicked()
+            {
+                match output_device_names()
+                {
+                    Ok(names) => self.output_devices = names,
+                    Err(e) => self.report_error(e.to_string()),
+                }
+            }
+            if device != self.settings.output_device
+            {
+                self.set_output_device(device);
+            }
+        });
+    }
+
+    /// Play through `device` (None for the system default) from now on, picking the playlist up
+    /// where it was if it was playing, and remember it for the next run
+    fn set_output_device(&mut self, device: Option<String>)
     {
-        if let Some(sink) = self.audio_sink.take() 
+        let resume = (self.is_playing && self.loading_tracks.is_none()).then(|| self.player.position());
+        self.stop_test_playback();
+        self.stop_playback();
+        if let Err(e) = self.player.set_device(device.as_deref())
+        {
+            self.report_error(format!("Can't switch to {}: {}", device.as_deref().unwrap_or("the default output"), e));
+            return;
+        }
+        self.update_status(format!("Playing through {}", device.as_deref().unwrap_or("the default output")));
+        self.settings.output_device = device;
+        self.save_settings();
+        // The playlist played into the old output, so it is opened again on the new one
+        if let Some(position) = resume
         {
-            let sink_guard = sink.lock().unwrap();
-            sink_guard.stop();
-            drop(sink_guard);  // Explicitly drop to ensure cleanup
+            self.play_playlist_async();
+            self.resume_at = Some((position.track, position.elapsed));
         }
+    }
+
+    fn stop_playback(&mut self) 
+    {
+        self.player.stop();
+        self.loading_tracks = None;
+        self.resume_at = None;
+        self.seek_drag = None;
         self.is_playing = false;
         self.update_status("Stopped".to_string());
     }
@@ -471,256 +3775,62 @@ impl CodecApp
 
 impl eframe::App for CodecApp 
 {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) 
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) 
     {
-        // Request repaint for progress updates
-        ctx.request_repaint_after(Duration::from_millis(100));
-        
-        egui::CentralPanel::default().show(ctx, |ui| 
+        // Request repaint for progress updates, and often enough for the meters to move smoothly while playing
+        ctx.request_repaint_after(Duration::from_millis(if self.is_playing { 33 } else { 100 }));
+        self.poll_playback();
+        self.poll_library_scan();
+        self.track_window(ctx);
+        self.handle_dropped_files(ctx);
+        self.handle_shortcuts(ctx);
+
+        egui::TopBottomPanel::top("tabs").show(ctx, |ui|
         {
-            ui.heading("Gapless Audio Codec");
-            
-            ui.separator();
-            
-            // Audio Device Testing Section
-            ui.collapsing("Audio Device Testing", |ui| 
-            {
-                ui.horizontal(|ui| 
-                {
-                    if ui.button("Select FLAC Test File").clicked() 
-                    {
-                        if let Some(path) = rfd::FileDialog::new()
-                            .add_filter("FLAC files", &["flac"])
-                            .pick_file()
-                        {
-                            self.test_file_path = Some(path);
-                        }
-                    }
-                    
-                    if let Some(ref path) = self.test_file_path 
-                    {
-                        ui.label(format!("Test file: {:?}", path.file_name().unwrap()));
-                    }
-                });
-                
-                if self.test_file_path.is_some()
-                {
-                    ui.horizontal(|ui| 
-                    {
-                        if !self.is_testing 
-                        {
-                            if ui.button("▶ Test Audio Output").clicked()
-                            {
-                                self.test_audio_device();
-                            }
-                        }
-                        else
-                        {
-                            if ui.button("⏹ Stop Test").clicked() 
-                            {
-                                self.stop_test_playback();
-                                self.update_status("Test playback stopped".to_string());
-                            }
-                        }
-                    });
-                }
-            });
-            
-            ui.separator();
-            
-            // File selection section
             ui.horizontal(|ui|
             {
-                if ui.button("Select Audio Files (WAV/FLAC)").clicked() 
-                {
-                    if let Some(paths) = rfd::FileDialog::new()
-                        .add_filter("Audio files", &["wav", "flac"])
-                        .pick_files()
-                    {
-                        self.selected_files = paths;
-                    }
-                }
-                
-                if !self.selected_files.is_empty() 
-                {
-                    ui.label(format!("{} files selected", self.selected_files.len()));
-                }
-            });
-            
-            // Encode button
-            if !self.selected_files.is_empty() 
-            {
-                ui.horizontal(|ui| 
-                {
-                    if ui.button("Encode Selected Files").clicked() 
-                    {
-                        for file in self.selected_files.clone() 
-                        {
-                            self.encode_file_async(file);
-                        }
-                    }
-                    
-                    // Show encoding progress
-                    if let Some(progress) = *self.encoding_progress.lock().unwrap() 
-                    {
-                        ui.add(egui::ProgressBar::new(progress / 100.0)
-                            .text(format!("{:.0}%", progress)));
-                    }
-                });
-            }
-            
-            ui.separator();
-            
-            // Load encoded files
-            if ui.button("Load Encoded Files (.glc)").clicked() 
-            {
-                if let Some(paths) = rfd::FileDialog::new()
-                    .add_filter("Encoded files", &["glc"])
-                    .pick_files()
-                {
-                    for path in paths 
-                    {
-                        if let Ok(encoded) = load_encoded(&path) 
-                        {
-                            self.encoded_files.push((path, encoded));
-                        }
-                    }
-                }
-            }
-            
-            // Encoded files list - with unique ID
-            ui.label("Encoded Files:");
-            egui::ScrollArea::vertical()
-                .id_source("encoded_files_scroll")
-                .max_height(120.0)
-                .show(ui, |ui| 
-            {
-                let mut files_to_add = Vec::new();
-                for (path, _) in &self.encoded_files
-                {
-                    ui.horizontal(|ui|
-                    {
-                        ui.label(format!("{:?}", path.file_name().unwrap()));
-                        if ui.button(format!("Add##{:?}", path)).clicked()
-                        {
-                            files_to_add.push(path.clone());
-                        }
-                    });
-                }
-                for path in files_to_add
-                {
-                    self.playlist.push(path);
-                }
-            });
-            
-            ui.separator();
-            
-            // Playlist section - with unique ID
-            ui.label("Playlist (for gapless playback test):");
-            egui::ScrollArea::vertical()
-                .id_source("playlist_scroll")
-                .max_height(120.0)
-                .show(ui, |ui| 
-            {
-                let mut to_remove = None;
-                for (i, path) in self.playlist.iter().enumerate()
+                ui.heading("Gapless Audio Codec");
+                ui.separator();
+                for tab in Tab::ALL
                 {
-                    ui.horizontal(|ui|
-                    {
-                        ui.label(format!("{}. {:?}", i + 1, path.file_name().unwrap()));
-                        if ui.button(format!("Remove##{}", i)).clicked()
-                        {
-                            to_remove = Some(i);
-                        }
-                    });
-                }
-                if let Some(idx) = to_remove
-                {
-                    self.playlist.remove(idx);
-                }
-            });
-            
-            ui.horizontal(|ui| 
-            {
-                if !self.playlist.is_empty() 
-                {
-                    if ui.button("Clear Playlist").clicked() 
-                    {
-                        self.playlist.clear();
-                    }
+                    ui.selectable_value(&mut self.settings.tab, tab, tab.name());
                 }
             });
-            
-            ui.separator();
-            
-            // Playback controls
-            ui.horizontal(|ui| 
-            {
-                if !self.is_playing 
-                {
-                    if ui.button("▶ Play Playlist (Gapless)").clicked() 
-                    {
-                        self.play_playlist_async();
-                    }
-                }
-                else
-                {
-                    if ui.button("⏹ Stop").clicked() 
-                    {
-                        self.stop_playback();
-                    }
-                }
-
-                // FLAC compression level selector
-                ui.horizontal(|ui|
-                {
-                    ui.label("FLAC Compression Level:");
-                    ui.add(egui::Slider::new(&mut self.flac_compression_level, 0..=8));
-                    ui.label(format!("{}", self.flac_compression_level));
-                });
+        });
 
-                let button_text = "Export Playlist as FLAC";
-                let default_filename = "output.flac";
+        // Status, background work, and errors stay in view whichever tab is open
+        egui::TopBottomPanel::bottom("status").show(ctx, |ui| self.show_status_bar(ui));
 
-                if ui.button(button_text).clicked()
+        egui::CentralPanel::default().show(ctx, |ui| 
+        {
+            egui::ScrollArea::vertical().auto_shrink([false; 2]).show(ui, |ui|
+            {
+                match self.settings.tab
                 {
-                    if let Some(path) = rfd::FileDialog::new()
-                        .set_file_name(default_filename)
-                        .add_filter("Audio files", &["flac", "wav"])
-                        .save_file()
-                    {
-                        self.export_playlist_async(path);
-                    }
+                    Tab::Encode => self.show_encode_tab(ui),
+                    Tab::Library => self.show_library_tab(ui),
+                    Tab::Play => self.show_play_tab(ui),
+                    Tab::Analyze => self.show_analyze_tab(ui),
                 }
             });
-            
-            // Export progress bar
-            if let Some(progress) = *self.export_progress.lock().unwrap() 
-            {
-                ui.add(egui::ProgressBar::new(progress / 100.0)
-                    .text(format!("Exporting: {:.0}%", progress)));
-            }
-            
-            ui.separator();
-            
-            // Status bars
-            ui.horizontal(|ui| 
-            {
-                ui.label("Status:");
-                ui.label(self.status.lock().unwrap().as_str());
-            });
-            
-            // Detailed status
-            let detailed = self.detailed_status.lock().unwrap().clone();
-            if !detailed.is_empty() 
-            {
-                ui.horizontal(|ui|
-                {
-                    ui.label("Details:");
-                    ui.label(detailed);
-                });
-            }
         });
+
+        if self.confirm_close
+        {
+            self.show_close_confirmation(ctx, frame);
+        }
     }
-}
 
+    /// Closing with encodes or an export still running asks first, rather than cutting them off
+    fn on_close_event(&mut self) -> bool
+    {
+        let (encodes, exporting) = self.running_tasks();
+        if self.allow_close || (encodes == 0 && !exporting)
+        {
+            self.save_session();
+            return true;
+        }
+        self.confirm_close = true;
+        false
+    }
+}
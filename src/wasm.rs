@@ -0,0 +1,81 @@
+//! wasm-bindgen bindings so GLC files can be encoded and auditioned in a browser
+//! Build with `wasm-pack build --target web --no-default-features --features wasm`
+use wasm_bindgen::prelude::*;
+use crate::codec::{Encoder, Decoder, encoded_to_bytes, encoded_from_bytes};
+
+/// Decoded PCM plus the stream parameters needed to build a Web Audio `AudioBuffer`
+#[wasm_bindgen]
+pub struct DecodedAudio
+{
+    samples: Vec<f32>,
+    sample_rate: u32,
+    channels: u16,
+}
+
+#[wasm_bindgen]
+impl DecodedAudio
+{
+    /// Interleaved samples (copied into a `Float32Array` on the JS side)
+    #[wasm_bindgen(getter)]
+    pub fn samples(&self) -> Vec<f32>
+    {
+        self.samples.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = sampleRate)]
+    pub fn sample_rate(&self) -> u32
+    {
+        self.sample_rate
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn channels(&self) -> u16
+    {
+        self.channels
+    }
+
+    /// Samples of a single channel, deinterleaved (what `AudioBuffer.copyToChannel` expects)
+    #[wasm_bindgen(js_name = channelData)]
+    pub fn channel_data(&self, channel: u16) -> Vec<f32>
+    {
+        let ch = self.channels as usize;
+        self.samples.iter()
+                    .skip(channel as usize)
+                    .step_by(ch.max(1))
+                    .copied()
+                    .collect()
+    }
+}
+
+/// Encode interleaved `samples` to the bytes of a `.glc` file
+#[wasm_bindgen]
+pub fn encode(samples: &[f32], sample_rate: u32, channels: u16) -> Result<Vec<u8>, JsValue>
+{
+    let mut encoder = Encoder::new(sample_rate);
+    let encoded = encoder.encode(samples, channels).map_err(to_js_error)?;
+    encoded_to_bytes(&encoded).map_err(to_js_error)
+}
+
+/// Decode the bytes of a `.glc` file
+#[wasm_bindgen]
+pub fn decode(data: &[u8]) -> Result<DecodedAudio, JsValue>
+{
+    let encoded = encoded_from_bytes(data).map_err(to_js_error)?;
+    let sample_rate = encoded.header.sample_rate;
+    let channels = encoded.header.channels;
+
+    let mut decoder = Decoder::new(channels as usize, sample_rate);
+    let samples = decoder.decode(&encoded, None).map_err(to_js_error)?;
+
+    Ok(DecodedAudio
+    {
+        samples,
+        sample_rate,
+        channels,
+    })
+}
+
+fn to_js_error(e: anyhow::Error) -> JsValue
+{
+    JsValue::from_str(&e.to_string())
+}
@@ -0,0 +1,74 @@
+//! Downsampled overviews of whole tracks, for drawing their waveforms
+//!
+//! A [`Waveform`] keeps the lowest and highest sample in each of a fixed number of columns,
+//! across all channels, which is all a waveform display a few hundred pixels wide needs.
+#[cfg(feature = "playback")]
+use anyhow::Result;
+#[cfg(feature = "playback")]
+use std::fs::File;
+#[cfg(feature = "playback")]
+use std::io::BufReader;
+#[cfg(feature = "playback")]
+use std::path::Path;
+#[cfg(feature = "playback")]
+use crate::codec::load_encoded;
+#[cfg(feature = "playback")]
+use crate::container::GlcDecoder;
+#[cfg(feature = "playback")]
+use crate::playback::{GlcSource, SeekableSource};
+
+/// Lowest and highest sample in each column of a track, in order
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Waveform
+{
+    /// `(min, max)` per column; columns with no samples (in tracks shorter than the column
+    /// count) are `(0.0, 0.0)`
+    pub columns: Vec<(f32, f32)>,
+    /// Length of the track in seconds
+    pub duration: f64,
+}
+
+impl Waveform
+{
+    /// Overview of `frames` frames of interleaved `samples` in `columns` columns; samples past
+    /// `frames` are ignored
+    pub fn from_samples(samples: impl IntoIterator<Item = f32>, frames: u64, sample_rate: u32, channels: u16,
+                        columns: usize) -> Self
+    {
+        let channels = channels.max(1) as u64;
+        let mut overview = vec![(f32::INFINITY, f32::NEG_INFINITY); columns];
+        if columns > 0 && frames > 0
+        {
+            for (i, sample) in samples.into_iter().take((frames * channels) as usize).enumerate()
+            {
+                let column = ((i as u64 / channels) * columns as u64 / frames) as usize;
+                let (min, max) = &mut overview[column];
+                *min = min.min(sample);
+                *max = max.max(sample);
+            }
+        }
+        for column in &mut overview
+        {
+            if column.0 > column.1
+            {
+                *column = (0.0, 0.0);
+            }
+        }
+        Self { columns: overview, duration: frames as f64 / sample_rate.max(1) as f64 }
+    }
+
+    /// Overview of the `.glc` file at `path`, decoded frame by frame with encoder delay and
+    /// padding trimmed, as it plays
+    #[cfg(feature = "playback")]
+    pub fn from_glc(path: &Path, columns: usize) -> Result<Self>
+    {
+        let source = match GlcDecoder::new(BufReader::new(File::open(path)?))
+        {
+            Ok(glc) => Box::new(GlcSource::new(glc)?) as Box<dyn SeekableSource>,
+            // Files from before the frame-delimited container
+            Err(_) => Box::new(GlcSource::new(load_encoded(path)?)?),
+        };
+        let (frames, sample_rate, channels) = (source.length(), source.sample_rate(), source.channels());
+        Ok(Self::from_samples(source, frames, sample_rate, channels, columns))
+    }
+}
@@ -0,0 +1,62 @@
+// Tests for the ABX listening tests run from the GUI
+use gapless_lossy_codec::abx::{excerpt, guessing_probability, AbxTest};
+
+#[test]
+fn test_abx_trials_and_score()
+{
+    let mut test = AbxTest::with_seed(44100 * 10, 44100 * 4, 16, 7);
+    let mut starts = Vec::new();
+    let mut x_is_a = 0;
+    while let Some(trial) = test.trial()
+    {
+        // Every excerpt fits in the track
+        assert_eq!(test.segment(), trial.start..trial.start + 44100 * 4);
+        assert!(test.segment().end <= 44100 * 10);
+        starts.push(trial.start);
+        x_is_a += trial.x_is_a as usize;
+        // Answer the first twelve right and the rest wrong
+        assert_eq!(test.answer(trial.x_is_a == (test.answered() < 12)), Some(test.answered() <= 12));
+    }
+    assert!(test.finished());
+    assert_eq!((test.correct(), test.answered(), test.trials()), (12, 16, 16));
+    assert_eq!(test.answer(true), None);
+    // Excerpts and X are drawn at random
+    assert!(starts.windows(2).any(|pair| pair[0] != pair[1]));
+    assert!(x_is_a > 0 && x_is_a < 16, "X was A {} times of 16", x_is_a);
+    assert!((test.p_value() - 0.0384).abs() < 1e-4, "{}", test.p_value());
+
+    // The same seed deals the same trials, and excerpts are cut short to the track
+    assert_eq!(AbxTest::with_seed(1000, 400, 4, 3).trial(), AbxTest::with_seed(1000, 400, 4, 3).trial());
+    let short = AbxTest::with_seed(1000, 5000, 4, 3);
+    assert_eq!(short.segment(), 0..1000);
+}
+
+#[test]
+fn test_guessing_probability()
+{
+    assert_eq!(guessing_probability(0, 16), 1.0);
+    assert_eq!(guessing_probability(0, 0), 1.0);
+    assert!((guessing_probability(16, 16) - 1.0 / 65536.0).abs() < 1e-12);
+    assert!((guessing_probability(1, 2) - 0.75).abs() < 1e-12);
+    assert!((guessing_probability(8, 16) - 0.5982).abs() < 1e-4);
+    assert_eq!(guessing_probability(17, 16), 0.0);
+}
+
+#[test]
+fn test_excerpt_fades()
+{
+    // One second of stereo at full scale, cut from the middle
+    let samples = vec![1.0f32; 2 * 1000];
+    let clip = excerpt(&samples, 2, 1000, 200, 500);
+    assert_eq!(clip.len(), 2 * 500);
+    // Silent at both ends, full level in between, both channels alike
+    assert_eq!(clip[0], 0.0);
+    assert_eq!(clip[clip.len() - 1], 0.0);
+    assert!((clip[2 * 5] - 0.5).abs() < 1e-6, "{}", clip[2 * 5]);
+    assert_eq!(clip[2 * 250], 1.0);
+    assert!(clip.chunks_exact(2).all(|frame| frame[0] == frame[1]));
+
+    // Past the end of the track there is nothing to play
+    assert_eq!(excerpt(&samples, 2, 1000, 900, 500).len(), 2 * 100);
+    assert!(excerpt(&samples, 2, 1000, 5000, 500).is_empty());
+}
@@ -0,0 +1,101 @@
+// Tests for the per-frame statistics behind `glc analyze`
+use gapless_lossy_codec::analysis::{analyze, summarize, write_csv, write_summary_csv};
+use gapless_lossy_codec::container::write_encoded;
+use gapless_lossy_codec::loudness::measure;
+use gapless_lossy_codec::{Decoder, Encoder};
+
+mod utils;
+use utils::{generate_sine_wave, generate_white_noise};
+
+#[test]
+fn test_steady_sine_stats()
+{
+    let samples = generate_sine_wave(440.0, 44100, 2, 1.0);
+    let encoded = Encoder::new(44100).encode(&samples, 2).expect("Encoding failed");
+
+    let stats = analyze(&encoded, &samples).expect("Analysis failed");
+    assert_eq!(stats.len(), encoded.frames.len());
+    assert_eq!(stats[0].start_sample, -1024);
+    assert_eq!(stats[1].start_sample, 0);
+
+    // Skip the first frame: it rises out of the encoder delay's silence
+    for s in &stats[1..stats.len() - 1]
+    {
+        assert!(!s.raw_pcm, "Frame {} fell back to raw PCM", s.frame);
+        assert!(!s.transient, "Frame {} flagged as transient", s.frame);
+        assert_eq!(s.coefficients.len(), 2);
+        assert!(s.coefficients.iter().all(|&c| c > 0 && c < 1024));
+        assert_eq!(s.scale_factors.len(), 2);
+        assert!(s.bits > 0);
+    }
+}
+
+#[test]
+fn test_attack_is_flagged()
+{
+    // Silence, then noise from sample 10000 on
+    let mut samples = vec![0.0f32; 10000];
+    samples.extend(generate_white_noise(44100, 1, 0.5, 7));
+    let encoded = Encoder::new(44100).encode(&samples, 1).expect("Encoding failed");
+
+    let stats = analyze(&encoded, &samples).expect("Analysis failed");
+    let transients: Vec<usize> = stats.iter().filter(|s| s.transient).map(|s| s.frame).collect();
+
+    // Frames starting at 8192 and 9216 see the onset after their first sub-block
+    assert_eq!(transients, vec![9, 10]);
+}
+
+#[test]
+fn test_csv_output()
+{
+    let samples = generate_sine_wave(1000.0, 44100, 2, 0.2);
+    let encoded = Encoder::new(44100).encode(&samples, 2).expect("Encoding failed");
+    let stats = analyze(&encoded, &samples).expect("Analysis failed");
+
+    let mut csv = Vec::new();
+    write_csv(&stats, &mut csv).expect("Writing CSV failed");
+    let csv = String::from_utf8(csv).expect("CSV is not UTF-8");
+    let lines: Vec<&str> = csv.lines().collect();
+
+    assert_eq!(lines[0], "frame,start_sample,raw_pcm,coefficients,scale_factors,bits,transient");
+    assert_eq!(lines.len(), stats.len() + 1);
+    assert!(lines[1..].iter().all(|l| l.split(',').count() == 7));
+    assert!(lines[2].starts_with("1,0,false,"));
+}
+
+#[test]
+fn test_quality_summary()
+{
+    // A second of tone, then a second of noise, which the encoder stores as raw PCM
+    let mut samples = generate_sine_wave(440.0, 44100, 2, 1.0);
+    samples.extend(generate_white_noise(44100, 2, 1.0, 7));
+    let encoded = Encoder::new(44100).encode(&samples, 2).expect("Encoding failed");
+    let bytes = write_encoded(Vec::new(), &encoded).expect("Writing failed").len() as u64;
+    let decoded = Decoder::new(2, 44100).decode(&encoded, None).expect("Decoding failed");
+
+    let summary = summarize(&encoded, bytes, &samples, &decoded);
+    assert_eq!(summary.frames, encoded.frames.len());
+    assert_eq!(summary.duration_secs, 2.0);
+    assert!((summary.kbps - bytes as f64 * 8.0 / 2000.0).abs() < 1e-9);
+    let raw = encoded.frames.iter().filter(|f| f.raw_pcm.is_some()).count();
+    assert!(raw > 0);
+    assert!((summary.raw_pcm_percent - raw as f64 * 100.0 / encoded.frames.len() as f64).abs() < 1e-9);
+    // The tone's frames keep only a few of their coefficients
+    assert!(summary.sparsity > 0.5 && summary.sparsity < 1.0, "{:?}", summary);
+    assert!(summary.snr_db > 20.0, "{:?}", summary);
+    let loudness = measure(&decoded, 44100, 2);
+    assert_eq!((summary.loudness_lufs, summary.peak), (loudness.integrated_lufs, loudness.peak));
+
+    let mut csv = Vec::new();
+    write_summary_csv(&summary, &mut csv).expect("Writing CSV failed");
+    let csv = String::from_utf8(csv).expect("CSV is not UTF-8");
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].split(',').count(), 9);
+    assert!(lines[1].starts_with(&format!("{},2,", encoded.frames.len())));
+
+    // An exact match has no SNR to speak of, written to JSON as null
+    let exact = summarize(&encoded, bytes, &decoded, &decoded);
+    assert_eq!(exact.snr_db, f64::INFINITY);
+    assert!(serde_json::to_string(&exact).unwrap().contains("\"snr_db\":null"));
+}
@@ -0,0 +1,48 @@
+// Tests for the tokio-based async encode/decode wrappers
+#![cfg(feature = "async")]
+use gapless_lossy_codec::async_codec::{encode_async, decode_async, decode_stream};
+use std::sync::Arc;
+use tokio_stream::StreamExt;
+
+mod utils;
+use utils::generate_sine_wave;
+
+#[tokio::test]
+async fn test_encode_decode_async()
+{
+    let samples = generate_sine_wave(440.0, 44100, 2, 1.0);
+    let encoded = encode_async(samples.clone(), 44100, 2).await.expect("Async encoding failed");
+    let decoded = decode_async(Arc::new(encoded), None).await.expect("Async decoding failed");
+
+    assert_eq!(decoded.len(), samples.len(), "Length mismatch");
+}
+
+#[tokio::test]
+async fn test_decode_stream_matches_sync_decode()
+{
+    let samples = generate_sine_wave(440.0, 44100, 1, 15.0);
+    let encoded = Arc::new(encode_async(samples, 44100, 1).await.expect("Async encoding failed"));
+
+    let mut stream = Box::pin(decode_stream(encoded.clone(), None));
+    let mut streamed = Vec::new();
+    let mut chunks = 0;
+    let mut saw_last = false;
+    while let Some(chunk) = stream.next().await
+    {
+        chunks += 1;
+        streamed.extend(chunk.samples);
+        if chunk.is_last
+        {
+            saw_last = true;
+            break;
+        }
+    }
+
+    assert!(saw_last, "Stream ended without a final chunk");
+    assert!(chunks > 1, "Expected a long input to be split into several chunks");
+
+    // Streamed output is untrimmed; it should match the synchronous decode once the delay is removed
+    let decoded = decode_async(encoded.clone(), None).await.expect("Async decoding failed");
+    let delay = encoded.gapless_info.encoder_delay as usize;
+    assert_eq!(&streamed[delay..delay + decoded.len()], &decoded[..]);
+}
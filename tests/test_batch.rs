@@ -0,0 +1,54 @@
+// Tests for concurrent multi-file encoding
+use gapless_lossy_codec::audio::export_to_wav;
+use gapless_lossy_codec::batch::BatchEncoder;
+use gapless_lossy_codec::codec::{Progress, load_encoded};
+use std::path::PathBuf;
+
+mod utils;
+use utils::generate_sine_wave;
+
+#[test]
+fn test_batch_encode_multiple_files()
+{
+    let mut batch = BatchEncoder::new();
+    let mut lengths = Vec::new();
+    for (i, freq) in [220.0, 440.0, 880.0].iter().enumerate()
+    {
+        let samples = generate_sine_wave(*freq, 44100, 2, 0.5 + i as f32 * 0.25);
+        let path = PathBuf::from(format!("/tmp/test_batch_{}.wav", i));
+        export_to_wav(&path, &samples, 44100, 2).expect("WAV export failed");
+        lengths.push(samples.len());
+        batch.add_file(&path);
+    }
+    // A missing input fails on its own without affecting the rest
+    batch.add_file("/tmp/test_batch_missing.wav");
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let results = batch.run(Some(tx));
+
+    assert_eq!(results.len(), 4);
+    for (result, &length) in results.iter().zip(lengths.iter())
+    {
+        assert!(result.result.is_ok(), "Encoding {:?} failed", result.input);
+        assert_eq!(result.output.extension().unwrap(), "glc");
+        let encoded = load_encoded(&result.output).expect("Loading failed");
+        assert_eq!(encoded.gapless_info.original_length, length as u64);
+        std::fs::remove_file(&result.input).ok();
+        std::fs::remove_file(&result.output).ok();
+    }
+    assert!(results[3].result.is_err(), "Missing input should fail");
+
+    let mut statuses = 0;
+    let mut saw_complete = false;
+    while let Ok(progress) = rx.try_recv()
+    {
+        match progress
+        {
+            Progress::Status(_) => statuses += 1,
+            Progress::Complete(_) => saw_complete = true,
+            _ => {}
+        }
+    }
+    assert_eq!(statuses, 4, "Expected one status message per file");
+    assert!(saw_complete, "Expected a completion message");
+}
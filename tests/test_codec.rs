@@ -1,7 +1,9 @@
-use gapless_lossy_codec::codec::{Encoder, Decoder};
+use gapless_lossy_codec::codec::{Encoder, Decoder, GlcError, OverlapState, StreamOptions, MAX_CHANNELS};
+use gapless_lossy_codec::container::{read_encoded, write_encoded};
+use std::io::Cursor;
 
 mod utils;
-use utils::{generate_sine_wave, generate_square_wave, generate_sawtooth_wave, calculate_snr};
+use utils::{generate_sine_wave, generate_square_wave, generate_sawtooth_wave, generate_frequency_sweep, generate_white_noise, calculate_snr};
 
 #[test]
 fn test_sine_wave_440hz_mono()
@@ -168,3 +170,580 @@ fn test_gapless_multiple_files()
     println!("Gapless test: {} original samples, {} decoded samples", 
              total_original_len, total_decoded_len);
 }
+
+#[test]
+fn test_decode_frame_matches_decode()
+{
+    let samples = generate_sine_wave(440.0, 44100, 2, 1.0);
+    let mut encoder = Encoder::new(44100);
+    let encoded = encoder.encode(&samples, 2).expect("Encoding failed");
+
+    let mut decoder = Decoder::new(2usize, 44100);
+    let expected = decoder.decode(&encoded, None).expect("Decoding failed");
+
+    // Drive the frame-level API by hand, then apply the same gapless trim as decode()
+    let mut state = OverlapState::new(2);
+    let mut manual = Vec::new();
+    for frame in &encoded.frames
+    {
+        let out = decoder.decode_frame(frame, &mut state);
+        assert_eq!(out.len(), 1024 * 2, "Each frame should yield one hop per channel");
+        manual.extend(out);
+    }
+    manual.extend(state.flush());
+    manual.drain(0..encoded.gapless_info.encoder_delay as usize * 2);  // the delay is per channel
+    manual.truncate(encoded.gapless_info.original_length as usize);
+
+    assert_eq!(manual, expected, "Frame-by-frame decode differs from decode()");
+}
+
+#[test]
+fn test_decode_into_appends_trimmed_audio()
+{
+    let file1 = generate_sine_wave(440.0, 44100, 2, 1.0);
+    let file2 = generate_square_wave(220.0, 44100, 2, 0.5);
+
+    let mut encoder = Encoder::new(44100);
+    let encoded1 = encoder.encode(&file1, 2).expect("File 1 encoding failed");
+    let encoded2 = encoder.encode(&file2, 2).expect("File 2 encoding failed");
+
+    let mut decoder = Decoder::new(2usize, 44100);
+    let mut expected = decoder.decode(&encoded1, None).expect("File 1 decoding failed");
+    expected.extend(decoder.decode(&encoded2, None).expect("File 2 decoding failed"));
+
+    let mut combined = Vec::new();
+    decoder.decode_into(&encoded1, None, &mut combined).expect("File 1 decoding failed");
+    decoder.decode_into(&encoded2, None, &mut combined).expect("File 2 decoding failed");
+
+    assert_eq!(combined.len(), file1.len() + file2.len(), "Gapless length mismatch");
+    assert_eq!(combined, expected, "decode_into differs from decode()");
+}
+
+#[test]
+fn test_encode_planar_matches_interleaved()
+{
+    let samples = generate_sine_wave(440.0, 44100, 2, 1.0);
+    let left: Vec<f32> = samples.iter().step_by(2).copied().collect();
+    let right: Vec<f32> = samples.iter().skip(1).step_by(2).copied().collect();
+
+    let mut encoder = Encoder::new(44100);
+    let interleaved = encoder.encode(&samples, 2).expect("Interleaved encoding failed");
+    let planar = encoder.encode_planar(&[&left, &right]).expect("Planar encoding failed");
+
+    assert_eq!(planar.header.channels, 2);
+    assert_eq!(planar.header.total_samples, interleaved.header.total_samples);
+    assert_eq!(planar.gapless_info.padding, interleaved.gapless_info.padding);
+    assert_eq!(planar.frames.len(), interleaved.frames.len());
+    for (a, b) in planar.frames.iter().zip(interleaved.frames.iter())
+    {
+        assert_eq!(a.sparse_coeffs_per_channel, b.sparse_coeffs_per_channel);
+        assert_eq!(a.raw_pcm, b.raw_pcm);
+    }
+
+    // Mismatched channel lengths are rejected
+    assert!(encoder.encode_planar(&[&left, &right[1..]]).is_err());
+}
+
+#[test]
+fn test_very_short_input()
+{
+    // Empty input makes an empty stream, anything else enough frames to cover every sample twice
+    for (length, frames) in [(0, 0), (1, 2), (100, 2), (1023, 2), (1024, 2), (1025, 3)]
+    {
+        // Faded in and out, so a hard cut at either end doesn't ring
+        let samples: Vec<f32> = generate_sine_wave(440.0, 44100, 2, 0.1).into_iter().take(2 * length).enumerate()
+            .map(|(i, x)| x * (std::f32::consts::PI * ((i / 2) as f32 + 0.5) / length as f32).sin())
+            .collect();
+        let mut encoder = Encoder::new(44100);
+        let encoded = encoder.encode(&samples, 2).expect("Very short encoding failed");
+        assert_eq!(encoded.frames.len(), frames, "{} samples", length);
+        assert_eq!(encoded.gapless_info.original_length, 2 * length as u64);
+
+        let bytes = write_encoded(Vec::new(), &encoded).expect("Writing failed");
+        let loaded = read_encoded(Cursor::new(&bytes)).expect("Reading failed");
+        let decoded = Decoder::new(2usize, 44100).decode(&loaded, None).expect("Very short decoding failed");
+        assert_eq!(decoded.len(), samples.len(), "{} samples", length);
+        let max_error = samples.iter().zip(&decoded).map(|(a, b)| (a - b).abs()).fold(0.0f32, f32::max);
+        assert!(max_error < 0.05, "{} samples: max error {}", length, max_error);
+    }
+}
+
+#[test]
+fn test_decode_streaming_custom_chunk_size()
+{
+    let samples = generate_sine_wave(440.0, 44100, 2, 1.0);
+    let mut encoder = Encoder::new(44100);
+    let encoded = std::sync::Arc::new(encoder.encode(&samples, 2).expect("Encoding failed"));
+
+    let options = StreamOptions::with_chunk_duration(std::time::Duration::from_millis(100), 44100);
+    assert_eq!(options.frames_per_chunk, 5, "100 ms at 44.1 kHz should round up to 5 frames");
+
+    let mut decoder = Decoder::new(2usize, 44100);
+    let rx = decoder.decode_streaming_with(encoded.clone(), None, options);
+    let mut streamed = Vec::new();
+    while let Ok(chunk) = rx.recv()
+    {
+        if !chunk.is_last
+        {
+            assert_eq!(chunk.samples.len(), 5 * 1024 * 2, "Unexpected chunk size");
+        }
+        streamed.extend(chunk.samples);
+        if chunk.is_last
+        {
+            break;
+        }
+    }
+
+    assert_eq!(streamed.len(), (encoded.frames.len() + 1) * 1024 * 2, "Streamed output should be untrimmed");
+}
+
+#[test]
+fn test_quality_setting()
+{
+    use gapless_lossy_codec::codec::{encoded_to_bytes, DEFAULT_QUALITY};
+
+    let samples = generate_square_wave(440.0, 44100, 2, 1.0);
+    let mut encoder = Encoder::new(44100);
+    assert_eq!(encoder.quality(), DEFAULT_QUALITY);
+
+    let mut sizes = Vec::new();
+    for quality in [0.1, 1.0]
+    {
+        encoder.set_quality(quality);
+        let encoded = encoder.encode(&samples, 2).expect("Encoding failed");
+        sizes.push(encoded_to_bytes(&encoded).expect("Serializing failed").len());
+    }
+    assert!(sizes[0] < sizes[1], "Lower quality should give a smaller file: {:?}", sizes);
+
+    // Out-of-range values are clamped
+    encoder.set_quality(5.0);
+    assert_eq!(encoder.quality(), 1.0);
+}
+
+#[test]
+fn test_cutoff_setting()
+{
+    use gapless_lossy_codec::codec::{encoded_to_bytes, MIN_CUTOFF};
+    use gapless_lossy_codec::spectrogram::Spectrogram;
+
+    let samples = generate_square_wave(440.0, 44100, 2, 1.0);
+    let mut encoder = Encoder::new(44100);
+    assert_eq!(encoder.cutoff(), None);
+    let full = encoder.encode(&samples, 2).expect("Encoding failed");
+
+    encoder.set_cutoff(Some(8000.0));
+    assert!((encoder.cutoff().unwrap() - 8000.0).abs() < 22.0, "{:?}", encoder.cutoff());
+    let cut = encoder.encode(&samples, 2).expect("Encoding failed");
+    assert!(encoded_to_bytes(&cut).unwrap().len() < encoded_to_bytes(&full).unwrap().len());
+
+    // The square wave's harmonics stop at the cutoff
+    let decoded = Decoder::new(2usize, 44100).decode(&cut, None).expect("Decoding failed");
+    let spectrogram = Spectrogram::new(&decoded, 44100, 2, 1);
+    let band = |from: f32, to: f32| spectrogram.columns[0].iter().enumerate()
+        .filter(|&(bin, _)| (from..to).contains(&spectrogram.bin_frequency(bin)))
+        .fold(f32::MIN, |peak, (_, &level)| peak.max(level));
+    assert!(band(9000.0, 20000.0) < band(4000.0, 7500.0) - 40.0,
+            "{} dB above the cutoff, {} dB below", band(9000.0, 20000.0), band(4000.0, 7500.0));
+
+    // Cutoffs are kept to a sensible range
+    encoder.set_cutoff(Some(10.0));
+    assert!((encoder.cutoff().unwrap() - MIN_CUTOFF).abs() < 22.0);
+    encoder.set_cutoff(None);
+    assert_eq!(encoder.cutoff(), None);
+}
+
+#[test]
+fn test_highpass_setting()
+{
+    use gapless_lossy_codec::codec::encoded_to_bytes;
+
+    // A tone on a DC offset and 3 Hz rumble
+    let tone = generate_sine_wave(440.0, 44100, 2, 2.0);
+    let samples: Vec<f32> = tone.iter().enumerate()
+        .map(|(i, &s)| s + 0.25 + 0.1 * (2.0 * std::f32::consts::PI * 3.0 * (i / 2) as f32 / 44100.0).sin())
+        .collect();
+    let mut encoder = Encoder::new(44100);
+    assert_eq!(encoder.highpass(), None);
+    let plain = encoder.encode(&samples, 2).expect("Encoding failed");
+    assert_eq!(plain.header.highpass, None);
+
+    encoder.set_highpass(Some(15.0));
+    let filtered = encoder.encode(&samples, 2).expect("Encoding failed");
+    assert_eq!(filtered.header.highpass, Some(15.0));
+    assert!(encoded_to_bytes(&filtered).unwrap().len() < encoded_to_bytes(&plain).unwrap().len());
+
+    // Once the filter has settled, only the tone is left
+    let decoded = Decoder::new(2usize, 44100).decode(&filtered, None).expect("Decoding failed");
+    let settled = 2 * 22050..decoded.len();
+    let mean = decoded[settled.clone()].iter().sum::<f32>() / settled.len() as f32;
+    assert!(mean.abs() < 0.005, "DC offset of {} left", mean);
+    let rms = |x: &[f32]| (x.iter().map(|s| s * s).sum::<f32>() / x.len() as f32).sqrt();
+    let ratio = rms(&decoded[settled.clone()]) / rms(&tone[settled]);
+    assert!((ratio - 1.0).abs() < 0.05, "Tone level changed by a factor of {}", ratio);
+
+    // Cutoffs are kept to a sensible range
+    encoder.set_highpass(Some(100.0));
+    assert_eq!(encoder.highpass(), Some(20.0));
+    encoder.set_highpass(Some(1.0));
+    assert_eq!(encoder.highpass(), Some(5.0));
+    encoder.set_highpass(None);
+    assert_eq!(encoder.highpass(), None);
+}
+
+/// Interleave one mono signal per channel
+fn interleave(channels: &[Vec<f32>]) -> Vec<f32>
+{
+    (0..channels[0].len()).flat_map(|i| channels.iter().map(move |c| c[i])).collect()
+}
+
+/// Channel `ch` of interleaved `samples`
+fn channel(samples: &[f32], channels: usize, ch: usize) -> Vec<f32>
+{
+    samples.iter().skip(ch).step_by(channels).copied().collect()
+}
+
+#[test]
+fn test_six_channel_round_trip()
+{
+    // 5.1: a different tone in every channel, the LFE a low one, so crossed channels show up
+    let tones: Vec<Vec<f32>> = [440.0, 660.0, 880.0, 60.0, 330.0, 550.0].iter()
+                                                                     .map(|&f| generate_sine_wave(f, 48000, 1, 1.0))
+                                                                     .collect();
+    let samples = interleave(&tones);
+    let encoded = Encoder::new(48000).encode(&samples, 6).expect("Encoding failed");
+    assert!(encoded.frames.iter().all(|f| f.raw_pcm.is_some() || f.sparse_coeffs_per_channel.len() == 6));
+
+    // Through the container as well as in memory
+    let bytes = write_encoded(Vec::new(), &encoded).expect("Writing failed");
+    let loaded = read_encoded(Cursor::new(bytes)).expect("Reading failed");
+    for encoded in [&encoded, &loaded]
+    {
+        let decoded = Decoder::new(6, 48000).decode(encoded, None).expect("Decoding failed");
+        assert_eq!(decoded.len(), samples.len());
+        for (ch, tone) in tones.iter().enumerate()
+        {
+            let snr = calculate_snr(tone, &channel(&decoded, 6, ch));
+            assert!(snr > 10.0, "Channel {} SNR too low: {:.2} dB", ch, snr);
+        }
+    }
+}
+
+#[test]
+fn test_six_channel_raw_pcm_round_trip()
+{
+    // Independent noise in each channel is stored as raw PCM, a whole frame per channel
+    let noise: Vec<Vec<f32>> = (0..6).map(|seed| generate_white_noise(44100, 1, 1.0, seed)).collect();
+    let samples = interleave(&noise);
+    let encoded = Encoder::new(44100).encode(&samples, 6).expect("Encoding failed");
+    let raw = encoded.frames.iter().filter_map(|f| f.raw_pcm.as_ref()).collect::<Vec<_>>();
+    assert!(raw.len() > encoded.frames.len() / 2, "Noise should fall back to raw PCM");
+    assert!(raw.iter().all(|pcm| pcm.len() == 2 * 1024 * 6));
+
+    let decoded = Decoder::new(6, 44100).decode(&encoded, None).expect("Decoding failed");
+    for (ch, original) in noise.iter().enumerate()
+    {
+        let snr = calculate_snr(original, &channel(&decoded, 6, ch));
+        assert!(snr > 60.0, "Channel {} SNR too low: {:.2} dB", ch, snr);
+    }
+}
+
+#[test]
+fn test_channel_count_limits()
+{
+    let samples = vec![0.0; 4096 * (MAX_CHANNELS as usize + 1)];
+    let mut encoder = Encoder::new(44100);
+    assert!(encoder.encode(&samples[..4096 * MAX_CHANNELS as usize], MAX_CHANNELS).is_ok());
+    for channels in [0, MAX_CHANNELS + 1]
+    {
+        let error = encoder.encode(&samples, channels).expect_err("Unsupported channel count accepted");
+        assert_eq!(error.downcast_ref::<GlcError>(), Some(&GlcError::UnsupportedChannels(channels)));
+    }
+}
+
+#[test]
+fn test_coefficient_fields_cover_every_hop_size()
+{
+    // A sweep up towards Nyquist reaches the top bins of every frame size
+    let samples = generate_frequency_sweep(1000.0, 23000.0, 48000, 1, 0.5);
+    for hop_size in [64, 128, 256, 512, 1024]
+    {
+        let mut encoder = Encoder::with_hop_size(48000, hop_size).expect("Supported hop size rejected");
+        encoder.set_quality(1.0);
+        let encoded = encoder.encode(&samples, 1).expect("Encoding failed");
+        let top = encoded.frames.iter()
+                                .flat_map(|f| f.sparse_coeffs_per_channel.iter().flatten())
+                                .map(|&(index, _)| index as usize)
+                                .max()
+                                .expect("No coefficients kept");
+        assert!(top >= hop_size * 3 / 4 && top < hop_size, "Top index {} for a hop of {}", top, hop_size);
+        assert!(encoded.frames.iter().all(|f| f.validate(1, hop_size).is_ok()));
+    }
+
+    // Larger hops are rejected, so the default hop bounds every index
+    let error = Encoder::with_hop_size(48000, 2048).err().expect("Oversized hop accepted");
+    assert_eq!(error.downcast_ref::<GlcError>(), Some(&GlcError::UnsupportedHopSize(2048)));
+}
+
+#[test]
+fn test_bandwidth_extension()
+{
+    use gapless_lossy_codec::codec::{encoded_from_bytes, encoded_to_bytes, BANDWIDTH_EXTENSION_RANGE};
+    use gapless_lossy_codec::spectrogram::Spectrogram;
+
+    let samples = generate_white_noise(44100, 2, 1.0, 7).iter().map(|s| s * 0.3).collect::<Vec<f32>>();
+    let mut encoder = Encoder::new(44100);
+    assert_eq!(encoder.bandwidth_extension(), None);
+    let full = encoder.encode(&samples, 2).expect("Encoding failed");
+    encoder.set_cutoff(Some(8000.0));
+    let cut = encoder.encode(&samples, 2).expect("Encoding failed");
+    encoder.set_cutoff(None);
+    encoder.set_bandwidth_extension(Some(8000.0));
+    assert!((encoder.bandwidth_extension().unwrap() - 8000.0).abs() < 22.0, "{:?}", encoder.bandwidth_extension());
+    let extended = encoder.encode(&samples, 2).expect("Encoding failed");
+    assert_eq!(extended.header.bandwidth_extension, encoder.bandwidth_extension());
+    assert!(extended.frames.iter().all(|f| f.envelope.as_ref().is_some_and(|e| e.levels.len() == 2)));
+
+    // Only a few bytes a frame more than cutting the band off, far less than coding it
+    let sizes = [&full, &cut, &extended].map(|encoded| encoded_to_bytes(encoded).unwrap().len());
+    assert!(sizes[2] * 2 < sizes[0] && sizes[2] < sizes[1] + 60 * extended.frames.len(), "{:?}", sizes);
+
+    // The band above the crossover comes back at about its original level, where a cutoff leaves it silent
+    let level = |samples: &[f32], from: f32, to: f32|
+    {
+        let spectrogram = Spectrogram::new(samples, 44100, 2, 8);
+        let levels: Vec<f32> = spectrogram.columns[2..6].iter().flat_map(|column| column.iter().enumerate()
+            .filter(|&(bin, _)| (from..to).contains(&spectrogram.bin_frequency(bin)))
+            .map(|(_, &level)| level))
+            .collect();
+        levels.iter().sum::<f32>() / levels.len() as f32
+    };
+    let decoded = Decoder::new(2usize, 44100).decode(&extended, None).expect("Decoding failed");
+    let cut_decoded = Decoder::new(2usize, 44100).decode(&cut, None).expect("Decoding failed");
+    for (from, to) in [(9000.0, 12000.0), (12000.0, 16000.0), (16000.0, 20000.0)]
+    {
+        let (original, rebuilt) = (level(&samples, from, to), level(&decoded, from, to));
+        assert!((original - rebuilt).abs() < 3.0, "{}-{} Hz: {} dB, originally {} dB", from, to, rebuilt, original);
+        assert!(level(&cut_decoded, from, to) < original - 30.0);
+    }
+    assert!(calculate_snr(&samples, &decoded) > -3.0);
+
+    // Envelopes and the crossover survive a round trip to bytes
+    let reloaded = encoded_from_bytes(&encoded_to_bytes(&extended).unwrap()).unwrap();
+    assert_eq!(reloaded.header.bandwidth_extension, extended.header.bandwidth_extension);
+    assert_eq!(Decoder::new(2usize, 44100).decode(&reloaded, None).expect("Decoding failed"), decoded);
+
+    encoder.set_bandwidth_extension(Some(100.0));
+    assert!((encoder.bandwidth_extension().unwrap() - BANDWIDTH_EXTENSION_RANGE.start()).abs() < 22.0);
+    encoder.set_bandwidth_extension(None);
+    assert_eq!(encoder.bandwidth_extension(), None);
+}
+
+#[test]
+fn test_long_term_prediction()
+{
+    use gapless_lossy_codec::codec::{encoded_from_bytes, encoded_to_bytes, LTP_RESTART_INTERVAL};
+    use gapless_lossy_codec::compare::compare;
+
+    // A sustained note with a few harmonics, the case prediction is for
+    let samples: Vec<f32> = (0..3 * 44100).flat_map(|i|
+    {
+        let t = i as f32 / 44100.0;
+        let s = (1..=4).map(|h| (2.0 * std::f32::consts::PI * 220.0 * h as f32 * t).sin() * 0.4 / h as f32).sum::<f32>();
+        [s, s * 0.8]
+    }).collect();
+    let mut encoder = Encoder::new(44100);
+    assert!(!encoder.long_term_prediction());
+    let plain = encoder.encode(&samples, 2).expect("Encoding failed");
+    encoder.set_long_term_prediction(true);
+    let predicted = encoder.encode(&samples, 2).expect("Encoding failed");
+    assert!(!plain.header.long_term_prediction && predicted.header.long_term_prediction);
+
+    // Prediction restarts at fixed intervals and is used in between
+    for (index, frame) in predicted.frames.iter().enumerate()
+    {
+        assert_eq!(frame.restarts_prediction(), index % LTP_RESTART_INTERVAL == 0, "frame {}", index);
+    }
+    assert!(predicted.frames.iter().flat_map(|f| &f.ltp).filter(|p| p.is_some()).count() > predicted.frames.len());
+
+    let plain_bytes = encoded_to_bytes(&plain).unwrap();
+    let predicted_bytes = encoded_to_bytes(&predicted).unwrap();
+    assert!(predicted_bytes.len() * 10 < plain_bytes.len() * 7, "{} vs {} bytes", predicted_bytes.len(), plain_bytes.len());
+
+    // The decoder follows the encoder's prediction, through a round trip to bytes too, and what
+    // is left uncoded stays as far below the masking threshold
+    let decoded = Decoder::new(2usize, 44100).decode(&predicted, None).expect("Decoding failed");
+    assert_eq!(decoded.len(), samples.len());
+    let reloaded = Decoder::new(2usize, 44100).decode(&encoded_from_bytes(&predicted_bytes).unwrap(), None).expect("Decoding failed");
+    assert_eq!(decoded, reloaded);
+    let plain_decoded = Decoder::new(2usize, 44100).decode(&plain, None).expect("Decoding failed");
+    let plain_quality = compare(&samples, &plain_decoded, 44100, 2).metrics;
+    let quality = compare(&samples, &decoded, 44100, 2).metrics;
+    assert!(quality.noise_to_mask_db <= plain_quality.noise_to_mask_db + 1.0, "{:?} vs {:?}", quality, plain_quality);
+    assert!(calculate_snr(&samples, &decoded) > 30.0);
+}
+
+#[test]
+fn test_adaptive_quality()
+{
+    use gapless_lossy_codec::codec::{encoded_to_bytes, EncodedAudio};
+    use gapless_lossy_codec::compare::compare;
+
+    // Half-second stretches of a pure tone between ones of a dense chord over noise: the tone takes
+    // few bits to code transparently, the chord many
+    let noise = generate_white_noise(44100, 2, 4.0, 3);
+    let samples: Vec<f32> = (0..4 * 44100).flat_map(|i|
+    {
+        let t = i as f32 / 44100.0;
+        let s = if (i / 22050) % 2 == 0
+        {
+            (2.0 * std::f32::consts::PI * 440.0 * t).sin() * 0.5
+        }
+        else
+        {
+            (1..=40).map(|k| (2.0 * std::f32::consts::PI * 97.0 * k as f32 * 1.013f32.powi(k) * t).sin() * 0.02).sum::<f32>()
+                + noise[2 * i] * 0.02
+        };
+        [s, s]
+    }).collect();
+    let mut encoder = Encoder::new(44100);
+    assert!(!encoder.adaptive_quality());
+    let fixed = encoder.encode(&samples, 2).expect("Encoding failed");
+    encoder.set_adaptive_quality(true);
+    assert!(encoder.adaptive_quality());
+    let adaptive = encoder.encode(&samples, 2).expect("Encoding failed");
+    assert_eq!(adaptive.frames.len(), fixed.frames.len());
+
+    // Frame fi covers samples from (fi - 1) * hop to (fi + 1) * hop
+    let stretch = |encoded: &EncodedAudio, complex: bool| -> usize
+    {
+        encoded.frames.iter().enumerate().filter(|&(fi, _)|
+        {
+            let (start, end) = ((fi.max(1) - 1) * 1024, (fi + 1) * 1024);
+            start / 22050 == (end - 1) / 22050 && (start / 22050 % 2 == 1) == complex && end <= 4 * 44100
+        }).map(|(_, f)| f.sparse_coeffs_per_channel.iter().map(Vec::len).sum::<usize>()).sum()
+    };
+    // The tone is squeezed harder and the chord keeps more detail, for about the same size
+    assert!(stretch(&adaptive, false) * 10 < stretch(&fixed, false) * 9, "{} vs {}", stretch(&adaptive, false), stretch(&fixed, false));
+    assert!(stretch(&adaptive, true) > stretch(&fixed, true), "{} vs {}", stretch(&adaptive, true), stretch(&fixed, true));
+    let sizes = [&fixed, &adaptive].map(|encoded| encoded_to_bytes(encoded).unwrap().len());
+    assert!(sizes[1] * 100 < sizes[0] * 103, "{:?}", sizes);
+
+    // Less of the coding noise is audible
+    let quality = [&fixed, &adaptive].map(|encoded| compare(&samples, &Decoder::new(2usize, 44100).decode(encoded, None).unwrap(), 44100, 2));
+    assert!(quality[1].metrics.noise_to_mask_db < quality[0].metrics.noise_to_mask_db - 0.5,
+            "{} vs {} dB", quality[1].metrics.noise_to_mask_db, quality[0].metrics.noise_to_mask_db);
+    assert!(quality[1].metrics.odg >= quality[0].metrics.odg, "{} vs {}", quality[1].metrics.odg, quality[0].metrics.odg);
+}
+
+#[test]
+fn test_coefficient_budget()
+{
+    use gapless_lossy_codec::codec::{encoded_to_bytes, BITRATE_RANGE};
+
+    let samples: Vec<f32> = generate_white_noise(44100, 2, 3.0, 11).iter().zip(generate_sine_wave(1000.0, 44100, 2, 3.0))
+        .map(|(noise, tone)| noise * 0.1 + tone * 0.5)
+        .collect();
+    let mut encoder = Encoder::new(44100);
+    assert_eq!((encoder.bitrate(), encoder.coefficient_budget(2)), (None, None));
+
+    // Every frame keeps the same number of coefficients, and the file comes out just under the bitrate
+    let mut snrs = Vec::new();
+    for kbps in [64, 128, 256]
+    {
+        encoder.set_bitrate(Some(kbps));
+        let budget = encoder.coefficient_budget(2).unwrap();
+        let encoded = encoder.encode(&samples, 2).expect("Encoding failed");
+        assert!(encoded.frames.iter().all(|f| f.sparse_coeffs_per_channel.iter().all(|c| c.len() == budget)));
+        let bytes = encoded_to_bytes(&encoded).unwrap().len() as f64;
+        let target = kbps as f64 * 125.0 * encoded.frames.len() as f64 * 1024.0 / 44100.0;
+        assert!(bytes <= target + 200.0 && bytes > target * 0.97, "{} kbit/s: {} bytes for {}", kbps, bytes, target);
+
+        // The tone is always among the coefficients kept
+        let tone_bin = (1000.0 * 2.0 * 1024.0 / 44100.0) as u16;
+        assert!(encoded.frames[10].sparse_coeffs_per_channel.iter().all(|c| c.iter().any(|&(k, _)| k.abs_diff(tone_bin) <= 1)));
+        let decoded = Decoder::new(2usize, 44100).decode(&encoded, None).expect("Decoding failed");
+        snrs.push(calculate_snr(&samples, &decoded));
+    }
+    assert!(snrs[0] < snrs[1] && snrs[1] < snrs[2], "{:?}", snrs);
+
+    encoder.set_bitrate(Some(1));
+    assert_eq!(encoder.bitrate(), Some(*BITRATE_RANGE.start()));
+    encoder.set_bitrate(None);
+    assert_eq!(encoder.coefficient_budget(2), None);
+}
+
+#[test]
+fn test_minimum_bitrate()
+{
+    use gapless_lossy_codec::codec::{BITRATE_RANGE, LOW_DELAY_HOP_SIZE};
+
+    // Encode at `kbps` and check every frame keeps coefficients and the audio isn't lost
+    let check = |mut encoder: Encoder, channels: u16, kbps: u32|
+    {
+        encoder.set_bitrate(Some(kbps));
+        let budget = encoder.coefficient_budget(channels as usize).unwrap();
+        assert!(budget >= 1, "No coefficients at {} kbit/s", kbps);
+        let samples = generate_sine_wave(440.0, 44100, channels, 1.0);
+        let encoded = encoder.encode(&samples, channels).expect("Encoding failed");
+        assert!(encoded.frames.iter().all(|f| f.raw_pcm.is_some() || f.sparse_coeffs_per_channel.iter().all(|c| !c.is_empty())));
+        let decoded = Decoder::new(channels as usize, 44100).decode(&encoded, None).expect("Decoding failed");
+        assert!(calculate_snr(&samples, &decoded) > 3.0, "Silent at {} kbit/s", kbps);
+    };
+
+    // The lowest bitrate accepted is enough for stereo and mono at the default hop
+    let lowest = *BITRATE_RANGE.start();
+    assert!(Encoder::new(48000).min_bitrate(2) <= lowest);
+    check(Encoder::new(44100), 2, lowest);
+    check(Encoder::new(44100), 1, lowest);
+
+    // Low-delay streams need more, and are refused below it rather than encoded without coefficients
+    let mut encoder = Encoder::with_hop_size(44100, LOW_DELAY_HOP_SIZE).unwrap();
+    let minimum = encoder.min_bitrate(2);
+    assert!(minimum > lowest);
+    encoder.set_bitrate(Some(minimum - 1));
+    assert_eq!(encoder.coefficient_budget(2), Some(0));
+    assert!(encoder.encode(&generate_sine_wave(440.0, 44100, 2, 0.1), 2).is_err());
+    assert!(gapless_lossy_codec::container::GlcEncoder::with_encoder(Vec::new(), encoder, 2).is_err());
+    check(Encoder::with_hop_size(44100, LOW_DELAY_HOP_SIZE).unwrap(), 2, minimum);
+}
+
+#[test]
+fn test_noise_shaping()
+{
+    use gapless_lossy_codec::codec::encoded_to_bytes;
+    use gapless_lossy_codec::compare::compare;
+
+    // Dense material, where many bins fall just under their thresholds: a sweep over noise, and a
+    // chord of many quiet partials
+    let noise = generate_white_noise(44100, 2, 3.0, 13);
+    let swept: Vec<f32> = noise.iter().zip(generate_frequency_sweep(100.0, 8000.0, 44100, 2, 3.0))
+        .map(|(noise, sweep)| noise * 0.05 + sweep * 0.4)
+        .collect();
+    let chord: Vec<f32> = (0..3 * 44100).flat_map(|i|
+    {
+        let t = i as f32 / 44100.0;
+        let s = (1..=40).map(|k| (2.0 * std::f32::consts::PI * 97.0 * k as f32 * 1.013f32.powi(k) * t).sin() * 0.02).sum::<f32>()
+            + noise[2 * i] * 0.02;
+        [s, s]
+    }).collect();
+
+    let mut encoder = Encoder::new(44100);
+    assert!(!encoder.noise_shaping());
+    for samples in [&swept, &chord]
+    {
+        encoder.set_noise_shaping(false);
+        let plain = encoder.encode(samples, 2).expect("Encoding failed");
+        encoder.set_noise_shaping(true);
+        assert!(encoder.noise_shaping());
+        let shaped = encoder.encode(samples, 2).expect("Encoding failed");
+
+        // A few more coefficients are kept, and the noise left sits further under the mask
+        let sizes = [&plain, &shaped].map(|encoded| encoded_to_bytes(encoded).unwrap().len());
+        assert!(sizes[0] < sizes[1] && sizes[1] * 100 < sizes[0] * 110, "{:?}", sizes);
+        let quality = [&plain, &shaped].map(|encoded| compare(samples, &Decoder::new(2usize, 44100).decode(encoded, None).unwrap(), 44100, 2));
+        assert!(quality[1].metrics.noise_to_mask_db < quality[0].metrics.noise_to_mask_db - 0.3,
+                "{} vs {} dB", quality[1].metrics.noise_to_mask_db, quality[0].metrics.noise_to_mask_db);
+        assert!(quality[1].metrics.disturbed_frames <= quality[0].metrics.disturbed_frames);
+        assert!(quality[1].snr_db >= quality[0].snr_db, "{} vs {} dB", quality[1].snr_db, quality[0].snr_db);
+    }
+}
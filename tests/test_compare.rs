@@ -0,0 +1,71 @@
+// Tests for the objective quality comparison behind `glc compare`
+use gapless_lossy_codec::compare::compare;
+use gapless_lossy_codec::{Decoder, Encoder};
+
+mod utils;
+use utils::{calculate_snr_range, generate_sine_wave};
+
+#[test]
+fn test_identical_signals()
+{
+    let samples = generate_sine_wave(440.0, 44100, 2, 0.5);
+    let report = compare(&samples, &samples, 44100, 2);
+
+    assert_eq!(report.compared_samples, samples.len() as u64 / 2);
+    assert_eq!(report.length_difference, 0);
+    assert!(report.snr_db.is_infinite() && report.snr_db > 0.0);
+    assert_eq!(report.peak_difference, 0.0);
+    assert_eq!(report.loudness_difference_db, 0.0);
+    assert!(report.bands.iter().all(|b| b.snr_db.is_infinite()));
+}
+
+#[test]
+fn test_error_lands_in_its_band()
+{
+    let original = generate_sine_wave(250.0, 44100, 1, 1.0);
+    let error = generate_sine_wave(4000.0, 44100, 1, 1.0);
+    // Error at 1/100 of the signal amplitude: 40 dB below it
+    let decoded: Vec<f32> = original.iter().zip(&error).map(|(o, e)| o + e * 0.01).collect();
+
+    let report = compare(&original, &decoded, 44100, 1);
+    println!("SNR {:.2} dB, bands {:?}", report.snr_db, report.bands);
+
+    assert!((report.snr_db - 40.0).abs() < 0.1, "SNR was {}", report.snr_db);
+    assert!((report.peak_difference - 0.005).abs() < 1e-4);
+
+    let band = |center: f32| report.bands.iter().find(|b| b.center_hz == center).expect("missing band").snr_db;
+    assert!(band(250.0) > 70.0, "250 Hz band SNR was {}", band(250.0));
+    assert!(band(4000.0) < 0.0, "4 kHz band SNR was {}", band(4000.0));
+}
+
+#[test]
+fn test_length_and_loudness_difference()
+{
+    let original = generate_sine_wave(1000.0, 32000, 2, 0.5);
+    // Half the amplitude (-6.02 dB) and 100 samples per channel short
+    let decoded: Vec<f32> = original[..original.len() - 200].iter().map(|s| s * 0.5).collect();
+
+    let report = compare(&original, &decoded, 32000, 2);
+
+    assert_eq!(report.length_difference, -100);
+    assert_eq!(report.compared_samples, decoded.len() as u64 / 2);
+    assert!((report.loudness_difference_db + 6.02).abs() < 0.01);
+    // 16 kHz is above 40% of 32 kHz, so the highest band is 8 kHz
+    assert_eq!(report.bands.last().map(|b| b.center_hz), Some(8000.0));
+}
+
+#[test]
+fn test_matches_round_trip_snr()
+{
+    let samples = generate_sine_wave(440.0, 44100, 1, 1.0);
+
+    let mut encoder = Encoder::new(44100);
+    let encoded = encoder.encode(&samples, 1).expect("Encoding failed");
+    let mut decoder = Decoder::new(1, 44100);
+    let decoded = decoder.decode(&encoded, None).expect("Decoding failed");
+
+    let report = compare(&samples, &decoded, 44100, 1);
+    let expected = calculate_snr_range(&samples, &decoded, 0, samples.len().min(decoded.len()));
+
+    assert!((report.snr_db - expected as f64).abs() < 0.05, "{} vs {}", report.snr_db, expected);
+}
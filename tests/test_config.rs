@@ -0,0 +1,44 @@
+// Tests for the `glc` config file
+use gapless_lossy_codec::config::Config;
+use std::path::PathBuf;
+
+#[test]
+fn test_parse_config()
+{
+    let config = Config::parse(r#"
+quality = 0.9
+out_dir = "/srv/music/glc"
+flac_level = 8
+threads = 4
+device = "USB Audio"
+"#).expect("Parsing failed");
+
+    assert_eq!(config.quality, Some(0.9));
+    assert_eq!(config.out_dir, Some(PathBuf::from("/srv/music/glc")));
+    assert_eq!(config.flac_level, Some(8));
+    assert_eq!(config.threads, Some(4));
+    assert_eq!(config.device.as_deref(), Some("USB Audio"));
+
+    assert_eq!(Config::parse("").expect("Parsing failed"), Config::default());
+}
+
+#[test]
+fn test_reject_bad_config()
+{
+    assert!(Config::parse("qualty = 0.5").is_err(), "Unknown keys should be rejected");
+    assert!(Config::parse("quality = 2.0").is_err());
+    assert!(Config::parse("flac_level = 9").is_err());
+    assert!(Config::parse("threads = \"four\"").is_err());
+}
+
+#[test]
+fn test_load_config_file()
+{
+    let path = PathBuf::from("/tmp/test_config_load.toml");
+    std::fs::write(&path, "flac_level = 3\n").expect("Writing failed");
+    assert_eq!(Config::load_or_default(Some(&path)).expect("Loading failed").flac_level, Some(3));
+    let _ = std::fs::remove_file(&path);
+
+    // An explicitly named file must exist
+    assert!(Config::load_or_default(Some(&PathBuf::from("/tmp/test_config_missing.toml"))).is_err());
+}
@@ -0,0 +1,744 @@
+// Tests for the GLC container and the Read/Write based streaming encoder/decoder
+use gapless_lossy_codec::codec::{Encoder, Decoder, EncodedFrame, GaplessInfo, GlcError, LongTermPrediction, OverlapState, SpectralEnvelope, encoded_from_bytes, save_encoded, LOW_DELAY_HOP_SIZE};
+use gapless_lossy_codec::container::{DecodeOptions, GlcEncoder, GlcDecoder, GlcFrameWriter, MappedGlcFile, SizeEstimator, Tags, Track, write_encoded, read_encoded, read_encoded_with, repair, rewrite_tags};
+use std::io::Cursor;
+use std::path::PathBuf;
+
+mod utils;
+use utils::{calculate_snr, generate_sine_wave, generate_white_noise};
+
+#[test]
+fn test_container_round_trip()
+{
+    let samples = generate_sine_wave(440.0, 44100, 2, 2.0);
+    let mut encoder = Encoder::new(44100);
+    let encoded = encoder.encode(&samples, 2).expect("Encoding failed");
+
+    let bytes = write_encoded(Vec::new(), &encoded).expect("Writing failed");
+    let mut reader = GlcDecoder::new(Cursor::new(bytes)).expect("Opening failed");
+
+    assert_eq!(reader.frame_count(), encoded.frames.len());
+    assert_eq!(reader.header().sample_rate, 44100);
+    assert_eq!(reader.header().channels, 2);
+    assert_eq!(reader.gapless_info().original_length, samples.len() as u64);
+
+    let loaded = reader.read_all().expect("Reading failed");
+    let mut decoder = Decoder::new(2, 44100);
+    let original = decoder.decode(&encoded, None).expect("Decoding failed");
+    let reloaded = decoder.decode(&loaded, None).expect("Decoding failed");
+    assert_eq!(original, reloaded, "Container round trip changed the decoded audio");
+}
+
+#[test]
+fn test_streaming_encoder_matches_batch_encoder()
+{
+    let samples = generate_white_noise(44100, 2, 1.5, 42);
+
+    let mut encoder = Encoder::new(44100);
+    let encoded = encoder.encode(&samples, 2).expect("Encoding failed");
+    let batch_bytes = write_encoded(Vec::new(), &encoded).expect("Writing failed");
+
+    // Feed the streaming encoder in odd-sized pieces
+    let mut stream = GlcEncoder::new(Vec::new(), 44100, 2).expect("Creating encoder failed");
+    for piece in samples.chunks(2 * 777)
+    {
+        stream.write_samples(piece).expect("Streaming encode failed");
+    }
+    let stream_bytes = stream.finish().expect("Finishing stream failed");
+
+    assert_eq!(batch_bytes, stream_bytes, "Streaming and batch encodes differ");
+}
+
+#[test]
+fn test_streaming_very_short_input()
+{
+    for length in [0, 1, 100, 1023]
+    {
+        let samples: Vec<f32> = generate_sine_wave(440.0, 44100, 2, 0.1).into_iter().take(2 * length).collect();
+        let encoded = Encoder::new(44100).encode(&samples, 2).expect("Encoding failed");
+        let batch_bytes = write_encoded(Vec::new(), &encoded).expect("Writing failed");
+
+        let mut stream = GlcEncoder::new(Vec::new(), 44100, 2).expect("Creating encoder failed");
+        stream.write_samples(&samples).expect("Streaming encode failed");
+        let stream_bytes = stream.finish().expect("Finishing stream failed");
+        assert_eq!(batch_bytes, stream_bytes, "{} samples: streaming and batch encodes differ", length);
+
+        let mut estimator = SizeEstimator::new(44100, 2, 1).expect("Creating estimator failed");
+        estimator.write_samples(&samples).expect("Estimating failed");
+        assert_eq!(estimator.finish().expect("Finishing estimate failed").frames, encoded.frames.len() as u64);
+
+        let mut reader = GlcDecoder::new(Cursor::new(&stream_bytes)).expect("Opening failed");
+        assert_eq!(reader.frame_count(), encoded.frames.len());
+        let loaded = reader.read_all().expect("Reading failed");
+        let decoded = Decoder::new(2usize, 44100).decode(&loaded, None).expect("Decoding failed");
+        assert_eq!(decoded.len(), samples.len(), "{} samples", length);
+    }
+}
+
+#[test]
+fn test_low_delay_stream()
+{
+    let samples = generate_sine_wave(440.0, 44100, 2, 1.0);
+
+    let mut encoder = Encoder::with_hop_size(44100, LOW_DELAY_HOP_SIZE).expect("Creating encoder failed");
+    let encoded = encoder.encode(&samples, 2).expect("Encoding failed");
+    assert_eq!(encoded.header.hop_size, 256);
+    assert_eq!(encoded.gapless_info.encoder_delay, 256);
+    let batch_bytes = write_encoded(Vec::new(), &encoded).expect("Writing failed");
+
+    let mut stream = GlcEncoder::with_hop_size(Vec::new(), 44100, 2, LOW_DELAY_HOP_SIZE).expect("Creating encoder failed");
+    for piece in samples.chunks(2 * 100)
+    {
+        stream.write_samples(piece).expect("Streaming encode failed");
+    }
+    let stream_bytes = stream.finish().expect("Finishing stream failed");
+    assert_eq!(batch_bytes, stream_bytes, "Streaming and batch encodes differ");
+
+    // Only streams with a non-default hop need the newer stream header
+    assert_eq!(u16::from_le_bytes([stream_bytes[4], stream_bytes[5]]), 3);
+    let default_bytes = write_encoded(Vec::new(), &Encoder::new(44100).encode(&samples, 2).expect("Encoding failed"))
+        .expect("Writing failed");
+    assert_eq!(u16::from_le_bytes([default_bytes[4], default_bytes[5]]), 2);
+
+    let mut reader = GlcDecoder::new(Cursor::new(&stream_bytes)).expect("Opening failed");
+    assert_eq!(reader.header().hop_size, 256);
+    assert_eq!(reader.frame_count(), encoded.frames.len());
+    let loaded = reader.read_all().expect("Reading failed");
+    let decoded = Decoder::new(2, 44100).decode(&loaded, None).expect("Decoding failed");
+    assert_eq!(decoded.len(), samples.len());
+    let snr = calculate_snr(&samples, &decoded);
+    assert!(snr > 10.0, "SNR too low: {} dB", snr);
+
+    assert!(Encoder::with_hop_size(44100, 300).is_err(), "Hop sizes must be powers of two");
+}
+
+#[test]
+fn test_random_frame_access_and_crc()
+{
+    let samples = generate_sine_wave(1000.0, 48000, 1, 1.0);
+    let mut encoder = Encoder::new(48000);
+    let encoded = encoder.encode(&samples, 1).expect("Encoding failed");
+    let mut bytes = write_encoded(Vec::new(), &encoded).expect("Writing failed");
+
+    {
+        let mut reader = GlcDecoder::new(Cursor::new(&bytes)).expect("Opening failed");
+        let last = reader.frame_count() - 1;
+        let frame = reader.read_frame(last).expect("Reading last frame failed");
+        assert_eq!(frame.scale_factors.len(), encoded.frames[last].scale_factors.len());
+        assert!(reader.read_frame(last + 1).is_err(), "Out-of-range frame index should fail");
+    }
+
+    // Flip a byte inside the first frame's payload (after the 12-byte stream header and 8-byte record prefix)
+    bytes[12 + 8 + 4] ^= 0xFF;
+    let mut reader = GlcDecoder::new(Cursor::new(&bytes)).expect("Opening failed");
+    assert!(reader.read_frame(0).is_err(), "Corrupted frame should fail its CRC check");
+}
+
+#[test]
+fn test_legacy_bincode_files_still_load()
+{
+    let samples = generate_sine_wave(440.0, 44100, 1, 1.0);
+    let mut encoder = Encoder::new(44100);
+    let encoded = encoder.encode(&samples, 1).expect("Encoding failed");
+
+    let legacy = bincode::serialize(&encoded).expect("Serializing failed");
+    let loaded = read_encoded(Cursor::new(&legacy)).expect("Reading legacy file failed");
+    assert_eq!(loaded.frames.len(), encoded.frames.len());
+
+    let loaded = encoded_from_bytes(&legacy).expect("Reading legacy bytes failed");
+    assert_eq!(loaded.gapless_info.original_length, samples.len() as u64);
+}
+
+#[test]
+fn test_mapped_file_matches_decoder()
+{
+    let samples = generate_sine_wave(330.0, 44100, 2, 1.5);
+    let mut encoder = Encoder::new(44100);
+    let encoded = encoder.encode(&samples, 2).expect("Encoding failed");
+
+    let path = PathBuf::from("/tmp/test_container_mapped.glc");
+    save_encoded(&encoded, &path).expect("Saving failed");
+
+    let mapped = MappedGlcFile::open(&path).expect("Mapping failed");
+    assert_eq!(mapped.frame_count(), encoded.frames.len());
+    assert_eq!(mapped.header().channels, 2);
+    assert_eq!(mapped.gapless_info().original_length, samples.len() as u64);
+
+    // Random access should agree with the in-memory frames
+    let last = mapped.frame_count() - 1;
+    let frame = mapped.read_frame(last).expect("Reading frame failed");
+    assert_eq!(frame.scale_factors, encoded.frames[last].scale_factors);
+    assert!(mapped.read_frame(mapped.frame_count()).is_err(), "Out-of-range frame should fail");
+
+    let frames: Vec<_> = mapped.frames().collect::<Result<_, _>>().expect("Reading frames failed");
+    assert_eq!(frames.len(), encoded.frames.len());
+    for (a, b) in frames.iter().zip(encoded.frames.iter())
+    {
+        assert_eq!(a.sparse_coeffs_per_channel, b.sparse_coeffs_per_channel);
+        assert_eq!(a.raw_pcm, b.raw_pcm);
+    }
+
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn test_streaming_file_encode_matches_batch()
+{
+    use gapless_lossy_codec::audio::{LosslessReader, encode_lossless, load_audio_file_lossless, export_to_wav, export_to_flac};
+
+    let samples = generate_sine_wave(523.25, 44100, 2, 3.0);
+    for ext in ["wav", "flac"]
+    {
+        let path = PathBuf::from(format!("/tmp/test_container_streaming.{}", ext));
+        match ext
+        {
+            "wav" => export_to_wav(&path, &samples, 44100, 2).expect("WAV export failed"),
+            _ => export_to_flac(&path, &samples, 44100, 2).expect("FLAC export failed"),
+        }
+
+        let reader = LosslessReader::open(&path).expect("Opening failed");
+        assert_eq!(reader.sample_rate(), 44100);
+        assert_eq!(reader.channels(), 2);
+        let streamed = encode_lossless(reader, Vec::new()).expect("Streaming encode failed");
+
+        let (loaded, sample_rate, channels) = load_audio_file_lossless(&path).expect("Loading failed");
+        let mut encoder = Encoder::new(sample_rate);
+        let encoded = encoder.encode(&loaded, channels).expect("Encoding failed");
+        let batch = write_encoded(Vec::new(), &encoded).expect("Writing failed");
+
+        assert_eq!(streamed, batch, "Streaming {} encode differs from batch encode", ext);
+        std::fs::remove_file(&path).ok();
+    }
+}
+
+#[test]
+fn test_tags_round_trip_and_rewrite()
+{
+    let samples = generate_sine_wave(550.0, 44100, 2, 1.0);
+
+    let mut tags = Tags::new();
+    tags.set("Artist", "Someone");
+    tags.set("title", "Song");
+    tags.add("genre", "Ambient");
+    tags.add("GENRE", "Drone");
+
+    let mut encoder = GlcEncoder::new(Vec::new(), 44100, 2).expect("Creating encoder failed");
+    encoder.set_tags(tags.clone());
+    encoder.write_samples(&samples).expect("Writing samples failed");
+    let bytes = encoder.finish().expect("Finishing failed");
+
+    let reader = GlcDecoder::new(Cursor::new(&bytes)).expect("Opening failed");
+    assert_eq!(reader.tags(), &tags);
+    assert_eq!(reader.tags().get("ARTIST"), Some("Someone"));
+    assert_eq!(reader.tags().iter().filter(|(k, _)| *k == "genre").count(), 2);
+
+    // Rewriting tags in place must leave the frames untouched
+    let path = PathBuf::from("/tmp/test_container_tags.glc");
+    std::fs::write(&path, &bytes).expect("Writing file failed");
+    let before = std::fs::read(&path).expect("Reading file failed");
+
+    let mut edited = tags.clone();
+    edited.remove("genre");
+    edited.set("title", "A much longer title than before");
+    rewrite_tags(&path, &edited).expect("Rewriting tags failed");
+
+    let mapped = MappedGlcFile::open(&path).expect("Mapping failed");
+    assert_eq!(mapped.tags(), &edited);
+    assert_eq!(mapped.frame_count(), reader.frame_count());
+    let mut reader = GlcDecoder::new(Cursor::new(&bytes)).expect("Opening failed");
+    for i in 0..mapped.frame_count()
+    {
+        let a = mapped.read_frame(i).expect("Reading frame failed");
+        let b = reader.read_frame(i).expect("Reading frame failed");
+        assert_eq!(a.sparse_coeffs_per_channel, b.sparse_coeffs_per_channel);
+    }
+
+    // Clearing the tags drops the tag record entirely
+    rewrite_tags(&path, &Tags::new()).expect("Clearing tags failed");
+    let after = std::fs::read(&path).expect("Reading file failed");
+    let untagged = write_encoded(Vec::new(), &reader.read_all().expect("Reading failed")).expect("Writing failed");
+    assert_eq!(after, untagged);
+    assert!(after.len() < before.len());
+
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn test_album_track_list()
+{
+    use gapless_lossy_codec::audio::{encode_album, export_to_wav};
+
+    let first = generate_sine_wave(440.0, 44100, 2, 1.3);
+    let second = generate_sine_wave(660.0, 44100, 2, 0.7);
+    let paths = vec![PathBuf::from("/tmp/test_album_01 Intro.wav"), PathBuf::from("/tmp/test_album_02 Outro.wav")];
+    export_to_wav(&paths[0], &first, 44100, 2).expect("Writing WAV failed");
+    export_to_wav(&paths[1], &second, 44100, 2).expect("Writing WAV failed");
+
+    let bytes = encode_album(&paths, Vec::new()).expect("Encoding album failed");
+    let mut reader = GlcDecoder::new(Cursor::new(&bytes)).expect("Opening failed");
+
+    let tracks = reader.tracks().to_vec();
+    assert_eq!(tracks.len(), 2);
+    assert_eq!((tracks[0].start, tracks[0].length), (0, first.len() as u64 / 2));
+    assert_eq!((tracks[1].start, tracks[1].length), (first.len() as u64 / 2, second.len() as u64 / 2));
+    assert_eq!(tracks[0].tags.get("title"), Some("test_album_01 Intro"));
+    assert_eq!(tracks[1].tags.get("title"), Some("test_album_02 Outro"));
+    assert!(reader.tags().is_empty());
+
+    let encoded = reader.read_all().expect("Reading failed");
+    let decoded = Decoder::new(2, 44100).decode(&encoded, None).expect("Decoding failed");
+    assert_eq!(decoded.len(), first.len() + second.len());
+
+    // Editing the album's tags keeps the track list
+    let path = PathBuf::from("/tmp/test_album.glc");
+    std::fs::write(&path, &bytes).expect("Writing file failed");
+    let mut tags = Tags::new();
+    tags.set("album", "Test Album");
+    rewrite_tags(&path, &tags).expect("Rewriting tags failed");
+
+    let mapped = MappedGlcFile::open(&path).expect("Mapping failed");
+    assert_eq!(mapped.tags(), &tags);
+    assert_eq!(mapped.tracks(), &tracks[..]);
+
+    // Mismatched stream formats are rejected
+    let mono = PathBuf::from("/tmp/test_album_03 Mono.wav");
+    export_to_wav(&mono, &generate_sine_wave(440.0, 44100, 1, 0.5), 44100, 1).expect("Writing WAV failed");
+    assert!(encode_album(&[paths[0].clone(), mono.clone()], Vec::new()).is_err());
+
+    for p in paths.iter().chain([&path, &mono])
+    {
+        std::fs::remove_file(p).ok();
+    }
+}
+
+/// Encode stereo `samples` with an encoder set up by `configure`, both at once and streamed in
+/// `chunk`-sample pieces, check the two streams match each other and an exact size estimate
+/// (which leaves out long-term prediction), and return the stream
+fn check_stream(samples: &[f32], chunk: usize, configure: impl Fn(&mut Encoder)) -> Vec<u8>
+{
+    let encoder = ||
+    {
+        let mut encoder = Encoder::new(44100);
+        configure(&mut encoder);
+        encoder
+    };
+    let batch_bytes = write_encoded(Vec::new(), &encoder().encode(samples, 2).expect("Encoding failed")).expect("Writing failed");
+
+    let mut stream = GlcEncoder::with_encoder(Vec::new(), encoder(), 2).expect("Creating encoder failed");
+    for piece in samples.chunks(chunk)
+    {
+        stream.write_samples(piece).expect("Streaming encode failed");
+    }
+    let stream_bytes = stream.finish().expect("Finishing stream failed");
+    assert_eq!(batch_bytes, stream_bytes, "Streaming and batch encodes differ");
+
+    if !encoder().long_term_prediction()
+    {
+        let mut estimator = SizeEstimator::with_encoder(encoder(), 2, 1).expect("Creating estimator failed");
+        estimator.write_samples(samples).expect("Estimating failed");
+        assert_eq!(estimator.finish().expect("Finishing failed").bytes, stream_bytes.len() as u64);
+    }
+    stream_bytes
+}
+
+#[test]
+fn test_highpass_recorded()
+{
+    // The filter carries its state from one write to the next
+    let samples: Vec<f32> = generate_sine_wave(440.0, 44100, 2, 1.0).iter().map(|s| s + 0.2).collect();
+    let stream_bytes = check_stream(&samples, 2 * 777, |encoder| encoder.set_highpass(Some(10.0)));
+
+    assert_eq!(GlcDecoder::new(Cursor::new(&stream_bytes)).expect("Opening failed").header().highpass, Some(10.0));
+    assert_eq!(read_encoded(Cursor::new(&stream_bytes)).expect("Reading failed").header.highpass, Some(10.0));
+
+    // Footers written before the cutoff was added end at the channel mask
+    let plain = write_encoded(Vec::new(), &Encoder::new(44100).encode(&samples, 2).expect("Encoding failed"))
+        .expect("Writing failed");
+    let mut old = plain.clone();
+    let marker = u64::from_le_bytes(old[old.len() - 12..old.len() - 4].try_into().unwrap()) as usize;
+    let length = u32::from_le_bytes(old[marker + 4..marker + 8].try_into().unwrap()) as usize;
+    let payload = old[marker + 12..marker + 12 + length - 1].to_vec();
+    assert_eq!(old[marker + 12 + length - 1], 0, "The footer should end with an empty cutoff");
+    old[marker + 4..marker + 8].copy_from_slice(&(length as u32 - 1).to_le_bytes());
+    old[marker + 8..marker + 12].copy_from_slice(&gapless_lossy_codec::container::crc32(&payload).to_le_bytes());
+    old.remove(marker + 12 + length - 1);
+    let reader = GlcDecoder::new(Cursor::new(&old)).expect("Opening an older footer failed");
+    assert_eq!(reader.header().highpass, None);
+    assert_eq!(reader.frame_count(), GlcDecoder::new(Cursor::new(&plain)).unwrap().frame_count());
+}
+
+#[test]
+fn test_long_term_prediction_stream()
+{
+    // Prediction carries the decoded audio from one write to the next
+    let samples = generate_sine_wave(220.0, 44100, 2, 2.0);
+    let stream_bytes = check_stream(&samples, 2 * 777, |encoder| encoder.set_long_term_prediction(true));
+
+    // Streams with prediction are format version 4; decoding starts back at a restart frame
+    assert_eq!(u16::from_le_bytes([stream_bytes[4], stream_bytes[5]]), 4);
+    let mut reader = GlcDecoder::new(Cursor::new(&stream_bytes)).expect("Opening failed");
+    assert!(reader.header().long_term_prediction);
+    assert_eq!(reader.restart_frame(70).unwrap(), 64);
+    assert_eq!(reader.restart_frame(64).unwrap(), 64);
+
+    let plain = write_encoded(Vec::new(), &Encoder::new(44100).encode(&samples, 2).expect("Encoding failed"))
+        .expect("Writing failed");
+    let mut reader = GlcDecoder::new(Cursor::new(&plain)).expect("Opening failed");
+    assert!(!reader.header().long_term_prediction);
+    assert_eq!(reader.restart_frame(70).unwrap(), 70);
+}
+
+#[test]
+fn test_bandwidth_extension_stream()
+{
+    let samples = generate_white_noise(44100, 2, 1.0, 3);
+    let stream_bytes = check_stream(&samples, samples.len(), |encoder| encoder.set_bandwidth_extension(Some(8000.0)));
+
+    // The crossover is appended to the footer, and the envelopes to the frames, without a new
+    // format version, since readers that skip them still decode the band below the crossover
+    assert_eq!(u16::from_le_bytes([stream_bytes[4], stream_bytes[5]]), 2);
+    let mut reader = GlcDecoder::new(Cursor::new(&stream_bytes)).expect("Opening failed");
+    let crossover = reader.header().bandwidth_extension.expect("The crossover should be recorded");
+    assert!((crossover - 8000.0).abs() < 44100.0 / 2048.0, "Crossover at {} Hz", crossover);
+    assert!(reader.read_frame(10).unwrap().envelope.is_some());
+}
+
+#[test]
+fn test_adaptive_quality_stream()
+{
+    // Quiet and loud passages, written in uneven chunks so the running average spans several flushes
+    let samples: Vec<f32> = generate_white_noise(44100, 2, 1.0, 5).iter().zip(generate_sine_wave(330.0, 44100, 2, 1.0))
+        .enumerate().map(|(i, (noise, tone))| if (i / 20000) % 2 == 0 { tone * 0.6 } else { noise * 0.05 + tone * 0.1 })
+        .collect();
+    check_stream(&samples, 2 * 3001, |encoder|
+    {
+        encoder.set_adaptive_quality(true);
+        encoder.set_long_term_prediction(true);
+    });
+    let stream_bytes = check_stream(&samples, 2 * 3001, |encoder| encoder.set_adaptive_quality(true));
+
+    // Only the encoder's choices change, not the format
+    assert_eq!(u16::from_le_bytes([stream_bytes[4], stream_bytes[5]]), 2);
+    let plain = write_encoded(Vec::new(), &Encoder::new(44100).encode(&samples, 2).expect("Encoding failed")).expect("Writing failed");
+    assert_ne!(plain, stream_bytes);
+}
+
+#[test]
+fn test_bitrate_estimate()
+{
+    // With a coefficient budget the size follows the bitrate, so a sampled estimate is close
+    let samples = generate_white_noise(44100, 2, 3.0, 9).iter().map(|s| s * 0.2).collect::<Vec<f32>>();
+    let budgeted = ||
+    {
+        let mut encoder = Encoder::new(44100);
+        encoder.set_bitrate(Some(96));
+        encoder
+    };
+    let mut encoder = GlcEncoder::with_encoder(Vec::new(), budgeted(), 2).expect("Creating encoder failed");
+    encoder.write_samples(&samples).expect("Writing failed");
+    let actual = encoder.finish().expect("Finishing failed").len() as f64;
+    assert!((actual / (96.0 * 125.0 * 3.0) - 1.0).abs() < 0.03, "{} bytes", actual);
+
+    let mut estimator = SizeEstimator::with_encoder(budgeted(), 2, 8).expect("Creating estimator failed");
+    estimator.write_samples(&samples).expect("Estimating failed");
+    let estimate = estimator.finish().expect("Finishing failed");
+    assert!((estimate.bytes as f64 / actual - 1.0).abs() < 0.005, "{} vs {} bytes", estimate.bytes, actual);
+}
+
+#[test]
+fn test_size_estimate()
+{
+    let samples = generate_white_noise(44100, 2, 3.0, 7);
+    let mut tags = Tags::new();
+    tags.set("title", "Noise");
+
+    let mut encoder = GlcEncoder::new(Vec::new(), 44100, 2).expect("Creating encoder failed");
+    encoder.set_tags(tags.clone());
+    encoder.write_samples(&samples).expect("Writing failed");
+    let actual = encoder.finish().expect("Finishing failed").len() as u64;
+
+    // Encoding every frame predicts the size exactly
+    let mut exact = SizeEstimator::new(44100, 2, 1).expect("Creating estimator failed");
+    exact.set_tags(tags.clone());
+    exact.write_samples(&samples).expect("Estimating failed");
+    let exact = exact.finish().expect("Finishing failed");
+    assert_eq!(exact.bytes, actual);
+    assert_eq!(exact.sampled_frames, exact.frames);
+    assert!((exact.duration - 3.0).abs() < 1e-9);
+
+    // Sampling one frame in eight lands close on a steady signal
+    let mut sampled = SizeEstimator::new(44100, 2, 8).expect("Creating estimator failed");
+    sampled.set_tags(tags);
+    for chunk in samples.chunks(10000)
+    {
+        sampled.write_samples(chunk).expect("Estimating failed");
+    }
+    let sampled = sampled.finish().expect("Finishing failed");
+    assert_eq!(sampled.frames, exact.frames);
+    assert_eq!(sampled.sampled_frames, exact.frames.div_ceil(8));
+    let error = (sampled.bytes as f64 - actual as f64).abs() / actual as f64;
+    assert!(error < 0.1, "Estimated {} bytes, actual {}", sampled.bytes, actual);
+    assert!((sampled.kbps() - sampled.bytes as f64 * 8.0 / 3000.0).abs() < 1e-9);
+}
+
+/// Encode noise to a GLC stream with a tag, returning the bytes and their decoded audio
+fn encode_noise_stream() -> (Vec<u8>, Vec<f32>)
+{
+    let samples = generate_white_noise(44100, 2, 3.0, 11);
+    let mut encoder = GlcEncoder::new(Vec::new(), 44100, 2).expect("Creating encoder failed");
+    let mut tags = Tags::new();
+    tags.set("title", "Noise");
+    encoder.set_tags(tags);
+    encoder.write_samples(&samples).expect("Writing failed");
+    let bytes = encoder.finish().expect("Finishing failed");
+    let decoded = decode_bytes(&bytes);
+    (bytes, decoded)
+}
+
+fn decode_bytes(bytes: &[u8]) -> Vec<f32>
+{
+    let encoded = read_encoded(Cursor::new(bytes)).expect("Reading failed");
+    Decoder::new(2, 44100).decode(&encoded, None).expect("Decoding failed")
+}
+
+#[test]
+fn test_repair_intact_stream()
+{
+    let (bytes, _) = encode_noise_stream();
+    let (repaired, report) = repair(&bytes, Vec::new()).expect("Repair failed");
+    assert!(report.is_intact());
+    assert!(report.exact);
+    assert_eq!(report.length, 44100 * 3);
+    assert_eq!(repaired, bytes);
+}
+
+#[test]
+fn test_repair_corrupt_frame()
+{
+    let (mut bytes, original) = encode_noise_stream();
+    let middle = bytes.len() / 2;
+    bytes[middle] ^= 0x55;
+    assert!(read_encoded(Cursor::new(&bytes)).is_err());
+
+    let (repaired, report) = repair(&bytes, Vec::new()).expect("Repair failed");
+    assert!(report.exact);
+    assert!(!report.truncated && !report.metadata_lost);
+    assert_eq!(report.lost_frames, 1);
+    assert_eq!(report.lost_ranges.len(), 1);
+    // One frame spans two hops
+    let lost = report.lost_ranges[0].clone();
+    assert_eq!(lost.end - lost.start, 2048);
+
+    let decoder = GlcDecoder::new(Cursor::new(&repaired)).expect("Opening repaired stream failed");
+    assert_eq!(decoder.tags().get("title"), Some("Noise"));
+
+    // Everything outside the lost range decodes exactly as before
+    let decoded = decode_bytes(&repaired);
+    assert_eq!(decoded.len(), original.len());
+    for (i, (a, b)) in decoded.iter().zip(&original).enumerate()
+    {
+        if !lost.contains(&((i / 2) as u64))
+        {
+            assert!((a - b).abs() < 1e-6, "Sample {} differs", i);
+        }
+    }
+}
+
+#[test]
+fn test_repair_truncated_stream()
+{
+    let (bytes, original) = encode_noise_stream();
+    let truncated = &bytes[..bytes.len() * 2 / 3];
+    assert!(GlcDecoder::new(Cursor::new(truncated)).is_err());
+
+    let (repaired, report) = repair(truncated, Vec::new()).expect("Repair failed");
+    assert!(report.truncated);
+    assert!(!report.exact);
+    assert_eq!(report.lost_frames, 0);
+    assert!(report.lost_ranges.is_empty());
+    assert_eq!(report.length, (report.frames * 1024 - 1024) as u64);
+    assert!(report.length > 44100 && report.length < 44100 * 3);
+
+    // The salvaged audio matches the start of the original
+    let decoded = decode_bytes(&repaired);
+    assert_eq!(decoded.len() as u64, report.length * 2);
+    for (i, (a, b)) in decoded.iter().zip(&original).enumerate()
+    {
+        assert!((a - b).abs() < 1e-6, "Sample {} differs", i);
+    }
+}
+
+#[test]
+fn test_decode_truncated_stream()
+{
+    let (bytes, original) = encode_noise_stream();
+    let allow = DecodeOptions { allow_truncated: true };
+
+    // Intact streams read the same either way, with nothing to report
+    let (encoded, report) = read_encoded_with(Cursor::new(&bytes), &allow).expect("Reading failed");
+    assert!(report.is_none());
+    assert_eq!(encoded.frames.len(), read_encoded(Cursor::new(&bytes)).unwrap().frames.len());
+
+    // Cut off partway through a frame: an error unless allowed
+    let truncated = &bytes[..bytes.len() / 2 + 7];
+    assert!(read_encoded_with(Cursor::new(truncated), &DecodeOptions::default()).is_err());
+    let (encoded, report) = read_encoded_with(Cursor::new(truncated), &allow).expect("Truncated stream rejected");
+    let report = report.expect("Truncation not reported");
+    assert!(report.truncated && report.lost_frames == 0);
+    assert_eq!(encoded.frames.len(), report.frames);
+
+    // Everything up to the last complete frame matches the start of the original
+    let decoded = Decoder::new(2, 44100).decode(&encoded, None).expect("Decoding failed");
+    assert_eq!(decoded.len() as u64, report.length * 2);
+    assert!(report.length > 44100 && report.length < 44100 * 2, "{:?}", report);
+    assert!(decoded.iter().zip(&original).all(|(a, b)| (a - b).abs() < 1e-6));
+
+    // Damage that isn't truncation is still an error
+    let mut corrupt = bytes.clone();
+    let middle = corrupt.len() / 2;
+    corrupt[middle] ^= 0x55;
+    assert!(read_encoded_with(Cursor::new(&corrupt), &allow).is_err());
+}
+
+#[test]
+fn test_repair_truncated_and_corrupt()
+{
+    let (mut bytes, _) = encode_noise_stream();
+    bytes.truncate(bytes.len() * 3 / 4);
+    let middle = bytes.len() / 2;
+    bytes[middle] ^= 0x55;
+
+    let (repaired, report) = repair(&bytes, Vec::new()).expect("Repair failed");
+    assert!(report.truncated && !report.exact);
+    // Noise frames are all about the same size, so the estimate is right
+    assert_eq!(report.lost_frames, 1);
+    assert_eq!(report.lost_ranges.len(), 1);
+
+    let decoder = GlcDecoder::new(Cursor::new(&repaired)).expect("Opening repaired stream failed");
+    assert_eq!(decoder.frame_count(), report.frames);
+
+    assert!(repair(b"not a glc file", Vec::new()).is_err());
+}
+
+#[test]
+fn test_hostile_stream_header()
+{
+    let samples = generate_sine_wave(440.0, 44100, 2, 0.5);
+    let encoded = Encoder::new(44100).encode(&samples, 2).expect("Encoding failed");
+    let bytes = write_encoded(Vec::new(), &encoded).expect("Writing failed");
+
+    // Channel count (bytes 10-11) and sample rate (bytes 6-9) are checked before anything is allocated for them
+    let mut channels = bytes.clone();
+    channels[10..12].copy_from_slice(&60000u16.to_le_bytes());
+    let error = GlcDecoder::new(Cursor::new(channels)).err().expect("Absurd channel count accepted");
+    assert!(error.to_string().contains("channel count"), "{}", error);
+    let mut rate = bytes;
+    rate[6..10].copy_from_slice(&0u32.to_le_bytes());
+    assert!(GlcDecoder::new(Cursor::new(rate)).is_err());
+
+    // Nor can such a stream be written
+    assert!(GlcFrameWriter::new(Vec::new(), 44100, 1000).is_err());
+}
+
+#[test]
+fn test_hostile_frames()
+{
+    let good = EncodedFrame { sparse_coeffs_per_channel: vec![vec![(3, 100)], vec![(5, -100)]], scale_factors: vec![1.0, 1.0], raw_pcm: None,
+                               ltp: Vec::new(), envelope: None };
+    assert!(good.validate(2, 1024).is_ok());
+
+    let missing_channel = EncodedFrame { sparse_coeffs_per_channel: vec![vec![(3, 100)]], scale_factors: vec![1.0], ..good.clone() };
+    let bad_scale = EncodedFrame { scale_factors: vec![1.0, f32::NAN], ..good.clone() };
+    let bad_index = EncodedFrame { sparse_coeffs_per_channel: vec![vec![(3, 100)], vec![(1024, 1)]], ..good.clone() };
+    let long_pcm = EncodedFrame { raw_pcm: Some(vec![0; 2 * 1024 * 2 + 1]), ..good.clone() };
+    let short_lag = EncodedFrame { ltp: vec![Some(LongTermPrediction { lag: 1024, gain_index: 0, bands: 1 }), None], ..good.clone() };
+    let empty_envelope = EncodedFrame { envelope: Some(SpectralEnvelope { start: 0, levels: vec![[0; 8]; 2] }), ..good.clone() };
+    for frame in [&missing_channel, &bad_scale, &bad_index, &long_pcm, &short_lag, &empty_envelope]
+    {
+        assert!(frame.validate(2, 1024).is_err(), "{:?} accepted", frame);
+    }
+
+    // Decoding one anyway gives silence where the frame has nothing, rather than a panic
+    let mut state = OverlapState::new(2);
+    let decoded = Decoder::new(2, 44100).decode_frame(&missing_channel, &mut state);
+    assert!(decoded.iter().skip(1).step_by(2).all(|&sample| sample == 0.0));
+
+    // A stream holding such a frame fails to read instead of reaching the decoder
+    let mut writer = GlcFrameWriter::new(Vec::new(), 44100, 2).expect("Writer failed");
+    writer.write_frame(&good).expect("Writing frame failed");
+    writer.write_frame(&missing_channel).expect("Writing frame failed");
+    let info = GaplessInfo { encoder_delay: 512, padding: 0, original_length: 2048 };
+    let bytes = writer.finish(info).expect("Finishing failed");
+    let mut reader = GlcDecoder::new(Cursor::new(&bytes)).expect("Opening failed");
+    assert!(reader.read_frame(0).is_ok());
+    let error = reader.read_frame(1).expect_err("Malformed frame accepted");
+    assert!(error.to_string().starts_with("Frame 1:"), "{}", error);
+    assert!(read_encoded(Cursor::new(&bytes)).is_err());
+}
+
+#[test]
+fn test_hostile_footer()
+{
+    let samples = generate_sine_wave(440.0, 44100, 2, 0.5);
+    let encoded = Encoder::new(44100).encode(&samples, 2).expect("Encoding failed");
+    let write = |tracks: Vec<Track>|
+    {
+        let mut writer = GlcFrameWriter::new(Vec::new(), 44100, 2).expect("Writer failed");
+        writer.set_tracks(tracks);
+        for frame in &encoded.frames
+        {
+            writer.write_frame(frame).expect("Writing frame failed");
+        }
+        writer.finish(encoded.gapless_info.clone()).expect("Finishing failed")
+    };
+
+    let length = samples.len() as u64 / 2;
+    let fits = vec![Track { start: 0, length, tags: Tags::new() }];
+    assert!(GlcDecoder::new(Cursor::new(write(fits))).is_ok());
+    // Ends past the stream, or past u64::MAX
+    for start in [1, u64::MAX - 1]
+    {
+        let hostile = vec![Track { start, length, tags: Tags::new() }];
+        let error = GlcDecoder::new(Cursor::new(write(hostile))).err().expect("Hostile track list accepted");
+        assert!(error.to_string().contains("track list"), "{}", error);
+    }
+}
+
+#[test]
+fn test_load_rejects_inconsistent_headers()
+{
+    let samples = generate_sine_wave(440.0, 44100, 2, 0.5);
+    let encoded = Encoder::new(44100).encode(&samples, 2).expect("Encoding failed");
+    let glc_error = |result: anyhow::Result<_>| result.err().and_then(|e| e.downcast::<GlcError>().ok());
+
+    // Legacy files carry their header in the bincode blob, with nothing checked by a writer
+    let legacy = |edit: fn(&mut gapless_lossy_codec::codec::EncodedAudio)|
+    {
+        let mut hostile = encoded.clone();
+        edit(&mut hostile);
+        glc_error(read_encoded(Cursor::new(bincode::serialize(&hostile).unwrap())))
+    };
+    assert_eq!(legacy(|e| e.header.channels = 0), Some(GlcError::UnsupportedChannels(0)));
+    assert_eq!(legacy(|e| e.header.sample_rate = 4_000_000_000), Some(GlcError::UnsupportedSampleRate(4_000_000_000)));
+    assert_eq!(legacy(|e| e.gapless_info.original_length += 1), Some(GlcError::RaggedLength { length: samples.len() as u64 + 1, channels: 2 }));
+    let frames = encoded.frames.len();
+    assert_eq!(legacy(|e| e.gapless_info.original_length = u64::MAX - 1),
+               Some(GlcError::LengthMismatch { delay: 1024, length: u64::MAX / 2, frames, hop_size: 1024 }));
+    assert_eq!(legacy(|e| e.frames.truncate(2)),
+               Some(GlcError::LengthMismatch { delay: 1024, length: samples.len() as u64 / 2, frames: 2, hop_size: 1024 }));
+
+    // A stream whose footer claims more audio than its frames hold
+    let mut writer = GlcFrameWriter::new(Vec::new(), 44100, 2).expect("Writer failed");
+    writer.write_frame(&encoded.frames[0]).expect("Writing frame failed");
+    let bytes = writer.finish(encoded.gapless_info.clone()).expect("Finishing failed");
+    assert!(matches!(glc_error(read_encoded(Cursor::new(&bytes))), Some(GlcError::LengthMismatch { frames: 1, .. })));
+
+    // An unknown format version (bytes 4-5), and a stream header the footer disagrees with
+    let bytes = write_encoded(Vec::new(), &encoded).expect("Writing failed");
+    let mut version = bytes.clone();
+    version[4..6].copy_from_slice(&99u16.to_le_bytes());
+    assert_eq!(glc_error(read_encoded(Cursor::new(version))), Some(GlcError::UnsupportedVersion(99)));
+    let mut rate = bytes;
+    rate[6..10].copy_from_slice(&48000u32.to_le_bytes());
+    assert_eq!(glc_error(read_encoded(Cursor::new(rate))), Some(GlcError::HeaderMismatch));
+}
@@ -0,0 +1,62 @@
+// Tests for the sample ring between the decode thread and the cpal device callback
+#![cfg(feature = "playback-cpal")]
+use gapless_lossy_codec::cpal_output::SampleRing;
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+fn test_sample_ring_wraps()
+{
+    let ring = SampleRing::new(8);
+    assert_eq!(ring.push(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]), 6);
+
+    let mut out = [0.0; 4];
+    assert_eq!(ring.pop(&mut out), 4);
+    assert_eq!(out, [1.0, 2.0, 3.0, 4.0]);
+
+    // Only as much as fits goes in, wrapping around the end
+    assert_eq!(ring.push(&[7.0, 8.0, 9.0, 10.0, 11.0, 12.0, 13.0]), 6);
+    assert_eq!(ring.len(), 8);
+    assert_eq!(ring.push(&[14.0]), 0);
+
+    let mut out = [0.0; 10];
+    assert_eq!(ring.pop(&mut out), 8);
+    assert_eq!(out[..8], [5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0]);
+    assert!(ring.is_empty());
+    assert_eq!(ring.pop(&mut out), 0);
+}
+
+#[test]
+fn test_sample_ring_across_threads()
+{
+    let ring = Arc::new(SampleRing::new(64));
+    let total = 100_000;
+
+    let producer =
+    {
+        let ring = ring.clone();
+        thread::spawn(move ||
+        {
+            let samples: Vec<f32> = (0..total).map(|i| i as f32).collect();
+            let mut pending = &samples[..];
+            while !pending.is_empty()
+            {
+                let written = ring.push(&pending[..pending.len().min(17)]);
+                pending = &pending[written..];
+                thread::yield_now();
+            }
+        })
+    };
+
+    // Every sample arrives once, in order
+    let mut received = Vec::with_capacity(total);
+    let mut block = [0.0; 23];
+    while received.len() < total
+    {
+        let read = ring.pop(&mut block);
+        received.extend_from_slice(&block[..read]);
+        thread::yield_now();
+    }
+    producer.join().unwrap();
+    assert!(received.iter().enumerate().all(|(i, &sample)| sample == i as f32));
+}
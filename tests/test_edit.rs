@@ -0,0 +1,388 @@
+// Tests for cutting GLC streams on frame boundaries and for cue sheets
+use gapless_lossy_codec::codec::{Decoder, Encoder, OverlapState};
+use gapless_lossy_codec::container::{GlcDecoder, GlcFrameWriter, Tags, Track, read_encoded, write_encoded};
+use gapless_lossy_codec::cue::parse_cue;
+use gapless_lossy_codec::edit::{chain, extract_range, apply_gain, measure_loudness, merge_tags, parse_gain, parse_time, replaygain_gain,
+                                 seek_point, set_replaygain, trim};
+use gapless_lossy_codec::loudness::{combined_loudness, measure};
+use std::io::Cursor;
+
+mod utils;
+use utils::{calculate_snr, generate_sine_wave};
+
+/// Encode `samples` to an in-memory `.glc` stream
+fn encode_to_bytes(samples: &[f32], channels: u16) -> Vec<u8>
+{
+    let encoded = Encoder::new(44100).encode(samples, channels).expect("Encoding failed");
+    write_encoded(Vec::new(), &encoded).expect("Writing failed")
+}
+
+fn decode_bytes(bytes: Vec<u8>) -> Vec<f32>
+{
+    let encoded = read_encoded(Cursor::new(bytes)).expect("Reading failed");
+    Decoder::new(encoded.header.channels as usize, encoded.header.sample_rate)
+        .decode(&encoded, None)
+        .expect("Decoding failed")
+}
+
+#[test]
+fn test_extract_matches_full_decode()
+{
+    for channels in [1u16, 2]
+    {
+        let samples = generate_sine_wave(440.0, 44100, channels, 3.0);
+        let bytes = encode_to_bytes(&samples, channels);
+        let full = decode_bytes(bytes.clone());
+        assert_eq!(full.len(), samples.len());
+
+        let ch = channels as usize;
+        let mut source = GlcDecoder::new(Cursor::new(&bytes)).expect("Opening failed");
+        for (start, length) in [(0u64, 1000u64), (30000, 50000), (12345, 100), (131000, 1300)]
+        {
+            let cut = extract_range(&mut source, start, length, Tags::new(), Vec::new()).expect("Extracting failed");
+            assert!(cut.len() < bytes.len());
+
+            let decoded = decode_bytes(cut);
+            let expected = &full[start as usize * ch..(start + length) as usize * ch];
+            assert_eq!(decoded.len(), expected.len(), "{} channels, range {}+{}", channels, start, length);
+            for (i, (a, b)) in decoded.iter().zip(expected).enumerate()
+            {
+                assert!((a - b).abs() < 1e-6, "{} channels, range {}+{}: sample {} differs: {} vs {}",
+                        channels, start, length, i, a, b);
+            }
+        }
+
+        assert!(extract_range(&mut source, 132000, 1000, Tags::new(), Vec::new()).is_err());
+        assert!(extract_range(&mut source, 100, 0, Tags::new(), Vec::new()).is_err());
+    }
+}
+
+#[test]
+fn test_extract_predicted_stream()
+{
+    let samples = generate_sine_wave(220.0, 44100, 2, 3.0);
+    let mut encoder = Encoder::new(44100);
+    encoder.set_long_term_prediction(true);
+    let bytes = write_encoded(Vec::new(), &encoder.encode(&samples, 2).expect("Encoding failed")).expect("Writing failed");
+    let full = decode_bytes(bytes.clone());
+
+    // Cuts start from the frame where prediction restarts, so they decode exactly as the whole does
+    let mut source = GlcDecoder::new(Cursor::new(&bytes)).expect("Opening failed");
+    for (start, length) in [(0u64, 1000u64), (80000, 20000), (100000, 30000)]
+    {
+        let cut = extract_range(&mut source, start, length, Tags::new(), Vec::new()).expect("Extracting failed");
+        assert!(GlcDecoder::new(Cursor::new(&cut)).unwrap().header().long_term_prediction);
+        let decoded = decode_bytes(cut);
+        let expected = &full[start as usize * 2..(start + length) as usize * 2];
+        assert_eq!(decoded.len(), expected.len(), "range {}+{}", start, length);
+        let worst = decoded.iter().zip(expected).map(|(a, b)| (a - b).abs()).fold(0.0f32, f32::max);
+        assert!(worst < 1e-5, "range {}+{}: worst difference {}", start, length, worst);
+    }
+
+    // Joined back together, predicted frames after the join are re-encoded until prediction restarts
+    let halves: Vec<Vec<u8>> = [(0u64, 60000u64), (60000, 72300)].iter()
+        .map(|&(start, length)| extract_range(&mut source, start, length, Tags::new(), Vec::new()).expect("Extracting failed"))
+        .collect();
+    let mut sources: Vec<GlcDecoder<_>> = halves.iter().map(|h| GlcDecoder::new(Cursor::new(h)).unwrap()).collect();
+    let (joined, summary) = chain(&mut sources, Vec::new()).expect("Chaining failed");
+    assert!(summary.copied_frames > 0 && summary.reencoded_frames > 0, "{:?}", summary);
+    let joined = decode_bytes(joined);
+    assert_eq!(joined.len(), samples.len());
+    assert!(calculate_snr(&samples, &joined) > 30.0);
+}
+
+#[test]
+fn test_seek_point_primes_overlap()
+{
+    let samples = generate_sine_wave(440.0, 44100, 2, 2.0);
+    let bytes = encode_to_bytes(&samples, 2);
+    let full = decode_bytes(bytes.clone());
+
+    let mut source = GlcDecoder::new(Cursor::new(&bytes)).expect("Opening failed");
+    let delay = source.gapless_info().encoder_delay as u64;
+    let decoder = Decoder::new(2, 44100);
+    for start in [0u64, 1, 1023, 1024, 5000, 44100, 80000]
+    {
+        // Decode from the seek point with a fresh overlap, as a player does after seeking
+        let (first, skip) = seek_point(start, delay, 1024);
+        let mut overlap = OverlapState::new(2);
+        let mut decoded = Vec::new();
+        for index in first as usize..(first as usize + 4).min(source.frame_count())
+        {
+            let frame = source.read_frame(index).expect("Reading frame failed");
+            decoded.extend(decoder.decode_frame(&frame, &mut overlap));
+        }
+
+        let decoded = &decoded[skip as usize * 2..];
+        let expected = &full[start as usize * 2..];
+        let compared = decoded.len().min(expected.len());
+        assert!(compared >= 2000, "start {}: only {} samples to compare", start, compared);
+        for (i, (a, b)) in decoded[..compared].iter().zip(&expected[..compared]).enumerate()
+        {
+            assert!((a - b).abs() < 1e-6, "start {}: sample {} differs: {} vs {}", start, i, a, b);
+        }
+    }
+}
+
+#[test]
+fn test_decode_trims_delay_per_channel()
+{
+    // A stereo signal must come back aligned with the input, not shifted by the encoder delay
+    let samples = generate_sine_wave(1000.0, 44100, 2, 1.0);
+    let decoded = decode_bytes(encode_to_bytes(&samples, 2));
+
+    let error: f32 = samples.iter().zip(&decoded).skip(4096).take(44100).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max);
+    assert!(error < 0.1, "Decoded stereo is misaligned (max error {})", error);
+}
+
+#[test]
+fn test_chain_rejoins_split_parts()
+{
+    let samples = generate_sine_wave(440.0, 44100, 2, 3.0);
+    let bytes = encode_to_bytes(&samples, 2);
+    let full = decode_bytes(bytes.clone());
+    let total = full.len() as u64 / 2;
+
+    // Parts cut from one stream stay on its frame grid, so only the frames at the joins change
+    let joins = [50000u64, 70000];
+    let mut source = GlcDecoder::new(Cursor::new(&bytes)).expect("Opening failed");
+    let mut parts = Vec::new();
+    for (start, end) in [(0, joins[0]), (joins[0], joins[1]), (joins[1], total)]
+    {
+        let mut tags = Tags::new();
+        tags.set("title", format!("Part from {}", start));
+        let part = extract_range(&mut source, start, end - start, tags, Vec::new()).expect("Extracting failed");
+        parts.push(GlcDecoder::new(Cursor::new(part)).expect("Opening part failed"));
+    }
+
+    let (chained, summary) = chain(&mut parts, Vec::new()).expect("Chaining failed");
+    assert!(summary.reencoded_frames <= 2 * joins.len(), "{:?}", summary);
+    assert!(summary.copied_frames > 100, "{:?}", summary);
+
+    let reader = GlcDecoder::new(Cursor::new(&chained)).expect("Opening chain failed");
+    let starts: Vec<u64> = reader.tracks().iter().map(|t| t.start).collect();
+    assert_eq!(starts, vec![0, joins[0], joins[1]]);
+    assert_eq!(reader.tracks()[1].tags.get("title"), Some("Part from 50000"));
+
+    let decoded = decode_bytes(chained);
+    assert_eq!(decoded.len(), full.len());
+    assert!(calculate_snr(&full, &decoded) > 20.0);
+
+    // Away from the joins the output comes from the very same frames
+    for (i, (a, b)) in decoded.iter().zip(&full).enumerate()
+    {
+        let position = (i / 2) as u64;
+        if joins.iter().all(|&j| position + 2048 < j || position > j + 2048)
+        {
+            assert!((a - b).abs() < 1e-6, "Sample {} differs: {} vs {}", i, a, b);
+        }
+    }
+}
+
+#[test]
+fn test_chain_unaligned_sources()
+{
+    // Lengths that are not a multiple of the hop put the second file off the first one's frame grid
+    let first = generate_sine_wave(440.0, 44100, 1, 0.7);
+    let second = generate_sine_wave(660.0, 44100, 1, 0.45);
+    let mut sources = vec![
+        GlcDecoder::new(Cursor::new(encode_to_bytes(&first, 1))).expect("Opening failed"),
+        GlcDecoder::new(Cursor::new(encode_to_bytes(&second, 1))).expect("Opening failed"),
+    ];
+
+    let (chained, summary) = chain(&mut sources, Vec::new()).expect("Chaining failed");
+    assert!(summary.reencoded_frames > 0);
+
+    let decoded = decode_bytes(chained);
+    let expected: Vec<f32> = first.iter().chain(&second).copied().collect();
+    assert_eq!(decoded.len(), expected.len());
+    let snr = calculate_snr(&expected, &decoded);
+    assert!(snr > -10.0, "SNR too low: {} dB", snr);
+}
+
+#[test]
+fn test_chain_rejects_mismatched_sources()
+{
+    let mono = encode_to_bytes(&generate_sine_wave(440.0, 44100, 1, 0.2), 1);
+    let stereo = encode_to_bytes(&generate_sine_wave(440.0, 44100, 2, 0.2), 2);
+    let mut sources = vec![
+        GlcDecoder::new(Cursor::new(mono)).expect("Opening failed"),
+        GlcDecoder::new(Cursor::new(stereo)).expect("Opening failed"),
+    ];
+    assert!(chain(&mut sources, Vec::new()).is_err());
+}
+
+#[test]
+fn test_trim_keeps_tags_and_clips_tracks()
+{
+    let samples = generate_sine_wave(440.0, 44100, 2, 3.0);
+    let bytes = encode_to_bytes(&samples, 2);
+    let full = decode_bytes(bytes.clone());
+
+    // Give the stream a title and two tracks, split at 1.5 s
+    let mut source = GlcDecoder::new(Cursor::new(&bytes)).expect("Opening failed");
+    let mut tags = Tags::new();
+    tags.set("title", "Whole");
+    let tracks: Vec<Track> = (0..2u64).map(|i| Track { start: i * 66150, length: 66150, tags: Tags::new() }).collect();
+    let mut chained = GlcFrameWriter::new(Vec::new(), 44100, 2).expect("Writer failed");
+    chained.set_tags(tags);
+    chained.set_tracks(tracks);
+    for i in 0..source.frame_count()
+    {
+        chained.write_frame_bytes(&source.read_frame_bytes(i).expect("Reading frame failed")).expect("Writing frame failed");
+    }
+    let chained = chained.finish(source.gapless_info().clone()).expect("Finishing failed");
+
+    let mut source = GlcDecoder::new(Cursor::new(chained)).expect("Opening failed");
+    let trimmed = trim(&mut source, 44100, 44100, Vec::new()).expect("Trimming failed");
+
+    let reader = GlcDecoder::new(Cursor::new(&trimmed)).expect("Opening trimmed failed");
+    assert_eq!(reader.tags().get("title"), Some("Whole"));
+    let clipped: Vec<(u64, u64)> = reader.tracks().iter().map(|t| (t.start, t.length)).collect();
+    assert_eq!(clipped, vec![(0, 22050), (22050, 22050)]);
+
+    let decoded = decode_bytes(trimmed);
+    let expected = &full[44100 * 2..88200 * 2];
+    assert_eq!(decoded.len(), expected.len());
+    assert!(decoded.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-6));
+}
+
+#[test]
+fn test_parse_time()
+{
+    assert_eq!(parse_time("83.5").unwrap(), 83.5);
+    assert_eq!(parse_time("1:23.5").unwrap(), 83.5);
+    assert_eq!(parse_time("4:56").unwrap(), 296.0);
+    assert_eq!(parse_time("1:02:03").unwrap(), 3723.0);
+    for bad in ["", "1:60", "a:10", "1:2:3:4", "-5", "1.5:00"]
+    {
+        assert!(parse_time(bad).is_err(), "'{}' should not parse", bad);
+    }
+}
+
+#[test]
+fn test_apply_gain_scales_output()
+{
+    let samples = generate_sine_wave(440.0, 44100, 2, 1.0);
+    let bytes = encode_to_bytes(&samples, 2);
+    let full = decode_bytes(bytes.clone());
+
+    let mut tags = Tags::new();
+    tags.set("replaygain_track_gain", "-4.00 dB");
+    tags.set("replaygain_track_peak", "0.500000");
+    let mut source = GlcDecoder::new(Cursor::new(bytes)).expect("Opening failed");
+    let mut tagged = GlcFrameWriter::new(Vec::new(), 44100, 2).expect("Writer failed");
+    tagged.set_tags(tags);
+    for i in 0..source.frame_count()
+    {
+        tagged.write_frame_bytes(&source.read_frame_bytes(i).expect("Reading frame failed")).expect("Writing frame failed");
+    }
+    let tagged = tagged.finish(source.gapless_info().clone()).expect("Finishing failed");
+
+    let mut source = GlcDecoder::new(Cursor::new(tagged)).expect("Opening failed");
+    let (quieter, clipped) = apply_gain(&mut source, -6.0, Vec::new()).expect("Applying gain failed");
+    assert_eq!(clipped, 0);
+
+    // The gain has been applied, so ReplayGain now asks for 6 dB less attenuation
+    let reader = GlcDecoder::new(Cursor::new(&quieter)).expect("Opening failed");
+    assert_eq!(reader.tags().get("replaygain_track_gain"), Some("2.00 dB"));
+    assert_eq!(reader.tags().get("replaygain_track_peak"), Some("0.250594"));
+
+    let factor = 10f32.powf(-6.0 / 20.0);
+    let decoded = decode_bytes(quieter);
+    assert_eq!(decoded.len(), full.len());
+    for (i, (a, b)) in decoded.iter().zip(&full).enumerate()
+    {
+        assert!((a - b * factor).abs() < 1e-3, "Sample {}: {} vs {}", i, a, b * factor);
+    }
+}
+
+#[test]
+fn test_parse_gain()
+{
+    assert_eq!(parse_gain("-3dB").unwrap(), -3.0);
+    assert_eq!(parse_gain("+1.5 dB").unwrap(), 1.5);
+    assert_eq!(parse_gain("-6.20 dB").unwrap(), -6.2);
+    assert_eq!(parse_gain("2").unwrap(), 2.0);
+    for bad in ["", "dB", "loud", "3 dBFS"]
+    {
+        assert!(parse_gain(bad).is_err(), "'{}' should not parse", bad);
+    }
+}
+
+#[test]
+fn test_replaygain_gain()
+{
+    let mut tags = Tags::new();
+    tags.set("REPLAYGAIN_TRACK_GAIN", "-7.25 dB");
+    tags.set("replaygain_album_gain", "garbage");
+    assert_eq!(replaygain_gain(&tags, "track"), Some(-7.25));
+    assert_eq!(replaygain_gain(&tags, "album"), None);
+    assert_eq!(replaygain_gain(&Tags::new(), "track"), None);
+}
+
+#[test]
+fn test_replaygain_scan()
+{
+    // Two tracks of an album, one at a quarter of the other's level
+    let loud: Vec<f32> = generate_sine_wave(1000.0, 44100, 2, 3.0);
+    let quiet: Vec<f32> = loud.iter().map(|s| s * 0.25).collect();
+    let files = [encode_to_bytes(&loud, 2), encode_to_bytes(&quiet, 2)];
+    let meters: Vec<_> = files.iter().map(|bytes|
+    {
+        let mut glc = GlcDecoder::new(Cursor::new(bytes)).expect("Opening failed");
+        measure_loudness(&mut glc).expect("Measuring failed")
+    }).collect();
+
+    // Exactly the decoded audio is measured, without the encoder delay or padding
+    let track = meters[0].loudness();
+    assert_eq!(track, measure(&decode_bytes(files[0].clone()), 44100, 2));
+    assert!((track.integrated_lufs - measure(&loud, 44100, 2).integrated_lufs).abs() < 0.1);
+
+    let mut tags = Tags::new();
+    set_replaygain(&mut tags, "track", &track);
+    set_replaygain(&mut tags, "album", &combined_loudness(&meters));
+    let track_gain = replaygain_gain(&tags, "track").unwrap();
+    assert!((track_gain - (-18.0 - track.integrated_lufs)).abs() < 0.01);
+    assert_eq!(tags.get("replaygain_track_peak"), Some(format!("{:.6}", track.peak).as_str()));
+    // The quiet track brings the album down, so the album gain is higher, and the album peak is the loud track's
+    assert!(replaygain_gain(&tags, "album").unwrap() > track_gain + 1.0);
+    assert_eq!(tags.get("replaygain_album_peak"), tags.get("replaygain_track_peak"));
+}
+
+#[test]
+fn test_parse_cue()
+{
+    let cue = r#"REM GENRE Ambient
+PERFORMER "Someone"
+TITLE "The Album"
+FILE "album.wav" WAVE
+  TRACK 01 AUDIO
+    TITLE "Opening"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Closing"
+    PERFORMER "Someone Else"
+    INDEX 00 00:01:00
+    INDEX 01 00:02:15
+"#;
+    let total = 44100 * 5;
+    let (album, tracks) = parse_cue(cue, 44100, total).expect("Parsing failed");
+
+    assert_eq!(album.get("album"), Some("The Album"));
+    assert_eq!(album.get("albumartist"), Some("Someone"));
+    assert_eq!(album.get("genre"), Some("Ambient"));
+
+    assert_eq!(tracks.len(), 2);
+    assert_eq!((tracks[0].start, tracks[0].length), (0, 2 * 44100 + 15 * 588));
+    assert_eq!((tracks[1].start, tracks[1].length), (2 * 44100 + 15 * 588, total - 2 * 44100 - 15 * 588));
+    assert_eq!(tracks[1].tags.get("title"), Some("Closing"));
+    assert_eq!(tracks[1].tags.get("tracknumber"), Some("2"));
+
+    let tags = merge_tags(&album, &tracks[1].tags);
+    assert_eq!(tags.get("album"), Some("The Album"));
+    assert_eq!(tags.get("artist"), Some("Someone Else"));
+
+    assert!(parse_cue("TRACK 01 AUDIO\n", 44100, total).is_err());
+    assert!(parse_cue("TRACK 01 AUDIO\nINDEX 01 00:09:00\n", 44100, total).is_err());
+}
@@ -0,0 +1,95 @@
+// Tests for the playback equalizer
+use gapless_lossy_codec::equalizer::{graphic_bands, parse_bands, BandKind, EqBand, Equalizer, FilterBank,
+                                      BASS_FREQUENCY, GRAPHIC_FREQUENCIES, OCTAVE_Q};
+
+mod utils;
+use utils::generate_sine_wave;
+
+/// RMS level of `samples` in dB, skipping the first `skip` while the filters settle
+fn rms_db(samples: &[f32], skip: usize) -> f64
+{
+    let tail = &samples[skip..];
+    let power = tail.iter().map(|&s| s as f64 * s as f64).sum::<f64>() / tail.len() as f64;
+    10.0 * power.log10()
+}
+
+#[test]
+fn test_eq_parse_bands()
+{
+    let bands = parse_bands("60:+3, 1k:-2dB,2.5kHz:1.5:0.7,bass:+4,TREBLE:-2").unwrap();
+    assert_eq!(bands[0], EqBand::peak(60.0, 3.0, OCTAVE_Q));
+    assert_eq!(bands[1], EqBand::peak(1000.0, -2.0, OCTAVE_Q));
+    assert_eq!(bands[2], EqBand::peak(2500.0, 1.5, 0.7));
+    assert_eq!(bands[3], EqBand::bass(4.0));
+    assert_eq!(bands[4].kind, BandKind::HighShelf);
+    assert_eq!(bands[4].gain_db, -2.0);
+
+    assert!(parse_bands("").unwrap().is_empty());
+    for invalid in ["60", "60:+3:1:2", "hz:+3", "-60:+3", "60:+30", "60:+3:0", "60:loud"]
+    {
+        assert!(parse_bands(invalid).is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn test_eq_response()
+{
+    // A peak reaches its gain at its centre and fades away from it
+    let bank = FilterBank::new(&[EqBand::peak(1000.0, 6.0, OCTAVE_Q)], 44100, 2);
+    assert!((bank.response_db(1000.0) - 6.0).abs() < 0.01);
+    assert!(bank.response_db(50.0).abs() < 0.1);
+    assert!(bank.response_db(15000.0).abs() < 0.1);
+
+    // Shelves reach their gain well past their corner
+    let bank = FilterBank::new(&[EqBand::bass(-4.0), EqBand::treble(5.0)], 48000, 2);
+    assert!((bank.response_db(20.0) + 4.0).abs() < 0.1);
+    assert!((bank.response_db(20000.0) - 5.0).abs() < 0.3);
+    assert!(bank.response_db(1000.0).abs() < 0.5);
+
+    // A flat graphic EQ has no filters at all
+    assert!(FilterBank::new(&graphic_bands(&[0.0; 10]), 44100, 2).is_flat());
+}
+
+#[test]
+fn test_eq_filters_audio()
+{
+    let bands = [EqBand::peak(1000.0, 6.0, OCTAVE_Q), EqBand::bass(-6.0)];
+    for (frequency, expected) in [(1000.0, 6.0), (BASS_FREQUENCY / 4.0, -6.0), (8000.0, 0.0)]
+    {
+        let samples = generate_sine_wave(frequency, 44100, 2, 1.0);
+        let mut filtered = samples.clone();
+
+        // Block by block, as playback hands it over, matches the response curve
+        let mut bank = FilterBank::new(&bands, 44100, 2);
+        for block in filtered.chunks_mut(1000)
+        {
+            bank.process(block);
+        }
+        let change = rms_db(&filtered, 8820) - rms_db(&samples, 8820);
+        assert!((change - bank.response_db(frequency)).abs() < 0.1, "{} Hz: {} dB", frequency, change);
+        assert!((change - expected).abs() < 0.5, "{} Hz: {} dB", frequency, change);
+
+        // Channels are filtered independently
+        assert!(filtered.chunks(2).skip(4410).all(|frame| (frame[0] - frame[1]).abs() < 1e-6));
+    }
+}
+
+#[test]
+fn test_equalizer_settings()
+{
+    let equalizer = Equalizer::default();
+    assert!(equalizer.enabled());
+    assert!(equalizer.bands().is_empty());
+
+    let generation = equalizer.generation();
+    let gains = [3.0, 0.0, 0.0, 0.0, -2.0, 0.0, 0.0, 0.0, 0.0, 1.0];
+    equalizer.set_bands(graphic_bands(&gains));
+    assert_ne!(equalizer.generation(), generation);
+    let bands = equalizer.bands();
+    assert_eq!(bands.len(), GRAPHIC_FREQUENCIES.len());
+    assert_eq!(bands[4], EqBand::peak(500.0, -2.0, OCTAVE_Q));
+
+    equalizer.set_enabled(false);
+    assert!(!equalizer.enabled());
+    assert_eq!(equalizer.bands(), bands);
+}
@@ -1,6 +1,7 @@
 // Test audio export functionality (FLAC when available, WAV as fallback)
 use gapless_lossy_codec::codec::{Encoder, Decoder};
-use gapless_lossy_codec::audio::load_audio_file_lossless;
+use gapless_lossy_codec::audio::{export_to_aiff, load_audio_file_lossless, resample, write_raw_pcm, FormatConverter, RawFormat, Resampler};
+use gapless_lossy_codec::alac::encode_alac;
 use std::path::PathBuf;
 
 #[cfg(feature = "flac-export")]
@@ -163,4 +164,231 @@ fn test_export_gapless_playlist()
     std::fs::remove_file(output_path).ok();
 
     println!("Gapless playlist export test passed: {} total samples", all_samples.len());
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_write_raw_pcm()
+{
+    let samples = [0.5f32, -1.0, 2.0];
+    let raw = |format: RawFormat| -> Vec<u8>
+    {
+        let mut bytes = Vec::new();
+        write_raw_pcm(&mut bytes, &samples, format).expect("Writing raw PCM failed");
+        assert_eq!(bytes.len(), samples.len() * format.bytes_per_sample());
+        bytes
+    };
+
+    let f32le = raw(RawFormat::F32Le);
+    assert_eq!(f32::from_le_bytes(f32le[4..8].try_into().unwrap()), -1.0);
+    // Float output is not clamped
+    assert_eq!(f32::from_le_bytes(f32le[8..12].try_into().unwrap()), 2.0);
+
+    let s16le = raw(RawFormat::S16Le);
+    assert_eq!(s16le, [16383i16, -32767, 32767].iter().flat_map(|s| s.to_le_bytes()).collect::<Vec<u8>>());
+
+    let s24le = raw(RawFormat::S24Le);
+    assert_eq!(&s24le[0..3], &4194303i32.to_le_bytes()[..3]);
+    assert_eq!(&s24le[3..6], &(-8388607i32).to_le_bytes()[..3]);
+    assert_eq!(&s24le[6..9], &8388607i32.to_le_bytes()[..3]);
+
+    let s32le = raw(RawFormat::S32Le);
+    assert_eq!(i32::from_le_bytes(s32le[8..12].try_into().unwrap()), i32::MAX);
+
+    assert_eq!(RawFormat::parse("F32LE").unwrap(), RawFormat::F32Le);
+    assert_eq!(RawFormat::parse("s24le").unwrap().name(), "s24le");
+    assert!(RawFormat::parse("u8").is_err());
+}
+
+#[test]
+fn test_resample()
+{
+    // Phases are computed in f64: in f32 a 40 kHz tone's phase loses ~1e-3 rad within a tenth of a second
+    let sine = |frequency: f64, rate: u32, i: usize| (2.0 * std::f64::consts::PI * frequency * i as f64 / rate as f64).sin() as f32;
+
+    // 44.1 kHz -> 48 kHz keeps the tone and the duration
+    let input: Vec<f32> = (0..44100).flat_map(|i| [sine(1000.0, 44100, i); 2]).collect();
+    let output = resample(&input, 2, 44100, 48000);
+    assert_eq!(output.len(), 48000 * 2);
+    for i in 100..47900
+    {
+        assert!((output[i * 2] - sine(1000.0, 48000, i)).abs() < 1e-3, "Frame {} is off", i);
+        assert_eq!(output[i * 2], output[i * 2 + 1]);
+    }
+
+    // 96 kHz -> 48 kHz removes content above the new Nyquist frequency instead of aliasing it
+    let input: Vec<f32> = (0..9600).map(|i| sine(1000.0, 96000, i) + sine(40000.0, 96000, i)).collect();
+    let output = resample(&input, 1, 96000, 48000);
+    assert_eq!(output.len(), 4800);
+    for (i, s) in output.iter().enumerate().take(4700).skip(100)
+    {
+        assert!((s - sine(1000.0, 48000, i)).abs() < 1e-3, "Frame {} is off", i);
+    }
+
+    assert_eq!(resample(&[0.25, 0.5], 1, 44100, 44100), vec![0.25, 0.5]);
+}
+
+#[test]
+fn test_resampler_streaming()
+{
+    let input = generate_sine_wave(1000.0, 44100, 2, 0.5);
+    let expected = resample(&input, 2, 44100, 48000);
+
+    // Uneven blocks give the same output as resampling everything at once
+    let mut resampler = Resampler::new(2, 44100, 48000);
+    let mut output = Vec::new();
+    for block in input.chunks(2 * 777)
+    {
+        resampler.process(block, &mut output);
+    }
+    resampler.finish(&mut output);
+    assert_eq!(output, expected);
+
+    // Finishing starts over
+    let mut again = Vec::new();
+    resampler.process(&input, &mut again);
+    resampler.finish(&mut again);
+    assert_eq!(again, expected);
+}
+
+#[test]
+fn test_format_converter()
+{
+    // Mono at 22.05 kHz, then stereo at 44.1 kHz, played as one 44.1 kHz stereo stream
+    let mut converter = FormatConverter::new(44100, 2);
+    let mut output = Vec::new();
+    converter.push(&vec![0.5; 22050], 22050, 1, &mut output);
+    converter.push(&vec![0.25; 44100 * 2], 44100, 2, &mut output);
+    converter.finish(&mut output);
+
+    // Each track keeps its duration
+    assert_eq!(output.len(), 2 * 44100 * 2);
+    assert!((output[20000] - 0.5).abs() < 1e-4);
+    assert_eq!(output[20000], output[20001]);
+    assert_eq!(output[44100 * 2 + 1000], 0.25);
+
+    // Channels beyond the input's are silent; downmixing to mono averages
+    let mut output = Vec::new();
+    FormatConverter::new(44100, 3).push(&[0.5, -0.5], 44100, 2, &mut output);
+    assert_eq!(output, vec![0.5, -0.5, 0.0]);
+    let mut output = Vec::new();
+    FormatConverter::new(44100, 1).push(&[0.5, 0.25], 44100, 2, &mut output);
+    assert_eq!(output, vec![0.375]);
+}
+
+#[test]
+fn test_export_aiff()
+{
+    let samples = generate_sine_wave(440.0, 44100, 2, 0.5);
+    let path = PathBuf::from("/tmp/test_export.aiff");
+    export_to_aiff(&path, &samples, 44100, 2).expect("AIFF export failed");
+
+    let (loaded, sample_rate, channels) = load_audio_file_lossless(&path).expect("Loading the AIFF failed");
+    assert_eq!(sample_rate, 44100);
+    assert_eq!(channels, 2);
+    assert_eq!(loaded.len(), samples.len());
+    for (a, b) in samples.iter().zip(&loaded)
+    {
+        assert!((a - b).abs() <= 0.5 / 32768.0, "{} vs {}", a, b);
+    }
+
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn test_export_alac()
+{
+    // 2.5 frames of 4096 samples, so the last frame is partial
+    let samples = generate_sine_wave(440.0, 44100, 2, 1.0)[..10240 * 2].to_vec();
+    let bytes = encode_alac(Vec::new(), &samples, 44100, 2).expect("ALAC export failed");
+    let find = |kind: &[u8]| bytes.windows(kind.len()).rposition(|w| w == kind).expect("box not found");
+    let be32 = |pos: usize| u32::from_be_bytes(bytes[pos..pos + 4].try_into().unwrap());
+
+    assert_eq!(&bytes[4..12], b"ftypM4A ");
+
+    // The magic cookie (the innermost `alac` box): frame length, version, bit depth, then the rice parameters and channel count
+    let cookie = find(b"alac\0\0\0\0") + 8;
+    assert_eq!(be32(cookie), 4096);
+    assert_eq!(bytes[cookie + 5], 16);
+    assert_eq!(bytes[cookie + 9], 2);
+    assert_eq!(be32(cookie + 20), 44100);
+
+    // Three frames, the last holding 2048 samples
+    let stsz = find(b"stsz");
+    assert_eq!(be32(stsz + 12), 3);
+    let stts = find(b"stts");
+    assert_eq!((be32(stts + 8), be32(stts + 12), be32(stts + 16)), (2, 2, 4096));
+    assert_eq!((be32(stts + 20), be32(stts + 24)), (1, 2048));
+
+    // A sine compresses, so no frame falls back to being stored verbatim
+    let frame_bytes: u32 = (0..3).map(|i| be32(stsz + 16 + 4 * i)).sum();
+    assert!((frame_bytes as usize) < samples.len() * 2, "{} bytes", frame_bytes);
+
+    assert!(encode_alac(Vec::new(), &[0.0; 6], 44100, 6).is_err());
+}
+
+#[cfg(feature = "opus")]
+#[test]
+fn test_export_ogg_opus()
+{
+    use gapless_lossy_codec::ogg_opus::encode_ogg_opus;
+
+    let samples = generate_sine_wave(440.0, 44100, 2, 1.0);
+    let bytes = encode_ogg_opus(Vec::new(), &samples, 44100, 2, 128).expect("Opus export failed");
+
+    // The first page holds only the OpusHead packet: 27 byte page header plus one lacing value
+    assert_eq!(&bytes[0..4], b"OggS");
+    assert_eq!(&bytes[28..36], b"OpusHead");
+    assert_eq!(bytes[37], 2);
+    let pre_skip = u16::from_le_bytes([bytes[38], bytes[39]]) as u64;
+    assert_eq!(u32::from_le_bytes(bytes[40..44].try_into().unwrap()), 44100);
+
+    // The last page ends the stream, and its granule position trims the padding off the end
+    let last_page = bytes.windows(4).rposition(|w| w == b"OggS").unwrap();
+    assert_eq!(bytes[last_page + 5] & 0x04, 0x04);
+    let granule = u64::from_le_bytes(bytes[last_page + 6..last_page + 14].try_into().unwrap());
+    assert_eq!(granule, pre_skip + 48000);
+
+    // Roughly 128 kbit/s for one second
+    assert!(bytes.len() > 10_000 && bytes.len() < 25_000, "{} bytes", bytes.len());
+
+    assert!(encode_ogg_opus(Vec::new(), &[0.0; 6], 44100, 6, 128).is_err());
+}
+
+#[test]
+fn test_mp3_mode_parse()
+{
+    use gapless_lossy_codec::audio::Mp3Mode;
+
+    assert_eq!(Mp3Mode::parse("192").unwrap(), Mp3Mode::Cbr(192));
+    assert_eq!(Mp3Mode::parse("320k").unwrap(), Mp3Mode::Cbr(320));
+    assert_eq!(Mp3Mode::parse("V0").unwrap(), Mp3Mode::Vbr(0));
+    assert_eq!(Mp3Mode::default(), Mp3Mode::Vbr(2));
+    assert!(Mp3Mode::parse("100").is_err());
+    assert!(Mp3Mode::parse("V10").is_err());
+    assert_eq!(Mp3Mode::Cbr(192).to_string(), "192 kbit/s");
+}
+
+#[cfg(feature = "mp3")]
+#[test]
+fn test_export_mp3()
+{
+    use gapless_lossy_codec::audio::Mp3Mode;
+    use gapless_lossy_codec::mp3::encode_mp3;
+
+    let samples = generate_sine_wave(440.0, 44100, 2, 1.0);
+    let bytes = encode_mp3(&samples, 44100, 2, Mp3Mode::Cbr(128)).expect("MP3 export failed");
+
+    // Starts with a frame sync, and the first frame carries LAME's Info header
+    assert_eq!(bytes[0], 0xFF);
+    assert_eq!(bytes[1] & 0xE0, 0xE0);
+    assert!(bytes.windows(4).any(|w| w == b"Info" || w == b"Xing"));
+
+    // Roughly 128 kbit/s for one second
+    assert!(bytes.len() > 12_000 && bytes.len() < 22_000, "{} bytes", bytes.len());
+
+    // 96 kHz is resampled rather than rejected
+    let samples = generate_sine_wave(440.0, 96000, 1, 0.5);
+    assert!(encode_mp3(&samples, 96000, 1, Mp3Mode::Vbr(2)).is_ok());
+
+    assert!(encode_mp3(&[0.0; 6], 44100, 6, Mp3Mode::default()).is_err());
+}
@@ -65,8 +65,9 @@ fn test_compression_sawtooth_wave()
     let samples = generate_sawtooth_wave(440.0, 44100, 2, 10.0); // 10 seconds stereo
     let ratio = test_waveform_compression(samples, "Sawtooth Wave (440 Hz)");
 
-    // Sawtooth waves have even more harmonics than square waves
-    assert!(ratio >= 2.0, "Compression ratio too low: {:.2}x", ratio);
+    // Sawtooth waves have even more harmonics than square waves, and land just under 2x: the
+    // whole-hop encoder delay adds a frame to every stream, about 0.25% of these 10 seconds
+    assert!(ratio >= 1.99, "Compression ratio too low: {:.2}x", ratio);
     println!("✓ Compression achieved {:.2}x ratio", ratio);
 }
 
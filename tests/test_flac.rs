@@ -157,4 +157,32 @@ fn test_flac_compression_levels()
 
         std::fs::remove_file(path).ok();
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_flac_streaming_writer()
+{
+    use gapless_lossy_codec::flac::{encode_flac_with_level, FlacWriter};
+    use std::io::Cursor;
+
+    // Two and a half blocks of stereo, fed in chunks that don't line up with the blocks
+    let samples: Vec<f32> = (0..2 * 10_000).map(|i| ((i / 2) as f32 * 0.01).sin() * 0.5).collect();
+    let mut flac = FlacWriter::new(Cursor::new(Vec::new()), 44100, 2, 5).unwrap();
+    for chunk in samples.chunks(3001)
+    {
+        flac.write_samples(chunk).unwrap();
+    }
+    let streamed = flac.finish().unwrap().into_inner();
+    assert_eq!(streamed, encode_flac_with_level(&samples, 44100, 2, 5).unwrap());
+
+    let path = Path::new("target/streamed.flac");
+    std::fs::write(path, &streamed).unwrap();
+    let (loaded, rate, channels) = load_audio_file_lossless(path).unwrap();
+    std::fs::remove_file(path).ok();
+    assert_eq!((rate, channels, loaded.len()), (44100, 2, samples.len()));
+    assert!(samples.iter().zip(&loaded).all(|(a, b)| (a - b).abs() < 1e-4));
+
+    // Too short to be a FLAC stream
+    let mut flac = FlacWriter::new(Cursor::new(Vec::new()), 44100, 1, 5).unwrap();
+    flac.write_samples(&[0.0; 15]).unwrap();
+    assert!(flac.finish().is_err());
+}
@@ -0,0 +1,72 @@
+// Tests for scanning music folders for the GUI's library browser
+use gapless_lossy_codec::audio::{encode_lossless_file, export_to_wav};
+use gapless_lossy_codec::container::{rewrite_tags, Tags};
+use gapless_lossy_codec::library::{is_library_file, scan, LibraryTrack};
+use std::path::{Path, PathBuf};
+
+mod utils;
+use utils::generate_sine_wave;
+
+#[test]
+fn test_library_track_read()
+{
+    let dir = PathBuf::from("/tmp/test_library_track_read");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let wav = dir.join("source.wav");
+    export_to_wav(&wav, &generate_sine_wave(440.0, 44100, 2, 1.0), 44100, 2).expect("WAV export failed");
+    let glc = dir.join("encoded.glc");
+    encode_lossless_file(&wav, &glc).expect("Encoding failed");
+    let mut tags = Tags::new();
+    tags.set("title", "A Tone");
+    tags.set("artist", "Tester");
+    rewrite_tags(&glc, &tags).expect("Tagging failed");
+
+    // Untagged source files are titled by their file name
+    let source = LibraryTrack::read(&wav).expect("Reading the WAV failed");
+    assert_eq!((source.title.as_str(), source.artist.as_str(), source.encoded), ("source", "", false));
+    assert!((source.duration.unwrap() - 1.0).abs() < 1e-9, "{:?}", source.duration);
+    assert!((source.kbps.unwrap() - 1411.2).abs() < 1.0, "{:?}", source.kbps);
+
+    let encoded = LibraryTrack::read(&glc).expect("Reading the .glc file failed");
+    assert_eq!((encoded.title.as_str(), encoded.artist.as_str(), encoded.album.as_str()), ("A Tone", "Tester", ""));
+    assert!(encoded.encoded);
+    assert!((encoded.duration.unwrap() - 1.0).abs() < 1e-9, "{:?}", encoded.duration);
+    assert!(encoded.kbps.unwrap() < source.kbps.unwrap() / 2.0, "{:?}", encoded.kbps);
+
+    assert!(is_library_file(Path::new("song.GLC")) && is_library_file(Path::new("song.flac")));
+    assert!(!is_library_file(Path::new("cover.jpg")));
+}
+
+#[test]
+fn test_library_scan()
+{
+    let dir = PathBuf::from("/tmp/test_library_scan");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("album")).unwrap();
+    std::fs::create_dir_all(dir.join(".hidden")).unwrap();
+    let samples = generate_sine_wave(440.0, 44100, 2, 0.2);
+    for path in ["b.wav", "album/a.wav", ".hidden/c.wav", ".d.wav"]
+    {
+        export_to_wav(&dir.join(path), &samples, 44100, 2).expect("WAV export failed");
+    }
+    encode_lossless_file(&dir.join("b.wav"), &dir.join("album/b.glc")).expect("Encoding failed");
+    std::fs::write(dir.join("notes.txt"), "not audio").unwrap();
+    std::fs::write(dir.join("broken.glc"), "not a .glc file").unwrap();
+
+    // Every library file is passed on in path order, hidden ones and other files left out
+    let mut found = Vec::new();
+    let count = scan(&dir, |path, track|
+    {
+        found.push((path.strip_prefix(&dir).unwrap().to_path_buf(), track.is_ok()));
+        true
+    }).expect("Scanning failed");
+    assert_eq!(count, 4);
+    let expected = [("album/a.wav", true), ("album/b.glc", true), ("b.wav", true), ("broken.glc", false)];
+    assert_eq!(found, expected.map(|(path, ok)| (PathBuf::from(path), ok)));
+
+    // Scanning stops when asked to
+    let mut seen = 0;
+    assert_eq!(scan(&dir, |_, _| { seen += 1; seen < 2 }).expect("Scanning failed"), 2);
+    assert!(scan(&dir.join("missing"), |_, _| true).is_err());
+}
@@ -0,0 +1,112 @@
+// Tests for loudness measurement and loudness normalization at encode time
+use gapless_lossy_codec::audio::{encode_lossless_with, export_to_wav, EncodeOptions};
+use gapless_lossy_codec::codec::Decoder;
+use gapless_lossy_codec::container::read_encoded;
+use gapless_lossy_codec::loudness::{combined_loudness, measure, Loudness, LoudnessMeter, Normalize};
+use std::io::Cursor;
+use std::path::PathBuf;
+
+mod utils;
+
+/// A sine wave peaking at `peak` (the shared generator's peak at 0.5)
+fn sine(frequency: f32, sample_rate: u32, channels: u16, duration_seconds: f32, peak: f32) -> Vec<f32>
+{
+    utils::generate_sine_wave(frequency, sample_rate, channels, duration_seconds).iter().map(|s| s * 2.0 * peak).collect()
+}
+
+#[test]
+fn test_sine_reference_levels()
+{
+    // BS.1770: a full-scale 997 Hz sine in one channel reads -3.01 LUFS
+    let full_scale = sine(997.0, 48000, 1, 2.0, 1.0);
+    let loudness = measure(&full_scale, 48000, 1);
+    assert!((loudness.integrated_lufs + 3.01).abs() < 0.05, "Measured {} LUFS", loudness.integrated_lufs);
+
+    // A 1 kHz sine peaking at -20 dBFS in both channels reads -20 LUFS (+3 dB for the second channel)
+    let quiet = sine(1000.0, 44100, 2, 2.0, 0.1);
+    let loudness = measure(&quiet, 44100, 2);
+    assert!((loudness.integrated_lufs + 20.0).abs() < 0.05, "Measured {} LUFS", loudness.integrated_lufs);
+    assert!((loudness.peak - 0.1).abs() < 1e-3);
+}
+
+#[test]
+fn test_gating_ignores_silence()
+{
+    let tone = sine(1000.0, 44100, 1, 10.0, 0.1);
+    let mut padded = vec![0.0f32; 44100 * 3];
+    padded.extend(&tone);
+    padded.extend(vec![0.0f32; 44100 * 3]);
+
+    let alone = measure(&tone, 44100, 1).integrated_lufs;
+    let with_silence = measure(&padded, 44100, 1).integrated_lufs;
+    // Only the few blocks straddling the edges of the tone count for less
+    assert!((alone - with_silence).abs() < 0.2, "{} vs {}", alone, with_silence);
+
+    assert_eq!(measure(&[0.0; 44100], 44100, 1).integrated_lufs, f64::NEG_INFINITY);
+}
+
+#[test]
+fn test_combined_loudness()
+{
+    // Two tracks 10 LU apart, then the same tracks joined into one
+    let loud = sine(1000.0, 44100, 2, 5.0, 0.2);
+    let quiet = sine(1000.0, 44100, 2, 5.0, 0.2 / 10f32.powf(0.5));
+    let mut meters = [LoudnessMeter::new(44100, 2), LoudnessMeter::new(44100, 2)];
+    meters[0].add_samples(&loud);
+    meters[1].add_samples(&quiet);
+    let joined = measure(&[loud.clone(), quiet].concat(), 44100, 2);
+
+    let album = combined_loudness(&meters);
+    assert!((album.integrated_lufs - joined.integrated_lufs).abs() < 0.05, "{} vs {}", album.integrated_lufs, joined.integrated_lufs);
+    assert_eq!(album.peak, meters[0].loudness().peak);
+    // The quieter track is less than 10 LU down, so it passes the relative gate and pulls the album down
+    assert!(album.integrated_lufs < meters[0].loudness().integrated_lufs - 1.0);
+
+    // Tracks at other rates and channel counts pool too
+    let mut mono = LoudnessMeter::new(48000, 1);
+    mono.add_samples(&sine(1000.0, 48000, 1, 5.0, 0.2));
+    let mixed = combined_loudness([&meters[0], &mono]);
+    assert!(mixed.integrated_lufs < meters[0].loudness().integrated_lufs);
+    assert!(mixed.integrated_lufs > mono.loudness().integrated_lufs);
+
+    assert_eq!(combined_loudness(&[]).integrated_lufs, f64::NEG_INFINITY);
+}
+
+#[test]
+fn test_normalize_targets()
+{
+    assert_eq!(Normalize::parse("lufs=-16").unwrap(), Normalize::Lufs(-16.0));
+    assert_eq!(Normalize::parse("peak").unwrap(), Normalize::Peak(0.0));
+    assert_eq!(Normalize::parse("PEAK=-1").unwrap(), Normalize::Peak(-1.0));
+    assert!(Normalize::parse("lufs").is_err());
+    assert!(Normalize::parse("rms=-20").is_err());
+
+    let loudness = Loudness { integrated_lufs: -23.0, peak: 0.25 };
+    assert!((Normalize::Lufs(-16.0).gain_db(&loudness) - 7.0).abs() < 1e-9);
+    assert!((Normalize::Peak(0.0).gain_db(&loudness) - 12.04).abs() < 0.01);
+    // Reaching -10 LUFS would take 13 dB and clip, so the gain stops at full scale
+    assert!((Normalize::Lufs(-10.0).gain_db(&loudness) - 12.04).abs() < 0.01);
+
+    let silence = Loudness { integrated_lufs: f64::NEG_INFINITY, peak: 0.0 };
+    assert_eq!(Normalize::Lufs(-16.0).gain_db(&silence), 0.0);
+}
+
+#[test]
+fn test_encode_normalized_to_lufs()
+{
+    let samples = sine(1000.0, 44100, 2, 3.0, 0.1);
+    let path = PathBuf::from("/tmp/test_loudness_normalize.wav");
+    export_to_wav(&path, &samples, 44100, 2).expect("WAV export failed");
+
+    let options = EncodeOptions { normalize: Some(Normalize::Lufs(-16.0)), ..EncodeOptions::default() };
+    let (bytes, gain_db) = encode_lossless_with(&path, &options, Vec::new()).expect("Encoding failed");
+    let gain_db = gain_db.expect("No gain reported");
+    assert!((gain_db - 4.0).abs() < 0.05, "Applied {} dB", gain_db);
+
+    let encoded = read_encoded(Cursor::new(bytes)).expect("Reading failed");
+    let decoded = Decoder::new(2, 44100).decode(&encoded, None).expect("Decoding failed");
+    let loudness = measure(&decoded, 44100, 2);
+    assert!((loudness.integrated_lufs + 16.0).abs() < 0.5, "Output measured {} LUFS", loudness.integrated_lufs);
+
+    let _ = std::fs::remove_file(&path);
+}
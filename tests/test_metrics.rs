@@ -0,0 +1,110 @@
+// Tests for the per-band and perceptually weighted error measurements
+use gapless_lossy_codec::metrics::{difference_grade, measure, power_ratio_db, snr_db, CRITICAL_BAND_EDGES_HZ};
+
+mod utils;
+use utils::{generate_sawtooth_wave, generate_sine_wave, generate_white_noise};
+
+#[test]
+fn test_snr_db()
+{
+    let original = generate_sine_wave(440.0, 44100, 2, 0.5);
+    // A tenth of the amplitude off everywhere: 20 dB
+    let decoded: Vec<f32> = original.iter().map(|s| s * 0.9).collect();
+    assert!((snr_db(&original, &decoded) - 20.0).abs() < 1e-4, "{}", snr_db(&original, &decoded));
+    assert_eq!(snr_db(&original, &original), f64::INFINITY);
+    // Only the samples both have are compared
+    assert_eq!(snr_db(&original, &original[..100]), f64::INFINITY);
+    assert_eq!(power_ratio_db(1.0, 0.001), 30.0);
+}
+
+#[test]
+fn test_identical_signals_measure_perfect()
+{
+    let samples = generate_sine_wave(1000.0, 44100, 2, 0.5);
+    let metrics = measure(&samples, &samples, 44100, 2);
+    assert_eq!(metrics.bands.len(), CRITICAL_BAND_EDGES_HZ.len() - 1);
+    assert!(metrics.bands.iter().all(|band| band.snr_db == f64::INFINITY));
+    assert_eq!(metrics.weighted_snr_db, f64::INFINITY);
+    assert_eq!(metrics.spectral_distortion_db, 0.0);
+    assert_eq!(metrics.noise_to_mask_db, f64::NEG_INFINITY);
+    assert_eq!(metrics.disturbed_frames, 0.0);
+    assert_eq!(metrics.odg, 0.0);
+}
+
+#[test]
+fn test_error_lands_in_its_critical_band()
+{
+    // A 500 Hz tone with a 10 kHz error 40 dB below it
+    let original = generate_sine_wave(500.0, 44100, 1, 1.0);
+    let error = generate_sine_wave(10000.0, 44100, 1, 1.0);
+    let decoded: Vec<f32> = original.iter().zip(&error).map(|(o, e)| o + e * 0.01).collect();
+    let metrics = measure(&original, &decoded, 44100, 1);
+
+    let band = |hz: f32| metrics.bands.iter().find(|band| band.low_hz <= hz && hz < band.high_hz).expect("missing band");
+    assert!(band(500.0).snr_db > 60.0, "{:?}", band(500.0));
+    assert!(band(10000.0).snr_db < 0.0, "{:?}", band(10000.0));
+    // A-weighting puts 10 kHz (-2.5 dB) a little above 500 Hz (-3.2 dB), so the error counts for a little more
+    assert!((metrics.weighted_snr_db - 39.3).abs() < 0.2, "{}", metrics.weighted_snr_db);
+
+    // The same error at 100 Hz, which hearing is far less sensitive to, barely counts
+    let hum = generate_sine_wave(100.0, 44100, 1, 1.0);
+    let decoded: Vec<f32> = original.iter().zip(&hum).map(|(o, e)| o + e * 0.01).collect();
+    assert!(measure(&original, &decoded, 44100, 1).weighted_snr_db > 55.0);
+}
+
+#[test]
+fn test_spectral_distortion_and_nyquist()
+{
+    // Dropping the upper of two tones leaves a hole in the decoded spectrum
+    let low = generate_sine_wave(300.0, 16000, 2, 1.0);
+    let high = generate_sine_wave(6000.0, 16000, 2, 1.0);
+    let original: Vec<f32> = low.iter().zip(&high).map(|(a, b)| (a + b) / 2.0).collect();
+    let decoded: Vec<f32> = low.iter().map(|a| a / 2.0).collect();
+    let metrics = measure(&original, &decoded, 16000, 2);
+    assert!(metrics.spectral_distortion_db > 1.0, "{}", metrics.spectral_distortion_db);
+
+    // Broadband noise at half the level is 6 dB down in every bin
+    let noise = generate_white_noise(16000, 2, 1.0, 7);
+    let quieter: Vec<f32> = noise.iter().map(|s| s * 0.5).collect();
+    let level = measure(&noise, &quieter, 16000, 2).spectral_distortion_db;
+    assert!((level - 6.02).abs() < 0.01, "{}", level);
+
+    // Bands stop at Nyquist, the one straddling it cut short
+    let last = metrics.bands.last().unwrap();
+    assert_eq!((last.low_hz, last.high_hz), (7700.0, 8000.0));
+}
+
+#[test]
+fn test_difference_grade_falls_with_noise()
+{
+    let original = generate_sawtooth_wave(220.0, 44100, 2, 1.0);
+    let noise = generate_white_noise(44100, 2, 1.0, 3);
+    let grades: Vec<f64> = [0.001, 0.01, 0.1, 1.0].iter().map(|&gain|
+    {
+        let decoded: Vec<f32> = original.iter().zip(&noise).map(|(o, n)| o + n * gain).collect();
+        measure(&original, &decoded, 44100, 2).odg
+    }).collect();
+    assert!(grades.windows(2).all(|pair| pair[0] > pair[1]), "{:?}", grades);
+    assert!(grades[0] > -0.1, "{:?}", grades);
+    assert!(grades[3] < -3.5, "{:?}", grades);
+
+    assert!((difference_grade(-2.0) + 2.0).abs() < 1e-9);
+    assert!(difference_grade(f64::NEG_INFINITY) == 0.0 && difference_grade(f64::INFINITY) == -4.0);
+}
+
+#[test]
+fn test_error_under_a_tone_is_masked()
+{
+    // The same error 30 dB below a 1 kHz tone, once right beside it and once far above it
+    let original = generate_sine_wave(1000.0, 44100, 1, 1.0);
+    let grade = |frequency: f32|
+    {
+        let error = generate_sine_wave(frequency, 44100, 1, 1.0);
+        let decoded: Vec<f32> = original.iter().zip(&error).map(|(o, e)| o + e * 0.03).collect();
+        measure(&original, &decoded, 44100, 1)
+    };
+    let (beside, above) = (grade(1050.0), grade(8000.0));
+    assert!(beside.noise_to_mask_db < above.noise_to_mask_db - 10.0, "{} vs {}", beside.noise_to_mask_db, above.noise_to_mask_db);
+    assert!(beside.odg > above.odg, "{} vs {}", beside.odg, above.odg);
+    assert!(above.disturbed_frames > 0.9, "{}", above.disturbed_frames);
+}
@@ -0,0 +1,56 @@
+// Tests for the playback volume and soft limiter
+use gapless_lossy_codec::mixer::{soft_limit, Mixer, LIMITER_THRESHOLD, MAX_VOLUME};
+
+#[test]
+fn test_soft_limit_curve()
+{
+    // Untouched up to the threshold
+    for sample in [0.0f32, 0.25, -0.5, LIMITER_THRESHOLD, -LIMITER_THRESHOLD]
+    {
+        assert_eq!(soft_limit(sample), sample);
+    }
+
+    // Louder samples stay within full scale, keep their sign and order, and join the line smoothly
+    let mut previous = LIMITER_THRESHOLD;
+    for i in 1..=400
+    {
+        let sample = LIMITER_THRESHOLD + i as f32 * 0.01;
+        let limited = soft_limit(sample);
+        assert!(limited >= previous && limited <= 1.0, "{} -> {}", sample, limited);
+        assert_eq!(soft_limit(-sample), -limited);
+        previous = limited;
+    }
+    let just_above = soft_limit(LIMITER_THRESHOLD + 1e-3);
+    assert!((just_above - (LIMITER_THRESHOLD + 1e-3)).abs() < 1e-5);
+    assert!(soft_limit(100.0) <= 1.0);
+}
+
+#[test]
+fn test_mixer_volume_and_limiter()
+{
+    let mixer = Mixer::default();
+    assert_eq!(mixer.volume(), 1.0);
+    assert!(mixer.limiter());
+    assert_eq!(mixer.apply(0.5), 0.5);
+    assert!(mixer.apply(1.3) < 1.0);
+
+    mixer.set_volume(0.5);
+    assert_eq!(mixer.apply(0.5), 0.25);
+
+    mixer.set_limiter(false);
+    mixer.set_volume(2.0);
+    assert_eq!(mixer.apply(0.75), 1.5);
+
+    // Blocks get the same treatment as single samples
+    let mut block = vec![0.1, -0.6, 0.9];
+    mixer.set_limiter(true);
+    mixer.process(&mut block);
+    let expected: Vec<f32> = [0.1f32, -0.6, 0.9].iter().map(|&s| soft_limit(s * 2.0)).collect();
+    assert_eq!(block, expected);
+
+    // Out-of-range volumes are clamped
+    mixer.set_volume(10.0);
+    assert_eq!(mixer.volume(), MAX_VOLUME);
+    mixer.set_volume(-1.0);
+    assert_eq!(mixer.volume(), 0.0);
+}
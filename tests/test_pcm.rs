@@ -0,0 +1,285 @@
+// Tests for WAV (extensible, RF64), AIFF/AIFF-C, and CAF input
+use gapless_lossy_codec::audio::{LosslessReader, encode_lossless, load_audio_file_lossless};
+use gapless_lossy_codec::container::GlcDecoder;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+mod utils;
+use utils::generate_sine_wave;
+
+/// Encode an integer sample rate as an 80-bit extended float
+fn extended(rate: u32) -> [u8; 10]
+{
+    let shift = 31 - rate.leading_zeros();
+    let exponent = (16383 + shift) as u16;
+    let mantissa = (rate as u64) << (63 - shift);
+
+    let mut bytes = [0u8; 10];
+    bytes[..2].copy_from_slice(&exponent.to_be_bytes());
+    bytes[2..].copy_from_slice(&mantissa.to_be_bytes());
+    bytes
+}
+
+fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8>
+{
+    let mut out = id.to_vec();
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1
+    {
+        out.push(0);
+    }
+    out
+}
+
+/// Build an AIFF (compression `None`) or AIFF-C file around already encoded sample bytes
+fn build_aiff(compression: Option<&[u8; 4]>, channels: u16, bits: u16, frames: u32, sample_data: &[u8]) -> Vec<u8>
+{
+    let mut comm = Vec::new();
+    comm.extend_from_slice(&channels.to_be_bytes());
+    comm.extend_from_slice(&frames.to_be_bytes());
+    comm.extend_from_slice(&bits.to_be_bytes());
+    comm.extend_from_slice(&extended(44100));
+    if let Some(compression) = compression
+    {
+        comm.extend_from_slice(compression);
+        comm.extend_from_slice(&[0, 0]); // empty Pascal string name, padded
+    }
+
+    let mut ssnd = vec![0u8; 8];
+    ssnd.extend_from_slice(sample_data);
+
+    let mut body = if compression.is_some() { b"AIFC".to_vec() } else { b"AIFF".to_vec() };
+    body.extend(chunk(b"NAME", b"Sine"));
+    body.extend(chunk(b"COMM", &comm));
+    body.extend(chunk(b"SSND", &ssnd));
+
+    let mut file = b"FORM".to_vec();
+    file.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    file.extend(body);
+    file
+}
+
+/// Build a CAF file holding little-endian linear PCM
+fn build_caf(float: bool, channels: u32, bits: u32, sample_data: &[u8]) -> Vec<u8>
+{
+    let mut file = b"caff".to_vec();
+    file.extend_from_slice(&1u16.to_be_bytes());
+    file.extend_from_slice(&0u16.to_be_bytes());
+
+    let flags: u32 = if float { 1 | 2 } else { 2 | 4 };
+    let mut desc = Vec::new();
+    desc.extend_from_slice(&44100f64.to_bits().to_be_bytes());
+    desc.extend_from_slice(b"lpcm");
+    desc.extend_from_slice(&flags.to_be_bytes());
+    desc.extend_from_slice(&(bits / 8 * channels).to_be_bytes());
+    desc.extend_from_slice(&1u32.to_be_bytes());
+    desc.extend_from_slice(&channels.to_be_bytes());
+    desc.extend_from_slice(&bits.to_be_bytes());
+    file.extend_from_slice(b"desc");
+    file.extend_from_slice(&(desc.len() as u64).to_be_bytes());
+    file.extend(desc);
+
+    let info = b"\0\0\0\x01artist\0Someone\0";
+    file.extend_from_slice(b"info");
+    file.extend_from_slice(&(info.len() as u64).to_be_bytes());
+    file.extend_from_slice(info);
+
+    // Unknown data size (-1): the data runs to the end of the file
+    file.extend_from_slice(b"data");
+    file.extend_from_slice(&u64::MAX.to_be_bytes());
+    file.extend_from_slice(&0u32.to_be_bytes());
+    file.extend_from_slice(sample_data);
+    file
+}
+
+/// Build a 16-bit WAVE_FORMAT_EXTENSIBLE file, as RF64 (data sizes in `ds64`) if `rf64` is set
+fn build_extensible_wav(rf64: bool, channels: u16, channel_mask: u32, sample_data: &[u8]) -> Vec<u8>
+{
+    let le_chunk = |id: &[u8; 4], data: &[u8]| -> Vec<u8>
+    {
+        let mut out = id.to_vec();
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(data);
+        out
+    };
+
+    let mut fmt = Vec::new();
+    fmt.extend_from_slice(&0xFFFEu16.to_le_bytes());
+    fmt.extend_from_slice(&channels.to_le_bytes());
+    fmt.extend_from_slice(&44100u32.to_le_bytes());
+    fmt.extend_from_slice(&(44100 * 2 * channels as u32).to_le_bytes());
+    fmt.extend_from_slice(&(2 * channels).to_le_bytes());
+    fmt.extend_from_slice(&16u16.to_le_bytes());
+    fmt.extend_from_slice(&22u16.to_le_bytes());
+    fmt.extend_from_slice(&16u16.to_le_bytes());
+    fmt.extend_from_slice(&channel_mask.to_le_bytes());
+    // KSDATAFORMAT_SUBTYPE_PCM
+    fmt.extend_from_slice(&[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71]);
+
+    let mut info = b"INFO".to_vec();
+    info.extend(le_chunk(b"INAM", b"Surround\0\0"));
+
+    let mut body = b"WAVE".to_vec();
+    if rf64
+    {
+        let mut ds64 = Vec::new();
+        ds64.extend_from_slice(&0u64.to_le_bytes());
+        ds64.extend_from_slice(&(sample_data.len() as u64).to_le_bytes());
+        ds64.extend_from_slice(&0u64.to_le_bytes());
+        ds64.extend_from_slice(&0u32.to_le_bytes());
+        body.extend(le_chunk(b"ds64", &ds64));
+    }
+    body.extend(le_chunk(b"fmt ", &fmt));
+    body.extend(le_chunk(b"LIST", &info));
+    body.extend_from_slice(b"data");
+    let data_size = if rf64 { u32::MAX } else { sample_data.len() as u32 };
+    body.extend_from_slice(&data_size.to_le_bytes());
+    body.extend_from_slice(sample_data);
+
+    let mut file = if rf64 { b"RF64".to_vec() } else { b"RIFF".to_vec() };
+    let riff_size = if rf64 { u32::MAX } else { body.len() as u32 };
+    file.extend_from_slice(&riff_size.to_le_bytes());
+    file.extend(body);
+    file
+}
+
+fn to_i16(samples: &[f32]) -> Vec<i16>
+{
+    samples.iter().map(|&s| (s * 32767.0).round() as i16).collect()
+}
+
+fn assert_close(loaded: &[f32], expected: &[f32], tolerance: f32)
+{
+    assert_eq!(loaded.len(), expected.len());
+    for (i, (a, b)) in loaded.iter().zip(expected.iter()).enumerate()
+    {
+        assert!((a - b).abs() <= tolerance, "Sample {} differs: {} vs {}", i, a, b);
+    }
+}
+
+#[test]
+fn test_aiff_variants_load()
+{
+    let samples = generate_sine_wave(440.0, 44100, 2, 0.25);
+    let frames = (samples.len() / 2) as u32;
+    let ints = to_i16(&samples);
+
+    let be16: Vec<u8> = ints.iter().flat_map(|s| s.to_be_bytes()).collect();
+    let le16: Vec<u8> = ints.iter().flat_map(|s| s.to_le_bytes()).collect();
+    let be24: Vec<u8> = samples.iter()
+                               .flat_map(|&s| ((s * 8388607.0).round() as i32).to_be_bytes()[1..].to_vec())
+                               .collect();
+    let fl32: Vec<u8> = samples.iter().flat_map(|s| s.to_be_bytes()).collect();
+
+    let cases: Vec<(&str, Vec<u8>, f32)> = vec![
+        ("aiff", build_aiff(None, 2, 16, frames, &be16), 2.0 / 32767.0),
+        ("aiff", build_aiff(None, 2, 24, frames, &be24), 2.0 / 8388607.0),
+        ("aifc", build_aiff(Some(b"sowt"), 2, 16, frames, &le16), 2.0 / 32767.0),
+        ("aifc", build_aiff(Some(b"fl32"), 2, 32, frames, &fl32), 0.0),
+    ];
+
+    for (i, (ext, bytes, tolerance)) in cases.into_iter().enumerate()
+    {
+        let path = PathBuf::from(format!("/tmp/test_pcm_{}.{}", i, ext));
+        std::fs::write(&path, &bytes).expect("Writing file failed");
+
+        let (loaded, sample_rate, channels) = load_audio_file_lossless(&path).expect("Loading AIFF failed");
+        assert_eq!(sample_rate, 44100);
+        assert_eq!(channels, 2);
+        assert_close(&loaded, &samples, tolerance);
+
+        let reader = LosslessReader::open(&path).expect("Opening AIFF failed");
+        assert_eq!(reader.tags().get("title"), Some("Sine"));
+
+        std::fs::remove_file(&path).ok();
+    }
+}
+
+#[test]
+fn test_caf_loads()
+{
+    let samples = generate_sine_wave(330.0, 44100, 1, 0.25);
+    let le16: Vec<u8> = to_i16(&samples).iter().flat_map(|s| s.to_le_bytes()).collect();
+    let lef32: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+
+    for (i, (bytes, tolerance)) in [(build_caf(false, 1, 16, &le16), 2.0 / 32767.0), (build_caf(true, 1, 32, &lef32), 0.0)]
+        .into_iter()
+        .enumerate()
+    {
+        let path = PathBuf::from(format!("/tmp/test_pcm_caf_{}.caf", i));
+        std::fs::write(&path, &bytes).expect("Writing file failed");
+
+        let (loaded, sample_rate, channels) = load_audio_file_lossless(&path).expect("Loading CAF failed");
+        assert_eq!(sample_rate, 44100);
+        assert_eq!(channels, 1);
+        assert_close(&loaded, &samples, tolerance);
+
+        let reader = LosslessReader::open(&path).expect("Opening CAF failed");
+        assert_eq!(reader.tags().get("artist"), Some("Someone"));
+
+        std::fs::remove_file(&path).ok();
+    }
+}
+
+#[test]
+fn test_extensible_and_rf64_wav_keep_channel_mask()
+{
+    // 5.1: FL FR FC LFE BL BR
+    let channel_mask = 0x3F;
+    let samples = generate_sine_wave(440.0, 44100, 6, 0.3);
+    let le16: Vec<u8> = to_i16(&samples).iter().flat_map(|s| s.to_le_bytes()).collect();
+
+    for rf64 in [false, true]
+    {
+        let path = PathBuf::from(format!("/tmp/test_pcm_extensible_{}.wav", rf64));
+        std::fs::write(&path, build_extensible_wav(rf64, 6, channel_mask, &le16)).expect("Writing file failed");
+
+        let (loaded, sample_rate, channels) = load_audio_file_lossless(&path).expect("Loading WAV failed");
+        assert_eq!(sample_rate, 44100);
+        assert_eq!(channels, 6);
+        assert_close(&loaded, &samples, 2.0 / 32767.0);
+
+        let reader = LosslessReader::open(&path).expect("Opening WAV failed");
+        assert_eq!(reader.channel_mask(), channel_mask);
+        assert_eq!(reader.tags().get("title"), Some("Surround"));
+
+        let bytes = encode_lossless(reader, Vec::new()).expect("Encoding failed");
+        let glc = GlcDecoder::new(Cursor::new(bytes)).expect("Opening GLC failed");
+        assert_eq!(glc.channel_mask(), channel_mask);
+        assert_eq!(glc.header().channels, 6);
+
+        std::fs::remove_file(&path).ok();
+    }
+}
+
+#[test]
+fn test_ffmpeg_fallback()
+{
+    use gapless_lossy_codec::audio::export_to_wav;
+
+    // Writing a WAV under an unrecognised extension gives input only ffmpeg will read
+    let samples = generate_sine_wave(440.0, 44100, 2, 0.5);
+    let path = PathBuf::from("/tmp/test_pcm_ffmpeg.audio");
+    export_to_wav(&path, &samples, 44100, 2).expect("Writing file failed");
+
+    assert!(LosslessReader::open(&path).is_err());
+    assert!(LosslessReader::open_with(&path, false).is_err());
+
+    if std::process::Command::new("ffmpeg").arg("-version").output().is_err()
+    {
+        eprintln!("ffmpeg not found; skipping the decode check");
+        std::fs::remove_file(&path).ok();
+        return;
+    }
+
+    let mut reader = LosslessReader::open_with(&path, true).expect("Opening through ffmpeg failed");
+    assert_eq!(reader.sample_rate(), 44100);
+    assert_eq!(reader.channels(), 2);
+
+    let mut loaded = Vec::new();
+    while reader.read_block(&mut loaded).expect("Reading through ffmpeg failed") {}
+    assert_close(&loaded, &samples, 2.0 / 32767.0);
+
+    std::fs::remove_file(&path).ok();
+}
@@ -18,6 +18,23 @@ fn test_benchmark_perceptual_weights_creation()
     println!("Per encoder: {:.4}ms", elapsed.as_secs_f64() * 1000.0 / 1000.0);
 }
 
+#[test]
+fn test_benchmark_decoder_creation()
+{
+    // MDCT tables are cached after the first construction, so this should be nearly free
+    let _warm_up = Decoder::new(2, 44100);
+
+    let start = Instant::now();
+    for _ in 0..1000
+    {
+        let _decoder = Decoder::new(2, 44100);
+    }
+    let elapsed = start.elapsed();
+    println!("Creating 1000 decoders (shared MDCT tables): {:.2}ms",
+             elapsed.as_secs_f64() * 1000.0);
+    assert!(elapsed.as_secs_f64() < 1.0, "Decoder construction should reuse cached MDCT tables");
+}
+
 #[test]
 fn benchmark_single_frame_encoding()
 {
@@ -87,21 +104,21 @@ fn benchmark_complex_waveform_encoding()
              sine_time.as_secs_f64() * 1000.0,
              encoded_sine.frames.len(),
              encoded_sine.frames.iter()
-                         .map(|f| f.sparse_coeffs_per_channel[0].len())
+                         .flat_map(|f| f.sparse_coeffs_per_channel.iter().map(Vec::len))
                          .sum::<usize>());
 
     println!("  Square wave:   {:.2}ms ({} frames, {} total coeffs)",
              square_time.as_secs_f64() * 1000.0,
              encoded_square.frames.len(),
              encoded_square.frames.iter()
-                           .map(|f| f.sparse_coeffs_per_channel[0].len())
+                           .flat_map(|f| f.sparse_coeffs_per_channel.iter().map(Vec::len))
                            .sum::<usize>());
 
     println!("  Sawtooth wave: {:.2}ms ({} frames, {} total coeffs)",
              saw_time.as_secs_f64() * 1000.0,
              encoded_saw.frames.len(),
              encoded_saw.frames.iter()
-                        .map(|f| f.sparse_coeffs_per_channel[0].len())
+                        .flat_map(|f| f.sparse_coeffs_per_channel.iter().map(Vec::len))
                         .sum::<usize>());
 }
 
@@ -180,7 +197,7 @@ fn profile_encoding_stages()
     // Count coefficient statistics
     let total_possible_coeffs = encoded.frames.len() * 1024; // HOP_SIZE
     let total_kept_coeffs: usize = encoded.frames.iter()
-                                          .map(|f| f.sparse_coeffs_per_channel[0].len())
+                                          .flat_map(|f| f.sparse_coeffs_per_channel.iter().map(Vec::len))
                                           .sum();
 
     println!("  Sparsity: {:.2}% coefficients kept",
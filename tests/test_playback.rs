@@ -0,0 +1,248 @@
+// Tests for the playback engine behind the player, driven without an audio device
+#![cfg(feature = "playback")]
+use gapless_lossy_codec::capture::CaptureFormat;
+use gapless_lossy_codec::equalizer::EqBand;
+use gapless_lossy_codec::playback::{OutputLevels, PlaybackControl, PlaybackEvent, PlaylistSource, SamplesSource, Track,
+                                    RECENT_OUTPUT_FRAMES};
+use gapless_lossy_codec::playlist::Repeat;
+
+/// `frames` frames of `channels` channels, each frame holding its own index plus `base`
+fn ramp(frames: usize, channels: u16, base: f32) -> Vec<f32>
+{
+    (0..frames).flat_map(|i| vec![base + i as f32; channels as usize]).collect()
+}
+
+fn track(samples: Vec<f32>, sample_rate: u32, channels: u16) -> Track
+{
+    Track::new(SamplesSource::new(samples, sample_rate, channels))
+}
+
+/// A control that leaves samples as they are, so output can be compared exactly
+fn unprocessed() -> PlaybackControl
+{
+    let control = PlaybackControl::default();
+    control.mixer().set_limiter(false);
+    control
+}
+
+#[test]
+fn test_playlist_is_gapless()
+{
+    let playlist = PlaylistSource::with_control(vec![track(ramp(10000, 2, 0.0), 44100, 2),
+                                                     track(ramp(5000, 2, 1e5), 44100, 2)],
+                                                unprocessed());
+    let mut expected = ramp(10000, 2, 0.0);
+    expected.extend(ramp(5000, 2, 1e5));
+    assert!(playlist.collect::<Vec<f32>>() == expected, "tracks don't join sample for sample");
+}
+
+#[test]
+fn test_playlist_converts_formats()
+{
+    // A mono track in a stereo playlist is played on both channels
+    let playlist = PlaylistSource::with_control(vec![track(vec![0.25; 3000], 44100, 1),
+                                                     track(vec![0.5; 2 * 2000], 44100, 2)],
+                                                unprocessed());
+    assert_eq!(playlist.converted_tracks(), 1);
+    let output: Vec<f32> = playlist.collect();
+    assert_eq!(output.len(), 2 * 5000);
+    assert!(output[..2 * 3000].iter().all(|&sample| sample == 0.25));
+}
+
+#[test]
+fn test_playlist_crossfade()
+{
+    let control = unprocessed();
+    control.set_crossfade(0.1);
+    let playlist = PlaylistSource::with_control(vec![track(vec![1.0; 2 * 44100], 44100, 2),
+                                                     track(vec![0.5; 2 * 44100], 44100, 2)],
+                                                control);
+    // The tracks overlap by 0.1 s
+    assert_eq!(playlist.count(), 2 * (2 * 44100 - 4410));
+}
+
+#[test]
+fn test_playlist_repeat_track()
+{
+    let control = unprocessed();
+    control.set_repeat(Repeat::Track);
+    let mut playlist = PlaylistSource::with_control(vec![track(ramp(3000, 2, 0.0), 44100, 2),
+                                                         track(ramp(100, 2, 1e5), 44100, 2)],
+                                                    control.clone());
+    let looped: Vec<f32> = playlist.by_ref().take(2 * 6000).collect();
+    let mut expected = ramp(3000, 2, 0.0);
+    expected.extend(ramp(3000, 2, 0.0));
+    assert!(looped == expected, "the repeated track doesn't loop gaplessly");
+
+    // Skipping still moves on, and the last track then repeats in turn
+    control.next_track();
+    let next: Vec<f32> = playlist.by_ref().take(2 * 200).collect();
+    let mut expected = ramp(100, 2, 1e5);
+    expected.extend(ramp(100, 2, 1e5));
+    assert!(next == expected, "skipping while repeating a track");
+}
+
+#[test]
+fn test_playlist_queue_while_playing()
+{
+    let control = unprocessed();
+    let events = control.events();
+    control.queue(track(ramp(2000, 2, 0.0), 44100, 2));
+    let mut playlist = PlaylistSource::with_control(Vec::new(), control.clone());
+
+    let mut output: Vec<f32> = playlist.by_ref().take(2 * 1000).collect();
+    control.queue(track(ramp(500, 2, 1e5), 44100, 2));
+    output.extend(playlist.by_ref());
+
+    // The queued track follows straight on
+    let mut expected = ramp(2000, 2, 0.0);
+    expected.extend(ramp(500, 2, 1e5));
+    assert!(output == expected, "the queued track doesn't join gaplessly");
+    assert_eq!(events.try_iter().collect::<Vec<_>>(),
+               [PlaybackEvent::TrackChanged(0), PlaybackEvent::TrackChanged(1), PlaybackEvent::Finished]);
+    assert!((control.position().total_duration - 2500.0 / 44100.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_playlist_seek()
+{
+    let control = unprocessed();
+    let events = control.events();
+    let mut playlist = PlaylistSource::with_control(vec![track(ramp(44100, 1, 0.0), 44100, 1),
+                                                         track(ramp(44100, 1, 1e5), 44100, 1)],
+                                                    control.clone());
+    playlist.next();
+
+    // A relative seek lands 0.5 s on from the sample just played
+    control.seek_by(0.5);
+    assert_eq!(playlist.next(), Some(22051.0));
+    assert!((control.position().elapsed - 0.5).abs() < 0.01);
+
+    // Seeking past the end of a track starts the next one
+    control.seek_by(1.0);
+    assert_eq!(playlist.next(), Some(1e5));
+    assert_eq!(control.position().track, 1);
+
+    // Going back within the first seconds of a track returns to the previous one
+    control.previous_track();
+    assert_eq!(playlist.next(), Some(0.0));
+
+    // A point on the playlist's timeline picks the track and the time within it
+    control.seek_track(1, 0.5);
+    assert_eq!(playlist.next(), Some(1e5 + 22050.0));
+    assert!((control.position().total_elapsed - 1.5).abs() < 0.01);
+    assert_eq!(events.try_iter().collect::<Vec<_>>(),
+               [PlaybackEvent::TrackChanged(0), PlaybackEvent::TrackChanged(1), PlaybackEvent::TrackChanged(0),
+                PlaybackEvent::TrackChanged(1)]);
+}
+
+#[test]
+fn test_playlist_play_track_and_peaks()
+{
+    let control = unprocessed();
+    let mut playlist = PlaylistSource::with_control(vec![track([0.25, -0.5].repeat(1000), 44100, 2),
+                                                         track(ramp(1000, 2, 1e5), 44100, 2),
+                                                         track([-0.75, 0.125].repeat(1000), 44100, 2)],
+                                                    control.clone());
+    assert!(control.take_peaks().is_empty());
+    // Peaks gather over several position updates until taken
+    playlist.by_ref().take(2 * 512).for_each(drop);
+    assert_eq!(control.take_peaks(), [0.25, 0.5]);
+
+    control.play_track(2);
+    assert_eq!(playlist.next(), Some(-0.75));
+    assert_eq!(control.position().track, 2);
+    playlist.by_ref().take(2 * 511 - 1).for_each(drop);
+    assert_eq!(control.take_peaks(), [0.75, 0.125]);
+}
+
+#[test]
+fn test_playlist_levels_and_recent_output()
+{
+    let control = unprocessed();
+    let square: Vec<f32> = (0..4000).flat_map(|i| if i % 2 == 0 { [0.5, 0.25] } else { [-0.5, 0.25] }).collect();
+    let mut playlist = PlaylistSource::with_control(vec![track(square, 44100, 2)], control.clone());
+    assert_eq!(control.take_levels(), OutputLevels::default());
+    assert!(control.recent_output().is_empty());
+
+    playlist.by_ref().take(2 * 512).for_each(drop);
+    assert_eq!(control.take_levels(), OutputLevels { peaks: vec![0.5, 0.25], rms: vec![0.5, 0.25] });
+    // Frames are mixed to mono as they play
+    let recent = control.recent_output();
+    assert_eq!(recent.len(), 512);
+    assert_eq!(recent[..2], [0.375, -0.125]);
+
+    // Only the most recent output is kept
+    playlist.by_ref().take(2 * 3000).for_each(drop);
+    assert_eq!(control.recent_output().len(), RECENT_OUTPUT_FRAMES);
+    assert!(control.take_peaks() == [0.5, 0.25] && control.take_levels().rms.is_empty());
+}
+
+#[test]
+fn test_playlist_stats()
+{
+    let control = unprocessed();
+    let mut playlist = PlaylistSource::with_control(vec![track(vec![0.5; 2 * 44100], 44100, 2)], control.clone());
+
+    playlist.next();
+    let stats = control.stats();
+    assert_eq!(stats.blocks, 1);
+    assert!(stats.speed > 1.0, "decoding from memory is slower than playback: {:?}", stats);
+    assert!(stats.buffered > 0.0);
+
+    // The whole second of audio comes in 4096-frame blocks, then nothing is left buffered
+    playlist.by_ref().for_each(drop);
+    let stats = control.stats();
+    assert_eq!(stats.blocks, 11);
+    assert!(stats.slow_blocks <= stats.blocks);
+    assert_eq!(stats.buffered, 0.0);
+
+    // A new playlist on the same control starts counting again
+    PlaylistSource::with_control(Vec::new(), control.clone());
+    assert_eq!(control.stats(), Default::default());
+}
+
+#[test]
+fn test_playlist_tempo_and_eq()
+{
+    // Half speed plays twice as long, with positions still in the track's own time
+    let control = unprocessed();
+    control.set_tempo(0.5);
+    let mut playlist = PlaylistSource::with_control(vec![track(vec![0.5; 2 * 44100], 44100, 2)], control.clone());
+    let first: Vec<f32> = playlist.by_ref().take(2 * 44100).collect();
+    let elapsed = control.position().elapsed;
+    assert!((elapsed - 0.5).abs() < 0.05, "{} s into the track after 1 s of output", elapsed);
+    let total = first.len() + playlist.count();
+    assert!((total as f64 - 4.0 * 44100.0).abs() < 0.03 * 4.0 * 44100.0, "{} samples at half speed", total);
+
+    // A bass cut takes DC away entirely once the filter settles
+    let control = unprocessed();
+    control.equalizer().set_bands(vec![EqBand::bass(-12.0)]);
+    let output: Vec<f32> = PlaylistSource::with_control(vec![track(vec![0.5; 2 * 44100], 44100, 2)], control).collect();
+    assert!((output[output.len() - 1] - 0.5 * 10f32.powf(-12.0 / 20.0)).abs() < 1e-3, "{}", output[output.len() - 1]);
+}
+
+#[test]
+fn test_playlist_capture()
+{
+    let path = std::path::PathBuf::from("/tmp/test_playlist_capture.wav");
+    let control = PlaybackControl::default();
+    control.mixer().set_volume(0.5);
+    control.start_capture(&path).unwrap();
+    assert!(control.start_capture(&path).is_err(), "two captures at once");
+
+    let playlist = PlaylistSource::with_control(vec![track(ramp(10000, 2, 0.0), 44100, 2),
+                                                     track(vec![0.5; 2 * 5000], 44100, 2)],
+                                                control.clone());
+    let played: Vec<f32> = playlist.collect();
+    let summary = control.stop_capture().unwrap().unwrap();
+    assert_eq!((summary.frames, summary.sample_rate, summary.channels), (15000, 44100, 2));
+    assert!(!control.is_capturing());
+    assert!(control.stop_capture().unwrap().is_none());
+
+    // The file holds exactly what was played, volume and limiter included
+    let captured: Vec<f32> = hound::WavReader::open(&path).unwrap().samples::<f32>().map(Result::unwrap).collect();
+    assert!(captured == played, "the capture differs from the output");
+    assert_eq!(played[2 * 10000], 0.25);
+    assert!(CaptureFormat::from_path(std::path::Path::new("out.mp3")).is_err());
+}
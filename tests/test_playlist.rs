@@ -0,0 +1,181 @@
+// Tests for the playlist order under the repeat and shuffle modes
+use gapless_lossy_codec::playlist::{read_m3u, write_m3u, PlayOrder, Repeat};
+use std::path::PathBuf;
+
+/// Follow `order` from its start through `count` track ends
+fn play(order: &mut PlayOrder, count: usize) -> Vec<Option<usize>>
+{
+    let mut played = vec![order.start()];
+    for _ in 1..count
+    {
+        let next = order.peek_next();
+        assert_eq!(order.advance(), next, "peek_next disagrees with advance");
+        played.push(next);
+    }
+    played
+}
+
+/// Advance `count` times from the current track without restarting
+fn play_on(order: &mut PlayOrder, count: usize) -> Vec<Option<usize>>
+{
+    (0..count).map(|_| order.advance()).collect()
+}
+
+fn is_permutation(tracks: &[Option<usize>], len: usize) -> bool
+{
+    let mut sorted: Vec<usize> = tracks.iter().map(|track| track.unwrap()).collect();
+    sorted.sort_unstable();
+    sorted == (0..len).collect::<Vec<_>>()
+}
+
+#[test]
+fn test_play_order_repeat()
+{
+    let mut order = PlayOrder::with_seed(3, 1);
+    assert_eq!(play(&mut order, 5), [Some(0), Some(1), Some(2), None, None]);
+    assert_eq!(order.played(), [0, 1, 2]);
+
+    let mut order = PlayOrder::with_seed(3, 1);
+    order.set_repeat(Repeat::Track);
+    assert_eq!(play(&mut order, 3), [Some(0), Some(0), Some(0)]);
+    // Skipping still moves on while repeating a track
+    assert_eq!(order.skip(1), Some(1));
+    assert_eq!(order.advance(), Some(1));
+
+    let mut order = PlayOrder::with_seed(3, 1);
+    order.set_repeat(Repeat::Playlist);
+    assert_eq!(play(&mut order, 7), [Some(0), Some(1), Some(2), Some(0), Some(1), Some(2), Some(0)]);
+    assert_eq!(order.played(), [] as [usize; 0]);
+
+    // Going back from the first track stays there, or wraps around when repeating the playlist
+    assert_eq!(order.skip(-1), Some(2));
+    order.set_repeat(Repeat::Off);
+    assert_eq!(order.skip(-5), Some(0));
+    assert_eq!(order.skip(5), None);
+}
+
+#[test]
+fn test_play_order_shuffle()
+{
+    let mut order = PlayOrder::with_seed(8, 42);
+    order.set_shuffle(true);
+    order.set_repeat(Repeat::Playlist);
+    let played = play(&mut order, 8 * 20);
+
+    // Every pass plays each track once, passes differ, and no track plays twice in a row
+    let passes: Vec<&[Option<usize>]> = played.chunks(8).collect();
+    assert!(passes.iter().all(|pass| is_permutation(pass, 8)));
+    assert!(passes.windows(2).any(|pair| pair[0] != pair[1]));
+    assert!(played.windows(2).all(|pair| pair[0] != pair[1]));
+    assert_ne!(passes[0], (0..8).map(Some).collect::<Vec<_>>().as_slice());
+
+    // The same seed gives the same order
+    let mut again = PlayOrder::with_seed(8, 42);
+    again.set_shuffle(true);
+    again.set_repeat(Repeat::Playlist);
+    assert_eq!(play(&mut again, 8 * 20), played);
+}
+
+#[test]
+fn test_play_order_toggle_shuffle()
+{
+    let mut order = PlayOrder::with_seed(6, 7);
+    order.start();
+    order.skip(2);
+
+    // The current track keeps playing, followed by every other track
+    order.set_shuffle(true);
+    assert_eq!(order.current(), Some(2));
+    let rest = play_on(&mut order, 5);
+    let mut pass = vec![Some(2)];
+    pass.extend(rest);
+    assert!(is_permutation(&pass, 6));
+    assert_eq!(order.advance(), None);
+
+    // Turning it off again carries on in the original order
+    let mut order = PlayOrder::with_seed(6, 7);
+    order.set_shuffle(true);
+    order.start();
+    let current = order.skip(1).unwrap();
+    order.set_shuffle(false);
+    assert_eq!(order.current(), Some(current));
+    assert_eq!(order.played(), (0..current).collect::<Vec<_>>());
+    assert_eq!(order.advance(), if current < 5 { Some(current + 1) } else { None });
+}
+
+#[test]
+fn test_play_order_empty()
+{
+    let mut order = PlayOrder::with_seed(0, 3);
+    order.set_shuffle(true);
+    order.set_repeat(Repeat::Playlist);
+    assert!(order.is_empty());
+    assert_eq!(order.start(), None);
+    assert_eq!(order.peek_next(), None);
+    assert_eq!(order.advance(), None);
+    assert_eq!(order.skip(-1), None);
+}
+
+#[test]
+fn test_play_order_extend()
+{
+    let mut order = PlayOrder::with_seed(2, 5);
+    order.start();
+    order.extend(2);
+    assert_eq!(play_on(&mut order, 4), [Some(1), Some(2), Some(3), None]);
+
+    // Tracks added to an ended playlist follow on from where it ended
+    order.extend(1);
+    assert_eq!(order.current(), Some(4));
+
+    // When shuffling, added tracks land among those still to come
+    let mut order = PlayOrder::with_seed(4, 9);
+    order.set_shuffle(true);
+    let first = order.start();
+    order.extend(3);
+    let mut pass = vec![first];
+    pass.extend(play_on(&mut order, 6));
+    assert!(is_permutation(&pass, 7));
+    assert_eq!(order.advance(), None);
+
+    // Starting over keeps the modes
+    order.set_repeat(Repeat::Track);
+    order.reset(3);
+    assert_eq!(order.len(), 3);
+    assert!(order.shuffle());
+    assert_eq!(order.repeat(), Repeat::Track);
+}
+
+#[test]
+fn test_play_order_jump_to()
+{
+    let mut order = PlayOrder::with_seed(6, 9);
+    order.set_shuffle(true);
+    order.start();
+    // Picking a track plays it, then carries on through the shuffled order from there
+    assert_eq!(order.jump_to(4), Some(4));
+    let after = order.peek_next();
+    assert_ne!(after, Some(4));
+    assert!(order.played().len() < 6);
+
+    assert_eq!(order.jump_to(6), None);
+    assert_eq!(order.current(), Some(4));
+}
+
+#[test]
+fn test_m3u_round_trip()
+{
+    let path = PathBuf::from("/tmp/test_playlist/list.m3u");
+    std::fs::create_dir_all("/tmp/test_playlist/album").unwrap();
+    let files = vec![PathBuf::from("/tmp/test_playlist/album/01.glc"), PathBuf::from("/music/02 side b.glc")];
+    write_m3u(&path, &files).expect("Writing the playlist failed");
+
+    // Files beside the playlist are stored relative to it
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(text, "#EXTM3U\nalbum/01.glc\n/music/02 side b.glc\n");
+    assert_eq!(read_m3u(&path).expect("Reading the playlist failed"), files);
+
+    // Comments, extended info, blank lines, and CRLF line ends from other players
+    std::fs::write(&path, "\u{feff}#EXTM3U\r\n#EXTINF:123,Artist - Title\r\nalbum/01.glc\r\n\r\n/music/02 side b.glc\r\n").unwrap();
+    assert_eq!(read_m3u(&path).unwrap(), files);
+}
@@ -0,0 +1,30 @@
+// Property-based round-trip tests over generated signals (see `test_utils::check_round_trip`)
+use gapless_lossy_codec::test_utils::proptest::prelude::*;
+use gapless_lossy_codec::test_utils::strategies::{quality, signal};
+use gapless_lossy_codec::test_utils::{check_round_trip, DEFAULT_MIN_SNR_DB};
+
+mod utils;
+use utils::generate_sine_wave;
+
+proptest!
+{
+    #![proptest_config(ProptestConfig::with_cases(24))]
+
+    #[test]
+    fn round_trip_meets_acceptance_criteria(signal in signal(0.5), quality in quality())
+    {
+        let result = check_round_trip(&signal.samples, signal.sample_rate, signal.channels, quality, DEFAULT_MIN_SNR_DB);
+        prop_assert!(result.is_ok(), "{:?} at {} Hz, {} channels, quality {}", result.err(), signal.sample_rate,
+                     signal.channels, quality);
+    }
+}
+
+#[test]
+fn test_round_trip_criteria_reject_shortfalls()
+{
+    let samples = generate_sine_wave(440.0, 44100, 2, 0.5);
+    let decoded = check_round_trip(&samples, 44100, 2, 0.7, DEFAULT_MIN_SNR_DB).expect("Round trip failed");
+    assert_eq!(decoded.len(), samples.len());
+    // No lossy codec reaches 200 dB
+    assert!(check_round_trip(&samples, 44100, 2, 0.7, 200.0).is_err());
+}
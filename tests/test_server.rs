@@ -0,0 +1,49 @@
+// Tests for the `glc serve` protocol
+#![cfg(feature = "playback")]
+use gapless_lossy_codec::server::{Request, Response, State, Status};
+use std::path::PathBuf;
+
+fn parse(line: &str) -> Request
+{
+    serde_json::from_str(line).unwrap()
+}
+
+#[test]
+fn test_server_parse_requests()
+{
+    assert_eq!(parse(r#"{"command": "enqueue", "path": "/music/01.glc"}"#),
+               Request::Enqueue { path: PathBuf::from("/music/01.glc") });
+    assert_eq!(parse(r#"{"command": "play"}"#), Request::Play);
+    assert_eq!(parse(r#"{"command": "seek", "seconds": 30}"#), Request::Seek { seconds: 30.0, relative: false });
+    assert_eq!(parse(r#"{"command": "seek", "seconds": -5, "relative": true}"#),
+               Request::Seek { seconds: -5.0, relative: true });
+    assert_eq!(parse(r#"{"command": "status"}"#), Request::Status);
+
+    assert!(serde_json::from_str::<Request>(r#"{"command": "rewind"}"#).is_err());
+    assert!(serde_json::from_str::<Request>(r#"{"command": "enqueue"}"#).is_err());
+}
+
+#[test]
+fn test_server_responses()
+{
+    let status = Status
+    {
+        state: State::Playing,
+        track: Some(1),
+        elapsed: 2.5,
+        duration: 180.0,
+        total_elapsed: 242.5,
+        total_duration: 420.0,
+        playlist: vec![PathBuf::from("a.glc"), PathBuf::from("b.glc")],
+        queued: Vec::new(),
+    };
+    let json: serde_json::Value = serde_json::to_value(Response::ok(status)).unwrap();
+    assert_eq!(json["ok"], true);
+    assert_eq!(json["status"]["state"], "playing");
+    assert_eq!(json["status"]["track"], 1);
+    assert_eq!(json["status"]["playlist"][1], "b.glc");
+    assert!(json.get("error").is_none());
+
+    let json = serde_json::to_value(Response::error("No such file".to_string())).unwrap();
+    assert_eq!(json, serde_json::json!({ "ok": false, "error": "No such file" }));
+}
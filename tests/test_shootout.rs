@@ -0,0 +1,75 @@
+// Tests for the encoder shootout harness
+use gapless_lossy_codec::shootout::{combine, Contender, DEFAULT_CONTENDERS};
+use std::path::PathBuf;
+
+mod utils;
+use utils::generate_sine_wave;
+
+#[test]
+fn test_parse_contenders()
+{
+    assert_eq!("glc:0.5".parse::<Contender>().unwrap(), Contender::Glc(0.5));
+    assert_eq!("Opus:96".parse::<Contender>().unwrap(), Contender::Opus(96));
+    assert_eq!("vorbis:-1".parse::<Contender>().unwrap(), Contender::Vorbis(-1.0));
+    for contender in DEFAULT_CONTENDERS
+    {
+        assert_eq!(contender.to_string().parse::<Contender>().unwrap(), *contender);
+    }
+
+    assert!("glc".parse::<Contender>().is_err());
+    assert!("glc:2".parse::<Contender>().is_err());
+    assert!("opus:1000".parse::<Contender>().is_err());
+    assert!("mp3:128".parse::<Contender>().is_err());
+}
+
+#[test]
+fn test_glc_scores_and_corpus()
+{
+    let dir = PathBuf::from("/tmp/test_shootout_glc");
+    std::fs::create_dir_all(&dir).unwrap();
+    let samples = generate_sine_wave(440.0, 44100, 2, 1.0);
+
+    let low = Contender::Glc(0.1).run(&samples, 44100, 2, &dir).expect("Shootout failed");
+    let high = Contender::Glc(1.0).run(&samples, 44100, 2, &dir).expect("Shootout failed");
+    assert!(Contender::Glc(0.1).tools().is_empty() && Contender::Glc(0.1).is_available());
+    assert_eq!(high.contender, "glc:1");
+    assert_eq!(high.seconds, 1.0);
+    assert!((high.kbps - high.bytes as f64 * 8.0 / 1000.0).abs() < 1e-9);
+    assert!(low.bytes <= high.bytes, "{} vs {}", low.bytes, high.bytes);
+    assert!(high.snr_db > 10.0 && high.odg <= 0.0, "{:?}", high);
+
+    // A corpus of two tracks: sizes and lengths add up, measures average
+    let corpus = combine(&[low.clone(), high.clone()]).unwrap();
+    assert_eq!((corpus.bytes, corpus.seconds), (low.bytes + high.bytes, 2.0));
+    assert!((corpus.snr_db - (low.snr_db + high.snr_db) / 2.0).abs() < 1e-9);
+    assert_eq!(combine(&[]), None);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_reference_codec_tools()
+{
+    use std::os::unix::fs::PermissionsExt;
+
+    // Stand-ins for opusenc and opusdec that pass the audio through untouched
+    let dir = PathBuf::from("/tmp/test_shootout_tools");
+    std::fs::create_dir_all(&dir).unwrap();
+    for tool in ["opusenc", "opusdec"]
+    {
+        let path = dir.join(tool);
+        std::fs::write(&path, "#!/bin/sh\n[ \"$1\" = --version ] && exit 0\ncp \"$4\" \"$5\"\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    let path = format!("{}:{}", dir.display(), std::env::var("PATH").unwrap_or_default());
+    // Safety: no other test in this file reads or writes the environment
+    unsafe { std::env::set_var("PATH", path) };
+
+    let contender = Contender::Opus(96);
+    assert!(contender.is_available());
+    let samples = generate_sine_wave(440.0, 44100, 2, 1.0);
+    let score = contender.run(&samples, 44100, 2, &dir).expect("Shootout failed");
+    // The "encoded" file is the 16-bit WAV handed to the encoder
+    assert_eq!(score.bytes, 44 + 44100 * 2 * 2);
+    assert!(score.snr_db > 80.0, "{:?}", score);
+    assert!(!dir.join("opus-96.opus").exists(), "Working files should be removed");
+}
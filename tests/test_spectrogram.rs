@@ -0,0 +1,56 @@
+// Tests for the spectrograms the GUI compares originals and decoded files with
+use gapless_lossy_codec::spectrogram::{spectrum, Spectrogram, SPECTROGRAM_FFT_SIZE, SPECTROGRAM_FLOOR_DB};
+
+mod utils;
+use utils::generate_sine_wave;
+
+#[test]
+fn test_spectrogram_finds_a_tone()
+{
+    // One second of silence, then one of a 1 kHz tone
+    let mut samples = vec![0.0; 2 * 44100];
+    samples.extend(generate_sine_wave(1000.0, 44100, 2, 1.0));
+    let spectrogram = Spectrogram::new(&samples, 44100, 2, 10);
+    assert_eq!(spectrogram.columns.len(), 10);
+    assert_eq!(spectrogram.bins(), 1024);
+
+    assert!(spectrogram.columns[2].iter().all(|&level| level == SPECTROGRAM_FLOOR_DB));
+    let column = &spectrogram.columns[7];
+    let (peak, &level) = column.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1)).unwrap();
+    assert!((spectrogram.bin_frequency(peak) - 1000.0).abs() < 22.0, "peak at {} Hz", spectrogram.bin_frequency(peak));
+    let expected = 20.0 * samples.iter().fold(0.0f32, |peak, &s| peak.max(s.abs())).log10();
+    assert!((level - expected).abs() < 2.0, "{} dB for a {} dBFS tone", level, expected);
+    // Far from the tone the window's sidelobes are well down
+    assert!(column[400] < level - 80.0, "{} dB at {} Hz", column[400], spectrogram.bin_frequency(400));
+}
+
+#[test]
+fn test_spectrogram_difference()
+{
+    let samples = generate_sine_wave(440.0, 44100, 1, 0.5);
+    let quieter: Vec<f32> = samples.iter().map(|s| s * 0.5).collect();
+    let original = Spectrogram::new(&samples, 44100, 1, 4);
+    let difference = original.difference(&Spectrogram::new(&quieter, 44100, 1, 4));
+    let peak = (440.0 / original.bin_frequency(1)).round() as usize;
+    assert!(difference.iter().all(|column| (column[peak] - 6.02).abs() < 0.01));
+    assert!(original.difference(&original).iter().flatten().all(|&db| db == 0.0));
+}
+
+#[test]
+fn test_spectrum_of_recent_samples()
+{
+    // Only the last FFT frame counts, so the silence before the tone doesn't show
+    let mut samples = vec![0.0; 10000];
+    samples.extend(generate_sine_wave(2000.0, 44100, 1, 0.1));
+    let levels = spectrum(&samples);
+    assert_eq!(levels.len(), SPECTROGRAM_FFT_SIZE / 2);
+    let peak = (2000.0 * SPECTROGRAM_FFT_SIZE as f32 / 44100.0).round() as usize;
+    let (loudest, &level) = levels.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1)).unwrap();
+    assert!(loudest.abs_diff(peak) <= 1, "peak in bin {} rather than {}", loudest, peak);
+    // The tone peaks at half of full scale, about -6 dB, less whatever falls between bins
+    assert!(level > -10.0 && level < -5.5, "{} dB", level);
+
+    // Fewer samples than a frame are zero-padded, and silence sits at the floor
+    assert!(spectrum(&[0.0; 100]).iter().all(|&level| level == SPECTROGRAM_FLOOR_DB));
+    assert_eq!(spectrum(&[]).len(), SPECTROGRAM_FFT_SIZE / 2);
+}
@@ -0,0 +1,66 @@
+// Tests for the Symphonia format reader and decoder
+#![cfg(feature = "symphonia")]
+use gapless_lossy_codec::codec::{Encoder, Decoder, encoded_to_bytes};
+use gapless_lossy_codec::symphonia_glc::{register_codecs, register_formats};
+use std::io::Cursor;
+use symphonia::core::audio::{AudioBufferRef, Signal};
+use symphonia::core::codecs::{CodecRegistry, DecoderOptions};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::{Hint, Probe};
+
+mod utils;
+use utils::generate_sine_wave;
+
+#[test]
+fn test_symphonia_decode_matches_native_decode()
+{
+    let samples = generate_sine_wave(440.0, 44100, 1, 2.0);
+    let mut encoder = Encoder::new(44100);
+    let encoded = encoder.encode(&samples, 1).expect("Encoding failed");
+    let bytes = encoded_to_bytes(&encoded).expect("Serialization failed");
+
+    let mut decoder = Decoder::new(1, 44100);
+    let expected = decoder.decode(&encoded, None).expect("Decoding failed");
+
+    let mut codecs = CodecRegistry::new();
+    register_codecs(&mut codecs);
+    let mut probe = Probe::default();
+    register_formats(&mut probe);
+
+    let source = MediaSourceStream::new(Box::new(Cursor::new(bytes)), Default::default());
+    let format_options = FormatOptions { enable_gapless: true, ..Default::default() };
+    let probed = probe.format(&Hint::new(), source, &format_options, &MetadataOptions::default())
+                      .expect("Probing failed");
+    let mut format = probed.format;
+
+    let track = format.default_track().expect("No track").clone();
+    assert_eq!(track.codec_params.sample_rate, Some(44100));
+    assert_eq!(track.codec_params.n_frames, Some(samples.len() as u64));
+
+    let mut symphonia_decoder = codecs.make(&track.codec_params, &DecoderOptions::default())
+                                      .expect("Creating decoder failed");
+    let mut decoded = Vec::new();
+    loop
+    {
+        let packet = match format.next_packet()
+        {
+            Ok(packet) => packet,
+            Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => panic!("Reading packet failed: {e}"),
+        };
+        match symphonia_decoder.decode(&packet).expect("Decoding packet failed")
+        {
+            AudioBufferRef::F32(buf) => decoded.extend_from_slice(buf.chan(0)),
+            _ => panic!("Unexpected sample format"),
+        }
+    }
+
+    assert_eq!(decoded.len(), expected.len(), "Gapless length mismatch");
+    for (i, (a, b)) in decoded.iter().zip(expected.iter()).enumerate()
+    {
+        assert!((a - b).abs() < 1e-6, "Sample {} differs: {} vs {}", i, a, b);
+    }
+}
@@ -0,0 +1,86 @@
+// Tests for changing playback tempo without changing pitch
+use gapless_lossy_codec::tempo::{TimeStretch, MAX_TEMPO, MIN_TEMPO};
+
+mod utils;
+use utils::{generate_sine_wave, generate_white_noise};
+
+/// Stretch `samples` in blocks of `block` frames, flushing at the end
+fn stretch(samples: &[f32], channels: u16, tempo: f64, block: usize) -> Vec<f32>
+{
+    let mut stretcher = TimeStretch::new(44100, channels);
+    let mut output = Vec::new();
+    for chunk in samples.chunks(block * channels as usize)
+    {
+        stretcher.process(chunk, tempo, &mut output);
+    }
+    stretcher.finish(&mut output);
+    output
+}
+
+/// Upward zero crossings of the left channel per second
+fn crossings_per_second(samples: &[f32], channels: usize) -> f64
+{
+    let left: Vec<f32> = samples.iter().step_by(channels).copied().collect();
+    let crossings = left.windows(2).filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0).count();
+    crossings as f64 * 44100.0 / left.len() as f64
+}
+
+#[test]
+fn test_tempo_one_passes_through()
+{
+    let samples = generate_white_noise(44100, 2, 0.5, 3);
+    assert_eq!(stretch(&samples, 2, 1.0, 1000), samples);
+}
+
+#[test]
+fn test_tempo_changes_length_not_pitch()
+{
+    let samples = generate_sine_wave(440.0, 44100, 2, 2.0);
+    for tempo in [MIN_TEMPO, 0.8, 1.25, MAX_TEMPO]
+    {
+        let output = stretch(&samples, 2, tempo, 1024);
+        assert_eq!(output.len() % 2, 0);
+        let expected = samples.len() as f64 / tempo;
+        assert!((output.len() as f64 - expected).abs() < 0.03 * expected,
+                "{}x: {} samples, expected about {}", tempo, output.len(), expected);
+
+        // Still a 440 Hz tone at the same level, without the seams showing as dips or bumps
+        let body = &output[8820..output.len() - 8820];
+        assert!((crossings_per_second(body, 2) - 440.0).abs() < 5.0, "{}x: {} Hz", tempo, crossings_per_second(body, 2));
+        let peak = body.iter().fold(0.0f32, |peak, &s| peak.max(s.abs()));
+        let source_peak = samples.iter().fold(0.0f32, |peak, &s| peak.max(s.abs()));
+        assert!((peak - source_peak).abs() < 0.05 * source_peak, "{}x: peak {} vs {}", tempo, peak, source_peak);
+    }
+}
+
+#[test]
+fn test_tempo_independent_of_block_size()
+{
+    let samples = generate_white_noise(44100, 2, 1.0, 11);
+    let whole = stretch(&samples, 2, 1.5, samples.len());
+    assert_eq!(stretch(&samples, 2, 1.5, 256), whole);
+    assert_eq!(stretch(&samples, 2, 1.5, 4099), whole);
+}
+
+#[test]
+fn test_tempo_changes_mid_stream()
+{
+    // Once stretching, returning to 1 keeps going through the stretcher (one frame behind)
+    // rather than jumping back, and a reset goes back to passing audio straight through
+    let samples = generate_sine_wave(220.0, 44100, 1, 1.0);
+    let mut stretcher = TimeStretch::new(44100, 1);
+    let mut output = Vec::new();
+    stretcher.process(&samples[..22050], 2.0, &mut output);
+    assert!(stretcher.is_active());
+    let before = output.len();
+    stretcher.process(&samples[22050..], 1.0, &mut output);
+    assert!(stretcher.is_active());
+    let added = output.len() - before;
+    assert!(added > 21000 && added <= 22050 + 2000, "{} samples at 1x", added);
+
+    stretcher.reset();
+    output.clear();
+    stretcher.process(&samples, 1.0, &mut output);
+    assert!(!stretcher.is_active());
+    assert_eq!(output, samples);
+}
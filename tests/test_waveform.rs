@@ -0,0 +1,46 @@
+// Tests for the waveform overviews drawn by the GUI
+use gapless_lossy_codec::waveform::Waveform;
+
+mod utils;
+
+#[test]
+fn test_waveform_from_samples()
+{
+    // A stereo ramp from -1 to 1 over 1000 frames, with the right channel at half level
+    let samples: Vec<f32> = (0..1000).flat_map(|i|
+    {
+        let left = i as f32 / 500.0 - 1.0;
+        [left, left / 2.0]
+    }).collect();
+    let waveform = Waveform::from_samples(samples, 1000, 1000, 2, 4);
+    assert_eq!(waveform.duration, 1.0);
+    assert_eq!(waveform.columns.len(), 4);
+    let (min, max) = waveform.columns[0];
+    assert!(min == -1.0 && (max - -0.251).abs() < 1e-6, "{:?}", waveform.columns[0]);
+    assert!((waveform.columns[3].1 - 0.998).abs() < 1e-6);
+
+    // Columns no frame falls in are left flat
+    let short = Waveform::from_samples([0.5, -0.5], 1, 44100, 2, 3);
+    assert_eq!(short.columns, [(-0.5, 0.5), (0.0, 0.0), (0.0, 0.0)]);
+    assert!(Waveform::from_samples(Vec::new(), 0, 44100, 2, 8).columns.iter().all(|&column| column == (0.0, 0.0)));
+}
+
+#[cfg(feature = "playback")]
+#[test]
+fn test_waveform_from_glc()
+{
+    use gapless_lossy_codec::codec::{save_encoded, Encoder};
+    use utils::generate_sine_wave;
+
+    // Half a second of silence, then half a second of a tone
+    let mut samples = vec![0.0; 2 * 22050];
+    samples.extend(generate_sine_wave(440.0, 44100, 2, 0.5));
+    let encoded = Encoder::new(44100).encode(&samples, 2).expect("Encoding failed");
+    let path = std::path::PathBuf::from("/tmp/test_waveform_from_glc.glc");
+    save_encoded(&encoded, &path).expect("Saving failed");
+
+    let waveform = Waveform::from_glc(&path, 100).expect("Reading the overview failed");
+    assert!((waveform.duration - 1.0).abs() < 1e-9, "{} s", waveform.duration);
+    assert!(waveform.columns[..45].iter().all(|&(min, max)| min.abs() < 0.01 && max.abs() < 0.01));
+    assert!(waveform.columns[55..].iter().all(|&(min, max)| min < -0.4 && max > 0.4));
+}